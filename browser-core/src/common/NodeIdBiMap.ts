@@ -59,11 +59,19 @@ export class NodeIdBiMap {
     }
   }
 
-  public assignNodeIdsToSubTree(node: Node) {
+  /**
+   * Assign an id to this node alone, leaving its children without one
+   */
+  public assignNodeId(node: Node): number {
     const id = ++this.maxNodeId;
     this.idToNodeMap.set(id, node);
 
     NodeIdBiMap.setNodeId(node, id);
+    return id;
+  }
+
+  public assignNodeIdsToSubTree(node: Node) {
+    this.assignNodeId(node);
 
     for (const child of node.childNodes) {
       this.assignNodeIdsToSubTree(child);
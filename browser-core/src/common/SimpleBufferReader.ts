@@ -3,6 +3,8 @@
 // BufferReader interface (matches proto-ts/src/frames.ts)
 interface BufferReader {
   readU32(): number;
+  readI32(): number;
+  readF32(): number;
   readU64(): bigint;
   readString(): string;
   readBytes(length: number): Uint8Array;
@@ -36,6 +38,19 @@ export class SimpleBufferReader implements BufferReader {
     return value >>> 0; // Convert to unsigned
   }
 
+  readI32(): number {
+    return this.readU32() | 0; // Reinterpret as signed
+  }
+
+  readF32(): number {
+    if (this.offset + 4 > this.buffer.length) {
+      throw new Error("Buffer underflow reading f32");
+    }
+    const view = new DataView(this.buffer.buffer, this.buffer.byteOffset + this.offset, 4);
+    this.offset += 4;
+    return view.getFloat32(0, false);
+  }
+
   readU64(): bigint {
     if (this.offset + 8 > this.buffer.length) {
       throw new Error("Buffer underflow reading u64");
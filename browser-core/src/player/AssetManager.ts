@@ -1,6 +1,10 @@
 import type { Asset, AssetReference } from "@domcorder/proto-ts";
 import { ASSET_CONTAINING_ATTRIBUTES } from '../common';
 
+// Fetch error the server sets on an asset missing from its cache when it
+// sends the asset's original URL instead
+const ORIGINAL_URL_FALLBACK = 'domcorder:original-url';
+
 export type AssetLoadedHandler = (asset: AssetEntry) => void;
 
 interface AssetEntry {
@@ -207,6 +211,11 @@ export class AssetManager {
     let blob: Blob | undefined;
     let objectUrl: string | undefined;
     
+    // The server no longer has the asset and sent the URL it was recorded from
+    if (asset.fetch_error.type === 'unknown' && asset.fetch_error.message === ORIGINAL_URL_FALLBACK) {
+      console.warn(`⚠️ Asset ${asset.asset_id}: missing from the cache, loading it from ${asset.url}`);
+    }
+
     // If buffer is empty, use the URL directly (expecting HTTP URL)
     if (asset.buf.byteLength === 0) {
       // Validate that the URL is a valid HTTP/HTTPS URL
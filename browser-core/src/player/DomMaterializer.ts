@@ -51,6 +51,22 @@ export class DomMaterializer {
     this.clear();
   }
 
+  /**
+   * Clears the document for a chunked keyframe; its nodes follow through
+   * materializeNode() and its adopted stylesheets through
+   * materializeAdoptedStyleSheets()
+   * @param documentId The id of the recorded document
+   */
+  public beginDocument(documentId: number): void {
+    this.clearDocumentChildren();
+    NodeIdBiMap.setNodeId(this.document, documentId);
+  }
+
+  public materializeAdoptedStyleSheets(adoptedStyleSheets: VStyleSheet[]): void {
+    this.applyAdoptedStylesheets(adoptedStyleSheets);
+    this.clear();
+  }
+
   public materializeNode(vNode: VNode): Node {
     const node = this.createNode(vNode);
     if (!node) {
@@ -3,6 +3,7 @@ import type { DomOperation } from '../common/DomOperation';
 import type { StringMutationOperation } from '../common/StringMutationOperation';
 import { applyChanges } from '../recorder/StringChangeDetector';
 import type { AssetManager } from './AssetManager';
+import type { CanvasPatch } from '@domcorder/proto-ts';
 
 /**
  * DomMutator - A specification-aligned implementation of DOM mutation.
@@ -379,5 +380,33 @@ export class DomMutator {
     ctx.clearRect(0, 0, canvas.width, canvas.height);
     ctx.drawImage(bitmap, 0, 0);
   }
-}
 
+  /**
+   * Draws the redrawn regions of a canvas over its current image.
+   * Each patch replaces the pixels under it, transparent ones included.
+   */
+  public async patchCanvas(nodeId: number, patches: CanvasPatch[]): Promise<void> {
+    const node = this.nodeMap.getNodeById(nodeId);
+    if (!node || node.nodeType !== Node.ELEMENT_NODE) {
+      return;
+    }
+
+    const canvas = node as HTMLCanvasElement;
+    if (canvas.tagName !== 'CANVAS') {
+      return;
+    }
+
+    const ctx = canvas.getContext("2d");
+    if (!ctx) {
+      throw new Error("2D context not available");
+    }
+
+    const bitmaps = await Promise.all(
+      patches.map((patch) => createImageBitmap(new Blob([patch.data], { type: patch.mimeType })))
+    );
+    patches.forEach((patch, i) => {
+      ctx.clearRect(patch.x, patch.y, patch.width, patch.height);
+      ctx.drawImage(bitmaps[i], patch.x, patch.y);
+    });
+  }
+}
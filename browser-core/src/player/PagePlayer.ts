@@ -15,13 +15,21 @@ import {
   DomTextChanged,
   DomNodeResized,
   Keyframe,
+  KeyframeBegin,
+  KeyframeChunk,
+  KeyframeEnd,
   AdoptedStyleSheetsChanged,
   NewAdoptedStyleSheet,
+  NewAdoptedStyleSheetReference,
   ScrollOffsetChanged,
+  ScrollOffsetChangedV2,
   MouseMoved,
+  MouseMovedV2,
   MouseClicked,
+  MouseClickedV2,
   TextSelectionChanged,
   ElementScrolled,
+  ElementScrolledV2,
   Timestamp,
   ViewportResized,
   KeyPressed,
@@ -32,9 +40,12 @@ import {
   DomNodePropertyChanged,
   DomNodePropertyTextChanged,
   CanvasChanged,
+  CanvasChangedDelta,
   StyleSheetRuleInserted,
   StyleSheetRuleDeleted,
-  StyleSheetReplaced
+  StyleSheetReplaced,
+  StyleSheetReplacedReference,
+  VStyleSheet
 } from "@domcorder/proto-ts";
 import type { StringMutationOperation } from "../common/StringMutationOperation";
 import { StyleSheetWatcher, type StyleSheetWatcherEvent } from "../recorder/StyleSheetWatcher";
@@ -58,6 +69,11 @@ export class PagePlayer {
   private selectionSimulator: SelectionSimulator | null;
 
   private mutator: DomMutator | null;
+
+  // The node map of the last keyframe, and the adopted stylesheets of a
+  // chunked keyframe until its KeyframeEnd
+  private keyframeNodeIdMap: NodeIdBiMap | null = null;
+  private pendingAdoptedStyleSheets: VStyleSheet[] = [];
   private readonly styleSheetWatcher: StyleSheetWatcher;
   private readonly adoptedStyleSheetMutator: AdoptedStyleSheetsMutator;
   private readonly styleSheetMutator: StyleSheetMutator;
@@ -70,6 +86,8 @@ export class PagePlayer {
 
   private readonly playbackQueue: PlaybackQueue;
   private urlResolver: UrlResolver | null = null;
+  // Set for recordings of one element rather than the whole page
+  private rootSelector: string | null = null;
 
   // Live catch-up mode state
   private isLiveMode: boolean = false;
@@ -131,6 +149,14 @@ export class PagePlayer {
     });
   }
 
+  /**
+   * The selector of the element a scoped recording captures (its keyframes
+   * hold only that element), or null for a recording of the whole page
+   */
+  public getRootSelector(): string | null {
+    return this.rootSelector;
+  }
+
   public queueFrame(frame: Frame) {
     this.playbackQueue.enqueueFrame(frame);
   }
@@ -151,6 +177,12 @@ export class PagePlayer {
         // No action needed
       } else if (frame instanceof Keyframe) {
         await this._handleKeyFrame(frame as Keyframe);
+      } else if (frame instanceof KeyframeBegin) {
+        this._handleKeyframeBeginFrame(frame as KeyframeBegin);
+      } else if (frame instanceof KeyframeChunk) {
+        this._handleKeyframeChunkFrame(frame as KeyframeChunk);
+      } else if (frame instanceof KeyframeEnd) {
+        this._handleKeyframeEndFrame();
       } else if (frame instanceof Asset) {
         await this._handleAssetFrame(frame as Asset);
       } else if (frame instanceof AssetReference) {
@@ -196,18 +228,22 @@ export class PagePlayer {
     }
     else if (frame instanceof NewAdoptedStyleSheet) {
       await this._handleAdoptedStyleSheetAddedFrame(frame);
-    } else if (frame instanceof ScrollOffsetChanged) {
+    } else if (frame instanceof NewAdoptedStyleSheetReference || frame instanceof StyleSheetReplacedReference) {
+      await this._handleStyleSheetReferenceFrame(frame);
+    } else if (frame instanceof ScrollOffsetChanged || frame instanceof ScrollOffsetChangedV2) {
       await this._handleWindowScrolledFrame(frame);
-    } else if (frame instanceof ElementScrolled) {
+    } else if (frame instanceof ElementScrolled || frame instanceof ElementScrolledV2) {
       await this._handleElementScrolledFrame(frame);
-    } else if (frame instanceof MouseMoved) {
+    } else if (frame instanceof MouseMoved || frame instanceof MouseMovedV2) {
       await this._handleMouseMovedFrame(frame);
-    } else if (frame instanceof MouseClicked) {
+    } else if (frame instanceof MouseClicked || frame instanceof MouseClickedV2) {
       await this._handleMouseClickedFrame(frame);
     } else if (frame instanceof TextSelectionChanged) {
       await this._handleTextSelectionChangedFrame(frame);
     } else if (frame instanceof CanvasChanged) {
       await this._handleCanvasChangedFrame(frame);
+    } else if (frame instanceof CanvasChangedDelta) {
+      await this._handleCanvasChangedDeltaFrame(frame);
     } else {
       console.warn('Unhandled frame type:', frame.constructor.name);
     }
@@ -218,14 +254,19 @@ export class PagePlayer {
   }
   
   private _handleCanvasChangedFrame(frame: CanvasChanged) {
-    this.mutator?.updateCanvas(frame.nodeId, frame.mimeType, frame.data);
+    // Awaited so deltas are drawn over the image they were diffed against
+    return this.mutator?.updateCanvas(frame.nodeId, frame.mimeType, frame.data);
   }
 
-  private _handleElementScrolledFrame(frame: ElementScrolled) {
+  private _handleCanvasChangedDeltaFrame(frame: CanvasChangedDelta) {
+    return this.mutator?.patchCanvas(frame.nodeId, frame.patches);
+  }
+
+  private _handleElementScrolledFrame(frame: ElementScrolled | ElementScrolledV2) {
     this.mutator!.updateElementScrollPosition(frame.node_id, frame.scrollXOffset, frame.scrollYOffset);
   }
 
-  private _handleWindowScrolledFrame(scrollFrame: ScrollOffsetChanged) {
+  private _handleWindowScrolledFrame(scrollFrame: ScrollOffsetChanged | ScrollOffsetChangedV2) {
     if (this.isLiveMode && this.latestTimestamp !== null && this.currentTimestamp < this.latestTimestamp) {
       // Track scroll but don't apply during catch-up
       this.catchUpState.lastScrollPosition = { 
@@ -258,6 +299,7 @@ export class PagePlayer {
 
     const targetDocNodeIdMap = new NodeIdBiMap();
     targetDocNodeIdMap.adoptNodesFromSubTree(this.targetDocument);
+    this.keyframeNodeIdMap = targetDocNodeIdMap;
 
     this.mutator = new DomMutator(targetDocNodeIdMap, this.assetManager);
 
@@ -265,6 +307,44 @@ export class PagePlayer {
     this.selectionSimulator = new SelectionSimulator(this.overlayElement, targetDocNodeIdMap, this.targetDocument);
   }
 
+  private _handleKeyframeBeginFrame(frame: KeyframeBegin) {
+    this.viewportWidth = frame.viewportWidth;
+    this.viewportHeight = frame.viewportHeight;
+    this._updateIframeSize();
+
+    this.materializer.beginDocument(frame.documentId);
+    this.pendingAdoptedStyleSheets = frame.adoptedStyleSheets;
+
+    this.keyframeNodeIdMap = new NodeIdBiMap();
+    this.keyframeNodeIdMap.adoptNodesFromSubTree(this.targetDocument);
+  }
+
+  private _handleKeyframeChunkFrame(frame: KeyframeChunk) {
+    const parent = this.keyframeNodeIdMap?.getNodeById(frame.parentNodeId);
+    if (!parent) {
+      console.error('Keyframe chunk parent not found with ID:', frame.parentNodeId);
+      return;
+    }
+
+    for (const vNode of frame.nodes) {
+      const node = this.materializer.materializeNode(vNode);
+      parent.appendChild(node);
+      this.keyframeNodeIdMap!.adoptNodesFromSubTree(node);
+    }
+  }
+
+  private _handleKeyframeEndFrame() {
+    if (!this.keyframeNodeIdMap) {
+      return;
+    }
+
+    this.materializer.materializeAdoptedStyleSheets(this.pendingAdoptedStyleSheets);
+    this.pendingAdoptedStyleSheets = [];
+
+    this.mutator = new DomMutator(this.keyframeNodeIdMap, this.assetManager);
+    this.selectionSimulator = new SelectionSimulator(this.overlayElement, this.keyframeNodeIdMap, this.targetDocument);
+  }
+
   private _handleNodeAddedFrame(domNodeAddedData: DomNodeAdded) {
     const materializedNode = this.materializer.materializeNode(domNodeAddedData.vNode);
 
@@ -377,6 +457,10 @@ export class PagePlayer {
     try {
       this.urlResolver = createUrlResolver(frame.storage_type, frame.config_json);
       console.debug(`📦 PlaybackConfig: storage_type=${frame.storage_type}`);
+      this.rootSelector = frame.root_selector;
+      if (this.rootSelector !== null) {
+        console.debug(`🔲 Scoped recording of ${this.rootSelector}`);
+      }
       
       // Initialize live catch-up mode if recording is live
       this.isLiveMode = frame.is_live;
@@ -432,7 +516,7 @@ export class PagePlayer {
     this.mouseSimulator.stop();
   }
 
-  private _handleMouseMovedFrame(mouseMovedData: MouseMoved): void {
+  private _handleMouseMovedFrame(mouseMovedData: MouseMoved | MouseMovedV2): void {
     if (this.isLiveMode && this.latestTimestamp !== null && this.currentTimestamp < this.latestTimestamp) {
       // Track position but don't update cursor during catch-up
       this.catchUpState.lastMousePosition = { x: mouseMovedData.x, y: mouseMovedData.y };
@@ -441,7 +525,7 @@ export class PagePlayer {
     }
   }
 
-  private _handleMouseClickedFrame(mouseClickedData: MouseClicked): void {
+  private _handleMouseClickedFrame(mouseClickedData: MouseClicked | MouseClickedV2): void {
     if (this.isLiveMode && this.latestTimestamp !== null && this.currentTimestamp < this.latestTimestamp) {
       // Suppress click animation and sound during catch-up
       // Just track the position
@@ -608,4 +692,34 @@ export class PagePlayer {
   private async _handleStyleSheetReplacedFrame(frame: StyleSheetReplaced): Promise<void> {
     await this.styleSheetMutator.replaceSheet(frame.styleSheetId, frame.content);
   }
+
+  /**
+   * Handle stylesheet text the server moved to the asset cache: fetch it and
+   * apply it like the frame it stands for
+   */
+  private async _handleStyleSheetReferenceFrame(
+    frame: NewAdoptedStyleSheetReference | StyleSheetReplacedReference
+  ): Promise<void> {
+    if (!this.urlResolver) {
+      throw new Error('URL resolver not initialized. PlaybackConfig frame must be received first.');
+    }
+
+    let text: string;
+    try {
+      const response = await fetch(this.urlResolver.resolveUrl(frame.hash));
+      if (!response.ok) {
+        throw new Error(`HTTP ${response.status}`);
+      }
+      text = await response.text();
+    } catch (error) {
+      console.error(`❌ Failed to fetch stylesheet text ${frame.hash}:`, error);
+      return;
+    }
+
+    if (frame instanceof NewAdoptedStyleSheetReference) {
+      this._handleAdoptedStyleSheetAddedFrame(new NewAdoptedStyleSheet(new VStyleSheet(frame.id, text, frame.media)));
+    } else {
+      await this._handleStyleSheetReplacedFrame(new StyleSheetReplaced(frame.styleSheetId, text));
+    }
+  }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-import { Frame, Timestamp } from "@domcorder/proto-ts";
+import { Frame, IdlePeriod, RecordingPaused, RecordingResumed, Timestamp } from "@domcorder/proto-ts";
 
 export type PlaybackTimeBucket  = {
   frames: Frame[];
@@ -19,6 +19,12 @@ export class PlaybackQueue {
   private readonly playbackHandler: (frame: Frame, timestamp: number) => Promise<void>;
   private readonly live: boolean;
 
+  // Paused spans (RecordingPaused to RecordingResumed) and idle periods are
+  // cut out of the playback timeline rather than played as blank time
+  private paused: boolean = false;
+  private lastRecordedTimestamp: number | null = null;
+  private skippedMs: number = 0;
+
   constructor(live: boolean, playbackHandler: (frame: Frame, timestamp: number) => Promise<void>) {
     this.lastPlayedTimestamp = 0;
     this.frameQueue = [];
@@ -31,11 +37,24 @@ export class PlaybackQueue {
   }
 
   public enqueueFrame(frame: Frame) {
+    if (frame instanceof RecordingPaused) {
+      this.paused = true;
+      return;
+    }
+    if (frame instanceof RecordingResumed) {
+      this.paused = false;
+      return;
+    }
+    if (frame instanceof IdlePeriod) {
+      this.skippedMs += Number(frame.durationMs);
+      return;
+    }
+
     if (this.live) {
       // Live mode: process ASAP, preserve timestamp context via buckets
       if (frame instanceof Timestamp) {
         // Update lastPlayedTimestamp and create new bucket
-        this.lastPlayedTimestamp = Number(frame.timestamp);
+        this.lastPlayedTimestamp = this.playbackTimestamp(frame);
         this.frameQueue.push({ frames: [], timestamp: this.lastPlayedTimestamp });
       } else {
         // Operation frame
@@ -67,7 +86,7 @@ export class PlaybackQueue {
       // Always queue frames to ensure sequential processing and avoid race conditions
       if (frame instanceof Timestamp) {
         // Create new bucket for this timestamp
-        this.lastPlayedTimestamp = Number(frame.timestamp);
+        this.lastPlayedTimestamp = this.playbackTimestamp(frame);
         this.frameQueue.push({ frames: [], timestamp: this.lastPlayedTimestamp });
         this.setNextEventTimeout();
       } else {
@@ -97,6 +116,18 @@ export class PlaybackQueue {
     }
   }
 
+  /**
+   * Map a recorded timestamp onto the playback timeline, dropping time spent paused.
+   */
+  private playbackTimestamp(frame: Timestamp): number {
+    const recorded = Number(frame.timestamp);
+    if (this.paused && this.lastRecordedTimestamp !== null) {
+      this.skippedMs += Math.max(recorded - this.lastRecordedTimestamp, 0);
+    }
+    this.lastRecordedTimestamp = recorded;
+    return recorded - this.skippedMs;
+  }
+
   /**
    * Process a single frame with the given timestamp.
    * Ensures sequential execution by chaining to pendingOperation.
@@ -1,3 +1,4 @@
+import type { CanvasPatch } from '@domcorder/proto-ts';
 import { NodeIdBiMap } from '../common';
 
 export type CanvasWriteKind = "2d" | "webgl" | "webgl2";
@@ -16,7 +17,13 @@ export type CanvasChangedEvent = {
   data: ArrayBuffer;
 };
 
-export type CanvasChangedCallback = (event: CanvasChangedEvent) => void;
+// Only the regions redrawn since the canvas' previous event
+export type CanvasDeltaEvent = {
+  nodeId: number;
+  patches: CanvasPatch[];
+};
+
+export type CanvasChangedCallback = (event: CanvasChangedEvent | CanvasDeltaEvent) => void;
 
 export type WatchAllOptions = {
   watch2D?: boolean;
@@ -27,8 +34,13 @@ export type WatchAllOptions = {
   watchIframes?: boolean;        // same-origin iframes (default: false)
   processIntervalMs?: number;    // interval to process dirty canvases (default: 100ms)
   mimeType?: string;             // mime type for canvas blob conversion (default: 'image/png')
+  deltas?: boolean;              // send only redrawn regions after the first snapshot (default: true, PNG only)
+  deltaTileSize?: number;        // size of the squares compared for changes, in pixels (default: 64)
+  deltaMaxDirtyRatio?: number;   // send a full snapshot when more of the canvas than this changed (default: 0.5)
 };
 
+type Rect = { x: number; y: number; width: number; height: number };
+
 export type Unwatch = () => void;
 
 
@@ -40,6 +52,8 @@ export class CanvasTracker {
   private observedRoots = new WeakSet<Node>();
 
   private dirtyCanvases: Set<HTMLCanvasElement> = new Set();
+  // Pixels of each canvas as of its last event, to diff against
+  private previousPixels = new WeakMap<HTMLCanvasElement, ImageData>();
   private processInterval: number | null = null;
   private nodeIdBiMap: NodeIdBiMap;
 
@@ -86,6 +100,9 @@ export class CanvasTracker {
       watchIframes: options.watchIframes ?? false,
       processIntervalMs: options.processIntervalMs ?? 1000,
       mimeType: options.mimeType ?? 'image/png',
+      deltas: options.deltas ?? true,
+      deltaTileSize: options.deltaTileSize ?? 64,
+      deltaMaxDirtyRatio: options.deltaMaxDirtyRatio ?? 0.5,
     };
   }
 
@@ -293,10 +310,14 @@ export class CanvasTracker {
           continue;
         }
         
+        if (this.options.deltas && this.options.mimeType === 'image/png') {
+          if (await this.emitDelta(canvas, nodeId)) {
+            continue;
+          }
+        }
+
         // Convert canvas to blob
-        const blob = await new Promise<Blob | null>((resolve) => {
-          canvas.toBlob(resolve, this.options.mimeType);
-        });
+        const blob = await this.toBlob(canvas, this.options.mimeType);
 
         if (blob) {
           const arrayBuffer = await blob.arrayBuffer();
@@ -315,6 +336,120 @@ export class CanvasTracker {
     }
   }
 
+  private toBlob(canvas: HTMLCanvasElement, mimeType: string): Promise<Blob | null> {
+    return new Promise<Blob | null>((resolve) => {
+      canvas.toBlob(resolve, mimeType);
+    });
+  }
+
+  // Copy a canvas' pixels; going through a 2D canvas works for WebGL too
+  private readPixels(canvas: HTMLCanvasElement): ImageData | null {
+    if (canvas.width === 0 || canvas.height === 0) return null;
+    const scratch = canvas.ownerDocument.createElement("canvas");
+    scratch.width = canvas.width;
+    scratch.height = canvas.height;
+    const ctx = scratch.getContext("2d", { willReadFrequently: true });
+    if (!ctx) return null;
+    ctx.drawImage(canvas, 0, 0);
+    return ctx.getImageData(0, 0, canvas.width, canvas.height);
+  }
+
+  // Rects covering the tiles whose pixels differ between two same-sized images
+  private diffTiles(previous: ImageData, current: ImageData): Rect[] {
+    const tile = this.options.deltaTileSize;
+    const { width, height } = current;
+    const before = new Uint32Array(previous.data.buffer);
+    const after = new Uint32Array(current.data.buffer);
+
+    // Dirty tiles joined into horizontal runs, row by row
+    const runs: Rect[][] = [];
+    for (let ty = 0; ty < height; ty += tile) {
+      const rowRuns: Rect[] = [];
+      const tileHeight = Math.min(tile, height - ty);
+      for (let tx = 0; tx < width; tx += tile) {
+        const tileWidth = Math.min(tile, width - tx);
+        if (!this.tileChanged(before, after, width, tx, ty, tileWidth, tileHeight)) continue;
+        const last = rowRuns[rowRuns.length - 1];
+        if (last && last.x + last.width === tx) {
+          last.width += tileWidth;
+        } else {
+          rowRuns.push({ x: tx, y: ty, width: tileWidth, height: tileHeight });
+        }
+      }
+      runs.push(rowRuns);
+    }
+
+    // Grow runs downwards while the next row has a run with the same span
+    const rects: Rect[] = [];
+    for (let row = 0; row < runs.length; row++) {
+      for (const run of runs[row]) {
+        for (let next = row + 1; next < runs.length; next++) {
+          const index = runs[next].findIndex((r) => r.x === run.x && r.width === run.width);
+          if (index === -1) break;
+          run.height += runs[next][index].height;
+          runs[next].splice(index, 1);
+        }
+        rects.push(run);
+      }
+    }
+    return rects;
+  }
+
+  private tileChanged(
+    before: Uint32Array,
+    after: Uint32Array,
+    stride: number,
+    x: number,
+    y: number,
+    width: number,
+    height: number
+  ): boolean {
+    for (let row = y; row < y + height; row++) {
+      const start = row * stride + x;
+      for (let i = start; i < start + width; i++) {
+        if (before[i] !== after[i]) return true;
+      }
+    }
+    return false;
+  }
+
+  // Emit the redrawn regions of a canvas; false means a full snapshot is
+  // needed instead (no previous pixels, a resize, or too much changed)
+  private async emitDelta(canvas: HTMLCanvasElement, nodeId: number): Promise<boolean> {
+    const current = this.readPixels(canvas);
+    if (!current) return false;
+    const previous = this.previousPixels.get(canvas);
+    this.previousPixels.set(canvas, current);
+    if (!previous || previous.width !== current.width || previous.height !== current.height) {
+      return false;
+    }
+
+    const rects = this.diffTiles(previous, current);
+    const dirtyArea = rects.reduce((sum, r) => sum + r.width * r.height, 0);
+    if (dirtyArea > current.width * current.height * this.options.deltaMaxDirtyRatio) {
+      return false;
+    }
+
+    const patches: CanvasPatch[] = [];
+    const scratch = canvas.ownerDocument.createElement("canvas");
+    const ctx = scratch.getContext("2d");
+    if (!ctx) return false;
+    for (const rect of rects) {
+      scratch.width = rect.width;
+      scratch.height = rect.height;
+      ctx.putImageData(current, -rect.x, -rect.y, rect.x, rect.y, rect.width, rect.height);
+      const blob = await this.toBlob(scratch, "image/png");
+      if (!blob) return false;
+      patches.push({ ...rect, mimeType: "image/png", data: await blob.arrayBuffer() });
+    }
+
+    this.dirtyCanvases.delete(canvas);
+    if (patches.length > 0) {
+      this.callback({ nodeId, patches });
+    }
+    return true;
+  }
+
   // Start watching canvases
   public watch(): void {
     // Start: main document, extra docs, optional iframes, provided shadow roots
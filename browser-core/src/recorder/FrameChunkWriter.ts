@@ -9,6 +9,13 @@ export type FrameChunkWriterHandler = {
 
 export type FrameChunkWriterOptions = {
   chunkSize?: number;
+  /**
+   * Pack chunks written in quick succession into one, up to this many bytes
+   * (0, the default, passes every chunk through as soon as it is written).
+   * A partial batch is passed on after `batchDelayMs`.
+   */
+  batchSize?: number;
+  batchDelayMs?: number;
   writerFactory?: () => [Writer, ReadableStream<Uint8Array>];
 }
 
@@ -17,6 +24,12 @@ export class FrameChunkWriter {
   private readonly handler: FrameChunkWriterHandler;
   private readonly writer: Writer;
   private readonly stream: ReadableStream<Uint8Array>;
+  private readonly batchSize: number;
+  private readonly batchDelayMs: number;
+
+  private batch: Uint8Array[] = [];
+  private batchBytes: number = 0;
+  private batchTimer: ReturnType<typeof setTimeout> | null = null;
 
   constructor(
     handler: FrameChunkWriterHandler,
@@ -29,6 +42,8 @@ export class FrameChunkWriter {
     this.writer = writer;
     this.stream = stream;
     this.handler = handler;
+    this.batchSize = options.batchSize ?? 0;
+    this.batchDelayMs = options.batchDelayMs ?? 0;
 
     void this.start();
   }
@@ -44,12 +59,13 @@ export class FrameChunkWriter {
         const { done, value } = await reader.read();
 
         if (done) {
+          this.flushBatch();
           this.handler.done();
           break;
         }
 
         if (value) {
-          this.handler.next(value);
+          this.enqueue(value);
         }
       }
 
@@ -57,4 +73,44 @@ export class FrameChunkWriter {
       this.handler.error(error as Error);
     }
   }
-}
\ No newline at end of file
+
+  private enqueue(chunk: Uint8Array): void {
+    if (this.batchSize <= 0) {
+      this.handler.next(chunk);
+      return;
+    }
+
+    this.batch.push(chunk);
+    this.batchBytes += chunk.byteLength;
+    if (this.batchBytes >= this.batchSize) {
+      this.flushBatch();
+    } else if (this.batchTimer === null) {
+      this.batchTimer = setTimeout(() => this.flushBatch(), this.batchDelayMs);
+    }
+  }
+
+  private flushBatch(): void {
+    if (this.batchTimer !== null) {
+      clearTimeout(this.batchTimer);
+      this.batchTimer = null;
+    }
+    if (this.batch.length === 0) {
+      return;
+    }
+
+    let chunk: Uint8Array;
+    if (this.batch.length === 1) {
+      chunk = this.batch[0];
+    } else {
+      chunk = new Uint8Array(this.batchBytes);
+      let offset = 0;
+      for (const part of this.batch) {
+        chunk.set(part, offset);
+        offset += part.byteLength;
+      }
+    }
+    this.batch = [];
+    this.batchBytes = 0;
+    this.handler.next(chunk);
+  }
+}
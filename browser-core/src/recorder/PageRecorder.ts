@@ -5,6 +5,7 @@ import { UserInteractionTracker, type UserInteractionEventHandler } from "./User
 import {
   Frame,
   Keyframe,
+  chunkKeyframe,
   Asset,
   DomNodeAdded,
   DomNodeRemoved,
@@ -13,12 +14,12 @@ import {
   DomTextChanged,
   NewAdoptedStyleSheet,
   AdoptedStyleSheetsChanged,
-  MouseMoved,
-  MouseClicked,
+  MouseMovedV2,
+  MouseClickedV2,
   KeyPressed,
   ViewportResized,
-  ScrollOffsetChanged,
-  ElementScrolled,
+  ScrollOffsetChangedV2,
+  ElementScrolledV2,
   ElementFocused,
   ElementBlurred,
   TextSelectionChanged,
@@ -28,24 +29,45 @@ import {
   type TextRemoveOperationData,
   type TextOperationData,
   Timestamp,
+  RecordingPaused,
+  RecordingResumed,
   DomNodePropertyChanged,
   DomNodePropertyTextChanged,
   CanvasChanged,
+  CanvasChangedDelta,
   StyleSheetRuleDeleted,
   StyleSheetRuleInserted,
-  StyleSheetReplaced
+  StyleSheetReplaced,
+  SessionIdentity,
+  SessionLink
 } from "@domcorder/proto-ts";
 import { NodeIdBiMap } from "../common";
+import { sha256 } from "../common/hash";
 import type { DomOperation } from "../common/DomOperation";
 import { getAdoptedStyleSheetId } from "../common/StyleSheetIdUtils";
 import { StyleSheetWatcher, type StyleSheetWatcherEvent } from "./StyleSheetWatcher";
 import { inlineAdoptedStyleSheet, type InlineAdoptedStyleSheetEvent } from "./inliner/inlineAdoptedStyleSheet";
 import { AssetTracker } from "./inliner/AssetTracker";
-import { CanvasChangedCallback, CanvasChangedEvent, CanvasTracker } from "./CanvasTracker";
+import { CanvasChangedCallback, CanvasChangedEvent, CanvasDeltaEvent, CanvasTracker } from "./CanvasTracker";
 import { FormFieldTracker } from "./FormFieldTracker";
 
 export type FrameHandler = (frame: Frame) => Promise<void>;
 
+export interface RecorderIdentity {
+  /** Stable id for the browser; a random one is kept for the recorder if omitted */
+  anonymousId?: string;
+  /** The application's id for the signed-in user */
+  userId?: string;
+  /** Hashed in the browser; only the hash is recorded */
+  email?: string;
+}
+
+/**
+ * Keyframes larger than this are streamed as KeyframeBegin, KeyframeChunk...
+ * and KeyframeEnd frames, so huge pages are not buffered whole downstream.
+ */
+const DEFAULT_KEYFRAME_CHUNK_BYTES = 1024 * 1024;
+
 export class PageRecorder {
   private sourceDocument: Document;
 
@@ -58,7 +80,11 @@ export class PageRecorder {
   private userInteractionTracker: UserInteractionTracker | null;
   private sourceDocNodeIdMap: NodeIdBiMap | null;
   private recordingEpoch: number;
+  private paused: boolean;
   private readonly assetTracker: AssetTracker;
+  private keyframeChunkBytes: number;
+  private anonymousId: string;
+  private rootSelector: string | null;
   
   constructor(sourceDocument: Document) {
     this.sourceDocument = sourceDocument;
@@ -71,7 +97,33 @@ export class PageRecorder {
     this.canvasTracker = null;
     this.formFieldTracker = null;
     this.recordingEpoch = Date.now();
+    this.paused = false;
     this.assetTracker = new AssetTracker();
+    this.keyframeChunkBytes = DEFAULT_KEYFRAME_CHUNK_BYTES;
+    this.anonymousId = crypto.randomUUID();
+    this.rootSelector = null;
+  }
+
+  /**
+   * Set the size above which keyframes are split into chunks (Infinity to
+   * always emit a single Keyframe frame).
+   */
+  public setKeyframeChunkBytes(bytes: number) {
+    this.keyframeChunkBytes = bytes;
+  }
+
+  /**
+   * Record only the element matching `selector` and its subtree (e.g. a
+   * widget embedded in someone else's page). Keyframes hold just that
+   * element, sized as the viewport, and the rest of the page is not recorded.
+   * Call before start().
+   */
+  public setRootSelector(selector: string) {
+    this.rootSelector = selector;
+  }
+
+  public getRootSelector(): string | null {
+    return this.rootSelector;
   }
 
   public addFrameHandler(handler: FrameHandler) {
@@ -102,8 +154,94 @@ export class PageRecorder {
 
   start() {
     this.recordingEpoch = Date.now();
+    this.capture();
+  }
+
+  /**
+   * Stop capturing (e.g. while a sensitive screen is shown). Players skip the
+   * time until resume() instead of showing it as a blank stretch.
+   */
+  public async pause(reason?: string) {
+    if (this.paused) {
+      return;
+    }
+    this.paused = true;
+    this.stop();
+    await this.emitFrame(new RecordingPaused(reason));
+  }
+
+  /**
+   * Resume capturing after pause(), starting from a fresh keyframe since the
+   * page may have changed in the meantime.
+   */
+  public async resume() {
+    if (!this.paused) {
+      return;
+    }
+    this.paused = false;
+    await this.emitFrame(new RecordingResumed());
+    this.capture();
+  }
+
+  /**
+   * Start again from a fresh keyframe, for when the server reports that the
+   * frames sent so far no longer describe the page. Does nothing while paused,
+   * since resume() starts from a keyframe anyway.
+   */
+  public recapture() {
+    if (this.paused) {
+      return;
+    }
+    this.stop();
+    this.capture();
+  }
+
+  /**
+   * Record who this session belongs to, so it can be found by user (e.g. call
+   * after sign-in). Can be called again as the user changes.
+   */
+  public async identify(identity: RecorderIdentity) {
+    if (identity.anonymousId !== undefined) {
+      this.anonymousId = identity.anonymousId;
+    }
+    const email = identity.email?.trim().toLowerCase();
+    const emailHash = email ? await sha256(new TextEncoder().encode(email).buffer as ArrayBuffer) : undefined;
+    await this.emitFrame(new SessionIdentity(this.anonymousId, identity.userId, emailHash));
+  }
+
+  /**
+   * Link this recording to the recordings of the same user session in other
+   * tabs and windows (e.g. pass the app's own session id in each of them),
+   * so they can be played back as one timeline.
+   */
+  public async linkSession(sessionId: string) {
+    await this.emitFrame(new SessionLink(sessionId));
+  }
+
+  /**
+   * The element a scoped recording captures, or null to capture the page
+   */
+  private scopeRoot(): Element | null {
+    if (this.rootSelector === null) {
+      return null;
+    }
+    const root = this.sourceDocument.querySelector(this.rootSelector);
+    if (!root) {
+      console.warn(`No element matches the root selector ${this.rootSelector}; recording the whole page`);
+    }
+    return root;
+  }
+
+  private capture() {
+    const scopeRoot = this.scopeRoot();
+    const root: Node = scopeRoot ?? this.sourceDocument;
+
     this.sourceDocNodeIdMap = new NodeIdBiMap();
-    this.sourceDocNodeIdMap.assignNodeIdsToSubTree(this.sourceDocument);
+    if (scopeRoot) {
+      // Nodes outside the scope get no ids, so nothing refers to them
+      this.sourceDocNodeIdMap.assignNodeId(this.sourceDocument);
+    }
+    this.sourceDocNodeIdMap.assignNodeIdsToSubTree(root);
 
     // Setup user interaction tracking
     this.userInteractionTracker = new UserInteractionTracker(
@@ -117,11 +255,12 @@ export class PageRecorder {
       this.sourceDocument,
       this.sourceDocNodeIdMap, 
       this.createKeyFrameHandler(),
-      this.assetTracker
+      this.assetTracker,
+      { root: scopeRoot ?? undefined }
     );
 
     this.changeDetector = new DomChangeDetector(
-      this.sourceDocument,
+      root,
       this.sourceDocNodeIdMap,
       async (operations) => {
         if (operations.length > 0) {
@@ -156,7 +295,7 @@ export class PageRecorder {
     // Mark all nodes from the keyframe as already emitted
     // (they exist in the initial DOM snapshot, so no DomNodeAdded frames will be emitted for them)
     const allInitialNodes: Node[] = [];
-    this.collectNodesFromDocumentRecursive(this.sourceDocument, allInitialNodes);
+    this.collectNodesFromDocumentRecursive(root, allInitialNodes);
     this.styleSheetWatcher.markSubtreeEmitted(allInitialNodes);
 
     this.canvasTracker = new CanvasTracker(this.createCanvasHandler(), this.sourceDocNodeIdMap, {
@@ -171,7 +310,7 @@ export class PageRecorder {
 
     // Setup form field tracking
     this.formFieldTracker = new FormFieldTracker(
-      this.sourceDocument,
+      root,
       this.sourceDocNodeIdMap,
       {
         onPropertyChanged: async (operations) => {
@@ -397,11 +536,11 @@ export class PageRecorder {
   private createUserInteractionHandler(): UserInteractionEventHandler {
     return {
       onMouseMove: (event) => {
-        const frame = new MouseMoved(event.x, event.y);
+        const frame = new MouseMovedV2(event.x, event.y);
         this.emitFrame(frame);
       },
       onMouseClick: (event) => {
-        const frame = new MouseClicked(event.x, event.y);
+        const frame = new MouseClickedV2(event.x, event.y);
         this.emitFrame(frame);
       },
       onKeyPress: (event) => {
@@ -413,11 +552,11 @@ export class PageRecorder {
         this.emitFrame(frame);
       },
       onScroll: (event) => {
-        const frame = new ScrollOffsetChanged(event.scrollX, event.scrollY);
+        const frame = new ScrollOffsetChangedV2(event.scrollX, event.scrollY);
         this.emitFrame(frame);
       },
       onElementScroll: (event) => {
-        const frame = new ElementScrolled(event.elementId, event.scrollLeft, event.scrollTop);
+        const frame = new ElementScrolledV2(event.elementId, event.scrollLeft, event.scrollTop);
         this.emitFrame(frame);
       },
       onElementFocus: (event) => {
@@ -447,7 +586,11 @@ export class PageRecorder {
     return {
       onKeyFrameStarted: async (ev: KeyFrameStartedEvent) => {
         const keyframe = new Keyframe(ev.document, ev.viewportWidth, ev.viewportHeight);
-        await this.emitFrame(keyframe);
+        const frames = chunkKeyframe(keyframe, this.keyframeChunkBytes);
+        for (let i = 0; i < frames.length; i++) {
+          // One timestamp for the whole keyframe
+          await this.emitFrame(frames[i], i === 0);
+        }
       },
       onAsset: async (asset: InlinerAsset) => {
         const fetchError = (asset as any).fetchError || { type: 'none' };
@@ -492,8 +635,10 @@ export class PageRecorder {
   }
 
   private createCanvasHandler(): CanvasChangedCallback {
-    return (event: CanvasChangedEvent) => {
-      const frame = new CanvasChanged(event.nodeId, event.mime, event.data);
+    return (event: CanvasChangedEvent | CanvasDeltaEvent) => {
+      const frame = 'patches' in event
+        ? new CanvasChangedDelta(event.nodeId, event.patches)
+        : new CanvasChanged(event.nodeId, event.mime, event.data);
       try {
       this.emitFrame(frame, true);
       } catch (error) {
@@ -1,4 +1,4 @@
-import { Frame, RecordingMetadata, AssetReference, Asset, CacheManifest as ProtoCacheManifest, Heartbeat } from "@domcorder/proto-ts";
+import { Frame, RecordingMetadata, AssetReference, Asset, CacheManifest as ProtoCacheManifest, CacheInventory, Heartbeat, HeartbeatPing, HeartbeatPong, RecordingScope, RequestKeyframe, StopRecording } from "@domcorder/proto-ts";
 import type { FrameHandler, PageRecorder } from "./PageRecorder";
 import { FrameChunkWriter } from "./FrameChunkWriter";
 import { sha256 } from "../common/hash";
@@ -6,9 +6,21 @@ import { SimpleBufferReader } from "../common/SimpleBufferReader";
 
 export type PageRecordingClientOptions = {
   chunkSize?: number;
+  /**
+   * Preferred WebSocket message size in bytes: frames written together are
+   * packed into one message (0 sends one message per chunk). Requested from
+   * the server with the `batch_bytes` handshake parameter; the server accepts
+   * up to 1 MiB.
+   */
+  batchSize?: number;
   webSocketFactory?: (serverUrl: string) => WebSocket;
 }
 
+const DEFAULT_BATCH_SIZE = 64 * 1024;
+
+// Most asset hashes kept between page loads and reported in the handshake
+const MAX_CACHE_INVENTORY = 1000;
+
 // Cache manifest entry from server
 interface ManifestEntry {
   url: string;
@@ -128,10 +140,46 @@ export class PageRecordingClient {
 
       }
     }, {
-      chunkSize: this.options.chunkSize ?? 512 * 1024
+      chunkSize: this.options.chunkSize ?? 512 * 1024,
+      batchSize: this.batchSize(),
     });
   }
 
+  private batchSize(): number {
+    return this.options.batchSize ?? DEFAULT_BATCH_SIZE;
+  }
+
+  /** localStorage key of the asset hashes kept for this server */
+  private inventoryKey(): string {
+    return `domcorder:cache-inventory:${this.serverUrl}`;
+  }
+
+  /** Asset hashes the server sent in earlier sessions' manifests */
+  private loadCacheInventory(): string[] {
+    try {
+      const stored = window.localStorage.getItem(this.inventoryKey());
+      return stored ? JSON.parse(stored) : [];
+    } catch {
+      // Storage may be unavailable (e.g. private browsing) or corrupt
+      return [];
+    }
+  }
+
+  private saveCacheInventory(): void {
+    try {
+      const hashes = [...this.cacheManifest.keys()].slice(-MAX_CACHE_INVENTORY);
+      window.localStorage.setItem(this.inventoryKey(), JSON.stringify(hashes));
+    } catch {
+      // Without storage the next session gets the full manifest
+    }
+  }
+
+  /** The server URL with the batch size request appended */
+  private handshakeUrl(): string {
+    const separator = this.serverUrl.includes('?') ? '&' : '?';
+    return `${this.serverUrl}${separator}batch_bytes=${this.batchSize()}`;
+  }
+
   private async sendFrameImmediately(frame: Frame): Promise<void> {
     if (!this.frameChunkWriter) {
       this.frameChunkWriter = this.createFrameChunkWriter();
@@ -205,18 +253,32 @@ export class PageRecordingClient {
 
   private connectToServer(): void {
     try {
+      const url = this.handshakeUrl();
       this.ws = this.options.webSocketFactory ?
-        this.options.webSocketFactory(this.serverUrl) :
-        new WebSocket(this.serverUrl);
+        this.options.webSocketFactory(url) :
+        new WebSocket(url);
 
       this.ws.onopen = async () => {
 
-        // Send RecordingMetadata frame with initial URL and heartbeat interval
+        // Report the assets kept from earlier sessions, so the manifest only
+        // lists the others, then send RecordingMetadata with initial URL and
+        // heartbeat interval
         if (!this.metadataSent) {
+          const inventory = this.loadCacheInventory();
+          for (const hash of inventory) {
+            this.cacheManifest.set(hash, '');
+          }
+          if (inventory.length > 0) {
+            await this.sendFrameImmediately(new CacheInventory(inventory));
+          }
           const initialUrl = this.recorder.getInitialUrl?.() || window.location.href;
           const heartbeatInterval = 30; // Default: 30 seconds (can be made configurable)
           const metadataFrame = new RecordingMetadata(initialUrl, heartbeatInterval);
           await this.sendFrameImmediately(metadataFrame);
+          const rootSelector = this.recorder.getRootSelector();
+          if (rootSelector !== null) {
+            await this.sendFrameImmediately(new RecordingScope(rootSelector));
+          }
           this.metadataSent = true;
           this.heartbeatIntervalSeconds = heartbeatInterval;
           this.resetHeartbeatTimer();
@@ -255,13 +317,25 @@ export class PageRecordingClient {
             const frame = Frame.decode(reader);
             
             if (frame instanceof ProtoCacheManifest) {
-              // Build hash-to-URL map
-              this.cacheManifest.clear();
+              // The manifest lists what we didn't report as cached
               for (const entry of frame.assets) {
                 this.cacheManifest.set(entry.sha256_hash, entry.url);
               }
+              this.saveCacheInventory();
               
               console.debug(`📦 Received cache manifest frame with ${frame.assets.length} entries`);
+            } else if (frame instanceof HeartbeatPing) {
+              // Answer with our clock so the server can estimate its skew. The
+              // pong is queued so it never lands inside a frame being written.
+              void this.frameHandler(new HeartbeatPong(frame.serverTimestamp, Date.now()));
+            } else if (frame instanceof StopRecording) {
+              // The server won't store this session (e.g. it was not sampled)
+              console.info(`⏹️ Recording stopped by the server: ${frame.reason}`);
+              this.stop();
+            } else if (frame instanceof RequestKeyframe) {
+              // The server lost track of the page; start over from a keyframe
+              console.info(`🔄 Keyframe requested by the server: ${frame.reason}`);
+              this.recorder.recapture();
             } else {
               console.debug('📦 Received binary frame (not manifest):', frame?.constructor.name || 'null');
             }
@@ -26,6 +26,7 @@ export interface KeyFrameGenerationOptions {
   inlineCrossOrigin?: boolean; // Allow CORS inlining (default false)
   quietWindowMs?: number;      // Wait for DOM quiescence before snapshot
   freezeAnimations?: boolean;  // Disable animations during snapshot
+  root?: Element;              // Snapshot only this subtree, sized as the viewport
 }
 
 export async function generateKeyFrame(
@@ -44,12 +45,13 @@ export async function generateKeyFrame(
     }
 
     // Phase 1: synchronous snapshot + assign ids + rewrite to asset:<id>
-    const snap = snapshotVDomStreaming(doc, nodeIdMap, assetTracker, antiAnimationStylesheet);
+    const snap = snapshotVDomStreaming(doc, nodeIdMap, assetTracker, antiAnimationStylesheet, opts.root);
     rewriteAllRefsToAssetIds(snap, doc.baseURI, assetTracker); // proactive rewrite
 
-    // Capture viewport dimensions
-    const viewportWidth = doc.defaultView?.innerWidth || 600;
-    const viewportHeight = doc.defaultView?.innerHeight || 500;
+    // Capture viewport dimensions; a scoped recording's viewport is its root
+    const rootRect = opts.root?.getBoundingClientRect();
+    const viewportWidth = (rootRect ? Math.ceil(rootRect.width) : doc.defaultView?.innerWidth) || 600;
+    const viewportHeight = (rootRect ? Math.ceil(rootRect.height) : doc.defaultView?.innerHeight) || 500;
 
     // Emit the structural snapshot right away
     handler.onKeyFrameStarted({
@@ -96,9 +98,10 @@ async function waitForQuietWindow(doc: Document, ms: number): Promise<void> {
   });
 }
 
-function snapshotVDomStreaming(doc: Document, nodeIdMap: NodeIdBiMap, assetTracker: AssetTracker, antiAnimationStylesheet: CSSStyleSheet | null = null): VDocument {
+function snapshotVDomStreaming(doc: Document, nodeIdMap: NodeIdBiMap, assetTracker: AssetTracker, antiAnimationStylesheet: CSSStyleSheet | null = null, root?: Element): VDocument {
   
-  const children = Array.from(doc.childNodes);
+  // A scoped snapshot holds its root as the document's only child
+  const children = root ? [root] : Array.from(doc.childNodes);
   const vChildren: VNode[] = [];
   for (const child of children) {
     vChildren.push(snapshotNode(child, assetTracker, nodeIdMap));
@@ -0,0 +1,104 @@
+//! Minimal command-line argument parsing
+//!
+//! Options are `--name value`, `--name=value`, or boolean `--flag`. Which
+//! options take a value is declared by each subcommand.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Parse arguments; names in `value_options` consume the following argument
+    pub fn parse(args: &[String], value_options: &[&str]) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg.clone());
+                continue;
+            };
+            match option.split_once('=') {
+                Some((name, value)) => {
+                    parsed.options.insert(name.to_string(), Some(value.to_string()));
+                }
+                None if value_options.contains(&option) => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| format!("--{} requires a value", option))?;
+                    parsed.options.insert(option.to_string(), Some(value.clone()));
+                }
+                None => {
+                    parsed.options.insert(option.to_string(), None);
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Whether a flag (or option) was given
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// The value of an option, if given
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|value| value.as_deref())
+    }
+
+    /// Parse the value of an option
+    pub fn parsed<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid value for --{}: {}", name, value))
+            })
+            .transpose()
+    }
+
+    /// A comma-separated list option
+    pub fn list(&self, name: &str) -> Vec<String> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The positional argument at `index`, or an error naming what is missing
+    pub fn required(&self, index: usize, what: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {}", what))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        let args: Vec<String> = ["file.dcrr", "--frames", "--type", "Keyframe, MouseClicked", "--from=3"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let parsed = Args::parse(&args, &["type", "from"]).unwrap();
+
+        assert_eq!(parsed.positional, vec!["file.dcrr"]);
+        assert!(parsed.flag("frames"));
+        assert_eq!(parsed.list("type"), vec!["Keyframe", "MouseClicked"]);
+        assert_eq!(parsed.parsed::<u64>("from").unwrap(), Some(3));
+        assert!(Args::parse(&["--type".to_string()], &["type"]).is_err());
+    }
+}
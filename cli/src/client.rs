@@ -0,0 +1,66 @@
+//! HTTP access to a DomCorder server
+
+use crate::args::Args;
+use reqwest::{RequestBuilder, Response};
+
+pub const SERVER_ENV: &str = "DOMCORDER_SERVER";
+pub const API_KEY_ENV: &str = "DOMCORDER_API_KEY";
+
+/// Options taking a value that every server command accepts
+pub const VALUE_OPTIONS: [&str; 2] = ["server", "api-key"];
+
+pub struct ServerClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl ServerClient {
+    /// Build a client from `--server`/`--api-key` or their environment variables
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let base_url = args
+            .value("server")
+            .map(str::to_string)
+            .or_else(|| std::env::var(SERVER_ENV).ok())
+            .ok_or_else(|| format!("missing --server (or set {})", SERVER_ENV))?;
+        let api_key = args
+            .value("api-key")
+            .map(str::to_string)
+            .or_else(|| std::env::var(API_KEY_ENV).ok());
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.get(self.url(path)))
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.http.post(self.url(path)))
+    }
+
+    /// Send a request, turning non-success statuses into errors with the response body
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, String> {
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(format!("server returned {}: {}", status, body.trim()))
+    }
+}
@@ -0,0 +1,117 @@
+//! `dcrr convert`: binary recordings to and from newline-delimited JSON
+
+use crate::args::Args;
+use crate::input::open_recording;
+use crate::output::create_recording;
+use domcorder_proto::{FileHeader, Frame};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub const USAGE: &str = "\
+dcrr convert <input> <output> [options]
+
+Convert between binary recordings and newline-delimited JSON (one frame per
+line, in the same format as the server's /recording/{id}.ndjson endpoint).
+The direction is chosen from the file extensions: .ndjson, .jsonl or .json
+are JSON; anything else is binary.
+
+Options:
+  --created-at MS   Header timestamp (Unix ms) when writing a .dcrr file;
+                    defaults to the current time
+  --compress        zstd-compress large frames when writing a .dcrr file;
+                    the browser player can't read such files
+  --checksums       End each frame with a CRC32 when writing a .dcrr file,
+                    so readers detect corrupted frames";
+
+fn is_json(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("ndjson" | "jsonl" | "json")
+    )
+}
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["created-at"])?;
+    let input = Path::new(args.required(0, "<input>")?);
+    let output = Path::new(args.required(1, "<output>")?);
+
+    let count = match (is_json(input), is_json(output)) {
+        (false, true) => to_json(input, output).await?,
+        (true, false) => {
+            let header = match args.parsed("created-at")? {
+                Some(created_at) => FileHeader::with_timestamp(created_at),
+                None => FileHeader::new(),
+            };
+            let header = header
+                .with_frame_compression(args.flag("compress"))
+                .with_frame_checksums(args.flag("checksums"));
+            from_json(input, output, &header)?
+        }
+        _ => return Err("one of <input> and <output> must be JSON (.ndjson, .jsonl or .json)".to_string()),
+    };
+
+    println!("Converted {} frames to {}", count, output.display());
+    Ok(())
+}
+
+async fn to_json(input: &Path, output: &Path) -> Result<u64, String> {
+    let (mut reader, _header) = open_recording(input)
+        .await
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    let file = File::create(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut count = 0u64;
+    loop {
+        let offset = reader.position();
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(e) => return Err(format!("frame #{} at byte offset {}: {}", count, offset, e)),
+        };
+        serde_json::to_writer(&mut writer, &frame).map_err(|e| e.to_string())?;
+        writer.write_all(b"\n").map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+fn from_json(input: &Path, output: &Path, header: &FileHeader) -> Result<u64, String> {
+    let file = File::open(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    let mut writer = create_recording(output, header).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    let mut count = 0u64;
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame: Frame = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", input.display(), line_number + 1, e))?;
+        writer.write_frame(&frame).map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip_is_lossless() {
+        let sample = Path::new("../.sample_data/proto/file-basic.dcrr");
+        let dir = tempfile::TempDir::new().unwrap();
+        let json = dir.path().join("sample.ndjson");
+        let binary = dir.path().join("sample.dcrr");
+
+        let count = to_json(sample, &json).await.unwrap();
+        let (_, header) = open_recording(sample).await.unwrap();
+        assert_eq!(from_json(&json, &binary, &header.unwrap()).unwrap(), count);
+
+        assert_eq!(std::fs::read(sample).unwrap(), std::fs::read(&binary).unwrap());
+    }
+}
@@ -0,0 +1,69 @@
+//! Human-readable frame summaries
+
+use domcorder_proto::Frame;
+
+/// A one-line description of a frame's most useful fields
+pub fn frame_detail(frame: &Frame) -> String {
+    match frame {
+        Frame::Timestamp(d) => format!("t={}", d.timestamp),
+        Frame::Keyframe(d) => format!("{}x{}", d.viewport_width, d.viewport_height),
+        Frame::KeyframeBegin(d) => format!("{}x{}", d.viewport_width, d.viewport_height),
+        Frame::KeyframeChunk(d) => format!("parent={} nodes={}", d.parent_node_id, d.nodes.len()),
+        Frame::ViewportResized(d) => format!("{}x{}", d.width, d.height),
+        Frame::ScrollOffsetChanged(d) => format!("({}, {})", d.scroll_x_offset, d.scroll_y_offset),
+        Frame::MouseMoved(d) => format!("({}, {})", d.x, d.y),
+        Frame::MouseClicked(d) => format!("({}, {})", d.x, d.y),
+        Frame::ScrollOffsetChangedV2(d) => format!("({}, {})", d.scroll_x_offset, d.scroll_y_offset),
+        Frame::MouseMovedV2(d) => format!("({}, {})", d.x, d.y),
+        Frame::MouseClickedV2(d) => format!("({}, {})", d.x, d.y),
+        Frame::KeyPressed(d) => d.code.clone(),
+        Frame::RecordingMetadata(d) => {
+            format!("url={} heartbeat={}s", d.initial_url, d.heartbeat_interval_seconds)
+        }
+        Frame::Asset(d) => format!("id={} url={} {} bytes", d.asset_id, d.url, d.buf.len()),
+        Frame::AssetReference(d) => format!("id={} url={}", d.asset_id, d.url),
+        Frame::NewAdoptedStyleSheetReference(d) => format!("sheet={} hash={}", d.id, d.hash),
+        Frame::StyleSheetReplacedReference(d) => format!("sheet={} hash={}", d.style_sheet_id, d.hash),
+        Frame::DomNodeAdded(d) => format!("parent={} idx={}", d.parent_node_id, d.index),
+        Frame::DomNodeRemoved(d) => format!("node={}", d.node_id),
+        Frame::DomAttributeChanged(d) => format!("node={} {}=...", d.node_id, d.attribute_name),
+        Frame::DomAttributeRemoved(d) => format!("node={} {}", d.node_id, d.attribute_name),
+        Frame::DomTextChanged(d) => format!("node={}", d.node_id),
+        Frame::ElementScrolled(d) => format!("node={} ({},{})", d.node_id, d.scroll_x_offset, d.scroll_y_offset),
+        Frame::ElementScrolledV2(d) => format!("node={} ({},{})", d.node_id, d.scroll_x_offset, d.scroll_y_offset),
+        Frame::CanvasChangedDelta(d) => format!("node={} patches={}", d.node_id, d.patches.len()),
+        Frame::HeartbeatPing(d) => format!("server={}", d.server_timestamp),
+        Frame::HeartbeatPong(d) => format!("server={} client={}", d.server_timestamp, d.client_timestamp),
+        Frame::SessionIdentity(d) => match &d.user_id {
+            Some(user_id) => format!("anonymous={} user={}", d.anonymous_id, user_id),
+            None => format!("anonymous={}", d.anonymous_id),
+        },
+        Frame::StopRecording(d) => d.reason.clone(),
+        Frame::IdlePeriod(d) => format!("{} ms", d.duration_ms),
+        Frame::RecordingTruncated(d) => format!("{} bytes discarded", d.discarded_bytes),
+        Frame::CacheInventory(d) => format!("{} cached assets", d.sha256_hashes.len()),
+        Frame::RequestKeyframe(d) => d.reason.clone(),
+        Frame::RecordingScope(d) => d.root_selector.clone(),
+        Frame::SessionLink(d) => d.session_id.clone(),
+        Frame::PlaybackConfig(d) => format!("storage={} live={}", d.storage_type, d.is_live),
+        Frame::PageError(d) => d.message.clone(),
+        Frame::Annotation(d) => d.name.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Format a byte count for display
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,36 @@
+//! Opening recordings for reading
+
+use domcorder_proto::{FileHeader, FrameReader};
+use std::io;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, BufReader};
+
+pub type RecordingReader = FrameReader<BufReader<File>>;
+
+/// Open a `.dcrr` file or a raw frame stream
+///
+/// The DCRR magic is detected automatically; the header is returned if present.
+pub async fn open_recording(path: &Path) -> io::Result<(RecordingReader, Option<FileHeader>)> {
+    let mut magic = [0u8; 4];
+    let has_header = {
+        let mut peek = File::open(path).await?;
+        peek.read_exact(&mut magic).await.is_ok() && &magic == b"DCRR"
+    };
+
+    let file = File::open(path).await?;
+    let mut reader = FrameReader::new(BufReader::new(file), has_header);
+    let header = if has_header {
+        Some(reader.read_header().await?)
+    } else {
+        None
+    };
+    Ok((reader, header))
+}
+
+/// Format a header's creation time for display
+pub fn format_created(header: &FileHeader) -> String {
+    chrono::DateTime::from_timestamp_millis(header.created_at as i64)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| format!("{}ms", header.created_at))
+}
@@ -0,0 +1,145 @@
+//! `dcrr inspect`: header, frame counts and sizes, and optional frame dump
+
+use crate::args::Args;
+use crate::format::{format_bytes, frame_detail};
+use crate::input::{format_created, open_recording};
+use domcorder_proto::Frame;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+dcrr inspect <file> [options]
+
+Print the file header, frame counts and sizes by type.
+
+Options:
+  --frames          Dump every frame (index, byte offset, size, type, detail)
+  --json            Dump frames as JSON lines instead of text (implies --frames)
+  --type A,B        Only dump frames of these types
+  --from N          Only dump frames with index >= N
+  --to N            Only dump frames with index <= N";
+
+struct FrameFilter {
+    types: Vec<String>,
+    from: u64,
+    to: u64,
+}
+
+impl FrameFilter {
+    fn matches(&self, index: u64, frame: &Frame) -> bool {
+        index >= self.from
+            && index <= self.to
+            && (self.types.is_empty() || self.types.iter().any(|t| t == frame.type_name()))
+    }
+}
+
+#[derive(Default)]
+struct TypeStats {
+    count: u64,
+    bytes: u64,
+}
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["type", "from", "to"])?;
+    let path = Path::new(args.required(0, "<file>")?);
+    let json = args.flag("json");
+    let dump = json || args.flag("frames");
+    let filter = FrameFilter {
+        types: args.list("type"),
+        from: args.parsed("from")?.unwrap_or(0),
+        to: args.parsed("to")?.unwrap_or(u64::MAX),
+    };
+
+    let file_size = std::fs::metadata(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .len();
+    let (mut reader, header) = open_recording(path)
+        .await
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if !json {
+        println!("File:    {} ({})", path.display(), format_bytes(file_size));
+        match &header {
+            Some(header) => println!("Format:  DCRR v{}, created {}", header.version, format_created(header)),
+            None => println!("Format:  raw frame stream (no DCRR header)"),
+        }
+        if dump {
+            println!();
+        }
+    }
+
+    let mut stats: HashMap<&'static str, TypeStats> = HashMap::new();
+    let mut frame_count = 0u64;
+    let mut first_timestamp = None;
+    let mut last_timestamp = None;
+    let mut read_error = None;
+
+    loop {
+        let offset = reader.position();
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(e) => {
+                read_error = Some(format!("frame #{} at byte offset {}: {}", frame_count, offset, e));
+                break;
+            }
+        };
+        let size = reader.position() - offset;
+        let index = frame_count;
+        frame_count += 1;
+
+        let entry = stats.entry(frame.type_name()).or_default();
+        entry.count += 1;
+        entry.bytes += size;
+        if let Frame::Timestamp(ts) = &frame {
+            first_timestamp.get_or_insert(ts.timestamp);
+            last_timestamp = Some(ts.timestamp);
+        }
+
+        if !dump || !filter.matches(index, &frame) {
+            continue;
+        }
+        if json {
+            let line = serde_json::json!({
+                "index": index,
+                "offset": offset,
+                "size": size,
+                "frame": frame,
+            });
+            println!("{}", line);
+        } else {
+            let detail = frame_detail(&frame);
+            let separator = if detail.is_empty() { "" } else { " — " };
+            println!(
+                "  #{:<6} @{:<9} {:>8}  {}{}{}",
+                index,
+                offset,
+                size,
+                frame.type_name(),
+                separator,
+                detail
+            );
+        }
+    }
+
+    if !json {
+        println!();
+        println!("Frames:  {}", frame_count);
+        if let (Some(first), Some(last)) = (first_timestamp, last_timestamp) {
+            println!("Span:    {} ms ({} → {})", last.saturating_sub(first), first, last);
+        }
+        println!();
+
+        let mut sorted: Vec<_> = stats.into_iter().collect();
+        sorted.sort_by_key(|(name, stats)| (std::cmp::Reverse(stats.bytes), *name));
+        println!("  {:<30} {:>8} {:>12}", "Type", "Count", "Bytes");
+        for (name, stats) in &sorted {
+            println!("  {:<30} {:>8} {:>12}", name, stats.count, format_bytes(stats.bytes));
+        }
+    }
+
+    match read_error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,96 @@
+//! `dcrr`: command-line tools for DomCorder recordings
+
+mod args;
+mod client;
+mod convert;
+mod format;
+mod input;
+mod inspect;
+mod merge;
+mod output;
+mod play;
+mod pull;
+mod push;
+mod split;
+mod stats;
+mod trim;
+mod validate;
+
+use std::env;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: dcrr <command> [args]
+
+Commands:
+  inspect    Show the header, frame counts and sizes, and optionally dump frames
+  convert    Convert between .dcrr and newline-delimited JSON
+  stats      Show duration, size breakdown, largest assets and compression estimates
+  validate   Check a recording for corrupt frames and semantic problems
+  trim       Keep a time range of a recording
+  split      Split a recording into self-contained chunks
+  merge      Concatenate recordings
+  push       Upload a recording to a server
+  pull       Download a recording from a server
+  play       Serve a recording locally for the player
+
+Run `dcrr <command> --help` for command options.";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let usage = match command.as_str() {
+        "inspect" => inspect::USAGE,
+        "validate" => validate::USAGE,
+        "stats" => stats::USAGE,
+        "convert" => convert::USAGE,
+        "trim" => trim::USAGE,
+        "split" => split::USAGE,
+        "merge" => merge::USAGE,
+        "push" => push::USAGE,
+        "pull" => pull::USAGE,
+        "play" => play::USAGE,
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        other => {
+            eprintln!("Unknown command: {}\n\n{}", other, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    if rest.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", usage);
+        return ExitCode::SUCCESS;
+    }
+
+    let result = match command.as_str() {
+        "inspect" => inspect::run(rest).await,
+        "validate" => validate::run(rest).await,
+        "stats" => stats::run(rest).await,
+        "convert" => convert::run(rest).await,
+        "trim" => trim::run(rest).await,
+        "split" => split::run(rest).await,
+        "merge" => merge::run(rest).await,
+        "push" => push::run(rest).await,
+        "pull" => pull::run(rest).await,
+        "play" => play::run(rest).await,
+        _ => unreachable!("command validated above"),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            if e.starts_with("missing ") {
+                eprintln!("\n{}", usage);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
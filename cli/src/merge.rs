@@ -0,0 +1,74 @@
+//! `dcrr merge`: concatenate recordings
+
+use crate::args::Args;
+use crate::input::open_recording;
+use crate::output::create_derived_recording;
+use domcorder_proto::Frame;
+use domcorder_proto::edit::AssetIdRemapper;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+dcrr merge <input>... --output <file>
+
+Concatenate recordings in the order given. Asset ids are renumbered so they
+do not collide; each input keeps its own keyframe. The output header is taken
+from the first input.
+
+Options:
+  --output FILE     The merged recording";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["output"])?;
+    let output = Path::new(args.value("output").ok_or("missing --output")?);
+    if args.positional.len() < 2 {
+        return Err("missing <input>: at least two recordings are required".to_string());
+    }
+
+    let mut writer = None;
+    let mut remapper = AssetIdRemapper::new();
+    let mut last_timestamp: Option<u64> = None;
+    let mut written = 0u64;
+
+    let inputs: Vec<&Path> = args.positional.iter().map(Path::new).collect();
+    for &input in &inputs {
+        let (mut reader, header) = open_recording(input)
+            .await
+            .map_err(|e| format!("{}: {}", input.display(), e))?;
+        if writer.is_none() {
+            let header = header.unwrap_or_default();
+            let merged = create_derived_recording(output, &header, "merge", &inputs);
+            writer = Some(merged.map_err(|e| format!("{}: {}", output.display(), e))?);
+        }
+        let writer = writer.as_mut().expect("writer created above");
+
+        remapper.next_recording();
+        let mut first_in_input = true;
+        loop {
+            let offset = reader.position();
+            let mut frame = match reader.read_frame().await {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => return Err(format!("{}: byte offset {}: {}", input.display(), offset, e)),
+            };
+            if let Frame::Timestamp(ts) = &frame {
+                if first_in_input && last_timestamp.is_some_and(|last| ts.timestamp < last) {
+                    eprintln!(
+                        "Warning: {} starts before the previous input ends; timestamps will go backwards",
+                        input.display()
+                    );
+                }
+                first_in_input = false;
+                last_timestamp = Some(ts.timestamp);
+            }
+            remapper.remap(&mut frame);
+            writer.write_frame(&frame).map_err(|e| e.to_string())?;
+            written += 1;
+        }
+    }
+
+    if let Some(mut writer) = writer {
+        writer.flush().map_err(|e| e.to_string())?;
+    }
+    println!("Wrote {} frames to {}", written, output.display());
+    Ok(())
+}
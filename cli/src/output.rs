@@ -0,0 +1,36 @@
+//! Writing recordings
+
+use domcorder_proto::{FileHeader, FrameWriter};
+use domcorder_server::provenance::Provenance;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+pub type RecordingWriter = FrameWriter<BufWriter<File>>;
+
+/// Create a `.dcrr` file and write its header
+pub fn create_recording(path: &Path, header: &FileHeader) -> io::Result<RecordingWriter> {
+    let mut writer = FrameWriter::new(BufWriter::new(File::create(path)?));
+    writer.write_header(header)?;
+    Ok(writer)
+}
+
+/// This tool, as named in the provenance of the recordings it derives
+pub const TOOL: &str = concat!("dcrr/", env!("CARGO_PKG_VERSION"));
+
+/// Create a `.dcrr` file derived from `sources` by `operation`: its header,
+/// then an annotation recording its provenance
+pub fn create_derived_recording(
+    path: &Path,
+    header: &FileHeader,
+    operation: &str,
+    sources: &[&Path],
+) -> io::Result<RecordingWriter> {
+    let mut writer = create_recording(path, header)?;
+    let sources = sources
+        .iter()
+        .map(|source| source.file_name().unwrap_or(source.as_os_str()).to_string_lossy().to_string())
+        .collect();
+    writer.write_frame(&Provenance::new(TOOL, operation, sources).to_frame())?;
+    Ok(writer)
+}
@@ -0,0 +1,152 @@
+//! `dcrr play`: serve a single recording to the player from a local server
+
+use crate::args::Args;
+use crate::input::open_recording;
+use crate::output::create_recording;
+use axum::Router;
+use domcorder_server::asset_cache::local::LocalBinaryStore;
+use domcorder_server::asset_cache::sqlite::SqliteMetadataStore;
+use domcorder_server::{DomcorderRouter, RouteGroup, StorageState};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower_http::services::{ServeDir, ServeFile};
+
+/// The port the player fetches recordings from
+const DEFAULT_PORT: u16 = 8723;
+
+pub const USAGE: &str = "\
+dcrr play <file> [options]
+
+Serve a recording from a throwaway local server so the player can open it.
+The server exposes only the read-only routes (/recordings, /recording/{id},
+/assets/{hash}, ...) and stops on Ctrl-C, deleting its temporary storage.
+
+The player fetches from http://localhost:8723, so keep the default port
+unless you have pointed the player elsewhere.
+
+Options:
+  --port N        Port to listen on (default 8723)
+  --player DIR    Also serve a built player (e.g. player/dist) at /
+                  (or DOMCORDER_PLAYER_DIR)";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["port", "player"])?;
+    let path = Path::new(args.required(0, "<file>")?);
+    let port = args.parsed::<u16>("port")?.unwrap_or(DEFAULT_PORT);
+    let player_dir = args
+        .value("player")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("DOMCORDER_PLAYER_DIR").map(PathBuf::from));
+
+    let storage = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let filename = stored_filename(path);
+    let recordings_dir = storage.path().join("recordings");
+    std::fs::create_dir_all(&recordings_dir).map_err(|e| e.to_string())?;
+    store_recording(path, &recordings_dir.join(&filename)).await?;
+
+    let base_url = format!("http://127.0.0.1:{}", port);
+    let metadata_store = SqliteMetadataStore::new(storage.path().join("asset_cache.db"))
+        .map_err(|e| e.to_string())?;
+    let asset_file_store = LocalBinaryStore::new(storage.path().join("assets"), base_url.clone())
+        .map_err(|e| e.to_string())?;
+    let state = StorageState::new(
+        storage.path().to_path_buf(),
+        Box::new(metadata_store),
+        Box::new(asset_file_store),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut app = DomcorderRouter::new(Arc::new(state)).routes(&[RouteGroup::Listing, RouteGroup::Playback]);
+    if let Some(dir) = &player_dir {
+        app = with_player(app, dir)?;
+    }
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("cannot listen on port {}: {}", port, e))?;
+
+    eprintln!("Serving {} on {}", path.display(), base_url);
+    eprintln!("  recording: {}/recording/{}", base_url, filename);
+    match &player_dir {
+        Some(_) => eprintln!("  player:    {}/", base_url),
+        None => eprintln!("  open the player (or pass --player DIR) and select {}", filename),
+    }
+    eprintln!("Press Ctrl-C to stop");
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Dropping `storage` removes the copied recording and any cached assets
+    drop(storage);
+    Ok(())
+}
+
+/// Copy a recording into the server's storage
+///
+/// The server expects a header on every stored file, so raw frame streams get
+/// one. Decoding everything up front also reports a corrupt file here rather
+/// than halfway through playback.
+async fn store_recording(input: &Path, stored: &Path) -> Result<(), String> {
+    let (mut reader, header) = open_recording(input)
+        .await
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    let header = header.unwrap_or_default();
+    let mut writer = create_recording(stored, &header).map_err(|e| e.to_string())?;
+    while let Some(frame) = reader
+        .read_frame()
+        .await
+        .map_err(|e| format!("{}: {}", input.display(), e))?
+    {
+        writer.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Serve a built player bundle for any path the API does not handle
+fn with_player(app: Router, dir: &Path) -> Result<Router, String> {
+    let index = dir.join("index.html");
+    if !index.is_file() {
+        return Err(format!(
+            "{} has no index.html; build the player first (cd player && bun run build)",
+            dir.display()
+        ));
+    }
+    Ok(app.fallback_service(ServeDir::new(dir).fallback(ServeFile::new(index))))
+}
+
+/// Name the copy after the input file, so the player lists something familiar
+fn stored_filename(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("recording");
+    format!("{}.dcrr", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::writer::HEADER_SIZE;
+
+    #[tokio::test]
+    async fn test_raw_streams_are_stored_with_a_header() {
+        let sample = Path::new("../.sample_data/proto/file-basic.dcrr");
+        let dir = tempfile::TempDir::new().unwrap();
+        let raw = dir.path().join("bug-report.bin");
+        let stored = dir.path().join(stored_filename(&raw));
+        std::fs::write(&raw, &std::fs::read(sample).unwrap()[HEADER_SIZE..]).unwrap();
+
+        store_recording(&raw, &stored).await.unwrap();
+
+        assert_eq!(stored.file_name().unwrap(), "bug-report.dcrr");
+        let original = std::fs::read(sample).unwrap();
+        let copy = std::fs::read(&stored).unwrap();
+        assert_eq!(&copy[..4], b"DCRR");
+        assert_eq!(copy[HEADER_SIZE..], original[HEADER_SIZE..]);
+    }
+}
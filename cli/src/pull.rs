@@ -0,0 +1,97 @@
+//! `dcrr pull`: download a recording from a server
+
+use crate::args::Args;
+use crate::client::{ServerClient, VALUE_OPTIONS};
+use crate::output::create_recording;
+use domcorder_proto::{AssetData, AssetFetchError, FileHeader, Frame, FrameReader};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+dcrr pull <id> [options]
+
+Download a recording as a .dcrr file. Assets cached on the server are
+downloaded and inlined so the file plays back offline.
+
+Options:
+  --output FILE     Where to write the recording (default: <id>, with .dcrr added)
+  --no-assets       Keep asset references instead of inlining cached assets
+  --server URL      Server base URL (or DOMCORDER_SERVER)
+  --api-key KEY     API key (or DOMCORDER_API_KEY)";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let mut value_options = VALUE_OPTIONS.to_vec();
+    value_options.push("output");
+    let args = Args::parse(args, &value_options)?;
+    let id = args.required(0, "<id>")?;
+    let client = ServerClient::from_args(&args)?;
+    let inline_assets = !args.flag("no-assets");
+    let output = match args.value("output") {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let name = id.rsplit('/').next().unwrap_or(id);
+            let mut path = PathBuf::from(name);
+            if path.extension().is_none_or(|ext| ext != "dcrr") {
+                path.set_extension("dcrr");
+            }
+            path
+        }
+    };
+
+    // The playback endpoint returns a PlaybackConfig frame followed by the
+    // recording's frames (without the file header, but with its index)
+    // Ids of recordings in subdirectories contain `/`, which the route takes encoded
+    let path = format!("/recording/{}", id.replace('/', "%2F"));
+    let mut response = client.send(client.get(&path)).await?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+    }
+
+    let mut reader = FrameReader::new(Cursor::new(body), false).with_index(true);
+    let mut frames = Vec::new();
+    loop {
+        let offset = reader.position();
+        match reader.read_frame().await {
+            Ok(Some(Frame::PlaybackConfig(_))) => {}
+            Ok(Some(frame)) => frames.push(frame),
+            Ok(None) => break,
+            Err(e) => return Err(format!("byte offset {} of download: {}", offset, e)),
+        }
+    }
+
+    let created_at = frames.iter().find_map(|frame| match frame {
+        Frame::Timestamp(ts) => Some(ts.timestamp),
+        _ => None,
+    });
+    let header = created_at.map_or_else(FileHeader::new, FileHeader::with_timestamp);
+    let mut writer = create_recording(&output, &header).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    let mut inlined = 0;
+    for frame in frames {
+        let frame = match frame {
+            Frame::AssetReference(reference) if inline_assets => {
+                // Stored recordings reference assets by their retrieval token
+                let response = client.send(client.get(&format!("/assets/{}", reference.hash))).await?;
+                let buf = response.bytes().await.map_err(|e| e.to_string())?.to_vec();
+                inlined += 1;
+                Frame::Asset(AssetData {
+                    asset_id: reference.asset_id,
+                    url: reference.url,
+                    mime: reference.mime,
+                    buf,
+                    fetch_error: AssetFetchError::None,
+                })
+            }
+            frame => frame,
+        };
+        writer.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    if inlined > 0 {
+        eprintln!("Inlined {} assets", inlined);
+    }
+    println!("{}", output.display());
+    Ok(())
+}
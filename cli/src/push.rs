@@ -0,0 +1,60 @@
+//! `dcrr push`: upload a recording to a server
+
+use crate::args::Args;
+use crate::client::{ServerClient, VALUE_OPTIONS};
+use crate::format::format_bytes;
+use domcorder_proto::writer::{DCRR_MAGIC, HEADER_SIZE};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Read size when streaming the file to the server
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+pub const USAGE: &str = "\
+dcrr push <file> [options]
+
+Upload a recording to a server's POST /record endpoint and print the id it
+was stored as. The server writes a new header, so the creation time becomes
+the upload time.
+
+Options:
+  --server URL      Server base URL (or DOMCORDER_SERVER)
+  --api-key KEY     API key (or DOMCORDER_API_KEY)";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &VALUE_OPTIONS)?;
+    let path = Path::new(args.required(0, "<file>")?);
+    let client = ServerClient::from_args(&args)?;
+
+    let io_error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    let mut file = tokio::fs::File::open(path).await.map_err(io_error)?;
+    let len = file.metadata().await.map_err(io_error)?.len();
+
+    // POST /record takes a bare frame stream
+    let mut magic = [0u8; 4];
+    let has_header = len >= HEADER_SIZE as u64 && {
+        file.read_exact(&mut magic).await.map_err(io_error)?;
+        magic == DCRR_MAGIC
+    };
+    let start = if has_header { HEADER_SIZE as u64 } else { 0 };
+    file.seek(SeekFrom::Start(start)).await.map_err(io_error)?;
+
+    let size = len - start;
+    let body = reqwest::Body::wrap_stream(ReaderStream::with_capacity(file, UPLOAD_CHUNK_SIZE));
+    let request = client
+        .post("/record")
+        .header(reqwest::header::CONTENT_LENGTH, size)
+        .body(body);
+    let response = client.send(request).await?;
+    let message = response.text().await.map_err(|e| e.to_string())?;
+    let id = message
+        .strip_prefix("Recording saved as ")
+        .unwrap_or(&message)
+        .trim();
+
+    eprintln!("Uploaded {} to {}", format_bytes(size), client.url("/record"));
+    println!("{}", id);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! `dcrr split`: cut a recording into self-contained chunks
+
+use crate::args::Args;
+use crate::input::open_recording;
+use crate::output::{RecordingWriter, create_derived_recording};
+use crate::trim::header_at;
+use domcorder_proto::Frame;
+use domcorder_proto::edit::{SplitMode, Splitter};
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+dcrr split <input> (--at-keyframes | --every MS) [options]
+
+Split a recording into chunks named <name>.partNNN.dcrr. Each chunk starts
+with a synthesized keyframe and the assets seen so far, so it plays back on
+its own.
+
+Options:
+  --at-keyframes    Start a new chunk at each keyframe (page load)
+  --every MS        Start a new chunk every MS milliseconds
+  --out-dir DIR     Directory for the chunks (default: next to the input)";
+
+fn chunk_path(input: &Path, out_dir: &Path, index: usize) -> PathBuf {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("recording");
+    out_dir.join(format!("{}.part{:03}.dcrr", stem, index))
+}
+
+fn first_timestamp(frames: &[Frame]) -> Option<u64> {
+    frames.iter().find_map(|frame| match frame {
+        Frame::Timestamp(ts) => Some(ts.timestamp),
+        _ => None,
+    })
+}
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["every", "out-dir"])?;
+    let input = Path::new(args.required(0, "<input>")?);
+    let mode = match (args.flag("at-keyframes"), args.parsed("every")?) {
+        (true, None) => SplitMode::Keyframes,
+        (false, Some(interval)) => SplitMode::EveryMs(interval),
+        _ => return Err("exactly one of --at-keyframes and --every is required".to_string()),
+    };
+    let out_dir = match args.value("out-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    std::fs::create_dir_all(&out_dir).map_err(|e| format!("{}: {}", out_dir.display(), e))?;
+
+    let (mut reader, header) = open_recording(input)
+        .await
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    let header = header.unwrap_or_default();
+
+    let mut splitter = Splitter::new(mode);
+    let mut chunks: Vec<PathBuf> = Vec::new();
+    let mut writer: Option<RecordingWriter> = None;
+
+    loop {
+        let offset = reader.position();
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(e) => return Err(format!("byte offset {}: {}", offset, e)),
+        };
+        let step = splitter.push(frame);
+
+        if step.new_chunk || writer.is_none() {
+            if let Some(mut previous) = writer.take() {
+                previous.flush().map_err(|e| e.to_string())?;
+            }
+            let path = chunk_path(input, &out_dir, chunks.len() + 1);
+            let chunk_header = if chunks.is_empty() {
+                header.clone()
+            } else {
+                header_at(first_timestamp(&step.frames), &header)
+            };
+            let chunk = create_derived_recording(&path, &chunk_header, "split", &[input]);
+            writer = Some(chunk.map_err(|e| format!("{}: {}", path.display(), e))?);
+            chunks.push(path);
+        }
+
+        let current = writer.as_mut().expect("chunk writer created above");
+        for frame in &step.frames {
+            current.write_frame(frame).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(mut last) = writer {
+        last.flush().map_err(|e| e.to_string())?;
+    }
+
+    for chunk in &chunks {
+        println!("{}", chunk.display());
+    }
+    println!("Wrote {} chunks", chunks.len());
+    Ok(())
+}
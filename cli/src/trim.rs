@@ -0,0 +1,63 @@
+//! `dcrr trim`: keep a time range of a recording
+
+use crate::args::Args;
+use crate::input::open_recording;
+use crate::output::create_derived_recording;
+use domcorder_proto::FileHeader;
+use domcorder_proto::edit::Trimmer;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+dcrr trim <input> <output> [options]
+
+Keep only the frames between two offsets (ms since the first timestamp).
+When trimming the start, the output begins with a keyframe synthesized from
+the replayed DOM so it plays back on its own.
+
+Options:
+  --start MS        Start offset (default 0)
+  --end MS          End offset (default: end of recording)";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["start", "end"])?;
+    let input = Path::new(args.required(0, "<input>")?);
+    let output = Path::new(args.required(1, "<output>")?);
+    let start_ms = args.parsed("start")?.unwrap_or(0);
+    let end_ms: Option<u64> = args.parsed("end")?;
+    if end_ms.is_some_and(|end| end < start_ms) {
+        return Err("--end must not be before --start".to_string());
+    }
+
+    let (mut reader, header) = open_recording(input)
+        .await
+        .map_err(|e| format!("{}: {}", input.display(), e))?;
+    let header = header.unwrap_or_default();
+    let mut writer = create_derived_recording(output, &header, "trim", &[input]).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    let mut trimmer = Trimmer::new(start_ms, end_ms);
+    let mut written = 0u64;
+    while !trimmer.is_finished() {
+        let offset = reader.position();
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(e) => return Err(format!("byte offset {}: {}", offset, e)),
+        };
+        for frame in trimmer.push(frame) {
+            writer.write_frame(&frame).map_err(|e| e.to_string())?;
+            written += 1;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    if written == 0 {
+        eprintln!("Warning: no frames in range; {} has only a header", output.display());
+    }
+    println!("Wrote {} frames to {}", written, output.display());
+    Ok(())
+}
+
+/// Header for an output that starts at `timestamp`, falling back to the input header
+pub fn header_at(timestamp: Option<u64>, fallback: &FileHeader) -> FileHeader {
+    timestamp.map_or_else(|| fallback.clone(), FileHeader::with_timestamp)
+}
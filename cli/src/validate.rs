@@ -0,0 +1,78 @@
+//! `dcrr validate`: structural and semantic checks
+
+use crate::args::Args;
+use crate::input::open_recording;
+use domcorder_proto::FrameValidator;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+dcrr validate <file> [options]
+
+Check that every frame decodes, DOM mutations reference existing node ids,
+timestamps never go backwards, and every asset:N placeholder is defined.
+Exits non-zero if any problem is found.
+
+Options:
+  --max-issues N    Stop printing after N issues (default 100)";
+
+const DEFAULT_MAX_ISSUES: usize = 100;
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["max-issues"])?;
+    let path = Path::new(args.required(0, "<file>")?);
+    let max_issues = args.parsed("max-issues")?.unwrap_or(DEFAULT_MAX_ISSUES);
+
+    let (mut reader, _header) = open_recording(path)
+        .await
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut validator = FrameValidator::new();
+    // Byte offset of each frame, for reporting issues found at end of stream
+    let mut offsets = Vec::new();
+    let mut issue_count = 0usize;
+    let mut report = |frame_index: u64, offset: u64, message: String| {
+        issue_count += 1;
+        if issue_count <= max_issues {
+            println!("frame #{} @{}: {}", frame_index, offset, message);
+        }
+    };
+
+    let mut corrupt = false;
+    loop {
+        let offset = reader.position();
+        match reader.read_frame().await {
+            Ok(Some(frame)) => {
+                offsets.push(offset);
+                for issue in validator.push_frame(&frame) {
+                    report(issue.frame_index(), offset, issue.to_string());
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                // Frames are length-prefixed without sync markers, so nothing
+                // after a corrupt frame can be trusted
+                report(offsets.len() as u64, offset, format!("corrupt frame: {}", e));
+                corrupt = true;
+                break;
+            }
+        }
+    }
+
+    let frame_count = validator.frame_count();
+    for issue in validator.finish() {
+        let offset = offsets[issue.frame_index() as usize];
+        report(issue.frame_index(), offset, issue.to_string());
+    }
+
+    if issue_count > max_issues {
+        println!("... {} more issues not shown", issue_count - max_issues);
+    }
+    if issue_count == 0 {
+        println!("OK: {} frames, no problems found", frame_count);
+        return Ok(());
+    }
+
+    let checked = if corrupt { "before the corrupt frame" } else { "checked" };
+    let plural = if issue_count == 1 { "" } else { "s" };
+    Err(format!("{} problem{} found ({} frames {})", issue_count, plural, frame_count, checked))
+}
@@ -0,0 +1,9 @@
+//! Rust client for streaming recordings to a DomCorder server
+//!
+//! [`Recorder`] speaks the same `/ws/record` protocol as the browser
+//! recorder, so headless-browser automation and server-side renderers can
+//! produce recordings without going through a page.
+
+pub mod recorder;
+
+pub use recorder::{ReconnectPolicy, Recorder, RecorderConfig, RecorderError, RecorderStats};
@@ -0,0 +1,557 @@
+use domcorder_proto::edit::RecordingState;
+use domcorder_proto::{
+    AssetReferenceData, CacheInventoryData, Frame, FrameReader, FrameWriter, HeartbeatPongData, RecordingMetadataData,
+};
+use futures_util::{SinkExt, StreamExt};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, header};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long to wait for the server to acknowledge our close before giving up
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshake query parameter and response header for the batch size
+const BATCH_BYTES_PARAM: &str = "batch_bytes";
+const BATCH_BYTES_HEADER: &str = "x-domcorder-batch-bytes";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecorderError {
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tungstenite::Error),
+
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+
+    #[error("Handshake failed: {0}")]
+    Handshake(String),
+
+    /// The server won't store this session (e.g. it was not sampled)
+    #[error("Server declined the recording: {0}")]
+    Declined(String),
+
+    #[error("Failed to encode frame: {0}")]
+    Encode(#[from] std::io::Error),
+
+    #[error("Gave up after {0} reconnection attempts")]
+    ReconnectFailed(u32),
+
+    #[error("Recorder stopped")]
+    Stopped,
+}
+
+/// Backoff for re-establishing a dropped connection
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Attempts per outage before the recorder gives up (0 disables reconnection)
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Fail as soon as the connection drops
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before the given (1-based) attempt: doubling from `initial_delay`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    /// WebSocket endpoint, e.g. `ws://127.0.0.1:8723/ws/record`
+    pub url: String,
+    /// Page URL sent in the RecordingMetadata frame
+    pub initial_url: String,
+    /// Sent as a bearer token when the server requires an API key
+    pub api_key: Option<String>,
+    /// Send a Heartbeat frame after this long without frames (0 disables)
+    pub heartbeat_interval_seconds: u32,
+    /// Frames buffered before [`Recorder::send`] waits for the socket
+    pub queue_capacity: usize,
+    /// How long to wait for the cache manifest after sending metadata
+    pub handshake_timeout: Duration,
+    pub reconnect: ReconnectPolicy,
+    /// Preferred WebSocket message size: queued frames are packed into one
+    /// message until it reaches this many bytes (0 sends one frame per
+    /// message). The server may lower it during the handshake.
+    pub batch_bytes: usize,
+    /// SHA-256 hashes of assets the server is known to have for the site
+    /// (e.g. from an earlier session's manifest); they are reported during
+    /// the handshake, and the manifest lists only the others
+    pub cached_assets: HashSet<String>,
+}
+
+impl RecorderConfig {
+    pub fn new(url: impl Into<String>, initial_url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            initial_url: initial_url.into(),
+            api_key: None,
+            heartbeat_interval_seconds: 30,
+            queue_capacity: 256,
+            handshake_timeout: Duration::from_secs(10),
+            reconnect: ReconnectPolicy::default(),
+            batch_bytes: 64 * 1024,
+            cached_assets: HashSet::new(),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_heartbeat_interval(mut self, seconds: u32) -> Self {
+        self.heartbeat_interval_seconds = seconds;
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    pub fn with_reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    pub fn with_batch_bytes(mut self, bytes: usize) -> Self {
+        self.batch_bytes = bytes;
+        self
+    }
+
+    pub fn with_cached_assets(mut self, hashes: impl IntoIterator<Item = String>) -> Self {
+        self.cached_assets.extend(hashes);
+        self
+    }
+
+    /// The endpoint with the batch size request appended
+    fn handshake_url(&self) -> String {
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{}{}={}", self.url, separator, BATCH_BYTES_PARAM, self.batch_bytes)
+    }
+}
+
+/// Counters reported by [`Recorder::finish`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecorderStats {
+    pub frames_sent: u64,
+    /// WebSocket messages the frames were packed into
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    /// Asset frames replaced by references because the server already had them
+    pub assets_deduplicated: u64,
+    pub reconnects: u32,
+}
+
+/// Streams frames to a DomCorder server over `/ws/record`
+///
+/// Frames are queued and sent by a background task; `send` waits when the
+/// queue is full, so a slow connection slows the producer down rather than
+/// buffering without bound. Frames already queued when the task sends are
+/// packed into one message, up to the negotiated batch size. Assets the
+/// server's cache manifest already lists are sent as AssetReference frames.
+///
+/// If the connection drops, the recorder reconnects according to its
+/// [`ReconnectPolicy`]. The server stores each connection as a separate
+/// recording, so the new connection starts with frames that recreate the page
+/// as it was (a synthesized Keyframe, properties, assets and scroll offset).
+/// Tracking that state keeps every asset seen so far in memory.
+pub struct Recorder {
+    frames: mpsc::Sender<Frame>,
+    batch_bytes: usize,
+    task: JoinHandle<Result<RecorderStats, RecorderError>>,
+}
+
+impl Recorder {
+    /// Connect and complete the metadata/manifest handshake
+    pub async fn connect(config: RecorderConfig) -> Result<Self, RecorderError> {
+        let connection = Connection::open(&config, &config.cached_assets).await?;
+        info!("🔌 Connected to {} ({} cached assets)", config.url, connection.manifest.len());
+
+        let (frames, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let batch_bytes = connection.batch_bytes;
+        let session = Session {
+            config,
+            connection,
+            state: RecordingState::new(),
+            stats: RecorderStats::default(),
+            failures: 0,
+        };
+        let task = tokio::spawn(session.run(receiver));
+        Ok(Self { frames, batch_bytes, task })
+    }
+
+    /// The batch size agreed with the server
+    pub fn batch_bytes(&self) -> usize {
+        self.batch_bytes
+    }
+
+    /// Queue a frame for sending
+    ///
+    /// Fails with [`RecorderError::Stopped`] once the connection is lost for
+    /// good; [`Recorder::finish`] then returns the underlying error.
+    pub async fn send(&self, frame: Frame) -> Result<(), RecorderError> {
+        self.frames.send(frame).await.map_err(|_| RecorderError::Stopped)
+    }
+
+    /// Send the remaining frames and close the connection
+    ///
+    /// Returns once the server has acknowledged the close, which it does after
+    /// the recording has been saved.
+    pub async fn finish(self) -> Result<RecorderStats, RecorderError> {
+        drop(self.frames);
+        self.task.await.map_err(|_| RecorderError::Stopped)?
+    }
+}
+
+struct Connection {
+    socket: Socket,
+    /// SHA-256 hashes of the assets the server already has for this site
+    manifest: HashSet<String>,
+    /// Agreed batch size: our preference, lowered if the server asked
+    batch_bytes: usize,
+}
+
+impl Connection {
+    /// Connect and complete the handshake, reporting the `cached` assets so
+    /// the manifest leaves them out
+    async fn open(config: &RecorderConfig, cached: &HashSet<String>) -> Result<Self, RecorderError> {
+        let mut request = config.handshake_url().into_client_request()?;
+        if let Some(api_key) = &config.api_key {
+            let value = HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| RecorderError::InvalidApiKey(e.to_string()))?;
+            request.headers_mut().insert(header::AUTHORIZATION, value);
+        }
+        let (mut socket, response) = tokio_tungstenite::connect_async(request).await?;
+        // Servers that predate batching send no header and accept any message size
+        let batch_bytes = response
+            .headers()
+            .get(BATCH_BYTES_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map_or(config.batch_bytes, |agreed: usize| agreed.min(config.batch_bytes));
+
+        if !cached.is_empty() {
+            let inventory = Frame::CacheInventory(CacheInventoryData {
+                sha256_hashes: cached.iter().cloned().collect(),
+            });
+            socket.send(Message::Binary(encode(&inventory)?.into())).await?;
+        }
+        let metadata = Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: config.initial_url.clone(),
+            heartbeat_interval_seconds: config.heartbeat_interval_seconds,
+        });
+        socket.send(Message::Binary(encode(&metadata)?.into())).await?;
+
+        let mut manifest = tokio::time::timeout(config.handshake_timeout, read_manifest(&mut socket))
+            .await
+            .map_err(|_| RecorderError::Handshake("timed out waiting for the cache manifest".to_string()))??;
+        manifest.extend(cached.iter().cloned());
+        Ok(Self { socket, manifest, batch_bytes })
+    }
+}
+
+async fn read_manifest(socket: &mut Socket) -> Result<HashSet<String>, RecorderError> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            Message::Binary(data) => {
+                match decode(&data).await {
+                    Some(Frame::CacheManifest(manifest)) => {
+                        return Ok(manifest.assets.into_iter().map(|entry| entry.sha256_hash).collect());
+                    }
+                    Some(Frame::StopRecording(stop)) => return Err(RecorderError::Declined(stop.reason)),
+                    _ => {}
+                }
+            }
+            // The server reports a rejected recording as a text message
+            Message::Text(reason) => return Err(RecorderError::Handshake(reason.to_string())),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+    Err(RecorderError::Handshake(
+        "server closed the connection before sending the cache manifest".to_string(),
+    ))
+}
+
+/// Frames packed into one WebSocket message
+#[derive(Default)]
+struct Batch {
+    frames: Vec<Frame>,
+    data: Vec<u8>,
+    /// Frames in `data` sent as AssetReferences
+    deduplicated: u64,
+}
+
+/// State owned by the background send task
+struct Session {
+    config: RecorderConfig,
+    connection: Connection,
+    state: RecordingState,
+    stats: RecorderStats,
+    /// Reconnection attempts since the last successful write
+    failures: u32,
+}
+
+impl Session {
+    async fn run(mut self, mut frames: mpsc::Receiver<Frame>) -> Result<RecorderStats, RecorderError> {
+        let heartbeat = match self.config.heartbeat_interval_seconds {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds as u64)),
+        };
+
+        loop {
+            let next = tokio::select! {
+                next = frames.recv() => next,
+                Some(Ok(Message::Binary(data))) = self.connection.socket.next() => {
+                    match decode(&data).await {
+                        Some(Frame::HeartbeatPing(ping)) => self.answer_ping(ping.server_timestamp).await?,
+                        // The frames come from the caller, who owns the page
+                        Some(Frame::RequestKeyframe(request)) => {
+                            debug!("Server requested a keyframe: {}", request.reason);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                _ = idle(heartbeat) => {
+                    let mut batch = Batch::default();
+                    self.add(&mut batch, Frame::Heartbeat)?;
+                    self.deliver(batch).await?;
+                    continue;
+                }
+            };
+            let Some(frame) = next else { break };
+
+            // Pack in whatever else is already queued
+            let mut batch = Batch::default();
+            self.add(&mut batch, frame)?;
+            while batch.data.len() < self.connection.batch_bytes {
+                match frames.try_recv() {
+                    Ok(frame) => self.add(&mut batch, frame)?,
+                    Err(_) => break,
+                }
+            }
+            self.deliver(batch).await?;
+        }
+
+        self.close().await;
+        Ok(self.stats)
+    }
+
+    /// Answer a HeartbeatPing with our clock, so the server can estimate its skew
+    ///
+    /// A pong is only meaningful on the connection that was pinged, so it is
+    /// not resent after a reconnect; a failed send is left for the next
+    /// frame's delivery to notice.
+    async fn answer_ping(&mut self, server_timestamp: u64) -> Result<(), RecorderError> {
+        let client_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let mut batch = Batch::default();
+        self.add(&mut batch, Frame::HeartbeatPong(HeartbeatPongData {
+            server_timestamp,
+            client_timestamp,
+        }))?;
+        if let Err(e) = self.write(&mut batch).await {
+            debug!("Failed to answer heartbeat ping: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Send a batch, reconnecting as many times as the policy allows
+    async fn deliver(&mut self, mut batch: Batch) -> Result<(), RecorderError> {
+        loop {
+            match self.write(&mut batch).await {
+                Ok(()) => break,
+                Err(RecorderError::WebSocket(e)) => {
+                    warn!("⚠️ Connection lost: {}", e);
+                    self.reconnect().await?;
+                    // The new connection's manifest decides what is deduplicated
+                    let frames = std::mem::take(&mut batch.frames);
+                    batch = Batch::default();
+                    for frame in frames {
+                        self.add(&mut batch, frame)?;
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.failures = 0;
+        for frame in &batch.frames {
+            self.state.push(frame);
+        }
+        Ok(())
+    }
+
+    /// Encode `frame` onto the end of `batch`
+    fn add(&self, batch: &mut Batch, frame: Frame) -> Result<(), RecorderError> {
+        let outgoing = self.deduplicate(&frame);
+        FrameWriter::new(&mut batch.data).write_frame(outgoing.as_ref().unwrap_or(&frame))?;
+        if outgoing.is_some() {
+            batch.deduplicated += 1;
+        }
+        batch.frames.push(frame);
+        Ok(())
+    }
+
+    /// Send the batch's encoded frames, leaving its `data` empty
+    async fn write(&mut self, batch: &mut Batch) -> Result<(), RecorderError> {
+        if batch.frames.is_empty() {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut batch.data);
+        let len = data.len() as u64;
+        self.connection.socket.send(Message::Binary(data.into())).await?;
+        self.stats.frames_sent += batch.frames.len() as u64;
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += len;
+        self.stats.assets_deduplicated += batch.deduplicated;
+        Ok(())
+    }
+
+    /// An AssetReference to send instead of `frame`, if the server has the asset
+    fn deduplicate(&self, frame: &Frame) -> Option<Frame> {
+        let Frame::Asset(asset) = frame else { return None };
+        if asset.buf.is_empty() {
+            return None;
+        }
+        let hash = format!("{:x}", Sha256::digest(&asset.buf));
+        if !self.connection.manifest.contains(&hash) {
+            return None;
+        }
+        Some(Frame::AssetReference(AssetReferenceData {
+            asset_id: asset.asset_id,
+            url: asset.url.clone(),
+            hash,
+            mime: asset.mime.clone(),
+        }))
+    }
+
+    async fn reconnect(&mut self) -> Result<(), RecorderError> {
+        let policy = self.config.reconnect.clone();
+        loop {
+            self.failures += 1;
+            if self.failures > policy.max_attempts {
+                return Err(RecorderError::ReconnectFailed(policy.max_attempts));
+            }
+            tokio::time::sleep(policy.delay(self.failures)).await;
+
+            match self.resume().await {
+                Ok(()) => {
+                    self.stats.reconnects += 1;
+                    info!("🔌 Reconnected to {} (attempt {})", self.config.url, self.failures);
+                    return Ok(());
+                }
+                // Sampling is decided per session, so a declined one stays declined
+                Err(e @ RecorderError::Declined(_)) => return Err(e),
+                Err(e) => warn!("⚠️ Reconnection attempt {} failed: {}", self.failures, e),
+            }
+        }
+    }
+
+    /// Open a new connection and replay the current page state into it
+    async fn resume(&mut self) -> Result<(), RecorderError> {
+        // The previous connection's manifest still holds for the new one
+        let cached = self.connection.manifest.clone();
+        self.connection = Connection::open(&self.config, &cached).await?;
+        let mut batch = Batch::default();
+        for frame in self.state.prelude() {
+            // The handshake already sent the metadata
+            if matches!(frame, Frame::RecordingMetadata(_)) {
+                continue;
+            }
+            self.add(&mut batch, frame)?;
+            if batch.data.len() >= self.connection.batch_bytes {
+                self.write(&mut std::mem::take(&mut batch)).await?;
+            }
+        }
+        self.write(&mut batch).await
+    }
+
+    /// Close the socket and wait for the server to finish saving
+    async fn close(&mut self) {
+        let socket = &mut self.connection.socket;
+        if let Err(e) = socket.close(None).await {
+            debug!("Error closing WebSocket: {}", e);
+            return;
+        }
+        let drained = tokio::time::timeout(CLOSE_TIMEOUT, async {
+            while let Some(Ok(_)) = socket.next().await {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!("⚠️ Server did not acknowledge close within {:?}", CLOSE_TIMEOUT);
+        }
+    }
+}
+
+/// Resolves after `period` (the heartbeat interval), or never if heartbeats are disabled
+async fn idle(period: Option<Duration>) {
+    match period {
+        Some(period) => tokio::time::sleep(period).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn encode(frame: &Frame) -> std::io::Result<Vec<u8>> {
+    let mut writer = FrameWriter::new(Vec::new());
+    writer.write_frame(frame)?;
+    Ok(writer.into_inner())
+}
+
+async fn decode(data: &[u8]) -> Option<Frame> {
+    let mut reader = FrameReader::new(Cursor::new(data), false);
+    reader.read_frame().await.ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_delay_doubles_up_to_max() {
+        let policy = ReconnectPolicy {
+            max_attempts: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(5), Duration::from_secs(1));
+        assert_eq!(policy.delay(40), Duration::from_secs(1));
+    }
+}
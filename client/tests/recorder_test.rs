@@ -0,0 +1,345 @@
+use domcorder_client::{ReconnectPolicy, Recorder, RecorderConfig, RecorderError};
+use domcorder_proto::{Frame, FrameReader};
+use domcorder_server::asset_cache::local::LocalBinaryStore;
+use domcorder_server::asset_cache::sqlite::SqliteMetadataStore;
+use domcorder_server::auth::ApiKey;
+use domcorder_server::{AppState, DomcorderRouter, RecordingId, RouteGroup, StorageState};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const SAMPLE: &str = "../.sample_data/proto/file-basic.dcrr";
+
+fn create_state() -> (AppState, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_store = SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap();
+    let asset_store =
+        LocalBinaryStore::new(temp_dir.path().join("assets"), "http://localhost".to_string()).unwrap();
+    let state = StorageState::new(
+        temp_dir.path().to_path_buf(),
+        Box::new(metadata_store),
+        Box::new(asset_store),
+    )
+    .unwrap();
+    let state = Arc::new(state);
+    (state, temp_dir)
+}
+
+async fn serve(router: DomcorderRouter) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router.routes(RouteGroup::ALL);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+async fn sample_frames() -> Vec<Frame> {
+    let file = tokio::fs::File::open(SAMPLE).await.unwrap();
+    let mut reader = FrameReader::new(file, true);
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        // The recorder sends its own metadata during the handshake
+        if !matches!(frame, Frame::RecordingMetadata(_)) {
+            frames.push(frame);
+        }
+    }
+    frames
+}
+
+async fn read_recording(state: &AppState, filename: &str) -> Vec<Frame> {
+    let filename = RecordingId::new(filename).unwrap();
+    let mut reader = state.open_recording_reader(&filename).await.unwrap();
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        frames.push(frame);
+    }
+    frames
+}
+
+#[tokio::test]
+async fn test_recorder_streams_frames_to_server() {
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+
+    let config = RecorderConfig::new(format!("ws://{}/ws/record", addr), "https://example.com/")
+        .with_queue_capacity(2);
+    let recorder = Recorder::connect(config).await.unwrap();
+    let frames = sample_frames().await;
+    for frame in frames.clone() {
+        recorder.send(frame).await.unwrap();
+    }
+    let stats = recorder.finish().await.unwrap();
+
+    assert_eq!(stats.frames_sent, frames.len() as u64);
+    assert_eq!(stats.reconnects, 0);
+
+    let recordings = state.list_recordings(None).await.unwrap();
+    assert_eq!(recordings.len(), 1);
+    let stored = read_recording(&state, &recordings[0].filename).await;
+    assert!(matches!(
+        &stored[0],
+        Frame::RecordingMetadata(metadata) if metadata.initial_url == "https://example.com/"
+    ));
+    assert_eq!(stored.len(), frames.len() + 1);
+}
+
+#[tokio::test]
+async fn test_recorder_sends_cached_assets_as_references() {
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let url = format!("ws://{}/ws/record", addr);
+    let frames = sample_frames().await;
+    let asset_count = frames.iter().filter(|f| matches!(f, Frame::Asset(a) if !a.buf.is_empty())).count();
+    assert!(asset_count > 0);
+
+    // The second recording of the same site finds the assets in the manifest
+    let mut deduplicated = Vec::new();
+    for _ in 0..2 {
+        let recorder = Recorder::connect(RecorderConfig::new(url.clone(), "https://example.com/"))
+            .await
+            .unwrap();
+        for frame in frames.clone() {
+            recorder.send(frame).await.unwrap();
+        }
+        deduplicated.push(recorder.finish().await.unwrap().assets_deduplicated);
+    }
+    assert_eq!(deduplicated, vec![0, asset_count as u64]);
+
+    // A recorder that kept the hashes reports them instead of being sent them
+    // again, and still deduplicates every asset
+    let hashes = frames.iter().filter_map(|frame| match frame {
+        Frame::Asset(asset) if !asset.buf.is_empty() => Some(format!("{:x}", Sha256::digest(&asset.buf))),
+        _ => None,
+    });
+    let config = RecorderConfig::new(url.clone(), "https://example.com/").with_cached_assets(hashes);
+    let recorder = Recorder::connect(config).await.unwrap();
+    for frame in frames.clone() {
+        recorder.send(frame).await.unwrap();
+    }
+    assert_eq!(recorder.finish().await.unwrap().assets_deduplicated, asset_count as u64);
+}
+
+#[tokio::test]
+async fn test_recorder_uses_api_key() {
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state).with_auth(ApiKey::new("secret"))).await;
+    let config = RecorderConfig::new(format!("ws://{}/ws/record", addr), "https://example.com/")
+        .with_reconnect(ReconnectPolicy::never());
+
+    let rejected = Recorder::connect(config.clone()).await;
+    assert!(matches!(rejected, Err(RecorderError::WebSocket(_))));
+
+    let recorder = Recorder::connect(config.with_api_key("secret")).await.unwrap();
+    recorder.finish().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_recorder_negotiates_batch_size() {
+    use domcorder_server::recording_handler::MAX_BATCH_BYTES;
+
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let url = format!("ws://{}/ws/record", addr);
+    let frames = sample_frames().await;
+
+    // The server lowers an oversized request to its maximum
+    let config = RecorderConfig::new(url.clone(), "https://example.com/").with_batch_bytes(usize::MAX);
+    let recorder = Recorder::connect(config).await.unwrap();
+    assert_eq!(recorder.batch_bytes(), MAX_BATCH_BYTES);
+    for frame in frames.clone() {
+        recorder.send(frame).await.unwrap();
+    }
+    let batched = recorder.finish().await.unwrap();
+    assert_eq!(batched.frames_sent, frames.len() as u64);
+    assert!(batched.messages_sent <= batched.frames_sent);
+
+    // Batching can be turned off
+    let config = RecorderConfig::new(url, "https://example.com/").with_batch_bytes(0);
+    let recorder = Recorder::connect(config).await.unwrap();
+    assert_eq!(recorder.batch_bytes(), 0);
+    for frame in frames.clone() {
+        recorder.send(frame).await.unwrap();
+    }
+    let unbatched = recorder.finish().await.unwrap();
+    assert_eq!(unbatched.messages_sent, frames.len() as u64);
+
+    for recording in state.list_recordings(None).await.unwrap() {
+        assert_eq!(read_recording(&state, &recording.filename).await.len(), frames.len() + 1);
+    }
+}
+
+#[tokio::test]
+async fn test_recorder_answers_heartbeat_pings() {
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let config = RecorderConfig::new(format!("ws://{}/ws/record", addr), "https://example.com/")
+        .with_heartbeat_interval(1);
+    let recorder = Recorder::connect(config).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    recorder.finish().await.unwrap();
+
+    let recordings = state.list_recordings(None).await.unwrap();
+    let skew = state
+        .metadata_store
+        .get_clock_skew(&recordings[0].filename)
+        .await
+        .unwrap()
+        .expect("a pong was received");
+    // Both ends share a clock
+    assert!(skew.skew_ms.abs() <= skew.round_trip_ms as i64 / 2 + 1, "{:?}", skew);
+    // Pongs are not stored
+    assert_eq!(read_recording(&state, &recordings[0].filename).await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_unsampled_sessions_are_not_stored() {
+    use domcorder_server::policy::{OriginPolicy, UnsampledAction};
+
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let config = RecorderConfig::new(format!("ws://{}/ws/record", addr), "https://example.com/")
+        .with_reconnect(ReconnectPolicy::never());
+    let mut policy = OriginPolicy::new("https://example.com");
+    policy.sample_rate = 0.0;
+    state.metadata_store.set_origin_policy(&policy).await.unwrap();
+
+    let declined = Recorder::connect(config.clone()).await;
+    assert!(matches!(declined, Err(RecorderError::Declined(_))));
+
+    // Recorders that don't know StopRecording stream into the void
+    policy.unsampled = UnsampledAction::Discard;
+    state.metadata_store.set_origin_policy(&policy).await.unwrap();
+    let recorder = Recorder::connect(config).await.unwrap();
+    for frame in sample_frames().await {
+        recorder.send(frame).await.unwrap();
+    }
+    recorder.finish().await.unwrap();
+
+    assert!(state.list_recordings(None).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_viewers_co_watch_a_live_recording() {
+    use domcorder_proto::KeyPressedData;
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let config = RecorderConfig::new(format!("ws://{}/ws/record", addr), "https://example.com/");
+    let recorder = Recorder::connect(config).await.unwrap();
+    let frames = sample_frames().await;
+    for frame in frames.clone() {
+        recorder.send(frame).await.unwrap();
+    }
+    let filename = loop {
+        let recordings = state.list_recordings(None).await.unwrap();
+        if let Some(recording) = recordings.first() {
+            break recording.id.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+
+    let view_url = |viewer: &str| format!("ws://{}/ws/recording/{}?viewer={}", addr, filename, viewer);
+    let (mut agent, _) = tokio_tungstenite::connect_async(view_url("agent")).await.unwrap();
+    let (mut lead, _) = tokio_tungstenite::connect_async(view_url("lead")).await.unwrap();
+
+    // Frames recorded after the viewer joined arrive while the session is live
+    let marker = Frame::KeyPressed(KeyPressedData {
+        code: "F13".to_string(),
+        alt_key: false,
+        ctrl_key: false,
+        meta_key: false,
+        shift_key: false,
+    });
+    recorder.send(marker.clone()).await.unwrap();
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !received.contains(&marker) {
+            if let Message::Binary(data) = agent.next().await.unwrap().unwrap() {
+                let mut reader = FrameReader::new(&data[..], false);
+                received.push(reader.read_frame().await.unwrap().unwrap());
+            }
+        }
+    })
+    .await
+    .expect("the marker frame was played live");
+    assert!(matches!(&received[0], Frame::PlaybackConfig(config) if config.is_live));
+    // Each recorded frame is played once, in order
+    assert_eq!(received.len(), frames.len() + 3);
+
+    agent
+        .send(Message::Text(r#"{"type":"cursor","x":12,"y":34}"#.into()))
+        .await
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Message::Text(text) = lead.next().await.unwrap().unwrap()
+                && !text.contains("joined")
+            {
+                return text.to_string();
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(event, r#"{"type":"cursor","viewer":"agent","x":12.0,"y":34.0}"#);
+
+    recorder.finish().await.unwrap();
+    // Viewer events are not recorded
+    let stored = read_recording(&state, &filename).await;
+    assert_eq!(stored.len(), frames.len() + 2);
+}
+
+#[tokio::test]
+async fn test_dropped_connection_mid_frame_is_finalized() {
+    use domcorder_proto::{FrameWriter, RecordingMetadataData, RecordingTruncatedData, TimestampData};
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (state, _temp_dir) = create_state();
+    let addr = serve(DomcorderRouter::new(state.clone())).await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws/record", addr)).await.unwrap();
+
+    let encode = |frame: Frame| {
+        let mut data = Vec::new();
+        FrameWriter::new(&mut data).write_frame(&frame).unwrap();
+        data
+    };
+    let metadata = encode(Frame::RecordingMetadata(RecordingMetadataData {
+        initial_url: "https://example.com/".to_string(),
+        heartbeat_interval_seconds: 0,
+    }));
+    socket.send(Message::Binary(metadata.into())).await.unwrap();
+    // The cache manifest
+    socket.next().await.unwrap().unwrap();
+
+    let mut data = encode(Frame::Timestamp(TimestampData { timestamp: 1000 }));
+    let partial = encode(Frame::Timestamp(TimestampData { timestamp: 2000 }));
+    data.extend(&partial[..partial.len() - 3]);
+    socket.send(Message::Binary(data.into())).await.unwrap();
+    // Gone without a close handshake
+    drop(socket);
+
+    let id = loop {
+        let recordings = state.list_recordings(None).await.unwrap();
+        if let Some(recording) = recordings.first()
+            && !state.is_recording_active(&RecordingId::new(&recording.id).unwrap())
+        {
+            break recording.id.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    let frames = read_recording(&state, &id).await;
+    assert_eq!(frames.len(), 3);
+    assert_eq!(
+        frames[2],
+        Frame::RecordingTruncated(RecordingTruncatedData {
+            discarded_bytes: partial.len() as u64 - 3,
+        })
+    );
+    assert!(state.list_failed_recordings().await.unwrap().is_empty());
+}
@@ -9,7 +9,14 @@ export interface Recording {
   filename: string;
   size: number;
   created: string;
+  file_created: string;
   is_active: boolean;
+  site_origin?: string | null;
+  initial_url?: string | null;
+  duration_ms?: number | null;
+  frame_count?: number | null;
+  title?: string | null;
+  tags?: string[];
 }
 
 const GlobalStyle = createGlobalStyle`
@@ -0,0 +1,11 @@
+// When the server hosts the player itself (GET /play/{id}), it describes where
+// the API lives and which recording to open in <meta> tags on the page.
+function meta(name: string): string | null {
+    return document.querySelector<HTMLMetaElement>(`meta[name="${name}"]`)?.content ?? null;
+}
+
+// Base URL of the DomCorder API, without a trailing slash
+export const API_BASE = meta('domcorder-api-base') ?? 'http://localhost:8723';
+
+// Recording named in the /play/{id} URL, if any
+export const INITIAL_RECORDING_ID = meta('domcorder-recording');
@@ -2,6 +2,7 @@ import React, { useEffect, useRef, useState } from 'react';
 import styled, { keyframes } from 'styled-components';
 import { FrameChunkReader, PagePlayerComponent } from '@domcorder/browser-core';
 import { type Recording } from '../App';
+import { API_BASE } from '../api';
 
 
 interface PlayerWrapperProps {
@@ -182,10 +183,10 @@ export const PlayerWrapper: React.FC<PlayerWrapperProps> = ({ recording }) => {
             setError(null);
             setProgress(null);
 
-            console.debug('Loading recording:', recording.filename);
+            console.debug('Loading recording:', recording.id);
 
             // Fetch the recording data with streaming
-            const response = await fetch(`http://localhost:8723/recording/${recording.filename}`);
+            const response = await fetch(`${API_BASE}/recording/${encodeURIComponent(recording.id)}`);
 
             if (!response.ok) {
                 throw new Error(`Failed to load recording: ${response.status} ${response.statusText}`);
@@ -275,7 +276,7 @@ export const PlayerWrapper: React.FC<PlayerWrapperProps> = ({ recording }) => {
             <PlayerHeader>
                 {recording ? (
                     <RecordingInfo>
-                        <h3>🎬 {recording.filename}</h3>
+                        <h3>🎬 {recording.id}</h3>
                         <RecordingDetails>
                             <span>📁 {(recording.size / 1024 / 1024).toFixed(1)} MB</span>
                             <span>🕒 {new Date(recording.created).toLocaleString()}</span>
@@ -1,12 +1,20 @@
 import React, { useState, useEffect } from 'react';
 import styled from 'styled-components';
+import { API_BASE, INITIAL_RECORDING_ID } from '../api';
 
 interface Recording {
     id: string;
     filename: string;
     size: number;
     created: string;
+    file_created: string;
     is_active: boolean;
+    site_origin?: string | null;
+    initial_url?: string | null;
+    duration_ms?: number | null;
+    frame_count?: number | null;
+    title?: string | null;
+    tags?: string[];
 }
 
 interface RecordingsListProps {
@@ -179,13 +187,18 @@ export const RecordingsList: React.FC<RecordingsListProps> = ({
             setLoading(true);
             setError(null);
 
-            const response = await fetch('http://localhost:8723/recordings');
+            const response = await fetch(`${API_BASE}/recordings`);
             if (!response.ok) {
                 throw new Error(`Failed to fetch recordings: ${response.status}`);
             }
 
-            const data = await response.json();
+            const data: Recording[] = await response.json();
             setRecordings(data);
+
+            const initial = data.find((r) => r.id === INITIAL_RECORDING_ID);
+            if (initial && !selectedRecording) {
+                onRecordingSelect(initial);
+            }
         } catch (err) {
             setError(err instanceof Error ? err.message : 'Failed to load recordings');
             console.error('Error fetching recordings:', err);
@@ -202,6 +215,12 @@ export const RecordingsList: React.FC<RecordingsListProps> = ({
         return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + ' ' + sizes[i];
     };
 
+    const formatDuration = (ms: number): string => {
+        const seconds = Math.round(ms / 1000);
+        const minutes = Math.floor(seconds / 60);
+        return minutes > 0 ? `${minutes}m ${seconds % 60}s` : `${seconds}s`;
+    };
+
     const formatDate = (dateString: string): string => {
         const date = new Date(dateString);
         return date.toLocaleString();
@@ -267,12 +286,16 @@ export const RecordingsList: React.FC<RecordingsListProps> = ({
                                 onClick={() => onRecordingSelect(recording)}
                             >
                                 <RecordingInfo>
-                                    <RecordingFilename title={recording.filename}>
-                                        {recording.filename}
+                                    <RecordingFilename title={recording.initial_url ?? recording.id}>
+                                        {recording.title || recording.id}
                                     </RecordingFilename>
                                     <RecordingMeta>
                                         <span>📁 {formatFileSize(recording.size)}</span>
                                         <span>🕒 {formatDate(recording.created)}</span>
+                                        {recording.duration_ms != null && (
+                                            <span>⏱️ {formatDuration(recording.duration_ms)}</span>
+                                        )}
+                                        {recording.tags?.map((tag) => <span key={tag}>🏷️ {tag}</span>)}
                                         {recording.is_active && <span>🔴 Recording in progress</span>}
                                     </RecordingMeta>
                                 </RecordingInfo>
@@ -4,4 +4,7 @@ import react from '@vitejs/plugin-react'
 // https://vite.dev/config/
 export default defineConfig({
   plugins: [react()],
+  // Relative asset URLs so the build also works when the server embeds it
+  // under /play/ (see domcorder-server's player-ui feature)
+  base: './',
 })
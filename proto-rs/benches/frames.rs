@@ -0,0 +1,151 @@
+//! FrameWriter and FrameReader throughput over representative recordings
+//!
+//! Three synthetic corpora stress different parts of the hot path:
+//! mouse-heavy (many tiny frames), asset-heavy (few large frames) and
+//! keyframe-heavy (deep DOM trees). Each is encoded with `FrameWriter` and
+//! decoded with `SyncFrameReader` and the async `FrameReader`; the frame size
+//! histogram of each corpus is printed first so results can be read against
+//! its shape. Run with `cargo bench -p domcorder-proto --bench frames`, and see
+//! docs/benchmarks.md for the published baseline and how to compare against it.
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use domcorder_proto::stats::RecordingStats;
+use domcorder_proto::{FileHeader, Frame, FrameBuilder, FrameReader, FrameWriter, SyncFrameReader};
+use std::io::Cursor;
+
+/// A few minutes of pointer activity on one page
+fn mouse_heavy() -> Vec<Frame> {
+    let mut builder = FrameBuilder::new()
+        .metadata("https://example.com/")
+        .at(1_700_000_000_000)
+        .new_page()
+        .with_element("main#app", |main| main.text("Dashboard"));
+    for i in 0..2_000u32 {
+        let (x, y) = ((i * 37) % 1920, (i * 53) % 1080);
+        builder = builder.mouse_path(&[(x, y)], 16);
+        if i % 10 == 0 {
+            builder = builder.scroll(0, i % 4000).click(x, y);
+        }
+    }
+    builder.build()
+}
+
+/// A gallery page that inlines many large images
+fn asset_heavy() -> Vec<Frame> {
+    let mut builder = FrameBuilder::new().metadata("https://example.com/gallery").at(1_700_000_000_000).new_page();
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    for asset_id in 0..200u32 {
+        let size = (16 * 1024) << (asset_id % 5);
+        // Incompressible, like real image data
+        let buf: Vec<u8> = (0..size)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect();
+        let url = format!("https://cdn.example.com/{}.png", asset_id);
+        builder = builder.asset(asset_id, &url, "image/png", &buf).wait(5);
+    }
+    builder.build()
+}
+
+/// A single-page app that navigates often, with a large DOM per page
+fn keyframe_heavy() -> Vec<Frame> {
+    let mut builder = FrameBuilder::new().metadata("https://example.com/app").at(1_700_000_000_000);
+    for page in 0..50 {
+        builder = builder.new_page().title(&format!("Page {}", page)).with_element("div#root", |root| {
+            (0..100).fold(root, |root, row| {
+                root.with_element("div.row", |div| {
+                    div.attr("data-row", &row.to_string())
+                        .with_element("span.label", |span| span.text("Item name"))
+                        .with_element("a.link", |a| a.attr("href", "/items/123").text("Details"))
+                        .with_element("button.buy", |button| button.text("Add to cart"))
+                })
+            })
+        });
+        builder = builder.wait(2_000).click(100, 100);
+    }
+    builder.build()
+}
+
+fn encode(frames: &[Frame]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut writer = FrameWriter::new(&mut data);
+    writer.write_header(&FileHeader::with_timestamp(1_700_000_000_000)).unwrap();
+    for frame in frames {
+        writer.write_frame(frame).unwrap();
+    }
+    data
+}
+
+/// Print the corpus' frame count, size and frame size histogram
+fn describe(name: &str, frames: &[Frame], data: &[u8]) {
+    let mut reader = SyncFrameReader::new(Cursor::new(data), true);
+    let mut stats = RecordingStats::new();
+    let mut offset = reader.position();
+    while let Some(frame) = reader.read_frame().unwrap() {
+        stats.push(&frame, reader.position() - offset);
+        offset = reader.position();
+    }
+    println!("{}: {} frames, {} bytes", name, frames.len(), data.len());
+    for (largest, count) in stats.sizes.buckets() {
+        println!("  <= {:>8} B {:>8}", largest, count);
+    }
+}
+
+fn bench_corpus(c: &mut Criterion, name: &str, frames: Vec<Frame>) {
+    let data = encode(&frames);
+    describe(name, &frames, &data);
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("write", |b| {
+        b.iter_batched(
+            || Vec::with_capacity(data.len()),
+            |mut out| {
+                let mut writer = FrameWriter::new(&mut out);
+                writer.write_header(&FileHeader::with_timestamp(1_700_000_000_000)).unwrap();
+                for frame in &frames {
+                    writer.write_frame(frame).unwrap();
+                }
+                out
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("read_sync", |b| {
+        b.iter(|| {
+            let mut reader = SyncFrameReader::new(Cursor::new(&data[..]), true);
+            let mut count = 0;
+            while reader.read_frame().unwrap().is_some() {
+                count += 1;
+            }
+            count
+        })
+    });
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    group.bench_function("read_async", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let mut reader = FrameReader::new(&data[..], true);
+                let mut count = 0;
+                while reader.read_frame().await.unwrap().is_some() {
+                    count += 1;
+                }
+                count
+            })
+        })
+    });
+    group.finish();
+}
+
+fn frames(c: &mut Criterion) {
+    bench_corpus(c, "mouse_heavy", mouse_heavy());
+    bench_corpus(c, "asset_heavy", asset_heavy());
+    bench_corpus(c, "keyframe_heavy", keyframe_heavy());
+}
+
+criterion_group!(benches, frames);
+criterion_main!(benches);
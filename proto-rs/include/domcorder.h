@@ -0,0 +1,48 @@
+/*
+ * C ABI for DomCorder recordings (libdomcorder_proto, built with the `ffi`
+ * feature: cargo build --release -p domcorder-proto --features ffi).
+ *
+ * Frames cross the boundary as JSON in the shape `dcrr convert` writes, e.g.
+ * {"MouseMoved":{"x":150,"y":200}}. Functions that fail return NULL or -1;
+ * dc_last_error() then describes the failure on the calling thread.
+ */
+#ifndef DOMCORDER_H
+#define DOMCORDER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DcReader DcReader;
+typedef struct DcWriter DcWriter;
+
+/* A decoded frame; the strings stay valid until the next call on the reader */
+typedef struct DcFrame {
+    uint32_t frame_type;   /* numeric frame type, e.g. 4 for MouseMoved */
+    const char *type_name; /* e.g. "MouseMoved" */
+    const char *json;      /* the frame as JSON */
+} DcFrame;
+
+/* Message of the last failed call on this thread, or NULL */
+const char *dc_last_error(void);
+
+/* Open a .dcrr file or raw frame stream; NULL on error */
+DcReader *dc_reader_open(const char *path);
+/* 1 if a frame was read into *out, 0 at the end, -1 on error */
+int dc_reader_next(DcReader *reader, DcFrame *out);
+void dc_reader_free(DcReader *reader);
+
+/* Create a .dcrr file; created_at is ms since the epoch, 0 for now. NULL on error */
+DcWriter *dc_writer_create(const char *path, uint64_t created_at);
+/* Encode one frame given as JSON: 0 on success, -1 on error */
+int dc_writer_write_json(DcWriter *writer, const char *json);
+/* Flush and free the writer: 0 on success, -1 if the flush failed */
+int dc_writer_close(DcWriter *writer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DOMCORDER_H */
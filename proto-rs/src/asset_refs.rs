@@ -0,0 +1,151 @@
+use crate::frame::{Frame, TextOperationData};
+use crate::vdom::VNode;
+
+/// Find `asset:N` placeholders in an attribute value or CSS text
+///
+/// A placeholder must start the string or follow a quote, `(`, comma or
+/// whitespace, as in `src="asset:1"`, `url(asset:2)` or srcset lists.
+pub fn asset_references(text: &str, out: &mut Vec<u32>) {
+    for (_, _, asset_id) in placeholders(text) {
+        out.push(asset_id);
+    }
+}
+
+/// Replace the id of every `asset:N` placeholder in `text`
+pub fn rewrite_asset_references(text: &str, mut map: impl FnMut(u32) -> u32) -> String {
+    let mut rewritten = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, asset_id) in placeholders(text) {
+        rewritten.push_str(&text[last..start]);
+        rewritten.push_str(&map(asset_id).to_string());
+        last = end;
+    }
+    rewritten.push_str(&text[last..]);
+    rewritten
+}
+
+/// Byte range of each placeholder's id digits, with the parsed id
+fn placeholders(text: &str) -> Vec<(usize, usize, u32)> {
+    const PREFIX: &str = "asset:";
+    let mut found = Vec::new();
+    for (start, _) in text.match_indices(PREFIX) {
+        let bounded = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| matches!(c, '"' | '\'' | '(' | ',') || c.is_whitespace());
+        if !bounded {
+            continue;
+        }
+        let digits_start = start + PREFIX.len();
+        let rest = &text[digits_start..];
+        let digits_end = digits_start + rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if let Ok(asset_id) = text[digits_start..digits_end].parse() {
+            found.push((digits_start, digits_end, asset_id));
+        }
+    }
+    found
+}
+
+/// Call `f` with every string in a frame that may contain asset placeholders
+pub fn for_each_asset_text(frame: &Frame, f: &mut impl FnMut(&str)) {
+    match frame {
+        Frame::Keyframe(keyframe) => {
+            for sheet in &keyframe.document.adopted_style_sheets {
+                f(&sheet.text);
+            }
+            for child in &keyframe.document.children {
+                for_each_node_text(child, f);
+            }
+        }
+        Frame::KeyframeBegin(begin) => {
+            for sheet in &begin.adopted_style_sheets {
+                f(&sheet.text);
+            }
+        }
+        Frame::KeyframeChunk(chunk) => {
+            for node in &chunk.nodes {
+                for_each_node_text(node, f);
+            }
+        }
+        Frame::DomNodeAdded(added) => for_each_node_text(&added.node, f),
+        Frame::DomAttributeChanged(changed) => f(&changed.attribute_value),
+        Frame::DomTextChanged(changed) => {
+            for operation in &changed.operations {
+                if let TextOperationData::Insert(insert) = operation {
+                    f(&insert.text);
+                }
+            }
+        }
+        Frame::NewAdoptedStyleSheet(sheet) => f(&sheet.style_sheet.text),
+        Frame::StyleSheetRuleInserted(inserted) => f(&inserted.content),
+        Frame::StyleSheetReplaced(replaced) => f(&replaced.content),
+        _ => {}
+    }
+}
+
+fn for_each_node_text(node: &VNode, f: &mut impl FnMut(&str)) {
+    match node {
+        VNode::Element(element) => {
+            for (_, value) in &element.attrs {
+                f(value);
+            }
+            for child in &element.children {
+                for_each_node_text(child, f);
+            }
+        }
+        VNode::Text(text) => f(&text.content),
+        _ => {}
+    }
+}
+
+/// Mutable counterpart of [`for_each_asset_text`]
+pub fn for_each_asset_text_mut(frame: &mut Frame, f: &mut impl FnMut(&mut String)) {
+    match frame {
+        Frame::Keyframe(keyframe) => {
+            for sheet in &mut keyframe.document.adopted_style_sheets {
+                f(&mut sheet.text);
+            }
+            for child in &mut keyframe.document.children {
+                for_each_node_text_mut(child, f);
+            }
+        }
+        Frame::KeyframeBegin(begin) => {
+            for sheet in &mut begin.adopted_style_sheets {
+                f(&mut sheet.text);
+            }
+        }
+        Frame::KeyframeChunk(chunk) => {
+            for node in &mut chunk.nodes {
+                for_each_node_text_mut(node, f);
+            }
+        }
+        Frame::DomNodeAdded(added) => for_each_node_text_mut(&mut added.node, f),
+        Frame::DomAttributeChanged(changed) => f(&mut changed.attribute_value),
+        Frame::DomTextChanged(changed) => {
+            for operation in &mut changed.operations {
+                if let TextOperationData::Insert(insert) = operation {
+                    f(&mut insert.text);
+                }
+            }
+        }
+        Frame::NewAdoptedStyleSheet(sheet) => f(&mut sheet.style_sheet.text),
+        Frame::StyleSheetRuleInserted(inserted) => f(&mut inserted.content),
+        Frame::StyleSheetReplaced(replaced) => f(&mut replaced.content),
+        _ => {}
+    }
+}
+
+fn for_each_node_text_mut(node: &mut VNode, f: &mut impl FnMut(&mut String)) {
+    match node {
+        VNode::Element(element) => {
+            for (_, value) in &mut element.attrs {
+                f(value);
+            }
+            for child in &mut element.children {
+                for_each_node_text_mut(child, f);
+            }
+        }
+        VNode::Text(text) => f(&mut text.content),
+        _ => {}
+    }
+}
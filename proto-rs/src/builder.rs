@@ -0,0 +1,716 @@
+//! A builder for realistic frame sequences in tests and benchmarks
+//!
+//! ```
+//! use domcorder_proto::FrameBuilder;
+//!
+//! let frames = FrameBuilder::keyframe()
+//!     .title("Checkout")
+//!     .with_element("div#root", |root| {
+//!         root.with_element("button.buy", |button| button.text("Buy"))
+//!     })
+//!     .wait(100)
+//!     .mouse_path(&[(0, 0), (150, 200)], 16)
+//!     .click(150, 200)
+//!     .build();
+//! ```
+//!
+//! Node ids are handed out in creation order, which is the pre-order the
+//! recorder uses, so nested closures number a tree the same way a Keyframe
+//! from the browser would. Elements with an `id` attribute can be looked up
+//! with [`FrameBuilder::node_id`].
+
+use crate::frame::*;
+use crate::vdom::*;
+use std::collections::HashMap;
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+
+/// Largest distance between two interpolated [`FrameBuilder::mouse_path`] points
+const MOUSE_STEP_PX: f64 = 20.0;
+
+/// Hands out node ids and remembers which element carries each HTML id
+#[derive(Debug, Default)]
+struct NodeIds {
+    next: u32,
+    by_html_id: HashMap<String, u32>,
+}
+
+impl NodeIds {
+    fn next(&mut self) -> u32 {
+        let id = self.next;
+        self.next += 1;
+        id
+    }
+}
+
+/// Builds one element and its subtree; see [`FrameBuilder::with_element`]
+pub struct ElementBuilder<'a> {
+    ids: &'a mut NodeIds,
+    element: VElement,
+}
+
+impl<'a> ElementBuilder<'a> {
+    /// Start an element from a `tag#id.class.class` selector
+    fn new(ids: &'a mut NodeIds, selector: &str, ns: Option<String>) -> Self {
+        let id = ids.next();
+        let (tag, rest) = selector.split_at(selector.find(['#', '.']).unwrap_or(selector.len()));
+        let mut element = ElementBuilder {
+            ids,
+            element: VElement {
+                id,
+                tag: tag.to_string(),
+                // Children of an <svg> stay in its namespace
+                ns: ns.or_else(|| (tag == "svg").then(|| SVG_NS.to_string())),
+                attrs: vec![],
+                children: vec![],
+            },
+        };
+
+        let mut classes = Vec::new();
+        for part in selector_parts(rest) {
+            match part.split_at(1) {
+                ("#", html_id) => element = element.attr("id", html_id),
+                (_, class) => classes.push(class),
+            }
+        }
+        if !classes.is_empty() {
+            element = element.attr("class", &classes.join(" "));
+        }
+        element
+    }
+
+    /// Set an attribute, in the order given
+    pub fn attr(mut self, name: &str, value: &str) -> Self {
+        if name == "id" {
+            self.ids.by_html_id.insert(value.to_string(), self.element.id);
+        }
+        self.element.attrs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Put the element in a namespace (elements created under it inherit it)
+    pub fn ns(mut self, ns: &str) -> Self {
+        self.element.ns = Some(ns.to_string());
+        self
+    }
+
+    /// Append a child element
+    pub fn with_element(mut self, selector: &str, f: impl FnOnce(ElementBuilder<'_>) -> ElementBuilder<'_>) -> Self {
+        let child = f(ElementBuilder::new(self.ids, selector, self.element.ns.clone())).finish();
+        self.element.children.push(child);
+        self
+    }
+
+    /// Append a text node
+    pub fn text(mut self, content: &str) -> Self {
+        let node = text_node(self.ids, content);
+        self.element.children.push(node);
+        self
+    }
+
+    /// Append a comment node
+    pub fn comment(mut self, content: &str) -> Self {
+        let node = comment_node(self.ids, content);
+        self.element.children.push(node);
+        self
+    }
+
+    fn finish(self) -> VNode {
+        VNode::Element(self.element)
+    }
+}
+
+/// Builds the top level of a document; see [`FrameBuilder::keyframe_document`]
+pub struct DocumentBuilder<'a> {
+    ids: &'a mut NodeIds,
+    children: Vec<VNode>,
+}
+
+impl DocumentBuilder<'_> {
+    /// Append `<!DOCTYPE name>`
+    pub fn doctype(mut self, name: &str) -> Self {
+        let id = self.ids.next();
+        self.children.push(VNode::DocType(VDocumentType {
+            id,
+            name: name.to_string(),
+            public_id: None,
+            system_id: None,
+        }));
+        self
+    }
+
+    /// Append a top-level element (normally `html`)
+    pub fn with_element(mut self, selector: &str, f: impl FnOnce(ElementBuilder<'_>) -> ElementBuilder<'_>) -> Self {
+        let child = f(ElementBuilder::new(self.ids, selector, None)).finish();
+        self.children.push(child);
+        self
+    }
+
+    /// Append a top-level comment
+    pub fn comment(mut self, content: &str) -> Self {
+        let node = comment_node(self.ids, content);
+        self.children.push(node);
+        self
+    }
+}
+
+/// The `<!DOCTYPE html><html><head></head><body></body></html>` skeleton
+/// opened by [`FrameBuilder::keyframe`], filled in until the next frame
+#[derive(Debug)]
+struct OpenKeyframe {
+    document_id: u32,
+    doctype: VNode,
+    html_id: u32,
+    head: VElement,
+    body: VElement,
+}
+
+impl OpenKeyframe {
+    fn new(ids: &mut NodeIds) -> Self {
+        let document_id = ids.next();
+        let doctype = VNode::DocType(VDocumentType {
+            id: ids.next(),
+            name: "html".to_string(),
+            public_id: None,
+            system_id: None,
+        });
+        let html_id = ids.next();
+        let mut element = |tag: &str| VElement {
+            id: ids.next(),
+            tag: tag.to_string(),
+            ns: None,
+            attrs: vec![],
+            children: vec![],
+        };
+        let head = element("head");
+        let body = element("body");
+        Self {
+            document_id,
+            doctype,
+            html_id,
+            head,
+            body,
+        }
+    }
+
+    fn into_document(self) -> VDocument {
+        VDocument {
+            id: self.document_id,
+            adopted_style_sheets: vec![],
+            children: vec![
+                self.doctype,
+                VNode::Element(VElement {
+                    id: self.html_id,
+                    tag: "html".to_string(),
+                    ns: None,
+                    attrs: vec![],
+                    children: vec![VNode::Element(self.head), VNode::Element(self.body)],
+                }),
+            ],
+        }
+    }
+}
+
+/// Builds a sequence of frames; see the module docs
+///
+/// Timestamps are only written by [`at`](Self::at) and [`wait`](Self::wait)
+/// (and by [`mouse_path`](Self::mouse_path) when given an interval), so a
+/// sequence contains exactly the frames asked for.
+#[derive(Debug)]
+pub struct FrameBuilder {
+    frames: Vec<Frame>,
+    ids: NodeIds,
+    open: Option<OpenKeyframe>,
+    viewport: (u32, u32),
+    now: u64,
+    mouse: (u32, u32),
+}
+
+impl Default for FrameBuilder {
+    fn default() -> Self {
+        Self {
+            frames: vec![],
+            ids: NodeIds::default(),
+            open: None,
+            viewport: (1920, 1080),
+            now: 0,
+            mouse: (0, 0),
+        }
+    }
+}
+
+impl FrameBuilder {
+    /// An empty sequence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sequence starting with a Keyframe of an empty HTML page
+    ///
+    /// The page is filled with [`with_element`](Self::with_element),
+    /// [`title`](Self::title) and friends; the Keyframe is written when the
+    /// next frame is added.
+    pub fn keyframe() -> Self {
+        Self::new().new_page()
+    }
+
+    /// Start a Keyframe of a new, empty HTML page (e.g. after a navigation)
+    pub fn new_page(mut self) -> Self {
+        self.flush();
+        self.open = Some(OpenKeyframe::new(&mut self.ids));
+        self
+    }
+
+    /// Write a Keyframe of a document built node by node, for fixtures that
+    /// need the exact tree (whitespace text, comments, a custom `<head>`)
+    pub fn keyframe_document(mut self, f: impl FnOnce(DocumentBuilder<'_>) -> DocumentBuilder<'_>) -> Self {
+        self.flush();
+        let id = self.ids.next();
+        let children = f(DocumentBuilder {
+            ids: &mut self.ids,
+            children: vec![],
+        })
+        .children;
+        let document = VDocument {
+            id,
+            adopted_style_sheets: vec![],
+            children,
+        };
+        self.push_keyframe(document);
+        self
+    }
+
+    /// Viewport size for the following Keyframes (default 1920x1080)
+    pub fn viewport(mut self, width: u32, height: u32) -> Self {
+        self.viewport = (width, height);
+        self
+    }
+
+    /// Append an element to the open page's `<body>`
+    pub fn with_element(mut self, selector: &str, f: impl FnOnce(ElementBuilder<'_>) -> ElementBuilder<'_>) -> Self {
+        let open = self.open.as_mut().expect("with_element() needs a page from keyframe() or new_page()");
+        let child = f(ElementBuilder::new(&mut self.ids, selector, None)).finish();
+        open.body.children.push(child);
+        self
+    }
+
+    /// Append an element to the open page's `<head>`
+    pub fn with_head_element(
+        mut self,
+        selector: &str,
+        f: impl FnOnce(ElementBuilder<'_>) -> ElementBuilder<'_>,
+    ) -> Self {
+        let open = self.open.as_mut().expect("with_head_element() needs a page from keyframe() or new_page()");
+        let child = f(ElementBuilder::new(&mut self.ids, selector, None)).finish();
+        open.head.children.push(child);
+        self
+    }
+
+    /// Add a `<title>` to the open page
+    pub fn title(self, title: &str) -> Self {
+        self.with_head_element("title", |e| e.text(title))
+    }
+
+    /// Append a text node to the open page's `<body>`
+    pub fn with_text(mut self, content: &str) -> Self {
+        let open = self.open.as_mut().expect("with_text() needs a page from keyframe() or new_page()");
+        open.body.children.push(text_node(&mut self.ids, content));
+        self
+    }
+
+    /// Append a comment to the open page's `<body>`
+    pub fn with_comment(mut self, content: &str) -> Self {
+        let open = self.open.as_mut().expect("with_comment() needs a page from keyframe() or new_page()");
+        open.body.children.push(comment_node(&mut self.ids, content));
+        self
+    }
+
+    /// Number the following nodes from `next`, e.g. to match a fixture
+    pub fn node_ids_from(mut self, next: u32) -> Self {
+        self.ids.next = next;
+        self
+    }
+
+    /// Node id of the element created with HTML id `html_id` (`"root"` or `"#root"`)
+    ///
+    /// Panics if there is none, which in a test is a typo.
+    pub fn node_id(&self, html_id: &str) -> u32 {
+        let html_id = html_id.strip_prefix('#').unwrap_or(html_id);
+        *self
+            .ids
+            .by_html_id
+            .get(html_id)
+            .unwrap_or_else(|| panic!("no element with id {:?}", html_id))
+    }
+
+    /// Write a Timestamp frame for `timestamp` (ms since the epoch)
+    pub fn at(mut self, timestamp: u64) -> Self {
+        self.now = timestamp;
+        self.frame(Frame::Timestamp(TimestampData { timestamp }))
+    }
+
+    /// Write a Timestamp frame `ms` after the previous one
+    pub fn wait(self, ms: u64) -> Self {
+        let timestamp = self.now + ms;
+        self.at(timestamp)
+    }
+
+    /// Append any frame
+    pub fn frame(mut self, frame: Frame) -> Self {
+        self.flush();
+        self.frames.push(frame);
+        self
+    }
+
+    pub fn metadata(self, initial_url: &str) -> Self {
+        self.frame(Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: initial_url.to_string(),
+            heartbeat_interval_seconds: 0,
+        }))
+    }
+
+    /// An inline asset the recorder fetched successfully
+    pub fn asset(self, asset_id: u32, url: &str, mime: &str, buf: &[u8]) -> Self {
+        self.frame(Frame::Asset(AssetData {
+            asset_id,
+            url: url.to_string(),
+            mime: Some(mime.to_string()),
+            buf: buf.to_vec(),
+            fetch_error: AssetFetchError::None,
+        }))
+    }
+
+    /// Write a ViewportResized frame; later Keyframes use the new size
+    pub fn resize(mut self, width: u32, height: u32) -> Self {
+        self.viewport = (width, height);
+        self.frame(Frame::ViewportResized(ViewportResizedData { width, height }))
+    }
+
+    pub fn scroll(self, x: u32, y: u32) -> Self {
+        self.frame(Frame::ScrollOffsetChanged(ScrollOffsetChangedData {
+            scroll_x_offset: x,
+            scroll_y_offset: y,
+        }))
+    }
+
+    pub fn mouse_move(mut self, x: u32, y: u32) -> Self {
+        self.mouse = (x, y);
+        self.frame(Frame::MouseMoved(MouseMovedData { x, y }))
+    }
+
+    /// Move the mouse along `points`, in steps of at most 20px like a real
+    /// pointer, starting from the last position
+    ///
+    /// With a non-zero `interval_ms` each step after the first is preceded by
+    /// a [`wait`](Self::wait) of that long.
+    pub fn mouse_path(mut self, points: &[(u32, u32)], interval_ms: u64) -> Self {
+        let mut first = true;
+        for &(x, y) in points {
+            let (from_x, from_y) = self.mouse;
+            let (dx, dy) = (x as f64 - from_x as f64, y as f64 - from_y as f64);
+            let steps = ((dx.hypot(dy) / MOUSE_STEP_PX).ceil() as u32).max(1);
+            for step in 1..=steps {
+                let t = step as f64 / steps as f64;
+                let point = (
+                    (from_x as f64 + dx * t).round() as u32,
+                    (from_y as f64 + dy * t).round() as u32,
+                );
+                if !first && interval_ms > 0 {
+                    self = self.wait(interval_ms);
+                }
+                first = false;
+                self = self.mouse_move(point.0, point.1);
+            }
+        }
+        self
+    }
+
+    pub fn click(mut self, x: u32, y: u32) -> Self {
+        self.mouse = (x, y);
+        self.frame(Frame::MouseClicked(MouseClickedData { x, y }))
+    }
+
+    /// A key press with no modifiers, by `KeyboardEvent.code` (e.g. "Enter")
+    pub fn key(self, code: &str) -> Self {
+        self.frame(Frame::KeyPressed(KeyPressedData {
+            code: code.to_string(),
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+            shift_key: false,
+        }))
+    }
+
+    /// One KeyPressed frame per character of `text`, as a US keyboard would
+    /// report it
+    pub fn type_text(mut self, text: &str) -> Self {
+        for c in text.chars() {
+            let code = match c {
+                'a'..='z' | 'A'..='Z' => format!("Key{}", c.to_ascii_uppercase()),
+                '0'..='9' => format!("Digit{}", c),
+                ' ' => "Space".to_string(),
+                '\n' => "Enter".to_string(),
+                _ => c.to_string(),
+            };
+            self = self.frame(Frame::KeyPressed(KeyPressedData {
+                code,
+                alt_key: false,
+                ctrl_key: false,
+                meta_key: false,
+                shift_key: c.is_ascii_uppercase(),
+            }));
+        }
+        self
+    }
+
+    pub fn focus(self, node_id: u32) -> Self {
+        self.frame(Frame::ElementFocused(ElementFocusedData { node_id }))
+    }
+
+    pub fn blur(self, node_id: u32) -> Self {
+        self.frame(Frame::ElementBlurred(ElementBlurredData { node_id }))
+    }
+
+    pub fn window_focused(self) -> Self {
+        self.frame(Frame::WindowFocused(WindowFocusedData {}))
+    }
+
+    pub fn window_blurred(self) -> Self {
+        self.frame(Frame::WindowBlurred(WindowBlurredData {}))
+    }
+
+    /// Insert a new element as child `index` of `parent_node_id`
+    pub fn add_node(
+        mut self,
+        parent_node_id: u32,
+        index: u32,
+        selector: &str,
+        f: impl FnOnce(ElementBuilder<'_>) -> ElementBuilder<'_>,
+    ) -> Self {
+        let node = f(ElementBuilder::new(&mut self.ids, selector, None)).finish();
+        self.frame(Frame::DomNodeAdded(DomNodeAddedData {
+            parent_node_id,
+            index,
+            node,
+        }))
+    }
+
+    pub fn remove_node(self, node_id: u32) -> Self {
+        self.frame(Frame::DomNodeRemoved(DomNodeRemovedData { node_id }))
+    }
+
+    pub fn set_attribute(self, node_id: u32, name: &str, value: &str) -> Self {
+        self.frame(Frame::DomAttributeChanged(DomAttributeChangedData {
+            node_id,
+            attribute_name: name.to_string(),
+            attribute_value: value.to_string(),
+        }))
+    }
+
+    pub fn remove_attribute(self, node_id: u32, name: &str) -> Self {
+        self.frame(Frame::DomAttributeRemoved(DomAttributeRemovedData {
+            node_id,
+            attribute_name: name.to_string(),
+        }))
+    }
+
+    /// Replace `length` characters at `index` of a text node with `text`
+    pub fn replace_text(self, node_id: u32, index: u32, length: u32, text: &str) -> Self {
+        let mut operations = vec![];
+        if length > 0 {
+            operations.push(TextOperationData::Remove(TextRemoveOperationData { index, length }));
+        }
+        if !text.is_empty() {
+            operations.push(TextOperationData::Insert(TextInsertOperationData {
+                index,
+                text: text.to_string(),
+            }));
+        }
+        self.frame(Frame::DomTextChanged(DomTextChangedData { node_id, operations }))
+    }
+
+    pub fn resize_node(self, node_id: u32, width: u32, height: u32) -> Self {
+        self.frame(Frame::DomNodeResized(DomNodeResizedData { node_id, width, height }))
+    }
+
+    /// Select from `start_offset` in `start_node_id` to `end_offset` in `end_node_id`
+    pub fn select(self, start_node_id: u32, start_offset: u32, end_node_id: u32, end_offset: u32) -> Self {
+        self.frame(Frame::TextSelectionChanged(TextSelectionChangedData {
+            selection_start_node_id: start_node_id,
+            selection_start_offset: start_offset,
+            selection_end_node_id: end_node_id,
+            selection_end_offset: end_offset,
+        }))
+    }
+
+    pub fn element_scrolled(self, node_id: u32, x: u32, y: u32) -> Self {
+        self.frame(Frame::ElementScrolled(ElementScrolledData {
+            node_id,
+            scroll_x_offset: x,
+            scroll_y_offset: y,
+        }))
+    }
+
+    pub fn adopted_style_sheets(self, style_sheet_ids: &[u32], added_count: u32) -> Self {
+        self.frame(Frame::AdoptedStyleSheetsChanged(AdoptedStyleSheetsChangedData {
+            style_sheet_ids: style_sheet_ids.to_vec(),
+            added_count,
+        }))
+    }
+
+    pub fn new_style_sheet(self, id: u32, text: &str, media: Option<&str>) -> Self {
+        self.frame(Frame::NewAdoptedStyleSheet(NewAdoptedStyleSheetData {
+            style_sheet: VStyleSheet {
+                id,
+                text: text.to_string(),
+                media: media.map(str::to_string),
+            },
+        }))
+    }
+
+    pub fn annotate(self, name: &str, data: Option<&str>) -> Self {
+        self.frame(Frame::Annotation(AnnotationData {
+            name: name.to_string(),
+            data: data.map(str::to_string),
+        }))
+    }
+
+    /// The frames, writing the open Keyframe if nothing followed it
+    pub fn build(mut self) -> Vec<Frame> {
+        self.flush();
+        self.frames
+    }
+
+    fn flush(&mut self) {
+        if let Some(open) = self.open.take() {
+            self.push_keyframe(open.into_document());
+        }
+    }
+
+    fn push_keyframe(&mut self, document: VDocument) {
+        self.frames.push(Frame::Keyframe(KeyframeData {
+            document,
+            viewport_width: self.viewport.0,
+            viewport_height: self.viewport.1,
+        }));
+    }
+}
+
+fn text_node(ids: &mut NodeIds, content: &str) -> VNode {
+    VNode::Text(VTextNode {
+        id: ids.next(),
+        content: content.to_string(),
+    })
+}
+
+fn comment_node(ids: &mut NodeIds, content: &str) -> VNode {
+    VNode::Comment(VComment {
+        id: ids.next(),
+        content: content.to_string(),
+    })
+}
+
+/// Split the `#id.class.class` part of a selector into `["#id", ".class", ".class"]`
+fn selector_parts(rest: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in rest.char_indices().skip(1) {
+        if c == '#' || c == '.' {
+            parts.push(&rest[start..i]);
+            start = i;
+        }
+    }
+    if start < rest.len() {
+        parts.push(&rest[start..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VDomEngine;
+
+    #[test]
+    fn test_keyframe_numbers_nodes_in_pre_order() {
+        let builder = FrameBuilder::keyframe()
+            .title("Test")
+            .with_element("div#root.app.dark", |root| root.with_element("p", |p| p.text("Hello")));
+        let root = builder.node_id("#root");
+        let frames = builder.build();
+
+        let Frame::Keyframe(keyframe) = &frames[0] else {
+            panic!("expected a keyframe, got {:?}", frames[0]);
+        };
+        // document 0, doctype 1, html 2, head 3, body 4, title 5, its text 6
+        assert_eq!(root, 7);
+        let VNode::Element(html) = &keyframe.document.children[1] else {
+            panic!("expected <html>");
+        };
+        let VNode::Element(body) = &html.children[1] else {
+            panic!("expected <body>");
+        };
+        let VNode::Element(div) = &body.children[0] else {
+            panic!("expected <div>");
+        };
+        assert_eq!(div.id, root);
+        assert_eq!(
+            div.attrs,
+            vec![
+                ("id".to_string(), "root".to_string()),
+                ("class".to_string(), "app dark".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequence_applies_to_vdom() {
+        let builder = FrameBuilder::keyframe().with_element("ul#list", |ul| ul.with_element("li", |li| li.text("one")));
+        let list = builder.node_id("list");
+        let frames = builder
+            .at(1_000)
+            .add_node(list, 1, "li.new", |li| li.text("two"))
+            .wait(50)
+            .set_attribute(list, "class", "done")
+            .build();
+
+        let mut engine = VDomEngine::new();
+        for frame in &frames {
+            engine.apply(frame).unwrap();
+        }
+        assert!(matches!(frames[1], Frame::Timestamp(TimestampData { timestamp: 1_000 })));
+        assert!(matches!(frames[3], Frame::Timestamp(TimestampData { timestamp: 1_050 })));
+    }
+
+    #[test]
+    fn test_mouse_path_interpolates_steps() {
+        let frames = FrameBuilder::new().at(0).mouse_path(&[(0, 0), (100, 0)], 10).build();
+        let moves: Vec<_> = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::MouseMoved(m) => Some((m.x, m.y)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(moves, vec![(0, 0), (20, 0), (40, 0), (60, 0), (80, 0), (100, 0)]);
+        assert_eq!(frames.last(), Some(&Frame::MouseMoved(MouseMovedData { x: 100, y: 0 })));
+        assert!(matches!(frames[frames.len() - 2], Frame::Timestamp(TimestampData { timestamp: 50 })));
+    }
+
+    #[test]
+    fn test_svg_children_inherit_namespace() {
+        let frames = FrameBuilder::new()
+            .add_node(1, 0, "svg", |svg| svg.with_element("circle", |c| c.attr("r", "4")))
+            .build();
+        let Frame::DomNodeAdded(added) = &frames[0] else {
+            panic!("expected DomNodeAdded");
+        };
+        let VNode::Element(svg) = &added.node else {
+            panic!("expected <svg>");
+        };
+        let VNode::Element(circle) = &svg.children[0] else {
+            panic!("expected <circle>");
+        };
+        assert_eq!(circle.ns.as_deref(), Some(SVG_NS));
+    }
+}
@@ -0,0 +1,81 @@
+//! Per-frame CRC32 checksums
+//!
+//! A recording tailed or streamed while it is written can be cut off or
+//! damaged mid-write, and a damaged frame may still decode, as the wrong
+//! frame. A file whose header has [`HEADER_FLAG_FRAME_CHECKSUM`] set in its
+//! first reserved byte (see [`FileHeader::with_frame_checksums`]) ends each
+//! frame with the CRC32 (IEEE, big-endian) of the bytes between the length
+//! prefix and the checksum: the flag byte, if any, and the payload. The
+//! length prefix covers the checksum.
+//!
+//! Both readers check each frame before decoding it and fail with an
+//! `InvalidData` error carrying a [`ChecksumMismatch`] (see
+//! [`ChecksumMismatch::find`]). Streams without a header have checksums only
+//! if the reader is told so (`with_frame_checksums`).
+//!
+//! [`FileHeader::with_frame_checksums`]: crate::writer::FileHeader::with_frame_checksums
+
+use std::fmt;
+use std::io;
+
+/// Set in the first reserved header byte when frames end with a CRC32
+pub const HEADER_FLAG_FRAME_CHECKSUM: u8 = 0x02;
+
+/// Bytes of checksum at the end of each frame
+pub const CHECKSUM_SIZE: usize = 4;
+
+/// A frame whose contents don't match its checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    /// The checksum written with the frame
+    pub expected: u32,
+    /// The checksum of the frame as read
+    pub actual: u32,
+}
+
+impl ChecksumMismatch {
+    /// The mismatch `error` reports, if it is one
+    pub fn find(error: &io::Error) -> Option<&ChecksumMismatch> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Frame checksum mismatch: expected {:08x}, got {:08x}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+impl From<ChecksumMismatch> for io::Error {
+    fn from(mismatch: ChecksumMismatch) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, mismatch)
+    }
+}
+
+/// The checksum of a frame made of `parts` (flag byte and payload)
+pub(crate) fn checksum(parts: &[&[u8]]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
+
+/// The frame data before its checksum, once the checksum matches
+pub(crate) fn verify(frame_data: &[u8]) -> io::Result<&[u8]> {
+    let Some((data, expected)) = frame_data.split_last_chunk::<CHECKSUM_SIZE>() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Frame is shorter than its checksum"));
+    };
+    let expected = u32::from_be_bytes(*expected);
+    let actual = checksum(&[data]);
+    if actual != expected {
+        return Err(ChecksumMismatch { expected, actual }.into());
+    }
+    Ok(data)
+}
@@ -0,0 +1,105 @@
+//! Per-frame zstd compression
+//!
+//! Keyframes and CanvasChanged frames are large and compress very well. A
+//! file whose header has [`HEADER_FLAG_FRAME_COMPRESSION`] set in its first
+//! reserved byte (see [`FileHeader::with_frame_compression`]) starts each
+//! frame, after the length prefix, with a flag byte: [`FRAME_FLAG_ZSTD`] if
+//! the rest is a zstd frame of the bincode payload, 0 if it is the payload
+//! as is. The length prefix covers the flag byte.
+//!
+//! [`FrameWriter`](crate::FrameWriter) compresses frames of at least
+//! [`COMPRESSION_MIN_BYTES`] when that makes them smaller, and both readers
+//! decompress them, refusing any that would expand past
+//! [`MAX_FRAME_BYTES`]. Streams without a header never have flag bytes.
+//! Without the `zstd` feature, frames are always written as they are, and
+//! compressed ones can't be read.
+//!
+//! [`FileHeader::with_frame_compression`]: crate::writer::FileHeader::with_frame_compression
+
+use std::borrow::Cow;
+use std::io;
+
+/// Set in the first reserved header byte when frames start with a flag byte
+pub const HEADER_FLAG_FRAME_COMPRESSION: u8 = 0x01;
+
+/// Frame flag: the payload is zstd-compressed
+pub const FRAME_FLAG_ZSTD: u8 = 0x01;
+
+/// Smaller payloads are written as they are
+pub const COMPRESSION_MIN_BYTES: usize = 512;
+
+/// zstd level used unless the writer is given another
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Largest payload a compressed frame may decompress to, so a small frame
+/// can't expand into gigabytes
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// The flag byte and body of a frame with this payload
+pub(crate) fn compress(payload: Vec<u8>, level: i32) -> io::Result<(u8, Vec<u8>)> {
+    #[cfg(feature = "zstd")]
+    if payload.len() >= COMPRESSION_MIN_BYTES {
+        let compressed = zstd::bulk::compress(&payload, level)?;
+        if compressed.len() < payload.len() {
+            return Ok((FRAME_FLAG_ZSTD, compressed));
+        }
+    }
+    #[cfg(not(feature = "zstd"))]
+    let _ = level;
+    Ok((0, payload))
+}
+
+/// The payload of a frame starting with a flag byte
+pub(crate) fn decompress(frame_data: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    let Some((&flag, body)) = frame_data.split_first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Frame is missing its flag byte"));
+    };
+    match flag {
+        0 => Ok(Cow::Borrowed(body)),
+        #[cfg(feature = "zstd")]
+        FRAME_FLAG_ZSTD => decompress_bounded(body, MAX_FRAME_BYTES).map(Cow::Owned),
+        #[cfg(not(feature = "zstd"))]
+        FRAME_FLAG_ZSTD => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Frame is zstd-compressed, which needs the zstd feature",
+        )),
+        flag => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown frame flags: {:#04x}", flag),
+        )),
+    }
+}
+
+/// Decompress a zstd frame, failing once it expands past `limit` bytes
+#[cfg(feature = "zstd")]
+fn decompress_bounded(body: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let invalid = |e: io::Error| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to decompress frame: {}", e));
+    let mut payload = Vec::new();
+    zstd::stream::read::Decoder::new(body)
+        .map_err(invalid)?
+        .take(limit as u64 + 1)
+        .read_to_end(&mut payload)
+        .map_err(invalid)?;
+    if payload.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Compressed frame expands past {} bytes", limit),
+        ));
+    }
+    Ok(payload)
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompression_is_bounded() {
+        let body = zstd::bulk::compress(&vec![0u8; 4096], DEFAULT_COMPRESSION_LEVEL).unwrap();
+        assert_eq!(decompress_bounded(&body, 4096).unwrap().len(), 4096);
+        let error = decompress_bounded(&body, 4095).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
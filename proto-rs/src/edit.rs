@@ -0,0 +1,248 @@
+use crate::asset_refs::{for_each_asset_text_mut, rewrite_asset_references};
+use crate::frame::*;
+use crate::vdom_engine::VDomEngine;
+use std::collections::BTreeMap;
+
+/// Tracks everything needed to start playback mid-recording
+///
+/// [`RecordingState::prelude`] produces frames that reproduce the current page
+/// state: a synthesized Keyframe from the replayed DOM, node properties,
+/// every asset seen so far, and the last scroll offset.
+#[derive(Debug, Default)]
+pub struct RecordingState {
+    engine: VDomEngine,
+    metadata: Option<Frame>,
+    assets: BTreeMap<u32, Frame>,
+    scroll: Option<Frame>,
+    first_timestamp: Option<u64>,
+    last_timestamp: Option<u64>,
+}
+
+impl RecordingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Milliseconds between the first and the most recent Timestamp frame
+    pub fn offset_ms(&self) -> u64 {
+        match (self.first_timestamp, self.last_timestamp) {
+            (Some(first), Some(last)) => last.saturating_sub(first),
+            _ => 0,
+        }
+    }
+
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.last_timestamp
+    }
+
+    /// Update the state with the next frame
+    ///
+    /// DOM errors are ignored: the state is best-effort, like the player.
+    pub fn push(&mut self, frame: &Frame) {
+        match frame {
+            Frame::Timestamp(ts) => {
+                self.first_timestamp.get_or_insert(ts.timestamp);
+                self.last_timestamp = Some(ts.timestamp);
+            }
+            Frame::RecordingMetadata(_) => {
+                self.metadata.get_or_insert_with(|| frame.clone());
+            }
+            Frame::Asset(asset) => {
+                self.assets.insert(asset.asset_id, frame.clone());
+            }
+            Frame::AssetReference(asset) => {
+                self.assets.insert(asset.asset_id, frame.clone());
+            }
+            Frame::ScrollOffsetChanged(_) | Frame::ScrollOffsetChangedV2(_) => self.scroll = Some(frame.clone()),
+            Frame::Keyframe(_) | Frame::KeyframeBegin(_) => self.scroll = None,
+            _ => {}
+        }
+        let _ = self.engine.apply(frame);
+    }
+
+    /// Frames that recreate the current state as the start of a new recording
+    pub fn prelude(&self) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        frames.extend(self.metadata.clone());
+        if let Some(timestamp) = self.last_timestamp {
+            frames.push(Frame::Timestamp(TimestampData { timestamp }));
+        }
+        if let (Some(document), Some((width, height))) = (self.engine.document(), self.engine.viewport()) {
+            frames.push(Frame::Keyframe(KeyframeData {
+                document,
+                viewport_width: width,
+                viewport_height: height,
+            }));
+            for (node_id, name, value) in self.engine.properties() {
+                frames.push(Frame::DomNodePropertyChanged(DomNodePropertyChangedData {
+                    node_id,
+                    property_name: name.to_string(),
+                    property_value: value.to_string(),
+                }));
+            }
+        }
+        frames.extend(self.assets.values().cloned());
+        frames.extend(self.scroll.clone());
+        frames
+    }
+}
+
+/// Keeps the frames within a time range, relative to the first Timestamp
+///
+/// If the range starts after the beginning, the output begins with a
+/// [`RecordingState::prelude`] so it plays back on its own.
+#[derive(Debug)]
+pub struct Trimmer {
+    state: RecordingState,
+    start_ms: u64,
+    end_ms: Option<u64>,
+    started: bool,
+    finished: bool,
+}
+
+impl Trimmer {
+    pub fn new(start_ms: u64, end_ms: Option<u64>) -> Self {
+        Self {
+            state: RecordingState::new(),
+            start_ms,
+            end_ms,
+            started: start_ms == 0,
+            finished: false,
+        }
+    }
+
+    /// Whether the end of the range has been passed (remaining input can be skipped)
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Feed the next frame, returning the frames to write
+    pub fn push(&mut self, frame: Frame) -> Vec<Frame> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.state.push(&frame);
+
+        let is_timestamp = matches!(frame, Frame::Timestamp(_));
+        if is_timestamp && self.end_ms.is_some_and(|end| self.state.offset_ms() > end) {
+            self.finished = true;
+            return Vec::new();
+        }
+        if self.started {
+            return vec![frame];
+        }
+        if is_timestamp && self.state.offset_ms() >= self.start_ms {
+            self.started = true;
+            return self.state.prelude();
+        }
+        Vec::new()
+    }
+}
+
+/// Where [`Splitter`] starts a new chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    /// At every Keyframe after the first (i.e. at each page load)
+    Keyframes,
+    /// At the first Timestamp at least this many ms after the chunk started
+    EveryMs(u64),
+}
+
+/// Output of [`Splitter::push`]
+#[derive(Debug, Default)]
+pub struct SplitStep {
+    /// The frames belong to a new chunk; close the current one first
+    pub new_chunk: bool,
+    pub frames: Vec<Frame>,
+}
+
+/// Splits a recording into self-contained chunks
+#[derive(Debug)]
+pub struct Splitter {
+    state: RecordingState,
+    mode: SplitMode,
+    chunk_start_ms: u64,
+    seen_keyframe: bool,
+}
+
+impl Splitter {
+    pub fn new(mode: SplitMode) -> Self {
+        Self {
+            state: RecordingState::new(),
+            mode,
+            chunk_start_ms: 0,
+            seen_keyframe: false,
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame) -> SplitStep {
+        self.state.push(&frame);
+
+        let boundary = match (&frame, self.mode) {
+            // The prelude's keyframe holds the empty document of a
+            // KeyframeBegin; the chunks that follow fill it in
+            (Frame::Keyframe(_) | Frame::KeyframeBegin(_), SplitMode::Keyframes) => {
+                std::mem::replace(&mut self.seen_keyframe, true)
+            }
+            (Frame::Timestamp(_), SplitMode::EveryMs(interval)) => {
+                self.state.offset_ms() >= self.chunk_start_ms + interval.max(1)
+            }
+            _ => false,
+        };
+        if !boundary {
+            return SplitStep {
+                new_chunk: false,
+                frames: vec![frame],
+            };
+        }
+
+        self.chunk_start_ms = self.state.offset_ms();
+        SplitStep {
+            new_chunk: true,
+            frames: self.state.prelude(),
+        }
+    }
+}
+
+/// Renumbers asset ids so recordings can be concatenated without collisions
+#[derive(Debug, Default)]
+pub struct AssetIdRemapper {
+    offset: u32,
+    max_seen: Option<u32>,
+}
+
+impl AssetIdRemapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the next recording: its ids are shifted past every id written so far
+    pub fn next_recording(&mut self) {
+        if let Some(max) = self.max_seen {
+            self.offset = max + 1;
+        }
+    }
+
+    /// Rewrite asset ids (in asset frames and `asset:N` placeholders) in place
+    pub fn remap(&mut self, frame: &mut Frame) {
+        let offset = self.offset;
+        let mut max_seen = self.max_seen;
+        let mut shift = |id: u32| {
+            let shifted = id + offset;
+            max_seen = Some(max_seen.map_or(shifted, |max| max.max(shifted)));
+            shifted
+        };
+
+        match frame {
+            Frame::Asset(asset) => asset.asset_id = shift(asset.asset_id),
+            Frame::AssetReference(asset) => asset.asset_id = shift(asset.asset_id),
+            _ => {}
+        }
+        for_each_asset_text_mut(frame, &mut |text: &mut String| {
+            if text.contains("asset:") {
+                *text = rewrite_asset_references(text, &mut shift);
+            }
+        });
+        self.max_seen = max_seen;
+    }
+}
@@ -0,0 +1,302 @@
+//! C ABI for reading and writing recordings
+//!
+//! For recorders that are not written in Rust (desktop apps, game engines)
+//! and want to emit or consume .dcrr files directly. The declarations are in
+//! `include/domcorder.h`. Frames cross the boundary as JSON in the shape
+//! `dcrr convert` writes, tagged with their numeric type and name.
+//!
+//! Functions that can fail return NULL or -1 and leave a message for
+//! [`dc_last_error`] on the calling thread.
+
+use crate::Frame;
+use crate::reader::SyncFrameReader;
+use crate::writer::{DCRR_MAGIC, FileHeader, FrameWriter};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl ToString) {
+    // Interior NULs would truncate the message; replace them
+    let message = message.to_string().replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Opaque reader handle
+pub struct DcReader {
+    reader: SyncFrameReader<BufReader<File>>,
+    /// Backing storage for the last frame's strings
+    type_name: CString,
+    json: CString,
+}
+
+/// Opaque writer handle
+pub struct DcWriter {
+    writer: FrameWriter<BufWriter<File>>,
+}
+
+/// A decoded frame
+///
+/// The strings stay valid until the next call on the same reader.
+#[repr(C)]
+pub struct DcFrame {
+    /// Numeric frame type (see `Frame::type_code`)
+    pub frame_type: u32,
+    /// Frame type name, e.g. "MouseMoved"
+    pub type_name: *const c_char,
+    /// The frame as JSON, e.g. `{"MouseMoved":{"x":1,"y":2}}`
+    pub json: *const c_char,
+}
+
+/// A `&str` from a C string argument, recording an error if it is unusable
+///
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Option<&'a str> {
+    if s.is_null() {
+        set_error(format!("{} is NULL", what));
+        return None;
+    }
+    // SAFETY: non-NULL and NUL-terminated per the caller's contract
+    match unsafe { CStr::from_ptr(s) }.to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error(format!("{} is not valid UTF-8", what));
+            None
+        }
+    }
+}
+
+/// The message of the last failed call on this thread, or NULL
+///
+/// Valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn dc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Open a .dcrr file or raw frame stream (the header is detected); NULL on error
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_reader_open(path: *const c_char) -> *mut DcReader {
+    // SAFETY: forwarded from the caller
+    let Some(path) = (unsafe { str_arg(path, "path") }) else {
+        return ptr::null_mut();
+    };
+    let open = || -> io::Result<SyncFrameReader<BufReader<File>>> {
+        let mut magic = [0u8; 4];
+        let has_header = File::open(path)?.read_exact(&mut magic).is_ok() && magic == DCRR_MAGIC;
+        let mut reader = SyncFrameReader::new(BufReader::new(File::open(path)?), has_header);
+        if has_header {
+            reader.read_header()?;
+        }
+        Ok(reader)
+    };
+    match open() {
+        Ok(reader) => Box::into_raw(Box::new(DcReader {
+            reader,
+            type_name: CString::default(),
+            json: CString::default(),
+        })),
+        Err(e) => {
+            set_error(format!("{}: {}", path, e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Decode the next frame into `out`: 1 if a frame was read, 0 at the end, -1 on error
+///
+/// # Safety
+/// `reader` must come from [`dc_reader_open`] and not be freed; `out` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_reader_next(reader: *mut DcReader, out: *mut DcFrame) -> c_int {
+    if reader.is_null() || out.is_null() {
+        set_error("reader or out is NULL");
+        return -1;
+    }
+    // SAFETY: a live handle from dc_reader_open, per the caller's contract
+    let reader = unsafe { &mut *reader };
+    let frame = match reader.reader.read_frame() {
+        Ok(Some(frame)) => frame,
+        Ok(None) => return 0,
+        Err(e) => {
+            set_error(format!("frame at byte offset {}: {}", reader.reader.position(), e));
+            return -1;
+        }
+    };
+    let json = serde_json::to_string(&frame).map_err(|e| e.to_string());
+    match json.and_then(|json| CString::new(json).map_err(|e| e.to_string())) {
+        Ok(json) => {
+            reader.json = json;
+            reader.type_name = CString::new(frame.type_name()).expect("type names have no NUL");
+        }
+        Err(e) => {
+            set_error(e);
+            return -1;
+        }
+    }
+    // SAFETY: checked non-NULL above; the caller guarantees it is writable
+    unsafe {
+        out.write(DcFrame {
+            frame_type: frame.type_code(),
+            type_name: reader.type_name.as_ptr(),
+            json: reader.json.as_ptr(),
+        });
+    }
+    1
+}
+
+/// Close a reader (NULL is ignored)
+///
+/// # Safety
+/// `reader` must be NULL or come from [`dc_reader_open`], and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_reader_free(reader: *mut DcReader) {
+    if !reader.is_null() {
+        // SAFETY: created by Box::into_raw in dc_reader_open
+        drop(unsafe { Box::from_raw(reader) });
+    }
+}
+
+/// Create a .dcrr file; `created_at` is ms since the epoch (0 for now). NULL on error.
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_writer_create(path: *const c_char, created_at: u64) -> *mut DcWriter {
+    // SAFETY: forwarded from the caller
+    let Some(path) = (unsafe { str_arg(path, "path") }) else {
+        return ptr::null_mut();
+    };
+    let create = || -> io::Result<FrameWriter<BufWriter<File>>> {
+        let mut writer = FrameWriter::new(BufWriter::new(File::create(path)?));
+        let header = match created_at {
+            0 => FileHeader::new(),
+            created_at => FileHeader::with_timestamp(created_at),
+        };
+        writer.write_header(&header)?;
+        Ok(writer)
+    };
+    match create() {
+        Ok(writer) => Box::into_raw(Box::new(DcWriter { writer })),
+        Err(e) => {
+            set_error(format!("{}: {}", path, e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Encode a frame given as JSON: 0 on success, -1 on error
+///
+/// # Safety
+/// `writer` must come from [`dc_writer_create`] and not be closed; `json` must
+/// be a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_writer_write_json(writer: *mut DcWriter, json: *const c_char) -> c_int {
+    if writer.is_null() {
+        set_error("writer is NULL");
+        return -1;
+    }
+    // SAFETY: forwarded from the caller
+    let Some(json) = (unsafe { str_arg(json, "json") }) else {
+        return -1;
+    };
+    let frame: Frame = match serde_json::from_str(json) {
+        Ok(frame) => frame,
+        Err(e) => {
+            set_error(format!("invalid frame JSON: {}", e));
+            return -1;
+        }
+    };
+    // SAFETY: a live handle from dc_writer_create, per the caller's contract
+    match unsafe { &mut *writer }.writer.write_frame(&frame) {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+/// Flush and close a writer: 0 on success, -1 if the final flush failed
+///
+/// The handle is freed either way; NULL is ignored.
+///
+/// # Safety
+/// `writer` must be NULL or come from [`dc_writer_create`], and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dc_writer_close(writer: *mut DcWriter) -> c_int {
+    if writer.is_null() {
+        return 0;
+    }
+    // SAFETY: created by Box::into_raw in dc_writer_create
+    let mut writer = unsafe { Box::from_raw(writer) };
+    match writer.writer.flush() {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuilder;
+
+    #[test]
+    fn test_write_then_read_through_the_c_abi() {
+        let path = std::env::temp_dir().join(format!("domcorder-ffi-{}.dcrr", std::process::id()));
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let frames = FrameBuilder::keyframe().at(1_000).click(3, 4).key("Enter").build();
+
+        unsafe {
+            let writer = dc_writer_create(path.as_ptr(), 1_722_550_000_000);
+            assert!(!writer.is_null());
+            for frame in &frames {
+                let json = CString::new(serde_json::to_string(frame).unwrap()).unwrap();
+                assert_eq!(dc_writer_write_json(writer, json.as_ptr()), 0);
+            }
+            assert_eq!(dc_writer_write_json(writer, c"{\"Nope\":{}}".as_ptr()), -1);
+            assert!(CStr::from_ptr(dc_last_error()).to_str().unwrap().starts_with("invalid frame JSON"));
+            assert_eq!(dc_writer_close(writer), 0);
+
+            let reader = dc_reader_open(path.as_ptr());
+            assert!(!reader.is_null());
+            let mut out = DcFrame {
+                frame_type: 0,
+                type_name: ptr::null(),
+                json: ptr::null(),
+            };
+            let mut read = Vec::new();
+            while dc_reader_next(reader, &mut out) == 1 {
+                let frame: Frame = serde_json::from_str(CStr::from_ptr(out.json).to_str().unwrap()).unwrap();
+                assert_eq!(out.frame_type, frame.type_code());
+                assert_eq!(CStr::from_ptr(out.type_name).to_str().unwrap(), frame.type_name());
+                read.push(frame);
+            }
+            dc_reader_free(reader);
+            assert_eq!(read, frames);
+        }
+        std::fs::remove_file(path.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_missing_file_sets_error() {
+        unsafe {
+            assert!(dc_reader_open(c"/nonexistent/recording.dcrr".as_ptr()).is_null());
+            assert!(!dc_last_error().is_null());
+            assert!(dc_reader_open(ptr::null()).is_null());
+            assert_eq!(CStr::from_ptr(dc_last_error()).to_str().unwrap(), "path is NULL");
+        }
+    }
+}
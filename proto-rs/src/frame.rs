@@ -1,8 +1,9 @@
 use crate::vdom::{VDocument, VNode, VStyleSheet};
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 
 /// Frame types - each frame is its own struct
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[repr(u32)]
 pub enum Frame {
     Timestamp(TimestampData) = 0,
@@ -43,6 +44,170 @@ pub enum Frame {
     CacheManifest(CacheManifestData) = 30,
     PlaybackConfig(PlaybackConfigData) = 31,
     Heartbeat = 32,
+    PageError(PageErrorData) = 33,
+    Annotation(AnnotationData) = 34,
+    TraceContext(TraceContextData) = 35,
+    RecordingPaused(RecordingPausedData) = 36,
+    RecordingResumed = 37,
+
+    // Signed scroll offsets and fractional pointer positions; see `Frame::upgrade`
+    ScrollOffsetChangedV2(ScrollOffsetChangedV2Data) = 38,
+    MouseMovedV2(MouseMovedV2Data) = 39,
+    MouseClickedV2(MouseClickedV2Data) = 40,
+    ElementScrolledV2(ElementScrolledV2Data) = 41,
+
+    // A keyframe split into frames, for documents too large to buffer whole;
+    // see `keyframe_chunks`
+    KeyframeBegin(KeyframeBeginData) = 42,
+    KeyframeChunk(KeyframeChunkData) = 43,
+    KeyframeEnd = 44,
+
+    // Stylesheet text stored once in the server's asset cache
+    NewAdoptedStyleSheetReference(NewAdoptedStyleSheetReferenceData) = 45,
+    StyleSheetReplacedReference(StyleSheetReplacedReferenceData) = 46,
+
+    CanvasChangedDelta(CanvasChangedDeltaData) = 47,
+
+    // Round trips between the server and the recorder, for latency and
+    // clock skew
+    HeartbeatPing(HeartbeatPingData) = 48,
+    HeartbeatPong(HeartbeatPongData) = 49,
+
+    SessionIdentity(SessionIdentityData) = 50,
+
+    // Sent by the server to a recorder whose session it won't store
+    StopRecording(StopRecordingData) = 51,
+
+    // Written by the server in place of a long span without activity
+    IdlePeriod(IdlePeriodData) = 52,
+
+    // Written by the server where a recording's stream ended part way through a frame
+    RecordingTruncated(RecordingTruncatedData) = 53,
+
+    // Sent by the recorder before RecordingMetadata: the assets it already
+    // knows the server has, so the manifest can leave them out
+    CacheInventory(CacheInventoryData) = 54,
+
+    // Sent by the server to a live recorder whose stream no longer matches
+    // the page it recorded
+    RequestKeyframe(RequestKeyframeData) = 55,
+
+    // Sent by a recorder that records one element of the page, right after
+    // RecordingMetadata
+    RecordingScope(RecordingScopeData) = 56,
+
+    // Links the recording to the other tabs and windows of the same session
+    SessionLink(SessionLinkData) = 57,
+}
+
+impl Frame {
+    /// The numeric frame type: the enum discriminant, which is also the
+    /// variant index on the wire
+    pub fn type_code(&self) -> u32 {
+        // SAFETY: a `#[repr(u32)]` enum starts with its u32 discriminant
+        unsafe { *(self as *const Self as *const u32) }
+    }
+
+    /// The variant name, for logging and reporting
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Frame::Timestamp(_) => "Timestamp",
+            Frame::Keyframe(_) => "Keyframe",
+            Frame::ViewportResized(_) => "ViewportResized",
+            Frame::ScrollOffsetChanged(_) => "ScrollOffsetChanged",
+            Frame::MouseMoved(_) => "MouseMoved",
+            Frame::MouseClicked(_) => "MouseClicked",
+            Frame::KeyPressed(_) => "KeyPressed",
+            Frame::ElementFocused(_) => "ElementFocused",
+            Frame::TextSelectionChanged(_) => "TextSelectionChanged",
+            Frame::DomNodeAdded(_) => "DomNodeAdded",
+            Frame::DomNodeRemoved(_) => "DomNodeRemoved",
+            Frame::DomAttributeChanged(_) => "DomAttributeChanged",
+            Frame::DomAttributeRemoved(_) => "DomAttributeRemoved",
+            Frame::DomTextChanged(_) => "DomTextChanged",
+            Frame::DomNodeResized(_) => "DomNodeResized",
+            Frame::DomNodePropertyChanged(_) => "DomNodePropertyChanged",
+            Frame::Asset(_) => "Asset",
+            Frame::AdoptedStyleSheetsChanged(_) => "AdoptedStyleSheetsChanged",
+            Frame::NewAdoptedStyleSheet(_) => "NewAdoptedStyleSheet",
+            Frame::ElementScrolled(_) => "ElementScrolled",
+            Frame::ElementBlurred(_) => "ElementBlurred",
+            Frame::WindowFocused(_) => "WindowFocused",
+            Frame::WindowBlurred(_) => "WindowBlurred",
+            Frame::StyleSheetRuleInserted(_) => "StyleSheetRuleInserted",
+            Frame::StyleSheetRuleDeleted(_) => "StyleSheetRuleDeleted",
+            Frame::StyleSheetReplaced(_) => "StyleSheetReplaced",
+            Frame::CanvasChanged(_) => "CanvasChanged",
+            Frame::DomNodePropertyTextChanged(_) => "DomNodePropertyTextChanged",
+            Frame::RecordingMetadata(_) => "RecordingMetadata",
+            Frame::AssetReference(_) => "AssetReference",
+            Frame::CacheManifest(_) => "CacheManifest",
+            Frame::PlaybackConfig(_) => "PlaybackConfig",
+            Frame::Heartbeat => "Heartbeat",
+            Frame::PageError(_) => "PageError",
+            Frame::Annotation(_) => "Annotation",
+            Frame::TraceContext(_) => "TraceContext",
+            Frame::RecordingPaused(_) => "RecordingPaused",
+            Frame::RecordingResumed => "RecordingResumed",
+            Frame::ScrollOffsetChangedV2(_) => "ScrollOffsetChangedV2",
+            Frame::MouseMovedV2(_) => "MouseMovedV2",
+            Frame::MouseClickedV2(_) => "MouseClickedV2",
+            Frame::ElementScrolledV2(_) => "ElementScrolledV2",
+            Frame::KeyframeBegin(_) => "KeyframeBegin",
+            Frame::KeyframeChunk(_) => "KeyframeChunk",
+            Frame::KeyframeEnd => "KeyframeEnd",
+            Frame::NewAdoptedStyleSheetReference(_) => "NewAdoptedStyleSheetReference",
+            Frame::StyleSheetReplacedReference(_) => "StyleSheetReplacedReference",
+            Frame::CanvasChangedDelta(_) => "CanvasChangedDelta",
+            Frame::HeartbeatPing(_) => "HeartbeatPing",
+            Frame::HeartbeatPong(_) => "HeartbeatPong",
+            Frame::SessionIdentity(_) => "SessionIdentity",
+            Frame::StopRecording(_) => "StopRecording",
+            Frame::IdlePeriod(_) => "IdlePeriod",
+            Frame::RecordingTruncated(_) => "RecordingTruncated",
+            Frame::CacheInventory(_) => "CacheInventory",
+            Frame::RequestKeyframe(_) => "RequestKeyframe",
+            Frame::RecordingScope(_) => "RecordingScope",
+            Frame::SessionLink(_) => "SessionLink",
+        }
+    }
+
+    /// Bytes the frame takes in a stream, its length prefix included
+    pub fn encoded_size(&self) -> u64 {
+        bincode::DefaultOptions::new()
+            .with_big_endian()
+            .with_fixint_encoding()
+            .serialized_size(self)
+            .map_or(u64::MAX, |size| size + 4)
+    }
+
+    /// Replace a legacy scroll or mouse frame with its V2 equivalent; other
+    /// frames are returned unchanged
+    ///
+    /// Old recorders wrote negative scroll offsets (RTL pages, overscroll) as
+    /// wrapped u32s, so offsets are reinterpreted as i32 rather than clamped.
+    pub fn upgrade(self) -> Frame {
+        match self {
+            Frame::ScrollOffsetChanged(data) => Frame::ScrollOffsetChangedV2(ScrollOffsetChangedV2Data {
+                scroll_x_offset: data.scroll_x_offset as i32,
+                scroll_y_offset: data.scroll_y_offset as i32,
+            }),
+            Frame::MouseMoved(data) => Frame::MouseMovedV2(MouseMovedV2Data {
+                x: data.x as f32,
+                y: data.y as f32,
+            }),
+            Frame::MouseClicked(data) => Frame::MouseClickedV2(MouseClickedV2Data {
+                x: data.x as f32,
+                y: data.y as f32,
+            }),
+            Frame::ElementScrolled(data) => Frame::ElementScrolledV2(ElementScrolledV2Data {
+                node_id: data.node_id,
+                scroll_x_offset: data.scroll_x_offset as i32,
+                scroll_y_offset: data.scroll_y_offset as i32,
+            }),
+            frame => frame,
+        }
+    }
 }
 
 /// Frame data structures corresponding to TypeScript frame data types
@@ -249,6 +414,25 @@ pub struct CanvasChangedData {
     pub data: Vec<u8>,
 }
 
+/// The regions of a canvas redrawn since its previous CanvasChanged or
+/// CanvasChangedDelta frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanvasChangedDeltaData {
+    pub node_id: u32,
+    pub patches: Vec<CanvasPatchData>,
+}
+
+/// An image that replaces the pixels of one rect of the previous snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanvasPatchData {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DomNodePropertyTextChangedData {
     pub node_id: u32,
@@ -303,4 +487,201 @@ pub struct PlaybackConfigData {
     pub is_live: bool,
     /// The latest timestamp in the recording (None if not live)
     pub latest_timestamp: Option<u64>,
+    /// The selector of the element the recording is scoped to (None if it
+    /// records the whole page)
+    pub root_selector: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageErrorData {
+    /// The error message (e.g. from `window.onerror` or an unhandled rejection)
+    pub message: String,
+    /// The script URL the error originated from, if known
+    pub source_url: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub stack: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnotationData {
+    /// Application-defined label (e.g. "checkout:started")
+    pub name: String,
+    /// Optional JSON payload supplied by the recorder API
+    pub data: Option<String>,
+}
+
+/// Links the recording to a distributed trace from this point on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContextData {
+    /// W3C `traceparent` header value (e.g. "00-<trace-id>-<span-id>-01")
+    pub traceparent: String,
+    /// W3C `tracestate` header value, if any
+    pub tracestate: Option<String>,
+}
+
+/// Capture stopped (e.g. on a sensitive screen) until the next RecordingResumed
+///
+/// The time between the two is a gap: players skip it and analytics do not
+/// count it towards the session's duration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingPausedData {
+    /// Why the recorder paused, if it says (e.g. "payment-form")
+    pub reason: Option<String>,
+}
+
+/// Window scroll offset; negative on RTL pages and during overscroll
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollOffsetChangedV2Data {
+    #[serde(rename = "scrollXOffset")]
+    pub scroll_x_offset: i32,
+    #[serde(rename = "scrollYOffset")]
+    pub scroll_y_offset: i32,
+}
+
+/// Pointer position in CSS pixels, fractional at non-integer devicePixelRatio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MouseMovedV2Data {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MouseClickedV2Data {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementScrolledV2Data {
+    pub node_id: u32,
+    #[serde(rename = "scrollXOffset")]
+    pub scroll_x_offset: i32,
+    #[serde(rename = "scrollYOffset")]
+    pub scroll_y_offset: i32,
+}
+
+/// Starts a chunked keyframe: a new, empty document
+///
+/// KeyframeChunk frames fill it in and KeyframeEnd completes it. Together
+/// they are equivalent to one Keyframe.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyframeBeginData {
+    pub document_id: u32,
+    pub adopted_style_sheets: Vec<VStyleSheet>,
+    pub viewport_width: u32,
+    pub viewport_height: u32,
+}
+
+/// Subtrees appended, in order, to the children of a node sent earlier in
+/// the same keyframe (or the document)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyframeChunkData {
+    pub parent_node_id: u32,
+    pub nodes: Vec<VNode>,
+}
+
+/// A NewAdoptedStyleSheet whose text was moved to the asset cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewAdoptedStyleSheetReferenceData {
+    pub id: u32,
+    pub media: Option<String>,
+    /// The cached text's hash (random_id when stored in a recording)
+    pub hash: String,
+}
+
+/// A StyleSheetReplaced whose content was moved to the asset cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyleSheetReplacedReferenceData {
+    pub style_sheet_id: u32,
+    /// The cached content's hash (random_id when stored in a recording)
+    pub hash: String,
+}
+
+/// Sent by the server to the recorder, which answers with a HeartbeatPong
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatPingData {
+    /// Server time the ping was sent (ms since the epoch)
+    pub server_timestamp: u64,
+}
+
+/// The recorder's answer to a HeartbeatPing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatPongData {
+    /// The ping's `server_timestamp`, echoed back
+    pub server_timestamp: u64,
+    /// Recorder time the pong was sent (ms since the epoch)
+    pub client_timestamp: u64,
+}
+
+/// Who the recorded session belongs to, set by the recorder's host page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionIdentityData {
+    /// Identifies the browser across sessions without identifying the person
+    pub anonymous_id: String,
+    /// The host application's id for the signed-in user
+    pub user_id: Option<String>,
+    /// Hex SHA-256 of the user's trimmed, lowercased email address
+    pub email_hash: Option<String>,
+}
+
+/// Sent by the server at the handshake when it won't store the session (e.g.
+/// it was not sampled); the recorder stops and closes the connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopRecordingData {
+    pub reason: String,
+}
+
+/// A span of `duration_ms` in which only timestamps, pointer movement and
+/// heartbeats were recorded, collapsed at ingestion
+///
+/// It is followed by the last pointer position and timestamp of the span.
+/// Players skip the time, as for a pause.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdlePeriodData {
+    pub duration_ms: u64,
+}
+
+/// The recorder's stream ended in the middle of a frame (e.g. its connection
+/// dropped), so the `discarded_bytes` received of that frame were not stored
+///
+/// It is the last frame of the recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingTruncatedData {
+    pub discarded_bytes: u64,
+}
+
+/// SHA-256 hashes of the assets the recorder kept from earlier manifests for
+/// the site; the server answers with a CacheManifest of the others
+///
+/// It is part of the handshake and is not stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheInventoryData {
+    pub sha256_hashes: Vec<String>,
+}
+
+/// Sent by the server during a live recording when the frames stop making
+/// sense against the document they build (e.g. a mutation refers to a node
+/// it doesn't have); the recorder answers with a fresh Keyframe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestKeyframeData {
+    pub reason: String,
+}
+
+/// Scopes a recording to the element matching `root_selector` (e.g. a widget
+/// embedded in a third-party page): its keyframes hold that element as the
+/// document's only child, and its mutations stay inside it
+///
+/// A separate frame rather than a RecordingMetadata field, so recordings and
+/// recorders from before scoping still decode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingScopeData {
+    pub root_selector: String,
+}
+
+/// The user session the recording belongs to, shared by the recordings of
+/// the session's other tabs and windows so they can be played together
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLinkData {
+    pub session_id: String,
 }
@@ -0,0 +1,169 @@
+//! Seek index of DCRR v2 files
+//!
+//! A v2 file may end with a trailer indexing its keyframes, so a reader on a
+//! seekable source can start at a point in time without decoding every frame
+//! before it. The trailer follows the last frame:
+//!
+//! - [`INDEX_MARKER`] in place of a frame length prefix, which ends the frames
+//! - the number of entries (u32), then each entry's timestamp, byte offset in
+//!   the file and number of frames before it (u64 each)
+//! - the byte offset of the marker (u64) and [`INDEX_MAGIC`]
+//!
+//! All integers are big-endian. A file still being written, or whose writer
+//! went away, has no trailer, and neither do v1 files. Readers stop at the
+//! marker after a v2 header, or in a stream without a header if told to
+//! expect an index; anywhere else it is an invalid frame length.
+
+use crate::Frame;
+
+/// Written in place of a frame length prefix where the frames end and the
+/// index begins
+pub const INDEX_MARKER: u32 = u32::MAX;
+
+/// Last bytes of a file with an index: "DCRI"
+pub const INDEX_MAGIC: [u8; 4] = [0x44, 0x43, 0x52, 0x49];
+
+/// The marker's offset and [`INDEX_MAGIC`], at the very end of the file
+pub const INDEX_FOOTER_SIZE: u64 = 12;
+
+const ENTRY_SIZE: usize = 24;
+
+/// A keyframe playback can start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The latest Timestamp before the keyframe, or the first after it for a
+    /// keyframe written before any
+    pub timestamp: u64,
+    /// Byte offset of the keyframe's length prefix in the file
+    pub offset: u64,
+    /// Frames before the keyframe
+    pub frame_index: u64,
+}
+
+/// The keyframes of a file, and where its frames end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameIndex {
+    pub entries: Vec<IndexEntry>,
+    /// Byte offset of the [`INDEX_MARKER`]
+    pub frames_end: u64,
+}
+
+impl FrameIndex {
+    /// The last keyframe at or before `timestamp`, or the first keyframe if
+    /// they are all later
+    pub fn entry_at(&self, timestamp: u64) -> Option<IndexEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.timestamp <= timestamp)
+            .or(self.entries.first())
+            .copied()
+    }
+
+    /// Parse the trailer from the marker up to the footer; None if it is
+    /// not an index
+    pub(crate) fn parse(trailer: &[u8], frames_end: u64) -> Option<Self> {
+        let u64_at = |at: usize| u64::from_be_bytes(trailer[at..at + 8].try_into().unwrap());
+        if trailer.len() < 8 || trailer[0..4] != INDEX_MARKER.to_be_bytes() {
+            return None;
+        }
+        let count = u32::from_be_bytes(trailer[4..8].try_into().unwrap()) as usize;
+        if trailer.len() != 8 + count * ENTRY_SIZE {
+            return None;
+        }
+        let entries = (0..count)
+            .map(|i| 8 + i * ENTRY_SIZE)
+            .map(|at| IndexEntry {
+                timestamp: u64_at(at),
+                offset: u64_at(at + 8),
+                frame_index: u64_at(at + 16),
+            })
+            .collect();
+        Some(Self { entries, frames_end })
+    }
+}
+
+/// The offset of the [`INDEX_MARKER`] named by a file's last
+/// [`INDEX_FOOTER_SIZE`] bytes; None if the file has no index
+pub(crate) fn parse_footer(footer: &[u8; INDEX_FOOTER_SIZE as usize]) -> Option<u64> {
+    (footer[8..12] == INDEX_MAGIC).then(|| u64::from_be_bytes(footer[0..8].try_into().unwrap()))
+}
+
+/// Collects the index of a file as its frames are written
+#[derive(Debug, Default)]
+pub(crate) struct IndexBuilder {
+    entries: Vec<IndexEntry>,
+    timestamp: Option<u64>,
+    /// Keyframes written before the first Timestamp, which take its time
+    untimed: usize,
+    frames: u64,
+}
+
+impl IndexBuilder {
+    /// Note a frame about to be written at byte `offset`
+    pub fn push(&mut self, frame: &Frame, offset: u64) {
+        match frame {
+            Frame::Timestamp(data) => {
+                if self.timestamp.is_none() {
+                    for entry in &mut self.entries[..self.untimed] {
+                        entry.timestamp = data.timestamp;
+                    }
+                }
+                self.timestamp = Some(data.timestamp);
+            }
+            Frame::Keyframe(_) | Frame::KeyframeBegin(_) => {
+                if self.timestamp.is_none() {
+                    self.untimed += 1;
+                }
+                self.entries.push(IndexEntry {
+                    timestamp: self.timestamp.unwrap_or(0),
+                    offset,
+                    frame_index: self.frames,
+                });
+            }
+            _ => {}
+        }
+        self.frames += 1;
+    }
+
+    /// The trailer for a file whose frames end at byte `frames_end`
+    pub fn encode(&self, frames_end: u64) -> Vec<u8> {
+        let mut trailer = Vec::with_capacity(8 + self.entries.len() * ENTRY_SIZE + INDEX_FOOTER_SIZE as usize);
+        trailer.extend_from_slice(&INDEX_MARKER.to_be_bytes());
+        trailer.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            trailer.extend_from_slice(&entry.timestamp.to_be_bytes());
+            trailer.extend_from_slice(&entry.offset.to_be_bytes());
+            trailer.extend_from_slice(&entry.frame_index.to_be_bytes());
+        }
+        trailer.extend_from_slice(&frames_end.to_be_bytes());
+        trailer.extend_from_slice(&INDEX_MAGIC);
+        trailer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u64, offset: u64) -> IndexEntry {
+        IndexEntry {
+            timestamp,
+            offset,
+            frame_index: 0,
+        }
+    }
+
+    #[test]
+    fn test_entry_at() {
+        let index = FrameIndex {
+            entries: vec![entry(1_000, 32), entry(5_000, 900), entry(9_000, 4_000)],
+            frames_end: 5_000,
+        };
+        assert_eq!(index.entry_at(500), Some(entry(1_000, 32)));
+        assert_eq!(index.entry_at(5_000), Some(entry(5_000, 900)));
+        assert_eq!(index.entry_at(8_999), Some(entry(5_000, 900)));
+        assert_eq!(index.entry_at(20_000), Some(entry(9_000, 4_000)));
+        assert_eq!(FrameIndex { entries: vec![], frames_end: 32 }.entry_at(0), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Chunked keyframes: one Keyframe as KeyframeBegin, KeyframeChunk... and
+//! KeyframeEnd frames
+//!
+//! A huge page's keyframe can be tens of MB, and every reader, the server
+//! and the player would have to buffer it whole. Split into chunks, the
+//! document streams through them a few subtrees at a time. An element whose
+//! subtree does not fit in a chunk is sent without its children, which follow
+//! in chunks of their own.
+
+use crate::frame::{Frame, KeyframeBeginData, KeyframeChunkData, KeyframeData};
+use crate::vdom::{VDocument, VNode};
+use crate::vdom_engine::{VDomEngine, VDomError};
+use bincode::Options;
+
+/// Encoded size of a node and its subtree
+fn encoded_size(node: &VNode) -> u64 {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .serialized_size(node)
+        .unwrap_or(u64::MAX)
+}
+
+/// Split a keyframe whose nodes take more than `max_chunk_bytes` into a
+/// chunked keyframe; smaller keyframes are returned as they are
+///
+/// Chunks hold up to `max_chunk_bytes` of nodes, except for single nodes that
+/// cannot be split further (e.g. a huge text node).
+pub fn chunk_keyframe(keyframe: KeyframeData, max_chunk_bytes: usize) -> Vec<Frame> {
+    let max_chunk_bytes = max_chunk_bytes as u64;
+    let total: u64 = keyframe.document.children.iter().map(encoded_size).sum();
+    if total <= max_chunk_bytes {
+        return vec![Frame::Keyframe(keyframe)];
+    }
+
+    let KeyframeData {
+        document,
+        viewport_width,
+        viewport_height,
+    } = keyframe;
+    let mut chunker = Chunker {
+        frames: vec![Frame::KeyframeBegin(KeyframeBeginData {
+            document_id: document.id,
+            adopted_style_sheets: document.adopted_style_sheets,
+            viewport_width,
+            viewport_height,
+        })],
+        current: KeyframeChunkData {
+            parent_node_id: document.id,
+            nodes: Vec::new(),
+        },
+        current_size: 0,
+        max_chunk_bytes,
+    };
+    for child in document.children {
+        chunker.push(child, document.id);
+    }
+    chunker.flush();
+    chunker.frames.push(Frame::KeyframeEnd);
+    chunker.frames
+}
+
+struct Chunker {
+    frames: Vec<Frame>,
+    current: KeyframeChunkData,
+    current_size: u64,
+    max_chunk_bytes: u64,
+}
+
+impl Chunker {
+    fn push(&mut self, mut node: VNode, parent_node_id: u32) {
+        let size = encoded_size(&node);
+        // Only elements with element children are worth splitting: a lone
+        // text child is no smaller on its own
+        let splittable = matches!(&node, VNode::Element(element)
+            if element.children.iter().any(|child| matches!(child, VNode::Element(_))));
+        if size <= self.max_chunk_bytes || !splittable {
+            self.add(node, size, parent_node_id);
+            return;
+        }
+
+        let VNode::Element(element) = &mut node else {
+            unreachable!("only elements are split")
+        };
+        let children = std::mem::take(&mut element.children);
+        let id = element.id;
+        let size = encoded_size(&node);
+        self.add(node, size, parent_node_id);
+        for child in children {
+            self.push(child, id);
+        }
+    }
+
+    fn add(&mut self, node: VNode, size: u64, parent_node_id: u32) {
+        let full = self.current_size + size > self.max_chunk_bytes;
+        if self.current.parent_node_id != parent_node_id || (full && !self.current.nodes.is_empty()) {
+            self.flush();
+            self.current.parent_node_id = parent_node_id;
+        }
+        self.current.nodes.push(node);
+        self.current_size += size;
+    }
+
+    fn flush(&mut self) {
+        if !self.current.nodes.is_empty() {
+            let nodes = std::mem::take(&mut self.current.nodes);
+            self.frames.push(Frame::KeyframeChunk(KeyframeChunkData {
+                parent_node_id: self.current.parent_node_id,
+                nodes,
+            }));
+        }
+        self.current_size = 0;
+    }
+}
+
+/// Joins chunked keyframes back into single Keyframe frames, for consumers
+/// that need whole documents
+#[derive(Debug, Default)]
+pub struct KeyframeAssembler {
+    pending: Option<VDomEngine>,
+}
+
+impl KeyframeAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a KeyframeBegin has been pushed without its KeyframeEnd
+    pub fn is_assembling(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Feed the next frame, getting back the frame to pass on, if any
+    ///
+    /// Chunked keyframe frames are held until KeyframeEnd, which returns the
+    /// joined Keyframe; every other frame is returned as it is.
+    pub fn push(&mut self, frame: Frame) -> Result<Option<Frame>, VDomError> {
+        match (&frame, &mut self.pending) {
+            (Frame::KeyframeBegin(_), _) => {
+                let mut engine = VDomEngine::new();
+                engine.apply(&frame)?;
+                self.pending = Some(engine);
+                Ok(None)
+            }
+            (Frame::KeyframeChunk(_), Some(engine)) => {
+                engine.apply(&frame)?;
+                Ok(None)
+            }
+            (Frame::KeyframeEnd, Some(_)) => {
+                let engine = self.pending.take().expect("matched Some");
+                let document = engine.document().unwrap_or_else(|| VDocument {
+                    id: 0,
+                    adopted_style_sheets: Vec::new(),
+                    children: Vec::new(),
+                });
+                let (viewport_width, viewport_height) = engine.viewport().unwrap_or_default();
+                Ok(Some(Frame::Keyframe(KeyframeData {
+                    document,
+                    viewport_width,
+                    viewport_height,
+                })))
+            }
+            _ => Ok(Some(frame)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuilder;
+
+    fn large_keyframe() -> KeyframeData {
+        let frames = FrameBuilder::keyframe()
+            .with_element("main#content", |main| {
+                (0..40).fold(main, |main, i| {
+                    main.with_element("section", |section| {
+                        section
+                            .with_element("h2", |h2| h2.text(&format!("Section {}", i)))
+                            .with_element("p", |p| p.text(&"lorem ipsum ".repeat(20)))
+                    })
+                })
+            })
+            .build();
+        let Some(Frame::Keyframe(keyframe)) = frames.into_iter().next() else {
+            panic!("expected a keyframe");
+        };
+        keyframe
+    }
+
+    #[test]
+    fn test_small_keyframes_are_unchanged() {
+        let keyframe = large_keyframe();
+        let frames = chunk_keyframe(keyframe.clone(), usize::MAX);
+        assert_eq!(frames, vec![Frame::Keyframe(keyframe)]);
+    }
+
+    #[test]
+    fn test_chunks_reassemble_to_the_same_document() {
+        let keyframe = large_keyframe();
+        let frames = chunk_keyframe(keyframe.clone(), 1024);
+
+        assert!(matches!(frames.first(), Some(Frame::KeyframeBegin(_))));
+        assert_eq!(frames.last(), Some(&Frame::KeyframeEnd));
+        let chunks: Vec<_> = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::KeyframeChunk(chunk) => Some(chunk),
+                _ => None,
+            })
+            .collect();
+        assert!(chunks.len() > 5);
+        for chunk in &chunks {
+            let size: u64 = chunk.nodes.iter().map(encoded_size).sum();
+            assert!(size <= 1024 || chunk.nodes.len() == 1, "chunk of {} bytes", size);
+        }
+
+        let mut assembler = KeyframeAssembler::new();
+        let mut output = Vec::new();
+        for frame in frames {
+            output.extend(assembler.push(frame).unwrap());
+        }
+        assert!(!assembler.is_assembling());
+        assert_eq!(output, vec![Frame::Keyframe(keyframe)]);
+    }
+
+    #[test]
+    fn test_assembler_passes_other_frames_through() {
+        let mut assembler = KeyframeAssembler::new();
+        assert_eq!(assembler.push(Frame::Heartbeat), Ok(Some(Frame::Heartbeat)));
+        assert_eq!(assembler.push(Frame::KeyframeEnd), Ok(Some(Frame::KeyframeEnd)));
+    }
+}
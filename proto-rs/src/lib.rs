@@ -1,9 +1,33 @@
+pub mod asset_refs;
+pub mod builder;
+pub mod checksum;
+pub mod compression;
+pub mod edit;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod frame;
+pub mod index;
+pub mod keyframe_chunks;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
 pub mod reader;
+pub mod stats;
+pub mod validator;
 pub mod vdom;
+pub mod vdom_engine;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod writer;
 
+pub use builder::FrameBuilder;
+pub use checksum::ChecksumMismatch;
 pub use frame::*;
+pub use index::{FrameIndex, IndexEntry};
+pub use keyframe_chunks::{KeyframeAssembler, chunk_keyframe};
+#[cfg(feature = "tokio")]
 pub use reader::FrameReader;
+pub use reader::SyncFrameReader;
+pub use validator::{FrameValidator, ValidationIssue};
 pub use vdom::*;
+pub use vdom_engine::{VDomEngine, VDomError};
 pub use writer::{FileHeader, FrameWriter};
@@ -0,0 +1,1641 @@
+//! Protobuf equivalent of the frame model, with converters to and from [`Frame`]
+//!
+//! The schema is `proto/domcorder/v1/frames.proto`, for services in other
+//! languages that want frames without depending on bincode's encoding.
+//! Conversion into protobuf always succeeds; conversion back fails when a
+//! message field that the Rust model requires is missing.
+
+pub mod pb;
+
+use crate::frame::*;
+use crate::vdom::*;
+use prost::Message;
+use std::fmt;
+
+/// Error converting protobuf messages into frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtobufError {
+    /// The message could not be decoded
+    Decode(prost::DecodeError),
+    /// A required message field (e.g. "KeyframeData.document") was not set
+    MissingField(&'static str),
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtobufError::Decode(e) => write!(f, "invalid protobuf message: {}", e),
+            ProtobufError::MissingField(field) => write!(f, "missing required field {}", field),
+        }
+    }
+}
+
+impl std::error::Error for ProtobufError {}
+
+impl From<prost::DecodeError> for ProtobufError {
+    fn from(e: prost::DecodeError) -> Self {
+        ProtobufError::Decode(e)
+    }
+}
+
+/// Encode a frame as a `domcorder.v1.Frame` message
+pub fn encode(frame: Frame) -> Vec<u8> {
+    pb::Frame::from(frame).encode_to_vec()
+}
+
+/// Decode a `domcorder.v1.Frame` message
+pub fn decode(buf: &[u8]) -> Result<Frame, ProtobufError> {
+    pb::Frame::decode(buf)?.try_into()
+}
+
+fn required<T>(value: Option<T>, field: &'static str) -> Result<T, ProtobufError> {
+    value.ok_or(ProtobufError::MissingField(field))
+}
+
+impl From<Frame> for pb::Frame {
+    fn from(frame: Frame) -> Self {
+        let frame = match frame {
+            Frame::Timestamp(data) => pb::frame::Frame::Timestamp(data.into()),
+            Frame::Keyframe(data) => pb::frame::Frame::Keyframe(data.into()),
+            Frame::ViewportResized(data) => pb::frame::Frame::ViewportResized(data.into()),
+            Frame::ScrollOffsetChanged(data) => pb::frame::Frame::ScrollOffsetChanged(data.into()),
+            Frame::MouseMoved(data) => pb::frame::Frame::MouseMoved(data.into()),
+            Frame::MouseClicked(data) => pb::frame::Frame::MouseClicked(data.into()),
+            Frame::KeyPressed(data) => pb::frame::Frame::KeyPressed(data.into()),
+            Frame::ElementFocused(data) => pb::frame::Frame::ElementFocused(data.into()),
+            Frame::TextSelectionChanged(data) => pb::frame::Frame::TextSelectionChanged(data.into()),
+            Frame::DomNodeAdded(data) => pb::frame::Frame::DomNodeAdded(data.into()),
+            Frame::DomNodeRemoved(data) => pb::frame::Frame::DomNodeRemoved(data.into()),
+            Frame::DomAttributeChanged(data) => pb::frame::Frame::DomAttributeChanged(data.into()),
+            Frame::DomAttributeRemoved(data) => pb::frame::Frame::DomAttributeRemoved(data.into()),
+            Frame::DomTextChanged(data) => pb::frame::Frame::DomTextChanged(data.into()),
+            Frame::DomNodeResized(data) => pb::frame::Frame::DomNodeResized(data.into()),
+            Frame::DomNodePropertyChanged(data) => pb::frame::Frame::DomNodePropertyChanged(data.into()),
+            Frame::Asset(data) => pb::frame::Frame::Asset(data.into()),
+            Frame::AdoptedStyleSheetsChanged(data) => pb::frame::Frame::AdoptedStyleSheetsChanged(data.into()),
+            Frame::NewAdoptedStyleSheet(data) => pb::frame::Frame::NewAdoptedStyleSheet(data.into()),
+            Frame::ElementScrolled(data) => pb::frame::Frame::ElementScrolled(data.into()),
+            Frame::ElementBlurred(data) => pb::frame::Frame::ElementBlurred(data.into()),
+            Frame::WindowFocused(data) => pb::frame::Frame::WindowFocused(data.into()),
+            Frame::WindowBlurred(data) => pb::frame::Frame::WindowBlurred(data.into()),
+            Frame::StyleSheetRuleInserted(data) => pb::frame::Frame::StyleSheetRuleInserted(data.into()),
+            Frame::StyleSheetRuleDeleted(data) => pb::frame::Frame::StyleSheetRuleDeleted(data.into()),
+            Frame::StyleSheetReplaced(data) => pb::frame::Frame::StyleSheetReplaced(data.into()),
+            Frame::CanvasChanged(data) => pb::frame::Frame::CanvasChanged(data.into()),
+            Frame::DomNodePropertyTextChanged(data) => pb::frame::Frame::DomNodePropertyTextChanged(data.into()),
+            Frame::RecordingMetadata(data) => pb::frame::Frame::RecordingMetadata(data.into()),
+            Frame::AssetReference(data) => pb::frame::Frame::AssetReference(data.into()),
+            Frame::CacheManifest(data) => pb::frame::Frame::CacheManifest(data.into()),
+            Frame::PlaybackConfig(data) => pb::frame::Frame::PlaybackConfig(data.into()),
+            Frame::Heartbeat => pb::frame::Frame::Heartbeat(pb::HeartbeatData {}),
+            Frame::PageError(data) => pb::frame::Frame::PageError(data.into()),
+            Frame::Annotation(data) => pb::frame::Frame::Annotation(data.into()),
+            Frame::TraceContext(data) => pb::frame::Frame::TraceContext(data.into()),
+            Frame::RecordingPaused(data) => pb::frame::Frame::RecordingPaused(data.into()),
+            Frame::RecordingResumed => pb::frame::Frame::RecordingResumed(pb::RecordingResumedData {}),
+            Frame::ScrollOffsetChangedV2(data) => pb::frame::Frame::ScrollOffsetChangedV2(data.into()),
+            Frame::MouseMovedV2(data) => pb::frame::Frame::MouseMovedV2(data.into()),
+            Frame::MouseClickedV2(data) => pb::frame::Frame::MouseClickedV2(data.into()),
+            Frame::ElementScrolledV2(data) => pb::frame::Frame::ElementScrolledV2(data.into()),
+            Frame::KeyframeBegin(data) => pb::frame::Frame::KeyframeBegin(data.into()),
+            Frame::KeyframeChunk(data) => pb::frame::Frame::KeyframeChunk(data.into()),
+            Frame::KeyframeEnd => pb::frame::Frame::KeyframeEnd(pb::KeyframeEndData {}),
+            Frame::NewAdoptedStyleSheetReference(data) => pb::frame::Frame::NewAdoptedStyleSheetReference(data.into()),
+            Frame::StyleSheetReplacedReference(data) => pb::frame::Frame::StyleSheetReplacedReference(data.into()),
+            Frame::CanvasChangedDelta(data) => pb::frame::Frame::CanvasChangedDelta(data.into()),
+            Frame::HeartbeatPing(data) => pb::frame::Frame::HeartbeatPing(data.into()),
+            Frame::HeartbeatPong(data) => pb::frame::Frame::HeartbeatPong(data.into()),
+            Frame::SessionIdentity(data) => pb::frame::Frame::SessionIdentity(data.into()),
+            Frame::StopRecording(data) => pb::frame::Frame::StopRecording(data.into()),
+            Frame::IdlePeriod(data) => pb::frame::Frame::IdlePeriod(data.into()),
+            Frame::RecordingTruncated(data) => pb::frame::Frame::RecordingTruncated(data.into()),
+            Frame::CacheInventory(data) => pb::frame::Frame::CacheInventory(data.into()),
+            Frame::RequestKeyframe(data) => pb::frame::Frame::RequestKeyframe(data.into()),
+            Frame::RecordingScope(data) => pb::frame::Frame::RecordingScope(data.into()),
+            Frame::SessionLink(data) => pb::frame::Frame::SessionLink(data.into()),
+        };
+        Self { frame: Some(frame) }
+    }
+}
+
+impl TryFrom<pb::Frame> for Frame {
+    type Error = ProtobufError;
+
+    fn try_from(frame: pb::Frame) -> Result<Self, Self::Error> {
+        Ok(match required(frame.frame, "Frame.frame")? {
+            pb::frame::Frame::Timestamp(data) => Frame::Timestamp(data.try_into()?),
+            pb::frame::Frame::Keyframe(data) => Frame::Keyframe(data.try_into()?),
+            pb::frame::Frame::ViewportResized(data) => Frame::ViewportResized(data.try_into()?),
+            pb::frame::Frame::ScrollOffsetChanged(data) => Frame::ScrollOffsetChanged(data.try_into()?),
+            pb::frame::Frame::MouseMoved(data) => Frame::MouseMoved(data.try_into()?),
+            pb::frame::Frame::MouseClicked(data) => Frame::MouseClicked(data.try_into()?),
+            pb::frame::Frame::KeyPressed(data) => Frame::KeyPressed(data.try_into()?),
+            pb::frame::Frame::ElementFocused(data) => Frame::ElementFocused(data.try_into()?),
+            pb::frame::Frame::TextSelectionChanged(data) => Frame::TextSelectionChanged(data.try_into()?),
+            pb::frame::Frame::DomNodeAdded(data) => Frame::DomNodeAdded(data.try_into()?),
+            pb::frame::Frame::DomNodeRemoved(data) => Frame::DomNodeRemoved(data.try_into()?),
+            pb::frame::Frame::DomAttributeChanged(data) => Frame::DomAttributeChanged(data.try_into()?),
+            pb::frame::Frame::DomAttributeRemoved(data) => Frame::DomAttributeRemoved(data.try_into()?),
+            pb::frame::Frame::DomTextChanged(data) => Frame::DomTextChanged(data.try_into()?),
+            pb::frame::Frame::DomNodeResized(data) => Frame::DomNodeResized(data.try_into()?),
+            pb::frame::Frame::DomNodePropertyChanged(data) => Frame::DomNodePropertyChanged(data.try_into()?),
+            pb::frame::Frame::Asset(data) => Frame::Asset(data.try_into()?),
+            pb::frame::Frame::AdoptedStyleSheetsChanged(data) => Frame::AdoptedStyleSheetsChanged(data.try_into()?),
+            pb::frame::Frame::NewAdoptedStyleSheet(data) => Frame::NewAdoptedStyleSheet(data.try_into()?),
+            pb::frame::Frame::ElementScrolled(data) => Frame::ElementScrolled(data.try_into()?),
+            pb::frame::Frame::ElementBlurred(data) => Frame::ElementBlurred(data.try_into()?),
+            pb::frame::Frame::WindowFocused(data) => Frame::WindowFocused(data.try_into()?),
+            pb::frame::Frame::WindowBlurred(data) => Frame::WindowBlurred(data.try_into()?),
+            pb::frame::Frame::StyleSheetRuleInserted(data) => Frame::StyleSheetRuleInserted(data.try_into()?),
+            pb::frame::Frame::StyleSheetRuleDeleted(data) => Frame::StyleSheetRuleDeleted(data.try_into()?),
+            pb::frame::Frame::StyleSheetReplaced(data) => Frame::StyleSheetReplaced(data.try_into()?),
+            pb::frame::Frame::CanvasChanged(data) => Frame::CanvasChanged(data.try_into()?),
+            pb::frame::Frame::DomNodePropertyTextChanged(data) => Frame::DomNodePropertyTextChanged(data.try_into()?),
+            pb::frame::Frame::RecordingMetadata(data) => Frame::RecordingMetadata(data.try_into()?),
+            pb::frame::Frame::AssetReference(data) => Frame::AssetReference(data.try_into()?),
+            pb::frame::Frame::CacheManifest(data) => Frame::CacheManifest(data.try_into()?),
+            pb::frame::Frame::PlaybackConfig(data) => Frame::PlaybackConfig(data.try_into()?),
+            pb::frame::Frame::Heartbeat(_) => Frame::Heartbeat,
+            pb::frame::Frame::PageError(data) => Frame::PageError(data.try_into()?),
+            pb::frame::Frame::Annotation(data) => Frame::Annotation(data.try_into()?),
+            pb::frame::Frame::TraceContext(data) => Frame::TraceContext(data.try_into()?),
+            pb::frame::Frame::RecordingPaused(data) => Frame::RecordingPaused(data.try_into()?),
+            pb::frame::Frame::RecordingResumed(_) => Frame::RecordingResumed,
+            pb::frame::Frame::ScrollOffsetChangedV2(data) => Frame::ScrollOffsetChangedV2(data.try_into()?),
+            pb::frame::Frame::MouseMovedV2(data) => Frame::MouseMovedV2(data.try_into()?),
+            pb::frame::Frame::MouseClickedV2(data) => Frame::MouseClickedV2(data.try_into()?),
+            pb::frame::Frame::ElementScrolledV2(data) => Frame::ElementScrolledV2(data.try_into()?),
+            pb::frame::Frame::KeyframeBegin(data) => Frame::KeyframeBegin(data.try_into()?),
+            pb::frame::Frame::KeyframeChunk(data) => Frame::KeyframeChunk(data.try_into()?),
+            pb::frame::Frame::KeyframeEnd(_) => Frame::KeyframeEnd,
+            pb::frame::Frame::NewAdoptedStyleSheetReference(data) => Frame::NewAdoptedStyleSheetReference(data.try_into()?),
+            pb::frame::Frame::StyleSheetReplacedReference(data) => Frame::StyleSheetReplacedReference(data.try_into()?),
+            pb::frame::Frame::CanvasChangedDelta(data) => Frame::CanvasChangedDelta(data.try_into()?),
+            pb::frame::Frame::HeartbeatPing(data) => Frame::HeartbeatPing(data.try_into()?),
+            pb::frame::Frame::HeartbeatPong(data) => Frame::HeartbeatPong(data.try_into()?),
+            pb::frame::Frame::SessionIdentity(data) => Frame::SessionIdentity(data.try_into()?),
+            pb::frame::Frame::StopRecording(data) => Frame::StopRecording(data.try_into()?),
+            pb::frame::Frame::IdlePeriod(data) => Frame::IdlePeriod(data.try_into()?),
+            pb::frame::Frame::RecordingTruncated(data) => Frame::RecordingTruncated(data.try_into()?),
+            pb::frame::Frame::CacheInventory(data) => Frame::CacheInventory(data.try_into()?),
+            pb::frame::Frame::RequestKeyframe(data) => Frame::RequestKeyframe(data.try_into()?),
+            pb::frame::Frame::RecordingScope(data) => Frame::RecordingScope(data.try_into()?),
+            pb::frame::Frame::SessionLink(data) => Frame::SessionLink(data.try_into()?),
+        })
+    }
+}
+
+impl From<TimestampData> for pb::TimestampData {
+    fn from(value: TimestampData) -> Self {
+        Self {
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+impl TryFrom<pb::TimestampData> for TimestampData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TimestampData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            timestamp: value.timestamp,
+        })
+    }
+}
+
+impl From<KeyframeData> for pb::KeyframeData {
+    fn from(value: KeyframeData) -> Self {
+        Self {
+            document: Some(value.document.into()),
+            viewport_width: value.viewport_width,
+            viewport_height: value.viewport_height,
+        }
+    }
+}
+
+impl TryFrom<pb::KeyframeData> for KeyframeData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::KeyframeData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            document: required(value.document, "KeyframeData.document")?.try_into()?,
+            viewport_width: value.viewport_width,
+            viewport_height: value.viewport_height,
+        })
+    }
+}
+
+impl From<ViewportResizedData> for pb::ViewportResizedData {
+    fn from(value: ViewportResizedData) -> Self {
+        Self {
+            width: value.width,
+            height: value.height,
+        }
+    }
+}
+
+impl TryFrom<pb::ViewportResizedData> for ViewportResizedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ViewportResizedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            width: value.width,
+            height: value.height,
+        })
+    }
+}
+
+impl From<ScrollOffsetChangedData> for pb::ScrollOffsetChangedData {
+    fn from(value: ScrollOffsetChangedData) -> Self {
+        Self {
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        }
+    }
+}
+
+impl TryFrom<pb::ScrollOffsetChangedData> for ScrollOffsetChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ScrollOffsetChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        })
+    }
+}
+
+impl From<MouseMovedData> for pb::MouseMovedData {
+    fn from(value: MouseMovedData) -> Self {
+        Self { x: value.x, y: value.y }
+    }
+}
+
+impl TryFrom<pb::MouseMovedData> for MouseMovedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::MouseMovedData) -> Result<Self, Self::Error> {
+        Ok(Self { x: value.x, y: value.y })
+    }
+}
+
+impl From<MouseClickedData> for pb::MouseClickedData {
+    fn from(value: MouseClickedData) -> Self {
+        Self { x: value.x, y: value.y }
+    }
+}
+
+impl TryFrom<pb::MouseClickedData> for MouseClickedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::MouseClickedData) -> Result<Self, Self::Error> {
+        Ok(Self { x: value.x, y: value.y })
+    }
+}
+
+impl From<KeyPressedData> for pb::KeyPressedData {
+    fn from(value: KeyPressedData) -> Self {
+        Self {
+            code: value.code,
+            alt_key: value.alt_key,
+            ctrl_key: value.ctrl_key,
+            meta_key: value.meta_key,
+            shift_key: value.shift_key,
+        }
+    }
+}
+
+impl TryFrom<pb::KeyPressedData> for KeyPressedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::KeyPressedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            code: value.code,
+            alt_key: value.alt_key,
+            ctrl_key: value.ctrl_key,
+            meta_key: value.meta_key,
+            shift_key: value.shift_key,
+        })
+    }
+}
+
+impl From<ElementFocusedData> for pb::ElementFocusedData {
+    fn from(value: ElementFocusedData) -> Self {
+        Self { node_id: value.node_id }
+    }
+}
+
+impl TryFrom<pb::ElementFocusedData> for ElementFocusedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ElementFocusedData) -> Result<Self, Self::Error> {
+        Ok(Self { node_id: value.node_id })
+    }
+}
+
+impl From<TextSelectionChangedData> for pb::TextSelectionChangedData {
+    fn from(value: TextSelectionChangedData) -> Self {
+        Self {
+            selection_start_node_id: value.selection_start_node_id,
+            selection_start_offset: value.selection_start_offset,
+            selection_end_node_id: value.selection_end_node_id,
+            selection_end_offset: value.selection_end_offset,
+        }
+    }
+}
+
+impl TryFrom<pb::TextSelectionChangedData> for TextSelectionChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TextSelectionChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            selection_start_node_id: value.selection_start_node_id,
+            selection_start_offset: value.selection_start_offset,
+            selection_end_node_id: value.selection_end_node_id,
+            selection_end_offset: value.selection_end_offset,
+        })
+    }
+}
+
+impl From<DomNodeAddedData> for pb::DomNodeAddedData {
+    fn from(value: DomNodeAddedData) -> Self {
+        Self {
+            parent_node_id: value.parent_node_id,
+            index: value.index,
+            node: Some(value.node.into()),
+        }
+    }
+}
+
+impl TryFrom<pb::DomNodeAddedData> for DomNodeAddedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomNodeAddedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            parent_node_id: value.parent_node_id,
+            index: value.index,
+            node: required(value.node, "DomNodeAddedData.node")?.try_into()?,
+        })
+    }
+}
+
+impl From<DomNodeRemovedData> for pb::DomNodeRemovedData {
+    fn from(value: DomNodeRemovedData) -> Self {
+        Self { node_id: value.node_id }
+    }
+}
+
+impl TryFrom<pb::DomNodeRemovedData> for DomNodeRemovedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomNodeRemovedData) -> Result<Self, Self::Error> {
+        Ok(Self { node_id: value.node_id })
+    }
+}
+
+impl From<DomAttributeChangedData> for pb::DomAttributeChangedData {
+    fn from(value: DomAttributeChangedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            attribute_name: value.attribute_name,
+            attribute_value: value.attribute_value,
+        }
+    }
+}
+
+impl TryFrom<pb::DomAttributeChangedData> for DomAttributeChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomAttributeChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            attribute_name: value.attribute_name,
+            attribute_value: value.attribute_value,
+        })
+    }
+}
+
+impl From<DomAttributeRemovedData> for pb::DomAttributeRemovedData {
+    fn from(value: DomAttributeRemovedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            attribute_name: value.attribute_name,
+        }
+    }
+}
+
+impl TryFrom<pb::DomAttributeRemovedData> for DomAttributeRemovedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomAttributeRemovedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            attribute_name: value.attribute_name,
+        })
+    }
+}
+
+impl From<DomTextChangedData> for pb::DomTextChangedData {
+    fn from(value: DomTextChangedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            operations: value.operations.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::DomTextChangedData> for DomTextChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomTextChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            operations: value
+                .operations
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<DomNodeResizedData> for pb::DomNodeResizedData {
+    fn from(value: DomNodeResizedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            width: value.width,
+            height: value.height,
+        }
+    }
+}
+
+impl TryFrom<pb::DomNodeResizedData> for DomNodeResizedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomNodeResizedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            width: value.width,
+            height: value.height,
+        })
+    }
+}
+
+impl From<DomNodePropertyChangedData> for pb::DomNodePropertyChangedData {
+    fn from(value: DomNodePropertyChangedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            property_name: value.property_name,
+            property_value: value.property_value,
+        }
+    }
+}
+
+impl TryFrom<pb::DomNodePropertyChangedData> for DomNodePropertyChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomNodePropertyChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            property_name: value.property_name,
+            property_value: value.property_value,
+        })
+    }
+}
+
+impl From<AssetData> for pb::AssetData {
+    fn from(value: AssetData) -> Self {
+        Self {
+            asset_id: value.asset_id,
+            url: value.url,
+            mime: value.mime,
+            buf: value.buf,
+            fetch_error: Some(value.fetch_error.into()),
+        }
+    }
+}
+
+impl TryFrom<pb::AssetData> for AssetData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::AssetData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            asset_id: value.asset_id,
+            url: value.url,
+            mime: value.mime,
+            buf: value.buf,
+            fetch_error: value.fetch_error.map_or(AssetFetchError::None, Into::into),
+        })
+    }
+}
+
+impl From<AdoptedStyleSheetsChangedData> for pb::AdoptedStyleSheetsChangedData {
+    fn from(value: AdoptedStyleSheetsChangedData) -> Self {
+        Self {
+            style_sheet_ids: value.style_sheet_ids,
+            added_count: value.added_count,
+        }
+    }
+}
+
+impl TryFrom<pb::AdoptedStyleSheetsChangedData> for AdoptedStyleSheetsChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::AdoptedStyleSheetsChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet_ids: value.style_sheet_ids,
+            added_count: value.added_count,
+        })
+    }
+}
+
+impl From<NewAdoptedStyleSheetData> for pb::NewAdoptedStyleSheetData {
+    fn from(value: NewAdoptedStyleSheetData) -> Self {
+        Self {
+            style_sheet: Some(value.style_sheet.into()),
+        }
+    }
+}
+
+impl TryFrom<pb::NewAdoptedStyleSheetData> for NewAdoptedStyleSheetData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::NewAdoptedStyleSheetData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet: required(value.style_sheet, "NewAdoptedStyleSheetData.style_sheet")?.try_into()?,
+        })
+    }
+}
+
+impl From<ElementScrolledData> for pb::ElementScrolledData {
+    fn from(value: ElementScrolledData) -> Self {
+        Self {
+            node_id: value.node_id,
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        }
+    }
+}
+
+impl TryFrom<pb::ElementScrolledData> for ElementScrolledData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ElementScrolledData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        })
+    }
+}
+
+impl From<ElementBlurredData> for pb::ElementBlurredData {
+    fn from(value: ElementBlurredData) -> Self {
+        Self { node_id: value.node_id }
+    }
+}
+
+impl TryFrom<pb::ElementBlurredData> for ElementBlurredData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ElementBlurredData) -> Result<Self, Self::Error> {
+        Ok(Self { node_id: value.node_id })
+    }
+}
+
+impl From<WindowFocusedData> for pb::WindowFocusedData {
+    fn from(_value: WindowFocusedData) -> Self {
+        Self {}
+    }
+}
+
+impl TryFrom<pb::WindowFocusedData> for WindowFocusedData {
+    type Error = ProtobufError;
+
+    fn try_from(_value: pb::WindowFocusedData) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+}
+
+impl From<WindowBlurredData> for pb::WindowBlurredData {
+    fn from(_value: WindowBlurredData) -> Self {
+        Self {}
+    }
+}
+
+impl TryFrom<pb::WindowBlurredData> for WindowBlurredData {
+    type Error = ProtobufError;
+
+    fn try_from(_value: pb::WindowBlurredData) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+}
+
+impl From<StyleSheetRuleInsertedData> for pb::StyleSheetRuleInsertedData {
+    fn from(value: StyleSheetRuleInsertedData) -> Self {
+        Self {
+            style_sheet_id: value.style_sheet_id,
+            rule_index: value.rule_index,
+            content: value.content,
+        }
+    }
+}
+
+impl TryFrom<pb::StyleSheetRuleInsertedData> for StyleSheetRuleInsertedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::StyleSheetRuleInsertedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet_id: value.style_sheet_id,
+            rule_index: value.rule_index,
+            content: value.content,
+        })
+    }
+}
+
+impl From<StyleSheetRuleDeletedData> for pb::StyleSheetRuleDeletedData {
+    fn from(value: StyleSheetRuleDeletedData) -> Self {
+        Self {
+            style_sheet_id: value.style_sheet_id,
+            rule_index: value.rule_index,
+        }
+    }
+}
+
+impl TryFrom<pb::StyleSheetRuleDeletedData> for StyleSheetRuleDeletedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::StyleSheetRuleDeletedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet_id: value.style_sheet_id,
+            rule_index: value.rule_index,
+        })
+    }
+}
+
+impl From<StyleSheetReplacedData> for pb::StyleSheetReplacedData {
+    fn from(value: StyleSheetReplacedData) -> Self {
+        Self {
+            style_sheet_id: value.style_sheet_id,
+            content: value.content,
+        }
+    }
+}
+
+impl TryFrom<pb::StyleSheetReplacedData> for StyleSheetReplacedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::StyleSheetReplacedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet_id: value.style_sheet_id,
+            content: value.content,
+        })
+    }
+}
+
+impl From<CanvasChangedData> for pb::CanvasChangedData {
+    fn from(value: CanvasChangedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            mime_type: value.mime_type,
+            data: value.data,
+        }
+    }
+}
+
+impl TryFrom<pb::CanvasChangedData> for CanvasChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::CanvasChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            mime_type: value.mime_type,
+            data: value.data,
+        })
+    }
+}
+
+impl From<CanvasChangedDeltaData> for pb::CanvasChangedDeltaData {
+    fn from(value: CanvasChangedDeltaData) -> Self {
+        Self {
+            node_id: value.node_id,
+            patches: value.patches.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::CanvasChangedDeltaData> for CanvasChangedDeltaData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::CanvasChangedDeltaData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            patches: value.patches.into_iter().map(TryInto::try_into).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<CanvasPatchData> for pb::CanvasPatchData {
+    fn from(value: CanvasPatchData) -> Self {
+        Self {
+            x: value.x,
+            y: value.y,
+            width: value.width,
+            height: value.height,
+            mime_type: value.mime_type,
+            data: value.data,
+        }
+    }
+}
+
+impl TryFrom<pb::CanvasPatchData> for CanvasPatchData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::CanvasPatchData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            x: value.x,
+            y: value.y,
+            width: value.width,
+            height: value.height,
+            mime_type: value.mime_type,
+            data: value.data,
+        })
+    }
+}
+
+impl From<DomNodePropertyTextChangedData> for pb::DomNodePropertyTextChangedData {
+    fn from(value: DomNodePropertyTextChangedData) -> Self {
+        Self {
+            node_id: value.node_id,
+            property_name: value.property_name,
+            operations: value.operations.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::DomNodePropertyTextChangedData> for DomNodePropertyTextChangedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::DomNodePropertyTextChangedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            property_name: value.property_name,
+            operations: value
+                .operations
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<RecordingMetadataData> for pb::RecordingMetadataData {
+    fn from(value: RecordingMetadataData) -> Self {
+        Self {
+            initial_url: value.initial_url,
+            heartbeat_interval_seconds: value.heartbeat_interval_seconds,
+        }
+    }
+}
+
+impl TryFrom<pb::RecordingMetadataData> for RecordingMetadataData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::RecordingMetadataData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            initial_url: value.initial_url,
+            heartbeat_interval_seconds: value.heartbeat_interval_seconds,
+        })
+    }
+}
+
+impl From<AssetReferenceData> for pb::AssetReferenceData {
+    fn from(value: AssetReferenceData) -> Self {
+        Self {
+            asset_id: value.asset_id,
+            url: value.url,
+            hash: value.hash,
+            mime: value.mime,
+        }
+    }
+}
+
+impl TryFrom<pb::AssetReferenceData> for AssetReferenceData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::AssetReferenceData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            asset_id: value.asset_id,
+            url: value.url,
+            hash: value.hash,
+            mime: value.mime,
+        })
+    }
+}
+
+impl From<CacheManifestData> for pb::CacheManifestData {
+    fn from(value: CacheManifestData) -> Self {
+        Self {
+            site_origin: value.site_origin,
+            assets: value.assets.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::CacheManifestData> for CacheManifestData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::CacheManifestData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            site_origin: value.site_origin,
+            assets: value
+                .assets
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<PlaybackConfigData> for pb::PlaybackConfigData {
+    fn from(value: PlaybackConfigData) -> Self {
+        Self {
+            storage_type: value.storage_type,
+            config_json: value.config_json,
+            is_live: value.is_live,
+            latest_timestamp: value.latest_timestamp,
+            root_selector: value.root_selector,
+        }
+    }
+}
+
+impl TryFrom<pb::PlaybackConfigData> for PlaybackConfigData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::PlaybackConfigData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            storage_type: value.storage_type,
+            config_json: value.config_json,
+            is_live: value.is_live,
+            latest_timestamp: value.latest_timestamp,
+            root_selector: value.root_selector,
+        })
+    }
+}
+
+impl From<PageErrorData> for pb::PageErrorData {
+    fn from(value: PageErrorData) -> Self {
+        Self {
+            message: value.message,
+            source_url: value.source_url,
+            line: value.line,
+            column: value.column,
+            stack: value.stack,
+        }
+    }
+}
+
+impl TryFrom<pb::PageErrorData> for PageErrorData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::PageErrorData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            message: value.message,
+            source_url: value.source_url,
+            line: value.line,
+            column: value.column,
+            stack: value.stack,
+        })
+    }
+}
+
+impl From<AnnotationData> for pb::AnnotationData {
+    fn from(value: AnnotationData) -> Self {
+        Self {
+            name: value.name,
+            data: value.data,
+        }
+    }
+}
+
+impl TryFrom<pb::AnnotationData> for AnnotationData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::AnnotationData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.name,
+            data: value.data,
+        })
+    }
+}
+
+impl From<TraceContextData> for pb::TraceContextData {
+    fn from(value: TraceContextData) -> Self {
+        Self {
+            traceparent: value.traceparent,
+            tracestate: value.tracestate,
+        }
+    }
+}
+
+impl TryFrom<pb::TraceContextData> for TraceContextData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TraceContextData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            traceparent: value.traceparent,
+            tracestate: value.tracestate,
+        })
+    }
+}
+
+impl From<RecordingPausedData> for pb::RecordingPausedData {
+    fn from(value: RecordingPausedData) -> Self {
+        Self { reason: value.reason }
+    }
+}
+
+impl TryFrom<pb::RecordingPausedData> for RecordingPausedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::RecordingPausedData) -> Result<Self, Self::Error> {
+        Ok(Self { reason: value.reason })
+    }
+}
+
+impl From<ScrollOffsetChangedV2Data> for pb::ScrollOffsetChangedV2Data {
+    fn from(value: ScrollOffsetChangedV2Data) -> Self {
+        Self {
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        }
+    }
+}
+
+impl TryFrom<pb::ScrollOffsetChangedV2Data> for ScrollOffsetChangedV2Data {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ScrollOffsetChangedV2Data) -> Result<Self, Self::Error> {
+        Ok(Self {
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        })
+    }
+}
+
+impl From<MouseMovedV2Data> for pb::MouseMovedV2Data {
+    fn from(value: MouseMovedV2Data) -> Self {
+        Self { x: value.x, y: value.y }
+    }
+}
+
+impl TryFrom<pb::MouseMovedV2Data> for MouseMovedV2Data {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::MouseMovedV2Data) -> Result<Self, Self::Error> {
+        Ok(Self { x: value.x, y: value.y })
+    }
+}
+
+impl From<MouseClickedV2Data> for pb::MouseClickedV2Data {
+    fn from(value: MouseClickedV2Data) -> Self {
+        Self { x: value.x, y: value.y }
+    }
+}
+
+impl TryFrom<pb::MouseClickedV2Data> for MouseClickedV2Data {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::MouseClickedV2Data) -> Result<Self, Self::Error> {
+        Ok(Self { x: value.x, y: value.y })
+    }
+}
+
+impl From<ElementScrolledV2Data> for pb::ElementScrolledV2Data {
+    fn from(value: ElementScrolledV2Data) -> Self {
+        Self {
+            node_id: value.node_id,
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        }
+    }
+}
+
+impl TryFrom<pb::ElementScrolledV2Data> for ElementScrolledV2Data {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ElementScrolledV2Data) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_id: value.node_id,
+            scroll_x_offset: value.scroll_x_offset,
+            scroll_y_offset: value.scroll_y_offset,
+        })
+    }
+}
+
+impl From<KeyframeBeginData> for pb::KeyframeBeginData {
+    fn from(value: KeyframeBeginData) -> Self {
+        Self {
+            document_id: value.document_id,
+            adopted_style_sheets: value.adopted_style_sheets.into_iter().map(Into::into).collect(),
+            viewport_width: value.viewport_width,
+            viewport_height: value.viewport_height,
+        }
+    }
+}
+
+impl TryFrom<pb::KeyframeBeginData> for KeyframeBeginData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::KeyframeBeginData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            document_id: value.document_id,
+            adopted_style_sheets: value
+                .adopted_style_sheets
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            viewport_width: value.viewport_width,
+            viewport_height: value.viewport_height,
+        })
+    }
+}
+
+impl From<KeyframeChunkData> for pb::KeyframeChunkData {
+    fn from(value: KeyframeChunkData) -> Self {
+        Self {
+            parent_node_id: value.parent_node_id,
+            nodes: value.nodes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::KeyframeChunkData> for KeyframeChunkData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::KeyframeChunkData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            parent_node_id: value.parent_node_id,
+            nodes: value.nodes.into_iter().map(TryInto::try_into).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<NewAdoptedStyleSheetReferenceData> for pb::NewAdoptedStyleSheetReferenceData {
+    fn from(value: NewAdoptedStyleSheetReferenceData) -> Self {
+        Self {
+            id: value.id,
+            media: value.media,
+            hash: value.hash,
+        }
+    }
+}
+
+impl TryFrom<pb::NewAdoptedStyleSheetReferenceData> for NewAdoptedStyleSheetReferenceData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::NewAdoptedStyleSheetReferenceData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            media: value.media,
+            hash: value.hash,
+        })
+    }
+}
+
+impl From<StyleSheetReplacedReferenceData> for pb::StyleSheetReplacedReferenceData {
+    fn from(value: StyleSheetReplacedReferenceData) -> Self {
+        Self {
+            style_sheet_id: value.style_sheet_id,
+            hash: value.hash,
+        }
+    }
+}
+
+impl TryFrom<pb::StyleSheetReplacedReferenceData> for StyleSheetReplacedReferenceData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::StyleSheetReplacedReferenceData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            style_sheet_id: value.style_sheet_id,
+            hash: value.hash,
+        })
+    }
+}
+
+impl From<HeartbeatPingData> for pb::HeartbeatPingData {
+    fn from(value: HeartbeatPingData) -> Self {
+        Self {
+            server_timestamp: value.server_timestamp,
+        }
+    }
+}
+
+impl TryFrom<pb::HeartbeatPingData> for HeartbeatPingData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::HeartbeatPingData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            server_timestamp: value.server_timestamp,
+        })
+    }
+}
+
+impl From<HeartbeatPongData> for pb::HeartbeatPongData {
+    fn from(value: HeartbeatPongData) -> Self {
+        Self {
+            server_timestamp: value.server_timestamp,
+            client_timestamp: value.client_timestamp,
+        }
+    }
+}
+
+impl TryFrom<pb::HeartbeatPongData> for HeartbeatPongData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::HeartbeatPongData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            server_timestamp: value.server_timestamp,
+            client_timestamp: value.client_timestamp,
+        })
+    }
+}
+
+impl From<SessionIdentityData> for pb::SessionIdentityData {
+    fn from(value: SessionIdentityData) -> Self {
+        Self {
+            anonymous_id: value.anonymous_id,
+            user_id: value.user_id,
+            email_hash: value.email_hash,
+        }
+    }
+}
+
+impl TryFrom<pb::SessionIdentityData> for SessionIdentityData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::SessionIdentityData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            anonymous_id: value.anonymous_id,
+            user_id: value.user_id,
+            email_hash: value.email_hash,
+        })
+    }
+}
+
+impl From<StopRecordingData> for pb::StopRecordingData {
+    fn from(value: StopRecordingData) -> Self {
+        Self { reason: value.reason }
+    }
+}
+
+impl TryFrom<pb::StopRecordingData> for StopRecordingData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::StopRecordingData) -> Result<Self, Self::Error> {
+        Ok(Self { reason: value.reason })
+    }
+}
+
+impl From<IdlePeriodData> for pb::IdlePeriodData {
+    fn from(value: IdlePeriodData) -> Self {
+        Self {
+            duration_ms: value.duration_ms,
+        }
+    }
+}
+
+impl TryFrom<pb::IdlePeriodData> for IdlePeriodData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::IdlePeriodData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            duration_ms: value.duration_ms,
+        })
+    }
+}
+
+impl From<RecordingTruncatedData> for pb::RecordingTruncatedData {
+    fn from(value: RecordingTruncatedData) -> Self {
+        Self {
+            discarded_bytes: value.discarded_bytes,
+        }
+    }
+}
+
+impl TryFrom<pb::RecordingTruncatedData> for RecordingTruncatedData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::RecordingTruncatedData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            discarded_bytes: value.discarded_bytes,
+        })
+    }
+}
+
+impl From<CacheInventoryData> for pb::CacheInventoryData {
+    fn from(value: CacheInventoryData) -> Self {
+        Self {
+            sha256_hashes: value.sha256_hashes,
+        }
+    }
+}
+
+impl TryFrom<pb::CacheInventoryData> for CacheInventoryData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::CacheInventoryData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sha256_hashes: value.sha256_hashes,
+        })
+    }
+}
+
+impl From<RequestKeyframeData> for pb::RequestKeyframeData {
+    fn from(value: RequestKeyframeData) -> Self {
+        Self { reason: value.reason }
+    }
+}
+
+impl TryFrom<pb::RequestKeyframeData> for RequestKeyframeData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::RequestKeyframeData) -> Result<Self, Self::Error> {
+        Ok(Self { reason: value.reason })
+    }
+}
+
+impl From<RecordingScopeData> for pb::RecordingScopeData {
+    fn from(value: RecordingScopeData) -> Self {
+        Self {
+            root_selector: value.root_selector,
+        }
+    }
+}
+
+impl TryFrom<pb::RecordingScopeData> for RecordingScopeData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::RecordingScopeData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            root_selector: value.root_selector,
+        })
+    }
+}
+
+impl From<SessionLinkData> for pb::SessionLinkData {
+    fn from(value: SessionLinkData) -> Self {
+        Self {
+            session_id: value.session_id,
+        }
+    }
+}
+
+impl TryFrom<pb::SessionLinkData> for SessionLinkData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::SessionLinkData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            session_id: value.session_id,
+        })
+    }
+}
+
+impl From<ManifestEntryData> for pb::ManifestEntryData {
+    fn from(value: ManifestEntryData) -> Self {
+        Self {
+            url: value.url,
+            sha256_hash: value.sha256_hash,
+        }
+    }
+}
+
+impl TryFrom<pb::ManifestEntryData> for ManifestEntryData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::ManifestEntryData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            url: value.url,
+            sha256_hash: value.sha256_hash,
+        })
+    }
+}
+
+impl From<TextInsertOperationData> for pb::TextInsertOperationData {
+    fn from(value: TextInsertOperationData) -> Self {
+        Self {
+            index: value.index,
+            text: value.text,
+        }
+    }
+}
+
+impl TryFrom<pb::TextInsertOperationData> for TextInsertOperationData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TextInsertOperationData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            index: value.index,
+            text: value.text,
+        })
+    }
+}
+
+impl From<TextRemoveOperationData> for pb::TextRemoveOperationData {
+    fn from(value: TextRemoveOperationData) -> Self {
+        Self {
+            index: value.index,
+            length: value.length,
+        }
+    }
+}
+
+impl TryFrom<pb::TextRemoveOperationData> for TextRemoveOperationData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TextRemoveOperationData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            index: value.index,
+            length: value.length,
+        })
+    }
+}
+
+impl From<VDocument> for pb::VDocument {
+    fn from(value: VDocument) -> Self {
+        Self {
+            id: value.id,
+            adopted_style_sheets: value.adopted_style_sheets.into_iter().map(Into::into).collect(),
+            children: value.children.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::VDocument> for VDocument {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VDocument) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            adopted_style_sheets: value
+                .adopted_style_sheets
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            children: value
+                .children
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<VElement> for pb::VElement {
+    fn from(value: VElement) -> Self {
+        Self {
+            id: value.id,
+            tag: value.tag,
+            ns: value.ns,
+            attrs: value
+                .attrs
+                .into_iter()
+                .map(|(name, value)| pb::VAttribute { name, value })
+                .collect(),
+            children: value.children.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<pb::VElement> for VElement {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VElement) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            tag: value.tag,
+            ns: value.ns,
+            attrs: value.attrs.into_iter().map(|attr| (attr.name, attr.value)).collect(),
+            children: value
+                .children
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<VTextNode> for pb::VTextNode {
+    fn from(value: VTextNode) -> Self {
+        Self {
+            id: value.id,
+            content: value.content,
+        }
+    }
+}
+
+impl TryFrom<pb::VTextNode> for VTextNode {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VTextNode) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            content: value.content,
+        })
+    }
+}
+
+impl From<VCDATASection> for pb::VCDATASection {
+    fn from(value: VCDATASection) -> Self {
+        Self {
+            id: value.id,
+            content: value.content,
+        }
+    }
+}
+
+impl TryFrom<pb::VCDATASection> for VCDATASection {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VCDATASection) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            content: value.content,
+        })
+    }
+}
+
+impl From<VComment> for pb::VComment {
+    fn from(value: VComment) -> Self {
+        Self {
+            id: value.id,
+            content: value.content,
+        }
+    }
+}
+
+impl TryFrom<pb::VComment> for VComment {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VComment) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            content: value.content,
+        })
+    }
+}
+
+impl From<VDocumentType> for pb::VDocumentType {
+    fn from(value: VDocumentType) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            public_id: value.public_id,
+            system_id: value.system_id,
+        }
+    }
+}
+
+impl TryFrom<pb::VDocumentType> for VDocumentType {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VDocumentType) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            name: value.name,
+            public_id: value.public_id,
+            system_id: value.system_id,
+        })
+    }
+}
+
+impl From<VProcessingInstruction> for pb::VProcessingInstruction {
+    fn from(value: VProcessingInstruction) -> Self {
+        Self {
+            id: value.id,
+            target: value.target,
+            data: value.data,
+        }
+    }
+}
+
+impl TryFrom<pb::VProcessingInstruction> for VProcessingInstruction {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VProcessingInstruction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            target: value.target,
+            data: value.data,
+        })
+    }
+}
+
+impl From<VStyleSheet> for pb::VStyleSheet {
+    fn from(value: VStyleSheet) -> Self {
+        Self {
+            id: value.id,
+            text: value.text,
+            media: value.media,
+        }
+    }
+}
+
+impl TryFrom<pb::VStyleSheet> for VStyleSheet {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VStyleSheet) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            text: value.text,
+            media: value.media,
+        })
+    }
+}
+
+impl From<VNode> for pb::VNode {
+    fn from(value: VNode) -> Self {
+        use pb::v_node::Node;
+        let node = match value {
+            VNode::Element(node) => Node::Element(node.into()),
+            VNode::Text(node) => Node::Text(node.into()),
+            VNode::CData(node) => Node::Cdata(node.into()),
+            VNode::Comment(node) => Node::Comment(node.into()),
+            VNode::DocType(node) => Node::DocType(node.into()),
+            VNode::ProcessingInstruction(node) => Node::ProcessingInstruction(node.into()),
+        };
+        Self { node: Some(node) }
+    }
+}
+
+impl TryFrom<pb::VNode> for VNode {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::VNode) -> Result<Self, Self::Error> {
+        use pb::v_node::Node;
+        Ok(match required(value.node, "VNode.node")? {
+            Node::Element(node) => VNode::Element(node.try_into()?),
+            Node::Text(node) => VNode::Text(node.try_into()?),
+            Node::Cdata(node) => VNode::CData(node.try_into()?),
+            Node::Comment(node) => VNode::Comment(node.try_into()?),
+            Node::DocType(node) => VNode::DocType(node.try_into()?),
+            Node::ProcessingInstruction(node) => VNode::ProcessingInstruction(node.try_into()?),
+        })
+    }
+}
+
+impl From<TextOperationData> for pb::TextOperationData {
+    fn from(value: TextOperationData) -> Self {
+        use pb::text_operation_data::Operation;
+        let operation = match value {
+            TextOperationData::Insert(op) => Operation::Insert(op.into()),
+            TextOperationData::Remove(op) => Operation::Remove(op.into()),
+        };
+        Self {
+            operation: Some(operation),
+        }
+    }
+}
+
+impl TryFrom<pb::TextOperationData> for TextOperationData {
+    type Error = ProtobufError;
+
+    fn try_from(value: pb::TextOperationData) -> Result<Self, Self::Error> {
+        use pb::text_operation_data::Operation;
+        Ok(match required(value.operation, "TextOperationData.operation")? {
+            Operation::Insert(op) => TextOperationData::Insert(op.try_into()?),
+            Operation::Remove(op) => TextOperationData::Remove(op.try_into()?),
+        })
+    }
+}
+
+impl From<AssetFetchError> for pb::AssetFetchError {
+    fn from(value: AssetFetchError) -> Self {
+        use pb::asset_fetch_error::Kind;
+        let (kind, message) = match value {
+            AssetFetchError::None => (Kind::None, String::new()),
+            AssetFetchError::CORS => (Kind::Cors, String::new()),
+            AssetFetchError::Network => (Kind::Network, String::new()),
+            AssetFetchError::Http => (Kind::Http, String::new()),
+            AssetFetchError::Unknown(message) => (Kind::Unknown, message),
+        };
+        Self {
+            kind: kind as i32,
+            message,
+        }
+    }
+}
+
+impl From<pb::AssetFetchError> for AssetFetchError {
+    fn from(value: pb::AssetFetchError) -> Self {
+        use pb::asset_fetch_error::Kind;
+        match Kind::try_from(value.kind) {
+            Ok(Kind::None) => AssetFetchError::None,
+            Ok(Kind::Cors) => AssetFetchError::CORS,
+            Ok(Kind::Network) => AssetFetchError::Network,
+            Ok(Kind::Http) => AssetFetchError::Http,
+            // Kinds added by newer schemas degrade to Unknown
+            Ok(Kind::Unknown) | Err(_) => AssetFetchError::Unknown(value.message),
+        }
+    }
+}
@@ -0,0 +1,795 @@
+//! Message types for `proto/domcorder/v1/frames.proto`
+//!
+//! Written by hand in the shape prost-build generates, so building the crate
+//! does not need protoc. Keep in sync with the schema.
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Frame {
+    #[prost(
+        oneof = "frame::Frame",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58"
+    )]
+    pub frame: Option<frame::Frame>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeartbeatData {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingResumedData {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyframeEndData {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TimestampData {
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyframeData {
+    #[prost(message, optional, tag = "1")]
+    pub document: Option<VDocument>,
+    #[prost(uint32, tag = "2")]
+    pub viewport_width: u32,
+    #[prost(uint32, tag = "3")]
+    pub viewport_height: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ViewportResizedData {
+    #[prost(uint32, tag = "1")]
+    pub width: u32,
+    #[prost(uint32, tag = "2")]
+    pub height: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScrollOffsetChangedData {
+    #[prost(uint32, tag = "1")]
+    pub scroll_x_offset: u32,
+    #[prost(uint32, tag = "2")]
+    pub scroll_y_offset: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MouseMovedData {
+    #[prost(uint32, tag = "1")]
+    pub x: u32,
+    #[prost(uint32, tag = "2")]
+    pub y: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MouseClickedData {
+    #[prost(uint32, tag = "1")]
+    pub x: u32,
+    #[prost(uint32, tag = "2")]
+    pub y: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyPressedData {
+    #[prost(string, tag = "1")]
+    pub code: String,
+    #[prost(bool, tag = "2")]
+    pub alt_key: bool,
+    #[prost(bool, tag = "3")]
+    pub ctrl_key: bool,
+    #[prost(bool, tag = "4")]
+    pub meta_key: bool,
+    #[prost(bool, tag = "5")]
+    pub shift_key: bool,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ElementFocusedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TextSelectionChangedData {
+    #[prost(uint32, tag = "1")]
+    pub selection_start_node_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub selection_start_offset: u32,
+    #[prost(uint32, tag = "3")]
+    pub selection_end_node_id: u32,
+    #[prost(uint32, tag = "4")]
+    pub selection_end_offset: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomNodeAddedData {
+    #[prost(uint32, tag = "1")]
+    pub parent_node_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub index: u32,
+    #[prost(message, optional, tag = "3")]
+    pub node: Option<VNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomNodeRemovedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomAttributeChangedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(string, tag = "2")]
+    pub attribute_name: String,
+    #[prost(string, tag = "3")]
+    pub attribute_value: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomAttributeRemovedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(string, tag = "2")]
+    pub attribute_name: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomTextChangedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub operations: Vec<TextOperationData>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomNodeResizedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub width: u32,
+    #[prost(uint32, tag = "3")]
+    pub height: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomNodePropertyChangedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(string, tag = "2")]
+    pub property_name: String,
+    #[prost(string, tag = "3")]
+    pub property_value: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AssetData {
+    #[prost(uint32, tag = "1")]
+    pub asset_id: u32,
+    #[prost(string, tag = "2")]
+    pub url: String,
+    #[prost(string, optional, tag = "3")]
+    pub mime: Option<String>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub buf: Vec<u8>,
+    #[prost(message, optional, tag = "5")]
+    pub fetch_error: Option<AssetFetchError>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AdoptedStyleSheetsChangedData {
+    #[prost(uint32, repeated, tag = "1")]
+    pub style_sheet_ids: Vec<u32>,
+    #[prost(uint32, tag = "2")]
+    pub added_count: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NewAdoptedStyleSheetData {
+    #[prost(message, optional, tag = "1")]
+    pub style_sheet: Option<VStyleSheet>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ElementScrolledData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub scroll_x_offset: u32,
+    #[prost(uint32, tag = "3")]
+    pub scroll_y_offset: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ElementBlurredData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WindowFocusedData {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WindowBlurredData {}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StyleSheetRuleInsertedData {
+    #[prost(uint32, tag = "1")]
+    pub style_sheet_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub rule_index: u32,
+    #[prost(string, tag = "3")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StyleSheetRuleDeletedData {
+    #[prost(uint32, tag = "1")]
+    pub style_sheet_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub rule_index: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StyleSheetReplacedData {
+    #[prost(uint32, tag = "1")]
+    pub style_sheet_id: u32,
+    #[prost(string, tag = "2")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanvasChangedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(string, tag = "2")]
+    pub mime_type: String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanvasChangedDeltaData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub patches: Vec<CanvasPatchData>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanvasPatchData {
+    #[prost(uint32, tag = "1")]
+    pub x: u32,
+    #[prost(uint32, tag = "2")]
+    pub y: u32,
+    #[prost(uint32, tag = "3")]
+    pub width: u32,
+    #[prost(uint32, tag = "4")]
+    pub height: u32,
+    #[prost(string, tag = "5")]
+    pub mime_type: String,
+    #[prost(bytes = "vec", tag = "6")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DomNodePropertyTextChangedData {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(string, tag = "2")]
+    pub property_name: String,
+    #[prost(message, repeated, tag = "3")]
+    pub operations: Vec<TextOperationData>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingMetadataData {
+    #[prost(string, tag = "1")]
+    pub initial_url: String,
+    #[prost(uint32, tag = "2")]
+    pub heartbeat_interval_seconds: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AssetReferenceData {
+    #[prost(uint32, tag = "1")]
+    pub asset_id: u32,
+    #[prost(string, tag = "2")]
+    pub url: String,
+    #[prost(string, tag = "3")]
+    pub hash: String,
+    #[prost(string, optional, tag = "4")]
+    pub mime: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CacheManifestData {
+    #[prost(string, tag = "1")]
+    pub site_origin: String,
+    #[prost(message, repeated, tag = "2")]
+    pub assets: Vec<ManifestEntryData>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PlaybackConfigData {
+    #[prost(string, tag = "1")]
+    pub storage_type: String,
+    #[prost(string, tag = "2")]
+    pub config_json: String,
+    #[prost(bool, tag = "3")]
+    pub is_live: bool,
+    #[prost(uint64, optional, tag = "4")]
+    pub latest_timestamp: Option<u64>,
+    #[prost(string, optional, tag = "5")]
+    pub root_selector: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PageErrorData {
+    #[prost(string, tag = "1")]
+    pub message: String,
+    #[prost(string, optional, tag = "2")]
+    pub source_url: Option<String>,
+    #[prost(uint32, optional, tag = "3")]
+    pub line: Option<u32>,
+    #[prost(uint32, optional, tag = "4")]
+    pub column: Option<u32>,
+    #[prost(string, optional, tag = "5")]
+    pub stack: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnnotationData {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, optional, tag = "2")]
+    pub data: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TraceContextData {
+    #[prost(string, tag = "1")]
+    pub traceparent: String,
+    #[prost(string, optional, tag = "2")]
+    pub tracestate: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingPausedData {
+    #[prost(string, optional, tag = "1")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScrollOffsetChangedV2Data {
+    #[prost(sint32, tag = "1")]
+    pub scroll_x_offset: i32,
+    #[prost(sint32, tag = "2")]
+    pub scroll_y_offset: i32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MouseMovedV2Data {
+    #[prost(float, tag = "1")]
+    pub x: f32,
+    #[prost(float, tag = "2")]
+    pub y: f32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MouseClickedV2Data {
+    #[prost(float, tag = "1")]
+    pub x: f32,
+    #[prost(float, tag = "2")]
+    pub y: f32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ElementScrolledV2Data {
+    #[prost(uint32, tag = "1")]
+    pub node_id: u32,
+    #[prost(sint32, tag = "2")]
+    pub scroll_x_offset: i32,
+    #[prost(sint32, tag = "3")]
+    pub scroll_y_offset: i32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyframeBeginData {
+    #[prost(uint32, tag = "1")]
+    pub document_id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub adopted_style_sheets: Vec<VStyleSheet>,
+    #[prost(uint32, tag = "3")]
+    pub viewport_width: u32,
+    #[prost(uint32, tag = "4")]
+    pub viewport_height: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyframeChunkData {
+    #[prost(uint32, tag = "1")]
+    pub parent_node_id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub nodes: Vec<VNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NewAdoptedStyleSheetReferenceData {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, optional, tag = "2")]
+    pub media: Option<String>,
+    #[prost(string, tag = "3")]
+    pub hash: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StyleSheetReplacedReferenceData {
+    #[prost(uint32, tag = "1")]
+    pub style_sheet_id: u32,
+    #[prost(string, tag = "2")]
+    pub hash: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeartbeatPingData {
+    #[prost(uint64, tag = "1")]
+    pub server_timestamp: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeartbeatPongData {
+    #[prost(uint64, tag = "1")]
+    pub server_timestamp: u64,
+    #[prost(uint64, tag = "2")]
+    pub client_timestamp: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionIdentityData {
+    #[prost(string, tag = "1")]
+    pub anonymous_id: String,
+    #[prost(string, optional, tag = "2")]
+    pub user_id: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub email_hash: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StopRecordingData {
+    #[prost(string, tag = "1")]
+    pub reason: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IdlePeriodData {
+    #[prost(uint64, tag = "1")]
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingTruncatedData {
+    #[prost(uint64, tag = "1")]
+    pub discarded_bytes: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CacheInventoryData {
+    #[prost(string, repeated, tag = "1")]
+    pub sha256_hashes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestKeyframeData {
+    #[prost(string, tag = "1")]
+    pub reason: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordingScopeData {
+    #[prost(string, tag = "1")]
+    pub root_selector: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionLinkData {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ManifestEntryData {
+    #[prost(string, tag = "1")]
+    pub url: String,
+    #[prost(string, tag = "2")]
+    pub sha256_hash: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TextInsertOperationData {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(string, tag = "2")]
+    pub text: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TextRemoveOperationData {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub length: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VDocument {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub adopted_style_sheets: Vec<VStyleSheet>,
+    #[prost(message, repeated, tag = "3")]
+    pub children: Vec<VNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VElement {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub tag: String,
+    #[prost(string, optional, tag = "3")]
+    pub ns: Option<String>,
+    #[prost(message, repeated, tag = "4")]
+    pub attrs: Vec<VAttribute>,
+    #[prost(message, repeated, tag = "5")]
+    pub children: Vec<VNode>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VTextNode {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VCDATASection {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VComment {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub content: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VDocumentType {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(string, optional, tag = "3")]
+    pub public_id: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub system_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VProcessingInstruction {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub target: String,
+    #[prost(string, tag = "3")]
+    pub data: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VStyleSheet {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub text: String,
+    #[prost(string, optional, tag = "3")]
+    pub media: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VNode {
+    #[prost(oneof = "v_node::Node", tags = "1, 2, 3, 4, 5, 6")]
+    pub node: Option<v_node::Node>,
+}
+
+pub mod v_node {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Node {
+        #[prost(message, tag = "1")]
+        Element(super::VElement),
+        #[prost(message, tag = "2")]
+        Text(super::VTextNode),
+        #[prost(message, tag = "3")]
+        Cdata(super::VCDATASection),
+        #[prost(message, tag = "4")]
+        Comment(super::VComment),
+        #[prost(message, tag = "5")]
+        DocType(super::VDocumentType),
+        #[prost(message, tag = "6")]
+        ProcessingInstruction(super::VProcessingInstruction),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VAttribute {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TextOperationData {
+    #[prost(oneof = "text_operation_data::Operation", tags = "1, 2")]
+    pub operation: Option<text_operation_data::Operation>,
+}
+
+pub mod text_operation_data {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Operation {
+        #[prost(message, tag = "1")]
+        Insert(super::TextInsertOperationData),
+        #[prost(message, tag = "2")]
+        Remove(super::TextRemoveOperationData),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AssetFetchError {
+    #[prost(enumeration = "asset_fetch_error::Kind", tag = "1")]
+    pub kind: i32,
+    /// Error message, for `Kind::Unknown`
+    #[prost(string, tag = "2")]
+    pub message: String,
+}
+
+pub mod asset_fetch_error {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Kind {
+        None = 0,
+        Cors = 1,
+        Network = 2,
+        Http = 3,
+        Unknown = 4,
+    }
+}
+
+pub mod frame {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Frame {
+        #[prost(message, tag = "1")]
+        Timestamp(super::TimestampData),
+        #[prost(message, tag = "2")]
+        Keyframe(super::KeyframeData),
+        #[prost(message, tag = "3")]
+        ViewportResized(super::ViewportResizedData),
+        #[prost(message, tag = "4")]
+        ScrollOffsetChanged(super::ScrollOffsetChangedData),
+        #[prost(message, tag = "5")]
+        MouseMoved(super::MouseMovedData),
+        #[prost(message, tag = "6")]
+        MouseClicked(super::MouseClickedData),
+        #[prost(message, tag = "7")]
+        KeyPressed(super::KeyPressedData),
+        #[prost(message, tag = "8")]
+        ElementFocused(super::ElementFocusedData),
+        #[prost(message, tag = "9")]
+        TextSelectionChanged(super::TextSelectionChangedData),
+        #[prost(message, tag = "10")]
+        DomNodeAdded(super::DomNodeAddedData),
+        #[prost(message, tag = "11")]
+        DomNodeRemoved(super::DomNodeRemovedData),
+        #[prost(message, tag = "12")]
+        DomAttributeChanged(super::DomAttributeChangedData),
+        #[prost(message, tag = "13")]
+        DomAttributeRemoved(super::DomAttributeRemovedData),
+        #[prost(message, tag = "14")]
+        DomTextChanged(super::DomTextChangedData),
+        #[prost(message, tag = "15")]
+        DomNodeResized(super::DomNodeResizedData),
+        #[prost(message, tag = "16")]
+        DomNodePropertyChanged(super::DomNodePropertyChangedData),
+        #[prost(message, tag = "17")]
+        Asset(super::AssetData),
+        #[prost(message, tag = "18")]
+        AdoptedStyleSheetsChanged(super::AdoptedStyleSheetsChangedData),
+        #[prost(message, tag = "19")]
+        NewAdoptedStyleSheet(super::NewAdoptedStyleSheetData),
+        #[prost(message, tag = "20")]
+        ElementScrolled(super::ElementScrolledData),
+        #[prost(message, tag = "21")]
+        ElementBlurred(super::ElementBlurredData),
+        #[prost(message, tag = "22")]
+        WindowFocused(super::WindowFocusedData),
+        #[prost(message, tag = "23")]
+        WindowBlurred(super::WindowBlurredData),
+        #[prost(message, tag = "24")]
+        StyleSheetRuleInserted(super::StyleSheetRuleInsertedData),
+        #[prost(message, tag = "25")]
+        StyleSheetRuleDeleted(super::StyleSheetRuleDeletedData),
+        #[prost(message, tag = "26")]
+        StyleSheetReplaced(super::StyleSheetReplacedData),
+        #[prost(message, tag = "27")]
+        CanvasChanged(super::CanvasChangedData),
+        #[prost(message, tag = "28")]
+        DomNodePropertyTextChanged(super::DomNodePropertyTextChangedData),
+        #[prost(message, tag = "29")]
+        RecordingMetadata(super::RecordingMetadataData),
+        #[prost(message, tag = "30")]
+        AssetReference(super::AssetReferenceData),
+        #[prost(message, tag = "31")]
+        CacheManifest(super::CacheManifestData),
+        #[prost(message, tag = "32")]
+        PlaybackConfig(super::PlaybackConfigData),
+        #[prost(message, tag = "33")]
+        Heartbeat(super::HeartbeatData),
+        #[prost(message, tag = "34")]
+        PageError(super::PageErrorData),
+        #[prost(message, tag = "35")]
+        Annotation(super::AnnotationData),
+        #[prost(message, tag = "36")]
+        TraceContext(super::TraceContextData),
+        #[prost(message, tag = "37")]
+        RecordingPaused(super::RecordingPausedData),
+        #[prost(message, tag = "38")]
+        RecordingResumed(super::RecordingResumedData),
+        #[prost(message, tag = "39")]
+        ScrollOffsetChangedV2(super::ScrollOffsetChangedV2Data),
+        #[prost(message, tag = "40")]
+        MouseMovedV2(super::MouseMovedV2Data),
+        #[prost(message, tag = "41")]
+        MouseClickedV2(super::MouseClickedV2Data),
+        #[prost(message, tag = "42")]
+        ElementScrolledV2(super::ElementScrolledV2Data),
+        #[prost(message, tag = "43")]
+        KeyframeBegin(super::KeyframeBeginData),
+        #[prost(message, tag = "44")]
+        KeyframeChunk(super::KeyframeChunkData),
+        #[prost(message, tag = "45")]
+        KeyframeEnd(super::KeyframeEndData),
+        #[prost(message, tag = "46")]
+        NewAdoptedStyleSheetReference(super::NewAdoptedStyleSheetReferenceData),
+        #[prost(message, tag = "47")]
+        StyleSheetReplacedReference(super::StyleSheetReplacedReferenceData),
+        #[prost(message, tag = "48")]
+        CanvasChangedDelta(super::CanvasChangedDeltaData),
+        #[prost(message, tag = "49")]
+        HeartbeatPing(super::HeartbeatPingData),
+        #[prost(message, tag = "50")]
+        HeartbeatPong(super::HeartbeatPongData),
+        #[prost(message, tag = "51")]
+        SessionIdentity(super::SessionIdentityData),
+        #[prost(message, tag = "52")]
+        StopRecording(super::StopRecordingData),
+        #[prost(message, tag = "53")]
+        IdlePeriod(super::IdlePeriodData),
+        #[prost(message, tag = "54")]
+        RecordingTruncated(super::RecordingTruncatedData),
+        #[prost(message, tag = "55")]
+        CacheInventory(super::CacheInventoryData),
+        #[prost(message, tag = "56")]
+        RequestKeyframe(super::RequestKeyframeData),
+        #[prost(message, tag = "57")]
+        RecordingScope(super::RecordingScopeData),
+        #[prost(message, tag = "58")]
+        SessionLink(super::SessionLinkData),
+    }
+}
@@ -1,22 +1,41 @@
-use std::io;
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(feature = "tokio")]
 use std::pin::Pin;
+#[cfg(feature = "tokio")]
 use std::task::{Context, Poll};
-use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+#[cfg(feature = "tokio")]
 use tokio_stream::Stream;
 
 use crate::Frame;
-use crate::writer::{DCRR_MAGIC, DCRR_VERSION, FileHeader, HEADER_SIZE};
+use crate::checksum::verify;
+use crate::compression::decompress;
+use crate::index::{FrameIndex, INDEX_FOOTER_SIZE, INDEX_MARKER, IndexEntry, parse_footer};
+use crate::writer::{DCRR_MAGIC, DCRR_MIN_VERSION, DCRR_VERSION, FileHeader, HEADER_SIZE};
 use bincode::Options;
 
 /// Async stream-based reader for .dcrr file format and frame streams
+#[cfg(feature = "tokio")]
 pub struct FrameReader<R: AsyncRead + Unpin> {
     reader: R,
     header: Option<FileHeader>,
     buffer: Vec<u8>,
     header_read: bool,
     expect_header: bool,
+    position: u64,
+    upgrade: bool,
+    /// The frames ended at an index (see [`crate::index`])
+    ended: bool,
+    /// Frames start with a flag byte (see [`crate::compression`])
+    flagged: bool,
+    /// Frames end with a checksum (see [`crate::checksum`])
+    checksummed: bool,
+    /// Frames may end at an index (see [`crate::index`])
+    indexed: bool,
 }
 
+#[cfg(feature = "tokio")]
 impl<R: AsyncRead + Unpin> FrameReader<R> {
     /// Create a new async frame reader
     /// If expect_header is true, will try to read DCRR header first
@@ -27,14 +46,68 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
             buffer: Vec::new(),
             header_read: false,
             expect_header,
+            position: 0,
+            upgrade: false,
+            ended: false,
+            flagged: false,
+            checksummed: false,
+            indexed: false,
         }
     }
 
+    /// Return legacy scroll and mouse frames as their V2 versions (see
+    /// [`Frame::upgrade`]), so callers only handle the current ones
+    pub fn with_upgrade(mut self, upgrade: bool) -> Self {
+        self.upgrade = upgrade;
+        self
+    }
+
+    /// Check the checksum each frame of a stream without a header ends with
+    /// (see [`crate::checksum`]); a header, if read, says for itself
+    pub fn with_frame_checksums(mut self, checksums: bool) -> Self {
+        self.checksummed = checksums;
+        self
+    }
+
+    /// Let the frames of a stream without a header end at a v2 file's seek
+    /// index (see [`crate::index`]), as when the stream is such a file past
+    /// its header; a header, if read, says for itself
+    pub fn with_index(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
     /// Get the file header if one was read
     pub fn header(&self) -> Option<&FileHeader> {
         self.header.as_ref()
     }
 
+    /// Byte offset in the stream of the next unread frame (after the header,
+    /// if one was read)
+    ///
+    /// When `read_frame` fails to decode a frame, this is the offset of the
+    /// corrupt frame's length prefix.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Bytes read from the stream past the last complete frame
+    ///
+    /// When `read_frame` fails with `UnexpectedEof` because the stream ended
+    /// part way through a frame, these are the bytes received of that frame.
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The underlying source, positioned after the bytes already buffered
+    ///
+    /// Right after [`read_header`](Self::read_header) or
+    /// [`seek_to_timestamp`](Self::seek_to_timestamp) nothing is buffered,
+    /// so the source is at the first frame or the keyframe.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
     /// Read the header (for compatibility with old API)
     pub async fn read_header(&mut self) -> io::Result<FileHeader> {
         self.read_header_if_needed().await?;
@@ -57,89 +130,48 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
         let mut header_buf = [0u8; HEADER_SIZE];
         self.reader.read_exact(&mut header_buf).await?;
 
-        // Check magic bytes
-        if &header_buf[0..4] != &DCRR_MAGIC {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid DCRR magic bytes - not a .dcrr file",
-            ));
-        }
-
-        // Parse version
-        let version =
-            u32::from_be_bytes([header_buf[4], header_buf[5], header_buf[6], header_buf[7]]);
-
-        if version != DCRR_VERSION {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "Unsupported DCRR version: {} (expected {})",
-                    version, DCRR_VERSION
-                ),
-            ));
-        }
-
-        // Parse timestamp
-        let created_at = u64::from_be_bytes([
-            header_buf[8],
-            header_buf[9],
-            header_buf[10],
-            header_buf[11],
-            header_buf[12],
-            header_buf[13],
-            header_buf[14],
-            header_buf[15],
-        ]);
-
-        // Parse reserved bytes
-        let mut reserved = [0u8; 16];
-        reserved.copy_from_slice(&header_buf[16..32]);
-
-        let header = FileHeader {
-            magic: DCRR_MAGIC,
-            version,
-            created_at,
-            reserved,
-        };
+        let header = parse_header(&header_buf)?;
 
+        self.flagged = header.frame_compression();
+        self.checksummed = header.frame_checksums();
+        self.indexed = header.version >= 2;
         self.header = Some(header);
         self.header_read = true;
+        self.position = HEADER_SIZE as u64;
         Ok(())
     }
 
     async fn try_read_frame(&mut self) -> io::Result<Option<Frame>> {
-        let config = bincode::DefaultOptions::new()
-            .with_big_endian()
-            .with_fixint_encoding();
-
         // Read chunks until we have enough data for the length and the frame
         let mut temp_buf = [0u8; 4096];
 
         loop {
+            if self.ended {
+                return Ok(None);
+            }
+
             // Check if we have at least the length prefix (4 bytes)
             if self.buffer.len() >= 4 {
                 // Peek at the length
                 let len_bytes = [self.buffer[0], self.buffer[1], self.buffer[2], self.buffer[3]];
+                if u32::from_be_bytes(len_bytes) == INDEX_MARKER {
+                    if !self.indexed {
+                        return Err(unexpected_index());
+                    }
+                    self.ended = true;
+                    continue;
+                }
                 let frame_len = u32::from_be_bytes(len_bytes) as usize;
 
                 // Check if we have the full frame
                 if self.buffer.len() >= 4 + frame_len {
                     // We have the full frame!
-                    let frame_data = &self.buffer[4..4 + frame_len];
-                    
-                    match config.deserialize::<Frame>(frame_data) {
-                        Ok(frame) => {
-                            // Success! Remove length + frame from buffer
-                            self.buffer.drain(..4 + frame_len);
-                            return Ok(Some(frame));
-                        }
-                        Err(e) => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("Failed to decode frame: {}", e),
-                            ));
-                        }
-                    }
+                    let frame = decode_frame(&self.buffer[4..4 + frame_len], self.flagged, self.checksummed)?;
+
+                    // Success! Remove length + frame from buffer
+                    self.buffer.drain(..4 + frame_len);
+                    self.position += (4 + frame_len) as u64;
+                    return Ok(Some(if self.upgrade { frame.upgrade() } else { frame }));
                 }
             }
 
@@ -165,6 +197,53 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
     }
 }
 
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + AsyncSeek + Unpin> FrameReader<R> {
+    /// Read the file's seek index, if it has one, leaving the reader where it was
+    pub async fn read_index(&mut self) -> io::Result<Option<FrameIndex>> {
+        self.read_header_if_needed().await?;
+        let resume = self.reader.stream_position().await?;
+        let len = self.reader.seek(SeekFrom::End(0)).await?;
+        let index = match index_bounds(len) {
+            Some(footer_at) => {
+                let mut footer = [0u8; INDEX_FOOTER_SIZE as usize];
+                self.reader.seek(SeekFrom::Start(footer_at)).await?;
+                self.reader.read_exact(&mut footer).await?;
+                match parse_footer(&footer).filter(|&frames_end| (HEADER_SIZE as u64..footer_at).contains(&frames_end)) {
+                    Some(frames_end) => {
+                        let mut trailer = vec![0u8; (footer_at - frames_end) as usize];
+                        self.reader.seek(SeekFrom::Start(frames_end)).await?;
+                        self.reader.read_exact(&mut trailer).await?;
+                        FrameIndex::parse(&trailer, frames_end)
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        self.reader.seek(SeekFrom::Start(resume)).await?;
+        Ok(index)
+    }
+
+    /// Move to the last keyframe at or before `timestamp` (see
+    /// [`FrameIndex::entry_at`]), so the next frame read is that keyframe
+    ///
+    /// Returns None, leaving the reader where it was, if the file has no
+    /// index or no keyframes. The reader must have been created at the start
+    /// of the file.
+    pub async fn seek_to_timestamp(&mut self, timestamp: u64) -> io::Result<Option<IndexEntry>> {
+        let Some(entry) = self.read_index().await?.and_then(|index| index.entry_at(timestamp)) else {
+            return Ok(None);
+        };
+        self.reader.seek(SeekFrom::Start(entry.offset)).await?;
+        self.buffer.clear();
+        self.position = entry.offset;
+        self.ended = false;
+        Ok(Some(entry))
+    }
+}
+
+#[cfg(feature = "tokio")]
 impl<R: AsyncRead + Unpin> Stream for FrameReader<R> {
     type Item = io::Result<Frame>;
 
@@ -189,3 +268,290 @@ impl<R: AsyncRead + Unpin> Stream for FrameReader<R> {
         boxed.as_mut().poll(cx)
     }
 }
+
+/// Blocking reader for .dcrr files and frame streams, for consumers without an async runtime
+///
+/// Reads the same format as the async `FrameReader`, one frame per call or as an iterator.
+pub struct SyncFrameReader<R: Read> {
+    reader: R,
+    header: Option<FileHeader>,
+    header_read: bool,
+    expect_header: bool,
+    position: u64,
+    upgrade: bool,
+    /// The frames ended at an index (see [`crate::index`])
+    ended: bool,
+    /// Frames start with a flag byte (see [`crate::compression`])
+    flagged: bool,
+    /// Frames end with a checksum (see [`crate::checksum`])
+    checksummed: bool,
+    /// Frames may end at an index (see [`crate::index`])
+    indexed: bool,
+}
+
+impl<R: Read> SyncFrameReader<R> {
+    /// Create a new blocking frame reader
+    /// If expect_header is true, will try to read DCRR header first
+    pub fn new(reader: R, expect_header: bool) -> Self {
+        Self {
+            reader,
+            header: None,
+            header_read: false,
+            expect_header,
+            position: 0,
+            upgrade: false,
+            ended: false,
+            flagged: false,
+            checksummed: false,
+            indexed: false,
+        }
+    }
+
+    /// Return legacy scroll and mouse frames as their V2 versions (see
+    /// [`Frame::upgrade`]), so callers only handle the current ones
+    pub fn with_upgrade(mut self, upgrade: bool) -> Self {
+        self.upgrade = upgrade;
+        self
+    }
+
+    /// Check the checksum each frame of a stream without a header ends with
+    /// (see [`crate::checksum`]); a header, if read, says for itself
+    pub fn with_frame_checksums(mut self, checksums: bool) -> Self {
+        self.checksummed = checksums;
+        self
+    }
+
+    /// Let the frames of a stream without a header end at a v2 file's seek
+    /// index (see [`crate::index`]), as when the stream is such a file past
+    /// its header; a header, if read, says for itself
+    pub fn with_index(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    /// Get the file header if one was read
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    /// Byte offset in the stream of the next unread frame
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read the header
+    pub fn read_header(&mut self) -> io::Result<FileHeader> {
+        self.read_header_if_needed()?;
+        self.header
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No header available"))
+    }
+
+    /// Read the next frame, or `None` at the end of the stream
+    pub fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        self.read_header_if_needed()?;
+        if self.ended {
+            return Ok(None);
+        }
+
+        let mut len_bytes = [0u8; 4];
+        let filled = read_up_to(&mut self.reader, &mut len_bytes)?;
+        if filled == 0 {
+            return Ok(None);
+        }
+        if filled < len_bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Incomplete frame at end of stream",
+            ));
+        }
+
+        if u32::from_be_bytes(len_bytes) == INDEX_MARKER {
+            if !self.indexed {
+                return Err(unexpected_index());
+            }
+            self.ended = true;
+            return Ok(None);
+        }
+
+        let frame_len = u32::from_be_bytes(len_bytes) as usize;
+        let mut frame_data = vec![0u8; frame_len];
+        self.reader.read_exact(&mut frame_data).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Incomplete frame at end of stream",
+            ),
+            _ => e,
+        })?;
+
+        let frame = decode_frame(&frame_data, self.flagged, self.checksummed)?;
+        self.position += (4 + frame_len) as u64;
+        Ok(Some(if self.upgrade { frame.upgrade() } else { frame }))
+    }
+
+    fn read_header_if_needed(&mut self) -> io::Result<()> {
+        if !self.expect_header || self.header_read {
+            return Ok(());
+        }
+
+        let mut header_buf = [0u8; HEADER_SIZE];
+        self.reader.read_exact(&mut header_buf)?;
+        let header = parse_header(&header_buf)?;
+        self.flagged = header.frame_compression();
+        self.checksummed = header.frame_checksums();
+        self.indexed = header.version >= 2;
+        self.header = Some(header);
+        self.header_read = true;
+        self.position = HEADER_SIZE as u64;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> SyncFrameReader<R> {
+    /// Read the file's seek index, if it has one, leaving the reader where it was
+    pub fn read_index(&mut self) -> io::Result<Option<FrameIndex>> {
+        self.read_header_if_needed()?;
+        let resume = self.reader.stream_position()?;
+        let len = self.reader.seek(SeekFrom::End(0))?;
+        let index = match index_bounds(len) {
+            Some(footer_at) => {
+                let mut footer = [0u8; INDEX_FOOTER_SIZE as usize];
+                self.reader.seek(SeekFrom::Start(footer_at))?;
+                self.reader.read_exact(&mut footer)?;
+                match parse_footer(&footer).filter(|&frames_end| (HEADER_SIZE as u64..footer_at).contains(&frames_end)) {
+                    Some(frames_end) => {
+                        let mut trailer = vec![0u8; (footer_at - frames_end) as usize];
+                        self.reader.seek(SeekFrom::Start(frames_end))?;
+                        self.reader.read_exact(&mut trailer)?;
+                        FrameIndex::parse(&trailer, frames_end)
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        self.reader.seek(SeekFrom::Start(resume))?;
+        Ok(index)
+    }
+
+    /// Move to the last keyframe at or before `timestamp` (see
+    /// [`FrameIndex::entry_at`]), so the next frame read is that keyframe
+    ///
+    /// Returns None, leaving the reader where it was, if the file has no
+    /// index or no keyframes. The reader must have been created at the start
+    /// of the file.
+    pub fn seek_to_timestamp(&mut self, timestamp: u64) -> io::Result<Option<IndexEntry>> {
+        let Some(entry) = self.read_index()?.and_then(|index| index.entry_at(timestamp)) else {
+            return Ok(None);
+        };
+        self.reader.seek(SeekFrom::Start(entry.offset))?;
+        self.position = entry.offset;
+        self.ended = false;
+        Ok(Some(entry))
+    }
+}
+
+impl<R: Read> Iterator for SyncFrameReader<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Fill `buf` as far as the stream allows, returning how many bytes were read
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// The error for an index marker in a stream that has no index, where it
+/// is a frame length no writer produces
+fn unexpected_index() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Index marker in a stream without an index")
+}
+
+/// Where the index footer starts in a file of `len` bytes, if it is long
+/// enough to have one
+fn index_bounds(len: u64) -> Option<u64> {
+    len.checked_sub(INDEX_FOOTER_SIZE).filter(|&footer_at| footer_at > HEADER_SIZE as u64)
+}
+
+/// Parse and check a DCRR file header
+pub(crate) fn parse_header(header_buf: &[u8; HEADER_SIZE]) -> io::Result<FileHeader> {
+    // Check magic bytes
+    if header_buf[0..4] != DCRR_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid DCRR magic bytes - not a .dcrr file",
+        ));
+    }
+
+    // Parse version
+    let version =
+        u32::from_be_bytes([header_buf[4], header_buf[5], header_buf[6], header_buf[7]]);
+
+    if !(DCRR_MIN_VERSION..=DCRR_VERSION).contains(&version) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Unsupported DCRR version: {} (expected {} to {})",
+                version, DCRR_MIN_VERSION, DCRR_VERSION
+            ),
+        ));
+    }
+
+    // Parse timestamp
+    let created_at = u64::from_be_bytes([
+        header_buf[8],
+        header_buf[9],
+        header_buf[10],
+        header_buf[11],
+        header_buf[12],
+        header_buf[13],
+        header_buf[14],
+        header_buf[15],
+    ]);
+
+    // Parse reserved bytes
+    let mut reserved = [0u8; 16];
+    reserved.copy_from_slice(&header_buf[16..32]);
+
+    Ok(FileHeader {
+        magic: DCRR_MAGIC,
+        version,
+        created_at,
+        reserved,
+    })
+}
+
+/// Decode one frame (without its length prefix), which starts with a flag
+/// byte if `flagged` and ends with a checksum if `checksummed`
+pub(crate) fn decode_frame(frame_data: &[u8], flagged: bool, checksummed: bool) -> io::Result<Frame> {
+    let frame_data = match checksummed {
+        true => verify(frame_data)?,
+        false => frame_data,
+    };
+    let payload = match flagged {
+        true => decompress(frame_data)?,
+        false => frame_data.into(),
+    };
+    bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding()
+        .deserialize::<Frame>(&payload)
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to decode frame: {}", e),
+            )
+        })
+}
@@ -0,0 +1,166 @@
+//! Summary statistics of a recording: duration, frame counts and sizes by
+//! type, a histogram of frame sizes, and inline assets
+
+use crate::frame::Frame;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Count and encoded size of the frames of one type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeStats {
+    pub count: u64,
+    /// Encoded bytes, including each frame's length prefix
+    pub bytes: u64,
+}
+
+/// Frame counts by encoded size, in power-of-two buckets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    /// Frames of at most `2^i` bytes (and more than `2^(i-1)`), by `i`
+    counts: Vec<u64>,
+}
+
+impl SizeHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a frame of `size` bytes
+    pub fn add(&mut self, size: u64) {
+        let bucket = size.max(1).next_power_of_two().trailing_zeros() as usize;
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+    }
+
+    /// Non-empty buckets, smallest first, as (largest size, count)
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| (1 << bucket, *count))
+    }
+}
+
+/// An inline Asset frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub asset_id: u32,
+    pub url: String,
+    pub mime: Option<String>,
+    pub size: usize,
+}
+
+/// Accumulates [`TypeStats`] and assets frame by frame
+#[derive(Debug, Default)]
+pub struct RecordingStats {
+    pub frame_count: u64,
+    pub first_timestamp: Option<u64>,
+    pub last_timestamp: Option<u64>,
+    pub by_type: BTreeMap<&'static str, TypeStats>,
+    /// Encoded sizes of all frames
+    pub sizes: SizeHistogram,
+    /// Inline assets in recording order
+    pub assets: Vec<AssetInfo>,
+    /// Bytes of inline assets whose content already appeared earlier
+    pub duplicate_asset_bytes: u64,
+    asset_hashes: HashSet<u64>,
+}
+
+impl RecordingStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `frame`, which took `encoded_size` bytes in the stream
+    pub fn push(&mut self, frame: &Frame, encoded_size: u64) {
+        self.frame_count += 1;
+        let entry = self.by_type.entry(frame.type_name()).or_default();
+        entry.count += 1;
+        entry.bytes += encoded_size;
+        self.sizes.add(encoded_size);
+
+        match frame {
+            Frame::Timestamp(ts) => {
+                self.first_timestamp.get_or_insert(ts.timestamp);
+                self.last_timestamp = Some(ts.timestamp);
+            }
+            Frame::Asset(asset) => {
+                let mut hasher = DefaultHasher::new();
+                asset.buf.hash(&mut hasher);
+                if !asset.buf.is_empty() && !self.asset_hashes.insert(hasher.finish()) {
+                    self.duplicate_asset_bytes += asset.buf.len() as u64;
+                }
+                self.assets.push(AssetInfo {
+                    asset_id: asset.asset_id,
+                    url: asset.url.clone(),
+                    mime: asset.mime.clone(),
+                    size: asset.buf.len(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Milliseconds between the first and last Timestamp frames
+    pub fn duration_ms(&self) -> Option<u64> {
+        Some(self.last_timestamp?.saturating_sub(self.first_timestamp?))
+    }
+
+    /// Encoded bytes of all frames
+    pub fn total_bytes(&self) -> u64 {
+        self.by_type.values().map(|stats| stats.bytes).sum()
+    }
+
+    /// Frame types, largest total size first
+    pub fn types_by_size(&self) -> Vec<(&'static str, TypeStats)> {
+        let mut sorted: Vec<_> = self.by_type.iter().map(|(name, stats)| (*name, *stats)).collect();
+        sorted.sort_by_key(|(name, stats)| (std::cmp::Reverse(stats.bytes), *name));
+        sorted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuilder;
+
+    #[test]
+    fn test_counts_types_duration_and_duplicate_assets() {
+        let frames = FrameBuilder::new()
+            .at(1_000)
+            .asset(1, "https://example.com/a.png", "image/png", b"png")
+            .asset(2, "https://example.com/b.png", "image/png", b"png")
+            .wait(2_500)
+            .click(1, 1)
+            .build();
+
+        let mut stats = RecordingStats::new();
+        for frame in &frames {
+            stats.push(frame, 10);
+        }
+        assert_eq!(stats.frame_count, 5);
+        assert_eq!(stats.duration_ms(), Some(2_500));
+        assert_eq!(stats.total_bytes(), 50);
+        assert_eq!(stats.by_type["Asset"], TypeStats { count: 2, bytes: 20 });
+        assert_eq!(stats.types_by_size()[0].0, "Asset");
+        assert_eq!(stats.assets.len(), 2);
+        assert_eq!(stats.duplicate_asset_bytes, 3);
+        assert_eq!(stats.sizes.buckets().collect::<Vec<_>>(), [(16, 5)]);
+    }
+
+    #[test]
+    fn test_size_histogram_buckets() {
+        let mut sizes = SizeHistogram::new();
+        for size in [0, 1, 2, 3, 4, 5, 1000, 1024, 1025] {
+            sizes.add(size);
+        }
+        assert_eq!(
+            sizes.buckets().collect::<Vec<_>>(),
+            [(1, 2), (2, 1), (4, 2), (8, 1), (1024, 2), (2048, 1)]
+        );
+    }
+}
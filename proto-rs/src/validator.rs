@@ -0,0 +1,127 @@
+use crate::asset_refs::{asset_references, for_each_asset_text};
+use crate::frame::Frame;
+use crate::vdom_engine::{VDomEngine, VDomError};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// A semantic problem found in a frame stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// A Timestamp frame went backwards
+    NonMonotonicTimestamp {
+        frame_index: u64,
+        previous: u64,
+        timestamp: u64,
+    },
+    /// A DOM frame could not be applied (unknown node id, duplicate id, ...)
+    Dom { frame_index: u64, error: VDomError },
+    /// An `asset:N` placeholder was never defined by an Asset or AssetReference frame
+    UnresolvedAsset {
+        /// The first frame that referenced the asset
+        frame_index: u64,
+        asset_id: u32,
+    },
+}
+
+impl ValidationIssue {
+    /// Index of the frame the issue was found at
+    pub fn frame_index(&self) -> u64 {
+        match self {
+            ValidationIssue::NonMonotonicTimestamp { frame_index, .. }
+            | ValidationIssue::Dom { frame_index, .. }
+            | ValidationIssue::UnresolvedAsset { frame_index, .. } => *frame_index,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::NonMonotonicTimestamp { previous, timestamp, .. } => {
+                write!(f, "timestamp {} is earlier than previous timestamp {}", timestamp, previous)
+            }
+            ValidationIssue::Dom { error, .. } => write!(f, "{}", error),
+            ValidationIssue::UnresolvedAsset { asset_id, .. } => {
+                write!(f, "asset:{} is referenced but never defined", asset_id)
+            }
+        }
+    }
+}
+
+/// Checks a frame stream for semantic consistency
+///
+/// - DOM mutations must reference node ids that exist (replayed with [`VDomEngine`])
+/// - Timestamps must not go backwards
+/// - Every `asset:N` placeholder must be defined by an Asset or AssetReference
+///   frame somewhere in the stream (assets usually follow the keyframe that
+///   references them, so this is checked in [`FrameValidator::finish`])
+#[derive(Debug, Default)]
+pub struct FrameValidator {
+    engine: VDomEngine,
+    frame_index: u64,
+    last_timestamp: Option<u64>,
+    defined_assets: HashSet<u32>,
+    /// Referenced asset id -> first referencing frame index
+    referenced_assets: BTreeMap<u32, u64>,
+}
+
+impl FrameValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of frames checked so far
+    pub fn frame_count(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// Check the next frame, returning any issues it introduces
+    pub fn push_frame(&mut self, frame: &Frame) -> Vec<ValidationIssue> {
+        let frame_index = self.frame_index;
+        self.frame_index += 1;
+        let mut issues = Vec::new();
+
+        match frame {
+            Frame::Timestamp(ts) => {
+                if let Some(previous) = self.last_timestamp
+                    && ts.timestamp < previous
+                {
+                    issues.push(ValidationIssue::NonMonotonicTimestamp {
+                        frame_index,
+                        previous,
+                        timestamp: ts.timestamp,
+                    });
+                }
+                self.last_timestamp = Some(ts.timestamp);
+            }
+            Frame::Asset(asset) => {
+                self.defined_assets.insert(asset.asset_id);
+            }
+            Frame::AssetReference(asset) => {
+                self.defined_assets.insert(asset.asset_id);
+            }
+            _ => {}
+        }
+
+        let mut references = Vec::new();
+        for_each_asset_text(frame, &mut |text| asset_references(text, &mut references));
+        for asset_id in references {
+            self.referenced_assets.entry(asset_id).or_insert(frame_index);
+        }
+
+        if let Err(error) = self.engine.apply(frame) {
+            issues.push(ValidationIssue::Dom { frame_index, error });
+        }
+
+        issues
+    }
+
+    /// Finish validation, returning issues only detectable at end of stream
+    pub fn finish(self) -> Vec<ValidationIssue> {
+        self.referenced_assets
+            .into_iter()
+            .filter(|(asset_id, _)| !self.defined_assets.contains(asset_id))
+            .map(|(asset_id, frame_index)| ValidationIssue::UnresolvedAsset { frame_index, asset_id })
+            .collect()
+    }
+}
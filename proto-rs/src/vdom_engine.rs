@@ -0,0 +1,402 @@
+use crate::frame::{Frame, TextOperationData};
+use crate::vdom::{VDocument, VNode, VStyleSheet};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Errors produced while applying frames to a [`VDomEngine`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VDomError {
+    /// A mutation frame arrived before any Keyframe
+    NoDocument,
+    /// A frame referenced a node id that is not in the document
+    UnknownNode(u32),
+    /// A DomNodeAdded frame reused a node id that is already in the document
+    DuplicateNode(u32),
+    /// A node was added to a parent that cannot have children
+    InvalidParent(u32),
+    /// A text operation was out of range for the node's content
+    InvalidTextOperation { node_id: u32, index: u32 },
+    /// A text operation targeted a node without text content
+    NotTextNode(u32),
+}
+
+impl fmt::Display for VDomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VDomError::NoDocument => write!(f, "mutation received before the first keyframe"),
+            VDomError::UnknownNode(id) => write!(f, "unknown node id {}", id),
+            VDomError::DuplicateNode(id) => write!(f, "node id {} is already in the document", id),
+            VDomError::InvalidParent(id) => write!(f, "node {} cannot have children", id),
+            VDomError::InvalidTextOperation { node_id, index } => {
+                write!(f, "text operation at index {} is out of range for node {}", index, node_id)
+            }
+            VDomError::NotTextNode(id) => write!(f, "node {} has no text content", id),
+        }
+    }
+}
+
+impl std::error::Error for VDomError {}
+
+/// A node stored in the engine arena. Children are tracked by id so that
+/// mutations can be applied without walking the tree.
+#[derive(Debug, Clone)]
+struct EngineNode {
+    parent: Option<u32>,
+    /// The node with its `children` emptied (for elements)
+    node: VNode,
+    children: Vec<u32>,
+}
+
+/// Replays keyframes and DOM mutation frames into an in-memory virtual DOM
+///
+/// This mirrors what the player does when materializing a recording, so it can
+/// be used server-side to inspect the page state at any point in a recording.
+#[derive(Debug, Clone, Default)]
+pub struct VDomEngine {
+    document_id: Option<u32>,
+    root_children: Vec<u32>,
+    nodes: HashMap<u32, EngineNode>,
+    properties: HashMap<u32, BTreeMap<String, String>>,
+    adopted_style_sheets: Vec<VStyleSheet>,
+    viewport: Option<(u32, u32)>,
+}
+
+impl VDomEngine {
+    /// Create an empty engine (no document until a Keyframe is applied)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a keyframe has been applied
+    pub fn has_document(&self) -> bool {
+        self.document_id.is_some()
+    }
+
+    /// Whether the given node id exists in the current document
+    pub fn contains(&self, node_id: u32) -> bool {
+        self.nodes.contains_key(&node_id) || self.document_id == Some(node_id)
+    }
+
+    /// Number of nodes in the current document (excluding the document itself)
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The tag name of an element in the current document
+    pub fn tag(&self, node_id: u32) -> Option<&str> {
+        match &self.nodes.get(&node_id)?.node {
+            VNode::Element(element) => Some(&element.tag),
+            _ => None,
+        }
+    }
+
+    /// The current viewport size, from the last Keyframe or ViewportResized frame
+    pub fn viewport(&self) -> Option<(u32, u32)> {
+        self.viewport
+    }
+
+    /// Get a node property (e.g. an input's `value`) set via property frames
+    pub fn property(&self, node_id: u32, name: &str) -> Option<&str> {
+        self.properties
+            .get(&node_id)
+            .and_then(|props| props.get(name))
+            .map(String::as_str)
+    }
+
+    /// All node properties set via property frames, as (node id, name, value)
+    pub fn properties(&self) -> impl Iterator<Item = (u32, &str, &str)> {
+        self.properties.iter().flat_map(|(node_id, props)| {
+            props
+                .iter()
+                .map(move |(name, value)| (*node_id, name.as_str(), value.as_str()))
+        })
+    }
+
+    /// Apply a frame to the document
+    ///
+    /// Frames that do not affect the DOM structure are ignored.
+    pub fn apply(&mut self, frame: &Frame) -> Result<(), VDomError> {
+        match frame {
+            Frame::Keyframe(keyframe) => {
+                self.load_document(&keyframe.document);
+                self.viewport = Some((keyframe.viewport_width, keyframe.viewport_height));
+                Ok(())
+            }
+            Frame::KeyframeBegin(begin) => {
+                self.load_document(&VDocument {
+                    id: begin.document_id,
+                    adopted_style_sheets: begin.adopted_style_sheets.clone(),
+                    children: Vec::new(),
+                });
+                self.viewport = Some((begin.viewport_width, begin.viewport_height));
+                Ok(())
+            }
+            Frame::KeyframeChunk(chunk) => {
+                self.require_document()?;
+                for node in &chunk.nodes {
+                    self.insert_node(chunk.parent_node_id, usize::MAX, node)?;
+                }
+                Ok(())
+            }
+            Frame::ViewportResized(resized) => {
+                self.viewport = Some((resized.width, resized.height));
+                Ok(())
+            }
+            Frame::DomNodeAdded(added) => {
+                self.require_document()?;
+                self.insert_node(added.parent_node_id, added.index as usize, &added.node)
+            }
+            Frame::DomNodeRemoved(removed) => {
+                self.require_document()?;
+                self.remove_node(removed.node_id)
+            }
+            Frame::DomAttributeChanged(changed) => {
+                let attrs = self.element_attrs_mut(changed.node_id)?;
+                match attrs.iter_mut().find(|(name, _)| *name == changed.attribute_name) {
+                    Some(attr) => attr.1 = changed.attribute_value.clone(),
+                    None => attrs.push((changed.attribute_name.clone(), changed.attribute_value.clone())),
+                }
+                Ok(())
+            }
+            Frame::DomAttributeRemoved(removed) => {
+                let attrs = self.element_attrs_mut(removed.node_id)?;
+                attrs.retain(|(name, _)| *name != removed.attribute_name);
+                Ok(())
+            }
+            Frame::DomTextChanged(changed) => {
+                self.require_document()?;
+                let entry = self
+                    .nodes
+                    .get_mut(&changed.node_id)
+                    .ok_or(VDomError::UnknownNode(changed.node_id))?;
+                let content = match &mut entry.node {
+                    VNode::Text(text) => &mut text.content,
+                    VNode::CData(cdata) => &mut cdata.content,
+                    VNode::Comment(comment) => &mut comment.content,
+                    VNode::ProcessingInstruction(pi) => &mut pi.data,
+                    _ => return Err(VDomError::NotTextNode(changed.node_id)),
+                };
+                *content = apply_text_operations(content, &changed.operations, changed.node_id)?;
+                Ok(())
+            }
+            Frame::DomNodePropertyChanged(changed) => {
+                self.require_node(changed.node_id)?;
+                self.properties
+                    .entry(changed.node_id)
+                    .or_default()
+                    .insert(changed.property_name.clone(), changed.property_value.clone());
+                Ok(())
+            }
+            Frame::DomNodePropertyTextChanged(changed) => {
+                self.require_node(changed.node_id)?;
+                let value = self
+                    .properties
+                    .entry(changed.node_id)
+                    .or_default()
+                    .entry(changed.property_name.clone())
+                    .or_default();
+                *value = apply_text_operations(value, &changed.operations, changed.node_id)?;
+                Ok(())
+            }
+            Frame::NewAdoptedStyleSheet(sheet) => {
+                self.adopted_style_sheets.retain(|s| s.id != sheet.style_sheet.id);
+                self.adopted_style_sheets.push(sheet.style_sheet.clone());
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Build a snapshot of the current document
+    pub fn document(&self) -> Option<VDocument> {
+        let id = self.document_id?;
+        Some(VDocument {
+            id,
+            adopted_style_sheets: self.adopted_style_sheets.clone(),
+            children: self
+                .root_children
+                .iter()
+                .filter_map(|child| self.build_node(*child))
+                .collect(),
+        })
+    }
+
+    fn require_document(&self) -> Result<(), VDomError> {
+        if self.document_id.is_some() {
+            Ok(())
+        } else {
+            Err(VDomError::NoDocument)
+        }
+    }
+
+    fn require_node(&self, node_id: u32) -> Result<(), VDomError> {
+        self.require_document()?;
+        if self.contains(node_id) {
+            Ok(())
+        } else {
+            Err(VDomError::UnknownNode(node_id))
+        }
+    }
+
+    fn element_attrs_mut(&mut self, node_id: u32) -> Result<&mut Vec<(String, String)>, VDomError> {
+        self.require_document()?;
+        match self.nodes.get_mut(&node_id) {
+            Some(EngineNode {
+                node: VNode::Element(element),
+                ..
+            }) => Ok(&mut element.attrs),
+            Some(_) => Err(VDomError::InvalidParent(node_id)),
+            None => Err(VDomError::UnknownNode(node_id)),
+        }
+    }
+
+    fn load_document(&mut self, document: &VDocument) {
+        self.document_id = Some(document.id);
+        self.nodes.clear();
+        self.properties.clear();
+        self.root_children.clear();
+        self.adopted_style_sheets = document.adopted_style_sheets.clone();
+        for child in &document.children {
+            let id = self.store_subtree(child, None);
+            self.root_children.push(id);
+        }
+    }
+
+    /// Store a node and its descendants in the arena, returning the node id
+    fn store_subtree(&mut self, node: &VNode, parent: Option<u32>) -> u32 {
+        let id = node_id(node);
+        let mut stored = node.clone();
+        let mut children = Vec::new();
+        if let VNode::Element(element) = &mut stored {
+            for child in std::mem::take(&mut element.children) {
+                children.push(self.store_subtree(&child, Some(id)));
+            }
+        }
+        self.nodes.insert(
+            id,
+            EngineNode {
+                parent,
+                node: stored,
+                children,
+            },
+        );
+        id
+    }
+
+    fn insert_node(&mut self, parent_id: u32, index: usize, node: &VNode) -> Result<(), VDomError> {
+        let id = node_id(node);
+        if self.contains(id) {
+            return Err(VDomError::DuplicateNode(id));
+        }
+
+        if self.document_id == Some(parent_id) {
+            let index = index.min(self.root_children.len());
+            let stored = self.store_subtree(node, None);
+            self.root_children.insert(index, stored);
+            return Ok(());
+        }
+
+        match self.nodes.get(&parent_id) {
+            Some(EngineNode {
+                node: VNode::Element(_),
+                ..
+            }) => {}
+            Some(_) => return Err(VDomError::InvalidParent(parent_id)),
+            None => return Err(VDomError::UnknownNode(parent_id)),
+        }
+
+        let stored = self.store_subtree(node, Some(parent_id));
+        let parent = self.nodes.get_mut(&parent_id).expect("parent checked above");
+        let index = index.min(parent.children.len());
+        parent.children.insert(index, stored);
+        Ok(())
+    }
+
+    fn remove_node(&mut self, node_id: u32) -> Result<(), VDomError> {
+        let entry = self
+            .nodes
+            .get(&node_id)
+            .ok_or(VDomError::UnknownNode(node_id))?;
+
+        match entry.parent {
+            Some(parent_id) => {
+                if let Some(parent) = self.nodes.get_mut(&parent_id) {
+                    parent.children.retain(|child| *child != node_id);
+                }
+            }
+            None => self.root_children.retain(|child| *child != node_id),
+        }
+
+        let mut pending = vec![node_id];
+        while let Some(id) = pending.pop() {
+            if let Some(removed) = self.nodes.remove(&id) {
+                pending.extend(removed.children);
+            }
+            self.properties.remove(&id);
+        }
+        Ok(())
+    }
+
+    fn build_node(&self, id: u32) -> Option<VNode> {
+        let entry = self.nodes.get(&id)?;
+        let mut node = entry.node.clone();
+        if let VNode::Element(element) = &mut node {
+            element.children = entry
+                .children
+                .iter()
+                .filter_map(|child| self.build_node(*child))
+                .collect();
+        }
+        Some(node)
+    }
+}
+
+/// The id of any VNode variant
+pub fn node_id(node: &VNode) -> u32 {
+    match node {
+        VNode::Element(n) => n.id,
+        VNode::Text(n) => n.id,
+        VNode::CData(n) => n.id,
+        VNode::Comment(n) => n.id,
+        VNode::DocType(n) => n.id,
+        VNode::ProcessingInstruction(n) => n.id,
+    }
+}
+
+/// Apply insert/remove operations to a string
+///
+/// Indices are UTF-16 code units, matching the JavaScript strings the recorder
+/// diffs against.
+pub fn apply_text_operations(
+    content: &str,
+    operations: &[TextOperationData],
+    node_id: u32,
+) -> Result<String, VDomError> {
+    let mut units: Vec<u16> = content.encode_utf16().collect();
+    for operation in operations {
+        match operation {
+            TextOperationData::Insert(insert) => {
+                let index = insert.index as usize;
+                if index > units.len() {
+                    return Err(VDomError::InvalidTextOperation {
+                        node_id,
+                        index: insert.index,
+                    });
+                }
+                units.splice(index..index, insert.text.encode_utf16());
+            }
+            TextOperationData::Remove(remove) => {
+                let start = remove.index as usize;
+                let end = start + remove.length as usize;
+                if end > units.len() {
+                    return Err(VDomError::InvalidTextOperation {
+                        node_id,
+                        index: remove.index,
+                    });
+                }
+                units.drain(start..end);
+            }
+        }
+    }
+    Ok(String::from_utf16_lossy(&units))
+}
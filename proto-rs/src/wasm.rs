@@ -0,0 +1,177 @@
+//! wasm-bindgen wrappers around the frame reader and writer
+//!
+//! Built with `wasm-pack build proto-rs -- --no-default-features --features wasm`,
+//! this gives the browser recorder and player the same encoder and decoder
+//! as the server. Frames cross the boundary as JSON in the shape `dcrr
+//! convert` writes (`{"MouseMoved":{"x":10,"y":20}}`).
+//!
+//! The reader is push-based: the caller feeds it chunks as they arrive (from
+//! a WebSocket or a fetch body) and pulls out every complete frame.
+
+use crate::Frame;
+use crate::index::INDEX_MARKER;
+use crate::reader::{decode_frame, parse_header};
+use crate::writer::{FileHeader, FrameWriter, HEADER_SIZE};
+use std::io;
+use wasm_bindgen::prelude::*;
+
+/// Encodes frames given as JSON
+#[wasm_bindgen(js_name = FrameWriter)]
+pub struct WasmFrameWriter {
+    writer: FrameWriter<Vec<u8>>,
+}
+
+#[wasm_bindgen(js_class = FrameWriter)]
+impl WasmFrameWriter {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            writer: FrameWriter::new(Vec::new()),
+        }
+    }
+
+    /// Start a .dcrr file; `created_at` is in ms since the epoch (`Date.now()`)
+    #[wasm_bindgen(js_name = writeHeader)]
+    pub fn write_header(&mut self, created_at: f64) -> Result<(), JsError> {
+        Ok(self.writer.write_header(&FileHeader::with_timestamp(created_at as u64))?)
+    }
+
+    /// Encode one frame
+    #[wasm_bindgen(js_name = writeFrame)]
+    pub fn write_frame(&mut self, json: &str) -> Result<(), JsError> {
+        Ok(self.writer.write_frame(&frame_from_json(json)?)?)
+    }
+
+    /// The bytes encoded since the last call
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(self.writer.get_mut())
+    }
+}
+
+/// Decodes frames from chunks of a .dcrr file or frame stream
+#[wasm_bindgen(js_name = FrameReader)]
+pub struct WasmFrameReader {
+    buffer: Vec<u8>,
+    expect_header: bool,
+    header: Option<FileHeader>,
+}
+
+#[wasm_bindgen(js_class = FrameReader)]
+impl WasmFrameReader {
+    /// `expect_header` is true for .dcrr files, false for raw frame streams
+    #[wasm_bindgen(constructor)]
+    pub fn new(expect_header: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            expect_header,
+            header: None,
+        }
+    }
+
+    /// Append the next chunk of input
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// The next complete frame as JSON, or undefined until more input arrives
+    #[wasm_bindgen(js_name = nextFrame)]
+    pub fn next_frame(&mut self) -> Result<Option<String>, JsError> {
+        match self.read_frame()? {
+            Some(frame) => Ok(Some(frame_to_json(&frame)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The header's creation time (ms since the epoch), once it has been read
+    #[wasm_bindgen(getter, js_name = createdAt)]
+    pub fn created_at(&self) -> Option<f64> {
+        self.header.as_ref().map(|header| header.created_at as f64)
+    }
+
+    /// Input not yet decoded; non-zero at the end of input means a truncated frame
+    #[wasm_bindgen(getter, js_name = bufferedBytes)]
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl WasmFrameReader {
+    fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        if self.expect_header && self.header.is_none() {
+            let Some(header) = self.buffer.first_chunk::<HEADER_SIZE>() else {
+                return Ok(None);
+            };
+            self.header = Some(parse_header(header)?);
+            self.buffer.drain(..HEADER_SIZE);
+        }
+
+        let Some(len_bytes) = self.buffer.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        // The rest is a v2 file's seek index
+        if u32::from_be_bytes(*len_bytes) == INDEX_MARKER {
+            self.buffer.clear();
+            return Ok(None);
+        }
+        let frame_len = u32::from_be_bytes(*len_bytes) as usize;
+        if self.buffer.len() < 4 + frame_len {
+            return Ok(None);
+        }
+        let flagged = self.header.as_ref().is_some_and(FileHeader::frame_compression);
+        let checksummed = self.header.as_ref().is_some_and(FileHeader::frame_checksums);
+        let frame = decode_frame(&self.buffer[4..4 + frame_len], flagged, checksummed)?;
+        self.buffer.drain(..4 + frame_len);
+        Ok(Some(frame))
+    }
+}
+
+fn frame_from_json(json: &str) -> io::Result<Frame> {
+    serde_json::from_str(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn frame_to_json(frame: &Frame) -> io::Result<String> {
+    serde_json::to_string(frame).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuilder;
+
+    #[test]
+    fn test_round_trip_in_small_chunks() {
+        let frames = FrameBuilder::keyframe()
+            .with_element("div#root", |root| root.text("Hello"))
+            .at(1_000)
+            .click(10, 20)
+            .build();
+
+        let mut writer = WasmFrameWriter::new();
+        writer.write_header(1_722_550_000_000.0).unwrap();
+        for frame in &frames {
+            writer.write_frame(&frame_to_json(frame).unwrap()).unwrap();
+        }
+        let bytes = writer.take();
+        assert!(writer.take().is_empty());
+
+        let mut reader = WasmFrameReader::new(true);
+        let mut decoded = Vec::new();
+        for chunk in bytes.chunks(7) {
+            reader.push(chunk);
+            while let Some(json) = reader.next_frame().unwrap() {
+                decoded.push(frame_from_json(&json).unwrap());
+            }
+        }
+        assert_eq!(decoded, frames);
+        assert_eq!(reader.created_at(), Some(1_722_550_000_000.0));
+        assert_eq!(reader.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_reader_rejects_bad_magic() {
+        let mut reader = WasmFrameReader::new(true);
+        reader.push(&[0; HEADER_SIZE]);
+        assert_eq!(reader.read_frame().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,10 +1,16 @@
 use crate::Frame;
+use crate::checksum::{HEADER_FLAG_FRAME_CHECKSUM, checksum};
+use crate::compression::{DEFAULT_COMPRESSION_LEVEL, HEADER_FLAG_FRAME_COMPRESSION, compress};
+use crate::index::IndexBuilder;
 use bincode::Options;
 use std::io::{self, Write};
 
 // File format constants
 pub const DCRR_MAGIC: [u8; 4] = [0x44, 0x43, 0x52, 0x52]; // "DCRR"
-pub const DCRR_VERSION: u32 = 1;
+/// Version 2 files may end with a seek index (see [`crate::index`])
+pub const DCRR_VERSION: u32 = 2;
+/// Oldest version readers accept
+pub const DCRR_MIN_VERSION: u32 = 1;
 pub const HEADER_SIZE: usize = 32;
 
 /// File header for .dcrr format
@@ -39,12 +45,56 @@ impl FileHeader {
             reserved: [0; 16],
         }
     }
+
+    /// Whether the file's frames may be zstd-compressed (see [`crate::compression`])
+    pub fn frame_compression(&self) -> bool {
+        self.reserved[0] & HEADER_FLAG_FRAME_COMPRESSION != 0
+    }
+
+    /// Let the writer of the file compress its frames
+    pub fn with_frame_compression(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.reserved[0] |= HEADER_FLAG_FRAME_COMPRESSION;
+        } else {
+            self.reserved[0] &= !HEADER_FLAG_FRAME_COMPRESSION;
+        }
+        self
+    }
+
+    /// Whether the file's frames end with a CRC32 (see [`crate::checksum`])
+    pub fn frame_checksums(&self) -> bool {
+        self.reserved[0] & HEADER_FLAG_FRAME_CHECKSUM != 0
+    }
+
+    /// Have the writer of the file end each frame with a CRC32
+    pub fn with_frame_checksums(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.reserved[0] |= HEADER_FLAG_FRAME_CHECKSUM;
+        } else {
+            self.reserved[0] &= !HEADER_FLAG_FRAME_CHECKSUM;
+        }
+        self
+    }
+}
+
+impl Default for FileHeader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Writer for .dcrr file format and frame streams
 pub struct FrameWriter<W: Write> {
     writer: W,
     header_written: bool,
+    /// Bytes written so far
+    position: u64,
+    index: IndexBuilder,
+    /// Set by a header with frame compression on
+    compress: bool,
+    compression_level: i32,
+    /// Set by a header with frame checksums on
+    checksums: bool,
 }
 
 impl<W: Write> FrameWriter<W> {
@@ -53,9 +103,20 @@ impl<W: Write> FrameWriter<W> {
         Self {
             writer,
             header_written: false,
+            position: 0,
+            index: IndexBuilder::default(),
+            compress: false,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            checksums: false,
         }
     }
 
+    /// zstd level for frames compressed once a header turns compression on
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.compression_level = level;
+        self
+    }
+
     /// Write file header (only for .dcrr file format)
     pub fn write_header(&mut self, header: &FileHeader) -> io::Result<()> {
         if self.header_written {
@@ -78,6 +139,9 @@ impl<W: Write> FrameWriter<W> {
         self.writer.write_all(&header.reserved)?;
 
         self.header_written = true;
+        self.compress = header.frame_compression();
+        self.checksums = header.frame_checksums();
+        self.position += HEADER_SIZE as u64;
         Ok(())
     }
 
@@ -91,12 +155,41 @@ impl<W: Write> FrameWriter<W> {
             .serialize(frame)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
+        // A flag byte first if the header allows compression
+        let (flag, encoded) = match self.compress {
+            true => compress(encoded, self.compression_level).map(|(flag, body)| (Some(flag), body))?,
+            false => (None, encoded),
+        };
+
+        // A checksum last if the header asks for one
+        let flag_bytes = flag.map(|flag| [flag]);
+        let flag_bytes = flag_bytes.as_ref().map_or(&[][..], |flag| &flag[..]);
+        let crc = self.checksums.then(|| checksum(&[flag_bytes, &encoded]));
+
         // Write frame length prefix (u32, big-endian)
-        let len = encoded.len() as u32;
+        let len = (flag_bytes.len() + encoded.len() + crc.map_or(0, |_| 4)) as u32;
         self.writer.write_all(&len.to_be_bytes())?;
 
         // Write frame data
+        self.writer.write_all(flag_bytes)?;
         self.writer.write_all(&encoded)?;
+        if let Some(crc) = crc {
+            self.writer.write_all(&crc.to_be_bytes())?;
+        }
+
+        self.index.push(frame, self.position);
+        self.position += 4 + len as u64;
+        Ok(())
+    }
+
+    /// End the frames with an index of the keyframes written, so readers of
+    /// seekable sources can start at a point in time (see [`crate::index`])
+    ///
+    /// Nothing may be written after the index.
+    pub fn write_index(&mut self) -> io::Result<()> {
+        let trailer = self.index.encode(self.position);
+        self.writer.write_all(&trailer)?;
+        self.position += trailer.len() as u64;
         Ok(())
     }
 
@@ -110,6 +203,11 @@ impl<W: Write> FrameWriter<W> {
         self.writer
     }
 
+    /// Get a mutable reference to the underlying writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
     /// Check if header has been written
     pub fn header_written(&self) -> bool {
         self.header_written
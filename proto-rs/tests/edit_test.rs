@@ -0,0 +1,95 @@
+use domcorder_proto::edit::*;
+use domcorder_proto::*;
+
+fn timestamp(timestamp: u64) -> Frame {
+    Frame::Timestamp(TimestampData { timestamp })
+}
+
+fn keyframe(src: &str) -> Frame {
+    Frame::Keyframe(KeyframeData {
+        document: VDocument {
+            id: 0,
+            adopted_style_sheets: vec![],
+            children: vec![VNode::Element(VElement {
+                id: 1,
+                tag: "img".to_string(),
+                ns: None,
+                attrs: vec![("src".to_string(), src.to_string())],
+                children: vec![],
+            })],
+        },
+        viewport_width: 800,
+        viewport_height: 600,
+    })
+}
+
+fn set_src(src: &str) -> Frame {
+    Frame::DomAttributeChanged(DomAttributeChangedData {
+        node_id: 1,
+        attribute_name: "src".to_string(),
+        attribute_value: src.to_string(),
+    })
+}
+
+fn asset(asset_id: u32) -> Frame {
+    Frame::AssetReference(AssetReferenceData {
+        asset_id,
+        url: "https://example.com/a.png".to_string(),
+        hash: "hash".to_string(),
+        mime: None,
+    })
+}
+
+#[test]
+fn trim_synthesizes_keyframe_at_start() {
+    let frames = vec![
+        timestamp(1000),
+        keyframe("asset:1"),
+        asset(1),
+        timestamp(2000),
+        set_src("asset:1?v=2"),
+        timestamp(3000),
+        Frame::MouseClicked(MouseClickedData { x: 1, y: 2 }),
+        timestamp(4000),
+        Frame::MouseClicked(MouseClickedData { x: 3, y: 4 }),
+    ];
+    let mut trimmer = Trimmer::new(2000, Some(2500));
+    let output: Vec<Frame> = frames.into_iter().flat_map(|frame| trimmer.push(frame)).collect();
+
+    assert!(trimmer.is_finished());
+    assert_eq!(output[0], timestamp(3000));
+    // The synthesized keyframe reflects the attribute change made before the cut
+    assert_eq!(output[1], keyframe("asset:1?v=2"));
+    assert_eq!(output[2], asset(1));
+    assert!(matches!(output[3], Frame::MouseClicked(MouseClickedData { x: 1, y: 2 })));
+    assert_eq!(output.len(), 4);
+}
+
+#[test]
+fn split_at_keyframes() {
+    let frames = vec![timestamp(0), keyframe("a"), asset(1), timestamp(10), keyframe("b")];
+    let mut splitter = Splitter::new(SplitMode::Keyframes);
+    let steps: Vec<SplitStep> = frames.into_iter().map(|frame| splitter.push(frame)).collect();
+
+    let boundaries: Vec<bool> = steps.iter().map(|step| step.new_chunk).collect();
+    assert_eq!(boundaries, vec![false, false, false, false, true]);
+    assert_eq!(steps[4].frames, vec![timestamp(10), keyframe("b"), asset(1)]);
+}
+
+#[test]
+fn remapper_shifts_asset_ids_per_recording() {
+    let mut remapper = AssetIdRemapper::new();
+
+    let mut first = vec![keyframe("asset:0"), asset(0), asset(4)];
+    for frame in &mut first {
+        remapper.remap(frame);
+    }
+    assert_eq!(first, vec![keyframe("asset:0"), asset(0), asset(4)]);
+
+    remapper.next_recording();
+    let mut second = vec![keyframe("asset:0"), asset(0)];
+    for frame in &mut second {
+        remapper.remap(frame);
+    }
+    assert_eq!(second, vec![keyframe("asset:5"), asset(5)]);
+}
@@ -64,6 +64,7 @@ async fn read_sample_file() {
     );
 
     // Create file reader (header expected)
+    let file_len = binary_data.len() as u64;
     let cursor = std::io::Cursor::new(binary_data);
     let mut reader = FrameReader::new(cursor, true);
 
@@ -85,6 +86,7 @@ async fn read_sample_file() {
         "✓ Successfully parsed {} frames from .dcrr file",
         parsed_frames.len()
     );
+    assert_eq!(reader.position(), file_len, "Reader position should be at end of file");
 
     let expected_frames = sample_frames();
 
@@ -194,3 +196,326 @@ async fn write_sample_file_stream() {
         frames.len()
     );
 }
+
+#[test]
+fn read_sample_file_without_a_runtime() {
+    let binary_data = fs::read("../.sample_data/proto/file-basic.dcrr")
+        .expect("Failed to read TypeScript-generated .dcrr file");
+    let file_len = binary_data.len() as u64;
+
+    let mut reader = SyncFrameReader::new(std::io::Cursor::new(binary_data), true);
+    assert_eq!(reader.read_header().unwrap().version, 1);
+    let parsed_frames: Vec<Frame> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+
+    assert_eq!(parsed_frames, sample_frames());
+    assert_eq!(reader.position(), file_len);
+
+    // A stream cut off mid-frame is an error, not a clean end
+    let truncated = fs::read("../.sample_data/proto/frames-basic.bin").unwrap();
+    let truncated = &truncated[..truncated.len() - 3];
+    let result: Result<Vec<Frame>, _> = SyncFrameReader::new(truncated, false).collect();
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn type_code_matches_wire_variant_index() {
+    let mut frames = sample_frames();
+    frames.push(Frame::Heartbeat);
+    frames.push(Frame::RecordingResumed);
+    for frame in frames {
+        let mut writer = FrameWriter::new(Vec::new());
+        writer.write_frame(&frame).unwrap();
+        let encoded = writer.into_inner();
+        // After the u32 length prefix comes the u32 variant index
+        let variant = u32::from_be_bytes(encoded[4..8].try_into().unwrap());
+        assert_eq!(frame.type_code(), variant, "{}", frame.type_name());
+    }
+}
+
+#[test]
+fn reader_upgrades_legacy_scroll_and_mouse_frames() {
+    let legacy = vec![
+        // Old recorders wrapped negative offsets into u32
+        Frame::ScrollOffsetChanged(ScrollOffsetChangedData {
+            scroll_x_offset: -120i32 as u32,
+            scroll_y_offset: 300,
+        }),
+        Frame::MouseMoved(MouseMovedData { x: 10, y: 20 }),
+        Frame::MouseClicked(MouseClickedData { x: 11, y: 21 }),
+        Frame::ElementScrolled(ElementScrolledData {
+            node_id: 7,
+            scroll_x_offset: -5i32 as u32,
+            scroll_y_offset: 0,
+        }),
+        Frame::Heartbeat,
+    ];
+    let mut writer = FrameWriter::new(Vec::new());
+    for frame in &legacy {
+        writer.write_frame(frame).unwrap();
+    }
+    let encoded = writer.into_inner();
+
+    let unchanged: Vec<Frame> = SyncFrameReader::new(encoded.as_slice(), false)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(unchanged, legacy);
+
+    let upgraded: Vec<Frame> = SyncFrameReader::new(encoded.as_slice(), false)
+        .with_upgrade(true)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        upgraded,
+        vec![
+            Frame::ScrollOffsetChangedV2(ScrollOffsetChangedV2Data {
+                scroll_x_offset: -120,
+                scroll_y_offset: 300,
+            }),
+            Frame::MouseMovedV2(MouseMovedV2Data { x: 10.0, y: 20.0 }),
+            Frame::MouseClickedV2(MouseClickedV2Data { x: 11.0, y: 21.0 }),
+            Frame::ElementScrolledV2(ElementScrolledV2Data {
+                node_id: 7,
+                scroll_x_offset: -5,
+                scroll_y_offset: 0,
+            }),
+            Frame::Heartbeat,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn v2_frames_round_trip() {
+    let frames = vec![
+        Frame::ScrollOffsetChangedV2(ScrollOffsetChangedV2Data {
+            scroll_x_offset: -1,
+            scroll_y_offset: i32::MAX,
+        }),
+        Frame::MouseMovedV2(MouseMovedV2Data { x: 100.5, y: -0.25 }),
+        Frame::MouseClickedV2(MouseClickedV2Data { x: 33.333, y: 0.0 }),
+    ];
+    let mut writer = FrameWriter::new(Vec::new());
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    let encoded = writer.into_inner();
+
+    let mut reader = FrameReader::new(encoded.as_slice(), false).with_upgrade(true);
+    let mut read = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        read.push(frame);
+    }
+    assert_eq!(read, frames);
+}
+
+#[tokio::test]
+async fn chunked_keyframe_streams_and_reassembles() {
+    let keyframe = sample_frames()
+        .into_iter()
+        .find_map(|frame| match frame {
+            Frame::Keyframe(keyframe) => Some(keyframe),
+            _ => None,
+        })
+        .unwrap();
+    let frames = chunk_keyframe(keyframe.clone(), 64);
+    assert!(frames.len() > 3);
+
+    let mut writer = FrameWriter::new(Vec::new());
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    let encoded = writer.into_inner();
+
+    let mut reader = FrameReader::new(encoded.as_slice(), false);
+    let mut assembler = KeyframeAssembler::new();
+    let mut read = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        read.extend(assembler.push(frame).unwrap());
+    }
+    assert_eq!(read, vec![Frame::Keyframe(keyframe)]);
+}
+
+#[tokio::test]
+async fn indexed_file_seeks_to_keyframes() {
+    let frames = FrameBuilder::new()
+        .metadata("https://example.com/")
+        .new_page()
+        .at(1_000)
+        .wait(500)
+        .new_page()
+        .at(3_000)
+        .scroll(0, 100)
+        .new_page()
+        .at(6_000)
+        .build();
+    let mut writer = FrameWriter::new(Vec::new());
+    writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    writer.write_index().unwrap();
+    let file = writer.into_inner();
+
+    // Readers stop where the index begins
+    let mut reader = FrameReader::new(std::io::Cursor::new(file.clone()), true);
+    assert_eq!(reader.read_header().await.unwrap().version, 2);
+    let mut read = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        read.push(frame);
+    }
+    assert_eq!(read, frames);
+    let sync: Vec<Frame> = SyncFrameReader::new(file.as_slice(), true)
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(sync, frames);
+
+    // Past the header, the marker only ends the frames of a stream said to have an index
+    let past_header = &file[32..];
+    let sync: Vec<Frame> = SyncFrameReader::new(past_header, false)
+        .with_index(true)
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(sync, frames);
+    let mut reader = FrameReader::new(past_header, false);
+    let error = loop {
+        match reader.read_frame().await {
+            Ok(Some(_)) => {}
+            Ok(None) => panic!("the index marker ended a stream without an index"),
+            Err(error) => break error,
+        }
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    let error = SyncFrameReader::new(past_header, false).find_map(Result::err).unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    let mut reader = FrameReader::new(std::io::Cursor::new(file.clone()), true);
+    let index = reader.read_index().await.unwrap().unwrap();
+    // The first keyframe was written before any timestamp, so takes the first one's
+    assert_eq!(
+        index.entries.iter().map(|entry| (entry.timestamp, entry.frame_index)).collect::<Vec<_>>(),
+        [(1_000, 1), (1_500, 4), (3_000, 7)]
+    );
+
+    let entry = reader.seek_to_timestamp(2_000).await.unwrap().unwrap();
+    assert_eq!(entry.frame_index, 4);
+    assert_eq!(reader.position(), entry.offset);
+    let mut rest = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        rest.push(frame);
+    }
+    assert_eq!(rest, frames[4..]);
+
+    let mut reader = SyncFrameReader::new(std::io::Cursor::new(file), true);
+    let entry = reader.seek_to_timestamp(0).unwrap().unwrap();
+    assert_eq!(entry.frame_index, 1);
+    assert_eq!(reader.read_frame().unwrap().as_ref(), Some(&frames[1]));
+
+    // Without an index, the reader stays put
+    let mut writer = FrameWriter::new(Vec::new());
+    writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    let mut reader = FrameReader::new(std::io::Cursor::new(writer.into_inner()), true);
+    assert_eq!(reader.seek_to_timestamp(2_000).await.unwrap(), None);
+    assert_eq!(reader.read_frame().await.unwrap().as_ref(), Some(&frames[0]));
+}
+
+#[tokio::test]
+async fn compressed_frames_round_trip() {
+    let canvas = Frame::CanvasChanged(CanvasChangedData {
+        node_id: 7,
+        mime_type: "image/png".to_string(),
+        data: vec![0; 64 * 1024],
+    });
+    let frames = FrameBuilder::new()
+        .metadata("https://example.com/")
+        .new_page()
+        .at(1_000)
+        .frame(canvas.clone())
+        .new_page()
+        .at(2_000)
+        .frame(canvas)
+        .build();
+    let write = |header: FileHeader| {
+        let mut writer = FrameWriter::new(Vec::new());
+        writer.write_header(&header).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        writer.write_index().unwrap();
+        writer.into_inner()
+    };
+    let plain = write(FileHeader::with_timestamp(1_000));
+    let file = write(FileHeader::with_timestamp(1_000).with_frame_compression(true));
+    assert!(file.len() < plain.len() / 10, "{} bytes compressed, {} not", file.len(), plain.len());
+
+    let mut reader = FrameReader::new(std::io::Cursor::new(file.clone()), true);
+    assert!(reader.read_header().await.unwrap().frame_compression());
+    let mut read = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        read.push(frame);
+    }
+    assert_eq!(read, frames);
+    let sync: Vec<Frame> = SyncFrameReader::new(file.as_slice(), true)
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(sync, frames);
+
+    // The index points at the compressed keyframes
+    let mut reader = SyncFrameReader::new(std::io::Cursor::new(file), true);
+    let entry = reader.seek_to_timestamp(2_500).unwrap().unwrap();
+    assert_eq!(reader.read_frame().unwrap().as_ref(), Some(&frames[entry.frame_index as usize]));
+}
+
+#[tokio::test]
+async fn checksummed_frames_detect_corruption() {
+    use domcorder_proto::ChecksumMismatch;
+
+    let frames = FrameBuilder::new()
+        .metadata("https://example.com/")
+        .new_page()
+        .at(1_000)
+        .build();
+    let mut writer = FrameWriter::new(Vec::new());
+    writer
+        .write_header(&FileHeader::with_timestamp(1_000).with_frame_checksums(true).with_frame_compression(true))
+        .unwrap();
+    for frame in &frames {
+        writer.write_frame(frame).unwrap();
+    }
+    writer.write_index().unwrap();
+    let file = writer.into_inner();
+
+    let mut reader = FrameReader::new(std::io::Cursor::new(file.clone()), true);
+    assert!(reader.read_header().await.unwrap().frame_checksums());
+    let mut read = Vec::new();
+    while let Some(frame) = reader.read_frame().await.unwrap() {
+        read.push(frame);
+    }
+    assert_eq!(read, frames);
+
+    // A byte of the second frame's payload flipped mid-write
+    let second = 32 + 4 + u32::from_be_bytes(file[32..36].try_into().unwrap()) as usize;
+    let mut corrupt = file.clone();
+    corrupt[second + 6] ^= 0xff;
+    let mut reader = FrameReader::new(std::io::Cursor::new(corrupt.clone()), true);
+    assert_eq!(reader.read_frame().await.unwrap().as_ref(), Some(&frames[0]));
+    let error = reader.read_frame().await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(ChecksumMismatch::find(&error).is_some(), "{}", error);
+    assert_eq!(reader.position(), second as u64);
+    let sync: std::io::Result<Vec<Frame>> = SyncFrameReader::new(corrupt.as_slice(), true).collect();
+    assert!(ChecksumMismatch::find(&sync.unwrap_err()).is_some());
+
+    // Without a header the reader has to be told
+    let mut plain = FrameWriter::new(Vec::new());
+    plain.write_header(&FileHeader::with_timestamp(1_000).with_frame_checksums(true)).unwrap();
+    plain.write_frame(&frames[0]).unwrap();
+    let plain = plain.into_inner();
+    let mut reader = SyncFrameReader::new(&plain[32..], false).with_frame_checksums(true);
+    assert_eq!(reader.read_frame().unwrap().as_ref(), Some(&frames[0]));
+    let Err(error) = SyncFrameReader::new(&plain[32..], false).read_frame() else {
+        panic!("the checksum was read as part of the frame");
+    };
+    assert!(ChecksumMismatch::find(&error).is_none());
+}
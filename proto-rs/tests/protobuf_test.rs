@@ -0,0 +1,166 @@
+#![cfg(feature = "protobuf")]
+
+mod common;
+
+use common::sample_frames;
+use domcorder_proto::protobuf::{self, ProtobufError, pb};
+use domcorder_proto::*;
+
+const SCHEMA: &str = include_str!("../proto/domcorder/v1/frames.proto");
+
+/// The sample keyframe split into KeyframeBegin, KeyframeChunk... and KeyframeEnd
+fn chunked_sample_keyframe() -> Vec<Frame> {
+    let keyframe = sample_frames()
+        .into_iter()
+        .find_map(|frame| match frame {
+            Frame::Keyframe(keyframe) => Some(keyframe),
+            _ => None,
+        })
+        .expect("samples have a keyframe");
+    chunk_keyframe(keyframe, 64)
+}
+
+#[test]
+fn sample_frames_round_trip() {
+    for frame in sample_frames().into_iter().chain(chunked_sample_keyframe()) {
+        let bytes = protobuf::encode(frame.clone());
+        assert_eq!(protobuf::decode(&bytes).unwrap(), frame);
+    }
+}
+
+fn special_cases() -> Vec<Frame> {
+    vec![
+        Frame::Heartbeat,
+        Frame::RecordingResumed,
+        Frame::Asset(AssetData {
+            asset_id: 7,
+            url: "https://example.com/font.woff2".to_string(),
+            mime: None,
+            buf: vec![],
+            fetch_error: AssetFetchError::Unknown("TypeError: Failed to fetch".to_string()),
+        }),
+        Frame::DomTextChanged(DomTextChangedData {
+            node_id: 12,
+            operations: vec![
+                TextOperationData::Remove(TextRemoveOperationData { index: 0, length: 3 }),
+                TextOperationData::Insert(TextInsertOperationData {
+                    index: 0,
+                    text: "new".to_string(),
+                }),
+            ],
+        }),
+        Frame::NewAdoptedStyleSheetReference(NewAdoptedStyleSheetReferenceData {
+            id: 3,
+            media: Some("print".to_string()),
+            hash: "9f86d081884c7d65".to_string(),
+        }),
+        Frame::StyleSheetReplacedReference(StyleSheetReplacedReferenceData {
+            style_sheet_id: 3,
+            hash: "9f86d081884c7d65".to_string(),
+        }),
+        Frame::CanvasChangedDelta(CanvasChangedDeltaData {
+            node_id: 9,
+            patches: vec![CanvasPatchData {
+                x: 16,
+                y: 32,
+                width: 8,
+                height: 4,
+                mime_type: "image/png".to_string(),
+                data: vec![0x89, b'P', b'N', b'G'],
+            }],
+        }),
+        Frame::HeartbeatPing(HeartbeatPingData {
+            server_timestamp: 1_700_000_000_000,
+        }),
+        Frame::HeartbeatPong(HeartbeatPongData {
+            server_timestamp: 1_700_000_000_000,
+            client_timestamp: 1_700_000_000_420,
+        }),
+        Frame::SessionIdentity(SessionIdentityData {
+            anonymous_id: "a1b2c3".to_string(),
+            user_id: Some("customer-42".to_string()),
+            email_hash: None,
+        }),
+        Frame::StopRecording(StopRecordingData {
+            reason: "session not sampled".to_string(),
+        }),
+        Frame::IdlePeriod(IdlePeriodData {
+            duration_ms: 8 * 60 * 60 * 1000,
+        }),
+        Frame::RecordingTruncated(RecordingTruncatedData { discarded_bytes: 37 }),
+        Frame::CacheInventory(CacheInventoryData {
+            sha256_hashes: vec!["ab".repeat(32), "cd".repeat(32)],
+        }),
+        Frame::RequestKeyframe(RequestKeyframeData {
+            reason: "unknown node id 42".to_string(),
+        }),
+        Frame::RecordingScope(RecordingScopeData {
+            root_selector: "#support-widget".to_string(),
+        }),
+        Frame::SessionLink(SessionLinkData {
+            session_id: "c0ffee-session".to_string(),
+        }),
+        Frame::PlaybackConfig(PlaybackConfigData {
+            storage_type: "local".to_string(),
+            config_json: "{}".to_string(),
+            is_live: false,
+            latest_timestamp: None,
+            root_selector: Some("#support-widget".to_string()),
+        }),
+    ]
+}
+
+#[test]
+fn special_cases_round_trip() {
+    for frame in special_cases() {
+        assert_eq!(protobuf::decode(&protobuf::encode(frame.clone())).unwrap(), frame);
+    }
+}
+
+#[test]
+fn missing_required_fields_are_errors() {
+    let empty = pb::Frame { frame: None };
+    assert_eq!(Frame::try_from(empty), Err(ProtobufError::MissingField("Frame.frame")));
+
+    let keyframe = pb::Frame {
+        frame: Some(pb::frame::Frame::Keyframe(pb::KeyframeData {
+            document: None,
+            viewport_width: 800,
+            viewport_height: 600,
+        })),
+    };
+    assert_eq!(
+        Frame::try_from(keyframe),
+        Err(ProtobufError::MissingField("KeyframeData.document"))
+    );
+    assert!(matches!(protobuf::decode(&[0xff]), Err(ProtobufError::Decode(_))));
+}
+
+/// The schema's Frame oneof numbers each variant by its type code plus one
+#[test]
+fn schema_frame_numbers_follow_type_codes() {
+    let frame_message = SCHEMA
+        .split("message Frame {")
+        .nth(1)
+        .and_then(|rest| rest.split("\n}").next())
+        .expect("schema has a Frame message");
+    let mut numbered = 0;
+    let frames = sample_frames()
+        .into_iter()
+        .flat_map(|frame| [frame.clone(), frame.upgrade()])
+        .chain(chunked_sample_keyframe())
+        .chain([Frame::Heartbeat, Frame::RecordingResumed])
+        .chain(special_cases());
+    for frame in frames {
+        let field = format!("{}Data ", frame.type_name());
+        let line = frame_message
+            .lines()
+            .find(|line| line.trim_start().starts_with(&field))
+            .unwrap_or_else(|| panic!("{} is missing from the schema", frame.type_name()));
+        let number: u32 = line.trim_end_matches(';').rsplit(' ').next().unwrap().parse().unwrap();
+        assert_eq!(number, frame.type_code() + 1, "{}", line.trim());
+        numbered += 1;
+    }
+    assert!(numbered > 0);
+    assert_eq!(frame_message.matches(" = ").count(), 58);
+}
@@ -0,0 +1,90 @@
+use domcorder_proto::asset_refs::{asset_references, rewrite_asset_references};
+use domcorder_proto::*;
+
+fn keyframe_with_image(asset: &str) -> Frame {
+    Frame::Keyframe(KeyframeData {
+        document: VDocument {
+            id: 0,
+            adopted_style_sheets: vec![],
+            children: vec![VNode::Element(VElement {
+                id: 1,
+                tag: "img".to_string(),
+                ns: None,
+                attrs: vec![("src".to_string(), asset.to_string())],
+                children: vec![],
+            })],
+        },
+        viewport_width: 800,
+        viewport_height: 600,
+    })
+}
+
+fn asset(asset_id: u32) -> Frame {
+    Frame::Asset(AssetData {
+        asset_id,
+        url: "https://example.com/a.png".to_string(),
+        mime: None,
+        buf: vec![1, 2, 3],
+        fetch_error: AssetFetchError::None,
+    })
+}
+
+fn timestamp(timestamp: u64) -> Frame {
+    Frame::Timestamp(TimestampData { timestamp })
+}
+
+#[test]
+fn accepts_consistent_stream() {
+    let mut validator = FrameValidator::new();
+    let frames = [timestamp(1), keyframe_with_image("asset:7"), asset(7), timestamp(1), timestamp(2)];
+    for frame in &frames {
+        assert!(validator.push_frame(frame).is_empty());
+    }
+    assert_eq!(validator.frame_count(), 5);
+    assert!(validator.finish().is_empty());
+}
+
+#[test]
+fn reports_semantic_issues() {
+    let mut validator = FrameValidator::new();
+    assert!(validator.push_frame(&timestamp(10)).is_empty());
+    assert!(validator.push_frame(&keyframe_with_image("asset:3")).is_empty());
+
+    assert_eq!(
+        validator.push_frame(&timestamp(5)),
+        vec![ValidationIssue::NonMonotonicTimestamp {
+            frame_index: 2,
+            previous: 10,
+            timestamp: 5,
+        }]
+    );
+    assert_eq!(
+        validator.push_frame(&Frame::DomNodeRemoved(DomNodeRemovedData { node_id: 99 })),
+        vec![ValidationIssue::Dom {
+            frame_index: 3,
+            error: VDomError::UnknownNode(99),
+        }]
+    );
+    assert_eq!(
+        validator.finish(),
+        vec![ValidationIssue::UnresolvedAsset {
+            frame_index: 1,
+            asset_id: 3,
+        }]
+    );
+}
+
+#[test]
+fn finds_asset_placeholders() {
+    let mut found = Vec::new();
+    asset_references("asset:1", &mut found);
+    asset_references("background: url(\"asset:2\") no-repeat, url(asset:3)", &mut found);
+    asset_references("asset:4 1x, asset:5 2x", &mut found);
+    asset_references("myasset:6 and asset:x", &mut found);
+    assert_eq!(found, vec![1, 2, 3, 4, 5]);
+
+    assert_eq!(
+        rewrite_asset_references("url(asset:2) asset:10 myasset:3", |id| id + 100),
+        "url(asset:102) asset:110 myasset:3"
+    );
+}
@@ -0,0 +1,121 @@
+use domcorder_proto::*;
+
+mod common;
+use common::sample_frames;
+
+fn text(id: u32, content: &str) -> VNode {
+    VNode::Text(VTextNode {
+        id,
+        content: content.to_string(),
+    })
+}
+
+fn element(id: u32, tag: &str, children: Vec<VNode>) -> VNode {
+    VNode::Element(VElement {
+        id,
+        tag: tag.to_string(),
+        ns: None,
+        attrs: vec![],
+        children,
+    })
+}
+
+fn keyframe(children: Vec<VNode>) -> Frame {
+    Frame::Keyframe(KeyframeData {
+        document: VDocument {
+            id: 0,
+            adopted_style_sheets: vec![],
+            children,
+        },
+        viewport_width: 800,
+        viewport_height: 600,
+    })
+}
+
+#[test]
+fn replays_sample_frames() {
+    let mut engine = VDomEngine::new();
+    for frame in sample_frames() {
+        // The sample fixture references nodes that are not in its keyframe,
+        // so only check that structural frames are applied without panicking.
+        let _ = engine.apply(&frame);
+    }
+    assert!(engine.has_document());
+    assert_eq!(engine.viewport(), Some((1920, 1080)));
+}
+
+#[test]
+fn applies_mutations() {
+    let mut engine = VDomEngine::new();
+    engine
+        .apply(&keyframe(vec![element(1, "html", vec![element(2, "body", vec![])])]))
+        .unwrap();
+
+    engine
+        .apply(&Frame::DomNodeAdded(DomNodeAddedData {
+            parent_node_id: 2,
+            index: 0,
+            node: element(3, "p", vec![text(4, "hello")]),
+        }))
+        .unwrap();
+    engine
+        .apply(&Frame::DomAttributeChanged(DomAttributeChangedData {
+            node_id: 3,
+            attribute_name: "class".to_string(),
+            attribute_value: "greeting".to_string(),
+        }))
+        .unwrap();
+    engine
+        .apply(&Frame::DomTextChanged(DomTextChangedData {
+            node_id: 4,
+            operations: vec![
+                TextOperationData::Remove(TextRemoveOperationData { index: 0, length: 1 }),
+                TextOperationData::Insert(TextInsertOperationData {
+                    index: 0,
+                    text: "J".to_string(),
+                }),
+            ],
+        }))
+        .unwrap();
+
+    let document = engine.document().unwrap();
+    let VNode::Element(html) = &document.children[0] else { panic!("expected html") };
+    let VNode::Element(body) = &html.children[0] else { panic!("expected body") };
+    let VNode::Element(p) = &body.children[0] else { panic!("expected p") };
+    assert_eq!(p.attrs, vec![("class".to_string(), "greeting".to_string())]);
+    assert_eq!(p.children, vec![text(4, "Jello")]);
+
+    engine
+        .apply(&Frame::DomNodeRemoved(DomNodeRemovedData { node_id: 3 }))
+        .unwrap();
+    assert!(!engine.contains(3));
+    assert!(!engine.contains(4));
+    assert_eq!(engine.node_count(), 2);
+}
+
+#[test]
+fn rejects_unknown_nodes() {
+    let mut engine = VDomEngine::new();
+    let removal = Frame::DomNodeRemoved(DomNodeRemovedData { node_id: 9 });
+    assert_eq!(engine.apply(&removal), Err(VDomError::NoDocument));
+
+    engine.apply(&keyframe(vec![element(1, "html", vec![])])).unwrap();
+    assert_eq!(engine.apply(&removal), Err(VDomError::UnknownNode(9)));
+    assert_eq!(
+        engine.apply(&Frame::DomNodeAdded(DomNodeAddedData {
+            parent_node_id: 0,
+            index: 0,
+            node: element(1, "html", vec![]),
+        })),
+        Err(VDomError::DuplicateNode(1))
+    );
+}
+
+#[test]
+fn text_operations_use_utf16_indices() {
+    let ops = vec![TextOperationData::Insert(TextInsertOperationData {
+        index: 2,
+        text: "!".to_string(),
+    })];
+    assert_eq!(vdom_engine::apply_text_operations("😀", &ops, 1).unwrap(), "😀!");
+}
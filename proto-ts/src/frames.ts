@@ -1,5 +1,5 @@
 import { Writer } from "./writer";
-import { VNode, VDocument, VStyleSheet } from "./vdom";
+import { VNode, VDocument, VDocumentType, VElement, VStyleSheet } from "./vdom";
 
 
 export enum FrameType {
@@ -46,11 +46,66 @@ export enum FrameType {
     CacheManifest = 30,
     PlaybackConfig = 31,
     Heartbeat = 32,
+    PageError = 33,
+    Annotation = 34,
+    TraceContext = 35,
+    RecordingPaused = 36,
+    RecordingResumed = 37,
+
+    // Signed scroll offsets and fractional pointer positions
+    ScrollOffsetChangedV2 = 38,
+    MouseMovedV2 = 39,
+    MouseClickedV2 = 40,
+    ElementScrolledV2 = 41,
+
+    // Keyframes streamed in chunks
+    KeyframeBegin = 42,
+    KeyframeChunk = 43,
+    KeyframeEnd = 44,
+
+    // Stylesheet text stored once in the server's asset cache
+    NewAdoptedStyleSheetReference = 45,
+    StyleSheetReplacedReference = 46,
+
+    // Only the redrawn regions of a canvas
+    CanvasChangedDelta = 47,
+
+    // Round trips between the server and the recorder, for latency and clock skew
+    HeartbeatPing = 48,
+    HeartbeatPong = 49,
+
+    SessionIdentity = 50,
+
+    // Sent by the server to a recorder whose session it won't store
+    StopRecording = 51,
+
+    // Written by the server in place of a long span without activity
+    IdlePeriod = 52,
+
+    // Written by the server where a recording's stream ended part way through a frame
+    RecordingTruncated = 53,
+
+    // Sent by the recorder before RecordingMetadata: the assets it already
+    // knows the server has, so the manifest can leave them out
+    CacheInventory = 54,
+
+    // Sent by the server to a live recorder whose stream no longer matches
+    // the page it recorded
+    RequestKeyframe = 55,
+
+    // Sent by a recorder that records one element of the page, right after
+    // RecordingMetadata
+    RecordingScope = 56,
+
+    // Links the recording to the other tabs and windows of the same session
+    SessionLink = 57,
 }
 
 // BufferReader interface for decoding
 interface BufferReader {
     readU32(): number;
+    readI32(): number;
+    readF32(): number;
     readU64(): bigint;
     readString(): string;
     readBytes(length: number): Uint8Array;
@@ -264,6 +319,382 @@ export class Heartbeat extends Frame {
     }
 }
 
+// Sent by the server on the heartbeat interval; the recorder answers with a HeartbeatPong
+export class HeartbeatPing extends Frame {
+    constructor(
+        public serverTimestamp: number
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): HeartbeatPing {
+        if (reader.readU32() !== FrameType.HeartbeatPing) throw new Error(`Expected HeartbeatPing frame type`);
+        const serverTimestamp = Number(reader.readU64());
+        return new HeartbeatPing(serverTimestamp);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.HeartbeatPing);
+        w.u64(toU64(this.serverTimestamp));
+        await w.endFrame();
+    }
+}
+
+export class HeartbeatPong extends Frame {
+    constructor(
+        public serverTimestamp: number,   // echoed from the ping
+        public clientTimestamp: number
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): HeartbeatPong {
+        if (reader.readU32() !== FrameType.HeartbeatPong) throw new Error(`Expected HeartbeatPong frame type`);
+        const serverTimestamp = Number(reader.readU64());
+        const clientTimestamp = Number(reader.readU64());
+        return new HeartbeatPong(serverTimestamp, clientTimestamp);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.HeartbeatPong);
+        w.u64(toU64(this.serverTimestamp));
+        w.u64(toU64(this.clientTimestamp));
+        await w.endFrame();
+    }
+}
+
+// Who the recorded session belongs to; the email is only ever sent hashed
+export class SessionIdentity extends Frame {
+    constructor(
+        public anonymousId: string,
+        public userId?: string,
+        public emailHash?: string     // hex SHA-256 of the trimmed, lowercased email
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): SessionIdentity {
+        if (reader.readU32() !== FrameType.SessionIdentity) throw new Error(`Expected SessionIdentity frame type`);
+        const anonymousId = reader.readString();
+        const userId = reader.readByte() === 1 ? reader.readString() : undefined;
+        const emailHash = reader.readByte() === 1 ? reader.readString() : undefined;
+        return new SessionIdentity(anonymousId, userId, emailHash);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.SessionIdentity);
+        w.strUtf8(this.anonymousId);
+        if (this.userId !== undefined) { w.byte(1); w.strUtf8(this.userId); } else { w.byte(0); }
+        if (this.emailHash !== undefined) { w.byte(1); w.strUtf8(this.emailHash); } else { w.byte(0); }
+        await w.endFrame();
+    }
+}
+
+// Sent by the server at the handshake when it won't store the session; the recorder stops
+export class StopRecording extends Frame {
+    constructor(
+        public reason: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): StopRecording {
+        if (reader.readU32() !== FrameType.StopRecording) throw new Error(`Expected StopRecording frame type`);
+        const reason = reader.readString();
+        return new StopRecording(reason);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.StopRecording);
+        w.strUtf8(this.reason);
+        await w.endFrame();
+    }
+}
+
+// A span with only timestamps, pointer movement and heartbeats, collapsed at
+// ingestion; followed by the span's last pointer position and timestamp
+export class IdlePeriod extends Frame {
+    constructor(
+        public durationMs: number | bigint
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): IdlePeriod {
+        if (reader.readU32() !== FrameType.IdlePeriod) throw new Error(`Expected IdlePeriod frame type`);
+        const durationMs = reader.readU64();
+        return new IdlePeriod(durationMs);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.IdlePeriod);
+        w.u64(toU64(this.durationMs));
+        await w.endFrame();
+    }
+}
+
+// The recorder's stream ended in the middle of a frame; the bytes received of
+// that frame were discarded. Always the last frame of a recording.
+export class RecordingTruncated extends Frame {
+    constructor(
+        public discardedBytes: number | bigint
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): RecordingTruncated {
+        if (reader.readU32() !== FrameType.RecordingTruncated) throw new Error(`Expected RecordingTruncated frame type`);
+        const discardedBytes = reader.readU64();
+        return new RecordingTruncated(discardedBytes);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.RecordingTruncated);
+        w.u64(toU64(this.discardedBytes));
+        await w.endFrame();
+    }
+}
+
+// SHA-256 hashes of the assets the recorder kept from earlier manifests for
+// the site. Part of the handshake; the server doesn't store it.
+export class CacheInventory extends Frame {
+    constructor(
+        public sha256Hashes: string[]
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): CacheInventory {
+        if (reader.readU32() !== FrameType.CacheInventory) throw new Error(`Expected CacheInventory frame type`);
+        const count = Number(reader.readU64());
+        const sha256Hashes: string[] = [];
+        for (let i = 0; i < count; i++) {
+            sha256Hashes.push(reader.readString());
+        }
+        return new CacheInventory(sha256Hashes);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.CacheInventory);
+        w.u64(BigInt(this.sha256Hashes.length));
+        for (const hash of this.sha256Hashes) {
+            w.strUtf8(hash);
+        }
+        await w.endFrame();
+    }
+}
+
+// Sent by the server during a live recording when the frames stop making sense
+// against the document they build; the recorder answers with a fresh keyframe
+export class RequestKeyframe extends Frame {
+    constructor(
+        public reason: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): RequestKeyframe {
+        if (reader.readU32() !== FrameType.RequestKeyframe) throw new Error(`Expected RequestKeyframe frame type`);
+        const reason = reader.readString();
+        return new RequestKeyframe(reason);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.RequestKeyframe);
+        w.strUtf8(this.reason);
+        await w.endFrame();
+    }
+}
+
+// Scopes a recording to the element matching rootSelector: its keyframes hold
+// that element as the document's only child, and its mutations stay inside it
+export class RecordingScope extends Frame {
+    constructor(
+        public rootSelector: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): RecordingScope {
+        if (reader.readU32() !== FrameType.RecordingScope) throw new Error(`Expected RecordingScope frame type`);
+        const rootSelector = reader.readString();
+        return new RecordingScope(rootSelector);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.RecordingScope);
+        w.strUtf8(this.rootSelector);
+        await w.endFrame();
+    }
+}
+
+// The user session the recording belongs to, shared by the recordings of the
+// session's other tabs and windows so they can be played together
+export class SessionLink extends Frame {
+    constructor(
+        public sessionId: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): SessionLink {
+        if (reader.readU32() !== FrameType.SessionLink) throw new Error(`Expected SessionLink frame type`);
+        const sessionId = reader.readString();
+        return new SessionLink(sessionId);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.SessionLink);
+        w.strUtf8(this.sessionId);
+        await w.endFrame();
+    }
+}
+
+export class PageError extends Frame {
+    constructor(
+        public message: string,
+        public source_url?: string,
+        public line?: number,
+        public column?: number,
+        public stack?: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): PageError {
+        if (reader.readU32() !== FrameType.PageError) throw new Error(`Expected PageError frame type`);
+        const message = reader.readString();
+        const source_url = reader.readByte() === 1 ? reader.readString() : undefined;
+        const line = reader.readByte() === 1 ? reader.readU32() : undefined;
+        const column = reader.readByte() === 1 ? reader.readU32() : undefined;
+        const stack = reader.readByte() === 1 ? reader.readString() : undefined;
+        return new PageError(message, source_url, line, column, stack);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.PageError);
+        w.strUtf8(this.message);
+        // Optional fields - bincode format: 1 byte for None/Some
+        if (this.source_url !== undefined) { w.byte(1); w.strUtf8(this.source_url); } else { w.byte(0); }
+        if (this.line !== undefined) { w.byte(1); w.u32(this.line); } else { w.byte(0); }
+        if (this.column !== undefined) { w.byte(1); w.u32(this.column); } else { w.byte(0); }
+        if (this.stack !== undefined) { w.byte(1); w.strUtf8(this.stack); } else { w.byte(0); }
+        await w.endFrame();
+    }
+}
+
+export class Annotation extends Frame {
+    constructor(
+        public name: string,
+        public data?: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): Annotation {
+        if (reader.readU32() !== FrameType.Annotation) throw new Error(`Expected Annotation frame type`);
+        const name = reader.readString();
+        const data = reader.readByte() === 1 ? reader.readString() : undefined;
+        return new Annotation(name, data);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.Annotation);
+        w.strUtf8(this.name);
+        if (this.data !== undefined) {
+            w.byte(1);
+            w.strUtf8(this.data);
+        } else {
+            w.byte(0);
+        }
+        await w.endFrame();
+    }
+}
+
+export class TraceContext extends Frame {
+    constructor(
+        public traceparent: string,
+        public tracestate?: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): TraceContext {
+        if (reader.readU32() !== FrameType.TraceContext) throw new Error(`Expected TraceContext frame type`);
+        const traceparent = reader.readString();
+        const tracestate = reader.readByte() === 1 ? reader.readString() : undefined;
+        return new TraceContext(traceparent, tracestate);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.TraceContext);
+        w.strUtf8(this.traceparent);
+        if (this.tracestate !== undefined) {
+            w.byte(1);
+            w.strUtf8(this.tracestate);
+        } else {
+            w.byte(0);
+        }
+        await w.endFrame();
+    }
+}
+
+export class RecordingPaused extends Frame {
+    constructor(
+        public reason?: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): RecordingPaused {
+        if (reader.readU32() !== FrameType.RecordingPaused) throw new Error(`Expected RecordingPaused frame type`);
+        const reason = reader.readByte() === 1 ? reader.readString() : undefined;
+        return new RecordingPaused(reason);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.RecordingPaused);
+        if (this.reason !== undefined) {
+            w.byte(1);
+            w.strUtf8(this.reason);
+        } else {
+            w.byte(0);
+        }
+        await w.endFrame();
+    }
+}
+
+export class RecordingResumed extends Frame {
+    constructor() {
+        super();
+    }
+
+    static decode(reader: BufferReader): RecordingResumed {
+        if (reader.readU32() !== FrameType.RecordingResumed) throw new Error(`Expected RecordingResumed frame type`);
+        return new RecordingResumed();
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.RecordingResumed);
+        await w.endFrame();
+    }
+}
+
 export class AssetReference extends Frame {
     constructor(
         public asset_id: number,
@@ -348,7 +779,9 @@ export class PlaybackConfig extends Frame {
         public storage_type: string,
         public config_json: string,
         public is_live: boolean,
-        public latest_timestamp: number | null
+        public latest_timestamp: number | null,
+        // The selector of the element the recording is scoped to, if any
+        public root_selector: string | null = null
     ) {
         super();
     }
@@ -360,7 +793,9 @@ export class PlaybackConfig extends Frame {
         const is_live = reader.readByte() !== 0;
         const has_timestamp = reader.readByte() !== 0;
         const latest_timestamp = has_timestamp ? Number(reader.readU64()) : null;
-        return new PlaybackConfig(storage_type, config_json, is_live, latest_timestamp);
+        const has_root_selector = reader.readByte() !== 0;
+        const root_selector = has_root_selector ? reader.readString() : null;
+        return new PlaybackConfig(storage_type, config_json, is_live, latest_timestamp, root_selector);
     }
 
     async encode(w: Writer): Promise<void> {
@@ -375,6 +810,12 @@ export class PlaybackConfig extends Frame {
         } else {
             w.byte(0);
         }
+        if (this.root_selector !== null) {
+            w.byte(1);
+            w.strUtf8(this.root_selector);
+        } else {
+            w.byte(0);
+        }
         await w.endFrame();
     }
 }
@@ -859,6 +1300,62 @@ export class CanvasChanged extends Frame {
     }
 }
 
+// One rect of a canvas, replacing the pixels under it in the previous snapshot
+export interface CanvasPatch {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+    mimeType: string;
+    data: ArrayBuffer;
+}
+
+export class CanvasChangedDelta extends Frame {
+    constructor(
+        public nodeId: number,
+        public patches: CanvasPatch[]
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): CanvasChangedDelta {
+        if (reader.readU32() !== FrameType.CanvasChangedDelta) throw new Error(`Expected CanvasChangedDelta frame type`);
+        const nodeId = reader.readU32();
+        const patchCount = Number(reader.readU64());
+        const patches: CanvasPatch[] = [];
+        for (let i = 0; i < patchCount; i++) {
+            const x = reader.readU32();
+            const y = reader.readU32();
+            const width = reader.readU32();
+            const height = reader.readU32();
+            const mimeType = reader.readString();
+            const length = Number(reader.readU64());
+            const bytes = reader.readBytes(length);
+            const data = bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength) as ArrayBuffer;
+            patches.push({ x, y, width, height, mimeType, data });
+        }
+        return new CanvasChangedDelta(nodeId, patches);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.CanvasChangedDelta);
+        w.u32(this.nodeId);
+        w.u64(BigInt(this.patches.length));
+        for (const patch of this.patches) {
+            w.u32(patch.x);
+            w.u32(patch.y);
+            w.u32(patch.width);
+            w.u32(patch.height);
+            w.strUtf8(patch.mimeType);
+            const bytes = new Uint8Array(patch.data);
+            w.u64(BigInt(bytes.length));
+            w.bytes(bytes);
+        }
+        await w.endFrame();
+    }
+}
+
 export class AdoptedStyleSheetsChanged extends Frame {
     constructor(
         public styleSheetIds: number[],
@@ -1066,6 +1563,322 @@ export class StyleSheetReplaced extends Frame {
     }
 }
 
+// Window scroll offset; negative on RTL pages and during overscroll
+export class ScrollOffsetChangedV2 extends Frame {
+    constructor(public scrollXOffset: number, public scrollYOffset: number) {
+        super();
+    }
+
+    static decode(reader: BufferReader): ScrollOffsetChangedV2 {
+        if (reader.readU32() !== FrameType.ScrollOffsetChangedV2) throw new Error(`Expected ScrollOffsetChangedV2 frame type`);
+        const scrollXOffset = reader.readI32();
+        const scrollYOffset = reader.readI32();
+        return new ScrollOffsetChangedV2(scrollXOffset, scrollYOffset);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.ScrollOffsetChangedV2);
+        w.i32(Math.round(this.scrollXOffset));
+        w.i32(Math.round(this.scrollYOffset));
+        await w.endFrame();
+    }
+}
+
+// Pointer position in CSS pixels, fractional at non-integer devicePixelRatio
+export class MouseMovedV2 extends Frame {
+    constructor(public x: number, public y: number) {
+        super();
+    }
+
+    static decode(reader: BufferReader): MouseMovedV2 {
+        if (reader.readU32() !== FrameType.MouseMovedV2) throw new Error(`Expected MouseMovedV2 frame type`);
+        const x = reader.readF32();
+        const y = reader.readF32();
+        return new MouseMovedV2(x, y);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.MouseMovedV2);
+        w.f32(this.x);
+        w.f32(this.y);
+        await w.endFrame();
+    }
+}
+
+export class MouseClickedV2 extends Frame {
+    constructor(public x: number, public y: number) {
+        super();
+    }
+
+    static decode(reader: BufferReader): MouseClickedV2 {
+        if (reader.readU32() !== FrameType.MouseClickedV2) throw new Error(`Expected MouseClickedV2 frame type`);
+        const x = reader.readF32();
+        const y = reader.readF32();
+        return new MouseClickedV2(x, y);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.MouseClickedV2);
+        w.f32(this.x);
+        w.f32(this.y);
+        await w.endFrame();
+    }
+}
+
+export class ElementScrolledV2 extends Frame {
+    constructor(
+        public node_id: number,
+        public scrollXOffset: number,
+        public scrollYOffset: number
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): ElementScrolledV2 {
+        if (reader.readU32() !== FrameType.ElementScrolledV2) throw new Error(`Expected ElementScrolledV2 frame type`);
+        const node_id = reader.readU32();
+        const scrollXOffset = reader.readI32();
+        const scrollYOffset = reader.readI32();
+        return new ElementScrolledV2(node_id, scrollXOffset, scrollYOffset);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.ElementScrolledV2);
+        w.u32(this.node_id);
+        w.i32(Math.round(this.scrollXOffset));
+        w.i32(Math.round(this.scrollYOffset));
+        await w.endFrame();
+    }
+}
+
+export class KeyframeBegin extends Frame {
+    constructor(
+        public documentId: number,
+        public adoptedStyleSheets: VStyleSheet[],
+        public viewportWidth: number,
+        public viewportHeight: number
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): KeyframeBegin {
+        if (reader.readU32() !== FrameType.KeyframeBegin) throw new Error(`Expected KeyframeBegin frame type`);
+        const documentId = reader.readU32();
+        const sheetCount = Number(reader.readU64());
+        const adoptedStyleSheets: VStyleSheet[] = [];
+        for (let i = 0; i < sheetCount; i++) {
+            adoptedStyleSheets.push(VStyleSheet.decode(reader));
+        }
+        const viewportWidth = reader.readU32();
+        const viewportHeight = reader.readU32();
+        return new KeyframeBegin(documentId, adoptedStyleSheets, viewportWidth, viewportHeight);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.KeyframeBegin);
+        w.u32(this.documentId);
+        w.u64(BigInt(this.adoptedStyleSheets.length));
+        for (const sheet of this.adoptedStyleSheets) {
+            sheet.encode(w);
+        }
+        w.u32(this.viewportWidth);
+        w.u32(this.viewportHeight);
+        await w.endFrame();
+    }
+}
+
+export class KeyframeChunk extends Frame {
+    constructor(
+        public parentNodeId: number,
+        public nodes: VNode[]
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): KeyframeChunk {
+        if (reader.readU32() !== FrameType.KeyframeChunk) throw new Error(`Expected KeyframeChunk frame type`);
+        const parentNodeId = reader.readU32();
+        const nodeCount = Number(reader.readU64());
+        const nodes: VNode[] = [];
+        for (let i = 0; i < nodeCount; i++) {
+            nodes.push(VNode.decode(reader));
+        }
+        return new KeyframeChunk(parentNodeId, nodes);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.KeyframeChunk);
+        w.u32(this.parentNodeId);
+        w.u64(BigInt(this.nodes.length));
+        for (const node of this.nodes) {
+            node.encode(w);
+        }
+        await w.endFrame();
+    }
+}
+
+export class KeyframeEnd extends Frame {
+    constructor() {
+        super();
+    }
+
+    static decode(reader: BufferReader): KeyframeEnd {
+        if (reader.readU32() !== FrameType.KeyframeEnd) throw new Error(`Expected KeyframeEnd frame type`);
+        return new KeyframeEnd();
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.KeyframeEnd);
+        await w.endFrame();
+    }
+}
+
+// A NewAdoptedStyleSheet whose text the server moved to its asset cache;
+// `hash` resolves to the text like an AssetReference hash
+export class NewAdoptedStyleSheetReference extends Frame {
+    constructor(
+        public id: number,
+        public media: string | undefined,
+        public hash: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): NewAdoptedStyleSheetReference {
+        if (reader.readU32() !== FrameType.NewAdoptedStyleSheetReference) throw new Error(`Expected NewAdoptedStyleSheetReference frame type`);
+        const id = reader.readU32();
+        const media = reader.readByte() === 1 ? reader.readString() : undefined;
+        const hash = reader.readString();
+        return new NewAdoptedStyleSheetReference(id, media, hash);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.NewAdoptedStyleSheetReference);
+        w.u32(this.id);
+        if (this.media !== undefined) {
+            w.byte(1);
+            w.strUtf8(this.media);
+        } else {
+            w.byte(0);
+        }
+        w.strUtf8(this.hash);
+        await w.endFrame();
+    }
+}
+
+// A StyleSheetReplaced whose content the server moved to its asset cache
+export class StyleSheetReplacedReference extends Frame {
+    constructor(
+        public styleSheetId: number,
+        public hash: string
+    ) {
+        super();
+    }
+
+    static decode(reader: BufferReader): StyleSheetReplacedReference {
+        if (reader.readU32() !== FrameType.StyleSheetReplacedReference) throw new Error(`Expected StyleSheetReplacedReference frame type`);
+        const styleSheetId = reader.readU32();
+        const hash = reader.readString();
+        return new StyleSheetReplacedReference(styleSheetId, hash);
+    }
+
+    async encode(w: Writer): Promise<void> {
+        w.startFrame();
+        w.u32(FrameType.StyleSheetReplacedReference);
+        w.u32(this.styleSheetId);
+        w.strUtf8(this.hash);
+        await w.endFrame();
+    }
+}
+
+/**
+ * Rough encoded size of a node and its subtree, for chunking decisions
+ */
+function estimateNodeSize(node: VNode): number {
+    if (node instanceof VElement) {
+        let size = 32 + node.tag.length + (node.ns?.length ?? 0);
+        for (const [name, value] of Object.entries(node.attrs || {})) {
+            size += 16 + name.length + value.length;
+        }
+        for (const child of node.children || []) {
+            size += estimateNodeSize(child);
+        }
+        return size;
+    }
+    if (node instanceof VDocumentType) {
+        return 24 + node.name.length + (node.publicId?.length ?? 0) + (node.systemId?.length ?? 0);
+    }
+    return 16 + ((node as { text?: string }).text?.length ?? 0);
+}
+
+/**
+ * Split a keyframe larger than `maxChunkBytes` into KeyframeBegin,
+ * KeyframeChunk... and KeyframeEnd frames; smaller keyframes are returned
+ * as they are.
+ *
+ * Mirrors `chunk_keyframe` in proto-rs: an element that does not fit in a
+ * chunk is sent without its children, which follow in chunks of their own.
+ */
+export function chunkKeyframe(keyframe: Keyframe, maxChunkBytes: number): Frame[] {
+    const doc = keyframe.vDocument;
+    const total = doc.children.reduce((sum, child) => sum + estimateNodeSize(child), 0);
+    if (total <= maxChunkBytes) {
+        return [keyframe];
+    }
+
+    const frames: Frame[] = [
+        new KeyframeBegin(doc.id, doc.adoptedStyleSheets, keyframe.viewportWidth, keyframe.viewportHeight),
+    ];
+    let parentNodeId = doc.id;
+    let nodes: VNode[] = [];
+    let size = 0;
+
+    const flush = () => {
+        if (nodes.length > 0) {
+            frames.push(new KeyframeChunk(parentNodeId, nodes));
+        }
+        nodes = [];
+        size = 0;
+    };
+    const add = (node: VNode, nodeSize: number, parent: number) => {
+        if (parent !== parentNodeId || (size + nodeSize > maxChunkBytes && nodes.length > 0)) {
+            flush();
+            parentNodeId = parent;
+        }
+        nodes.push(node);
+        size += nodeSize;
+    };
+    const push = (node: VNode, parent: number) => {
+        const nodeSize = estimateNodeSize(node);
+        const splittable = node instanceof VElement && node.children.some(child => child instanceof VElement);
+        if (nodeSize <= maxChunkBytes || !splittable) {
+            add(node, nodeSize, parent);
+            return;
+        }
+        const element = node as VElement;
+        const shallow = new VElement(element.id, element.tag, element.ns, element.attrs, [], element.shadow);
+        add(shallow, estimateNodeSize(shallow), parent);
+        for (const child of element.children) {
+            push(child, element.id);
+        }
+    };
+
+    for (const child of doc.children) {
+        push(child, doc.id);
+    }
+    flush();
+    frames.push(new KeyframeEnd());
+    return frames;
+}
+
 DECODERS[FrameType.Timestamp] = Timestamp.decode;
 DECODERS[FrameType.Keyframe] = Keyframe.decode;
 DECODERS[FrameType.Asset] = Asset.decode;
@@ -1098,4 +1911,29 @@ DECODERS[FrameType.RecordingMetadata] = RecordingMetadata.decode;
 DECODERS[FrameType.AssetReference] = AssetReference.decode;
 DECODERS[FrameType.CacheManifest] = CacheManifest.decode;
 DECODERS[FrameType.PlaybackConfig] = PlaybackConfig.decode;
-DECODERS[FrameType.Heartbeat] = Heartbeat.decode;
\ No newline at end of file
+DECODERS[FrameType.Heartbeat] = Heartbeat.decode;
+DECODERS[FrameType.PageError] = PageError.decode;
+DECODERS[FrameType.Annotation] = Annotation.decode;
+DECODERS[FrameType.TraceContext] = TraceContext.decode;
+DECODERS[FrameType.RecordingPaused] = RecordingPaused.decode;
+DECODERS[FrameType.RecordingResumed] = RecordingResumed.decode;
+DECODERS[FrameType.ScrollOffsetChangedV2] = ScrollOffsetChangedV2.decode;
+DECODERS[FrameType.MouseMovedV2] = MouseMovedV2.decode;
+DECODERS[FrameType.MouseClickedV2] = MouseClickedV2.decode;
+DECODERS[FrameType.ElementScrolledV2] = ElementScrolledV2.decode;
+DECODERS[FrameType.KeyframeBegin] = KeyframeBegin.decode;
+DECODERS[FrameType.KeyframeChunk] = KeyframeChunk.decode;
+DECODERS[FrameType.KeyframeEnd] = KeyframeEnd.decode;
+DECODERS[FrameType.NewAdoptedStyleSheetReference] = NewAdoptedStyleSheetReference.decode;
+DECODERS[FrameType.StyleSheetReplacedReference] = StyleSheetReplacedReference.decode;
+DECODERS[FrameType.CanvasChangedDelta] = CanvasChangedDelta.decode;
+DECODERS[FrameType.HeartbeatPing] = HeartbeatPing.decode;
+DECODERS[FrameType.HeartbeatPong] = HeartbeatPong.decode;
+DECODERS[FrameType.SessionIdentity] = SessionIdentity.decode;
+DECODERS[FrameType.StopRecording] = StopRecording.decode;
+DECODERS[FrameType.IdlePeriod] = IdlePeriod.decode;
+DECODERS[FrameType.RecordingTruncated] = RecordingTruncated.decode;
+DECODERS[FrameType.CacheInventory] = CacheInventory.decode;
+DECODERS[FrameType.RequestKeyframe] = RequestKeyframe.decode;
+DECODERS[FrameType.RecordingScope] = RecordingScope.decode;
+DECODERS[FrameType.SessionLink] = SessionLink.decode;
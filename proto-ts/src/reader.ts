@@ -3,12 +3,47 @@ import { Frame } from "./frames";
 // BufferReader interface for DOM decoding
 interface BufferReader {
     readU32(): number;
+    readI32(): number;
+    readF32(): number;
     readU64(): bigint;
     readString(): string;
     readBytes(length: number): Uint8Array;
     readByte(): number; // Read a single byte
 }
 
+/** Written in place of a frame length where a v2 file's seek index begins */
+const INDEX_MARKER = 0xFFFFFFFF;
+
+/** Set in the first reserved header byte when each frame starts with a flag byte */
+const HEADER_FLAG_FRAME_COMPRESSION = 0x01;
+/** Frame flag: the payload is zstd-compressed */
+const FRAME_FLAG_ZSTD = 0x01;
+/** Set in the first reserved header byte when each frame ends with a CRC32 */
+const HEADER_FLAG_FRAME_CHECKSUM = 0x02;
+/** Bytes of the CRC32 ending each frame of a checksummed file */
+const CHECKSUM_SIZE = 4;
+
+/** CRC32 (IEEE) lookup table */
+const CRC32_TABLE = (() => {
+    const table = new Uint32Array(256);
+    for (let n = 0; n < 256; n++) {
+        let c = n;
+        for (let k = 0; k < 8; k++) {
+            c = c & 1 ? 0xEDB88320 ^ (c >>> 1) : c >>> 1;
+        }
+        table[n] = c >>> 0;
+    }
+    return table;
+})();
+
+function crc32(bytes: Uint8Array): number {
+    let crc = 0xFFFFFFFF;
+    for (let i = 0; i < bytes.length; i++) {
+        crc = CRC32_TABLE[(crc ^ bytes[i]) & 0xFF] ^ (crc >>> 8);
+    }
+    return (crc ^ 0xFFFFFFFF) >>> 0;
+}
+
 // Header structure for .dcrr files
 export interface DCRRHeader {
     magic: Uint8Array;
@@ -25,6 +60,12 @@ export class Reader implements BufferReader {
     private expectHeader: boolean;
     private headerParsed: boolean = false;
     private frameNumber: number = 0;
+    /** Set at the seek index of a v2 file, after which the rest is ignored */
+    private ended: boolean = false;
+    /** Frames start with a compression flag byte, as the header says */
+    private flagged: boolean = false;
+    /** Frames end with a CRC32, as the header says */
+    private checksummed: boolean = false;
     private static dec = new TextDecoder();
 
     private constructor(inputStream: ReadableStream<Uint8Array>, expectHeader: boolean) {
@@ -70,7 +111,7 @@ export class Reader implements BufferReader {
 
                 if (done) {
                     // End of input - check if we have incomplete data
-                    if (this.bufferOffset < this.buffer.length) {
+                    if (!this.ended && this.bufferOffset < this.buffer.length) {
                         throw new Error("Unexpected end of stream: incomplete frame data");
                     }
                     this.controller?.close();
@@ -113,7 +154,7 @@ export class Reader implements BufferReader {
         }
 
         // Process frames
-        while (this.tryParseFrame()) {
+        while (!this.ended && this.tryParseFrame()) {
             // Keep parsing frames while we have complete ones
         }
     }
@@ -146,7 +187,9 @@ export class Reader implements BufferReader {
             // Parse timestamp (u64 BE)  
             const createdAt = this.readU64();
 
-            // Skip reserved bytes (16 bytes)
+            // Reserved bytes (16 bytes), the first of them header flags
+            this.flagged = (this.buffer[this.bufferOffset] & HEADER_FLAG_FRAME_COMPRESSION) !== 0;
+            this.checksummed = (this.buffer[this.bufferOffset] & HEADER_FLAG_FRAME_CHECKSUM) !== 0;
             this.bufferOffset += 16;
 
             this.header = { magic, version, createdAt };
@@ -166,6 +209,12 @@ export class Reader implements BufferReader {
         const startOffset = this.bufferOffset;
         const frameLength = this.readU32();
 
+        // A v2 file's seek index follows its frames, marked by a length of 0xFFFFFFFF
+        if (frameLength === INDEX_MARKER) {
+            this.ended = true;
+            return false;
+        }
+
         // Check if we have the full frame
         if (this.availableBytes() < frameLength) {
             this.bufferOffset = startOffset; // Backtrack
@@ -175,6 +224,31 @@ export class Reader implements BufferReader {
         try {
             this.frameNumber++;
 
+            // The checksum covers everything after the length prefix before it
+            if (this.checksummed && frameLength < CHECKSUM_SIZE) {
+                throw new Error(`Frame ${this.frameNumber} is shorter than its checksum`);
+            }
+            const bodyLength = this.checksummed ? frameLength - CHECKSUM_SIZE : frameLength;
+            if (this.checksummed) {
+                const bodyStart = this.bufferOffset;
+                const view = new DataView(this.buffer.buffer, this.buffer.byteOffset + bodyStart + bodyLength, CHECKSUM_SIZE);
+                const expected = view.getUint32(0, false);
+                const actual = crc32(this.buffer.subarray(bodyStart, bodyStart + bodyLength));
+                if (actual !== expected) {
+                    throw new Error(`Frame ${this.frameNumber} checksum mismatch: expected ${expected.toString(16)}, got ${actual.toString(16)}`);
+                }
+            }
+
+            if (this.flagged) {
+                const flag = this.readByte();
+                if (flag === FRAME_FLAG_ZSTD) {
+                    throw new Error(`Frame ${this.frameNumber} is zstd-compressed, which this reader doesn't support`);
+                }
+                if (flag !== 0) {
+                    throw new Error(`Frame ${this.frameNumber} has unknown flags: ${flag}`);
+                }
+            }
+
             // Parse frame using Frame.decode (which reads the frame type internally)
             const frame = Frame.decode(this);
 
@@ -182,13 +256,13 @@ export class Reader implements BufferReader {
                 throw new Error("Failed to decode frame - unknown or invalid frame type");
             }
 
-            // Verify we consumed exactly frameLength bytes
+            // Verify we consumed exactly the frame's bytes before any checksum
             const consumed = this.bufferOffset - (startOffset + 4);
-            if (consumed !== frameLength) {
+            if (consumed !== bodyLength) {
                 console.warn(`⚠️ Reader: Frame ${this.frameNumber} consumed ${consumed} bytes, but length prefix was ${frameLength}`);
-                // Correct the offset to match the frame boundary
-                this.bufferOffset = startOffset + 4 + frameLength;
             }
+            // Move to the frame boundary, past any checksum
+            this.bufferOffset = startOffset + 4 + frameLength;
 
             // Emit the frame
             this.controller?.enqueue(frame);
@@ -237,6 +311,28 @@ export class Reader implements BufferReader {
         return value;
     }
 
+    readI32(): number {
+        if (this.availableBytes() < 4) {
+            throw new Error("Not enough data for i32");
+        }
+
+        const view = new DataView(this.buffer.buffer, this.buffer.byteOffset + this.bufferOffset, 4);
+        const value = view.getInt32(0, false); // big-endian (bincode configured)
+        this.bufferOffset += 4;
+        return value;
+    }
+
+    readF32(): number {
+        if (this.availableBytes() < 4) {
+            throw new Error("Not enough data for f32");
+        }
+
+        const view = new DataView(this.buffer.buffer, this.buffer.byteOffset + this.bufferOffset, 4);
+        const value = view.getFloat32(0, false); // big-endian (bincode configured)
+        this.bufferOffset += 4;
+        return value;
+    }
+
     readU64(): bigint {
         if (this.availableBytes() < 8) {
             throw new Error("Not enough data for u64");
@@ -73,6 +73,19 @@ export class Writer {
         this.byte(n >>> 24); this.byte(n >>> 16); this.byte(n >>> 8); this.byte(n);
     }
 
+    i32(n: number): void {
+        if (this.debug) console.debug(`i32: ${n}`);
+        // two's complement, big-endian (bincode configured)
+        this.u32(n >>> 0);
+    }
+
+    f32(n: number): void {
+        if (this.debug) console.debug(`f32: ${n}`);
+        const bytes = new Uint8Array(4);
+        new DataView(bytes.buffer).setFloat32(0, n, false); // big-endian (bincode configured)
+        for (const b of bytes) this.byte(b);
+    }
+
     u64(n: bigint): void {
         if (this.debug) console.debug(`u64: ${n} (0x${n.toString(16)})`);
         // caller ensures 0n <= n < 2n**64n
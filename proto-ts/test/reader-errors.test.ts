@@ -112,4 +112,33 @@ describe("Reader Error Handling", () => {
             await streamReader.read();
         }).toThrow("Unexpected end of stream: incomplete frame data");
     });
+
+    test("should fail on a checksummed frame shorter than its checksum", async () => {
+        const shortFrame = new Uint8Array([
+            // Magic "DCRR", version 2
+            0x44, 0x43, 0x52, 0x52, 0x00, 0x00, 0x00, 0x02,
+            // Timestamp (u64 BE)
+            0x00, 0x00, 0x01, 0x8A, 0x6E, 0x26, 0x94, 0x00,
+            // Reserved (16 bytes), with the frame checksum flag
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // Frame length: 2 bytes, too few for a CRC32
+            0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00
+        ]);
+
+        const byteStream = new ReadableStream({
+            start(controller) {
+                controller.enqueue(shortFrame);
+                controller.close();
+            }
+        });
+
+        const [reader, frameStream] = Reader.create(byteStream, true);
+
+        await expect(async () => {
+            const streamReader = frameStream.getReader();
+            await streamReader.read();
+        }).toThrow("Frame 1 is shorter than its checksum");
+    });
 });
\ No newline at end of file
@@ -0,0 +1,286 @@
+//! Python bindings for DomCorder recordings (`import domcorder`)
+//!
+//! Frames are plain dicts with a `"type"` key next to the frame's fields,
+//! e.g. `{"type": "MouseMoved", "x": 150, "y": 200}`; nested values (DOM nodes,
+//! text operations) keep the `dcrr convert` JSON shape. Files are read with
+//! or without a DCRR header, detected from the magic bytes.
+
+use domcorder_proto::stats::RecordingStats;
+use domcorder_proto::writer::DCRR_MAGIC;
+use domcorder_proto::{FileHeader, Frame, FrameValidator, FrameWriter, SyncFrameReader, ValidationIssue};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString};
+use pythonize::{depythonize, pythonize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+type Reader = SyncFrameReader<BufReader<File>>;
+
+/// Open a recording, reading its header if it has one
+fn open(path: &Path) -> io::Result<Reader> {
+    let mut magic = [0u8; 4];
+    let has_header = File::open(path)?.read_exact(&mut magic).is_ok() && magic == DCRR_MAGIC;
+    let mut reader = SyncFrameReader::new(BufReader::new(File::open(path)?), has_header);
+    if has_header {
+        reader.read_header()?;
+    }
+    Ok(reader)
+}
+
+fn io_error(path: &Path, e: io::Error) -> PyErr {
+    PyIOError::new_err(format!("{}: {}", path.display(), e))
+}
+
+/// `{"MouseMoved": {...}}` (or `"Heartbeat"`) as `{"type": "MouseMoved", ...}`
+fn frame_to_dict<'py>(py: Python<'py>, frame: &Frame) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("type", frame.type_name())?;
+    let tagged = pythonize(py, frame)?;
+    if let Ok(tagged) = tagged.cast::<PyDict>()
+        && let Some((_, fields)) = tagged.iter().next()
+        && let Ok(fields) = fields.cast::<PyDict>()
+    {
+        dict.update(fields.as_mapping())?;
+    }
+    Ok(dict)
+}
+
+/// The inverse of [`frame_to_dict`]
+fn frame_from_dict(frame: &Bound<'_, PyDict>) -> PyResult<Frame> {
+    let py = frame.py();
+    let frame_type: String = frame
+        .get_item("type")?
+        .ok_or_else(|| PyValueError::new_err("frame has no \"type\""))?
+        .extract()?;
+    let fields = frame.copy()?;
+    fields.del_item("type")?;
+
+    let tagged = PyDict::new(py);
+    tagged.set_item(&frame_type, &fields)?;
+    match depythonize::<Frame>(tagged.as_any()) {
+        Ok(frame) => Ok(frame),
+        // Unit variants (Heartbeat, RecordingResumed) are bare strings
+        Err(_) if fields.is_empty() => depythonize::<Frame>(PyString::new(py, &frame_type).as_any())
+            .map_err(|e| PyValueError::new_err(format!("invalid {} frame: {}", frame_type, e))),
+        Err(e) => Err(PyValueError::new_err(format!("invalid {} frame: {}", frame_type, e))),
+    }
+}
+
+/// Iterates over the frames of a recording as dicts
+#[pyclass(name = "FrameReader", unsendable)]
+struct PyFrameReader {
+    path: PathBuf,
+    reader: Reader,
+}
+
+#[pymethods]
+impl PyFrameReader {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        let reader = open(&path).map_err(|e| io_error(&path, e))?;
+        Ok(Self { path, reader })
+    }
+
+    /// Creation time from the DCRR header (ms since the epoch), if there is one
+    #[getter]
+    fn created_at(&self) -> Option<u64> {
+        self.reader.header().map(|header| header.created_at)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        match self.reader.read_frame() {
+            Ok(Some(frame)) => Ok(Some(frame_to_dict(py, &frame)?)),
+            Ok(None) => Ok(None),
+            Err(e) => Err(io_error(&self.path, e)),
+        }
+    }
+}
+
+/// Read every frame of a recording
+#[pyfunction]
+fn read_frames<'py>(py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyList>> {
+    let frames = PyList::empty(py);
+    for frame in open(&path).map_err(|e| io_error(&path, e))? {
+        frames.append(frame_to_dict(py, &frame.map_err(|e| io_error(&path, e))?)?)?;
+    }
+    Ok(frames)
+}
+
+/// Write frames to a .dcrr file; `created_at` (ms since the epoch) defaults to now
+#[pyfunction]
+#[pyo3(signature = (path, frames, created_at=None))]
+fn write_frames(path: PathBuf, frames: &Bound<'_, PyAny>, created_at: Option<u64>) -> PyResult<()> {
+    let file = File::create(&path).map_err(|e| io_error(&path, e))?;
+    let mut writer = FrameWriter::new(BufWriter::new(file));
+    let header = created_at.map_or_else(FileHeader::new, FileHeader::with_timestamp);
+    writer.write_header(&header).map_err(|e| io_error(&path, e))?;
+    for frame in frames.try_iter()? {
+        let frame = frame_from_dict(frame?.cast::<PyDict>()?)?;
+        writer.write_frame(&frame).map_err(|e| io_error(&path, e))?;
+    }
+    writer.flush().map_err(|e| io_error(&path, e))
+}
+
+/// Duration, frame counts and sizes by type, and inline assets
+#[pyfunction]
+fn stats<'py>(py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyDict>> {
+    let mut reader = open(&path).map_err(|e| io_error(&path, e))?;
+    let mut stats = RecordingStats::new();
+    loop {
+        let offset = reader.position();
+        match reader.read_frame().map_err(|e| io_error(&path, e))? {
+            Some(frame) => stats.push(&frame, reader.position() - offset),
+            None => break,
+        }
+    }
+
+    let by_type = PyDict::new(py);
+    for (name, type_stats) in &stats.by_type {
+        let entry = PyDict::new(py);
+        entry.set_item("count", type_stats.count)?;
+        entry.set_item("bytes", type_stats.bytes)?;
+        by_type.set_item(name, entry)?;
+    }
+    let assets = PyList::empty(py);
+    for asset in &stats.assets {
+        let entry = PyDict::new(py);
+        entry.set_item("asset_id", asset.asset_id)?;
+        entry.set_item("url", &asset.url)?;
+        entry.set_item("mime", &asset.mime)?;
+        entry.set_item("size", asset.size)?;
+        assets.append(entry)?;
+    }
+
+    let result = PyDict::new(py);
+    result.set_item("frame_count", stats.frame_count)?;
+    result.set_item("duration_ms", stats.duration_ms())?;
+    result.set_item("total_bytes", stats.total_bytes())?;
+    result.set_item("by_type", by_type)?;
+    result.set_item("assets", assets)?;
+    result.set_item("duplicate_asset_bytes", stats.duplicate_asset_bytes)?;
+    Ok(result)
+}
+
+/// Semantic problems in a recording, as `{"frame_index", "kind", "message"}` dicts
+///
+/// A frame that cannot be decoded raises an IOError.
+#[pyfunction]
+fn validate<'py>(py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyList>> {
+    let mut validator = FrameValidator::new();
+    let mut issues = Vec::new();
+    for frame in open(&path).map_err(|e| io_error(&path, e))? {
+        issues.extend(validator.push_frame(&frame.map_err(|e| io_error(&path, e))?));
+    }
+    issues.extend(validator.finish());
+
+    let result = PyList::empty(py);
+    for issue in issues {
+        let entry = PyDict::new(py);
+        entry.set_item("frame_index", issue.frame_index())?;
+        entry.set_item(
+            "kind",
+            match issue {
+                ValidationIssue::NonMonotonicTimestamp { .. } => "non_monotonic_timestamp",
+                ValidationIssue::Dom { .. } => "dom",
+                ValidationIssue::UnresolvedAsset { .. } => "unresolved_asset",
+            },
+        )?;
+        entry.set_item("message", issue.to_string())?;
+        result.append(entry)?;
+    }
+    Ok(result)
+}
+
+#[pymodule]
+fn domcorder(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFrameReader>()?;
+    m.add_function(wrap_pyfunction!(read_frames, m)?)?;
+    m.add_function(wrap_pyfunction!(write_frames, m)?)?;
+    m.add_function(wrap_pyfunction!(stats, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyModule;
+
+    const SAMPLE: &str = "../.sample_data/proto/file-basic.dcrr";
+
+    fn module(py: Python<'_>) -> Bound<'_, PyModule> {
+        let module = PyModule::new(py, "domcorder").unwrap();
+        domcorder(&module).unwrap();
+        module
+    }
+
+    #[test]
+    fn test_frames_round_trip_through_dicts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let copy = temp_dir.path().join("copy.dcrr");
+        Python::attach(|py| {
+            let module = module(py);
+            let frames = module.getattr("read_frames").unwrap().call1((SAMPLE,)).unwrap();
+            let first = frames.get_item(0).unwrap();
+            assert_eq!(first.get_item("type").unwrap().extract::<String>().unwrap(), "Timestamp");
+
+            module
+                .getattr("write_frames")
+                .unwrap()
+                .call1((&copy, &frames, 1_722_550_000_000u64))
+                .unwrap();
+            let reader = module.getattr("FrameReader").unwrap().call1((&copy,)).unwrap();
+            assert_eq!(reader.getattr("created_at").unwrap().extract::<u64>().unwrap(), 1_722_550_000_000);
+        });
+
+        let original: Vec<Frame> = open(Path::new(SAMPLE)).unwrap().map(Result::unwrap).collect();
+        let copied: Vec<Frame> = open(&copy).unwrap().map(Result::unwrap).collect();
+        assert_eq!(copied, original);
+    }
+
+    #[test]
+    fn test_stats_and_validate() {
+        Python::attach(|py| {
+            let module = module(py);
+            let stats = module.getattr("stats").unwrap().call1((SAMPLE,)).unwrap();
+            let frame_count: u64 = stats.get_item("frame_count").unwrap().extract().unwrap();
+            let keyframes: u64 = stats
+                .get_item("by_type")
+                .unwrap()
+                .get_item("Keyframe")
+                .unwrap()
+                .get_item("count")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(frame_count > 1);
+            assert_eq!(keyframes, 1);
+
+            let issues = module.getattr("validate").unwrap().call1((SAMPLE,)).unwrap();
+            for issue in issues.try_iter().unwrap() {
+                let kind: String = issue.unwrap().get_item("kind").unwrap().extract().unwrap();
+                assert!(["non_monotonic_timestamp", "dom", "unresolved_asset"].contains(&kind.as_str()));
+            }
+        });
+    }
+
+    #[test]
+    fn test_unit_and_empty_frames_from_dicts() {
+        Python::attach(|py| {
+            let frame = |frame_type: &str| {
+                let dict = PyDict::new(py);
+                dict.set_item("type", frame_type).unwrap();
+                frame_from_dict(&dict)
+            };
+            assert_eq!(frame("Heartbeat").unwrap(), Frame::Heartbeat);
+            assert!(matches!(frame("WindowFocused").unwrap(), Frame::WindowFocused(_)));
+            assert!(frame("MouseMoved").is_err());
+        });
+    }
+}
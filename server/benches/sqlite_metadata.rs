@@ -0,0 +1,77 @@
+//! Concurrent ingestion against SqliteMetadataStore
+//!
+//! Simulates many recordings registering asset usage at once (the hot path
+//! during ingest) and reports throughput for a single connection versus the
+//! default pool. Run with `cargo bench -p domcorder-server --bench sqlite_metadata`.
+
+use domcorder_server::asset_cache::sqlite::{SqliteConfig, SqliteMetadataStore};
+use domcorder_server::asset_cache::{AssetMetadata, AssetUsageParams, MetadataStore};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const RECORDINGS: usize = 64;
+const ASSETS_PER_RECORDING: usize = 50;
+
+async fn ingest(store: Arc<SqliteMetadataStore>) -> Duration {
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..RECORDINGS)
+        .map(|recording| {
+            let store = Arc::clone(&store);
+            tokio::spawn(async move {
+                for asset in 0..ASSETS_PER_RECORDING {
+                    let sha256_hash = format!("{:064x}", asset);
+                    // Lookups dominate: most assets are already cached
+                    if store.resolve_hashes(&sha256_hash).await.unwrap().is_none() {
+                        store
+                            .store_asset_metadata(AssetMetadata {
+                                sha256_hash: sha256_hash.clone(),
+                                random_id: format!("{:064x}", asset + 1_000_000),
+                                size: 1024,
+                                mime_type: "text/css".to_string(),
+                            })
+                            .await
+                            .unwrap();
+                    }
+                    store
+                        .register_asset_usage(AssetUsageParams {
+                            site_origin: format!("https://site{}.example", recording % 8),
+                            url: format!("https://cdn.example/{}.css", asset),
+                            sha256_hash,
+                            size: 1024,
+                        })
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    start.elapsed()
+}
+
+async fn run(label: &str, config: SqliteConfig) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let store = Arc::new(SqliteMetadataStore::with_config(dir.path().join("bench.db"), config).unwrap());
+    let elapsed = ingest(store).await;
+    let ops = RECORDINGS * ASSETS_PER_RECORDING * 2;
+    println!(
+        "{:<24} {:>8.1} ms  {:>10.0} ops/s",
+        label,
+        elapsed.as_secs_f64() * 1000.0,
+        ops as f64 / elapsed.as_secs_f64()
+    );
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    // `cargo test --benches` runs this binary too; keep that cheap
+    if std::env::args().any(|arg| arg == "--bench") {
+        let timeout = Duration::from_secs(60);
+        let single = SqliteConfig { max_connections: 1, operation_timeout: timeout, ..SqliteConfig::default() };
+        let pooled = SqliteConfig { operation_timeout: timeout, ..SqliteConfig::default() };
+        run("1 connection", single).await;
+        run(&format!("{} connections", pooled.max_connections), pooled).await;
+    }
+}
@@ -0,0 +1,265 @@
+//! Alerting rules
+//!
+//! Rules are matched against each recording as it is ingested, e.g. "a
+//! PageError on `https://shop.example`" or "a session over 30 minutes", so a
+//! team hears of broken sessions while they happen rather than when someone
+//! watches them. A rule fires at most once per recording, on the frame that
+//! matches it. Its alert is logged, and can also be posted to a webhook as
+//! JSON, and marked in the recording with an Annotation frame named
+//! [`ALERT_ANNOTATION`] right after the matching frame, which shows on the
+//! timeline and can be a funnel step.
+//!
+//! Rules are JSON (see [`parse_alert_rules`]); the server binary loads them
+//! from the file named by `DOMCORDER_ALERT_RULES`. Webhooks need the
+//! `webhooks` feature.
+
+use crate::analytics::page_of_url;
+use crate::observability::names;
+use crate::timeline::ActiveClock;
+use crate::{RecordingId, StorageState};
+use domcorder_proto::{AnnotationData, Frame};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Name of the Annotation frames marking where an alert fired
+pub const ALERT_ANNOTATION: &str = "domcorder:alert";
+
+/// A condition to watch ingested recordings for, and what to do when one meets it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// Only recordings of this origin; every origin when unset
+    #[serde(default)]
+    pub origin: Option<String>,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+    /// URL the alert is posted to
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Mark the alert in the recording
+    #[serde(default)]
+    pub annotate: bool,
+}
+
+/// When an [`AlertRule`] fires, tagged by `when`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// The recording has `count` frames of type `frame` (see [`Frame::type_name`])
+    Frame {
+        frame: String,
+        #[serde(default = "one")]
+        count: u32,
+    },
+    /// The recording runs longer than `duration_ms`, not counting pauses and
+    /// idle periods
+    Duration { duration_ms: u64 },
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// A rule that fired, as posted to its webhook
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    pub rule: String,
+    pub recording_id: String,
+    pub site_origin: Option<String>,
+    /// Milliseconds since the session started, when the rule fired
+    pub offset_ms: u64,
+}
+
+/// Parse a JSON array of rules
+pub fn parse_alert_rules(json: &str) -> Result<Vec<AlertRule>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Matches the rules against the frames of one recording
+#[derive(Debug)]
+pub struct AlertMatcher<'a> {
+    rules: &'a [AlertRule],
+    site_origin: Option<String>,
+    clock: ActiveClock,
+    /// Frames counted towards each rule
+    counts: Vec<u32>,
+    fired: Vec<bool>,
+}
+
+impl<'a> AlertMatcher<'a> {
+    /// Match `rules` against a recording; `site_origin` overrides the origin
+    /// derived from the recording's initial URL
+    pub fn new(rules: &'a [AlertRule], site_origin: Option<&str>) -> Self {
+        Self {
+            rules,
+            site_origin: site_origin.map(str::to_string),
+            clock: ActiveClock::default(),
+            counts: vec![0; rules.len()],
+            fired: vec![false; rules.len()],
+        }
+    }
+
+    pub fn site_origin(&self) -> Option<&str> {
+        self.site_origin.as_deref()
+    }
+
+    /// Milliseconds since the session started
+    pub fn offset(&self) -> u64 {
+        self.clock.offset()
+    }
+
+    /// Feed the next frame, getting back the rules it makes fire
+    pub fn push_frame(&mut self, frame: &Frame) -> Vec<&'a AlertRule> {
+        self.clock.push_frame(frame);
+        if let Frame::RecordingMetadata(metadata) = frame
+            && self.site_origin.is_none()
+        {
+            self.site_origin = page_of_url(&metadata.initial_url).map(|(origin, _)| origin);
+        }
+
+        let mut fired = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if self.fired[i] || !self.applies_to_origin(rule) {
+                continue;
+            }
+            let matched = match &rule.condition {
+                AlertCondition::Frame {
+                    frame: type_name,
+                    count,
+                } => {
+                    if frame.type_name() == type_name {
+                        self.counts[i] += 1;
+                    }
+                    self.counts[i] >= *count
+                }
+                AlertCondition::Duration { duration_ms } => self.clock.offset() > *duration_ms,
+            };
+            if matched {
+                self.fired[i] = true;
+                fired.push(rule);
+            }
+        }
+        fired
+    }
+
+    fn applies_to_origin(&self, rule: &AlertRule) -> bool {
+        match &rule.origin {
+            None => true,
+            Some(origin) => self.site_origin.as_deref() == Some(origin.trim_end_matches('/')),
+        }
+    }
+}
+
+impl StorageState {
+    /// Raise the alert of `rule` for recording `id`: log it and post it to
+    /// the rule's webhook, returning the annotation to write if it asks for one
+    pub(crate) fn raise_alert(
+        &self,
+        rule: &AlertRule,
+        id: &RecordingId,
+        site_origin: Option<&str>,
+        offset_ms: u64,
+    ) -> Option<Frame> {
+        warn!("🚨 Alert {:?} fired for recording {}", rule.name, id);
+        self.observability.counter(names::ALERTS, 1, &[("rule", &rule.name)]);
+        let alert = Alert {
+            rule: rule.name.clone(),
+            recording_id: id.to_string(),
+            site_origin: site_origin.map(str::to_string),
+            offset_ms,
+        };
+        if let Some(url) = &rule.webhook {
+            tokio::spawn(post_alert(url.clone(), alert.clone()));
+        }
+        rule.annotate.then(|| {
+            Frame::Annotation(AnnotationData {
+                name: ALERT_ANNOTATION.to_string(),
+                data: Some(serde_json::to_string(&alert).expect("alerts serialize")),
+            })
+        })
+    }
+}
+
+/// Post `alert` to `url`; failures are logged
+#[cfg(feature = "webhooks")]
+async fn post_alert(url: String, alert: Alert) {
+    static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+    let client = CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .expect("HTTP client builds")
+    });
+    let result = client
+        .post(&url)
+        .json(&alert)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        warn!("⚠️ Failed to post alert {:?} to {}: {}", alert.rule, url, e);
+    }
+}
+
+#[cfg(not(feature = "webhooks"))]
+async fn post_alert(url: String, alert: Alert) {
+    warn!(
+        "⚠️ Not posting alert {:?} to {}: webhooks need the `webhooks` feature",
+        alert.rule, url
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{PageErrorData, RecordingMetadataData, TimestampData};
+
+    fn at(timestamp: u64) -> Frame {
+        Frame::Timestamp(TimestampData { timestamp })
+    }
+
+    fn page_error() -> Frame {
+        Frame::PageError(PageErrorData {
+            message: "boom".to_string(),
+            source_url: None,
+            line: None,
+            column: None,
+            stack: None,
+        })
+    }
+
+    #[test]
+    fn test_rules_fire_once_per_recording() {
+        let rules = parse_alert_rules(
+            r#"[
+                {"name": "shop errors", "origin": "https://shop.example/", "when": "frame", "frame": "PageError"},
+                {"name": "other errors", "origin": "https://other.example", "when": "frame", "frame": "PageError"},
+                {"name": "error storm", "when": "frame", "frame": "PageError", "count": 3},
+                {"name": "long session", "when": "duration", "duration_ms": 60000, "annotate": true}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(rules[3].condition, AlertCondition::Duration { duration_ms: 60_000 });
+
+        let mut matcher = AlertMatcher::new(&rules, None);
+        let mut fire = |frame: Frame| -> Vec<String> {
+            matcher
+                .push_frame(&frame)
+                .into_iter()
+                .map(|rule| rule.name.clone())
+                .collect()
+        };
+        let metadata = Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: "https://shop.example/cart".to_string(),
+            heartbeat_interval_seconds: 0,
+        });
+        assert!(fire(metadata).is_empty());
+        assert!(fire(at(1_000)).is_empty());
+        assert_eq!(fire(page_error()), vec!["shop errors"]);
+        assert!(fire(page_error()).is_empty());
+        assert_eq!(fire(page_error()), vec!["error storm"]);
+        assert!(fire(at(61_000)).is_empty());
+        assert_eq!(fire(at(61_001)), vec!["long session"]);
+        assert!(fire(page_error()).is_empty());
+    }
+}
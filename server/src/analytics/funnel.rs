@@ -0,0 +1,137 @@
+//! Funnel queries over session events
+//!
+//! A funnel is an ordered list of steps; a session reaches step N if it
+//! contains events matching steps 1..=N in that order (other events may occur
+//! in between).
+
+use super::session::{SessionEvent, SessionEventKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One step of a funnel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunnelStep {
+    pub kind: SessionEventKind,
+    pub name: String,
+}
+
+impl FunnelStep {
+    /// Parse `page:/path` (navigation) or `annotation:name`; a bare name is an annotation
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        let (kind, name) = match spec.split_once(':') {
+            Some(("page", path)) => (SessionEventKind::Navigation, path),
+            Some(("annotation", name)) => (SessionEventKind::Annotation, name),
+            Some(_) => return None,
+            None => (SessionEventKind::Annotation, spec),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(Self {
+            kind,
+            name: name.to_string(),
+        })
+    }
+
+    fn matches(&self, event: &SessionEvent) -> bool {
+        event.kind == self.kind && event.name == self.name
+    }
+}
+
+impl fmt::Display for FunnelStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            SessionEventKind::Navigation => write!(f, "page:{}", self.name),
+            SessionEventKind::Annotation => write!(f, "annotation:{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunnelStepResult {
+    pub step: String,
+    /// Sessions that reached this step (having completed all earlier steps)
+    pub sessions: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunnelResult {
+    pub total_sessions: u64,
+    pub steps: Vec<FunnelStepResult>,
+}
+
+/// Number of leading steps a session completed, in order
+fn steps_reached(steps: &[FunnelStep], events: &[SessionEvent]) -> usize {
+    let mut reached = 0;
+    for event in events {
+        if reached < steps.len() && steps[reached].matches(event) {
+            reached += 1;
+        }
+    }
+    reached
+}
+
+/// Evaluate a funnel over the events of each session
+pub fn evaluate_funnel<'a, I>(steps: &[FunnelStep], sessions: I) -> FunnelResult
+where
+    I: IntoIterator<Item = &'a [SessionEvent]>,
+{
+    let mut counts = vec![0u64; steps.len()];
+    let mut total_sessions = 0;
+    for events in sessions {
+        total_sessions += 1;
+        for count in counts.iter_mut().take(steps_reached(steps, events)) {
+            *count += 1;
+        }
+    }
+
+    FunnelResult {
+        total_sessions,
+        steps: steps
+            .iter()
+            .zip(counts)
+            .map(|(step, sessions)| FunnelStepResult {
+                step: step.to_string(),
+                sessions,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: SessionEventKind, name: &str) -> SessionEvent {
+        SessionEvent {
+            kind,
+            name: name.to_string(),
+            offset_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_funnel_requires_step_order() {
+        let steps: Vec<FunnelStep> = ["page:/cart", "checkout", "annotation:paid"]
+            .iter()
+            .map(|spec| FunnelStep::parse(spec).unwrap())
+            .collect();
+
+        let nav = |name| event(SessionEventKind::Navigation, name);
+        let note = |name| event(SessionEventKind::Annotation, name);
+        let sessions = [
+            vec![nav("/cart"), note("checkout"), note("paid")],
+            vec![nav("/cart"), note("other"), note("checkout")],
+            vec![note("checkout"), nav("/cart")],
+            vec![nav("/")],
+        ];
+        let result = evaluate_funnel(&steps, sessions.iter().map(Vec::as_slice));
+
+        assert_eq!(result.total_sessions, 4);
+        let counts: Vec<u64> = result.steps.iter().map(|s| s.sessions).collect();
+        assert_eq!(counts, vec![3, 2, 1]);
+        assert_eq!(result.steps[0].step, "page:/cart");
+        assert!(FunnelStep::parse("bogus:x").is_none());
+    }
+}
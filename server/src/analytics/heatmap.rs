@@ -0,0 +1,152 @@
+//! Click heatmap aggregation
+//!
+//! Click coordinates are normalized by the viewport size at the time of the
+//! click and counted in a fixed grid, so recordings made at different window
+//! sizes can be aggregated per page.
+
+use super::page_of_url;
+use domcorder_proto::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of buckets along each axis of the heatmap grid
+pub const HEATMAP_GRID_SIZE: u32 = 50;
+
+/// Click count for one grid cell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeatmapBucket {
+    /// Column, 0..HEATMAP_GRID_SIZE (left to right)
+    pub x: u32,
+    /// Row, 0..HEATMAP_GRID_SIZE (top to bottom of the viewport)
+    pub y: u32,
+    pub count: u64,
+}
+
+/// Aggregated heatmap for a page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickHeatmap {
+    pub site_origin: String,
+    pub path: String,
+    pub grid_size: u32,
+    pub total_clicks: u64,
+    pub buckets: Vec<HeatmapBucket>,
+}
+
+impl ClickHeatmap {
+    pub fn new(site_origin: &str, path: &str, buckets: Vec<HeatmapBucket>) -> Self {
+        Self {
+            site_origin: site_origin.to_string(),
+            path: path.to_string(),
+            grid_size: HEATMAP_GRID_SIZE,
+            total_clicks: buckets.iter().map(|b| b.count).sum(),
+            buckets,
+        }
+    }
+}
+
+/// The page a recording's clicks are attributed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeatmapPage {
+    pub site_origin: String,
+    pub path: String,
+}
+
+/// Collects click buckets for a single recording
+#[derive(Debug, Default)]
+pub struct ClickHeatmapCollector {
+    initial_url: Option<String>,
+    viewport: Option<(u32, u32)>,
+    counts: BTreeMap<(u32, u32), u64>,
+}
+
+impl ClickHeatmapCollector {
+    pub fn push_frame(&mut self, frame: &Frame) {
+        match frame {
+            Frame::RecordingMetadata(metadata) => {
+                self.initial_url.get_or_insert_with(|| metadata.initial_url.clone());
+            }
+            Frame::Keyframe(keyframe) => {
+                self.viewport = Some((keyframe.viewport_width, keyframe.viewport_height));
+            }
+            Frame::KeyframeBegin(begin) => {
+                self.viewport = Some((begin.viewport_width, begin.viewport_height));
+            }
+            Frame::ViewportResized(resized) => {
+                self.viewport = Some((resized.width, resized.height));
+            }
+            Frame::MouseClicked(click) => self.count_click(click.x, click.y),
+            // Fractional and off-page positions land in the nearest pixel
+            Frame::MouseClickedV2(click) => self.count_click(click.x as u32, click.y as u32),
+            _ => {}
+        }
+    }
+
+    fn count_click(&mut self, x: u32, y: u32) {
+        if let Some(bucket) = self.viewport.and_then(|viewport| bucket_for(x, y, viewport)) {
+            *self.counts.entry(bucket).or_default() += 1;
+        }
+    }
+
+    /// The page the clicks belong to, if the initial URL was seen
+    pub fn page(&self, site_origin: Option<&str>) -> Option<HeatmapPage> {
+        let (origin, path) = page_of_url(self.initial_url.as_deref()?)?;
+        Some(HeatmapPage {
+            site_origin: site_origin.map(str::to_string).unwrap_or(origin),
+            path,
+        })
+    }
+
+    pub fn buckets(&self) -> Vec<HeatmapBucket> {
+        self.counts
+            .iter()
+            .map(|(&(x, y), &count)| HeatmapBucket { x, y, count })
+            .collect()
+    }
+}
+
+/// Map a click to its grid cell, clamping clicks on the viewport edge
+fn bucket_for(x: u32, y: u32, (width, height): (u32, u32)) -> Option<(u32, u32)> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let column = (x as u64 * HEATMAP_GRID_SIZE as u64 / width as u64) as u32;
+    let row = (y as u64 * HEATMAP_GRID_SIZE as u64 / height as u64) as u32;
+    Some((column.min(HEATMAP_GRID_SIZE - 1), row.min(HEATMAP_GRID_SIZE - 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{MouseClickedData, RecordingMetadataData, ViewportResizedData};
+
+    #[test]
+    fn test_clicks_are_normalized_by_viewport() {
+        let mut collector = ClickHeatmapCollector::default();
+        collector.push_frame(&Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: "https://example.com/checkout?step=2#top".to_string(),
+            heartbeat_interval_seconds: 0,
+        }));
+        // Ignored: no viewport known yet
+        collector.push_frame(&Frame::MouseClicked(MouseClickedData { x: 1, y: 1 }));
+        collector.push_frame(&Frame::ViewportResized(ViewportResizedData { width: 1000, height: 500 }));
+        collector.push_frame(&Frame::MouseClicked(MouseClickedData { x: 500, y: 250 }));
+        collector.push_frame(&Frame::ViewportResized(ViewportResizedData { width: 2000, height: 1000 }));
+        collector.push_frame(&Frame::MouseClicked(MouseClickedData { x: 1000, y: 500 }));
+        collector.push_frame(&Frame::MouseClicked(MouseClickedData { x: 5000, y: 5000 }));
+
+        assert_eq!(
+            collector.buckets(),
+            vec![
+                HeatmapBucket { x: 25, y: 25, count: 2 },
+                HeatmapBucket { x: 49, y: 49, count: 1 },
+            ]
+        );
+        assert_eq!(
+            collector.page(None),
+            Some(HeatmapPage {
+                site_origin: "https://example.com".to_string(),
+                path: "/checkout".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Analytics derived from recordings during ingestion
+//!
+//! Collectors are fed every frame as it is written and persist their
+//! aggregates to the MetadataStore once the recording completes.
+
+pub mod funnel;
+pub mod heatmap;
+pub mod scroll;
+pub mod session;
+pub mod summary;
+pub mod text_entry;
+
+use crate::asset_cache::{AssetError, MetadataStore};
+use crate::frame_budget::SummarizedFrames;
+use domcorder_proto::Frame;
+use std::collections::BTreeMap;
+use heatmap::ClickHeatmapCollector;
+use scroll::ScrollDepthCollector;
+use session::SessionMetricsCollector;
+use summary::RecordingSummaryCollector;
+use text_entry::TextEntryCollector;
+
+/// All analytics collectors for a single recording being ingested
+#[derive(Debug)]
+pub struct IngestAnalytics {
+    site_origin: Option<String>,
+    heatmap: ClickHeatmapCollector,
+    scroll: ScrollDepthCollector,
+    session: SessionMetricsCollector,
+    summary: RecordingSummaryCollector,
+    text_entries: TextEntryCollector,
+}
+
+impl IngestAnalytics {
+    /// Create collectors for a recording; `site_origin` overrides the origin
+    /// derived from the recording's initial URL
+    pub fn new(recording_id: &str, site_origin: Option<&str>) -> Self {
+        Self {
+            site_origin: site_origin.map(str::to_string),
+            heatmap: ClickHeatmapCollector::default(),
+            scroll: ScrollDepthCollector::default(),
+            session: SessionMetricsCollector::new(recording_id),
+            summary: RecordingSummaryCollector::default(),
+            text_entries: TextEntryCollector::default(),
+        }
+    }
+
+    /// Feed the next frame written to the recording
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.heatmap.push_frame(frame);
+        self.scroll.push_frame(frame);
+        self.session.push_frame(frame);
+        self.summary.push_frame(frame);
+        self.text_entries.push_frame(frame);
+    }
+
+    /// Report frames dropped and counted by type, which never reach [`push_frame`](Self::push_frame)
+    pub fn set_dropped_frames(&mut self, dropped_frames: BTreeMap<String, u64>) {
+        self.summary.set_dropped_frames(dropped_frames);
+    }
+
+    /// Report frames left out once their type's byte budget was spent
+    pub fn set_summarized_frames(&mut self, summarized_frames: BTreeMap<String, SummarizedFrames>) {
+        self.summary.set_summarized_frames(summarized_frames);
+    }
+
+    /// Persist aggregates for the completed recording
+    pub async fn persist(self, metadata_store: &dyn MetadataStore) -> Result<(), AssetError> {
+        if let Some(page) = self.heatmap.page(self.site_origin.as_deref()) {
+            let buckets = self.heatmap.buckets();
+            if !buckets.is_empty() {
+                metadata_store
+                    .record_click_buckets(&page.site_origin, &page.path, &buckets)
+                    .await?;
+            }
+        }
+
+        let (metrics, events) = self.session.finish(self.site_origin.as_deref());
+        let summary = self.summary.finish(
+            metrics.recording_id.clone(),
+            metrics.site_origin.clone(),
+            metrics.initial_url.clone(),
+            metrics.duration_ms,
+        );
+        metadata_store.store_session_metrics(&metrics, &events).await?;
+        metadata_store.store_recording_summary(&summary).await?;
+        let entries = self.text_entries.finish();
+        metadata_store
+            .store_text_entries(&metrics.recording_id, &entries)
+            .await?;
+        if let Some(depth) = self.scroll.finish(self.site_origin.as_deref()) {
+            metadata_store
+                .store_scroll_depth(&metrics.recording_id, &depth)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a URL into its origin and path (query string and fragment dropped)
+pub fn page_of_url(url: &str) -> Option<(String, String)> {
+    let parsed = url::Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let origin = match parsed.port() {
+        Some(port) => format!("{}://{}:{}", parsed.scheme(), host, port),
+        None => format!("{}://{}", parsed.scheme(), host),
+    };
+    Some((origin, parsed.path().to_string()))
+}
@@ -0,0 +1,301 @@
+//! Scroll depth and above-the-fold exposure
+//!
+//! Follows the window's scroll offset against the viewport and the height of
+//! the document (the larger of the `html` and `body` elements, from
+//! DomNodeResized frames) to find how far down the page a session got, and
+//! how long any of the first screenful (above the fold) stayed in view.
+//! The document is replayed into a [`VDomEngine`] so resized nodes can be
+//! told apart. Sessions are aggregated per page, like click heatmaps.
+
+use super::page_of_url;
+use crate::timeline::ActiveClock;
+use domcorder_proto::{Frame, VDomEngine};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Depths, as a percentage of the page height, reported in [`ScrollReport`]
+pub const SCROLL_DEPTH_THRESHOLDS: [u8; 4] = [25, 50, 75, 100];
+
+/// How far down its page one session scrolled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollDepth {
+    pub site_origin: String,
+    pub path: String,
+    /// Last viewport height seen
+    pub viewport_height: u32,
+    /// Tallest the document got; unknown when no DomNodeResized frame reported it
+    pub page_height: Option<u32>,
+    /// Deepest point of the page brought into view, in pixels from the top
+    pub max_depth_px: u32,
+    /// Recorded time on the page, not counting pauses or idle periods
+    pub duration_ms: u64,
+    /// Part of `duration_ms` that some of the first screenful was in view
+    pub above_fold_ms: u64,
+}
+
+impl ScrollDepth {
+    /// `max_depth_px` as a percentage of the page height, if that is known
+    pub fn max_depth_percent(&self) -> Option<u8> {
+        let page_height = self.page_height.filter(|&height| height > 0)?;
+        Some((self.max_depth_px.min(page_height) as u64 * 100 / page_height as u64) as u8)
+    }
+}
+
+/// Sessions that scrolled at least `percent` of the page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthReached {
+    pub percent: u8,
+    pub sessions: u64,
+}
+
+/// Scroll depth aggregated over the sessions of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrollReport {
+    pub site_origin: String,
+    pub path: String,
+    pub sessions: u64,
+    /// Sessions whose page height is known, the denominator of the depth figures
+    pub measured_sessions: u64,
+    pub average_depth_percent: Option<f64>,
+    /// One entry per [`SCROLL_DEPTH_THRESHOLDS`] value
+    pub reached: Vec<DepthReached>,
+    /// Share (0 to 1) of the time on the page spent with the fold in view
+    pub above_fold_share: Option<f64>,
+}
+
+impl ScrollReport {
+    pub fn new(site_origin: &str, path: &str, depths: &[ScrollDepth]) -> Self {
+        let percents: Vec<u8> = depths.iter().filter_map(ScrollDepth::max_depth_percent).collect();
+        let duration_ms: u64 = depths.iter().map(|depth| depth.duration_ms).sum();
+        let above_fold_ms: u64 = depths.iter().map(|depth| depth.above_fold_ms).sum();
+        Self {
+            site_origin: site_origin.to_string(),
+            path: path.to_string(),
+            sessions: depths.len() as u64,
+            measured_sessions: percents.len() as u64,
+            average_depth_percent: (!percents.is_empty())
+                .then(|| percents.iter().map(|&p| p as f64).sum::<f64>() / percents.len() as f64),
+            reached: SCROLL_DEPTH_THRESHOLDS
+                .iter()
+                .map(|&percent| DepthReached {
+                    percent,
+                    sessions: percents.iter().filter(|&&p| p >= percent).count() as u64,
+                })
+                .collect(),
+            above_fold_share: (duration_ms > 0).then(|| above_fold_ms as f64 / duration_ms as f64),
+        }
+    }
+}
+
+/// Collects the scroll depth of a single recording
+#[derive(Debug, Default)]
+pub struct ScrollDepthCollector {
+    initial_url: Option<String>,
+    engine: VDomEngine,
+    clock: ActiveClock,
+    /// Clock offset time was last counted up to
+    counted_to: u64,
+    scroll_y: i32,
+    html_height: u32,
+    body_height: u32,
+    page_height: Option<u32>,
+    max_depth_px: u32,
+    duration_ms: u64,
+    above_fold_ms: u64,
+}
+
+impl ScrollDepthCollector {
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.clock.push_frame(frame);
+        // The time since the last frame was spent in the state before this one
+        let offset = self.clock.offset();
+        if let Some((_, viewport_height)) = self.engine.viewport() {
+            let elapsed = offset.saturating_sub(self.counted_to);
+            self.duration_ms += elapsed;
+            if self.scroll_y < viewport_height as i32 {
+                self.above_fold_ms += elapsed;
+            }
+        }
+        self.counted_to = offset;
+
+        match frame {
+            Frame::RecordingMetadata(metadata) => {
+                self.initial_url.get_or_insert_with(|| metadata.initial_url.clone());
+            }
+            Frame::ScrollOffsetChanged(_) | Frame::ScrollOffsetChangedV2(_) => {
+                if let Frame::ScrollOffsetChangedV2(scrolled) = frame.clone().upgrade() {
+                    self.scroll_y = scrolled.scroll_y_offset;
+                }
+            }
+            Frame::DomNodeResized(resized) => match self.engine.tag(resized.node_id) {
+                Some(tag) if tag.eq_ignore_ascii_case("html") => self.html_height = resized.height,
+                Some(tag) if tag.eq_ignore_ascii_case("body") => self.body_height = resized.height,
+                _ => {}
+            },
+            _ => {}
+        }
+        if let Err(e) = self.engine.apply(frame) {
+            debug!("Scroll depth skipping frame that could not be applied: {}", e);
+        }
+        if matches!(frame, Frame::Keyframe(_) | Frame::KeyframeBegin(_)) {
+            // Node ids start over with the new document
+            (self.html_height, self.body_height) = (0, 0);
+        }
+
+        let height = self.html_height.max(self.body_height);
+        if height > 0 {
+            self.page_height = Some(self.page_height.unwrap_or(0).max(height));
+        }
+        if let Some((_, viewport_height)) = self.engine.viewport() {
+            let bottom = (self.scroll_y.max(0) as u32).saturating_add(viewport_height);
+            self.max_depth_px = self.max_depth_px.max(bottom);
+        }
+    }
+
+    /// The recording's scroll depth, if it had a page and a viewport;
+    /// `site_origin` overrides the origin of the initial URL
+    pub fn finish(self, site_origin: Option<&str>) -> Option<ScrollDepth> {
+        let (origin, path) = page_of_url(self.initial_url.as_deref()?)?;
+        let (_, viewport_height) = self.engine.viewport()?;
+        Some(ScrollDepth {
+            site_origin: site_origin.map(str::to_string).unwrap_or(origin),
+            path,
+            viewport_height,
+            page_height: self.page_height,
+            max_depth_px: self.max_depth_px,
+            duration_ms: self.duration_ms,
+            above_fold_ms: self.above_fold_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{
+        DomNodeResizedData, KeyframeData, RecordingMetadataData, ScrollOffsetChangedData, ScrollOffsetChangedV2Data,
+        TimestampData, VDocument, VElement, VNode, ViewportResizedData,
+    };
+
+    fn element(id: u32, tag: &str, children: Vec<VNode>) -> VNode {
+        VNode::Element(VElement {
+            id,
+            tag: tag.to_string(),
+            ns: None,
+            attrs: Vec::new(),
+            children,
+        })
+    }
+
+    fn keyframe() -> Frame {
+        Frame::Keyframe(KeyframeData {
+            document: VDocument {
+                id: 0,
+                adopted_style_sheets: Vec::new(),
+                children: vec![element(
+                    1,
+                    "html",
+                    vec![element(2, "body", vec![element(3, "div", vec![])])],
+                )],
+            },
+            viewport_width: 800,
+            viewport_height: 600,
+        })
+    }
+
+    fn at(timestamp: u64) -> Frame {
+        Frame::Timestamp(TimestampData { timestamp })
+    }
+
+    fn resized(node_id: u32, height: u32) -> Frame {
+        Frame::DomNodeResized(DomNodeResizedData {
+            node_id,
+            width: 800,
+            height,
+        })
+    }
+
+    fn scrolled(y: i32) -> Frame {
+        Frame::ScrollOffsetChangedV2(ScrollOffsetChangedV2Data {
+            scroll_x_offset: 0,
+            scroll_y_offset: y,
+        })
+    }
+
+    #[test]
+    fn test_scroll_depth_and_fold_time() {
+        let mut collector = ScrollDepthCollector::default();
+        for frame in [
+            Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/pricing?plan=pro".to_string(),
+                heartbeat_interval_seconds: 0,
+            }),
+            at(1_000),
+            keyframe(),
+            resized(2, 2_400),
+            // Not the document
+            resized(3, 10_000),
+            at(3_000),
+            scrolled(1_200),
+            at(4_000),
+            Frame::ScrollOffsetChanged(ScrollOffsetChangedData {
+                scroll_x_offset: 0,
+                scroll_y_offset: 300,
+            }),
+            at(5_000),
+            Frame::ViewportResized(ViewportResizedData {
+                width: 800,
+                height: 200,
+            }),
+            scrolled(500),
+            at(9_000),
+        ] {
+            collector.push_frame(&frame);
+        }
+
+        let depth = collector.finish(None).unwrap();
+        assert_eq!(
+            depth,
+            ScrollDepth {
+                site_origin: "https://example.com".to_string(),
+                path: "/pricing".to_string(),
+                viewport_height: 200,
+                page_height: Some(2_400),
+                max_depth_px: 1_800,
+                duration_ms: 8_000,
+                above_fold_ms: 3_000,
+            }
+        );
+        assert_eq!(depth.max_depth_percent(), Some(75));
+    }
+
+    #[test]
+    fn test_report_aggregates_sessions() {
+        let depth = |page_height: Option<u32>, max_depth_px: u32, above_fold_ms: u64| ScrollDepth {
+            site_origin: "https://example.com".to_string(),
+            path: "/".to_string(),
+            viewport_height: 500,
+            page_height,
+            max_depth_px,
+            duration_ms: 1_000,
+            above_fold_ms,
+        };
+        let report = ScrollReport::new(
+            "https://example.com",
+            "/",
+            &[
+                depth(Some(1_000), 500, 1_000),
+                depth(Some(1_000), 1_200, 500),
+                depth(None, 900, 0),
+            ],
+        );
+        assert_eq!(report.sessions, 3);
+        assert_eq!(report.measured_sessions, 2);
+        assert_eq!(report.average_depth_percent, Some(75.0));
+        assert_eq!(
+            report.reached.iter().map(|r| r.sessions).collect::<Vec<_>>(),
+            vec![2, 2, 1, 1]
+        );
+        assert_eq!(report.above_fold_share, Some(0.5));
+    }
+}
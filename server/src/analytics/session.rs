@@ -0,0 +1,275 @@
+//! Per-session metrics
+//!
+//! Summarizes a recording (duration, pages, clicks, rage-clicks, errors) and
+//! keeps the ordered navigation/annotation events used by funnel queries.
+//! Time spent paused (RecordingPaused to RecordingResumed) or in idle periods
+//! counts towards neither the duration nor event offsets.
+
+use super::page_of_url;
+use crate::timeline::ActiveClock;
+use domcorder_proto::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Clicks within this window and radius count towards a rage-click
+pub const RAGE_CLICK_WINDOW_MS: u64 = 1000;
+pub const RAGE_CLICK_RADIUS_PX: u32 = 30;
+/// Number of clicks in the window that make a rage-click
+pub const RAGE_CLICK_THRESHOLD: usize = 3;
+
+/// Summary metrics for one recorded session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMetrics {
+    pub recording_id: String,
+    pub site_origin: Option<String>,
+    pub initial_url: Option<String>,
+    /// First Timestamp frame value (Unix ms)
+    pub started_at: Option<u64>,
+    /// Recorded time, not counting pauses or idle periods
+    pub duration_ms: u64,
+    /// Number of keyframes (page loads)
+    pub page_count: u32,
+    pub click_count: u32,
+    /// Bursts of rapid clicks in one spot; each burst counts once
+    pub rage_click_count: u32,
+    pub error_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEventKind {
+    /// A page load; the name is the page path
+    Navigation,
+    /// An Annotation frame; the name is the annotation name
+    Annotation,
+}
+
+impl SessionEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionEventKind::Navigation => "navigation",
+            SessionEventKind::Annotation => "annotation",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "navigation" => Some(SessionEventKind::Navigation),
+            "annotation" => Some(SessionEventKind::Annotation),
+            _ => None,
+        }
+    }
+}
+
+/// A navigation or annotation event within a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub kind: SessionEventKind,
+    pub name: String,
+    /// Milliseconds since the session started
+    pub offset_ms: u64,
+}
+
+/// Collects session metrics for a single recording
+#[derive(Debug, Default)]
+pub struct SessionMetricsCollector {
+    metrics: SessionMetrics,
+    events: Vec<SessionEvent>,
+    clock: ActiveClock,
+    pending_path: Option<String>,
+    /// Recent clicks (time, x, y) used for rage-click detection
+    recent_clicks: VecDeque<(u64, u32, u32)>,
+    in_rage_burst: bool,
+}
+
+impl SessionMetricsCollector {
+    pub fn new(recording_id: &str) -> Self {
+        Self {
+            metrics: SessionMetrics {
+                recording_id: recording_id.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        self.clock.offset()
+    }
+
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.clock.push_frame(frame);
+        match frame {
+            Frame::Timestamp(_) => {
+                self.metrics.started_at = self.clock.start();
+                self.metrics.duration_ms = self.clock.offset();
+            }
+            Frame::RecordingMetadata(metadata) if self.metrics.initial_url.is_none() => {
+                self.metrics.initial_url = Some(metadata.initial_url.clone());
+                if let Some((origin, path)) = page_of_url(&metadata.initial_url) {
+                    self.metrics.site_origin = Some(origin);
+                    self.pending_path = Some(path);
+                }
+            }
+            Frame::Keyframe(_) | Frame::KeyframeBegin(_) => {
+                self.metrics.page_count += 1;
+                if let Some(path) = self.pending_path.take() {
+                    self.events.push(SessionEvent {
+                        kind: SessionEventKind::Navigation,
+                        name: path,
+                        offset_ms: self.offset(),
+                    });
+                }
+            }
+            Frame::MouseClicked(click) => {
+                self.metrics.click_count += 1;
+                self.track_click(click.x, click.y);
+            }
+            Frame::MouseClickedV2(click) => {
+                self.metrics.click_count += 1;
+                self.track_click(click.x as u32, click.y as u32);
+            }
+            Frame::PageError(_) => {
+                self.metrics.error_count += 1;
+            }
+            Frame::Annotation(annotation) => {
+                self.events.push(SessionEvent {
+                    kind: SessionEventKind::Annotation,
+                    name: annotation.name.clone(),
+                    offset_ms: self.offset(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn track_click(&mut self, x: u32, y: u32) {
+        let now = self.clock.now().unwrap_or(0);
+        let near = |&(_, cx, cy): &(u64, u32, u32)| {
+            cx.abs_diff(x) <= RAGE_CLICK_RADIUS_PX && cy.abs_diff(y) <= RAGE_CLICK_RADIUS_PX
+        };
+
+        // A click elsewhere (or after a pause) ends the current burst
+        let continues = self
+            .recent_clicks
+            .back()
+            .is_some_and(|last| now.saturating_sub(last.0) <= RAGE_CLICK_WINDOW_MS && near(last));
+        if !continues {
+            self.recent_clicks.clear();
+            self.in_rage_burst = false;
+        }
+
+        self.recent_clicks.push_back((now, x, y));
+        while self
+            .recent_clicks
+            .front()
+            .is_some_and(|first| now.saturating_sub(first.0) > RAGE_CLICK_WINDOW_MS)
+        {
+            self.recent_clicks.pop_front();
+        }
+
+        if !self.in_rage_burst && self.recent_clicks.len() >= RAGE_CLICK_THRESHOLD {
+            self.in_rage_burst = true;
+            self.metrics.rage_click_count += 1;
+        }
+    }
+
+    /// Finish collection; `site_origin` overrides the origin derived from the initial URL
+    pub fn finish(mut self, site_origin: Option<&str>) -> (SessionMetrics, Vec<SessionEvent>) {
+        if let Some(origin) = site_origin {
+            self.metrics.site_origin = Some(origin.to_string());
+        }
+        (self.metrics, self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{
+        AnnotationData, MouseClickedData, PageErrorData, RecordingMetadataData, RecordingPausedData, TimestampData,
+    };
+
+    fn click(x: u32, y: u32) -> Frame {
+        Frame::MouseClicked(MouseClickedData { x, y })
+    }
+
+    fn at(timestamp: u64) -> Frame {
+        Frame::Timestamp(TimestampData { timestamp })
+    }
+
+    #[test]
+    fn test_session_metrics() {
+        let mut collector = SessionMetricsCollector::new("rec-1");
+        let frames = vec![
+            Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/cart?x=1".to_string(),
+                heartbeat_interval_seconds: 0,
+            }),
+            at(10_000),
+            // Rage burst: four clicks in one spot, counted once
+            click(100, 100),
+            at(10_200),
+            click(105, 98),
+            click(101, 102),
+            click(100, 100),
+            // Same spot, but after a pause: not a rage click
+            at(12_000),
+            click(100, 100),
+            Frame::Annotation(AnnotationData {
+                name: "checkout_started".to_string(),
+                data: None,
+            }),
+            Frame::PageError(PageErrorData {
+                message: "boom".to_string(),
+                source_url: None,
+                line: None,
+                column: None,
+                stack: None,
+            }),
+            at(15_000),
+        ];
+        for frame in &frames {
+            collector.push_frame(frame);
+        }
+        let (metrics, events) = collector.finish(None);
+
+        assert_eq!(metrics.site_origin.as_deref(), Some("https://example.com"));
+        assert_eq!(metrics.started_at, Some(10_000));
+        assert_eq!(metrics.duration_ms, 5_000);
+        assert_eq!(metrics.click_count, 5);
+        assert_eq!(metrics.rage_click_count, 1);
+        assert_eq!(metrics.error_count, 1);
+        assert_eq!(
+            events,
+            vec![SessionEvent {
+                kind: SessionEventKind::Annotation,
+                name: "checkout_started".to_string(),
+                offset_ms: 2_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_paused_time_is_not_counted() {
+        let mut collector = SessionMetricsCollector::new("rec-1");
+        let frames = vec![
+            at(10_000),
+            Frame::RecordingPaused(RecordingPausedData { reason: None }),
+            at(70_000),
+            Frame::RecordingResumed,
+            Frame::Annotation(AnnotationData {
+                name: "after_pause".to_string(),
+                data: None,
+            }),
+            at(73_000),
+        ];
+        for frame in &frames {
+            collector.push_frame(frame);
+        }
+        let (metrics, events) = collector.finish(None);
+
+        assert_eq!(metrics.duration_ms, 3_000);
+        assert_eq!(events[0].offset_ms, 0);
+    }
+}
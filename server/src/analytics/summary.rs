@@ -0,0 +1,215 @@
+//! Per-recording summary shown in listings
+//!
+//! The title is the `<title>` of the first page captured. Tags come from
+//! Annotation frames named [`TAG_ANNOTATION`] whose data is the tag, so a
+//! recorder can label a session (e.g. "checkout", "beta-user") as it records.
+//! The identity comes from SessionIdentity frames, so support can find every
+//! session of a customer. Frames dropped and counted at ingestion (see
+//! [`crate::frame_filter`]) and summarized once over budget (see
+//! [`crate::frame_budget`]) are reported by type.
+
+use crate::frame_budget::SummarizedFrames;
+use domcorder_proto::{Frame, VNode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Annotation name that tags the recording with the annotation's data
+pub const TAG_ANNOTATION: &str = "domcorder:tag";
+
+/// Listing details for one recording, stored when its ingestion completes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    pub recording_id: String,
+    pub site_origin: Option<String>,
+    pub initial_url: Option<String>,
+    pub duration_ms: u64,
+    pub frame_count: u64,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// From the last SessionIdentity frame
+    pub anonymous_id: Option<String>,
+    /// The last user id and email hash the recorder sent, kept through sign-out
+    pub user_id: Option<String>,
+    pub email_hash: Option<String>,
+    /// Set separately when the recorder connects (see
+    /// [`StorageState::store_client_ip`](crate::StorageState::store_client_ip));
+    /// not written by `store_recording_summary`
+    pub client_ip: Option<String>,
+    /// Frames dropped by type and counted, rather than stored
+    #[serde(default)]
+    pub dropped_frames: BTreeMap<String, u64>,
+    /// Frames left out by type once the type's byte budget was spent
+    #[serde(default)]
+    pub summarized_frames: BTreeMap<String, SummarizedFrames>,
+}
+
+/// Collects the frame count, title, tags and identity of a recording
+#[derive(Debug, Default)]
+pub struct RecordingSummaryCollector {
+    frame_count: u64,
+    title: Option<String>,
+    tags: Vec<String>,
+    anonymous_id: Option<String>,
+    user_id: Option<String>,
+    email_hash: Option<String>,
+    dropped_frames: BTreeMap<String, u64>,
+    summarized_frames: BTreeMap<String, SummarizedFrames>,
+}
+
+impl RecordingSummaryCollector {
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.frame_count += 1;
+        match frame {
+            Frame::Keyframe(keyframe) if self.title.is_none() => {
+                self.title = find_title(&keyframe.document.children);
+            }
+            // A <title> is small enough to arrive whole in one chunk
+            Frame::KeyframeChunk(chunk) if self.title.is_none() => {
+                self.title = find_title(&chunk.nodes);
+            }
+            Frame::Annotation(annotation) if annotation.name == TAG_ANNOTATION => {
+                let tag = annotation.data.as_deref().unwrap_or_default().trim();
+                if !tag.is_empty() && !self.tags.iter().any(|t| t == tag) {
+                    self.tags.push(tag.to_string());
+                }
+            }
+            Frame::SessionIdentity(identity) => {
+                self.anonymous_id = Some(identity.anonymous_id.clone());
+                if identity.user_id.is_some() {
+                    self.user_id = identity.user_id.clone();
+                }
+                if identity.email_hash.is_some() {
+                    self.email_hash = identity.email_hash.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the frames dropped and counted before they reached the collector
+    pub fn set_dropped_frames(&mut self, dropped_frames: BTreeMap<String, u64>) {
+        self.dropped_frames = dropped_frames;
+    }
+
+    /// Record the frames left out once their type went over budget
+    pub fn set_summarized_frames(&mut self, summarized_frames: BTreeMap<String, SummarizedFrames>) {
+        self.summarized_frames = summarized_frames;
+    }
+
+    /// The collected details, with the fields the session metrics already know
+    pub fn finish(
+        self,
+        recording_id: String,
+        site_origin: Option<String>,
+        initial_url: Option<String>,
+        duration_ms: u64,
+    ) -> RecordingSummary {
+        RecordingSummary {
+            recording_id,
+            site_origin,
+            initial_url,
+            duration_ms,
+            frame_count: self.frame_count,
+            title: self.title,
+            tags: self.tags,
+            anonymous_id: self.anonymous_id,
+            user_id: self.user_id,
+            email_hash: self.email_hash,
+            client_ip: None,
+            dropped_frames: self.dropped_frames,
+            summarized_frames: self.summarized_frames,
+        }
+    }
+}
+
+/// Text of the first HTML `<title>` element, whitespace collapsed
+fn find_title(nodes: &[VNode]) -> Option<String> {
+    nodes.iter().find_map(|node| {
+        let VNode::Element(element) = node else { return None };
+        // SVG has its own <title> elements
+        if element.tag.eq_ignore_ascii_case("title") && element.ns.is_none() {
+            let text: String = element
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    VNode::Text(text) => Some(text.content.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let title = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            return (!title.is_empty()).then_some(title);
+        }
+        find_title(&element.children)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{AnnotationData, KeyframeData, SessionIdentityData, VDocument, VElement, VTextNode};
+
+    fn element(id: u32, tag: &str, children: Vec<VNode>) -> VNode {
+        VNode::Element(VElement {
+            id,
+            tag: tag.to_string(),
+            ns: None,
+            attrs: vec![],
+            children,
+        })
+    }
+
+    fn tag(value: &str) -> Frame {
+        Frame::Annotation(AnnotationData {
+            name: TAG_ANNOTATION.to_string(),
+            data: Some(value.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_collects_title_tags_and_frame_count() {
+        let title = VNode::Text(VTextNode {
+            id: 4,
+            content: "\n  Checkout  | Shop ".to_string(),
+        });
+        let document = VDocument {
+            id: 0,
+            adopted_style_sheets: vec![],
+            children: vec![element(1, "html", vec![element(2, "head", vec![element(3, "TITLE", vec![title])])])],
+        };
+        let keyframe = Frame::Keyframe(KeyframeData {
+            document,
+            viewport_width: 800,
+            viewport_height: 600,
+        });
+
+        let mut collector = RecordingSummaryCollector::default();
+        for frame in [keyframe, tag("checkout"), tag(" checkout "), tag("beta"), tag("")] {
+            collector.push_frame(&frame);
+        }
+        let summary = collector.finish("rec".to_string(), None, None, 1500);
+
+        assert_eq!(summary.title.as_deref(), Some("Checkout | Shop"));
+        assert_eq!(summary.tags, ["checkout", "beta"]);
+        assert_eq!(summary.frame_count, 5);
+        assert_eq!(summary.duration_ms, 1500);
+    }
+
+    #[test]
+    fn test_keeps_user_through_sign_out() {
+        let identity = |anonymous_id: &str, user_id: Option<&str>| {
+            Frame::SessionIdentity(SessionIdentityData {
+                anonymous_id: anonymous_id.to_string(),
+                user_id: user_id.map(str::to_string),
+                email_hash: None,
+            })
+        };
+        let mut collector = RecordingSummaryCollector::default();
+        for frame in [identity("anon", None), identity("anon", Some("u42")), identity("anon2", None)] {
+            collector.push_frame(&frame);
+        }
+        let summary = collector.finish("rec".to_string(), None, None, 0);
+
+        assert_eq!(summary.anonymous_id.as_deref(), Some("anon2"));
+        assert_eq!(summary.user_id.as_deref(), Some("u42"));
+    }
+}
@@ -0,0 +1,531 @@
+//! Text typed into form fields
+//!
+//! Folds the keystrokes and value changes of each visit to a text field
+//! (from ElementFocused until it is blurred, another element is focused or
+//! the page changes) into one [`TextEntered`] record: the field's final
+//! value, how many keys were pressed and how long the typing took. Visits
+//! that don't change the value, like tabbing through a form, are left out.
+//!
+//! Text fields are text-like inputs, textareas and contenteditable elements,
+//! found in keyframes and added nodes. Only they are tracked, not the whole
+//! document. Inputs and textareas take their value from the `value`
+//! property; contenteditable elements from the text of the nodes inside
+//! them. Password values are never kept.
+
+use crate::timeline::ActiveClock;
+use domcorder_proto::vdom_engine::{apply_text_operations, node_id};
+use domcorder_proto::{Frame, TextOperationData, VElement, VNode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Input types whose value is typed text, as the recorder diffs them
+const TEXT_INPUT_TYPES: [&str; 6] = ["text", "email", "url", "password", "search", "tel"];
+
+/// One visit to a text field that changed its value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEntered {
+    /// The field's node id in the page
+    pub node_id: u32,
+    /// `input`, `textarea`, or the tag of a contenteditable element
+    pub tag: String,
+    /// The field's `name` attribute, or else its `id`
+    pub field: Option<String>,
+    /// The value the field was left with; None for password fields, and for
+    /// text the recording doesn't carry in full
+    pub value: Option<String>,
+    pub keystrokes: u32,
+    /// Milliseconds since the session started, of the first keystroke or
+    /// change
+    pub offset_ms: u64,
+    /// From the first keystroke or change to the last
+    pub typing_ms: u64,
+}
+
+#[derive(Debug)]
+struct Field {
+    tag: String,
+    name: Option<String>,
+    masked: bool,
+    editable: bool,
+}
+
+/// A node inside a contenteditable field
+#[derive(Debug)]
+struct EditableNode {
+    field: u32,
+    parent: Option<u32>,
+    children: Vec<u32>,
+    content: Content,
+}
+
+#[derive(Debug)]
+enum Content {
+    Element,
+    Text(String),
+    /// A text node whose text operations didn't apply
+    Unknown,
+}
+
+#[derive(Debug)]
+struct Visit {
+    node_id: u32,
+    keystrokes: u32,
+    first_input: Option<u64>,
+    last_input: u64,
+    changed: bool,
+}
+
+/// Collects the text entered into a single recording's fields
+#[derive(Debug, Default)]
+pub struct TextEntryCollector {
+    clock: ActiveClock,
+    fields: HashMap<u32, Field>,
+    /// The `value` of inputs and textareas; None once it is no longer known
+    values: HashMap<u32, Option<String>>,
+    /// The nodes inside contenteditable fields, fields included
+    editable: HashMap<u32, EditableNode>,
+    visit: Option<Visit>,
+    entries: Vec<TextEntered>,
+}
+
+impl TextEntryCollector {
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.clock.push_frame(frame);
+        match frame {
+            Frame::Keyframe(keyframe) => {
+                self.reset();
+                for node in &keyframe.document.children {
+                    self.add_node(node, None, None);
+                }
+            }
+            Frame::KeyframeBegin(_) => self.reset(),
+            Frame::KeyframeChunk(chunk) => {
+                for node in &chunk.nodes {
+                    self.add_node(node, Some(chunk.parent_node_id), None);
+                }
+            }
+            Frame::DomNodeAdded(added) => {
+                self.add_node(&added.node, Some(added.parent_node_id), Some(added.index as usize));
+                if let Some(field) = self.editable.get(&added.parent_node_id).map(|node| node.field) {
+                    self.input(field, true);
+                }
+            }
+            Frame::DomNodeRemoved(removed) => {
+                let field = self.editable.get(&removed.node_id).map(|node| node.field);
+                self.remove_node(removed.node_id);
+                if let Some(field) = field {
+                    self.input(field, true);
+                }
+            }
+            Frame::DomTextChanged(changed) => {
+                let Some(node) = self.editable.get_mut(&changed.node_id) else {
+                    return;
+                };
+                if let Content::Text(text) = &node.content {
+                    node.content = apply(text, &changed.operations, changed.node_id);
+                }
+                let field = node.field;
+                self.input(field, true);
+            }
+            Frame::DomNodePropertyChanged(changed) if changed.property_name == "value" => {
+                if let Some(value) = self.values.get_mut(&changed.node_id) {
+                    *value = Some(changed.property_value.clone());
+                    self.input(changed.node_id, true);
+                }
+            }
+            Frame::DomNodePropertyTextChanged(changed) if changed.property_name == "value" => {
+                if let Some(value) = self.values.get_mut(&changed.node_id) {
+                    *value = value
+                        .as_deref()
+                        .and_then(|value| apply_text_operations(value, &changed.operations, changed.node_id).ok());
+                    self.input(changed.node_id, true);
+                }
+            }
+            Frame::KeyPressed(_) => {
+                if let Some(visit) = &mut self.visit {
+                    visit.keystrokes += 1;
+                    let node_id = visit.node_id;
+                    self.input(node_id, false);
+                }
+            }
+            Frame::ElementFocused(focused) => {
+                self.end_visit();
+                if self.fields.contains_key(&focused.node_id) {
+                    self.visit = Some(Visit {
+                        node_id: focused.node_id,
+                        keystrokes: 0,
+                        first_input: None,
+                        last_input: 0,
+                        changed: false,
+                    });
+                }
+            }
+            Frame::ElementBlurred(blurred) if self.is_visiting(blurred.node_id) => self.end_visit(),
+            _ => {}
+        }
+    }
+
+    /// Finish collection, ending the visit in progress
+    pub fn finish(mut self) -> Vec<TextEntered> {
+        self.end_visit();
+        self.entries
+    }
+
+    /// A new page: node ids start over
+    fn reset(&mut self) {
+        self.end_visit();
+        self.fields.clear();
+        self.values.clear();
+        self.editable.clear();
+    }
+
+    fn is_visiting(&self, node_id: u32) -> bool {
+        self.visit.as_ref().is_some_and(|visit| visit.node_id == node_id)
+    }
+
+    /// Note a keystroke into, or a change to, field `node_id`
+    fn input(&mut self, node_id: u32, changed: bool) {
+        let offset = self.clock.offset();
+        if let Some(visit) = self.visit.as_mut().filter(|visit| visit.node_id == node_id) {
+            visit.first_input.get_or_insert(offset);
+            visit.last_input = offset;
+            visit.changed |= changed;
+        }
+    }
+
+    fn end_visit(&mut self) {
+        let Some(visit) = self.visit.take() else {
+            return;
+        };
+        let (Some(first_input), true) = (visit.first_input, visit.changed) else {
+            return;
+        };
+        let Some(field) = self.fields.get(&visit.node_id) else {
+            return;
+        };
+        let value = if field.masked {
+            None
+        } else if field.editable {
+            self.text_content(visit.node_id)
+        } else {
+            self.values.get(&visit.node_id).cloned().flatten()
+        };
+        self.entries.push(TextEntered {
+            node_id: visit.node_id,
+            tag: field.tag.clone(),
+            field: field.name.clone(),
+            value,
+            keystrokes: visit.keystrokes,
+            offset_ms: first_input,
+            typing_ms: visit.last_input.saturating_sub(first_input),
+        });
+    }
+
+    /// Track the fields in `node`, added to `parent` at `index` (or last)
+    fn add_node(&mut self, node: &VNode, parent: Option<u32>, index: Option<usize>) {
+        let id = node_id(node);
+        if let Some(field) = parent
+            .and_then(|parent| self.editable.get(&parent))
+            .map(|parent| parent.field)
+        {
+            let content = match node {
+                VNode::Text(text) => Content::Text(text.content.clone()),
+                _ => Content::Element,
+            };
+            self.insert_editable(id, field, parent, index, content);
+        } else if let VNode::Element(element) = node
+            && let Some(field) = field_of(element)
+        {
+            if field.editable {
+                self.insert_editable(id, id, None, None, Content::Element);
+            } else if field.tag == "textarea" {
+                self.values.insert(id, Some(text_of(&element.children)));
+            } else {
+                self.values
+                    .insert(id, Some(attribute(element, "value").unwrap_or_default().to_string()));
+            }
+            self.fields.insert(id, field);
+        }
+
+        if let VNode::Element(element) = node {
+            for child in &element.children {
+                self.add_node(child, Some(id), None);
+            }
+        }
+    }
+
+    fn insert_editable(&mut self, id: u32, field: u32, parent: Option<u32>, index: Option<usize>, content: Content) {
+        if let Some(siblings) = parent
+            .and_then(|parent| self.editable.get_mut(&parent))
+            .map(|parent| &mut parent.children)
+        {
+            let index = index.unwrap_or(siblings.len()).min(siblings.len());
+            siblings.insert(index, id);
+        }
+        self.editable.insert(
+            id,
+            EditableNode {
+                field,
+                parent,
+                children: Vec::new(),
+                content,
+            },
+        );
+    }
+
+    fn remove_node(&mut self, id: u32) {
+        self.fields.remove(&id);
+        self.values.remove(&id);
+        let Some(node) = self.editable.remove(&id) else {
+            return;
+        };
+        if let Some(parent) = node.parent.and_then(|parent| self.editable.get_mut(&parent)) {
+            parent.children.retain(|&child| child != id);
+        }
+        for child in node.children {
+            self.remove_node(child);
+        }
+    }
+
+    /// The text of the nodes inside a contenteditable field, like
+    /// `textContent`; None if any of it isn't known
+    fn text_content(&self, id: u32) -> Option<String> {
+        let node = self.editable.get(&id)?;
+        match &node.content {
+            Content::Text(text) => Some(text.clone()),
+            Content::Unknown => None,
+            Content::Element => node.children.iter().map(|&child| self.text_content(child)).collect(),
+        }
+    }
+}
+
+fn apply(text: &str, operations: &[TextOperationData], node_id: u32) -> Content {
+    match apply_text_operations(text, operations, node_id) {
+        Ok(text) => Content::Text(text),
+        Err(_) => Content::Unknown,
+    }
+}
+
+/// The text field `element` is, if it is one
+fn field_of(element: &VElement) -> Option<Field> {
+    let tag = element.tag.to_ascii_lowercase();
+    let input_type = attribute(element, "type").unwrap_or("text").to_ascii_lowercase();
+    let editable = attribute(element, "contenteditable").is_some_and(|value| !value.eq_ignore_ascii_case("false"));
+    let is_field = match tag.as_str() {
+        "input" => TEXT_INPUT_TYPES.contains(&input_type.as_str()),
+        "textarea" => true,
+        _ => editable,
+    };
+    if !is_field {
+        return None;
+    }
+    Some(Field {
+        masked: tag == "input" && input_type == "password",
+        editable: editable && tag != "input" && tag != "textarea",
+        name: attribute(element, "name")
+            .or_else(|| attribute(element, "id"))
+            .map(str::to_string),
+        tag,
+    })
+}
+
+fn attribute<'a>(element: &'a VElement, name: &str) -> Option<&'a str> {
+    element
+        .attrs
+        .iter()
+        .find(|(attr, _)| attr.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// The text of `nodes` and their descendants
+fn text_of(nodes: &[VNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            VNode::Text(text) => text.content.clone(),
+            VNode::Element(element) => text_of(&element.children),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{
+        DomNodeAddedData, DomNodePropertyTextChangedData, DomTextChangedData, ElementBlurredData, ElementFocusedData,
+        KeyPressedData, KeyframeData, TextInsertOperationData, TimestampData, VDocument, VTextNode,
+    };
+
+    fn element(id: u32, tag: &str, attrs: &[(&str, &str)], children: Vec<VNode>) -> VNode {
+        VNode::Element(VElement {
+            id,
+            tag: tag.to_string(),
+            ns: None,
+            attrs: attrs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            children,
+        })
+    }
+
+    fn text(id: u32, content: &str) -> VNode {
+        VNode::Text(VTextNode {
+            id,
+            content: content.to_string(),
+        })
+    }
+
+    fn insert(index: u32, text: &str) -> Vec<TextOperationData> {
+        vec![TextOperationData::Insert(TextInsertOperationData {
+            index,
+            text: text.to_string(),
+        })]
+    }
+
+    fn at(timestamp: u64) -> Frame {
+        Frame::Timestamp(TimestampData { timestamp })
+    }
+
+    fn key() -> Frame {
+        Frame::KeyPressed(KeyPressedData {
+            code: "KeyA".to_string(),
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+            shift_key: false,
+        })
+    }
+
+    fn typed(node_id: u32, index: u32, text: &str) -> Frame {
+        Frame::DomNodePropertyTextChanged(DomNodePropertyTextChangedData {
+            node_id,
+            property_name: "value".to_string(),
+            operations: insert(index, text),
+        })
+    }
+
+    fn focus(node_id: u32) -> Frame {
+        Frame::ElementFocused(ElementFocusedData { node_id })
+    }
+
+    fn blur(node_id: u32) -> Frame {
+        Frame::ElementBlurred(ElementBlurredData { node_id })
+    }
+
+    fn page() -> Frame {
+        let form = element(
+            2,
+            "form",
+            &[],
+            vec![
+                element(3, "input", &[("name", "email"), ("type", "email")], vec![]),
+                element(4, "input", &[("name", "password"), ("type", "password")], vec![]),
+                element(5, "textarea", &[("id", "notes")], vec![text(6, "Hi")]),
+                element(7, "div", &[("contenteditable", "true")], vec![text(8, "Dear")]),
+                element(9, "input", &[("type", "checkbox")], vec![]),
+            ],
+        );
+        Frame::Keyframe(KeyframeData {
+            document: VDocument {
+                id: 0,
+                adopted_style_sheets: vec![],
+                children: vec![element(1, "body", &[], vec![form])],
+            },
+            viewport_width: 800,
+            viewport_height: 600,
+        })
+    }
+
+    fn collect(frames: &[Frame]) -> Vec<TextEntered> {
+        let mut collector = TextEntryCollector::default();
+        for frame in frames {
+            collector.push_frame(frame);
+        }
+        collector.finish()
+    }
+
+    #[test]
+    fn test_typing_is_folded_into_one_entry_per_visit() {
+        let entries = collect(&[
+            at(1_000),
+            page(),
+            focus(3),
+            at(2_000),
+            key(),
+            typed(3, 0, "a"),
+            at(2_500),
+            key(),
+            key(),
+            typed(3, 1, "@b"),
+            blur(3),
+            // Tabbing through without typing isn't an entry
+            focus(9),
+            focus(4),
+            key(),
+            typed(4, 0, "hunter2"),
+            focus(5),
+            at(3_000),
+            typed(5, 2, " there"),
+        ]);
+
+        assert_eq!(
+            entries,
+            vec![
+                TextEntered {
+                    node_id: 3,
+                    tag: "input".to_string(),
+                    field: Some("email".to_string()),
+                    value: Some("a@b".to_string()),
+                    keystrokes: 3,
+                    offset_ms: 1_000,
+                    typing_ms: 500,
+                },
+                TextEntered {
+                    node_id: 4,
+                    tag: "input".to_string(),
+                    field: Some("password".to_string()),
+                    value: None,
+                    keystrokes: 1,
+                    offset_ms: 1_500,
+                    typing_ms: 0,
+                },
+                TextEntered {
+                    node_id: 5,
+                    tag: "textarea".to_string(),
+                    field: Some("notes".to_string()),
+                    value: Some("Hi there".to_string()),
+                    keystrokes: 0,
+                    offset_ms: 2_000,
+                    typing_ms: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contenteditable_text_is_its_text_nodes() {
+        let entries = collect(&[
+            at(1_000),
+            page(),
+            focus(7),
+            key(),
+            Frame::DomTextChanged(DomTextChangedData {
+                node_id: 8,
+                operations: insert(4, " Sam,"),
+            }),
+            Frame::DomNodeAdded(DomNodeAddedData {
+                parent_node_id: 7,
+                index: 1,
+                node: element(10, "div", &[], vec![text(11, "Thanks")]),
+            }),
+            blur(7),
+        ]);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].tag, "div");
+        assert_eq!(entries[0].value.as_deref(), Some("Dear Sam,Thanks"));
+        assert_eq!(entries[0].keystrokes, 1);
+    }
+}
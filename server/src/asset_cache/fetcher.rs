@@ -1,28 +1,89 @@
 //! Server-side asset fetcher for CORS-blocked assets
 
-use crate::asset_cache::{AssetError, AssetFileStore, MetadataStore, store_or_get_asset_metadata};
+use crate::asset_cache::{AssetError, AssetFileStore, FetchedAsset, MetadataStore, store_or_get_asset_metadata};
 use crate::asset_cache::hash::sha256;
+use crate::asset_cache::normalize::{AssetNormalization, decode_gzip};
+use crate::asset_cache::scanner::{AssetScanner, ScannedAsset, scan_new_asset};
+use crate::observability::{ObservabilityHooks, names};
 use reqwest::Client;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
-/// Fetch an asset from a URL and store it in the cache
-/// Returns (sha256_hash, random_id)
+/// How an asset is fetched
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FetchOptions<'a> {
+    /// Sent as the User-Agent (to avoid bot detection)
+    pub user_agent: Option<&'a str>,
+    /// Largest asset accepted, in bytes
+    pub max_size: Option<u64>,
+    pub normalization: AssetNormalization,
+}
+
+/// Fetch an asset from a URL and store it in the cache, normalized (see
+/// [`crate::asset_cache::normalize`])
+///
+/// Assets larger than `max_size` bytes are abandoned mid-download with
+/// [`AssetError::TooLarge`]; assets the scanner rejects fail with
+/// [`AssetError::Quarantined`].
 pub async fn fetch_and_cache_asset(
     url: &str,
-    user_agent: Option<&str>,
+    options: &FetchOptions<'_>,
     metadata_store: &dyn MetadataStore,
     asset_file_store: &dyn AssetFileStore,
-) -> Result<(String, String), AssetError> {
+    scanner: &dyn AssetScanner,
+    hooks: &dyn ObservabilityHooks,
+) -> Result<FetchedAsset, AssetError> {
     info!("🌐 Fetching asset from URL: {}", url);
 
+    let started = Instant::now();
+    let result = fetch_asset(&options.normalization.fetch_url(url), options).await;
+    hooks.histogram(names::ASSET_FETCH_SECONDS, started.elapsed().as_secs_f64(), &[]);
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    hooks.counter(names::ASSET_FETCHES, 1, &[("outcome", outcome)]);
+    let (data, mime_type) = result?;
+
+    // Compute SHA-256 hash (for storage and manifest)
+    let source_sha256_hash = sha256(&data);
+    let data = options.normalization.normalize(data, &mime_type, options.max_size)?;
+    let sha256_hash = sha256(&data);
+
+    let scanned = ScannedAsset {
+        url,
+        mime: &mime_type,
+        sha256_hash: &sha256_hash,
+        data: &data,
+    };
+    scan_new_asset(scanner, asset_file_store, hooks, &scanned).await?;
+
+    // Store asset and get/ensure random_id exists
+    let random_id = store_or_get_asset_metadata(
+        &sha256_hash,
+        &data,
+        &mime_type,
+        metadata_store,
+        asset_file_store,
+        hooks,
+    ).await?;
+
+    Ok(FetchedAsset {
+        sha256_hash,
+        source_sha256_hash,
+        random_id,
+        size: data.len() as u64,
+    })
+}
+
+/// Download an asset, returning its bytes and MIME type
+async fn fetch_asset(url: &str, options: &FetchOptions<'_>) -> Result<(Vec<u8>, String), AssetError> {
+    let max_size = options.max_size;
+
     // Create HTTP client with timeout
     let mut client_builder = Client::builder()
         .timeout(Duration::from_secs(30))
         .redirect(reqwest::redirect::Policy::limited(5));
 
     // Add User-Agent if provided (to avoid bot detection)
-    if let Some(ua) = user_agent {
+    if let Some(ua) = options.user_agent {
         client_builder = client_builder.user_agent(ua);
     }
 
@@ -30,15 +91,14 @@ pub async fn fetch_and_cache_asset(
         .map_err(|e| AssetError::Storage(Box::new(e)))?;
 
     // Fetch the asset
-    let response = client
+    let mut response = client
         .get(url)
         .send()
         .await
         .map_err(|e| AssetError::Storage(Box::new(e)))?;
 
     if !response.status().is_success() {
-        return Err(AssetError::Storage(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        return Err(AssetError::Storage(Box::new(std::io::Error::other(
             format!("HTTP error: {}", response.status()),
         ))));
     }
@@ -54,27 +114,42 @@ pub async fn fetch_and_cache_asset(
         .unwrap_or("application/octet-stream")
         .to_string();
 
-    // Read the asset data
-    let data = response
-        .bytes()
+    // Without reqwest's gzip feature, bodies come as the server encoded them
+    let gzipped = response
+        .headers()
+        .get("content-encoding")
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("gzip"));
+
+    // Refuse early when the server announces an oversized body
+    if let (Some(limit), Some(size)) = (max_size, response.content_length())
+        && size > limit
+    {
+        return Err(AssetError::TooLarge { size, limit });
+    }
+
+    // Read the asset data, stopping once it passes the limit
+    let mut data = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
         .await
         .map_err(|e| AssetError::Storage(Box::new(e)))?
-        .to_vec();
-
-    debug!("Fetched {} bytes from {}", data.len(), url);
+    {
+        data.extend_from_slice(&chunk);
+        if let Some(limit) = max_size
+            && data.len() as u64 > limit
+        {
+            return Err(AssetError::TooLarge { size: data.len() as u64, limit });
+        }
+    }
 
-    // Compute SHA-256 hash (for storage and manifest)
-    let sha256_hash = sha256(&data);
+    // Decode the body the way a browser would
+    if gzipped {
+        data = decode_gzip(&data, max_size)?;
+    }
 
-    // Store asset and get/ensure random_id exists
-    let random_id = store_or_get_asset_metadata(
-        &sha256_hash,
-        &data,
-        &mime_type,
-        metadata_store,
-        asset_file_store,
-    ).await?;
+    debug!("Fetched {} bytes from {}", data.len(), url);
 
-    Ok((sha256_hash, random_id))
+    Ok((data, mime_type))
 }
 
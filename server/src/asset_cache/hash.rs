@@ -17,8 +17,8 @@ pub fn sha256(data: &[u8]) -> String {
 /// encoded as Base64url (43 characters, URL-safe, no padding).
 pub fn generate_random_id() -> String {
     let mut random_bytes = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut random_bytes);
-    URL_SAFE_NO_PAD.encode(&random_bytes)
+    rand::rng().fill_bytes(&mut random_bytes);
+    URL_SAFE_NO_PAD.encode(random_bytes)
 }
 
 #[cfg(test)]
@@ -0,0 +1,105 @@
+//! Caps on the asset bytes a recording may add to the cache
+//!
+//! Limits apply to inline Asset frames and to server-side fetches alike.
+//! Assets over a limit are not cached; the recording gets an Annotation frame
+//! named [`ASSET_SKIPPED_ANNOTATION`] in their place so playback tools can
+//! tell a skipped asset from a missing one.
+
+use crate::asset_cache::AssetError;
+use domcorder_proto::AnnotationData;
+
+/// Annotation name written in place of an asset that exceeded a limit
+pub const ASSET_SKIPPED_ANNOTATION: &str = "domcorder:asset-skipped";
+
+/// Configured asset limits; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssetLimits {
+    /// Largest single asset admitted to the cache, in bytes
+    pub max_asset_size: Option<u64>,
+    /// Most asset bytes a single recording may add to the cache
+    pub max_recording_bytes: Option<u64>,
+}
+
+/// Tracks the asset bytes admitted for one recording against its limits
+#[derive(Debug)]
+pub struct AssetBudget {
+    limits: AssetLimits,
+    used: u64,
+}
+
+impl AssetBudget {
+    pub fn new(limits: AssetLimits) -> Self {
+        Self { limits, used: 0 }
+    }
+
+    /// Size of the largest asset that can still be admitted
+    pub fn max_next(&self) -> Option<u64> {
+        let remaining = self.limits.max_recording_bytes.map(|max| max.saturating_sub(self.used));
+        match (self.limits.max_asset_size, remaining) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Fail with [`AssetError::TooLarge`] if an asset of `size` bytes does not fit
+    pub fn check(&self, size: u64) -> Result<(), AssetError> {
+        match self.max_next() {
+            Some(limit) if size > limit => Err(AssetError::TooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count `size` bytes as admitted
+    pub fn charge(&mut self, size: u64) {
+        self.used += size;
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+}
+
+/// The marker recorded in place of an asset that exceeded a limit
+pub fn skipped_annotation(asset_id: u32, url: &str, size: u64, limit: u64) -> AnnotationData {
+    let data = serde_json::json!({
+        "asset_id": asset_id,
+        "url": url,
+        "size": size,
+        "limit": limit,
+    });
+    AnnotationData {
+        name: ASSET_SKIPPED_ANNOTATION.to_string(),
+        data: Some(data.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_applies_the_tighter_limit() {
+        let mut budget = AssetBudget::new(AssetLimits {
+            max_asset_size: Some(100),
+            max_recording_bytes: Some(250),
+        });
+        assert_eq!(budget.max_next(), Some(100));
+        assert!(budget.check(100).is_ok());
+        assert!(matches!(budget.check(101), Err(AssetError::TooLarge { size: 101, limit: 100 })));
+
+        budget.charge(100);
+        budget.charge(100);
+        assert_eq!(budget.max_next(), Some(50));
+        assert!(matches!(budget.check(60), Err(AssetError::TooLarge { size: 60, limit: 50 })));
+
+        budget.charge(50);
+        assert_eq!(budget.max_next(), Some(0));
+    }
+
+    #[test]
+    fn test_unlimited_budget() {
+        let budget = AssetBudget::new(AssetLimits::default());
+        assert_eq!(budget.max_next(), None);
+        assert!(budget.check(u64::MAX).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
 //! Local filesystem implementation of the AssetFileStore trait
 
-use crate::asset_cache::{AssetError, AssetFileStore};
+use crate::asset_cache::{AssetError, AssetFileStore, AssetReader};
+use crate::health::{BackendHealth, probe_directory};
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info};
@@ -90,6 +91,32 @@ impl AssetFileStore for LocalBinaryStore {
         Ok(data)
     }
 
+    async fn delete(&self, hash: &str) -> Result<(), AssetError> {
+        match tokio::fs::remove_file(self.hash_to_path(hash)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn open(&self, hash: &str) -> Result<AssetReader, AssetError> {
+        let file = tokio::fs::File::open(self.hash_to_path(hash)).await?;
+        Ok(Box::new(file))
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>, AssetError> {
+        let base_path = self.base_path.clone();
+        tokio::task::spawn_blocking(move || dir_size(&base_path))
+            .await
+            .map_err(|e| AssetError::Storage(Box::new(e)))?
+            .map(Some)
+            .map_err(AssetError::from)
+    }
+
+    async fn health(&self) -> BackendHealth {
+        BackendHealth::measure(probe_directory(&self.base_path)).await
+    }
+
     fn storage_type(&self) -> &str {
         "local"
     }
@@ -102,6 +129,24 @@ impl AssetFileStore for LocalBinaryStore {
     }
 }
 
+/// Total size of the files under `dir`, not following symlinks
+fn dir_size(dir: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
 // Clone implementation for LocalBinaryStore (needed for spawn_blocking)
 impl Clone for LocalBinaryStore {
     fn clone(&self) -> Self {
@@ -131,6 +176,12 @@ mod tests {
         
         let retrieved = store.get(hash).await.unwrap();
         assert_eq!(retrieved, data);
+
+        let mut streamed = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut store.open(hash).await.unwrap(), &mut streamed)
+            .await
+            .unwrap();
+        assert_eq!(streamed, data);
     }
 
     #[tokio::test]
@@ -1,8 +1,25 @@
 //! Cache manifest generation and management
+//!
+//! The manifest lists the assets the server already has for a site, so the
+//! recorder can send an AssetReference instead of the bytes. A recorder that
+//! keeps the manifests it was sent reports their hashes with a CacheInventory
+//! frame before RecordingMetadata, and gets a differential manifest: only the
+//! entries it doesn't have yet.
+//!
+//! The recording doesn't start until the recorder has its manifest, so the
+//! handshake gives the query a time budget ([`DEFAULT_MANIFEST_BUDGET`]). A
+//! query that overruns it keeps going in the background and refreshes the
+//! site's cached manifest, while this handshake gets the previous one (or an
+//! empty manifest for a site never seen before).
 
 use crate::asset_cache::{AssetError, ManifestEntry, MetadataStore};
+use crate::observability::names;
+use crate::{AppState, StorageState};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
 /// Cache manifest sent to the recorder
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,26 +30,107 @@ pub struct CacheManifest {
     pub site_origin: String,
 }
 
+impl CacheManifest {
+    /// The manifest without the assets whose SHA-256 hashes are in `cached`
+    pub fn without(mut self, cached: &HashSet<String>) -> Self {
+        self.assets.retain(|entry| !cached.contains(&entry.sha256_hash));
+        self
+    }
+}
+
 /// Default limit for manifest entries
 const DEFAULT_MANIFEST_LIMIT: usize = 200;
 
-/// Generate a cache manifest for a site
+/// How long the handshake waits for a manifest by default
+pub const DEFAULT_MANIFEST_BUDGET: Duration = Duration::from_millis(150);
+
+/// Generate a cache manifest for a site, leaving out the assets whose
+/// SHA-256 hashes are in `cached` (those the recorder already has)
 pub async fn generate_manifest(
     metadata_store: &dyn MetadataStore,
     site_origin: &str,
     limit: Option<usize>,
+    cached: &HashSet<String>,
 ) -> Result<CacheManifest, AssetError> {
     let limit = limit.unwrap_or(DEFAULT_MANIFEST_LIMIT);
-    
+
     info!("Generating cache manifest for site: {} (limit: {})", site_origin, limit);
-    
+
     let assets = metadata_store.get_site_manifest(site_origin, limit).await?;
-    
-    debug!("Generated manifest with {} entries for {}", assets.len(), site_origin);
-    
-    Ok(CacheManifest {
+    let total = assets.len();
+    let manifest = CacheManifest {
         assets,
         site_origin: site_origin.to_string(),
-    })
+    }
+    .without(cached);
+
+    debug!(
+        "Generated manifest with {} entries for {} ({} already cached by the recorder)",
+        manifest.assets.len(),
+        site_origin,
+        total - manifest.assets.len()
+    );
+
+    Ok(manifest)
 }
 
+/// The manifest last generated for each site, for handshakes whose own
+/// query overruns the budget
+#[derive(Debug, Default)]
+pub struct ManifestCache {
+    manifests: Mutex<HashMap<String, Vec<ManifestEntry>>>,
+}
+
+impl ManifestCache {
+    fn get(&self, site_origin: &str) -> Option<Vec<ManifestEntry>> {
+        self.manifests.lock().unwrap().get(site_origin).cloned()
+    }
+
+    pub(crate) fn store(&self, manifest: &CacheManifest) {
+        let mut manifests = self.manifests.lock().unwrap();
+        manifests.insert(manifest.site_origin.clone(), manifest.assets.clone());
+    }
+}
+
+impl StorageState {
+    /// The cache manifest for a handshake on `site_origin`, without the
+    /// `cached` assets, within the state's manifest budget
+    ///
+    /// If the query takes longer, the site's previous manifest is returned
+    /// (an empty one if there is none) and the query finishes in the
+    /// background to refresh it. A budget of zero never waits for the query.
+    pub async fn handshake_manifest(
+        self: &AppState,
+        site_origin: &str,
+        cached: &HashSet<String>,
+    ) -> Result<CacheManifest, AssetError> {
+        let state = self.clone();
+        let origin = site_origin.to_string();
+        let query = tokio::spawn(async move {
+            let manifest = generate_manifest(state.metadata_store.as_ref(), &origin, None, &HashSet::new()).await?;
+            state.manifest_cache.store(&manifest);
+            Ok::<_, AssetError>(manifest)
+        });
+
+        let answered = match self.manifest_budget {
+            Duration::ZERO => None,
+            budget => tokio::time::timeout(budget, query).await.ok(),
+        };
+        match answered {
+            Some(Ok(manifest)) => Ok(manifest?.without(cached)),
+            Some(Err(e)) => Err(AssetError::Database(format!("manifest query failed: {}", e))),
+            None => {
+                warn!(
+                    "⏱️ Manifest for {} took longer than {:?}, sending the previous one",
+                    site_origin, self.manifest_budget
+                );
+                self.observability.counter(names::MANIFEST_FALLBACKS, 1, &[]);
+                let manifest = CacheManifest {
+                    assets: self.manifest_cache.get(site_origin).unwrap_or_default(),
+                    site_origin: site_origin.to_string(),
+                };
+                Ok(manifest.without(cached))
+            }
+        }
+    }
+}
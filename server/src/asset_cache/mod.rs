@@ -4,17 +4,49 @@
 //! in a content-addressable store, with metadata tracking for efficient
 //! cache-aware recording.
 
+#[cfg(feature = "fetcher")]
 pub mod fetcher;
 pub mod hash;
+pub mod limits;
 pub mod local;
 pub mod manifest;
+pub mod normalize;
+pub mod pins;
 pub mod playback;
+pub mod scanner;
+pub mod stylesheets;
+#[cfg(feature = "sqlite")]
 pub mod sqlite;
+pub mod versions;
+pub mod warming;
 
+use crate::analytics::heatmap::HeatmapBucket;
+use crate::analytics::session::{SessionEvent, SessionMetrics};
+use crate::analytics::scroll::ScrollDepth;
+use crate::analytics::text_entry::TextEntered;
+use crate::analytics::summary::RecordingSummary;
+use crate::audit::AuditEvent;
+use crate::cluster::ActiveLease;
+use crate::collections::Collection;
+use crate::comments::RecordingComment;
+use crate::policy::OriginPolicy;
+use crate::provenance::Provenance;
+use crate::recording_meta::RecordingMeta;
+use crate::sites::SiteProfile;
+use crate::clock::ClockSkew;
+use crate::encryption::WrappedKey;
+use crate::health::BackendHealth;
+use crate::jobs::Job;
+use crate::observability::{ObservabilityHooks, names};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
+use tokio::io::AsyncRead;
 use tracing::{debug, error, info, warn};
 
+/// A streaming reader over one asset's bytes
+pub type AssetReader = Box<dyn AsyncRead + Unpin + Send>;
+
 /// Error type for asset caching operations
 #[derive(Error, Debug)]
 pub enum AssetError {
@@ -30,6 +62,12 @@ pub enum AssetError {
     #[error("Asset not found: {0}")]
     NotFound(String),
     
+    #[error("Asset too large: {size} bytes exceeds the {limit} byte limit")]
+    TooLarge { size: u64, limit: u64 },
+
+    #[error("Asset quarantined: {0}")]
+    Quarantined(String),
+
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
     
@@ -37,6 +75,7 @@ pub enum AssetError {
     Io(#[from] std::io::Error),
 }
 
+#[cfg(feature = "sqlite")]
 impl From<rusqlite::Error> for AssetError {
     fn from(e: rusqlite::Error) -> Self {
         AssetError::Database(e.to_string())
@@ -61,6 +100,39 @@ pub struct ManifestEntry {
     pub sha256_hash: String,
 }
 
+/// One version of a URL's content, as `GET /admin/assets/url-history` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UrlVersion {
+    pub sha256_hash: String,
+    pub first_seen_at: chrono::DateTime<chrono::Utc>,
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+    /// Whether the asset is still in the cache
+    pub cached: bool,
+}
+
+/// A cached asset found missing during playback
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetMiss {
+    pub random_id: String,
+    /// The original URL of the asset, as last recorded
+    pub url: String,
+    /// How many times playback found it missing
+    pub count: u64,
+    pub last_missed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// An asset fetched server-side and stored in the cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedAsset {
+    /// The SHA-256 hash of the stored (normalized) bytes
+    pub sha256_hash: String,
+    /// The SHA-256 hash of the bytes as the browser would have loaded them,
+    /// before normalization; what a recorder's reference to it carries
+    pub source_sha256_hash: String,
+    pub random_id: String,
+    pub size: u64,
+}
+
 /// Parameters for registering asset usage on a site
 #[derive(Debug, Clone)]
 pub struct AssetUsageParams {
@@ -87,6 +159,11 @@ pub struct AssetMetadata {
     pub mime_type: String,
 }
 
+/// Hash of the provisional AssetReference written while an asset is fetched
+/// server-side; the resolved AssetReference for the same `asset_id` follows
+/// later in the recording. Players skip references with an empty hash.
+pub const PENDING_ASSET_HASH: &str = "";
+
 /// Trait for managing asset metadata and site profiles
 ///
 /// This abstraction allows for different storage backends (SQLite, Postgres, etc.)
@@ -105,6 +182,7 @@ pub trait MetadataStore: Send + Sync {
     /// Generate a prioritized manifest for a site
     ///
     /// Returns up to `limit` entries, ordered by usage frequency and size.
+    /// Usage on every origin of the site's profile counts.
     async fn get_site_manifest(
         &self,
         site_origin: &str,
@@ -138,6 +216,265 @@ pub trait MetadataStore: Send + Sync {
     
     /// Get the MIME type for an asset by random_id
     async fn get_asset_mime_type(&self, random_id: &str) -> Result<Option<String>, AssetError>;
+
+    /// Every version of `url`'s content seen on any site, most recently
+    /// seen first
+    async fn get_url_versions(&self, url: &str) -> Result<Vec<UrlVersion>, AssetError>;
+
+    /// Every version of the content of each URL `site_origin` has used, with
+    /// its URL, in no particular order
+    async fn get_site_url_versions(&self, site_origin: &str) -> Result<Vec<(String, UrlVersion)>, AssetError>;
+
+    /// Record that playback found the asset with `random_id` (originally
+    /// loaded from `url`) missing from the cache
+    async fn record_asset_miss(&self, random_id: &str, url: &str) -> Result<(), AssetError>;
+
+    /// Every asset playback has found missing, most recently missed first
+    async fn list_asset_misses(&self) -> Result<Vec<AssetMiss>, AssetError>;
+
+    /// Add a recording's click counts to a page's heatmap
+    async fn record_click_buckets(
+        &self,
+        site_origin: &str,
+        path: &str,
+        buckets: &[HeatmapBucket],
+    ) -> Result<(), AssetError>;
+
+    /// Get the aggregated click heatmap for a page, across every origin of
+    /// the site's profile
+    async fn get_click_heatmap(&self, site_origin: &str, path: &str) -> Result<Vec<HeatmapBucket>, AssetError>;
+
+    /// Store (or replace) the metrics and funnel events for a session
+    async fn store_session_metrics(
+        &self,
+        metrics: &SessionMetrics,
+        events: &[SessionEvent],
+    ) -> Result<(), AssetError>;
+
+    /// Store (or replace) the listing summary of a completed recording
+    async fn store_recording_summary(&self, summary: &RecordingSummary) -> Result<(), AssetError>;
+
+    /// Get the stored summaries of the given recordings; ids without one are left out
+    async fn get_recording_summaries(&self, recording_ids: &[String]) -> Result<Vec<RecordingSummary>, AssetError>;
+
+    /// Store (or replace) a recording's clock skew estimate
+    async fn store_clock_skew(&self, recording_id: &str, skew: ClockSkew) -> Result<(), AssetError>;
+
+    /// Get a recording's clock skew estimate, if its recorder answered any pings
+    async fn get_clock_skew(&self, recording_id: &str) -> Result<Option<ClockSkew>, AssetError>;
+
+    /// Store (or replace) a recording's wrapped data key
+    async fn store_recording_key(&self, recording_id: &str, key: &WrappedKey) -> Result<(), AssetError>;
+
+    /// Get a recording's wrapped data key; None for recordings stored in plaintext
+    async fn get_recording_key(&self, recording_id: &str) -> Result<Option<WrappedKey>, AssetError>;
+
+    /// Remove a recording's data key, as when it is rewritten in plaintext
+    async fn delete_recording_key(&self, recording_id: &str) -> Result<(), AssetError>;
+
+    /// List the wrapped data keys not wrapped by master key `key_id`, keyed by
+    /// recording id
+    async fn list_recording_keys_not_wrapped_by(&self, key_id: &str) -> Result<Vec<(String, WrappedKey)>, AssetError>;
+
+    /// Place a recording on legal hold or release it, returning false if it
+    /// already was in that state
+    async fn set_legal_hold(&self, recording_id: &str, held: bool) -> Result<bool, AssetError>;
+
+    /// Check whether a recording is on legal hold
+    async fn is_on_legal_hold(&self, recording_id: &str) -> Result<bool, AssetError>;
+
+    /// List the ids of recordings on legal hold
+    async fn list_legal_holds(&self) -> Result<Vec<String>, AssetError>;
+
+    /// Append an entry to the audit log
+    async fn append_audit_event(&self, event: &AuditEvent) -> Result<(), AssetError>;
+
+    /// List audit log entries, oldest first, optionally of one recording only
+    async fn list_audit_events(&self, recording_id: Option<&str>) -> Result<Vec<AuditEvent>, AssetError>;
+
+    /// Store the recorder's address (already minimized) with a recording
+    async fn store_client_ip(&self, recording_id: &str, client_ip: &str) -> Result<(), AssetError>;
+
+    /// Store the selector of the element a recording is scoped to
+    async fn store_recording_scope(&self, recording_id: &str, root_selector: &str) -> Result<(), AssetError>;
+
+    /// Get the selector of the element a recording is scoped to; None for
+    /// recordings of the whole page
+    async fn get_recording_scope(&self, recording_id: &str) -> Result<Option<String>, AssetError>;
+
+    /// Link a recording to the user session it belongs to
+    async fn store_session_link(&self, recording_id: &str, session_id: &str) -> Result<(), AssetError>;
+
+    /// List the ids of the recordings linked to user session `session_id`
+    async fn list_session_recordings(&self, session_id: &str) -> Result<Vec<String>, AssetError>;
+
+    /// Cache the metadata read from a finished recording
+    async fn store_recording_meta(&self, recording_id: &str, meta: &RecordingMeta) -> Result<(), AssetError>;
+
+    /// Get the cached metadata of a recording, if any
+    async fn get_recording_meta(&self, recording_id: &str) -> Result<Option<RecordingMeta>, AssetError>;
+
+    /// List the ids of recordings whose user id, email hash or anonymous id
+    /// is `subject`, or that are tagged with it
+    async fn find_recordings_by_subject(&self, subject: &str) -> Result<Vec<String>, AssetError>;
+
+    /// Store a comment on a recording, returning its id
+    async fn add_comment(&self, comment: &RecordingComment) -> Result<i64, AssetError>;
+
+    /// List a recording's comments by offset, oldest first among equal offsets
+    async fn list_comments(&self, recording_id: &str) -> Result<Vec<RecordingComment>, AssetError>;
+
+    /// Create a collection, or change the description of an existing one
+    async fn save_collection(&self, name: &str, description: Option<&str>) -> Result<(), AssetError>;
+
+    /// Get a collection and its members, if it exists
+    async fn get_collection(&self, name: &str) -> Result<Option<Collection>, AssetError>;
+
+    /// List every collection and its members, by name
+    async fn list_collections(&self) -> Result<Vec<Collection>, AssetError>;
+
+    /// Delete a collection (not its recordings), returning false if there was none
+    async fn delete_collection(&self, name: &str) -> Result<bool, AssetError>;
+
+    /// Add a recording to a collection or take it out, returning whether
+    /// that changed anything
+    async fn set_collection_member(&self, name: &str, recording_id: &str, member: bool) -> Result<bool, AssetError>;
+
+    /// Move everything stored about a recording to a new id, as for
+    /// [`delete_recording_metadata`](Self::delete_recording_metadata) (the
+    /// audit log keeps the old id)
+    async fn rename_recording_metadata(&self, recording_id: &str, new_id: &str) -> Result<(), AssetError>;
+
+    /// Replace everything stored about `recording_id` with what is stored
+    /// about `from`, in one step, as when a rewritten copy takes its place
+    async fn replace_recording_metadata(&self, from: &str, recording_id: &str) -> Result<(), AssetError>;
+
+    /// Replace the data key of `recording_id` with the key of `from` (or
+    /// remove it if `from` has none), leaving the rest of its metadata
+    async fn replace_recording_key(&self, from: &str, recording_id: &str) -> Result<(), AssetError>;
+
+    /// Forget everything stored about a deleted recording: its summary, key,
+    /// metrics, events, comments and collection memberships (the audit log is kept)
+    async fn delete_recording_metadata(&self, recording_id: &str) -> Result<(), AssetError>;
+
+    /// Forget an asset and the URLs resolving to it, returning its SHA-256
+    /// hash so the caller can remove the data; None if it was unknown
+    async fn delete_asset(&self, random_id: &str) -> Result<Option<String>, AssetError>;
+
+    /// Record that a recording refers to the cached assets `random_ids`;
+    /// references already recorded are ignored
+    async fn add_asset_references(&self, recording_id: &str, random_ids: &[String]) -> Result<(), AssetError>;
+
+    /// Record that recording `recording_id` played `url` as the asset with
+    /// `random_id`, replacing the version recorded before
+    async fn pin_asset_version(&self, recording_id: &str, url: &str, random_id: &str) -> Result<(), AssetError>;
+
+    /// The version each asset URL of a recording was pinned to, by URL
+    async fn get_pinned_asset_versions(&self, recording_id: &str) -> Result<HashMap<String, String>, AssetError>;
+
+    /// The cached assets no recording refers to: those among `random_ids`,
+    /// or every one if None
+    async fn unreferenced_assets(&self, random_ids: Option<&[String]>) -> Result<Vec<String>, AssetError>;
+
+    /// The SHA-256 hash and MIME type of every cached asset
+    async fn list_asset_hashes(&self) -> Result<Vec<(String, String)>, AssetError>;
+
+    /// Take or renew `lease.node_id`'s lease on `lease.recording_id`; false
+    /// if another node holds an unexpired lease on it
+    async fn claim_active_recording(&self, lease: &ActiveLease) -> Result<bool, AssetError>;
+
+    /// Give up `node_id`'s lease on a recording, if it holds one
+    async fn release_active_recording(&self, recording_id: &str, node_id: &str) -> Result<(), AssetError>;
+
+    /// Every unexpired lease on a recording
+    async fn list_active_recordings(&self) -> Result<Vec<ActiveLease>, AssetError>;
+
+    /// Store (or replace) a background job and its progress
+    async fn save_job(&self, job: &Job) -> Result<(), AssetError>;
+
+    async fn get_job(&self, id: &str) -> Result<Option<Job>, AssetError>;
+
+    /// The jobs that are queued or running, oldest first
+    async fn list_unfinished_jobs(&self) -> Result<Vec<Job>, AssetError>;
+
+    /// Forget finished jobs beyond the `keep` most recently created
+    async fn prune_finished_jobs(&self, keep: usize) -> Result<(), AssetError>;
+
+    /// Append an entry to a recording's provenance
+    async fn add_recording_provenance(&self, recording_id: &str, provenance: &Provenance) -> Result<(), AssetError>;
+
+    /// A recording's provenance, oldest first
+    async fn list_recording_provenance(&self, recording_id: &str) -> Result<Vec<Provenance>, AssetError>;
+
+    /// Store (or replace) the text entered during a recording
+    async fn store_text_entries(&self, recording_id: &str, entries: &[TextEntered]) -> Result<(), AssetError>;
+
+    /// The text entered during a recording, in order
+    async fn list_text_entries(&self, recording_id: &str) -> Result<Vec<TextEntered>, AssetError>;
+
+    /// The text entered during every session on a site (every origin of its
+    /// profile) whose value contains `contains` (ignoring ASCII case), with
+    /// the recording it was entered in
+    async fn search_text_entries(
+        &self,
+        site_origin: &str,
+        contains: Option<&str>,
+    ) -> Result<Vec<(String, TextEntered)>, AssetError>;
+
+    /// Store (or replace) how far down its page a recording scrolled
+    async fn store_scroll_depth(&self, recording_id: &str, depth: &ScrollDepth) -> Result<(), AssetError>;
+
+    /// The scroll depth of every session on a page, across every origin of
+    /// the site's profile
+    async fn list_scroll_depths(&self, site_origin: &str, path: &str) -> Result<Vec<ScrollDepth>, AssetError>;
+
+    /// Whether the one-off data migration `name` has run
+    async fn is_migration_done(&self, name: &str) -> Result<bool, AssetError>;
+
+    /// Record that the one-off data migration `name` has run
+    async fn mark_migration_done(&self, name: &str) -> Result<(), AssetError>;
+
+    /// Get the recording policy of an origin (or of `*`), if one is set
+    async fn get_origin_policy(&self, origin: &str) -> Result<Option<OriginPolicy>, AssetError>;
+
+    /// Store (or replace) an origin's recording policy
+    async fn set_origin_policy(&self, policy: &OriginPolicy) -> Result<(), AssetError>;
+
+    /// Remove an origin's recording policy, returning false if it had none
+    async fn delete_origin_policy(&self, origin: &str) -> Result<bool, AssetError>;
+
+    /// List every recording policy, by origin
+    async fn list_origin_policies(&self) -> Result<Vec<OriginPolicy>, AssetError>;
+
+    /// Store (or replace) a site profile
+    async fn set_site_profile(&self, profile: &SiteProfile) -> Result<(), AssetError>;
+
+    /// Remove the profile of `site`, returning false if it had none
+    async fn delete_site_profile(&self, site: &str) -> Result<bool, AssetError>;
+
+    /// List every site profile, by site
+    async fn list_site_profiles(&self) -> Result<Vec<SiteProfile>, AssetError>;
+
+    /// List session metrics for a site (every origin of its profile), most
+    /// recent first
+    async fn list_session_metrics(&self, site_origin: &str) -> Result<Vec<SessionMetrics>, AssetError>;
+
+    /// Get the ordered funnel events of every session on a site (every origin
+    /// of its profile), keyed by recording id
+    async fn list_session_events(
+        &self,
+        site_origin: &str,
+    ) -> Result<Vec<(String, Vec<SessionEvent>)>, AssetError>;
+
+    /// Bytes the store occupies on disk, if it knows (reported by `/admin/storage`)
+    async fn disk_usage(&self) -> Result<Option<u64>, AssetError> {
+        Ok(None)
+    }
+
+    /// Probe the database (reported by `/readyz`)
+    async fn health(&self) -> BackendHealth {
+        BackendHealth::unknown()
+    }
 }
 
 /// Trait for physical storage of asset binary data
@@ -167,6 +504,29 @@ pub trait AssetFileStore: Send + Sync {
     /// Returns the asset bytes if the asset exists.
     async fn get(&self, hash: &str) -> Result<Vec<u8>, AssetError>;
 
+    /// Remove asset data from the store; removing a missing asset is not an error
+    async fn delete(&self, hash: &str) -> Result<(), AssetError>;
+
+    /// Open asset data for streaming
+    ///
+    /// Serving assets goes through here so large assets are never held in
+    /// memory. The default reads the whole asset with [`get`](Self::get);
+    /// backends that can stream should override it.
+    async fn open(&self, hash: &str) -> Result<AssetReader, AssetError> {
+        Ok(Box::new(std::io::Cursor::new(self.get(hash).await?)))
+    }
+
+    /// Bytes the stored assets occupy on disk, if the backend knows
+    /// (reported by `/admin/storage`)
+    async fn disk_usage(&self) -> Result<Option<u64>, AssetError> {
+        Ok(None)
+    }
+
+    /// Probe the backend the assets are kept in (reported by `/readyz`)
+    async fn health(&self) -> BackendHealth {
+        BackendHealth::unknown()
+    }
+
     /// Get the storage type identifier (e.g., "local", "s3")
     fn storage_type(&self) -> &str;
 
@@ -193,11 +553,14 @@ pub async fn store_or_get_asset_metadata(
     mime_type: &str,
     metadata_store: &dyn MetadataStore,
     asset_file_store: &dyn AssetFileStore,
+    hooks: &dyn ObservabilityHooks,
 ) -> Result<String, AssetError> {
     // Check if asset already exists (by SHA-256)
     let exists = asset_file_store.exists(sha256_hash).await?;
     
     if exists {
+        hooks.counter(names::ASSETS_CACHED, 1, &[("result", "deduplicated")]);
+
         // Asset exists in CAS - try to resolve SHA-256 to random_id
         match metadata_store.resolve_hashes(sha256_hash).await {
             Ok(Some(existing_random_id)) => {
@@ -244,6 +607,8 @@ pub async fn store_or_get_asset_metadata(
         
         // Store the asset in CAS (using SHA-256 as key)
         asset_file_store.put(sha256_hash, data, mime_type).await?;
+        hooks.counter(names::ASSETS_CACHED, 1, &[("result", "stored")]);
+        hooks.counter(names::ASSET_BYTES_STORED, data.len() as u64, &[]);
         info!("💾 Restored asset to CAS: sha256={}, random_id={} ({} bytes)", 
               &sha256_hash[..16], &existing_random_id[..16], data.len());
         
@@ -264,6 +629,8 @@ pub async fn store_or_get_asset_metadata(
     
     // Store the asset in CAS (using SHA-256 as key)
     asset_file_store.put(sha256_hash, data, mime_type).await?;
+    hooks.counter(names::ASSETS_CACHED, 1, &[("result", "stored")]);
+    hooks.counter(names::ASSET_BYTES_STORED, data.len() as u64, &[]);
     debug!("💾 Stored new asset: sha256={}, random_id={} ({} bytes)", 
           &sha256_hash[..16], &random_id[..16], data.len());
     
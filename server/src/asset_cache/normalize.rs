@@ -0,0 +1,223 @@
+//! Content-aware asset normalization
+//!
+//! The CAS dedupes assets by the SHA-256 of their bytes, so trivially
+//! different copies of the same logical asset each get their own entry. Before
+//! an asset is hashed it is normalized:
+//!
+//! - Server-side fetches drop tracking query parameters (`utm_*`, `fbclid`,
+//!   ...) from the URL, so campaign links don't each fetch the asset again.
+//! - Gzip bodies are decompressed the way a browser would. Fetched responses
+//!   with `Content-Encoding: gzip` always are, and text assets whose bytes
+//!   are still gzipped (a precompressed file served without the header,
+//!   `.svgz`) are by default. The recorder hashes what the browser decoded,
+//!   so server-side fetches now hash the same bytes.
+//! - Optionally, whitespace between SVG tags is removed. Off by default: it
+//!   can change how SVG text renders, and the recorder hashes SVGs as
+//!   loaded, so they no longer match the manifest and are always uploaded.
+
+use crate::asset_cache::AssetError;
+use flate2::read::MultiGzDecoder;
+use std::io::Read;
+
+/// Query parameters that only track where a visitor came from
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga", "_gl",
+];
+
+/// The gzip magic number
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How assets are normalized before they are hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetNormalization {
+    /// Drop tracking query parameters from URLs fetched server-side
+    pub strip_tracking_params: bool,
+    /// Decompress text assets whose bytes are still gzipped
+    pub decompress_gzip: bool,
+    /// Remove whitespace between SVG tags
+    pub canonicalize_svg: bool,
+}
+
+impl Default for AssetNormalization {
+    fn default() -> Self {
+        Self {
+            strip_tracking_params: true,
+            decompress_gzip: true,
+            canonicalize_svg: false,
+        }
+    }
+}
+
+impl AssetNormalization {
+    /// The URL to fetch for `url`
+    pub fn fetch_url(&self, url: &str) -> String {
+        if self.strip_tracking_params {
+            strip_tracking_params(url)
+        } else {
+            url.to_string()
+        }
+    }
+
+    /// Normalize the bytes of an asset of type `mime`, failing with
+    /// [`AssetError::TooLarge`] if decompressing makes it larger than `max_size`
+    pub fn normalize(&self, data: Vec<u8>, mime: &str, max_size: Option<u64>) -> Result<Vec<u8>, AssetError> {
+        let mut data = data;
+        if self.decompress_gzip && is_text(mime) && data.starts_with(&GZIP_MAGIC) {
+            match decode_gzip(&data, max_size) {
+                Ok(decoded) => data = decoded,
+                Err(e @ AssetError::TooLarge { .. }) => return Err(e),
+                // Not gzip after all; keep the bytes as they are
+                Err(_) => {}
+            }
+        }
+        if self.canonicalize_svg
+            && mime == "image/svg+xml"
+            && let Ok(text) = std::str::from_utf8(&data)
+        {
+            data = canonicalize_svg(text).into_bytes();
+        }
+        Ok(data)
+    }
+}
+
+/// `url` without its tracking query parameters; URLs without any (or that
+/// don't parse) are returned as they are
+pub fn strip_tracking_params(url: &str) -> String {
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return url.to_string();
+    };
+    let is_tracking = |name: &str| name.starts_with("utm_") || TRACKING_PARAMS.contains(&name);
+    if !parsed.query_pairs().any(|(name, _)| is_tracking(&name)) {
+        return url.to_string();
+    }
+    let kept: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(kept);
+    }
+    parsed.to_string()
+}
+
+/// Decompress a gzip body, failing with [`AssetError::TooLarge`] once it
+/// passes `max_size`
+pub fn decode_gzip(data: &[u8], max_size: Option<u64>) -> Result<Vec<u8>, AssetError> {
+    // One byte past the limit is enough to tell the body is too large
+    let mut decoder = MultiGzDecoder::new(data).take(max_size.map_or(u64::MAX, |limit| limit + 1));
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded)?;
+    if let Some(limit) = max_size
+        && decoded.len() as u64 > limit
+    {
+        return Err(AssetError::TooLarge {
+            size: decoded.len() as u64,
+            limit,
+        });
+    }
+    Ok(decoded)
+}
+
+/// Whether assets of type `mime` are text, so gzip magic means they were
+/// left compressed rather than being a gzip file
+fn is_text(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.ends_with("+xml")
+        || mime.ends_with("+json")
+        || matches!(mime, "application/javascript" | "application/json" | "application/xml")
+}
+
+/// `text` without whitespace between tags, and with Unix line endings
+fn canonicalize_svg(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    let mut canonical = String::with_capacity(text.len());
+    let mut rest = text.trim();
+    while let Some(end) = rest.find('>') {
+        canonical.push_str(&rest[..=end]);
+        rest = &rest[end + 1..];
+        let next = rest.trim_start();
+        if next.starts_with('<') {
+            rest = next;
+        }
+    }
+    canonical.push_str(rest);
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_strip_tracking_params() {
+        assert_eq!(
+            strip_tracking_params("https://cdn.example/app.css?utm_source=mail&v=3&fbclid=abc"),
+            "https://cdn.example/app.css?v=3"
+        );
+        assert_eq!(
+            strip_tracking_params("https://cdn.example/app.css?utm_campaign=x"),
+            "https://cdn.example/app.css"
+        );
+        // Left alone unless there is something to strip
+        assert_eq!(
+            strip_tracking_params("https://cdn.example/a.css?q=a%20b"),
+            "https://cdn.example/a.css?q=a%20b"
+        );
+        assert_eq!(
+            strip_tracking_params("not a url?utm_source=x"),
+            "not a url?utm_source=x"
+        );
+    }
+
+    #[test]
+    fn test_gzipped_text_is_decompressed() {
+        let normalization = AssetNormalization::default();
+        let css = b"body { color: red }".to_vec();
+        assert_eq!(normalization.normalize(gzip(&css), "text/css", None).unwrap(), css);
+        // A gzip file is an asset in its own right
+        let archive = gzip(&css);
+        assert_eq!(
+            normalization
+                .normalize(archive.clone(), "application/gzip", None)
+                .unwrap(),
+            archive
+        );
+        assert!(matches!(
+            normalization.normalize(gzip(&[b'a'; 100]), "text/css", Some(10)),
+            Err(AssetError::TooLarge { size: 11, limit: 10 })
+        ));
+    }
+
+    #[test]
+    fn test_svg_canonicalization() {
+        let spaced = "<svg>\r\n  <g>\n    <path d=\"M0 0\"/>\n  </g>\n</svg>\n";
+        let tight = "<svg><g><path d=\"M0 0\"/></g></svg>";
+        let normalization = AssetNormalization {
+            canonicalize_svg: true,
+            ..AssetNormalization::default()
+        };
+        assert_eq!(
+            normalization.normalize(spaced.into(), "image/svg+xml", None).unwrap(),
+            tight.as_bytes()
+        );
+        assert_eq!(canonicalize_svg("<text> a b </text>"), "<text> a b </text>");
+        // Off by default
+        let unchanged = AssetNormalization::default();
+        assert_eq!(
+            unchanged.normalize(spaced.into(), "image/svg+xml", None).unwrap(),
+            spaced.as_bytes()
+        );
+    }
+}
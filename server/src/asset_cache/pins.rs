@@ -0,0 +1,49 @@
+//! Asset versions pinned to recordings
+//!
+//! Sites serve new content at the same URL after a deploy, and the cache
+//! keeps every version. A recording should keep playing what each URL served
+//! when it was recorded, however often the URL changes afterwards. Ingestion
+//! pins, per recording, the version each asset URL resolved to, and playback
+//! resolves references through the pins where the recording itself can't
+//! say: a provisional reference whose fetch never finished, or a reference
+//! to an asset that has gone missing from the cache.
+//!
+//! When neither the recorder nor the server could load an asset, the
+//! recording gets the version the URL served at recording time, from the URL
+//! history (see [`crate::asset_cache::versions`]), rather than nothing. The
+//! newest version is never used unless it was also current then.
+
+use crate::asset_cache::PENDING_ASSET_HASH;
+use crate::asset_cache::versions::version_at;
+use crate::{RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use domcorder_proto::Frame;
+use std::collections::HashMap;
+
+/// The URL and random id a frame pins, if it is a resolved AssetReference
+pub(crate) fn pinned_version(frame: &Frame) -> Option<(&str, &str)> {
+    match frame {
+        Frame::AssetReference(reference) if reference.hash != PENDING_ASSET_HASH => {
+            Some((&reference.url, &reference.hash))
+        }
+        _ => None,
+    }
+}
+
+impl StorageState {
+    /// The cached version (random id) of the content `url` served at `at`
+    /// (ms since the epoch); None if it isn't known or no longer cached
+    pub(crate) async fn asset_version_at(&self, url: &str, at: u64) -> Result<Option<String>, StorageError> {
+        let versions = self.metadata_store.get_url_versions(url).await?;
+        let at = DateTime::from_timestamp_millis(at as i64).unwrap_or_else(Utc::now);
+        match version_at(&versions, at) {
+            Some(version) if version.cached => Ok(self.metadata_store.resolve_hashes(&version.sha256_hash).await?),
+            _ => Ok(None),
+        }
+    }
+
+    /// The version each asset URL of recording `id` was pinned to, by URL
+    pub async fn pinned_asset_versions(&self, id: &RecordingId) -> Result<HashMap<String, String>, StorageError> {
+        Ok(self.metadata_store.get_pinned_asset_versions(id.as_str()).await?)
+    }
+}
@@ -2,39 +2,144 @@
 //!
 //! This module handles converting AssetReference frames to HTTP URLs
 //! during playback, enabling browser caching.
+//!
+//! An asset can go missing from the cache after it was recorded (removed by
+//! hand, lost with a bucket). With the original URL fallback on, a reference
+//! to an asset that is no longer cached is sent to the player with the URL
+//! the page loaded it from, marked with [`ORIGINAL_URL_FALLBACK`], so it
+//! renders if the site still serves it. Each miss is recorded in the metadata
+//! store for `GET /admin/assets/misses`.
+//!
+//! Given the asset versions pinned to the recording (see
+//! [`crate::asset_cache::pins`]), provisional references resolve to the
+//! pinned version of their URL, and a reference to a missing asset to the
+//! pinned version if that is still cached.
 
-use crate::asset_cache::{AssetError, AssetFileStore, MetadataStore};
-use domcorder_proto::Frame;
-use tracing::debug;
+use crate::asset_cache::scanner::QUARANTINED_ASSET_HASH;
+use crate::asset_cache::stylesheets::expand_stylesheet_reference;
+use crate::asset_cache::{AssetError, AssetFileStore, MetadataStore, PENDING_ASSET_HASH};
+use domcorder_proto::{AssetReferenceData, Frame};
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// Fetch error of an asset the player loads from its original URL because
+/// the cached copy is missing
+pub const ORIGINAL_URL_FALLBACK: &str = "domcorder:original-url";
 
 /// Transform frames during playback to use HTTP URLs for cached assets
-pub struct PlaybackFrameTransformer {
-    metadata_store: Box<dyn MetadataStore>,
-    asset_file_store: Box<dyn AssetFileStore>,
+pub struct PlaybackFrameTransformer<'a> {
+    metadata_store: &'a dyn MetadataStore,
+    asset_file_store: &'a dyn AssetFileStore,
     base_url: String,
+    original_url_fallback: bool,
+    pinned_versions: Option<&'a HashMap<String, String>>,
 }
 
-impl PlaybackFrameTransformer {
+impl<'a> PlaybackFrameTransformer<'a> {
     pub fn new(
-        metadata_store: Box<dyn MetadataStore>,
-        asset_file_store: Box<dyn AssetFileStore>,
+        metadata_store: &'a dyn MetadataStore,
+        asset_file_store: &'a dyn AssetFileStore,
         base_url: String,
     ) -> Self {
         Self {
             metadata_store,
             asset_file_store,
             base_url,
+            original_url_fallback: false,
+            pinned_versions: None,
+        }
+    }
+
+    /// Resolve references through the versions pinned to the recording,
+    /// by URL
+    pub fn with_pinned_versions(mut self, pinned_versions: &'a HashMap<String, String>) -> Self {
+        self.pinned_versions = Some(pinned_versions);
+        self
+    }
+
+    /// The version pinned to `url`, if any
+    fn pinned_version(&self, url: &str) -> Option<&'a str> {
+        self.pinned_versions?.get(url).map(String::as_str)
+    }
+
+    /// Send references to assets missing from the cache with their original
+    /// URL instead of the cache's
+    pub fn with_original_url_fallback(mut self, fallback: bool) -> Self {
+        self.original_url_fallback = fallback;
+        self
+    }
+
+    /// Whether the asset with `random_id` is in the cache
+    async fn is_cached(&self, random_id: &str) -> Result<bool, AssetError> {
+        match self.metadata_store.resolve_random_id(random_id).await? {
+            Some(sha256) => self.asset_file_store.exists(&sha256).await,
+            None => Ok(false),
         }
     }
 
+    /// The frame for a reference to a missing asset: its original URL,
+    /// marked as a fallback; the miss is recorded (failures are logged)
+    async fn fall_back_to_original_url(&self, asset_ref: AssetReferenceData) -> Frame {
+        warn!(
+            "⚠️ Asset {} is missing from the cache; playing {} from its original URL",
+            asset_ref.hash, asset_ref.url
+        );
+        if let Err(e) = self.metadata_store.record_asset_miss(&asset_ref.hash, &asset_ref.url).await {
+            warn!("Failed to record missing asset {}: {}", asset_ref.hash, e);
+        }
+        Frame::Asset(domcorder_proto::AssetData {
+            asset_id: asset_ref.asset_id,
+            url: asset_ref.url,
+            mime: asset_ref.mime,
+            buf: Vec::new(),
+            fetch_error: domcorder_proto::AssetFetchError::Unknown(ORIGINAL_URL_FALLBACK.to_string()),
+        })
+    }
+
     /// Transform a frame for playback
     ///
     /// - AssetReference frames: hash field contains random_id, resolve to HTTP URL
+    ///   (or the pinned version's, or the original URL if missing and the
+    ///   fallback is on)
+    /// - Provisional AssetReference frames: resolve the pinned version, if any
     /// - Asset frames: Convert to AssetReference with HTTP URL (if cached)
+    /// - Stylesheet references: Read the text back from the CAS
     /// - Other frames: Pass through unchanged
     pub async fn transform_frame(&self, frame: Frame) -> Result<Frame, AssetError> {
         match frame {
-            Frame::AssetReference(asset_ref) => {
+            // Provisional reference; the resolved one comes later in the recording
+            Frame::AssetReference(mut asset_ref) if asset_ref.hash == PENDING_ASSET_HASH => {
+                match self.pinned_version(&asset_ref.url) {
+                    Some(pinned) => {
+                        asset_ref.hash = pinned.to_string();
+                        Box::pin(self.transform_frame(Frame::AssetReference(asset_ref))).await
+                    }
+                    None => Ok(Frame::AssetReference(asset_ref)),
+                }
+            }
+            Frame::AssetReference(asset_ref) if asset_ref.hash == QUARANTINED_ASSET_HASH => {
+                // Nothing to serve; the player treats it like an asset it failed to fetch
+                Ok(Frame::Asset(domcorder_proto::AssetData {
+                    asset_id: asset_ref.asset_id,
+                    url: asset_ref.url,
+                    mime: asset_ref.mime,
+                    buf: Vec::new(),
+                    fetch_error: domcorder_proto::AssetFetchError::Unknown("quarantined".to_string()),
+                }))
+            }
+            Frame::AssetReference(mut asset_ref) => {
+                if let Some(pinned) = self.pinned_version(&asset_ref.url)
+                    && pinned != asset_ref.hash
+                    && !self.is_cached(&asset_ref.hash).await?
+                    && self.is_cached(pinned).await?
+                {
+                    debug!("Asset {} is missing; playing the version pinned to {}", asset_ref.hash, asset_ref.url);
+                    asset_ref.hash = pinned.to_string();
+                }
+                if self.original_url_fallback && !self.is_cached(&asset_ref.hash).await? {
+                    return Ok(self.fall_back_to_original_url(asset_ref).await);
+                }
+
                 // hash field contains random_id (from recording stream)
                 // Resolve random_id to HTTP URL
                 let url = self.asset_file_store.resolve_url(&asset_ref.hash).await?;
@@ -100,6 +205,9 @@ impl PlaybackFrameTransformer {
                     Ok(Frame::Asset(asset))
                 }
             }
+            Frame::NewAdoptedStyleSheetReference(_) | Frame::StyleSheetReplacedReference(_) => {
+                expand_stylesheet_reference(frame, self.metadata_store, self.asset_file_store).await
+            }
             other => Ok(other),
         }
     }
@@ -0,0 +1,137 @@
+//! Content scanning for assets before they are admitted to the CAS
+//!
+//! The server persists and re-serves arbitrary downloaded binaries, so every
+//! new asset (inline or fetched server-side) is passed to an [`AssetScanner`]
+//! first. Quarantined assets are not stored: they are logged and the recording
+//! gets an AssetReference to [`QUARANTINED_ASSET_HASH`] instead, which playback
+//! reports to the player as a failed fetch.
+//!
+//! Assets already in the CAS passed a scan when they were admitted and are not
+//! scanned again.
+
+use crate::asset_cache::{AssetError, AssetFileStore};
+use crate::observability::{ObservabilityHooks, names};
+use tracing::warn;
+
+/// Placeholder stored in AssetReference frames for quarantined assets
+pub const QUARANTINED_ASSET_HASH: &str = "quarantined";
+
+/// An asset about to be admitted to the CAS
+#[derive(Debug, Clone, Copy)]
+pub struct ScannedAsset<'a> {
+    pub url: &'a str,
+    pub mime: &'a str,
+    pub sha256_hash: &'a str,
+    pub data: &'a [u8],
+}
+
+/// Outcome of scanning an asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    Quarantine { reason: String },
+}
+
+/// Decides whether an asset may be cached (e.g. ClamAV, or a size/type policy)
+///
+/// A scanner that returns an error fails closed: the asset is quarantined.
+#[async_trait::async_trait]
+pub trait AssetScanner: Send + Sync {
+    async fn scan(&self, asset: &ScannedAsset<'_>) -> Result<ScanVerdict, AssetError>;
+}
+
+/// Admits every asset
+#[derive(Debug, Default)]
+pub struct NoopScanner;
+
+#[async_trait::async_trait]
+impl AssetScanner for NoopScanner {
+    async fn scan(&self, _asset: &ScannedAsset<'_>) -> Result<ScanVerdict, AssetError> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+/// Quarantines assets whose MIME type starts with one of the denied prefixes
+/// (e.g. `application/x-msdownload`, `application/`)
+#[derive(Debug, Default)]
+pub struct MimePolicyScanner {
+    denied: Vec<String>,
+}
+
+impl MimePolicyScanner {
+    pub fn new(denied: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            denied: denied.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AssetScanner for MimePolicyScanner {
+    async fn scan(&self, asset: &ScannedAsset<'_>) -> Result<ScanVerdict, AssetError> {
+        let mime = asset.mime.to_ascii_lowercase();
+        Ok(match self.denied.iter().find(|denied| mime.starts_with(denied.as_str())) {
+            Some(denied) => ScanVerdict::Quarantine {
+                reason: format!("MIME type {} is denied by policy ({})", asset.mime, denied),
+            },
+            None => ScanVerdict::Clean,
+        })
+    }
+}
+
+/// Scan `asset` unless the CAS already holds it, failing with
+/// [`AssetError::Quarantined`] if it must not be stored
+pub async fn scan_new_asset(
+    scanner: &dyn AssetScanner,
+    asset_file_store: &dyn AssetFileStore,
+    hooks: &dyn ObservabilityHooks,
+    asset: &ScannedAsset<'_>,
+) -> Result<(), AssetError> {
+    if asset_file_store.exists(asset.sha256_hash).await? {
+        return Ok(());
+    }
+
+    let reason = match scanner.scan(asset).await {
+        Ok(ScanVerdict::Clean) => return Ok(()),
+        Ok(ScanVerdict::Quarantine { reason }) => reason,
+        Err(e) => format!("scan failed: {}", e),
+    };
+    warn!(
+        "☣️  Quarantined asset: url={}, mime={}, sha256={}, {} bytes: {}",
+        asset.url,
+        asset.mime,
+        asset.sha256_hash,
+        asset.data.len(),
+        reason
+    );
+    hooks.counter(names::ASSETS_QUARANTINED, 1, &[]);
+    Err(AssetError::Quarantined(reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(mime: &str) -> ScannedAsset<'_> {
+        ScannedAsset {
+            url: "https://example.com/file",
+            mime,
+            sha256_hash: "0",
+            data: b"MZ",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mime_policy_scanner() {
+        let scanner = MimePolicyScanner::new(["application/x-msdownload", "text/html"]);
+        assert_eq!(scanner.scan(&asset("image/png")).await.unwrap(), ScanVerdict::Clean);
+        assert!(matches!(
+            scanner.scan(&asset("Application/X-MSDownload")).await.unwrap(),
+            ScanVerdict::Quarantine { .. }
+        ));
+        assert!(matches!(
+            scanner.scan(&asset("text/html")).await.unwrap(),
+            ScanVerdict::Quarantine { .. }
+        ));
+    }
+}
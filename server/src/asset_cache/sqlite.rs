@@ -1,15 +1,84 @@
 //! SQLite implementation of the MetadataStore trait
 
-use crate::asset_cache::{AssetError, AssetMetadata, AssetUsageParams, ManifestEntry, MetadataStore, SiteInfo};
+use crate::analytics::heatmap::HeatmapBucket;
+use crate::analytics::session::{SessionEvent, SessionEventKind, SessionMetrics};
+use crate::analytics::summary::RecordingSummary;
+use crate::analytics::scroll::ScrollDepth;
+use crate::analytics::text_entry::TextEntered;
+use crate::asset_cache::{
+    AssetError, AssetMetadata, AssetMiss, AssetUsageParams, ManifestEntry, MetadataStore, SiteInfo, UrlVersion,
+};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::cluster::ActiveLease;
+use crate::collections::Collection;
+use crate::comments::RecordingComment;
+use crate::clock::ClockSkew;
+use crate::encryption::WrappedKey;
+use crate::health::BackendHealth;
+use crate::jobs::{Job, JobStatus};
+use crate::policy::{MaskingLevel, OriginPolicy, UnsampledAction};
+use crate::provenance::Provenance;
+use crate::recording_meta::RecordingMeta;
+use crate::sites::SiteProfile;
 use chrono::Utc;
-use rusqlite::{params, Connection};
-use std::path::Path;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Semaphore, oneshot};
 use tracing::{debug, info};
 
+/// Connection pool settings for [`SqliteMetadataStore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqliteConfig {
+    /// Most connections open at once (WAL mode lets readers run alongside a writer)
+    pub max_connections: usize,
+    /// How long an operation may wait for a connection plus run, before its
+    /// query is interrupted and it fails
+    pub operation_timeout: Duration,
+    /// How long SQLite itself retries when the database is locked
+    pub busy_timeout: Duration,
+}
+
+impl Default for SqliteConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 4,
+            operation_timeout: Duration::from_secs(5),
+            busy_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Tables keyed by recording id, which follow a recording when it is moved
+/// and go with it when it is deleted
+const RECORDING_TABLES: [&str; 11] = [
+    "recordings",
+    "session_metrics",
+    "session_events",
+    "recording_keys",
+    "recording_comments",
+    "collection_recordings",
+    "recording_assets",
+    "recording_asset_versions",
+    "recording_provenance",
+    "text_entries",
+    "scroll_depths",
+];
+
+/// The origins `?1` is grouped with by a site profile, `?1` included
+const SITE_ORIGINS: &str = "SELECT ?1 \
+    UNION SELECT site FROM site_aliases WHERE alias = ?1 \
+    UNION SELECT alias FROM site_aliases WHERE site = ?1 OR site IN (SELECT site FROM site_aliases WHERE alias = ?1)";
+
 /// SQLite-backed implementation of MetadataStore
+///
+/// Queries run on tokio's blocking thread pool over a small pool of
+/// connections, so a slow query never blocks the async executor. The database
+/// must be a file: each pooled connection opens it separately.
 pub struct SqliteMetadataStore {
-    conn: Arc<Mutex<Connection>>,
+    pool: Arc<ConnectionPool>,
 }
 
 impl SqliteMetadataStore {
@@ -17,18 +86,21 @@ impl SqliteMetadataStore {
     ///
     /// If the database doesn't exist, it will be created with the required schema.
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, AssetError> {
-        let conn = Connection::open(db_path)?;
-        let store = Self {
-            conn: Arc::new(Mutex::new(conn)),
-        };
-        store.init_schema()?;
-        Ok(store)
+        Self::with_config(db_path, SqliteConfig::default())
+    }
+
+    /// Create a store with explicit pool settings
+    pub fn with_config<P: AsRef<Path>>(db_path: P, config: SqliteConfig) -> Result<Self, AssetError> {
+        let pool = ConnectionPool::new(db_path.as_ref().to_path_buf(), config);
+        let conn = pool.open()?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init_schema(&conn)?;
+        pool.release(conn);
+        Ok(Self { pool: Arc::new(pool) })
     }
 
     /// Initialize the database schema
-    fn init_schema(&self) -> Result<(), AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
+    fn init_schema(conn: &Connection) -> Result<(), AssetError> {
         // Assets table: maps SHA-256 (storage key) to random_id (retrieval token)
         conn.execute(
             r#"
@@ -104,10 +176,440 @@ impl SqliteMetadataStore {
             [],
         )?;
 
+        // Listing summary, filled in when ingestion completes (tags as a JSON
+        // array, dropped frame counts and summarized frames as JSON objects)
+        for (column, declaration) in [
+            ("duration_ms", "INTEGER"),
+            ("frame_count", "INTEGER"),
+            ("title", "TEXT"),
+            ("tags", "TEXT"),
+            ("dropped_frames", "TEXT"),
+            ("summarized_frames", "TEXT"),
+            // Estimated from heartbeat round trips while recording
+            ("clock_skew_ms", "INTEGER"),
+            ("clock_round_trip_ms", "INTEGER"),
+            // Set while the recording is on legal hold: it may not be deleted
+            ("legal_hold", "INTEGER NOT NULL DEFAULT 0"),
+            // From the recording's SessionIdentity frames
+            ("anonymous_id", "TEXT"),
+            ("user_id", "TEXT"),
+            ("email_hash", "TEXT"),
+            // As far as the privacy policy keeps it
+            ("client_ip", "TEXT"),
+            // From the recording's RecordingScope frame
+            ("root_selector", "TEXT"),
+            // From the recording's SessionLink frame: the recordings of the
+            // user session's other tabs and windows share it
+            ("session_id", "TEXT"),
+            // RecordingMeta as JSON, cached by GET /recording/{id}/meta
+            ("meta", "TEXT"),
+        ] {
+            Self::add_column_if_missing(conn, "recordings", column, declaration)?;
+        }
+        for column in ["anonymous_id", "user_id", "email_hash", "session_id"] {
+            conn.execute(
+                &format!("CREATE INDEX IF NOT EXISTS idx_recordings_{0} ON recordings({0})", column),
+                [],
+            )?;
+        }
+
+        // Click heatmap table: aggregated click counts per page and grid cell
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS click_heatmap (
+                site_origin TEXT NOT NULL,
+                path TEXT NOT NULL,
+                bucket_x INTEGER NOT NULL,
+                bucket_y INTEGER NOT NULL,
+                click_count INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (site_origin, path, bucket_x, bucket_y)
+            )
+            "#,
+            [],
+        )?;
+
+        // Session metrics table: per-recording analytics summary
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS session_metrics (
+                recording_id TEXT PRIMARY KEY,
+                site_origin TEXT,
+                initial_url TEXT,
+                started_at INTEGER,
+                duration_ms INTEGER NOT NULL,
+                page_count INTEGER NOT NULL,
+                click_count INTEGER NOT NULL,
+                rage_click_count INTEGER NOT NULL,
+                error_count INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_session_metrics_origin ON session_metrics(site_origin, started_at DESC)",
+            [],
+        )?;
+
+        // Session events table: ordered navigation/annotation events for funnels
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS session_events (
+                recording_id TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                offset_ms INTEGER NOT NULL,
+                PRIMARY KEY (recording_id, seq)
+            )
+            "#,
+            [],
+        )?;
+
+        // Recording keys table: per-recording data keys and the master key wrapping each
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS recording_keys (
+                recording_id TEXT PRIMARY KEY,
+                key_id TEXT NOT NULL,
+                wrapped_key BLOB NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_recording_keys_key_id ON recording_keys(key_id)",
+            [],
+        )?;
+
+        // Audit log: holds applied and released, recordings deleted
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at INTEGER NOT NULL,
+                recording_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                actor TEXT,
+                reason TEXT
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_recording ON audit_log(recording_id, id)",
+            [],
+        )?;
+
+        // Recording policies by site origin, `*` for the default
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS origin_policies (
+                origin TEXT PRIMARY KEY,
+                allowed INTEGER NOT NULL,
+                masking TEXT NOT NULL,
+                sample_rate REAL NOT NULL,
+                max_duration_ms INTEGER
+            )
+            "#,
+            [],
+        )?;
+        Self::add_column_if_missing(conn, "origin_policies", "unsampled", "TEXT NOT NULL DEFAULT 'stop'")?;
+
+        // Reviewer comments, anchored by offset into the recording
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS recording_comments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recording_id TEXT NOT NULL,
+                offset_ms INTEGER NOT NULL,
+                author TEXT,
+                text TEXT NOT NULL,
+                created INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_recording_comments_recording ON recording_comments(recording_id, offset_ms)",
+            [],
+        )?;
+
+        // Named collections of recordings and their members
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS collections (
+                name TEXT PRIMARY KEY,
+                description TEXT,
+                created INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS collection_recordings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                collection TEXT NOT NULL,
+                recording_id TEXT NOT NULL,
+                UNIQUE (collection, recording_id)
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_collection_recordings_recording ON collection_recordings(recording_id)",
+            [],
+        )?;
+
+        // Which recordings refer to which cached assets (by random id); an
+        // asset's reference count is its number of rows
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS recording_assets (
+                recording_id TEXT NOT NULL,
+                random_id TEXT NOT NULL,
+                PRIMARY KEY (recording_id, random_id)
+            )
+            "#,
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_recording_assets_random_id ON recording_assets(random_id)",
+            [],
+        )?;
+
+        // The version (random id) of each asset URL a recording played, as
+        // of recording time
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS recording_asset_versions (
+                recording_id TEXT NOT NULL,
+                url TEXT NOT NULL,
+                random_id TEXT NOT NULL,
+                PRIMARY KEY (recording_id, url)
+            )
+            "#,
+            [],
+        )?;
+
+        // One-off data migrations that have run, by name
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS migrations (
+                name TEXT PRIMARY KEY,
+                completed INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        // Cached assets playback found missing, for integrity checks
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS asset_misses (
+                random_id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                miss_count INTEGER NOT NULL,
+                last_missed_at INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        // Site profiles: each alias origin and the site it belongs to
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS site_aliases (
+                alias TEXT PRIMARY KEY,
+                site TEXT NOT NULL
+            )
+            "#,
+            [],
+        )?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_site_aliases_site ON site_aliases(site)", [])?;
+
+        // Leases on the recordings each server is writing (expires_at in ms)
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS active_recordings (
+                recording_id TEXT PRIMARY KEY,
+                node_id TEXT NOT NULL,
+                latest_timestamp INTEGER,
+                expires_at INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+
+        // Background jobs, with their progress as JSON (created_at in ms)
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                finished INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                job TEXT NOT NULL
+            )
+            "#,
+            [],
+        )?;
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_jobs_finished ON jobs(finished, created_at)", [])?;
+
+        // How derived recordings were made, one row per step
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS recording_provenance (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recording_id TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                sources TEXT NOT NULL,
+                tool TEXT NOT NULL,
+                created INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_recording_provenance_recording ON recording_provenance(recording_id, id)",
+            [],
+        )?;
+
+        // Text typed into form fields, one row per visit to a field
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS text_entries (
+                recording_id TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                node_id INTEGER NOT NULL,
+                tag TEXT NOT NULL,
+                field TEXT,
+                value TEXT,
+                keystrokes INTEGER NOT NULL,
+                offset_ms INTEGER NOT NULL,
+                typing_ms INTEGER NOT NULL,
+                PRIMARY KEY (recording_id, seq)
+            )
+            "#,
+            [],
+        )?;
+
+        // Scroll depth table: how far down its page each recording scrolled
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS scroll_depths (
+                recording_id TEXT PRIMARY KEY,
+                site_origin TEXT NOT NULL,
+                path TEXT NOT NULL,
+                viewport_height INTEGER NOT NULL,
+                page_height INTEGER,
+                max_depth_px INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                above_fold_ms INTEGER NOT NULL
+            )
+            "#,
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_scroll_depths_page ON scroll_depths(site_origin, path)",
+            [],
+        )?;
+
         info!("Asset cache database schema initialized");
         Ok(())
     }
 
+    /// Read a `collections` row; members are filled in separately
+    fn collection_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Collection> {
+        Ok(Collection {
+            name: row.get(0)?,
+            description: row.get(1)?,
+            created: chrono::DateTime::from_timestamp_millis(row.get(2)?).unwrap_or_default(),
+            recording_ids: Vec::new(),
+        })
+    }
+
+    /// Recording ids in a collection, in the order they were added
+    fn collection_members(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT recording_id FROM collection_recordings WHERE collection = ?1 ORDER BY id")?;
+        stmt.query_map(params![name], |row| row.get(0))?.collect()
+    }
+
+    /// Read an `origin_policies` row
+    fn origin_policy_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<OriginPolicy> {
+        Ok(OriginPolicy {
+            origin: row.get(0)?,
+            allowed: row.get(1)?,
+            // Levels written by a newer version are treated as the strictest we know
+            masking: MaskingLevel::parse(&row.get::<_, String>(2)?).unwrap_or(MaskingLevel::All),
+            sample_rate: row.get(3)?,
+            max_duration_ms: row.get::<_, Option<i64>>(4)?.map(|ms| ms as u64),
+            unsampled: UnsampledAction::parse(&row.get::<_, String>(5)?).unwrap_or_default(),
+        })
+    }
+
+    /// Read a `url_versions` row whose `sha256_hash` is column `first`, with
+    /// whether its asset is cached
+    fn url_version_from_row(row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<UrlVersion> {
+        // RFC 3339 as written by register_asset_usage, or SQLite's CURRENT_TIMESTAMP
+        let seen_at = |text: String| {
+            chrono::DateTime::parse_from_rfc3339(&text)
+                .map(|at| at.with_timezone(&Utc))
+                .or_else(|_| chrono::NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S").map(|at| at.and_utc()))
+                .unwrap_or_default()
+        };
+        Ok(UrlVersion {
+            sha256_hash: row.get(first)?,
+            first_seen_at: seen_at(row.get(first + 1)?),
+            last_seen_at: seen_at(row.get(first + 2)?),
+            cached: row.get(first + 3)?,
+        })
+    }
+
+    /// Read a `text_entries` row whose `node_id` is column `first`
+    fn text_entry_from_row(row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<TextEntered> {
+        Ok(TextEntered {
+            node_id: row.get(first)?,
+            tag: row.get(first + 1)?,
+            field: row.get(first + 2)?,
+            value: row.get(first + 3)?,
+            keystrokes: row.get(first + 4)?,
+            offset_ms: row.get::<_, i64>(first + 5)? as u64,
+            typing_ms: row.get::<_, i64>(first + 6)? as u64,
+        })
+    }
+
+    /// Whether a recording is on legal hold (recordings without a row are not)
+    fn legal_hold(conn: &Connection, recording_id: &str) -> Result<bool, AssetError> {
+        let held = conn
+            .query_row(
+                "SELECT legal_hold FROM recordings WHERE recording_id = ?1",
+                params![recording_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(held.unwrap_or(false))
+    }
+
+    /// Add a column to a table created by an older version of the schema
+    fn add_column_if_missing(conn: &Connection, table: &str, column: &str, declaration: &str) -> Result<(), AssetError> {
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, declaration), [])?;
+        }
+        Ok(())
+    }
+
     /// Extract the origin from a URL
     fn extract_origin(url: &str) -> Result<String, AssetError> {
         url::Url::parse(url)
@@ -125,6 +627,93 @@ impl SqliteMetadataStore {
     }
 }
 
+/// Replace the rows of `table` keyed by `to` with those keyed by `from`
+fn replace_rows(conn: &Connection, table: &str, from: &str, to: &str) -> rusqlite::Result<()> {
+    conn.execute(&format!("DELETE FROM {} WHERE recording_id = ?1", table), params![to])?;
+    conn.execute(
+        &format!("UPDATE {} SET recording_id = ?2 WHERE recording_id = ?1", table),
+        params![from, to],
+    )?;
+    Ok(())
+}
+
+/// Connections to one database file, handed out to blocking tasks
+///
+/// Connections are opened on demand up to `max_connections`. One that is lost
+/// (its operation panicked) is simply reopened later.
+struct ConnectionPool {
+    path: PathBuf,
+    config: SqliteConfig,
+    idle: Mutex<Vec<Connection>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ConnectionPool {
+    fn new(path: PathBuf, config: SqliteConfig) -> Self {
+        let max_connections = config.max_connections.max(1);
+        Self {
+            path,
+            config,
+            idle: Mutex::new(Vec::with_capacity(max_connections)),
+            permits: Arc::new(Semaphore::new(max_connections)),
+        }
+    }
+
+    fn open(&self) -> Result<Connection, AssetError> {
+        let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(self.config.busy_timeout)?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Ok(conn)
+    }
+
+    fn release(&self, conn: Connection) {
+        self.idle.lock().unwrap().push(conn);
+    }
+
+    /// Run `f` with a pooled connection on the blocking thread pool
+    ///
+    /// An operation still running at the timeout has its query interrupted,
+    /// and the result is only reported once `f` has returned: if it finished
+    /// (and committed) before the interrupt took effect, that result stands.
+    async fn run<T, F>(self: &Arc<Self>, f: F) -> Result<T, AssetError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, AssetError> + Send + 'static,
+    {
+        let timeout = self.config.operation_timeout;
+        let timed_out = || AssetError::Database(format!("SQLite operation timed out after {:?}", timeout));
+        let deadline = tokio::time::Instant::now() + timeout;
+        let permit = tokio::time::timeout_at(deadline, Arc::clone(&self.permits).acquire_owned())
+            .await
+            .map_err(|_| timed_out())?
+            .expect("pool semaphore is never closed");
+        let pool = Arc::clone(self);
+        let (interrupt_sender, interrupt) = oneshot::channel();
+        let mut task = tokio::task::spawn_blocking(move || {
+            // Held until the connection is back in the pool, so no more than
+            // max_connections are ever open
+            let _permit = permit;
+            let idle = pool.idle.lock().unwrap().pop();
+            let mut conn = match idle {
+                Some(conn) => conn,
+                None => pool.open()?,
+            };
+            let _ = interrupt_sender.send(conn.get_interrupt_handle());
+            let result = f(&mut conn);
+            pool.release(conn);
+            result
+        });
+        let failed = |e: tokio::task::JoinError| AssetError::Database(format!("SQLite task failed: {}", e));
+        if let Ok(result) = tokio::time::timeout_at(deadline, &mut task).await {
+            return result.map_err(failed)?;
+        }
+        if let Ok(handle) = interrupt.await {
+            handle.interrupt();
+        }
+        task.await.map_err(failed)?.map_err(|_| timed_out())
+    }
+}
+
 #[async_trait::async_trait]
 impl MetadataStore for SqliteMetadataStore {
     async fn register_recording(
@@ -133,17 +722,17 @@ impl MetadataStore for SqliteMetadataStore {
         initial_url: &str,
     ) -> Result<SiteInfo, AssetError> {
         let origin = Self::extract_origin(initial_url)?;
-        let conn = self.conn.lock().unwrap();
-        
-        conn.execute(
-            "INSERT OR REPLACE INTO recordings (recording_id, site_origin, initial_url) VALUES (?1, ?2, ?3)",
-            params![recording_id, origin, initial_url],
-        )?;
+        let (recording_id, initial_url) = (recording_id.to_string(), initial_url.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO recordings (recording_id, site_origin, initial_url) VALUES (?1, ?2, ?3)",
+                    params![recording_id, origin, initial_url],
+                )?;
 
-        Ok(SiteInfo {
-            origin,
-            initial_url: initial_url.to_string(),
-        })
+                Ok(SiteInfo { origin, initial_url })
+            })
+            .await
     }
 
     async fn get_site_manifest(
@@ -151,150 +740,1654 @@ impl MetadataStore for SqliteMetadataStore {
         site_origin: &str,
         limit: usize,
     ) -> Result<Vec<ManifestEntry>, AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        // Query assets for this site, ordered by usage_count and size
-        // We join with assets table to get the size for sorting
-        let mut stmt = conn.prepare(
-            r#"
-            SELECT sa.url, sa.sha256_hash, a.size
-            FROM site_assets sa
-            JOIN assets a ON sa.sha256_hash = a.sha256_hash
-            WHERE sa.site_origin = ?1
-            ORDER BY sa.usage_count DESC, a.size DESC
-            LIMIT ?2
-            "#,
-        )?;
+        let site_origin = site_origin.to_string();
+        self.pool
+            .run(move |conn| {
+                // Query assets for this site, ordered by usage_count and size
+                // We join with assets table to get the size for sorting
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT sa.url, sa.sha256_hash, a.size
+                    FROM site_assets sa
+                    JOIN assets a ON sa.sha256_hash = a.sha256_hash
+                    WHERE sa.site_origin IN ({SITE_ORIGINS})
+                    GROUP BY sa.url, sa.sha256_hash
+                    ORDER BY SUM(sa.usage_count) DESC, a.size DESC
+                    LIMIT ?2
+                    "#
+                ))?;
 
-        let entries: Vec<ManifestEntry> = stmt
-            .query_map(params![site_origin, limit as i64], |row| {
-                Ok(ManifestEntry {
-                    url: row.get(0)?,
-                    sha256_hash: row.get(1)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+                let entries: Vec<ManifestEntry> = stmt
+                    .query_map(params![site_origin, limit as i64], |row| {
+                        Ok(ManifestEntry {
+                            url: row.get(0)?,
+                            sha256_hash: row.get(1)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
 
-        debug!("Generated manifest for {} with {} entries", site_origin, entries.len());
-        Ok(entries)
+                debug!("Generated manifest for {} with {} entries", site_origin, entries.len());
+                Ok(entries)
+            })
+            .await
     }
 
     async fn resolve_hashes(&self, sha256: &str) -> Result<Option<String>, AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare("SELECT random_id FROM assets WHERE sha256_hash = ?1")?;
-        let mut rows = stmt.query_map(params![sha256], |row| row.get::<_, String>(0))?;
-        
-        match rows.next() {
-            Some(Ok(random_id)) => Ok(Some(random_id)),
-            Some(Err(e)) => Err(AssetError::Database(e.to_string())),
-            None => Ok(None),
-        }
+        let sha256 = sha256.to_string();
+        self.pool
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT random_id FROM assets WHERE sha256_hash = ?1", params![sha256], |row| row.get(0))
+                    .optional()?)
+            })
+            .await
     }
     
     async fn resolve_random_id(&self, random_id: &str) -> Result<Option<String>, AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare("SELECT sha256_hash FROM assets WHERE random_id = ?1")?;
-        let mut rows = stmt.query_map(params![random_id], |row| row.get::<_, String>(0))?;
-        
-        match rows.next() {
-            Some(Ok(sha256)) => Ok(Some(sha256)),
-            Some(Err(e)) => Err(AssetError::Database(e.to_string())),
-            None => Ok(None),
-        }
+        let random_id = random_id.to_string();
+        self.pool
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT sha256_hash FROM assets WHERE random_id = ?1", params![random_id], |row| row.get(0))
+                    .optional()?)
+            })
+            .await
     }
 
     async fn register_asset_usage(&self, params: AssetUsageParams) -> Result<(), AssetError> {
-        let conn = self.conn.lock().unwrap();
-        let now = Utc::now().to_rfc3339();
-        
-        // Update site-specific asset usage
-        conn.execute(
-            r#"
-            INSERT INTO site_assets (site_origin, url, sha256_hash, usage_count, last_seen_at)
-            VALUES (?1, ?2, ?3, 1, ?4)
-            ON CONFLICT(site_origin, url, sha256_hash) DO UPDATE SET
-                usage_count = usage_count + 1,
-                last_seen_at = ?4
-            "#,
-            params![
-                params.site_origin,
-                params.url,
-                params.sha256_hash,
-                now
-            ],
-        )?;
+        self.pool
+            .run(move |conn| {
+                let now = Utc::now().to_rfc3339();
+                let tx = conn.transaction()?;
 
-        // Also track URL version globally (for version detection and stability analysis)
-        conn.execute(
-            r#"
-            INSERT INTO url_versions (url, sha256_hash, first_seen_at, last_seen_at)
-            VALUES (?1, ?2, ?3, ?3)
-            ON CONFLICT(url, sha256_hash) DO UPDATE SET
-                last_seen_at = ?3
-            "#,
-            params![
-                params.url,
-                params.sha256_hash,
-                now
-            ],
-        )?;
+                // Update site-specific asset usage
+                tx.execute(
+                    r#"
+                    INSERT INTO site_assets (site_origin, url, sha256_hash, usage_count, last_seen_at)
+                    VALUES (?1, ?2, ?3, 1, ?4)
+                    ON CONFLICT(site_origin, url, sha256_hash) DO UPDATE SET
+                        usage_count = usage_count + 1,
+                        last_seen_at = ?4
+                    "#,
+                    params![
+                        params.site_origin,
+                        params.url,
+                        params.sha256_hash,
+                        now
+                    ],
+                )?;
 
-        Ok(())
+                // Also track URL version globally (for version detection and stability analysis)
+                tx.execute(
+                    r#"
+                    INSERT INTO url_versions (url, sha256_hash, first_seen_at, last_seen_at)
+                    VALUES (?1, ?2, ?3, ?3)
+                    ON CONFLICT(url, sha256_hash) DO UPDATE SET
+                        last_seen_at = ?3
+                    "#,
+                    params![
+                        params.url,
+                        params.sha256_hash,
+                        now
+                    ],
+                )?;
+
+                tx.commit()?;
+                Ok(())
+            })
+            .await
     }
 
     async fn store_asset_metadata(&self, metadata: AssetMetadata) -> Result<(), AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        conn.execute(
-            r#"
-            INSERT OR REPLACE INTO assets (sha256_hash, random_id, size, mime_type, created_at)
-            VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
-            "#,
-            params![
-                metadata.sha256_hash,
-                metadata.random_id,
-                metadata.size as i64,
-                metadata.mime_type
-            ],
-        )?;
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT OR REPLACE INTO assets (sha256_hash, random_id, size, mime_type, created_at)
+                    VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+                    "#,
+                    params![
+                        metadata.sha256_hash,
+                        metadata.random_id,
+                        metadata.size as i64,
+                        metadata.mime_type
+                    ],
+                )?;
 
-        debug!(
-            "Stored asset metadata: sha256={}, random_id={}, size={}",
-            &metadata.sha256_hash[..16], &metadata.random_id[..16], metadata.size
-        );
-        Ok(())
+                debug!(
+                    "Stored asset metadata: sha256={}, random_id={}, size={}",
+                    &metadata.sha256_hash[..16], &metadata.random_id[..16], metadata.size
+                );
+                Ok(())
+            })
+            .await
     }
 
     async fn get_asset_metadata(&self, random_id: &str) -> Result<Option<(String, u64)>, AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare("SELECT mime_type, size FROM assets WHERE random_id = ?1")?;
-        let mut rows = stmt.query_map(params![random_id], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
-        })?;
-        
-        match rows.next() {
-            Some(Ok(metadata)) => Ok(Some(metadata)),
-            Some(Err(e)) => Err(AssetError::Database(e.to_string())),
-            None => Ok(None),
-        }
+        let random_id = random_id.to_string();
+        self.pool
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT mime_type, size FROM assets WHERE random_id = ?1", params![random_id], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+                    })
+                    .optional()?)
+            })
+            .await
     }
     
     async fn get_asset_mime_type(&self, random_id: &str) -> Result<Option<String>, AssetError> {
-        let conn = self.conn.lock().unwrap();
-        
-        let mut stmt = conn.prepare("SELECT mime_type FROM assets WHERE random_id = ?1")?;
-        let mut rows = stmt.query_map(params![random_id], |row| {
-            Ok(row.get::<_, String>(0)?)
-        })?;
-        
-        match rows.next() {
-            Some(Ok(mime_type)) => Ok(Some(mime_type)),
-            Some(Err(e)) => Err(AssetError::Database(e.to_string())),
-            None => Ok(None),
-        }
+        let random_id = random_id.to_string();
+        self.pool
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT mime_type FROM assets WHERE random_id = ?1", params![random_id], |row| row.get(0))
+                    .optional()?)
+            })
+            .await
+    }
+
+    async fn get_url_versions(&self, url: &str) -> Result<Vec<UrlVersion>, AssetError> {
+        let url = url.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT uv.sha256_hash, uv.first_seen_at, uv.last_seen_at, a.sha256_hash IS NOT NULL
+                    FROM url_versions uv
+                    LEFT JOIN assets a ON a.sha256_hash = uv.sha256_hash
+                    WHERE uv.url = ?1
+                    ORDER BY uv.last_seen_at DESC
+                    "#,
+                )?;
+                let versions = stmt
+                    .query_map(params![url], |row| Self::url_version_from_row(row, 0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(versions)
+            })
+            .await
+    }
+
+    async fn get_site_url_versions(&self, site_origin: &str) -> Result<Vec<(String, UrlVersion)>, AssetError> {
+        let site_origin = site_origin.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT uv.url, uv.sha256_hash, uv.first_seen_at, uv.last_seen_at, a.sha256_hash IS NOT NULL
+                    FROM url_versions uv
+                    JOIN (SELECT DISTINCT url FROM site_assets WHERE site_origin = ?1) sa ON sa.url = uv.url
+                    LEFT JOIN assets a ON a.sha256_hash = uv.sha256_hash
+                    "#,
+                )?;
+                let versions = stmt
+                    .query_map(params![site_origin], |row| {
+                        Ok((row.get(0)?, Self::url_version_from_row(row, 1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(versions)
+            })
+            .await
+    }
+
+    async fn record_asset_miss(&self, random_id: &str, url: &str) -> Result<(), AssetError> {
+        let (random_id, url) = (random_id.to_string(), url.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO asset_misses (random_id, url, miss_count, last_missed_at)
+                    VALUES (?1, ?2, 1, ?3)
+                    ON CONFLICT(random_id) DO UPDATE SET
+                        url = excluded.url,
+                        miss_count = miss_count + 1,
+                        last_missed_at = excluded.last_missed_at
+                    "#,
+                    params![random_id, url, Utc::now().timestamp_millis()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_asset_misses(&self) -> Result<Vec<AssetMiss>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT random_id, url, miss_count, last_missed_at FROM asset_misses ORDER BY last_missed_at DESC",
+                )?;
+                let misses = stmt
+                    .query_map([], |row| {
+                        Ok(AssetMiss {
+                            random_id: row.get(0)?,
+                            url: row.get(1)?,
+                            count: row.get::<_, i64>(2)? as u64,
+                            last_missed_at: chrono::DateTime::from_timestamp_millis(row.get(3)?).unwrap_or_default(),
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(misses)
+            })
+            .await
+    }
+
+    async fn record_click_buckets(
+        &self,
+        site_origin: &str,
+        path: &str,
+        buckets: &[HeatmapBucket],
+    ) -> Result<(), AssetError> {
+        let (site_origin, path, buckets) = (site_origin.to_string(), path.to_string(), buckets.to_vec());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        r#"
+                        INSERT INTO click_heatmap (site_origin, path, bucket_x, bucket_y, click_count)
+                        VALUES (?1, ?2, ?3, ?4, ?5)
+                        ON CONFLICT(site_origin, path, bucket_x, bucket_y) DO UPDATE SET
+                            click_count = click_count + excluded.click_count
+                        "#,
+                    )?;
+                    for bucket in &buckets {
+                        stmt.execute(params![site_origin, path, bucket.x, bucket.y, bucket.count as i64])?;
+                    }
+                }
+                tx.commit()?;
+
+                debug!("Recorded {} heatmap buckets for {}{}", buckets.len(), site_origin, path);
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_click_heatmap(&self, site_origin: &str, path: &str) -> Result<Vec<HeatmapBucket>, AssetError> {
+        let (site_origin, path) = (site_origin.to_string(), path.to_string());
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT bucket_x, bucket_y, SUM(click_count) FROM click_heatmap
+                    WHERE site_origin IN ({SITE_ORIGINS}) AND path = ?2
+                    GROUP BY bucket_x, bucket_y
+                    ORDER BY bucket_y, bucket_x
+                    "#
+                ))?;
+                let buckets = stmt
+                    .query_map(params![site_origin, path], |row| {
+                        Ok(HeatmapBucket {
+                            x: row.get(0)?,
+                            y: row.get(1)?,
+                            count: row.get::<_, i64>(2)? as u64,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(buckets)
+            })
+            .await
+    }
+
+    async fn store_session_metrics(
+        &self,
+        metrics: &SessionMetrics,
+        events: &[SessionEvent],
+    ) -> Result<(), AssetError> {
+        let (metrics, events) = (metrics.clone(), events.to_vec());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+
+                tx.execute(
+                    r#"
+                    INSERT OR REPLACE INTO session_metrics
+                        (recording_id, site_origin, initial_url, started_at, duration_ms,
+                         page_count, click_count, rage_click_count, error_count)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                    "#,
+                    params![
+                        metrics.recording_id,
+                        metrics.site_origin,
+                        metrics.initial_url,
+                        metrics.started_at.map(|t| t as i64),
+                        metrics.duration_ms as i64,
+                        metrics.page_count,
+                        metrics.click_count,
+                        metrics.rage_click_count,
+                        metrics.error_count,
+                    ],
+                )?;
+                tx.execute(
+                    "DELETE FROM session_events WHERE recording_id = ?1",
+                    params![metrics.recording_id],
+                )?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO session_events (recording_id, seq, kind, name, offset_ms) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    )?;
+                    for (seq, event) in events.iter().enumerate() {
+                        stmt.execute(params![
+                            metrics.recording_id,
+                            seq as i64,
+                            event.kind.as_str(),
+                            event.name,
+                            event.offset_ms as i64,
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+
+                debug!("Stored session metrics for {}", metrics.recording_id);
+                Ok(())
+            })
+            .await
+    }
+
+    async fn store_recording_summary(&self, summary: &RecordingSummary) -> Result<(), AssetError> {
+        let summary = summary.clone();
+        self.pool
+            .run(move |conn| {
+                let tags = serde_json::to_string(&summary.tags).map_err(|e| AssetError::Database(e.to_string()))?;
+                let dropped_frames = serde_json::to_string(&summary.dropped_frames)
+                    .map_err(|e| AssetError::Database(e.to_string()))?;
+                let summarized_frames = serde_json::to_string(&summary.summarized_frames)
+                    .map_err(|e| AssetError::Database(e.to_string()))?;
+                // Keep the origin and URL from registration if ingestion found none
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings
+                        (recording_id, site_origin, initial_url, duration_ms, frame_count, title, tags,
+                         anonymous_id, user_id, email_hash, dropped_frames, summarized_frames)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                    ON CONFLICT(recording_id) DO UPDATE SET
+                        site_origin = COALESCE(NULLIF(excluded.site_origin, ''), recordings.site_origin),
+                        initial_url = COALESCE(NULLIF(excluded.initial_url, ''), recordings.initial_url),
+                        duration_ms = excluded.duration_ms,
+                        frame_count = excluded.frame_count,
+                        title = excluded.title,
+                        tags = excluded.tags,
+                        anonymous_id = excluded.anonymous_id,
+                        user_id = excluded.user_id,
+                        email_hash = excluded.email_hash,
+                        dropped_frames = excluded.dropped_frames,
+                        summarized_frames = excluded.summarized_frames
+                    "#,
+                    params![
+                        summary.recording_id,
+                        summary.site_origin.unwrap_or_default(),
+                        summary.initial_url.unwrap_or_default(),
+                        summary.duration_ms as i64,
+                        summary.frame_count as i64,
+                        summary.title,
+                        tags,
+                        summary.anonymous_id,
+                        summary.user_id,
+                        summary.email_hash,
+                        dropped_frames,
+                        summarized_frames,
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn store_clock_skew(&self, recording_id: &str, skew: ClockSkew) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, clock_skew_ms, clock_round_trip_ms)
+                    VALUES (?1, '', '', ?2, ?3)
+                    ON CONFLICT(recording_id) DO UPDATE SET
+                        clock_skew_ms = excluded.clock_skew_ms,
+                        clock_round_trip_ms = excluded.clock_round_trip_ms
+                    "#,
+                    params![recording_id, skew.skew_ms, skew.round_trip_ms as i64],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_clock_skew(&self, recording_id: &str) -> Result<Option<ClockSkew>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let skew = conn
+                    .query_row(
+                        r#"
+                        SELECT clock_skew_ms, clock_round_trip_ms FROM recordings
+                        WHERE recording_id = ?1 AND clock_skew_ms IS NOT NULL
+                        "#,
+                        params![recording_id],
+                        |row| {
+                            Ok(ClockSkew {
+                                skew_ms: row.get(0)?,
+                                round_trip_ms: row.get::<_, i64>(1)? as u64,
+                            })
+                        },
+                    )
+                    .optional()?;
+                Ok(skew)
+            })
+            .await
+    }
+
+    async fn store_recording_key(&self, recording_id: &str, key: &WrappedKey) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        let key = key.clone();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO recording_keys (recording_id, key_id, wrapped_key) VALUES (?1, ?2, ?3)",
+                    params![recording_id, key.key_id, key.wrapped],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_recording_key(&self, recording_id: &str) -> Result<Option<WrappedKey>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let key = conn
+                    .query_row(
+                        "SELECT key_id, wrapped_key FROM recording_keys WHERE recording_id = ?1",
+                        params![recording_id],
+                        |row| {
+                            Ok(WrappedKey {
+                                key_id: row.get(0)?,
+                                wrapped: row.get(1)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                Ok(key)
+            })
+            .await
+    }
+
+    async fn delete_recording_key(&self, recording_id: &str) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                conn.execute("DELETE FROM recording_keys WHERE recording_id = ?1", params![recording_id])?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_recording_keys_not_wrapped_by(&self, key_id: &str) -> Result<Vec<(String, WrappedKey)>, AssetError> {
+        let key_id = key_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT recording_id, key_id, wrapped_key FROM recording_keys WHERE key_id != ?1 ORDER BY recording_id",
+                )?;
+                let keys = stmt
+                    .query_map(params![key_id], |row| {
+                        Ok((
+                            row.get(0)?,
+                            WrappedKey {
+                                key_id: row.get(1)?,
+                                wrapped: row.get(2)?,
+                            },
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(keys)
+            })
+            .await
+    }
+
+    async fn set_legal_hold(&self, recording_id: &str, held: bool) -> Result<bool, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                if Self::legal_hold(&tx, &recording_id)? == held {
+                    return Ok(false);
+                }
+                tx.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, legal_hold)
+                    VALUES (?1, '', '', ?2)
+                    ON CONFLICT(recording_id) DO UPDATE SET legal_hold = excluded.legal_hold
+                    "#,
+                    params![recording_id, held],
+                )?;
+                tx.commit()?;
+                Ok(true)
+            })
+            .await
+    }
+
+    async fn is_on_legal_hold(&self, recording_id: &str) -> Result<bool, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool.run(move |conn| Self::legal_hold(conn, &recording_id)).await
+    }
+
+    async fn list_legal_holds(&self) -> Result<Vec<String>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare("SELECT recording_id FROM recordings WHERE legal_hold != 0 ORDER BY recording_id")?;
+                let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+                Ok(ids)
+            })
+            .await
+    }
+
+    async fn append_audit_event(&self, event: &AuditEvent) -> Result<(), AssetError> {
+        let event = event.clone();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT INTO audit_log (at, recording_id, action, actor, reason) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        event.at.timestamp_millis(),
+                        event.recording_id,
+                        event.action.as_str(),
+                        event.actor,
+                        event.reason
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_audit_events(&self, recording_id: Option<&str>) -> Result<Vec<AuditEvent>, AssetError> {
+        let recording_id = recording_id.map(str::to_string);
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT at, recording_id, action, actor, reason FROM audit_log
+                    WHERE ?1 IS NULL OR recording_id = ?1
+                    ORDER BY id
+                    "#,
+                )?;
+                let rows = stmt.query_map(params![recording_id], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })?;
+
+                let mut events = Vec::new();
+                for row in rows {
+                    let (at, recording_id, action, actor, reason) = row?;
+                    // Entries written by a newer version with actions we don't know are skipped
+                    let (Some(at), Some(action)) = (chrono::DateTime::from_timestamp_millis(at), AuditAction::parse(&action))
+                    else {
+                        continue;
+                    };
+                    events.push(AuditEvent {
+                        at,
+                        recording_id,
+                        action,
+                        actor,
+                        reason,
+                    });
+                }
+                Ok(events)
+            })
+            .await
+    }
+
+    async fn add_comment(&self, comment: &RecordingComment) -> Result<i64, AssetError> {
+        let comment = comment.clone();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recording_comments (recording_id, offset_ms, author, text, created)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                    params![
+                        comment.recording_id,
+                        comment.offset_ms as i64,
+                        comment.author,
+                        comment.text,
+                        comment.created.timestamp_millis()
+                    ],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await
+    }
+
+    async fn list_comments(&self, recording_id: &str) -> Result<Vec<RecordingComment>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT id, recording_id, offset_ms, author, text, created FROM recording_comments
+                    WHERE recording_id = ?1
+                    ORDER BY offset_ms, id
+                    "#,
+                )?;
+                let rows = stmt.query_map(params![recording_id], |row| {
+                    Ok(RecordingComment {
+                        id: row.get(0)?,
+                        recording_id: row.get(1)?,
+                        offset_ms: row.get::<_, i64>(2)? as u64,
+                        author: row.get(3)?,
+                        text: row.get(4)?,
+                        created: chrono::DateTime::from_timestamp_millis(row.get(5)?).unwrap_or_default(),
+                    })
+                })?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await
+    }
+
+    async fn save_collection(&self, name: &str, description: Option<&str>) -> Result<(), AssetError> {
+        let (name, description) = (name.to_string(), description.map(str::to_string));
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO collections (name, description, created) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(name) DO UPDATE SET description = excluded.description
+                    "#,
+                    params![name, description, chrono::Utc::now().timestamp_millis()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_collection(&self, name: &str) -> Result<Option<Collection>, AssetError> {
+        let name = name.to_string();
+        self.pool
+            .run(move |conn| {
+                let Some(mut collection) = conn
+                    .query_row(
+                        "SELECT name, description, created FROM collections WHERE name = ?1",
+                        params![name],
+                        Self::collection_from_row,
+                    )
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+                collection.recording_ids = Self::collection_members(conn, &name)?;
+                Ok(Some(collection))
+            })
+            .await
+    }
+
+    async fn list_collections(&self) -> Result<Vec<Collection>, AssetError> {
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare("SELECT name, description, created FROM collections ORDER BY name")?;
+                let mut collections = stmt.query_map([], Self::collection_from_row)?.collect::<Result<Vec<_>, _>>()?;
+                for collection in &mut collections {
+                    collection.recording_ids = Self::collection_members(conn, &collection.name)?;
+                }
+                Ok(collections)
+            })
+            .await
+    }
+
+    async fn delete_collection(&self, name: &str) -> Result<bool, AssetError> {
+        let name = name.to_string();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM collection_recordings WHERE collection = ?1", params![name])?;
+                let deleted = tx.execute("DELETE FROM collections WHERE name = ?1", params![name])?;
+                tx.commit()?;
+                Ok(deleted > 0)
+            })
+            .await
+    }
+
+    async fn set_collection_member(&self, name: &str, recording_id: &str, member: bool) -> Result<bool, AssetError> {
+        let (name, recording_id) = (name.to_string(), recording_id.to_string());
+        self.pool
+            .run(move |conn| {
+                let changed = if member {
+                    conn.execute(
+                        "INSERT OR IGNORE INTO collection_recordings (collection, recording_id) VALUES (?1, ?2)",
+                        params![name, recording_id],
+                    )?
+                } else {
+                    conn.execute(
+                        "DELETE FROM collection_recordings WHERE collection = ?1 AND recording_id = ?2",
+                        params![name, recording_id],
+                    )?
+                };
+                Ok(changed > 0)
+            })
+            .await
+    }
+
+    async fn store_client_ip(&self, recording_id: &str, client_ip: &str) -> Result<(), AssetError> {
+        let (recording_id, client_ip) = (recording_id.to_string(), client_ip.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, client_ip)
+                    VALUES (?1, '', '', ?2)
+                    ON CONFLICT(recording_id) DO UPDATE SET client_ip = excluded.client_ip
+                    "#,
+                    params![recording_id, client_ip],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn store_recording_scope(&self, recording_id: &str, root_selector: &str) -> Result<(), AssetError> {
+        let (recording_id, root_selector) = (recording_id.to_string(), root_selector.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, root_selector)
+                    VALUES (?1, '', '', ?2)
+                    ON CONFLICT(recording_id) DO UPDATE SET root_selector = excluded.root_selector
+                    "#,
+                    params![recording_id, root_selector],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_recording_scope(&self, recording_id: &str) -> Result<Option<String>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let root_selector = conn
+                    .query_row(
+                        "SELECT root_selector FROM recordings WHERE recording_id = ?1",
+                        params![recording_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                Ok(root_selector.flatten())
+            })
+            .await
+    }
+
+    async fn store_session_link(&self, recording_id: &str, session_id: &str) -> Result<(), AssetError> {
+        let (recording_id, session_id) = (recording_id.to_string(), session_id.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, session_id)
+                    VALUES (?1, '', '', ?2)
+                    ON CONFLICT(recording_id) DO UPDATE SET session_id = excluded.session_id
+                    "#,
+                    params![recording_id, session_id],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_session_recordings(&self, session_id: &str) -> Result<Vec<String>, AssetError> {
+        let session_id = session_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT recording_id FROM recordings WHERE session_id = ?1 ORDER BY recording_id")?;
+                let ids = stmt.query_map(params![session_id], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+                Ok(ids)
+            })
+            .await
+    }
+
+    async fn store_recording_meta(&self, recording_id: &str, meta: &RecordingMeta) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        let meta = serde_json::to_string(meta).map_err(|e| AssetError::Database(e.to_string()))?;
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recordings (recording_id, site_origin, initial_url, meta)
+                    VALUES (?1, '', '', ?2)
+                    ON CONFLICT(recording_id) DO UPDATE SET meta = excluded.meta
+                    "#,
+                    params![recording_id, meta],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_recording_meta(&self, recording_id: &str) -> Result<Option<RecordingMeta>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let meta: Option<Option<String>> = conn
+                    .query_row(
+                        "SELECT meta FROM recordings WHERE recording_id = ?1",
+                        params![recording_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                meta.flatten()
+                    .map(|json| serde_json::from_str(&json).map_err(|e| AssetError::Database(e.to_string())))
+                    .transpose()
+            })
+            .await
+    }
+
+    async fn find_recordings_by_subject(&self, subject: &str) -> Result<Vec<String>, AssetError> {
+        let subject = subject.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT recording_id FROM recordings
+                    WHERE user_id = ?1 OR email_hash = ?1 OR anonymous_id = ?1
+                       OR (tags IS NOT NULL AND EXISTS (SELECT 1 FROM json_each(recordings.tags) WHERE value = ?1))
+                    ORDER BY recording_id
+                    "#,
+                )?;
+                let ids = stmt.query_map(params![subject], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+                Ok(ids)
+            })
+            .await
+    }
+
+    async fn rename_recording_metadata(&self, recording_id: &str, new_id: &str) -> Result<(), AssetError> {
+        let (recording_id, new_id) = (recording_id.to_string(), new_id.to_string());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                for table in RECORDING_TABLES {
+                    tx.execute(
+                        &format!("UPDATE {} SET recording_id = ?2 WHERE recording_id = ?1", table),
+                        params![recording_id, new_id],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn replace_recording_metadata(&self, from: &str, recording_id: &str) -> Result<(), AssetError> {
+        let (from, recording_id) = (from.to_string(), recording_id.to_string());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                for table in RECORDING_TABLES {
+                    replace_rows(&tx, table, &from, &recording_id)?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn replace_recording_key(&self, from: &str, recording_id: &str) -> Result<(), AssetError> {
+        let (from, recording_id) = (from.to_string(), recording_id.to_string());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                replace_rows(&tx, "recording_keys", &from, &recording_id)?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn delete_recording_metadata(&self, recording_id: &str) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                for table in RECORDING_TABLES {
+                    tx.execute(&format!("DELETE FROM {} WHERE recording_id = ?1", table), params![recording_id])?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn delete_asset(&self, random_id: &str) -> Result<Option<String>, AssetError> {
+        let random_id = random_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let sha256: Option<String> = tx
+                    .query_row("SELECT sha256_hash FROM assets WHERE random_id = ?1", params![random_id], |row| {
+                        row.get(0)
+                    })
+                    .optional()?;
+                let Some(sha256) = sha256 else {
+                    return Ok(None);
+                };
+                for table in ["assets", "site_assets", "url_versions"] {
+                    tx.execute(&format!("DELETE FROM {} WHERE sha256_hash = ?1", table), params![sha256])?;
+                }
+                tx.commit()?;
+                Ok(Some(sha256))
+            })
+            .await
+    }
+
+    async fn add_asset_references(&self, recording_id: &str, random_ids: &[String]) -> Result<(), AssetError> {
+        let (recording_id, random_ids) = (recording_id.to_string(), random_ids.to_vec());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt =
+                        tx.prepare("INSERT OR IGNORE INTO recording_assets (recording_id, random_id) VALUES (?1, ?2)")?;
+                    for random_id in &random_ids {
+                        stmt.execute(params![recording_id, random_id])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn pin_asset_version(&self, recording_id: &str, url: &str, random_id: &str) -> Result<(), AssetError> {
+        let (recording_id, url, random_id) = (recording_id.to_string(), url.to_string(), random_id.to_string());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recording_asset_versions (recording_id, url, random_id)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT(recording_id, url) DO UPDATE SET random_id = ?3
+                    "#,
+                    params![recording_id, url, random_id],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_pinned_asset_versions(&self, recording_id: &str) -> Result<HashMap<String, String>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare("SELECT url, random_id FROM recording_asset_versions WHERE recording_id = ?1")?;
+                let pins = stmt
+                    .query_map(params![recording_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<HashMap<_, _>, _>>()?;
+                Ok(pins)
+            })
+            .await
+    }
+
+    async fn unreferenced_assets(&self, random_ids: Option<&[String]>) -> Result<Vec<String>, AssetError> {
+        let random_ids = random_ids.map(<[String]>::to_vec);
+        self.pool
+            .run(move |conn| {
+                let unreferenced = "NOT EXISTS (SELECT 1 FROM recording_assets WHERE recording_assets.random_id = assets.random_id)";
+                let Some(random_ids) = random_ids else {
+                    let mut stmt =
+                        conn.prepare(&format!("SELECT random_id FROM assets WHERE {} ORDER BY random_id", unreferenced))?;
+                    let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+                    return Ok(ids);
+                };
+                let mut found = Vec::new();
+                // Stay well under SQLite's bound parameter limit
+                for ids in random_ids.chunks(500) {
+                    let placeholders = vec!["?"; ids.len()].join(", ");
+                    let mut stmt = conn.prepare(&format!(
+                        "SELECT random_id FROM assets WHERE random_id IN ({}) AND {} ORDER BY random_id",
+                        placeholders, unreferenced
+                    ))?;
+                    let rows = stmt.query_map(rusqlite::params_from_iter(ids), |row| row.get(0))?;
+                    for row in rows {
+                        found.push(row?);
+                    }
+                }
+                Ok(found)
+            })
+            .await
+    }
+
+    async fn list_asset_hashes(&self) -> Result<Vec<(String, String)>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare("SELECT sha256_hash, mime_type FROM assets ORDER BY sha256_hash")?;
+                let assets = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(assets)
+            })
+            .await
+    }
+
+    async fn claim_active_recording(&self, lease: &ActiveLease) -> Result<bool, AssetError> {
+        let lease = lease.clone();
+        self.pool
+            .run(move |conn| {
+                let claimed = conn.execute(
+                    r#"
+                    INSERT INTO active_recordings (recording_id, node_id, latest_timestamp, expires_at)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT(recording_id) DO UPDATE SET
+                        node_id = excluded.node_id,
+                        latest_timestamp = excluded.latest_timestamp,
+                        expires_at = excluded.expires_at
+                    WHERE active_recordings.node_id = excluded.node_id OR active_recordings.expires_at <= ?5
+                    "#,
+                    params![
+                        lease.recording_id,
+                        lease.node_id,
+                        lease.latest_timestamp.map(|timestamp| timestamp as i64),
+                        lease.expires_at.timestamp_millis(),
+                        Utc::now().timestamp_millis()
+                    ],
+                )?;
+                Ok(claimed > 0)
+            })
+            .await
+    }
+
+    async fn release_active_recording(&self, recording_id: &str, node_id: &str) -> Result<(), AssetError> {
+        let recording_id = recording_id.to_string();
+        let node_id = node_id.to_string();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    "DELETE FROM active_recordings WHERE recording_id = ?1 AND node_id = ?2",
+                    params![recording_id, node_id],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_active_recordings(&self) -> Result<Vec<ActiveLease>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT recording_id, node_id, latest_timestamp, expires_at FROM active_recordings
+                     WHERE expires_at > ?1 ORDER BY recording_id",
+                )?;
+                let leases = stmt
+                    .query_map(params![Utc::now().timestamp_millis()], |row| {
+                        Ok(ActiveLease {
+                            recording_id: row.get(0)?,
+                            node_id: row.get(1)?,
+                            latest_timestamp: row.get::<_, Option<i64>>(2)?.map(|timestamp| timestamp as u64),
+                            expires_at: chrono::DateTime::from_timestamp_millis(row.get(3)?).unwrap_or_default(),
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(leases)
+            })
+            .await
+    }
+
+    async fn save_job(&self, job: &Job) -> Result<(), AssetError> {
+        let json = serde_json::to_string(job).map_err(|e| AssetError::Database(e.to_string()))?;
+        let (id, finished, created_at) = (
+            job.id.clone(),
+            job.status == JobStatus::Finished,
+            job.created.timestamp_millis(),
+        );
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO jobs (id, finished, created_at, job) VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT(id) DO UPDATE SET finished = excluded.finished, job = excluded.job
+                    "#,
+                    params![id, finished, created_at, json],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_job(&self, id: &str) -> Result<Option<Job>, AssetError> {
+        let id = id.to_string();
+        let json: Option<String> = self
+            .pool
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT job FROM jobs WHERE id = ?1", params![id], |row| row.get(0))
+                    .optional()?)
+            })
+            .await?;
+        json.map(|json| serde_json::from_str(&json).map_err(|e| AssetError::Database(e.to_string())))
+            .transpose()
+    }
+
+    async fn list_unfinished_jobs(&self) -> Result<Vec<Job>, AssetError> {
+        let jobs: Vec<String> = self
+            .pool
+            .run(|conn| {
+                let mut stmt = conn.prepare("SELECT job FROM jobs WHERE finished = 0 ORDER BY created_at")?;
+                let jobs = stmt.query_map([], |row| row.get(0))?.collect::<Result<Vec<_>, _>>()?;
+                Ok(jobs)
+            })
+            .await?;
+        jobs.iter()
+            .map(|json| serde_json::from_str(json).map_err(|e| AssetError::Database(e.to_string())))
+            .collect()
+    }
+
+    async fn prune_finished_jobs(&self, keep: usize) -> Result<(), AssetError> {
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    DELETE FROM jobs WHERE finished = 1 AND id NOT IN (
+                        SELECT id FROM jobs WHERE finished = 1 ORDER BY created_at DESC LIMIT ?1
+                    )
+                    "#,
+                    params![keep as i64],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn add_recording_provenance(&self, recording_id: &str, provenance: &Provenance) -> Result<(), AssetError> {
+        let sources = serde_json::to_string(&provenance.sources).map_err(|e| AssetError::Database(e.to_string()))?;
+        let (recording_id, provenance) = (recording_id.to_string(), provenance.clone());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO recording_provenance (recording_id, operation, sources, tool, created)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                    params![
+                        recording_id,
+                        provenance.operation,
+                        sources,
+                        provenance.tool,
+                        provenance.created.timestamp_millis()
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_recording_provenance(&self, recording_id: &str) -> Result<Vec<Provenance>, AssetError> {
+        let recording_id = recording_id.to_string();
+        let rows: Vec<(String, String, String, i64)> = self
+            .pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT operation, sources, tool, created FROM recording_provenance
+                    WHERE recording_id = ?1
+                    ORDER BY id
+                    "#,
+                )?;
+                let rows = stmt
+                    .query_map(params![recording_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+        rows.into_iter()
+            .map(|(operation, sources, tool, created)| {
+                Ok(Provenance {
+                    operation,
+                    sources: serde_json::from_str(&sources).map_err(|e| AssetError::Database(e.to_string()))?,
+                    tool,
+                    created: chrono::DateTime::from_timestamp_millis(created).unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    async fn store_text_entries(&self, recording_id: &str, entries: &[TextEntered]) -> Result<(), AssetError> {
+        let (recording_id, entries) = (recording_id.to_string(), entries.to_vec());
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM text_entries WHERE recording_id = ?1", params![recording_id])?;
+                {
+                    let mut stmt = tx.prepare(
+                        r#"
+                        INSERT INTO text_entries
+                            (recording_id, seq, node_id, tag, field, value, keystrokes, offset_ms, typing_ms)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                        "#,
+                    )?;
+                    for (seq, entry) in entries.iter().enumerate() {
+                        stmt.execute(params![
+                            recording_id,
+                            seq as i64,
+                            entry.node_id,
+                            entry.tag,
+                            entry.field,
+                            entry.value,
+                            entry.keystrokes,
+                            entry.offset_ms as i64,
+                            entry.typing_ms as i64,
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_text_entries(&self, recording_id: &str) -> Result<Vec<TextEntered>, AssetError> {
+        let recording_id = recording_id.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT node_id, tag, field, value, keystrokes, offset_ms, typing_ms FROM text_entries
+                    WHERE recording_id = ?1
+                    ORDER BY seq
+                    "#,
+                )?;
+                let entries = stmt
+                    .query_map(params![recording_id], |row| Self::text_entry_from_row(row, 0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(entries)
+            })
+            .await
+    }
+
+    async fn search_text_entries(
+        &self,
+        site_origin: &str,
+        contains: Option<&str>,
+    ) -> Result<Vec<(String, TextEntered)>, AssetError> {
+        let (site_origin, contains) = (site_origin.to_string(), contains.map(str::to_string));
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT t.recording_id, t.node_id, t.tag, t.field, t.value, t.keystrokes, t.offset_ms, t.typing_ms
+                    FROM text_entries t
+                    JOIN session_metrics m ON m.recording_id = t.recording_id
+                    WHERE m.site_origin IN ({SITE_ORIGINS})
+                        AND (?2 IS NULL OR instr(lower(t.value), lower(?2)) > 0)
+                    ORDER BY t.recording_id, t.seq
+                    "#
+                ))?;
+                let entries = stmt
+                    .query_map(params![site_origin, contains], |row| {
+                        Ok((row.get(0)?, Self::text_entry_from_row(row, 1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(entries)
+            })
+            .await
+    }
+
+    async fn store_scroll_depth(&self, recording_id: &str, depth: &ScrollDepth) -> Result<(), AssetError> {
+        let (recording_id, depth) = (recording_id.to_string(), depth.clone());
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT OR REPLACE INTO scroll_depths
+                        (recording_id, site_origin, path, viewport_height, page_height, max_depth_px,
+                         duration_ms, above_fold_ms)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                    "#,
+                    params![
+                        recording_id,
+                        depth.site_origin,
+                        depth.path,
+                        depth.viewport_height,
+                        depth.page_height,
+                        depth.max_depth_px,
+                        depth.duration_ms as i64,
+                        depth.above_fold_ms as i64,
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn list_scroll_depths(&self, site_origin: &str, path: &str) -> Result<Vec<ScrollDepth>, AssetError> {
+        let (site_origin, path) = (site_origin.to_string(), path.to_string());
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT site_origin, path, viewport_height, page_height, max_depth_px, duration_ms, above_fold_ms
+                    FROM scroll_depths
+                    WHERE site_origin IN ({SITE_ORIGINS}) AND path = ?2
+                    ORDER BY recording_id
+                    "#
+                ))?;
+                let depths = stmt
+                    .query_map(params![site_origin, path], |row| {
+                        Ok(ScrollDepth {
+                            site_origin: row.get(0)?,
+                            path: row.get(1)?,
+                            viewport_height: row.get(2)?,
+                            page_height: row.get(3)?,
+                            max_depth_px: row.get(4)?,
+                            duration_ms: row.get::<_, i64>(5)? as u64,
+                            above_fold_ms: row.get::<_, i64>(6)? as u64,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(depths)
+            })
+            .await
+    }
+
+    async fn is_migration_done(&self, name: &str) -> Result<bool, AssetError> {
+        let name = name.to_string();
+        self.pool
+            .run(move |conn| {
+                let done = conn
+                    .query_row("SELECT 1 FROM migrations WHERE name = ?1", params![name], |_| Ok(()))
+                    .optional()?;
+                Ok(done.is_some())
+            })
+            .await
+    }
+
+    async fn mark_migration_done(&self, name: &str) -> Result<(), AssetError> {
+        let name = name.to_string();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT OR REPLACE INTO migrations (name, completed) VALUES (?1, ?2)",
+                    params![name, Utc::now().timestamp_millis()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_recording_summaries(&self, recording_ids: &[String]) -> Result<Vec<RecordingSummary>, AssetError> {
+        let recording_ids = recording_ids.to_vec();
+        self.pool
+            .run(move |conn| {
+                let mut summaries = Vec::new();
+                // Stay well under SQLite's bound parameter limit
+                for ids in recording_ids.chunks(500) {
+                    let placeholders = vec!["?"; ids.len()].join(", ");
+                    let mut stmt = conn.prepare(&format!(
+                        r#"
+                        SELECT recording_id, site_origin, initial_url, duration_ms, frame_count, title, tags,
+                               anonymous_id, user_id, email_hash, client_ip, dropped_frames,
+                               summarized_frames
+                        FROM recordings
+                        WHERE frame_count IS NOT NULL AND recording_id IN ({})
+                        "#,
+                        placeholders
+                    ))?;
+                    let rows = stmt.query_map(rusqlite::params_from_iter(ids), |row| {
+                        Ok((
+                            RecordingSummary {
+                                recording_id: row.get(0)?,
+                                site_origin: row.get::<_, Option<String>>(1)?.filter(|s| !s.is_empty()),
+                                initial_url: row.get::<_, Option<String>>(2)?.filter(|s| !s.is_empty()),
+                                duration_ms: row.get::<_, Option<i64>>(3)?.unwrap_or(0) as u64,
+                                frame_count: row.get::<_, i64>(4)? as u64,
+                                title: row.get(5)?,
+                                tags: Vec::new(),
+                                anonymous_id: row.get(7)?,
+                                user_id: row.get(8)?,
+                                email_hash: row.get(9)?,
+                                client_ip: row.get(10)?,
+                                dropped_frames: BTreeMap::new(),
+                                summarized_frames: BTreeMap::new(),
+                            },
+                            row.get::<_, Option<String>>(6)?,
+                            row.get::<_, Option<String>>(11)?,
+                            row.get::<_, Option<String>>(12)?,
+                        ))
+                    })?;
+                    for row in rows {
+                        let (mut summary, tags, dropped_frames, summarized_frames) = row?;
+                        summary.tags = tags
+                            .and_then(|tags| serde_json::from_str(&tags).ok())
+                            .unwrap_or_default();
+                        summary.dropped_frames = dropped_frames
+                            .and_then(|counts| serde_json::from_str(&counts).ok())
+                            .unwrap_or_default();
+                        summary.summarized_frames = summarized_frames
+                            .and_then(|summarized| serde_json::from_str(&summarized).ok())
+                            .unwrap_or_default();
+                        summaries.push(summary);
+                    }
+                }
+                Ok(summaries)
+            })
+            .await
+    }
+
+    async fn get_origin_policy(&self, origin: &str) -> Result<Option<OriginPolicy>, AssetError> {
+        let origin = origin.to_string();
+        self.pool
+            .run(move |conn| {
+                let policy = conn
+                    .query_row(
+                        r#"
+                        SELECT origin, allowed, masking, sample_rate, max_duration_ms, unsampled
+                        FROM origin_policies WHERE origin = ?1
+                        "#,
+                        params![origin],
+                        Self::origin_policy_from_row,
+                    )
+                    .optional()?;
+                Ok(policy)
+            })
+            .await
+    }
+
+    async fn set_origin_policy(&self, policy: &OriginPolicy) -> Result<(), AssetError> {
+        let policy = policy.clone();
+        self.pool
+            .run(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT OR REPLACE INTO origin_policies
+                        (origin, allowed, masking, sample_rate, max_duration_ms, unsampled)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                    "#,
+                    params![
+                        policy.origin,
+                        policy.allowed,
+                        policy.masking.as_str(),
+                        policy.sample_rate,
+                        policy.max_duration_ms.map(|ms| ms as i64),
+                        policy.unsampled.as_str()
+                    ],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn delete_origin_policy(&self, origin: &str) -> Result<bool, AssetError> {
+        let origin = origin.to_string();
+        self.pool
+            .run(move |conn| {
+                let deleted = conn.execute("DELETE FROM origin_policies WHERE origin = ?1", params![origin])?;
+                Ok(deleted > 0)
+            })
+            .await
+    }
+
+    async fn list_origin_policies(&self) -> Result<Vec<OriginPolicy>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT origin, allowed, masking, sample_rate, max_duration_ms, unsampled
+                    FROM origin_policies ORDER BY origin
+                    "#,
+                )?;
+                let policies = stmt.query_map([], Self::origin_policy_from_row)?.collect::<Result<Vec<_>, _>>()?;
+                Ok(policies)
+            })
+            .await
+    }
+
+    async fn set_site_profile(&self, profile: &SiteProfile) -> Result<(), AssetError> {
+        let profile = profile.clone();
+        self.pool
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM site_aliases WHERE site = ?1", params![profile.site])?;
+                for alias in &profile.aliases {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO site_aliases (alias, site) VALUES (?1, ?2)",
+                        params![alias, profile.site],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await
+    }
+
+    async fn delete_site_profile(&self, site: &str) -> Result<bool, AssetError> {
+        let site = site.to_string();
+        self.pool
+            .run(move |conn| {
+                let deleted = conn.execute("DELETE FROM site_aliases WHERE site = ?1", params![site])?;
+                Ok(deleted > 0)
+            })
+            .await
+    }
+
+    async fn list_site_profiles(&self) -> Result<Vec<SiteProfile>, AssetError> {
+        self.pool
+            .run(|conn| {
+                let mut stmt = conn.prepare("SELECT site, alias FROM site_aliases ORDER BY site, alias")?;
+                let mut profiles: Vec<SiteProfile> = Vec::new();
+                for row in stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))? {
+                    let (site, alias) = row?;
+                    match profiles.last_mut() {
+                        Some(profile) if profile.site == site => profile.aliases.push(alias),
+                        _ => profiles.push(SiteProfile { site, aliases: vec![alias] }),
+                    }
+                }
+                Ok(profiles)
+            })
+            .await
+    }
+
+    async fn list_session_metrics(&self, site_origin: &str) -> Result<Vec<SessionMetrics>, AssetError> {
+        let site_origin = site_origin.to_string();
+        self.pool
+            .run(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT recording_id, site_origin, initial_url, started_at, duration_ms,
+                           page_count, click_count, rage_click_count, error_count
+                    FROM session_metrics
+                    WHERE site_origin IN ({SITE_ORIGINS})
+                    ORDER BY started_at DESC
+                    "#
+                ))?;
+                let sessions = stmt
+                    .query_map(params![site_origin], |row| {
+                        Ok(SessionMetrics {
+                            recording_id: row.get(0)?,
+                            site_origin: row.get(1)?,
+                            initial_url: row.get(2)?,
+                            started_at: row.get::<_, Option<i64>>(3)?.map(|t| t as u64),
+                            duration_ms: row.get::<_, i64>(4)? as u64,
+                            page_count: row.get(5)?,
+                            click_count: row.get(6)?,
+                            rage_click_count: row.get(7)?,
+                            error_count: row.get(8)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(sessions)
+            })
+            .await
+    }
+
+    async fn list_session_events(
+        &self,
+        site_origin: &str,
+    ) -> Result<Vec<(String, Vec<SessionEvent>)>, AssetError> {
+        let site_origin = site_origin.to_string();
+        self.pool
+            .run(move |conn| {
+                // Sessions without events are included so funnel totals are accurate
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT m.recording_id, e.kind, e.name, e.offset_ms
+                    FROM session_metrics m
+                    LEFT JOIN session_events e ON e.recording_id = m.recording_id
+                    WHERE m.site_origin IN ({SITE_ORIGINS})
+                    ORDER BY m.recording_id, e.seq
+                    "#
+                ))?;
+                let rows = stmt.query_map(params![site_origin], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                    ))
+                })?;
+
+                let mut sessions: Vec<(String, Vec<SessionEvent>)> = Vec::new();
+                for row in rows {
+                    let (recording_id, kind, name, offset_ms) = row?;
+                    if sessions.last().is_none_or(|(id, _)| *id != recording_id) {
+                        sessions.push((recording_id, Vec::new()));
+                    }
+                    let kind = kind.as_deref().and_then(SessionEventKind::parse);
+                    if let (Some(kind), Some(name), Some(offset_ms)) = (kind, name, offset_ms) {
+                        let events = &mut sessions.last_mut().expect("pushed above").1;
+                        events.push(SessionEvent {
+                            kind,
+                            name,
+                            offset_ms: offset_ms as u64,
+                        });
+                    }
+                }
+
+                Ok(sessions)
+            })
+            .await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>, AssetError> {
+        // The database file plus its WAL and shared-memory files
+        let path = self.pool.path.to_string_lossy().to_string();
+        let total = ["", "-wal", "-shm"]
+            .iter()
+            .filter_map(|suffix| std::fs::metadata(format!("{}{}", path, suffix)).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        Ok(Some(total))
+    }
+
+    async fn health(&self) -> BackendHealth {
+        // Goes through the pool, so a wedged database times out
+        let probe = self
+            .pool
+            .run(|conn| Ok(conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))?));
+        BackendHealth::measure(probe).await
     }
 }
 
@@ -339,5 +2432,399 @@ mod tests {
         let not_found = store.resolve_hashes("unknown-hash").await.unwrap();
         assert_eq!(not_found, None);
     }
-}
 
+    #[tokio::test]
+    async fn test_click_heatmap_accumulates() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let store = SqliteMetadataStore::new(db_path).unwrap();
+
+        let bucket = |x, y, count| HeatmapBucket { x, y, count };
+        store
+            .record_click_buckets("https://example.com", "/", &[bucket(1, 2, 3)])
+            .await
+            .unwrap();
+        store
+            .record_click_buckets("https://example.com", "/", &[bucket(1, 2, 1), bucket(0, 0, 1)])
+            .await
+            .unwrap();
+
+        let heatmap = store.get_click_heatmap("https://example.com", "/").await.unwrap();
+        assert_eq!(heatmap, vec![bucket(0, 0, 1), bucket(1, 2, 4)]);
+
+        let other = store.get_click_heatmap("https://example.com", "/other").await.unwrap();
+        assert!(other.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_metrics_and_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let store = SqliteMetadataStore::new(db_path).unwrap();
+
+        let metrics = |id: &str| SessionMetrics {
+            recording_id: id.to_string(),
+            site_origin: Some("https://example.com".to_string()),
+            started_at: Some(1000),
+            click_count: 2,
+            ..Default::default()
+        };
+        let event = SessionEvent {
+            kind: SessionEventKind::Annotation,
+            name: "signup".to_string(),
+            offset_ms: 5,
+        };
+        store.store_session_metrics(&metrics("a"), std::slice::from_ref(&event)).await.unwrap();
+        store.store_session_metrics(&metrics("b"), &[]).await.unwrap();
+        // Re-storing replaces rather than duplicates events
+        store.store_session_metrics(&metrics("a"), std::slice::from_ref(&event)).await.unwrap();
+
+        let sessions = store.list_session_metrics("https://example.com").await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].click_count, 2);
+
+        let events = store.list_session_events("https://example.com").await.unwrap();
+        assert_eq!(
+            events,
+            vec![("a".to_string(), vec![event]), ("b".to_string(), vec![])]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_usage_from_pooled_connections() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Arc::new(SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap());
+
+        let tasks: Vec<_> = (0..32)
+            .map(|i| {
+                let store = Arc::clone(&store);
+                tokio::spawn(async move {
+                    store
+                        .register_asset_usage(AssetUsageParams {
+                            site_origin: "https://example.com".to_string(),
+                            url: format!("https://example.com/{}.css", i % 4),
+                            sha256_hash: format!("hash{}", i % 4),
+                            size: 0,
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let counts: i64 = store
+            .pool
+            .run(|conn| Ok(conn.query_row("SELECT SUM(usage_count) FROM site_assets", [], |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert_eq!(counts, 32);
+    }
+
+    #[tokio::test]
+    async fn test_operation_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SqliteConfig {
+            operation_timeout: Duration::from_millis(50),
+            ..SqliteConfig::default()
+        };
+        let store = SqliteMetadataStore::with_config(temp_dir.path().join("test.db"), config).unwrap();
+
+        // A query that would never finish is interrupted
+        let endless = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT count(*) FROM n";
+        let result = store
+            .pool
+            .run(move |conn| Ok(conn.query_row(endless, [], |row| row.get::<_, i64>(0))?))
+            .await;
+        assert!(matches!(result, Err(AssetError::Database(msg)) if msg.contains("timed out")));
+
+        // A write issued after the timeout is interrupted too, so a reported
+        // failure means nothing was committed
+        let result = store
+            .pool
+            .run(|conn| {
+                std::thread::sleep(Duration::from_millis(200));
+                conn.execute("INSERT INTO recordings (recording_id) VALUES ('late.dcrr')", [])?;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(result, Err(AssetError::Database(msg)) if msg.contains("timed out")));
+        let count: i64 = store
+            .pool
+            .run(|conn| Ok(conn.query_row("SELECT count(*) FROM recordings", [], |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_operations_never_open_more_than_max_connections() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SqliteConfig {
+            max_connections: 1,
+            operation_timeout: Duration::from_millis(50),
+            ..SqliteConfig::default()
+        };
+        let store = SqliteMetadataStore::with_config(temp_dir.path().join("test.db"), config).unwrap();
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let most_running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let operations = (0..4).map(|_| {
+            let (running, most_running) = (running.clone(), most_running.clone());
+            let pool = store.pool.clone();
+            async move {
+                pool.run(move |_| {
+                    let now = running.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    most_running.fetch_max(now, std::sync::atomic::Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(100));
+                    running.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                })
+                .await
+            }
+        });
+        let results = futures::future::join_all(operations).await;
+        // The first overran its timeout but finished; the rest timed out waiting for it
+        assert!(results[0].is_ok());
+        assert!(results[1..].iter().all(|result| result.is_err()));
+        assert_eq!(most_running.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recording_summaries() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        store.register_recording("a.dcrr", "https://example.com/start").await.unwrap();
+        store.register_recording("pending.dcrr", "https://example.com/").await.unwrap();
+
+        let summary = RecordingSummary {
+            recording_id: "a.dcrr".to_string(),
+            duration_ms: 1200,
+            frame_count: 42,
+            title: Some("Start".to_string()),
+            tags: vec!["checkout".to_string()],
+            ..Default::default()
+        };
+        store.store_recording_summary(&summary).await.unwrap();
+
+        let ids = ["a.dcrr", "pending.dcrr", "missing.dcrr"].map(str::to_string);
+        let summaries = store.get_recording_summaries(&ids).await.unwrap();
+        assert_eq!(
+            summaries,
+            vec![RecordingSummary {
+                site_origin: Some("https://example.com".to_string()),
+                initial_url: Some("https://example.com/start".to_string()),
+                ..summary
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recording_scope() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(store.get_recording_scope("a.dcrr").await.unwrap(), None);
+        store.store_recording_scope("a.dcrr", "#widget").await.unwrap();
+        assert_eq!(store.get_recording_scope("a.dcrr").await.unwrap().as_deref(), Some("#widget"));
+    }
+
+    #[tokio::test]
+    async fn test_session_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        store.register_recording("tab-2.dcrr", "https://example.com/cart").await.unwrap();
+        store.store_session_link("tab-2.dcrr", "session-1").await.unwrap();
+        store.store_session_link("tab-1.dcrr", "session-1").await.unwrap();
+        store.store_session_link("other.dcrr", "session-2").await.unwrap();
+
+        assert_eq!(store.list_session_recordings("session-1").await.unwrap(), ["tab-1.dcrr", "tab-2.dcrr"]);
+        assert!(store.list_session_recordings("session-3").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recording_meta() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(store.get_recording_meta("a.dcrr").await.unwrap(), None);
+
+        let meta = RecordingMeta {
+            size: 4096,
+            duration_ms: 2_500,
+            frame_count: 3,
+            frame_counts: [("Timestamp".to_string(), 3)].into(),
+            initial_url: Some("https://example.com/".to_string()),
+            ..RecordingMeta::default()
+        };
+        store.store_recording_meta("a.dcrr", &meta).await.unwrap();
+        assert_eq!(store.get_recording_meta("a.dcrr").await.unwrap(), Some(meta));
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        store.register_recording("a.dcrr", "https://example.com/").await.unwrap();
+        assert_eq!(store.get_clock_skew("a.dcrr").await.unwrap(), None);
+
+        let skew = ClockSkew {
+            skew_ms: -1500,
+            round_trip_ms: 40,
+        };
+        store.store_clock_skew("a.dcrr", skew).await.unwrap();
+        // Recordings that were never registered get a row too
+        store.store_clock_skew("unregistered.dcrr", skew).await.unwrap();
+        assert_eq!(store.get_clock_skew("a.dcrr").await.unwrap(), Some(skew));
+        assert_eq!(store.get_clock_skew("unregistered.dcrr").await.unwrap(), Some(skew));
+    }
+
+    #[tokio::test]
+    async fn test_recording_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), None);
+
+        let key = |key_id: &str, byte| WrappedKey {
+            key_id: key_id.to_string(),
+            wrapped: vec![byte; 60],
+        };
+        store.store_recording_key("a.dcrr", &key("old", 1)).await.unwrap();
+        store.store_recording_key("b.dcrr", &key("new", 2)).await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), Some(key("old", 1)));
+        assert_eq!(
+            store.list_recording_keys_not_wrapped_by("new").await.unwrap(),
+            [("a.dcrr".to_string(), key("old", 1))]
+        );
+
+        store.store_recording_key("a.dcrr", &key("new", 3)).await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), Some(key("new", 3)));
+        assert!(store.list_recording_keys_not_wrapped_by("new").await.unwrap().is_empty());
+
+        store.delete_recording_key("a.dcrr").await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), None);
+        assert_eq!(store.get_recording_key("b.dcrr").await.unwrap(), Some(key("new", 2)));
+    }
+
+    #[tokio::test]
+    async fn test_legal_holds_and_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        store.register_recording("a.dcrr", "https://example.com/").await.unwrap();
+        assert!(!store.is_on_legal_hold("a.dcrr").await.unwrap());
+        // Releasing a recording that was never held changes nothing
+        assert!(!store.set_legal_hold("b.dcrr", false).await.unwrap());
+
+        assert!(store.set_legal_hold("a.dcrr", true).await.unwrap());
+        assert!(!store.set_legal_hold("a.dcrr", true).await.unwrap());
+        assert!(store.set_legal_hold("b.dcrr", true).await.unwrap());
+        assert!(store.is_on_legal_hold("a.dcrr").await.unwrap());
+        assert_eq!(store.list_legal_holds().await.unwrap(), ["a.dcrr", "b.dcrr"]);
+        assert!(store.set_legal_hold("a.dcrr", false).await.unwrap());
+        assert_eq!(store.list_legal_holds().await.unwrap(), ["b.dcrr"]);
+
+        let applied = AuditEvent::now("a.dcrr", AuditAction::HoldApplied, Some("legal".into()), Some("case 42".into()));
+        let deleted = AuditEvent::now("b.dcrr", AuditAction::Deleted, None, None);
+        store.append_audit_event(&applied).await.unwrap();
+        store.append_audit_event(&deleted).await.unwrap();
+        // Times are kept to the millisecond
+        let truncate = |event: &AuditEvent| AuditEvent {
+            at: chrono::DateTime::from_timestamp_millis(event.at.timestamp_millis()).unwrap(),
+            ..event.clone()
+        };
+        assert_eq!(store.list_audit_events(None).await.unwrap(), [truncate(&applied), truncate(&deleted)]);
+        assert_eq!(store.list_audit_events(Some("b.dcrr")).await.unwrap(), [truncate(&deleted)]);
+    }
+
+    #[tokio::test]
+    async fn test_replacing_recording_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        let key = |key_id: &str| WrappedKey {
+            key_id: key_id.to_string(),
+            wrapped: vec![1, 2, 3],
+        };
+        let summary = RecordingSummary {
+            recording_id: "a.dcrr".to_string(),
+            frame_count: 1,
+            ..Default::default()
+        };
+        store.store_recording_summary(&summary).await.unwrap();
+        store.store_recording_key("a.dcrr", &key("old")).await.unwrap();
+        store.store_recording_key("a.dcrr.rewrite", &key("new")).await.unwrap();
+
+        store.replace_recording_key("a.dcrr.rewrite", "a.dcrr").await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), Some(key("new")));
+        assert_eq!(store.get_recording_key("a.dcrr.rewrite").await.unwrap(), None);
+        assert_eq!(store.get_recording_summaries(&["a.dcrr".to_string()]).await.unwrap().len(), 1);
+        // A copy written without encryption leaves the recording without a key
+        store.replace_recording_key("a.dcrr.rewrite", "a.dcrr").await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), None);
+
+        store.store_recording_key("a.dcrr.rewrite", &key("newer")).await.unwrap();
+        store.replace_recording_metadata("a.dcrr.rewrite", "a.dcrr").await.unwrap();
+        assert_eq!(store.get_recording_key("a.dcrr").await.unwrap(), Some(key("newer")));
+        assert!(store.get_recording_summaries(&["a.dcrr".to_string()]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subject_lookup_and_removal() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteMetadataStore::new(temp_dir.path().join("test.db")).unwrap();
+        let summaries = [
+            ("a.dcrr", vec!["user:1", "vip"], None),
+            ("b.dcrr", vec!["user:10"], None),
+            ("c.dcrr", vec![], Some("user:1")),
+            ("d.dcrr", vec![], Some("user:2")),
+        ];
+        for (id, tags, user_id) in summaries {
+            let summary = RecordingSummary {
+                recording_id: id.to_string(),
+                frame_count: 1,
+                tags: tags.into_iter().map(String::from).collect(),
+                user_id: user_id.map(String::from),
+                ..Default::default()
+            };
+            store.store_recording_summary(&summary).await.unwrap();
+        }
+        let summaries = store.get_recording_summaries(&["c.dcrr".to_string()]).await.unwrap();
+        assert_eq!(summaries[0].user_id.as_deref(), Some("user:1"));
+        assert_eq!(store.find_recordings_by_subject("user:1").await.unwrap(), ["a.dcrr", "c.dcrr"]);
+        assert!(store.find_recordings_by_subject("user").await.unwrap().is_empty());
+
+        store.delete_recording_metadata("a.dcrr").await.unwrap();
+        assert_eq!(store.find_recordings_by_subject("user:1").await.unwrap(), ["c.dcrr"]);
+        assert!(store.get_recording_summaries(&["a.dcrr".to_string()]).await.unwrap().is_empty());
+
+        let metadata = AssetMetadata {
+            sha256_hash: "abc123".to_string(),
+            random_id: "rand1".to_string(),
+            size: 3,
+            mime_type: "image/png".to_string(),
+        };
+        store.store_asset_metadata(metadata).await.unwrap();
+        assert_eq!(store.delete_asset("rand1").await.unwrap().as_deref(), Some("abc123"));
+        assert_eq!(store.resolve_random_id("rand1").await.unwrap(), None);
+        assert_eq!(store.delete_asset("rand1").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_adds_summary_columns_to_old_databases() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "CREATE TABLE recordings (recording_id TEXT PRIMARY KEY, site_origin TEXT NOT NULL, \
+                 initial_url TEXT NOT NULL, created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+                [],
+            )
+            .unwrap();
+
+        SqliteMetadataStore::new(&db_path).unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        let columns: i64 = conn
+            .query_row("SELECT COUNT(*) FROM pragma_table_info('recordings')", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(columns, 20);
+    }
+}
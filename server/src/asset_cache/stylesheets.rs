@@ -0,0 +1,98 @@
+//! Stylesheet text deduplication through the CAS
+//!
+//! Design-system sites send the same multi-hundred-KB stylesheets in every
+//! recording as `NewAdoptedStyleSheet` and `StyleSheetReplaced` frames. Text
+//! over the configured size is stored once in the asset cache like any other
+//! asset, and the recording gets a reference frame carrying its random_id.
+//! Playback puts the text back, so players never see the references.
+
+use crate::asset_cache::{AssetError, AssetFileStore, MetadataStore, store_or_get_asset_metadata};
+use crate::observability::ObservabilityHooks;
+use domcorder_proto::{
+    Frame, NewAdoptedStyleSheetData, NewAdoptedStyleSheetReferenceData, StyleSheetReplacedData,
+    StyleSheetReplacedReferenceData, VStyleSheet,
+};
+
+/// Stylesheet text at least this large is moved to the CAS by default
+pub const DEFAULT_STYLESHEET_DEDUP_BYTES: u64 = 16 * 1024;
+
+/// MIME type cached stylesheet text is stored with
+pub const STYLESHEET_MIME: &str = "text/css";
+
+/// Store stylesheet text of at least `min_bytes` in the CAS, returning the
+/// reference frame that replaces `frame`, or None to keep it as it is
+pub async fn dedup_stylesheet_frame(
+    frame: &Frame,
+    min_bytes: u64,
+    metadata_store: &dyn MetadataStore,
+    asset_file_store: &dyn AssetFileStore,
+    hooks: &dyn ObservabilityHooks,
+) -> Result<Option<Frame>, AssetError> {
+    let text = match frame {
+        Frame::NewAdoptedStyleSheet(sheet) => &sheet.style_sheet.text,
+        Frame::StyleSheetReplaced(replaced) => &replaced.content,
+        _ => return Ok(None),
+    };
+    if (text.len() as u64) < min_bytes {
+        return Ok(None);
+    }
+
+    let sha256_hash = crate::asset_cache::hash::sha256(text.as_bytes());
+    let hash = store_or_get_asset_metadata(
+        &sha256_hash,
+        text.as_bytes(),
+        STYLESHEET_MIME,
+        metadata_store,
+        asset_file_store,
+        hooks,
+    )
+    .await?;
+
+    Ok(Some(match frame {
+        Frame::NewAdoptedStyleSheet(sheet) => Frame::NewAdoptedStyleSheetReference(NewAdoptedStyleSheetReferenceData {
+            id: sheet.style_sheet.id,
+            media: sheet.style_sheet.media.clone(),
+            hash,
+        }),
+        Frame::StyleSheetReplaced(replaced) => Frame::StyleSheetReplacedReference(StyleSheetReplacedReferenceData {
+            style_sheet_id: replaced.style_sheet_id,
+            hash,
+        }),
+        _ => unreachable!("only stylesheet frames carry text"),
+    }))
+}
+
+/// Replace a stylesheet reference frame with the frame it stands for, reading
+/// the text back from the CAS; other frames are returned unchanged
+pub async fn expand_stylesheet_reference(
+    frame: Frame,
+    metadata_store: &dyn MetadataStore,
+    asset_file_store: &dyn AssetFileStore,
+) -> Result<Frame, AssetError> {
+    let random_id = match &frame {
+        Frame::NewAdoptedStyleSheetReference(reference) => &reference.hash,
+        Frame::StyleSheetReplacedReference(reference) => &reference.hash,
+        _ => return Ok(frame),
+    };
+    let sha256_hash = metadata_store
+        .resolve_random_id(random_id)
+        .await?
+        .ok_or_else(|| AssetError::NotFound(random_id.clone()))?;
+    let text =
+        String::from_utf8(asset_file_store.get(&sha256_hash).await?).map_err(|e| AssetError::Storage(Box::new(e)))?;
+
+    Ok(match frame {
+        Frame::NewAdoptedStyleSheetReference(reference) => Frame::NewAdoptedStyleSheet(NewAdoptedStyleSheetData {
+            style_sheet: VStyleSheet {
+                id: reference.id,
+                text,
+                media: reference.media,
+            },
+        }),
+        Frame::StyleSheetReplacedReference(reference) => Frame::StyleSheetReplaced(StyleSheetReplacedData {
+            style_sheet_id: reference.style_sheet_id,
+            content: text,
+        }),
+        _ => unreachable!("only stylesheet references are expanded"),
+    })
+}
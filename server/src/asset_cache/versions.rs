@@ -0,0 +1,137 @@
+//! Asset changes between two dates
+//!
+//! "Replay looks wrong since Tuesday" is usually a frontend deploy: a
+//! stylesheet or script URL started serving content that older recordings
+//! never cached. Comparing what each URL a site uses served at two times,
+//! from the URL history (see [`UrlVersion`]), lists the assets a deploy
+//! changed and when the new content was first seen.
+//!
+//! The history only keeps when each version was first and last seen, so the
+//! version a URL served at a time is a best guess: of the versions seen by
+//! then, one still being seen afterwards (the latest introduced, if
+//! several), or else the one seen last.
+
+use crate::asset_cache::UrlVersion;
+use crate::{StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A URL whose content changed between two times
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetChange {
+    pub url: String,
+    /// What it served at the start; None if the site hadn't used it yet
+    pub before: Option<UrlVersion>,
+    /// What it served at the end
+    pub after: UrlVersion,
+}
+
+/// The version of a URL served at `at`, from all of its versions
+pub(crate) fn version_at(versions: &[UrlVersion], at: DateTime<Utc>) -> Option<&UrlVersion> {
+    let seen: Vec<_> = versions.iter().filter(|version| version.first_seen_at <= at).collect();
+    seen.iter()
+        .filter(|version| version.last_seen_at >= at)
+        .max_by_key(|version| version.first_seen_at)
+        .or_else(|| seen.iter().max_by_key(|version| version.last_seen_at))
+        .copied()
+}
+
+/// The URLs whose version at `from` differs from their version at `to`,
+/// by URL
+pub fn compare_versions(
+    versions: Vec<(String, UrlVersion)>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<AssetChange> {
+    let mut by_url: BTreeMap<String, Vec<UrlVersion>> = BTreeMap::new();
+    for (url, version) in versions {
+        by_url.entry(url).or_default().push(version);
+    }
+    by_url
+        .into_iter()
+        .filter_map(|(url, versions)| {
+            let before = version_at(&versions, from);
+            let after = version_at(&versions, to)?;
+            (before.map(|before| &before.sha256_hash) != Some(&after.sha256_hash)).then(|| AssetChange {
+                before: before.cloned(),
+                after: after.clone(),
+                url,
+            })
+        })
+        .collect()
+}
+
+impl StorageState {
+    /// The assets of `site_origin` whose content changed between `from` and `to`
+    pub async fn asset_changes(
+        &self,
+        site_origin: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AssetChange>, StorageError> {
+        let versions = self.metadata_store.get_site_url_versions(site_origin).await?;
+        Ok(compare_versions(versions, from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, day, 12, 0, 0).unwrap()
+    }
+
+    fn version(url: &str, sha256_hash: &str, first: u32, last: u32) -> (String, UrlVersion) {
+        (
+            url.to_string(),
+            UrlVersion {
+                sha256_hash: sha256_hash.to_string(),
+                first_seen_at: day(first),
+                last_seen_at: day(last),
+                cached: true,
+            },
+        )
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        let versions = vec![
+            version("https://example.com/app.css", "css-1", 1, 6),
+            version("https://example.com/app.css", "css-2", 7, 20),
+            version("https://example.com/app.js", "js-1", 1, 20),
+            version("https://example.com/new.js", "new-1", 8, 20),
+            // Rolled back to the first version on the 10th
+            version("https://example.com/menu.css", "menu-1", 1, 20),
+            version("https://example.com/menu.css", "menu-2", 6, 9),
+        ];
+
+        let changes = compare_versions(versions.clone(), day(5), day(12));
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.url.as_str(),
+                    change.before.as_ref().map(|before| before.sha256_hash.as_str()),
+                    change.after.sha256_hash.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("https://example.com/app.css", Some("css-1"), "css-2"),
+                ("https://example.com/new.js", None, "new-1"),
+            ]
+        );
+
+        let changes = compare_versions(versions, day(5), day(8));
+        let urls: Vec<_> = changes.iter().map(|change| change.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            ["https://example.com/app.css", "https://example.com/menu.css", "https://example.com/new.js"]
+        );
+    }
+}
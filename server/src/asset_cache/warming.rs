@@ -0,0 +1,88 @@
+//! Cache warming for known sites
+//!
+//! After a deploy the sites' assets have often changed, so the first
+//! recordings miss the cache and inline every asset. Warming fetches the
+//! assets on each configured site's manifest server-side, caching whatever
+//! is new and pointing the manifest at it, before recorders ask. It goes
+//! through the same fetch path as a recording (the asset size limit, the
+//! scanner, and the `fetcher` feature), and skips origins whose recording
+//! policy denies them. It also fills the in-memory manifest cache, so a
+//! handshake whose manifest query overruns its budget isn't left with an
+//! empty manifest (see [`crate::asset_cache::manifest`]).
+
+use crate::asset_cache::AssetError;
+use crate::asset_cache::manifest::generate_manifest;
+use crate::{StorageError, StorageState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tracing::{info, warn};
+
+/// How one site's manifest assets fared when warmed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WarmingReport {
+    pub site_origin: String,
+    /// Asset URLs on the manifest, each fetched once
+    pub assets: usize,
+    /// Of those, the ones whose content changed and are now cached
+    pub changed: usize,
+    /// Fetched, but over the asset size limit or quarantined
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl StorageState {
+    /// Fetch and cache the assets on `site_origin`'s manifest, registering
+    /// any whose content changed so the manifest offers the new version too
+    ///
+    /// Fails with [`StorageError::Refused`] if the origin isn't recorded.
+    /// Failures for one asset are counted and logged.
+    pub async fn warm_site_assets(&self, site_origin: &str) -> Result<WarmingReport, StorageError> {
+        let policy = self.recording_policy(Some(site_origin)).await?;
+        if !policy.allowed {
+            return Err(StorageError::Refused {
+                origin: site_origin.to_string(),
+                reason: "recording is disabled for this origin".to_string(),
+            });
+        }
+
+        // A URL can be on the manifest once for each version seen
+        let manifest = generate_manifest(self.metadata_store.as_ref(), site_origin, None, &HashSet::new()).await?;
+        let mut versions: BTreeMap<&str, HashSet<&str>> = BTreeMap::new();
+        for entry in &manifest.assets {
+            versions.entry(&entry.url).or_default().insert(&entry.sha256_hash);
+        }
+        let mut report = WarmingReport {
+            site_origin: site_origin.to_string(),
+            assets: versions.len(),
+            ..WarmingReport::default()
+        };
+        for (url, known) in versions {
+            match self
+                .fetch_server_side(url, None, self.asset_limits.max_asset_size)
+                .await
+            {
+                Ok(fetched) if known.contains(fetched.sha256_hash.as_str()) => {}
+                Ok(fetched) => {
+                    self.register_asset_usage(Some(site_origin), url, &fetched.sha256_hash, fetched.size)
+                        .await;
+                    report.changed += 1;
+                }
+                Err(AssetError::TooLarge { .. } | AssetError::Quarantined(_)) => report.skipped += 1,
+                Err(e) => {
+                    warn!("⚠️ Failed to warm {}: {}", url, e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        // The handshake falls back to this if its own query overruns
+        let manifest = generate_manifest(self.metadata_store.as_ref(), site_origin, None, &HashSet::new()).await?;
+        self.manifest_cache.store(&manifest);
+
+        info!(
+            "🔥 Warmed {} assets for {}: {} changed, {} skipped, {} failed",
+            report.assets, site_origin, report.changed, report.skipped, report.failed
+        );
+        Ok(report)
+    }
+}
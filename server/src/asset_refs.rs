@@ -0,0 +1,173 @@
+//! Asset reference counting
+//!
+//! Cached assets are shared by every recording that used the same bytes, so
+//! one can only be removed once no recording refers to it. The metadata store
+//! records which recordings refer to which assets: ingestion adds an asset the
+//! first time a recording refers to it, and deleting a recording's metadata
+//! (deletion, erasure, a refused recording) takes its references with it. An
+//! asset's reference count is the number of recordings referring to it, and
+//! `POST /admin/assets/gc` removes every asset whose count is zero without
+//! reading a single recording.
+//!
+//! Recordings stored before references were tracked are indexed once, by
+//! scanning their frames, when the server starts ([`StorageState::index_asset_references`]).
+
+use crate::asset_cache::PENDING_ASSET_HASH;
+use crate::asset_cache::pins::pinned_version;
+use crate::{RecordingId, StorageError, StorageState};
+use domcorder_proto::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+
+/// Name of the migration indexing recordings stored before references were tracked
+const INDEX_MIGRATION: &str = "index_asset_references";
+
+/// The cached assets a frame refers to, by random id
+pub fn referenced_assets(frame: &Frame, out: &mut HashSet<String>) {
+    let random_id = match frame {
+        Frame::AssetReference(reference) => &reference.hash,
+        Frame::NewAdoptedStyleSheetReference(reference) => &reference.hash,
+        Frame::StyleSheetReplacedReference(reference) => &reference.hash,
+        _ => return,
+    };
+    // Provisional references are followed by the resolved one
+    if random_id != PENDING_ASSET_HASH {
+        out.insert(random_id.clone());
+    }
+}
+
+/// The assets one recording has referred to so far, as it is written
+#[derive(Debug, Default)]
+pub(crate) struct AssetReferences {
+    seen: HashSet<String>,
+    /// The version each asset URL was last pinned to (see
+    /// [`crate::asset_cache::pins`])
+    pins: HashMap<String, String>,
+}
+
+impl AssetReferences {
+    /// The assets `frame` refers to that the recording hadn't yet
+    pub(crate) fn new_references(&mut self, frame: &Frame) -> Vec<String> {
+        let mut referenced = HashSet::new();
+        referenced_assets(frame, &mut referenced);
+        referenced
+            .into_iter()
+            .filter(|random_id| self.seen.insert(random_id.clone()))
+            .collect()
+    }
+
+    /// The URL and version `frame` pins, unless the URL is already pinned to it
+    pub(crate) fn new_pin(&mut self, frame: &Frame) -> Option<(String, String)> {
+        let (url, random_id) = pinned_version(frame)?;
+        if self.pins.get(url).is_some_and(|pinned| pinned == random_id) {
+            return None;
+        }
+        self.pins.insert(url.to_string(), random_id.to_string());
+        Some((url.to_string(), random_id.to_string()))
+    }
+}
+
+/// The outcome of `POST /admin/assets/gc`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetCollection {
+    /// Recordings indexed first, if references had never been indexed
+    pub indexed: usize,
+    /// Assets no recording refers to
+    pub unreferenced: usize,
+    /// Of those, the ones removed
+    pub removed: usize,
+}
+
+impl StorageState {
+    /// Record the assets recording `id` refers to, from its frames on disk
+    ///
+    /// A partial or corrupt frame at the end is ignored, as the frames
+    /// before it are the ones played.
+    pub async fn index_recording_assets(&self, id: &RecordingId) -> Result<(), StorageError> {
+        let mut reader = self.open_recording_reader(id).await?;
+        let mut referenced = HashSet::new();
+        while let Ok(Some(frame)) = reader.read_frame().await {
+            referenced_assets(&frame, &mut referenced);
+        }
+        let referenced: Vec<String> = referenced.into_iter().collect();
+        self.metadata_store
+            .add_asset_references(id.as_str(), &referenced)
+            .await?;
+        Ok(())
+    }
+
+    /// Index the asset references of every recording, unless that has been
+    /// done, returning how many recordings were indexed
+    ///
+    /// Fails if any recording can't be read. The index is then not marked
+    /// complete, so indexing is retried next time, and unreferenced assets
+    /// are not removed until it succeeds: one the unread recording refers
+    /// to could otherwise be lost.
+    pub async fn index_asset_references(&self) -> Result<usize, StorageError> {
+        if self.metadata_store.is_migration_done(INDEX_MIGRATION).await? {
+            return Ok(0);
+        }
+        let recordings = self.list_all_recordings(None).await?;
+        info!("🔗 Indexing the asset references of {} recordings", recordings.len());
+        let mut failed = 0;
+        for recording in &recordings {
+            let indexed = async {
+                let id = RecordingId::new(recording.id.as_str())?;
+                self.index_recording_assets(&id).await
+            }
+            .await;
+            if let Err(e) = indexed {
+                warn!("⚠️ Failed to index the asset references of {}: {}", recording.id, e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            return Err(StorageError::Io(std::io::Error::other(format!(
+                "failed to index the asset references of {} recordings",
+                failed
+            ))));
+        }
+        self.metadata_store.mark_migration_done(INDEX_MIGRATION).await?;
+        Ok(recordings.len())
+    }
+
+    /// Remove every cached asset no recording refers to
+    ///
+    /// Fails without removing anything if the recordings stored before
+    /// references were tracked can't all be indexed.
+    pub async fn collect_unreferenced_assets(&self) -> Result<AssetCollection, StorageError> {
+        let indexed = self.index_asset_references().await?;
+        let unreferenced = self.metadata_store.unreferenced_assets(None).await?;
+        let collection = AssetCollection {
+            indexed,
+            unreferenced: unreferenced.len(),
+            removed: self.remove_assets(unreferenced).await,
+        };
+        info!(
+            "🧺 Collected {} of {} unreferenced assets",
+            collection.removed, collection.unreferenced
+        );
+        Ok(collection)
+    }
+
+    /// Remove cached assets and their data, returning how many were removed;
+    /// failures are logged
+    pub(crate) async fn remove_assets(&self, random_ids: Vec<String>) -> usize {
+        let mut removed = 0;
+        for random_id in random_ids {
+            let result = async {
+                if let Some(sha256) = self.metadata_store.delete_asset(&random_id).await? {
+                    self.asset_file_store.delete(&sha256).await?;
+                }
+                Ok::<_, StorageError>(())
+            }
+            .await;
+            match result {
+                Ok(()) => removed += 1,
+                Err(e) => warn!("⚠️ Failed to remove asset {}: {}", random_id, e),
+            }
+        }
+        removed
+    }
+}
@@ -0,0 +1,64 @@
+//! Audit log of compliance-relevant actions on recordings
+//!
+//! Applying or releasing a legal hold, deleting a recording and redacting one
+//! for a subject erasure are recorded in the metadata store with who asked
+//! and why, so the history of a recording can be shown to auditors after the
+//! fact.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What was done to a recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    HoldApplied,
+    HoldReleased,
+    Deleted,
+    Redacted,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::HoldApplied => "hold_applied",
+            AuditAction::HoldReleased => "hold_released",
+            AuditAction::Deleted => "deleted",
+            AuditAction::Redacted => "redacted",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "hold_applied" => Some(AuditAction::HoldApplied),
+            "hold_released" => Some(AuditAction::HoldReleased),
+            "deleted" => Some(AuditAction::Deleted),
+            "redacted" => Some(AuditAction::Redacted),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub at: DateTime<Utc>,
+    pub recording_id: String,
+    pub action: AuditAction,
+    /// Who asked for it, as given by the caller
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl AuditEvent {
+    /// An event for `action` on `recording_id`, happening now
+    pub fn now(recording_id: &str, action: AuditAction, actor: Option<String>, reason: Option<String>) -> Self {
+        Self {
+            at: Utc::now(),
+            recording_id: recording_id.to_string(),
+            action,
+            actor,
+            reason,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! API key authentication
+//!
+//! When an API key is configured, every request must present it, either as
+//! `Authorization: Bearer <key>`, an `X-API-Key` header, or an `api_key`
+//! query parameter (for WebSocket clients that cannot set headers). CORS
+//! preflight requests are always allowed.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::borrow::Cow;
+use std::sync::Arc;
+use tracing::warn;
+use url::form_urlencoded;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const API_KEY_QUERY_PARAM: &str = "api_key";
+
+/// A shared secret clients must present
+#[derive(Clone)]
+pub struct ApiKey(Arc<str>);
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(Arc::from(key.into()))
+    }
+
+    /// Compare in constant time to avoid leaking the key through timing
+    pub fn matches(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.as_bytes();
+        if expected.len() != candidate.len() {
+            return false;
+        }
+        expected
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKey(<redacted>)")
+    }
+}
+
+/// Extract the key a request presents, if any
+///
+/// The query parameter is percent-decoded, so keys with reserved
+/// characters work when clients encode them.
+pub fn presented_key<'a>(headers: &'a HeaderMap, query: Option<&'a str>) -> Option<Cow<'a, str>> {
+    if let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(Cow::Borrowed(bearer.trim()));
+    }
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+        return Some(Cow::Borrowed(key.trim()));
+    }
+    form_urlencoded::parse(query?.as_bytes()).find_map(|(name, value)| (name == API_KEY_QUERY_PARAM).then_some(value))
+}
+
+/// Middleware rejecting requests without the configured key
+pub async fn require_api_key(State(key): State<ApiKey>, request: Request, next: Next) -> Response {
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+    match presented_key(request.headers(), request.uri().query()) {
+        Some(candidate) if key.matches(&candidate) => next.run(request).await,
+        Some(_) => {
+            warn!("🔒 Rejected request with invalid API key: {}", request.uri().path());
+            (StatusCode::UNAUTHORIZED, "Invalid API key").into_response()
+        }
+        None => (StatusCode::UNAUTHORIZED, "API key required").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_presented_key_sources() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(presented_key(&headers, Some("a=1&api_key=secret")).as_deref(), Some("secret"));
+        assert_eq!(presented_key(&headers, Some("api_key=a%2Bb%3Dc%26d")).as_deref(), Some("a+b=c&d"));
+        assert_eq!(presented_key(&headers, Some("api_keys=x")), None);
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("from-header"));
+        assert_eq!(presented_key(&headers, None).as_deref(), Some("from-header"));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+        assert_eq!(presented_key(&headers, None).as_deref(), Some("token"));
+
+        let key = ApiKey::new("token");
+        assert!(key.matches("token"));
+        assert!(!key.matches("tokem"));
+        assert!(!key.matches("token2"));
+    }
+}
@@ -0,0 +1,289 @@
+//! Bulk operations over many recordings
+//!
+//! `POST /recordings/bulk` deletes, tags, moves, exports or redacts the
+//! recordings named by an id list or selected by a filter, as for
+//! `GET /recordings`. The work runs as a background job (see [`crate::jobs`])
+//! whose progress is polled at `GET /recordings/bulk/{job}`. Each recording
+//! succeeds or fails on its own, and failures don't stop the job. Recordings
+//! on legal hold or still being written are only ever tagged or exported.
+
+use crate::audit::{AuditAction, AuditEvent};
+use crate::jobs::{Job, JobRequest, JobStatus, submit_job};
+use crate::recording_id::RecordingId;
+use crate::storage::RecordingFilter;
+use crate::{AppState, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+/// What a bulk job does to each recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Delete,
+    /// Add tags to the recording's summary
+    Tag {
+        tags: Vec<String>,
+    },
+    /// Move the recording into `subdir` (empty for the top level), keeping its file name
+    Move {
+        subdir: String,
+    },
+    /// Copy the recording, decrypted, into the job's export directory
+    Export,
+    /// Mask the recording's content in place, as for a subject erasure
+    Redact,
+}
+
+impl BulkOperation {
+    /// Whether the operation changes or removes the recording, which is
+    /// refused for recordings on hold or being written
+    fn modifies(&self) -> bool {
+        !matches!(self, BulkOperation::Tag { .. } | BulkOperation::Export)
+    }
+}
+
+/// A `POST /recordings/bulk` body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkRequest {
+    #[serde(flatten)]
+    pub operation: BulkOperation,
+    /// The recordings to act on; otherwise those selected by `filter`
+    pub ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub filter: RecordingFilter,
+    /// Who asked and why, for the audit log
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl BulkRequest {
+    /// Why the request can't be run, if it can't
+    pub fn check(&self) -> Result<(), &'static str> {
+        // Acting on every recording has to be asked for with an explicit filter
+        if self.ids.is_none() && self.filter.is_empty() {
+            return Err("Select recordings with ids or a filter");
+        }
+        if matches!(&self.operation, BulkOperation::Tag { tags } if tags.is_empty()) {
+            return Err("No tags given");
+        }
+        Ok(())
+    }
+
+    /// The recordings the request names or selects
+    ///
+    /// Without ids, an empty filter selects every recording.
+    pub(crate) async fn recording_ids(&self, state: &StorageState) -> Result<Vec<String>, StorageError> {
+        match &self.ids {
+            Some(ids) => Ok(ids.clone()),
+            None => Ok(state
+                .filter_recordings(&self.filter)
+                .await?
+                .into_iter()
+                .map(|recording| recording.id)
+                .collect()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkSuccess {
+    pub recording_id: String,
+    /// The recording's new id, for moves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkFailure {
+    pub recording_id: String,
+    pub error: String,
+}
+
+/// Progress of a bulk job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkJob {
+    pub id: String,
+    #[serde(flatten)]
+    pub operation: BulkOperation,
+    pub status: JobStatus,
+    /// Recordings selected
+    pub total: usize,
+    pub succeeded: Vec<BulkSuccess>,
+    pub failed: Vec<BulkFailure>,
+    /// Where exported recordings are written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export_dir: Option<PathBuf>,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+impl BulkJob {
+    /// A job's progress as a bulk job; None if it isn't one
+    pub fn from_job(state: &StorageState, job: &Job) -> Option<Self> {
+        let JobRequest::Bulk(request) = &job.request else {
+            return None;
+        };
+        let (mut succeeded, mut failed) = (Vec::new(), Vec::new());
+        for result in &job.results {
+            match &result.error {
+                None => succeeded.push(BulkSuccess {
+                    recording_id: result.item.clone(),
+                    moved_to: result.output.clone(),
+                }),
+                Some(error) => failed.push(BulkFailure {
+                    recording_id: result.item.clone(),
+                    error: error.clone(),
+                }),
+            }
+        }
+        Some(Self {
+            id: job.id.clone(),
+            operation: request.operation.clone(),
+            status: job.status,
+            total: job.items.len(),
+            succeeded,
+            failed,
+            export_dir: matches!(request.operation, BulkOperation::Export).then(|| state.export_dir(&job.id)),
+            started: job.started.unwrap_or(job.created),
+            finished: job.finished,
+        })
+    }
+}
+
+/// Queue a job applying `request` to the recordings it selects, returning
+/// the job as queued
+pub async fn start_bulk_job(state: &AppState, request: BulkRequest) -> Result<BulkJob, StorageError> {
+    let job = submit_job(state, JobRequest::Bulk(request)).await?;
+    Ok(BulkJob::from_job(state, &job).expect("bulk requests start bulk jobs"))
+}
+
+impl StorageState {
+    /// Where export job `job_id` writes its recordings
+    pub(crate) fn export_dir(&self, job_id: &str) -> PathBuf {
+        self.storage_dir.join("exports").join(job_id)
+    }
+
+    /// Apply a bulk job's operation to one recording, returning its new id if it moved
+    pub(crate) async fn apply_bulk_operation(
+        &self,
+        job_id: &str,
+        request: &BulkRequest,
+        recording_id: &str,
+    ) -> Result<Option<String>, StorageError> {
+        let id = RecordingId::new(recording_id)?;
+        if !self.recording_exists(&id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        if request.operation.modifies() {
+            if self.is_recording_active(&id) {
+                return Err(StorageError::InUse(id.to_string()));
+            }
+            if self.is_on_legal_hold(&id).await? {
+                return Err(StorageError::OnHold(id.to_string()));
+            }
+        }
+        let (actor, reason) = (request.actor.clone(), request.reason.clone());
+        match &request.operation {
+            BulkOperation::Delete => self.delete_recording(&id, actor, reason).await?,
+            BulkOperation::Tag { tags } => self.tag_recording(&id, tags).await?,
+            BulkOperation::Move { subdir } => return Ok(Some(self.move_recording(&id, subdir).await?.to_string())),
+            BulkOperation::Export => self.export_recording(&id, &self.export_dir(job_id)).await?,
+            BulkOperation::Redact => {
+                self.redact_recording(&id).await?;
+                let event = AuditEvent::now(id.as_str(), AuditAction::Redacted, actor, reason);
+                self.metadata_store.append_audit_event(&event).await?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Add `tags` to a recording's summary, which must exist
+    async fn tag_recording(&self, id: &RecordingId, tags: &[String]) -> Result<(), StorageError> {
+        let summaries = self.metadata_store.get_recording_summaries(&[id.to_string()]).await?;
+        let Some(mut summary) = summaries.into_iter().next() else {
+            return Err(StorageError::NotFound(format!("{} (not yet ingested)", id)));
+        };
+        for tag in tags {
+            if !summary.tags.contains(tag) {
+                summary.tags.push(tag.clone());
+            }
+        }
+        self.metadata_store.store_recording_summary(&summary).await?;
+        Ok(())
+    }
+
+    /// Move a recording into `subdir`, keeping its file name, returning its new id
+    ///
+    /// The stored bytes are copied as they are, so an encrypted recording
+    /// stays encrypted under its own key, which moves with its metadata.
+    pub async fn move_recording(&self, id: &RecordingId, subdir: &str) -> Result<RecordingId, StorageError> {
+        let filename = id.as_str().rsplit('/').next().unwrap_or(id.as_str());
+        let subdir = subdir.trim_matches('/');
+        let target = match subdir {
+            "" => RecordingId::new(filename)?,
+            subdir => RecordingId::new(format!("{}/{}", subdir, filename))?,
+        };
+        if target == *id {
+            return Ok(target);
+        }
+        if self.recording_exists(&target).await {
+            let exists = io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target));
+            return Err(StorageError::Io(exists));
+        }
+
+        self.mark_recording_active(&target)?;
+        let copied = self.copy_stored_recording(id, &target).await;
+        self.mark_recording_completed(&target);
+        if let Err(e) = copied {
+            if let Err(e) = self.recording_store.delete(&target).await {
+                warn!("⚠️ Failed to remove partial copy {}: {}", target, e);
+            }
+            return Err(e);
+        }
+        self.metadata_store
+            .rename_recording_metadata(id.as_str(), target.as_str())
+            .await?;
+        self.recording_store
+            .delete(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        info!("🚚 Moved recording {} to {}", id, target);
+        Ok(target)
+    }
+
+    /// Copy a recording's stored bytes to `target`
+    async fn copy_stored_recording(&self, id: &RecordingId, target: &RecordingId) -> Result<(), StorageError> {
+        let mut reader = self
+            .recording_store
+            .open(id, 0)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        let mut writer = self.recording_store.create(target).await?;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..n])?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Write a recording, decrypted and with its header, under `dir`
+    async fn export_recording(&self, id: &RecordingId, dir: &std::path::Path) -> Result<(), StorageError> {
+        let path = dir.join(id.as_str());
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut reader = self.open_reader(id, 0).await?;
+        let mut file = tokio::fs::File::create(&path).await?;
+        tokio::io::copy(&mut reader, &mut file).await?;
+        Ok(())
+    }
+}
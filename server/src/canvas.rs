@@ -0,0 +1,286 @@
+//! Canvas snapshots materialized from CanvasChanged and CanvasChangedDelta frames
+//!
+//! Recorders send a full image the first time a canvas is drawn and only the
+//! redrawn regions after that. Consumers that want the whole canvas at some
+//! point in a recording (thumbnails, exports, the timeline UI) ask for it
+//! here: the last full snapshot is decoded and the later patches are drawn
+//! over it. Images are only decoded for the canvas that was asked for.
+
+use domcorder_proto::{CanvasChangedData, CanvasChangedDeltaData, Frame, FrameReader};
+use std::collections::HashMap;
+use std::io;
+use tokio::io::AsyncRead;
+
+/// Error type for canvas materialization
+#[derive(Debug, thiserror::Error)]
+pub enum CanvasError {
+    #[error("Failed to read recording: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Patches can only be applied to PNG images, not {0}")]
+    UnsupportedMime(String),
+
+    #[error("Invalid PNG: {0}")]
+    Decode(#[from] png::DecodingError),
+
+    #[error("Failed to encode PNG: {0}")]
+    Encode(#[from] png::EncodingError),
+}
+
+/// An encoded canvas image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanvasSnapshot {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A decoded canvas image, 8-bit RGBA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanvasImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl CanvasImage {
+    /// Decode a PNG of any color type into RGBA
+    pub fn decode_png(data: &[u8]) -> Result<Self, CanvasError> {
+        let mut decoder = png::Decoder::new(io::Cursor::new(data));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let size = reader.output_buffer_size().ok_or(png::DecodingError::LimitsExceeded)?;
+        let mut buf = vec![0; size];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+
+        let rgba = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 0xff]).collect(),
+            // EXPAND turns palettes into RGB(A)
+            png::ColorType::Indexed => unreachable!("indexed images are expanded"),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            rgba,
+        })
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, CanvasError> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(out)
+    }
+
+    /// Replace the pixels under `patch`, placed at (x, y); parts outside the
+    /// canvas are clipped
+    pub fn draw(&mut self, patch: &CanvasImage, x: u32, y: u32) {
+        let columns = patch.width.min(self.width.saturating_sub(x)) as usize;
+        let rows = patch.height.min(self.height.saturating_sub(y));
+        for row in 0..rows {
+            let src = (row * patch.width) as usize * 4;
+            let dst = (((y + row) * self.width + x) as usize) * 4;
+            self.rgba[dst..dst + columns * 4].copy_from_slice(&patch.rgba[src..src + columns * 4]);
+        }
+    }
+}
+
+/// The last full snapshot of a canvas and the deltas recorded since
+#[derive(Debug, Clone, Default)]
+struct CanvasHistory {
+    base: Option<CanvasChangedData>,
+    deltas: Vec<CanvasChangedDeltaData>,
+}
+
+/// Tracks canvas frames so any canvas can be materialized at the current
+/// point of a recording
+#[derive(Debug, Default)]
+pub struct CanvasCompositor {
+    canvases: HashMap<u32, CanvasHistory>,
+}
+
+impl CanvasCompositor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_frame(&mut self, frame: &Frame) {
+        match frame {
+            // A new document has new canvases
+            Frame::Keyframe(_) | Frame::KeyframeBegin(_) => self.canvases.clear(),
+            Frame::CanvasChanged(changed) => {
+                self.canvases.insert(
+                    changed.node_id,
+                    CanvasHistory {
+                        base: Some(changed.clone()),
+                        deltas: Vec::new(),
+                    },
+                );
+            }
+            Frame::CanvasChangedDelta(delta) => {
+                self.canvases
+                    .entry(delta.node_id)
+                    .or_default()
+                    .deltas
+                    .push(delta.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// The current image of canvas `node_id`, or None if it was never drawn
+    ///
+    /// A snapshot without later deltas is returned as it was recorded;
+    /// otherwise the result is a PNG.
+    pub fn snapshot(&self, node_id: u32) -> Result<Option<CanvasSnapshot>, CanvasError> {
+        let Some(history) = self.canvases.get(&node_id) else {
+            return Ok(None);
+        };
+        let Some(base) = &history.base else {
+            // Deltas without a base (a truncated recording) have nothing to patch
+            return Ok(None);
+        };
+        if history.deltas.is_empty() {
+            return Ok(Some(CanvasSnapshot {
+                mime_type: base.mime_type.clone(),
+                data: base.data.clone(),
+            }));
+        }
+
+        let mut image = decode(&base.mime_type, &base.data)?;
+        for delta in &history.deltas {
+            for patch in &delta.patches {
+                image.draw(&decode(&patch.mime_type, &patch.data)?, patch.x, patch.y);
+            }
+        }
+        Ok(Some(CanvasSnapshot {
+            mime_type: "image/png".to_string(),
+            data: image.encode_png()?,
+        }))
+    }
+}
+
+fn decode(mime_type: &str, data: &[u8]) -> Result<CanvasImage, CanvasError> {
+    if mime_type != "image/png" {
+        return Err(CanvasError::UnsupportedMime(mime_type.to_string()));
+    }
+    CanvasImage::decode_png(data)
+}
+
+/// The image of canvas `node_id` as of timestamp `at` (ms since the epoch),
+/// or at the end of the recording
+pub async fn materialize_canvas<R: AsyncRead + Unpin>(
+    reader: &mut FrameReader<R>,
+    node_id: u32,
+    at: Option<u64>,
+) -> Result<Option<CanvasSnapshot>, CanvasError> {
+    let mut compositor = CanvasCompositor::new();
+    while let Some(frame) = reader.read_frame().await? {
+        if let (Frame::Timestamp(timestamp), Some(at)) = (&frame, at)
+            && timestamp.timestamp > at
+        {
+            break;
+        }
+        compositor.push_frame(&frame);
+    }
+    compositor.snapshot(node_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{CanvasPatchData, KeyframeData, VDocument};
+
+    fn solid(width: u32, height: u32, pixel: [u8; 4]) -> CanvasImage {
+        CanvasImage {
+            width,
+            height,
+            rgba: pixel.repeat((width * height) as usize),
+        }
+    }
+
+    fn changed(image: &CanvasImage) -> Frame {
+        Frame::CanvasChanged(CanvasChangedData {
+            node_id: 5,
+            mime_type: "image/png".to_string(),
+            data: image.encode_png().unwrap(),
+        })
+    }
+
+    fn delta(patch: &CanvasImage, x: u32, y: u32) -> Frame {
+        Frame::CanvasChangedDelta(CanvasChangedDeltaData {
+            node_id: 5,
+            patches: vec![CanvasPatchData {
+                x,
+                y,
+                width: patch.width,
+                height: patch.height,
+                mime_type: "image/png".to_string(),
+                data: patch.encode_png().unwrap(),
+            }],
+        })
+    }
+
+    #[test]
+    fn test_deltas_are_drawn_over_the_last_snapshot() {
+        let red = [0xff, 0, 0, 0xff];
+        let blue = [0, 0, 0xff, 0xff];
+        let mut compositor = CanvasCompositor::new();
+        compositor.push_frame(&changed(&solid(4, 4, red)));
+        // The second patch hangs off the right edge and is clipped
+        compositor.push_frame(&delta(&solid(2, 1, blue), 1, 2));
+        compositor.push_frame(&delta(&solid(2, 2, blue), 3, 0));
+
+        let snapshot = compositor.snapshot(5).unwrap().unwrap();
+        assert_eq!(snapshot.mime_type, "image/png");
+        let image = CanvasImage::decode_png(&snapshot.data).unwrap();
+        let mut expected = solid(4, 4, red);
+        expected.draw(&solid(2, 1, blue), 1, 2);
+        expected.draw(&solid(1, 2, blue), 3, 0);
+        assert_eq!(image, expected);
+        assert_eq!(&image.rgba[(2 * 4 + 1) * 4..(2 * 4 + 1) * 4 + 4], &blue);
+    }
+
+    #[test]
+    fn test_snapshots_reset_with_new_documents() {
+        let mut compositor = CanvasCompositor::new();
+        let base = changed(&solid(2, 2, [0, 0xff, 0, 0xff]));
+        compositor.push_frame(&base);
+        let Frame::CanvasChanged(recorded) = &base else {
+            unreachable!()
+        };
+        assert_eq!(compositor.snapshot(5).unwrap().unwrap().data, recorded.data);
+
+        compositor.push_frame(&Frame::Keyframe(KeyframeData {
+            document: VDocument {
+                id: 0,
+                adopted_style_sheets: Vec::new(),
+                children: Vec::new(),
+            },
+            viewport_width: 800,
+            viewport_height: 600,
+        }));
+        compositor.push_frame(&delta(&solid(1, 1, [0; 4]), 0, 0));
+        assert_eq!(compositor.snapshot(5).unwrap(), None);
+        assert_eq!(compositor.snapshot(6).unwrap(), None);
+    }
+
+    #[test]
+    fn test_patches_need_png() {
+        let mut compositor = CanvasCompositor::new();
+        compositor.push_frame(&Frame::CanvasChanged(CanvasChangedData {
+            node_id: 5,
+            mime_type: "image/webp".to_string(),
+            data: vec![1, 2, 3],
+        }));
+        compositor.push_frame(&delta(&solid(1, 1, [0; 4]), 0, 0));
+        assert!(matches!(compositor.snapshot(5), Err(CanvasError::UnsupportedMime(m)) if m == "image/webp"));
+    }
+}
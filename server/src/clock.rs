@@ -0,0 +1,251 @@
+//! Recorder clock skew, measured with HeartbeatPing/HeartbeatPong round trips
+//!
+//! On the recorder's heartbeat interval the server sends a HeartbeatPing with
+//! its own time, and the recorder echoes it in a HeartbeatPong with its clock
+//! reading. When the pong arrives the round trip is known, and assuming both
+//! legs take as long, so is how far the recorder's clock is off. Queueing only
+//! ever adds latency, so the round trip with the lowest latency gives the best
+//! estimate and is the one kept.
+//!
+//! Recorder clocks also jump: NTP corrections and suspend/resume move them
+//! forwards or backwards mid-recording, which shows up in playback as negative
+//! or giant gaps. [`TimelineNormalizer`] rewrites Timestamp frames onto a
+//! monotonic timeline anchored to the server's clock as they are ingested.
+
+use domcorder_proto::{Frame, HeartbeatPongData, TimestampData};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How far a recording's timestamps are ahead of the server's clock
+///
+/// Without timestamp normalization this is the recorder's clock skew; with it,
+/// only what anchoring the timeline at arrival times left over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSkew {
+    /// Recorder time minus server time; negative if the recorder is behind
+    pub skew_ms: i64,
+    /// Round trip of the ping the estimate came from
+    pub round_trip_ms: u64,
+}
+
+impl ClockSkew {
+    /// The estimate from a pong that arrived at server time `received_at`
+    pub fn measure(pong: &HeartbeatPongData, received_at: u64) -> Self {
+        let round_trip_ms = received_at.saturating_sub(pong.server_timestamp);
+        let server_time_at_pong = pong.server_timestamp + round_trip_ms / 2;
+        Self {
+            skew_ms: pong.client_timestamp as i64 - server_time_at_pong as i64,
+            round_trip_ms,
+        }
+    }
+
+    /// A recorder timestamp on the server's clock
+    pub fn to_server_time(&self, timestamp: u64) -> u64 {
+        timestamp.saturating_add_signed(-self.skew_ms)
+    }
+
+    /// Move a Timestamp frame onto the server's clock; other frames are
+    /// returned unchanged
+    pub fn correct_frame(&self, frame: Frame) -> Frame {
+        match frame {
+            Frame::Timestamp(data) => Frame::Timestamp(TimestampData {
+                timestamp: self.to_server_time(data.timestamp),
+            }),
+            frame => frame,
+        }
+    }
+}
+
+/// Keeps the lowest-latency skew estimate of one recording
+#[derive(Debug, Default)]
+pub struct ClockSkewEstimator {
+    best: Option<ClockSkew>,
+}
+
+impl ClockSkewEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a measurement, returning true if it became the estimate
+    pub fn push(&mut self, sample: ClockSkew) -> bool {
+        if self.best.is_some_and(|best| best.round_trip_ms <= sample.round_trip_ms) {
+            return false;
+        }
+        self.best = Some(sample);
+        true
+    }
+
+    pub fn estimate(&self) -> Option<ClockSkew> {
+        self.best
+    }
+}
+
+/// How far ahead of its arrival a timestamp may appear on the timeline before
+/// the recorder's clock is taken to have jumped forwards
+pub const CLOCK_JUMP_TOLERANCE_MS: u64 = 2_000;
+
+/// Rewrites one recording's timestamps onto a monotonic timeline anchored to
+/// the server's clock
+///
+/// The first timestamp is placed at the time it arrived, and later ones keep
+/// their distance from it as measured by the recorder, so gaps between frames
+/// are the recorder's. A frame cannot have been recorded after it arrived, so
+/// a timestamp that lands more than [`CLOCK_JUMP_TOLERANCE_MS`] after its
+/// arrival means the recorder's clock jumped forwards; it is re-anchored at
+/// the arrival. Frames that were buffered (e.g. while reconnecting) arrive
+/// late, which is fine. A timestamp before the previous one means the clock
+/// jumped backwards; it is re-anchored at the previous timestamp.
+#[derive(Debug, Default)]
+pub struct TimelineNormalizer {
+    /// Recorder time minus timeline time, once the first timestamp is seen
+    offset: Option<i64>,
+    last: u64,
+}
+
+impl TimelineNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The timeline time of recorder timestamp `timestamp`, which arrived at
+    /// server time `arrived_at`
+    ///
+    /// Without an arrival time (a recording uploaded after the fact) the
+    /// timeline starts at the recorder's first timestamp and only backwards
+    /// jumps are corrected.
+    pub fn normalize(&mut self, timestamp: u64, arrived_at: Option<u64>) -> u64 {
+        let offset = *self
+            .offset
+            .get_or_insert_with(|| timestamp as i64 - arrived_at.unwrap_or(timestamp) as i64);
+        let mut normalized = timestamp.saturating_add_signed(-offset);
+        if let Some(arrived_at) = arrived_at
+            && normalized > arrived_at + CLOCK_JUMP_TOLERANCE_MS
+        {
+            normalized = arrived_at;
+        }
+        normalized = normalized.max(self.last);
+        self.offset = Some(timestamp as i64 - normalized as i64);
+        self.last = normalized;
+        normalized
+    }
+
+    /// Where recorder time `timestamp` falls on the timeline now, without
+    /// advancing it (e.g. for the recorder's clock reading in a pong)
+    pub fn to_timeline(&self, timestamp: u64) -> u64 {
+        timestamp.saturating_add_signed(-self.offset.unwrap_or(0))
+    }
+
+    /// Rewrite a Timestamp frame onto the timeline; other frames are returned
+    /// unchanged
+    pub fn normalize_frame(&mut self, frame: Frame, arrived_at: Option<u64>) -> Frame {
+        match frame {
+            Frame::Timestamp(data) => Frame::Timestamp(TimestampData {
+                timestamp: self.normalize(data.timestamp, arrived_at),
+            }),
+            frame => frame,
+        }
+    }
+}
+
+/// Server time in ms since the epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pong(server_timestamp: u64, client_timestamp: u64) -> HeartbeatPongData {
+        HeartbeatPongData {
+            server_timestamp,
+            client_timestamp,
+        }
+    }
+
+    #[test]
+    fn test_skew_assumes_symmetric_legs() {
+        // Sent at 1000, answered by a clock 5s ahead, back at 1100
+        let skew = ClockSkew::measure(&pong(1_000, 6_050), 1_100);
+        assert_eq!(
+            skew,
+            ClockSkew {
+                skew_ms: 5_000,
+                round_trip_ms: 100,
+            }
+        );
+        assert_eq!(skew.to_server_time(6_050), 1_050);
+
+        let behind = ClockSkew::measure(&pong(1_000, 50), 1_100);
+        assert_eq!(behind.skew_ms, -1_000);
+        assert_eq!(behind.to_server_time(50), 1_050);
+        assert_eq!(
+            behind.correct_frame(Frame::Timestamp(TimestampData { timestamp: 50 })),
+            Frame::Timestamp(TimestampData { timestamp: 1_050 })
+        );
+    }
+
+    #[test]
+    fn test_estimator_keeps_the_fastest_round_trip() {
+        let mut estimator = ClockSkewEstimator::new();
+        assert!(estimator.push(ClockSkew::measure(&pong(1_000, 6_400), 1_800)));
+        assert!(estimator.push(ClockSkew::measure(&pong(2_000, 7_020), 2_040)));
+        // A queued pong makes the recorder look further off than it is
+        assert!(!estimator.push(ClockSkew::measure(&pong(3_000, 8_100), 3_900)));
+        assert_eq!(
+            estimator.estimate(),
+            Some(ClockSkew {
+                skew_ms: 5_000,
+                round_trip_ms: 40,
+            })
+        );
+    }
+
+    #[test]
+    fn test_timeline_is_anchored_to_arrival() {
+        let mut timeline = TimelineNormalizer::new();
+        // A recorder 5s ahead: its gaps are kept, its offset is not
+        assert_eq!(timeline.normalize(6_000, Some(1_050)), 1_050);
+        assert_eq!(timeline.normalize(6_400, Some(1_460)), 1_450);
+        assert_eq!(timeline.to_timeline(6_500), 1_550);
+        // Frames buffered during a reconnect arrive late but keep their gaps
+        assert_eq!(timeline.normalize(7_000, Some(9_000)), 2_050);
+        assert_eq!(timeline.normalize(7_200, Some(9_000)), 2_250);
+    }
+
+    #[test]
+    fn test_timeline_absorbs_clock_jumps() {
+        const T: u64 = 1_700_000_000_000;
+        const HOUR: u64 = 3_600_000;
+        let mut timeline = TimelineNormalizer::new();
+        assert_eq!(timeline.normalize(T, Some(T)), T);
+        // NTP sets the clock back an hour: no negative gap, and later frames
+        // continue from where the timeline was
+        assert_eq!(timeline.normalize(T + 500 - HOUR, Some(T + 500)), T);
+        assert_eq!(timeline.normalize(T + 1_000 - HOUR, Some(T + 1_000)), T + 500);
+        // ...and forward a day: re-anchored at the arrival, not a giant gap
+        assert_eq!(timeline.normalize(T + 2_000 + 24 * HOUR, Some(T + 2_000)), T + 2_000);
+        assert_eq!(timeline.normalize(T + 2_300 + 24 * HOUR, Some(T + 2_300)), T + 2_300);
+        assert_eq!(
+            timeline.normalize_frame(
+                Frame::Timestamp(TimestampData {
+                    timestamp: T + 2_400 + 24 * HOUR,
+                }),
+                Some(T + 2_400)
+            ),
+            Frame::Timestamp(TimestampData { timestamp: T + 2_400 })
+        );
+    }
+
+    #[test]
+    fn test_timeline_without_arrival_times_is_monotonic() {
+        let mut timeline = TimelineNormalizer::new();
+        assert_eq!(timeline.normalize(5_000, None), 5_000);
+        assert_eq!(timeline.normalize(4_000, None), 5_000);
+        assert_eq!(timeline.normalize(4_250, None), 5_250);
+    }
+}
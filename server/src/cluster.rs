@@ -0,0 +1,176 @@
+//! Shared registry of active recordings
+//!
+//! Several servers can run behind a load balancer over shared storage. Each
+//! keeps the recordings its own sessions are writing in
+//! [`StorageState::active_recordings`], with the live frame channels only it
+//! can serve, and also holds a lease on each in the metadata store. A
+//! recording is claimed when its session starts and the claim is refused
+//! while another node's lease is unexpired, so two nodes never write the
+//! same recording.
+//!
+//! A heartbeat renews this node's leases every third of the lease period,
+//! releases those of finished recordings, and takes a snapshot of the
+//! recordings other nodes are writing. Every node then agrees on what's live:
+//! listings mark them active, deleting and moving them is refused, and
+//! playback follows them as they grow whichever node is asked. The leases of
+//! a node that stops without releasing them expire, so its recordings stop
+//! counting as live after at most one lease period.
+
+use crate::{AppState, RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// How long a lease lasts without being renewed, by default
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(30);
+
+/// A node's claim on a recording it is writing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveLease {
+    pub recording_id: String,
+    /// The node writing the recording
+    pub node_id: String,
+    /// Most recent Timestamp frame value written, for live playback
+    pub latest_timestamp: Option<u64>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// This node's identity and lease period
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterConfig {
+    /// Unique among the nodes sharing the metadata store
+    pub node_id: String,
+    pub lease: Duration,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            node_id: uuid::Uuid::new_v4().to_string(),
+            lease: DEFAULT_LEASE,
+        }
+    }
+}
+
+/// This node's view of the shared registry
+#[derive(Debug, Default)]
+pub struct ActiveRegistry {
+    pub config: ClusterConfig,
+    /// The recordings other nodes were writing at the last heartbeat
+    remote: Mutex<HashMap<RecordingId, ActiveLease>>,
+    /// Recordings this node finished whose leases haven't been released yet
+    released: Mutex<Vec<RecordingId>>,
+    /// Runs the heartbeat early, to release a lease promptly
+    wake: Notify,
+}
+
+impl ActiveRegistry {
+    pub fn new(config: ClusterConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    fn lease(&self, id: &RecordingId, latest_timestamp: Option<u64>) -> ActiveLease {
+        let lease = chrono::Duration::from_std(self.config.lease).unwrap_or(chrono::Duration::MAX);
+        ActiveLease {
+            recording_id: id.to_string(),
+            node_id: self.config.node_id.clone(),
+            latest_timestamp,
+            expires_at: Utc::now() + lease,
+        }
+    }
+
+    /// The unexpired lease another node holds on `id`, as of the last heartbeat
+    pub fn remote_lease(&self, id: &RecordingId) -> Option<ActiveLease> {
+        let remote = self.remote.lock().unwrap();
+        remote.get(id).filter(|lease| lease.expires_at > Utc::now()).cloned()
+    }
+
+    /// Release the lease on `id` at the next heartbeat, which is run now
+    pub(crate) fn release(&self, id: &RecordingId) {
+        self.released.lock().unwrap().push(id.clone());
+        self.wake.notify_one();
+    }
+}
+
+impl StorageState {
+    /// Set this node's identity and lease period
+    pub fn with_cluster(mut self, config: ClusterConfig) -> Self {
+        self.active_registry = ActiveRegistry::new(config);
+        self
+    }
+
+    /// Take the lease on `id` for this node, failing with
+    /// [`StorageError::InUse`] if another node is writing it
+    pub(crate) async fn claim_active_lease(&self, id: &RecordingId) -> Result<(), StorageError> {
+        let lease = self.active_registry.lease(id, None);
+        if !self.metadata_store.claim_active_recording(&lease).await? {
+            return Err(StorageError::InUse(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// One heartbeat: release the leases of finished recordings, renew the
+    /// others, and refresh the snapshot of what other nodes are writing
+    pub async fn sync_active_registry(&self) -> Result<(), StorageError> {
+        let registry = &self.active_registry;
+        let released = std::mem::take(&mut *registry.released.lock().unwrap());
+        for id in released {
+            // Finished and started again since
+            if self.active_recordings.lock().unwrap().contains_key(&id) {
+                continue;
+            }
+            self.metadata_store
+                .release_active_recording(id.as_str(), &registry.config.node_id)
+                .await?;
+        }
+
+        let leases: Vec<ActiveLease> = {
+            let active_recordings = self.active_recordings.lock().unwrap();
+            active_recordings
+                .iter()
+                .map(|(id, info)| registry.lease(id, info.latest_timestamp))
+                .collect()
+        };
+        for lease in leases {
+            if !self.metadata_store.claim_active_recording(&lease).await? {
+                warn!(
+                    "⚠️ Lost the lease on {} to another node; is the lease period too short?",
+                    lease.recording_id
+                );
+            }
+        }
+
+        let remote = self
+            .metadata_store
+            .list_active_recordings()
+            .await?
+            .into_iter()
+            .filter(|lease| lease.node_id != registry.config.node_id)
+            .filter_map(|lease| Some((RecordingId::new(lease.recording_id.as_str()).ok()?, lease)))
+            .collect();
+        *registry.remote.lock().unwrap() = remote;
+        Ok(())
+    }
+}
+
+/// Run the heartbeat until the process exits; failures are logged and retried
+/// at the next beat
+pub async fn run_active_registry(state: AppState) {
+    let interval = state.active_registry.config.lease / 3;
+    loop {
+        if let Err(e) = state.sync_active_registry().await {
+            warn!("⚠️ Failed to sync the active recording registry: {}", e);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = state.active_registry.wake.notified() => {}
+        }
+    }
+}
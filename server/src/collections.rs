@@ -0,0 +1,76 @@
+//! Named collections of recordings
+//!
+//! Collections group recordings under a name (e.g. "checkout bug repros") so
+//! they can be found again without renaming or moving the files. They are
+//! kept in the metadata store, edited through `/collections`, and filter the
+//! recordings list with `GET /recordings?collection=name`. A recording can be
+//! in any number of collections and leaves them all when it is deleted.
+
+use crate::{RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Longest collection name accepted, in bytes
+pub const MAX_COLLECTION_NAME_BYTES: usize = 100;
+
+/// A named group of recordings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    pub description: Option<String>,
+    pub created: DateTime<Utc>,
+    /// Member recording ids, in the order they were added
+    pub recording_ids: Vec<String>,
+}
+
+/// Whether `name` can name a collection: non-empty, without surrounding
+/// whitespace or control characters, and at most [`MAX_COLLECTION_NAME_BYTES`]
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_COLLECTION_NAME_BYTES
+        && name.trim() == name
+        && !name.chars().any(char::is_control)
+}
+
+impl StorageState {
+    /// The collection called `name`, failing with
+    /// [`StorageError::CollectionNotFound`] if there is none
+    pub async fn collection(&self, name: &str) -> Result<Collection, StorageError> {
+        self.metadata_store
+            .get_collection(name)
+            .await?
+            .ok_or_else(|| StorageError::CollectionNotFound(name.to_string()))
+    }
+
+    /// Add recording `id` to collection `name` (or take it out), returning
+    /// the collection as it now is
+    pub async fn set_collection_member(
+        &self,
+        name: &str,
+        id: &RecordingId,
+        member: bool,
+    ) -> Result<Collection, StorageError> {
+        self.collection(name).await?;
+        if member && !self.recording_exists(id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        self.metadata_store
+            .set_collection_member(name, id.as_str(), member)
+            .await?;
+        self.collection(name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_name() {
+        assert!(valid_name("checkout bug repros"));
+        assert!(!valid_name(""));
+        assert!(!valid_name(" padded"));
+        assert!(!valid_name("line\nbreak"));
+        assert!(!valid_name(&"x".repeat(MAX_COLLECTION_NAME_BYTES + 1)));
+    }
+}
@@ -0,0 +1,53 @@
+//! Reviewer comments pinned to moments in recordings
+//!
+//! Comments are kept in the metadata store, not in the recording, and are
+//! anchored by offset on the same recorded-time scale as the timeline, so a
+//! player can show them alongside timeline events. They are returned by
+//! `GET /recording/{id}/info` and forgotten when the recording is deleted.
+
+use crate::{RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Longest comment accepted, in bytes
+pub const MAX_COMMENT_BYTES: usize = 4096;
+
+/// A note left on a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingComment {
+    /// Assigned by the metadata store; 0 until the comment is stored
+    pub id: i64,
+    pub recording_id: String,
+    /// Milliseconds of recorded time since the first timestamp, as in timeline offsets
+    pub offset_ms: u64,
+    /// Who left it, as given by the caller
+    pub author: Option<String>,
+    pub text: String,
+    pub created: DateTime<Utc>,
+}
+
+impl StorageState {
+    /// Store a comment on recording `id` at `offset_ms`, returning it with
+    /// its assigned id
+    pub async fn add_comment(
+        &self,
+        id: &RecordingId,
+        offset_ms: u64,
+        author: Option<String>,
+        text: String,
+    ) -> Result<RecordingComment, StorageError> {
+        if !self.recording_exists(id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        let mut comment = RecordingComment {
+            id: 0,
+            recording_id: id.to_string(),
+            offset_ms,
+            author,
+            text,
+            created: Utc::now(),
+        };
+        comment.id = self.metadata_store.add_comment(&comment).await?;
+        Ok(comment)
+    }
+}
@@ -0,0 +1,271 @@
+//! Live co-viewing of recordings
+//!
+//! `GET /ws/recording/{id}` plays a recording over a WebSocket. Binary
+//! messages carry a PlaybackConfig frame followed by the recording's frames;
+//! while the recording is active, each frame is passed on as soon as it has
+//! been ingested instead of once it reaches the recording file. A viewer that
+//! falls too far behind continues from the file, so it sees every frame.
+//!
+//! Viewers of the same recording share a room. A viewer may send JSON text
+//! messages with its cursor position (`{"type":"cursor","x":..,"y":..}`) or a
+//! comment (`{"type":"comment","text":".."}`); the server labels them with
+//! the viewer's name and passes them on to the other viewers, along with
+//! `joined` and `left` events. Viewer events never enter the recording.
+
+use crate::server::{playback_clock_skew, playback_config};
+use crate::{AppState, RecordingId};
+use axum::extract::ws::{Message, WebSocket};
+use domcorder_proto::{Frame, FrameReader, FrameWriter};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncRead;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+/// Frames held for live viewers of one recording before the slowest falls
+/// back to reading the recording file
+pub const LIVE_FRAME_BUFFER: usize = 1024;
+
+/// Viewer events held for each room before slow viewers miss some
+const VIEWER_EVENT_BUFFER: usize = 256;
+
+/// A message from a viewer to the others
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ViewerMessage {
+    /// Pointer position, in the recorded page's coordinates
+    Cursor {
+        x: f64,
+        y: f64,
+    },
+    Comment {
+        text: String,
+    },
+}
+
+/// An event sent to the viewers of a recording
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ViewerEvent {
+    Joined { viewer: String },
+    Left { viewer: String },
+    Cursor { viewer: String, x: f64, y: f64 },
+    Comment { viewer: String, text: String },
+}
+
+impl ViewerMessage {
+    fn into_event(self, viewer: &str) -> ViewerEvent {
+        let viewer = viewer.to_string();
+        match self {
+            ViewerMessage::Cursor { x, y } => ViewerEvent::Cursor { viewer, x, y },
+            ViewerMessage::Comment { text } => ViewerEvent::Comment { viewer, text },
+        }
+    }
+}
+
+/// Viewer events, with the connection that sent them
+type RoomSender = broadcast::Sender<(u64, ViewerEvent)>;
+
+/// The viewers of each recording that has any
+#[derive(Debug, Default)]
+pub struct ViewerRooms {
+    rooms: Mutex<HashMap<RecordingId, RoomSender>>,
+    next_connection: AtomicU64,
+}
+
+impl ViewerRooms {
+    /// Join the room of recording `id`, returning the new connection's number
+    fn join(&self, id: &RecordingId) -> (u64, RoomSender, broadcast::Receiver<(u64, ViewerEvent)>) {
+        let connection = self.next_connection.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut rooms = self.rooms.lock().unwrap();
+        let room = rooms
+            .entry(id.clone())
+            .or_insert_with(|| broadcast::channel(VIEWER_EVENT_BUFFER).0);
+        (connection, room.clone(), room.subscribe())
+    }
+
+    /// Close the room of recording `id` once its last viewer has left
+    fn leave(&self, id: &RecordingId) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if rooms.get(id).is_some_and(|room| room.receiver_count() == 0) {
+            rooms.remove(id);
+        }
+    }
+
+    /// Number of viewers watching recording `id`
+    pub fn viewer_count(&self, id: &RecordingId) -> usize {
+        let rooms = self.rooms.lock().unwrap();
+        rooms.get(id).map_or(0, |room| room.receiver_count())
+    }
+}
+
+/// A recording's frames: read from the file up to where the live frames
+/// start, then taken from ingestion while the recording is active
+struct LiveFrames {
+    file: FrameReader<Box<dyn AsyncRead + Unpin + Send>>,
+    /// Frames read from the file
+    read: u64,
+    /// Frames returned
+    delivered: u64,
+    /// Live frames, and how many frames were written before the first
+    live: Option<(u64, broadcast::Receiver<Arc<Frame>>)>,
+}
+
+impl LiveFrames {
+    async fn open(state: AppState, id: &RecordingId) -> Result<Self, crate::StorageError> {
+        // Subscribe first, so every frame is either in the file or received live
+        let live = state.subscribe_live_frames(id);
+        let file = state.get_recording_stream(id).await?;
+        Ok(Self {
+            file: FrameReader::new(file, false).with_index(true),
+            read: 0,
+            delivered: 0,
+            live,
+        })
+    }
+
+    /// The next frame, or None once a finished recording has been read
+    async fn next(&mut self) -> Option<io::Result<Frame>> {
+        loop {
+            if let Some((start, live)) = &mut self.live
+                && self.read >= *start
+            {
+                match live.recv().await {
+                    Ok(frame) => {
+                        self.delivered += 1;
+                        return Some(Ok(Arc::unwrap_or_clone(frame)));
+                    }
+                    // The viewer fell behind, or the recording finished:
+                    // the rest is read from the file
+                    Err(_) => self.live = None,
+                }
+                continue;
+            }
+            match self.file.read_frame().await {
+                Ok(Some(frame)) => {
+                    self.read += 1;
+                    // Frames already received live are skipped
+                    if self.read > self.delivered {
+                        self.delivered += 1;
+                        return Some(Ok(frame));
+                    }
+                }
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Play recording `id` to one viewer and relay its events to the others
+pub async fn handle_viewer(socket: WebSocket, state: AppState, id: RecordingId, name: Option<String>) {
+    let skew = playback_clock_skew(&state, &id).await;
+    let config = playback_config(&state, &id, skew).await;
+    let mut frames = match LiveFrames::open(state.clone(), &id).await {
+        Ok(frames) => frames,
+        Err(e) => {
+            warn!("Failed to open {} for viewing: {}", id, e);
+            return;
+        }
+    };
+
+    let (connection, room, mut events) = state.viewer_rooms.join(&id);
+    let name = name.unwrap_or_else(|| format!("viewer-{}", connection));
+    info!("👀 {} is viewing {}", name, id);
+    let (mut sender, mut receiver) = socket.split();
+    let _ = room.send((connection, ViewerEvent::Joined { viewer: name.clone() }));
+
+    let mut next_frame = Some(config);
+    let mut frames_done = false;
+    loop {
+        if let Some(frame) = next_frame.take() {
+            let frame = match skew {
+                Some(skew) => skew.correct_frame(frame),
+                None => frame,
+            };
+            let mut buffer = Vec::new();
+            if let Err(e) = FrameWriter::new(&mut buffer).write_frame(&frame) {
+                warn!("Failed to encode frame for viewer: {}", e);
+                break;
+            }
+            if sender.send(Message::Binary(buffer.into())).await.is_err() {
+                break;
+            }
+        }
+
+        tokio::select! {
+            frame = frames.next(), if !frames_done => match frame {
+                Some(Ok(frame)) => next_frame = Some(frame),
+                Some(Err(e)) => {
+                    warn!("Failed to decode frame of {} for viewer: {}", id, e);
+                    frames_done = true;
+                }
+                // Viewers stay connected to talk about a finished recording
+                None => frames_done = true,
+            },
+            event = events.recv() => match event {
+                Ok((from, event)) if from != connection => {
+                    let text = serde_json::to_string(&event).expect("viewer events serialize");
+                    if sender.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    debug!("{} missed {} viewer events", name, missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ViewerMessage>(&text) {
+                    Ok(message) => {
+                        let _ = room.send((connection, message.into_event(&name)));
+                    }
+                    Err(e) => debug!("Ignoring viewer message from {}: {}", name, e),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    info!("👋 {} stopped viewing {}", name, id);
+    let _ = room.send((connection, ViewerEvent::Left { viewer: name }));
+    drop(events);
+    drop(room);
+    state.viewer_rooms.leave(&id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewer_messages() {
+        let message: ViewerMessage = serde_json::from_str(r#"{"type":"cursor","x":10,"y":20.5}"#).unwrap();
+        assert_eq!(message, ViewerMessage::Cursor { x: 10.0, y: 20.5 });
+        let event = serde_json::to_value(message.into_event("agent")).unwrap();
+        assert_eq!(
+            event,
+            serde_json::json!({"type": "cursor", "viewer": "agent", "x": 10.0, "y": 20.5})
+        );
+        assert!(serde_json::from_str::<ViewerMessage>(r#"{"type":"frame"}"#).is_err());
+    }
+
+    #[test]
+    fn test_rooms_close_when_empty() {
+        let rooms = ViewerRooms::default();
+        let id = RecordingId::new("live.dcrr").unwrap();
+        let (first, room, events) = rooms.join(&id);
+        let (second, _, _other) = rooms.join(&id);
+        assert_ne!(first, second);
+        assert_eq!(rooms.viewer_count(&id), 2);
+        drop((room, events));
+        rooms.leave(&id);
+        assert_eq!(rooms.viewer_count(&id), 1);
+    }
+}
@@ -0,0 +1,645 @@
+//! Structural comparison of two recordings
+//!
+//! Replays both recordings through a [`VDomEngine`], samples the DOM at aligned
+//! points (relative time offsets or keyframe boundaries) and reports structural
+//! differences between the two documents at each point. Node ids are never
+//! compared since they are assigned independently per recording.
+
+use domcorder_proto::{Frame, FrameReader, VDocument, VDomEngine, VNode};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::io::AsyncRead;
+use tracing::debug;
+
+/// Default spacing between time-aligned samples
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+/// Default cap on the number of aligned points compared
+const DEFAULT_MAX_POINTS: usize = 100;
+
+/// Default cap on differences reported per aligned point
+const DEFAULT_MAX_DIFFERENCES: usize = 200;
+
+/// Most aligned points a request may ask for; each holds a copy of both documents
+pub const MAX_POINTS: usize = 1000;
+
+/// Bounds on the requested spacing between time-aligned samples
+pub const MIN_INTERVAL_MS: u64 = 100;
+pub const MAX_INTERVAL_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// How samples from the two recordings are aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlignmentMode {
+    /// Sample both recordings every `interval_ms` from their first timestamp
+    Time,
+    /// Sample the final DOM state of each keyframe segment (page load/navigation)
+    Keyframes,
+}
+
+/// Options controlling a recording comparison
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub mode: AlignmentMode,
+    pub interval_ms: u64,
+    pub max_points: usize,
+    pub max_differences_per_point: usize,
+    /// Skip text nodes that only contain whitespace
+    pub ignore_whitespace: bool,
+    /// Attribute names excluded from comparison (e.g. nonces, generated ids)
+    pub ignore_attributes: Vec<String>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            mode: AlignmentMode::Time,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            max_points: DEFAULT_MAX_POINTS,
+            max_differences_per_point: DEFAULT_MAX_DIFFERENCES,
+            ignore_whitespace: true,
+            ignore_attributes: Vec::new(),
+        }
+    }
+}
+
+/// Kind of structural difference between the two documents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    /// The aligned point only exists in one recording
+    DocumentMissing,
+    /// Node present in B but not in A
+    NodeAdded,
+    /// Node present in A but not in B
+    NodeRemoved,
+    /// Node at the same position has a different type or tag
+    NodeChanged,
+    AttributeAdded,
+    AttributeRemoved,
+    AttributeChanged,
+    TextChanged,
+}
+
+/// A single difference at an aligned point
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomDifference {
+    /// XPath-like location of the node, e.g. `/html/body/div[2]`
+    pub path: String,
+    pub kind: DifferenceKind,
+    /// Attribute name for attribute differences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub a: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b: Option<String>,
+}
+
+/// Comparison result for one aligned point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlignedPointDiff {
+    pub index: usize,
+    /// Offset from the first timestamp (time alignment) or keyframe index
+    pub offset_ms: Option<u64>,
+    pub a_timestamp: Option<u64>,
+    pub b_timestamp: Option<u64>,
+    pub differences: Vec<DomDifference>,
+    /// Whether `differences` was cut off at `max_differences_per_point`
+    pub truncated: bool,
+}
+
+/// Full comparison report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDiff {
+    pub mode: AlignmentMode,
+    pub points: Vec<AlignedPointDiff>,
+    pub total_differences: usize,
+}
+
+/// DOM state captured at one sample point
+#[derive(Debug, Clone)]
+pub struct DomSample {
+    pub offset_ms: Option<u64>,
+    pub timestamp: Option<u64>,
+    pub document: Option<VDocument>,
+}
+
+/// Replay a frame stream and capture DOM samples according to `options`
+pub async fn collect_samples<R: AsyncRead + Unpin>(
+    reader: &mut FrameReader<R>,
+    options: &DiffOptions,
+) -> io::Result<Vec<DomSample>> {
+    let mut engine = VDomEngine::new();
+    let mut samples = Vec::new();
+    let mut first_timestamp: Option<u64> = None;
+    let mut last_timestamp: Option<u64> = None;
+    let mut next_offset = 0u64;
+    let interval = options.interval_ms.max(1);
+
+    while let Some(frame) = reader.read_frame().await? {
+        if samples.len() >= options.max_points {
+            break;
+        }
+
+        match (&frame, options.mode) {
+            (Frame::Timestamp(ts), AlignmentMode::Time) => {
+                let first = *first_timestamp.get_or_insert(ts.timestamp);
+                let offset = ts.timestamp.saturating_sub(first);
+                // Everything applied so far is the state at every boundary we just passed
+                while offset > next_offset && samples.len() < options.max_points {
+                    samples.push(DomSample {
+                        offset_ms: Some(next_offset),
+                        timestamp: Some(first + next_offset),
+                        document: engine.document(),
+                    });
+                    next_offset += interval;
+                }
+            }
+            (Frame::Keyframe(_) | Frame::KeyframeBegin(_), AlignmentMode::Keyframes) if engine.has_document() => {
+                samples.push(DomSample {
+                    offset_ms: None,
+                    timestamp: last_timestamp,
+                    document: engine.document(),
+                });
+            }
+            _ => {}
+        }
+
+        if let Frame::Timestamp(ts) = &frame {
+            last_timestamp = Some(ts.timestamp);
+        }
+
+        if let Err(e) = engine.apply(&frame) {
+            debug!("Skipping frame that could not be applied during diff: {}", e);
+        }
+    }
+
+    // Capture the final state
+    if samples.len() < options.max_points && engine.has_document() {
+        let offset_ms = match (options.mode, first_timestamp, last_timestamp) {
+            (AlignmentMode::Time, Some(first), Some(last)) => Some(last.saturating_sub(first)),
+            _ => None,
+        };
+        samples.push(DomSample {
+            offset_ms,
+            timestamp: last_timestamp,
+            document: engine.document(),
+        });
+    }
+
+    Ok(samples)
+}
+
+/// Compare two sets of samples point by point
+pub fn diff_samples(a: &[DomSample], b: &[DomSample], options: &DiffOptions) -> SessionDiff {
+    let mut points = Vec::new();
+    let mut total_differences = 0;
+
+    for index in 0..a.len().max(b.len()) {
+        let sample_a = a.get(index);
+        let sample_b = b.get(index);
+        let mut collector = DifferenceCollector::new(options);
+
+        match (
+            sample_a.and_then(|s| s.document.as_ref()),
+            sample_b.and_then(|s| s.document.as_ref()),
+        ) {
+            (Some(doc_a), Some(doc_b)) => {
+                collector.compare_children(&doc_a.children, &doc_b.children, "");
+            }
+            (Some(_), None) => collector.push(DomDifference {
+                path: "/".to_string(),
+                kind: DifferenceKind::DocumentMissing,
+                name: None,
+                a: Some("present".to_string()),
+                b: None,
+            }),
+            (None, Some(_)) => collector.push(DomDifference {
+                path: "/".to_string(),
+                kind: DifferenceKind::DocumentMissing,
+                name: None,
+                a: None,
+                b: Some("present".to_string()),
+            }),
+            (None, None) => {}
+        }
+
+        total_differences += collector.differences.len();
+        points.push(AlignedPointDiff {
+            index,
+            offset_ms: sample_a
+                .and_then(|s| s.offset_ms)
+                .or_else(|| sample_b.and_then(|s| s.offset_ms)),
+            a_timestamp: sample_a.and_then(|s| s.timestamp),
+            b_timestamp: sample_b.and_then(|s| s.timestamp),
+            differences: collector.differences,
+            truncated: collector.truncated,
+        });
+    }
+
+    SessionDiff {
+        mode: options.mode,
+        points,
+        total_differences,
+    }
+}
+
+/// Compare two frame streams end to end
+pub async fn diff_recordings<A: AsyncRead + Unpin, B: AsyncRead + Unpin>(
+    a: &mut FrameReader<A>,
+    b: &mut FrameReader<B>,
+    options: &DiffOptions,
+) -> io::Result<SessionDiff> {
+    let samples_a = collect_samples(a, options).await?;
+    let samples_b = collect_samples(b, options).await?;
+    Ok(diff_samples(&samples_a, &samples_b, options))
+}
+
+struct DifferenceCollector<'a> {
+    options: &'a DiffOptions,
+    differences: Vec<DomDifference>,
+    truncated: bool,
+}
+
+impl<'a> DifferenceCollector<'a> {
+    fn new(options: &'a DiffOptions) -> Self {
+        Self {
+            options,
+            differences: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, difference: DomDifference) {
+        if self.differences.len() >= self.options.max_differences_per_point {
+            self.truncated = true;
+        } else {
+            self.differences.push(difference);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.truncated
+    }
+
+    fn compare_children(&mut self, a: &[VNode], b: &[VNode], parent_path: &str) {
+        let a: Vec<&VNode> = a.iter().filter(|n| self.is_significant(n)).collect();
+        let b: Vec<&VNode> = b.iter().filter(|n| self.is_significant(n)).collect();
+        let paths_a = child_paths(&a, parent_path);
+        let paths_b = child_paths(&b, parent_path);
+
+        for step in align_children(&a, &b) {
+            if self.is_full() {
+                return;
+            }
+            match step {
+                AlignStep::Matched(i, j) => self.compare_nodes(a[i], b[j], &paths_a[i]),
+                AlignStep::OnlyA(i) => self.push(DomDifference {
+                    path: paths_a[i].clone(),
+                    kind: DifferenceKind::NodeRemoved,
+                    name: None,
+                    a: Some(describe(a[i])),
+                    b: None,
+                }),
+                AlignStep::OnlyB(j) => self.push(DomDifference {
+                    path: paths_b[j].clone(),
+                    kind: DifferenceKind::NodeAdded,
+                    name: None,
+                    a: None,
+                    b: Some(describe(b[j])),
+                }),
+            }
+        }
+    }
+
+    fn compare_nodes(&mut self, a: &VNode, b: &VNode, path: &str) {
+        match (a, b) {
+            (VNode::Element(ea), VNode::Element(eb)) if ea.tag.eq_ignore_ascii_case(&eb.tag) => {
+                self.compare_attributes(&ea.attrs, &eb.attrs, path);
+                self.compare_children(&ea.children, &eb.children, path);
+            }
+            (VNode::Text(ta), VNode::Text(tb)) => self.compare_text(&ta.content, &tb.content, path),
+            (VNode::CData(ca), VNode::CData(cb)) => self.compare_text(&ca.content, &cb.content, path),
+            (VNode::Comment(ca), VNode::Comment(cb)) => self.compare_text(&ca.content, &cb.content, path),
+            (VNode::DocType(da), VNode::DocType(db)) if da.name == db.name => {}
+            (VNode::ProcessingInstruction(pa), VNode::ProcessingInstruction(pb))
+                if pa.target == pb.target =>
+            {
+                self.compare_text(&pa.data, &pb.data, path)
+            }
+            _ => self.push(DomDifference {
+                path: path.to_string(),
+                kind: DifferenceKind::NodeChanged,
+                name: None,
+                a: Some(describe(a)),
+                b: Some(describe(b)),
+            }),
+        }
+    }
+
+    fn compare_text(&mut self, a: &str, b: &str, path: &str) {
+        let (a_cmp, b_cmp) = if self.options.ignore_whitespace {
+            (a.trim(), b.trim())
+        } else {
+            (a, b)
+        };
+        if a_cmp != b_cmp {
+            self.push(DomDifference {
+                path: path.to_string(),
+                kind: DifferenceKind::TextChanged,
+                name: None,
+                a: Some(a.to_string()),
+                b: Some(b.to_string()),
+            });
+        }
+    }
+
+    fn compare_attributes(&mut self, a: &[(String, String)], b: &[(String, String)], path: &str) {
+        let ignored = |name: &str| self.options.ignore_attributes.iter().any(|i| i == name);
+
+        for (name, value_a) in a.iter().filter(|(name, _)| !ignored(name)) {
+            match b.iter().find(|(other, _)| other == name) {
+                Some((_, value_b)) if value_a != value_b => self.push(DomDifference {
+                    path: path.to_string(),
+                    kind: DifferenceKind::AttributeChanged,
+                    name: Some(name.clone()),
+                    a: Some(value_a.clone()),
+                    b: Some(value_b.clone()),
+                }),
+                Some(_) => {}
+                None => self.push(DomDifference {
+                    path: path.to_string(),
+                    kind: DifferenceKind::AttributeRemoved,
+                    name: Some(name.clone()),
+                    a: Some(value_a.clone()),
+                    b: None,
+                }),
+            }
+        }
+
+        for (name, value_b) in b.iter().filter(|(name, _)| !ignored(name)) {
+            if !a.iter().any(|(other, _)| other == name) {
+                self.push(DomDifference {
+                    path: path.to_string(),
+                    kind: DifferenceKind::AttributeAdded,
+                    name: Some(name.clone()),
+                    a: None,
+                    b: Some(value_b.clone()),
+                });
+            }
+        }
+    }
+
+    fn is_significant(&self, node: &VNode) -> bool {
+        match node {
+            VNode::Text(text) if self.options.ignore_whitespace => !text.content.trim().is_empty(),
+            _ => true,
+        }
+    }
+}
+
+/// Key used to match children between documents (type + tag + id attribute)
+fn node_key(node: &VNode) -> String {
+    match node {
+        VNode::Element(element) => {
+            let id = element
+                .attrs
+                .iter()
+                .find(|(name, _)| name == "id")
+                .map(|(_, value)| value.as_str())
+                .unwrap_or("");
+            format!("<{}#{}", element.tag.to_ascii_lowercase(), id)
+        }
+        VNode::Text(_) => "#text".to_string(),
+        VNode::CData(_) => "#cdata".to_string(),
+        VNode::Comment(_) => "#comment".to_string(),
+        VNode::DocType(_) => "#doctype".to_string(),
+        VNode::ProcessingInstruction(pi) => format!("?{}", pi.target),
+    }
+}
+
+/// Short human-readable description of a node
+fn describe(node: &VNode) -> String {
+    match node {
+        VNode::Element(element) => format!("<{}>", element.tag.to_ascii_lowercase()),
+        VNode::Text(text) => text.content.clone(),
+        VNode::CData(cdata) => cdata.content.clone(),
+        VNode::Comment(comment) => format!("<!--{}-->", comment.content),
+        VNode::DocType(doctype) => format!("<!DOCTYPE {}>", doctype.name),
+        VNode::ProcessingInstruction(pi) => format!("<?{} {}?>", pi.target, pi.data),
+    }
+}
+
+/// XPath-like paths for a list of siblings, indexing repeated names from 1
+fn child_paths(nodes: &[&VNode], parent_path: &str) -> Vec<String> {
+    let names: Vec<String> = nodes
+        .iter()
+        .map(|node| match node {
+            VNode::Element(element) => element.tag.to_ascii_lowercase(),
+            VNode::Text(_) => "text()".to_string(),
+            VNode::CData(_) => "cdata()".to_string(),
+            VNode::Comment(_) => "comment()".to_string(),
+            VNode::DocType(_) => "doctype()".to_string(),
+            VNode::ProcessingInstruction(_) => "processing-instruction()".to_string(),
+        })
+        .collect();
+
+    let mut seen = std::collections::HashMap::new();
+    names
+        .iter()
+        .map(|name| {
+            let total = names.iter().filter(|other| *other == name).count();
+            let position = seen.entry(name.clone()).or_insert(0usize);
+            *position += 1;
+            if total > 1 {
+                format!("{}/{}[{}]", parent_path, name, position)
+            } else {
+                format!("{}/{}", parent_path, name)
+            }
+        })
+        .collect()
+}
+
+enum AlignStep {
+    Matched(usize, usize),
+    OnlyA(usize),
+    OnlyB(usize),
+}
+
+/// Largest child list product for which we run a full LCS alignment
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// Align two sibling lists using the longest common subsequence of node keys,
+/// so a single inserted node does not cascade into differences for every
+/// following sibling. Falls back to positional alignment for huge lists.
+fn align_children(a: &[&VNode], b: &[&VNode]) -> Vec<AlignStep> {
+    let keys_a: Vec<String> = a.iter().map(|n| node_key(n)).collect();
+    let keys_b: Vec<String> = b.iter().map(|n| node_key(n)).collect();
+    let (n, m) = (keys_a.len(), keys_b.len());
+
+    if n.saturating_mul(m) > MAX_LCS_CELLS {
+        let mut steps: Vec<AlignStep> = (0..n.min(m)).map(|i| AlignStep::Matched(i, i)).collect();
+        steps.extend((m..n).map(AlignStep::OnlyA));
+        steps.extend((n..m).map(AlignStep::OnlyB));
+        return steps;
+    }
+
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if keys_a[i] == keys_b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if keys_a[i] == keys_b[j] {
+            steps.push(AlignStep::Matched(i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            steps.push(AlignStep::OnlyA(i));
+            i += 1;
+        } else {
+            steps.push(AlignStep::OnlyB(j));
+            j += 1;
+        }
+    }
+    steps.extend((i..n).map(AlignStep::OnlyA));
+    steps.extend((j..m).map(AlignStep::OnlyB));
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{VElement, VTextNode};
+
+    fn element(id: u32, tag: &str, attrs: &[(&str, &str)], children: Vec<VNode>) -> VNode {
+        VNode::Element(VElement {
+            id,
+            tag: tag.to_string(),
+            ns: None,
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            children,
+        })
+    }
+
+    fn text(id: u32, content: &str) -> VNode {
+        VNode::Text(VTextNode {
+            id,
+            content: content.to_string(),
+        })
+    }
+
+    fn sample(children: Vec<VNode>) -> DomSample {
+        DomSample {
+            offset_ms: Some(0),
+            timestamp: Some(0),
+            document: Some(VDocument {
+                id: 0,
+                adopted_style_sheets: vec![],
+                children,
+            }),
+        }
+    }
+
+    #[test]
+    fn identical_documents_have_no_differences() {
+        let doc = vec![element(1, "body", &[], vec![text(2, "hi")])];
+        // Different node ids, same structure
+        let other = vec![element(10, "body", &[], vec![text(20, "hi")])];
+        let diff = diff_samples(&[sample(doc)], &[sample(other)], &DiffOptions::default());
+        assert_eq!(diff.total_differences, 0);
+    }
+
+    #[test]
+    fn reports_inserted_node_without_cascading() {
+        let a = vec![element(
+            1,
+            "body",
+            &[],
+            vec![element(2, "p", &[], vec![]), element(3, "div", &[], vec![])],
+        )];
+        let b = vec![element(
+            1,
+            "body",
+            &[],
+            vec![
+                element(4, "span", &[], vec![]),
+                element(2, "p", &[], vec![]),
+                element(3, "div", &[], vec![]),
+            ],
+        )];
+        let diff = diff_samples(&[sample(a)], &[sample(b)], &DiffOptions::default());
+        assert_eq!(diff.total_differences, 1);
+        assert_eq!(diff.points[0].differences[0].kind, DifferenceKind::NodeAdded);
+        assert_eq!(diff.points[0].differences[0].path, "/body/span");
+    }
+
+    #[test]
+    fn reports_attribute_and_text_changes() {
+        let a = vec![element(1, "p", &[("class", "old"), ("nonce", "1")], vec![text(2, "Buy")])];
+        let b = vec![element(1, "p", &[("class", "new"), ("nonce", "2")], vec![text(2, "Purchase")])];
+        let options = DiffOptions {
+            ignore_attributes: vec!["nonce".to_string()],
+            ..Default::default()
+        };
+        let diff = diff_samples(&[sample(a)], &[sample(b)], &options);
+        let kinds: Vec<_> = diff.points[0].differences.iter().map(|d| d.kind.clone()).collect();
+        assert_eq!(kinds, vec![DifferenceKind::AttributeChanged, DifferenceKind::TextChanged]);
+        assert_eq!(diff.points[0].differences[1].path, "/p/text()");
+    }
+
+    #[test]
+    fn missing_points_are_reported() {
+        let doc = vec![element(1, "body", &[], vec![])];
+        let diff = diff_samples(
+            &[sample(doc.clone()), sample(doc.clone())],
+            &[sample(doc)],
+            &DiffOptions::default(),
+        );
+        assert_eq!(diff.points.len(), 2);
+        assert_eq!(diff.points[1].differences[0].kind, DifferenceKind::DocumentMissing);
+    }
+
+    #[test]
+    fn differences_are_capped_per_point() {
+        let a = vec![element(1, "ul", &[], (0..10).map(|i| element(i + 2, "li", &[], vec![])).collect())];
+        let b = vec![element(1, "ul", &[], vec![])];
+        let options = DiffOptions {
+            max_differences_per_point: 3,
+            ..Default::default()
+        };
+        let diff = diff_samples(&[sample(a)], &[sample(b)], &options);
+        assert_eq!(diff.points[0].differences.len(), 3);
+        assert!(diff.points[0].truncated);
+    }
+
+    #[tokio::test]
+    async fn timestamps_that_go_backwards_end_at_offset_zero() {
+        use domcorder_proto::{FileHeader, FrameBuilder, FrameWriter};
+
+        // The recorder's clock was set back during the recording
+        let frames = FrameBuilder::keyframe().at(5_000).wait(100).at(1_000).build();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::with_timestamp(5_000)).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+
+        let mut reader = FrameReader::new(&data[..], true);
+        let samples = collect_samples(&mut reader, &DiffOptions::default()).await.unwrap();
+        assert_eq!(samples.last().unwrap().offset_ms, Some(0));
+    }
+}
@@ -0,0 +1,86 @@
+//! Storage usage reporting and the low-space guard
+//!
+//! `GET /admin/storage` reports how much space recordings, cached assets and
+//! the metadata database take, next to the free space on the filesystem
+//! holding the storage directory. When a minimum free space is configured,
+//! new recordings are refused with [`StorageError::InsufficientSpace`] once
+//! free space drops below it, rather than failing part way through a write.
+
+use crate::{StorageError, StorageState};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Space used by each part of the storage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub recordings: RecordingsUsage,
+    /// Bytes of cached assets; None if the asset store cannot tell
+    pub assets_bytes: Option<u64>,
+    /// Bytes of the metadata database; None if the metadata store cannot tell
+    pub database_bytes: Option<u64>,
+    pub filesystem: FilesystemUsage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingsUsage {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// The filesystem holding the storage directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilesystemUsage {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// Configured minimum free space for new recordings, if any
+    pub min_free_bytes: Option<u64>,
+    /// Whether new recordings are currently accepted
+    pub accepting_recordings: bool,
+}
+
+/// Free space on the filesystem holding `path`
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    fs4::available_space(path)
+}
+
+impl StorageState {
+    /// Fail with [`StorageError::InsufficientSpace`] if free space is below the
+    /// configured minimum
+    ///
+    /// If free space cannot be read, recordings are allowed; the write itself
+    /// will report any real problem.
+    pub fn check_free_space(&self) -> Result<(), StorageError> {
+        let Some(required) = self.min_free_bytes else {
+            return Ok(());
+        };
+        match available_space(&self.storage_dir) {
+            Ok(available) if available < required => Err(StorageError::InsufficientSpace { available, required }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Measure the space used by recordings, assets and the metadata database
+    pub async fn disk_usage(&self) -> Result<DiskUsage, StorageError> {
+        let recordings = self.recording_store.list_all(None).await?;
+        let recordings = RecordingsUsage {
+            count: recordings.len(),
+            bytes: recordings.iter().map(|recording| recording.size).sum(),
+        };
+        let assets_bytes = self.asset_file_store.disk_usage().await?;
+        let database_bytes = self.metadata_store.disk_usage().await?;
+
+        let total_bytes = fs4::total_space(&self.storage_dir)?;
+        let available_bytes = available_space(&self.storage_dir)?;
+        Ok(DiskUsage {
+            recordings,
+            assets_bytes,
+            database_bytes,
+            filesystem: FilesystemUsage {
+                total_bytes,
+                available_bytes,
+                min_free_bytes: self.min_free_bytes,
+                accepting_recordings: self.min_free_bytes.is_none_or(|min| available_bytes >= min),
+            },
+        })
+    }
+}
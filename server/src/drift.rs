@@ -0,0 +1,159 @@
+//! Keyframe requests for live recordings that drift from their page
+//!
+//! A long session can lose frames the recorder thought it sent (a mutation
+//! observer that missed a change, a bug in a tracker), after which its
+//! mutations refer to nodes the recording never added and playback degrades
+//! for the rest of the session. During live ingestion the frames are replayed
+//! into a virtual DOM; when a mutation refers to a node it doesn't have, the
+//! server sends the recorder a RequestKeyframe control frame over
+//! `/ws/record`. The recorder answers with a fresh Keyframe, which is stored
+//! in the stream like any other, and playback is back in step from there.
+//!
+//! One request is outstanding at a time: no other is sent until a keyframe
+//! arrives, so a recorder that doesn't know the frame is asked once.
+
+use crate::recording_id::RecordingId;
+use domcorder_proto::{Frame, VDomEngine, VDomError};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// Spots the first frame of each drift in one recording's frames
+#[derive(Debug, Default)]
+pub struct DriftDetector {
+    engine: VDomEngine,
+    /// A keyframe has been requested and hasn't arrived yet
+    requested: bool,
+}
+
+impl DriftDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the next frame, returning why a keyframe should be requested if
+    /// it shows the recording has drifted
+    pub fn push_frame(&mut self, frame: &Frame) -> Option<String> {
+        if matches!(frame, Frame::Keyframe(_) | Frame::KeyframeBegin(_)) {
+            self.requested = false;
+        }
+        match self.engine.apply(frame) {
+            Err(error @ (VDomError::UnknownNode(_) | VDomError::NoDocument)) if !self.requested => {
+                self.requested = true;
+                Some(format!("{} in {}", error, frame.type_name()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The live recorders that can be asked for a keyframe, by recording
+#[derive(Debug, Default)]
+pub struct KeyframeRequests {
+    recorders: Mutex<HashMap<RecordingId, mpsc::Sender<String>>>,
+}
+
+impl KeyframeRequests {
+    /// Take the keyframe requests for recording `id` until the returned
+    /// listener is dropped
+    pub fn listen(&self, id: &RecordingId) -> KeyframeRequestListener<'_> {
+        // Requests are rare and one outstanding is enough
+        let (sender, receiver) = mpsc::channel(1);
+        self.recorders.lock().unwrap().insert(id.clone(), sender);
+        KeyframeRequestListener {
+            requests: self,
+            id: id.clone(),
+            receiver,
+        }
+    }
+
+    /// Ask the recorder of `id` for a keyframe; false if no recorder is
+    /// listening
+    pub fn request(&self, id: &RecordingId, reason: String) -> bool {
+        let recorders = self.recorders.lock().unwrap();
+        match recorders.get(id) {
+            Some(sender) => {
+                let _ = sender.try_send(reason);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Receives the keyframe requests for one recording
+pub struct KeyframeRequestListener<'a> {
+    requests: &'a KeyframeRequests,
+    id: RecordingId,
+    receiver: mpsc::Receiver<String>,
+}
+
+impl KeyframeRequestListener<'_> {
+    /// The reason for the next request
+    pub async fn next(&mut self) -> Option<String> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for KeyframeRequestListener<'_> {
+    fn drop(&mut self) {
+        self.requests.recorders.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{DomNodeRemovedData, KeyframeData, VDocument};
+
+    fn keyframe() -> Frame {
+        Frame::Keyframe(KeyframeData {
+            document: VDocument {
+                id: 1,
+                adopted_style_sheets: Vec::new(),
+                children: Vec::new(),
+            },
+            viewport_width: 800,
+            viewport_height: 600,
+        })
+    }
+
+    fn remove(node_id: u32) -> Frame {
+        Frame::DomNodeRemoved(DomNodeRemovedData { node_id })
+    }
+
+    #[test]
+    fn requests_once_until_the_next_keyframe() {
+        let mut drift = DriftDetector::new();
+        assert_eq!(drift.push_frame(&keyframe()), None);
+        assert_eq!(
+            drift.push_frame(&remove(7)).as_deref(),
+            Some("unknown node id 7 in DomNodeRemoved")
+        );
+        assert_eq!(drift.push_frame(&remove(8)), None);
+
+        assert_eq!(drift.push_frame(&keyframe()), None);
+        assert!(drift.push_frame(&remove(9)).is_some());
+    }
+
+    #[test]
+    fn mutations_before_any_keyframe_are_drift() {
+        let mut drift = DriftDetector::new();
+        assert_eq!(drift.push_frame(&Frame::Heartbeat), None);
+        assert!(drift.push_frame(&remove(3)).is_some());
+    }
+
+    #[tokio::test]
+    async fn requests_reach_the_listener_until_it_is_dropped() {
+        let requests = KeyframeRequests::default();
+        let id = RecordingId::new("live.dcrr").unwrap();
+        assert!(!requests.request(&id, "before".to_string()));
+
+        let mut listener = requests.listen(&id);
+        assert!(requests.request(&id, "drifted".to_string()));
+        assert_eq!(listener.next().await.as_deref(), Some("drifted"));
+
+        drop(listener);
+        assert!(!requests.request(&id, "after".to_string()));
+    }
+}
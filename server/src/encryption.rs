@@ -0,0 +1,486 @@
+//! At-rest encryption of recordings with per-recording data keys
+//!
+//! Every recording is encrypted with its own random AES-256-GCM data key. The
+//! data key is stored in the metadata store wrapped (encrypted) by a master
+//! key, together with the id of that master key. Rotating the master key only
+//! rewraps the data keys; recording files are never rewritten.
+//!
+//! Recording files are a sequence of sealed segments, each a u32 (big-endian)
+//! ciphertext length followed by the ciphertext. Segments are sealed when
+//! enough plaintext has been buffered, when time has passed, or on flush, so
+//! live viewers tailing an encrypted recording lag as little as they do with a
+//! plain one. Segment `n` uses nonce `n`, which is safe because every data key
+//! encrypts exactly one recording. As in the STREAM construction, the segment
+//! sealed by `finish()` has a last-segment flag set in its nonce, so a
+//! recording cut off at a segment boundary doesn't read as a shorter but
+//! complete one.
+
+use crate::recording_store::{RecordingReader, RecordingWriter};
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, AeadCore, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Plaintext buffered before a segment is sealed
+pub const SEGMENT_BYTES: usize = 64 * 1024;
+
+/// Longest a write waits in the buffer before its segment is sealed
+pub const SEGMENT_INTERVAL: Duration = Duration::from_millis(250);
+
+const KEY_BYTES: usize = 32;
+const NONCE_BYTES: usize = 12;
+const TAG_BYTES: usize = 16;
+
+/// Error type for key management
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    #[error("Master key {0:?} is not configured")]
+    UnknownKey(String),
+
+    #[error("Invalid master key {0:?}: expected id:base64 of 32 bytes")]
+    InvalidMasterKey(String),
+
+    #[error("No master keys configured")]
+    NoMasterKey,
+
+    #[error("Wrapped key could not be decrypted with master key {0:?}")]
+    Unwrap(String),
+}
+
+/// A recording's data key, encrypted by the master key `key_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    pub key_id: String,
+    /// Nonce followed by the sealed data key
+    pub wrapped: Vec<u8>,
+}
+
+/// The master keys that wrap data keys
+///
+/// The current key wraps new data keys; the others are kept so data keys
+/// wrapped before a rotation can still be read and rewrapped.
+pub struct KeyRing {
+    current: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl std::fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ids: Vec<_> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("KeyRing")
+            .field("current", &self.current)
+            .field("keys", &ids)
+            .finish()
+    }
+}
+
+impl KeyRing {
+    /// A ring holding only `key`, which becomes current
+    pub fn new(key_id: impl Into<String>, key: [u8; KEY_BYTES]) -> Self {
+        let key_id = key_id.into();
+        Self {
+            keys: HashMap::from([(key_id.clone(), Aes256Gcm::new(&key.into()))]),
+            current: key_id,
+        }
+    }
+
+    /// Add an older key, used only to unwrap
+    pub fn with_key(mut self, key_id: impl Into<String>, key: [u8; KEY_BYTES]) -> Self {
+        self.keys.insert(key_id.into(), Aes256Gcm::new(&key.into()));
+        self
+    }
+
+    /// Parse comma-separated `id:base64` keys; the first is current
+    pub fn parse(spec: &str) -> Result<Self, EncryptionError> {
+        let mut ring: Option<Self> = None;
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let invalid = || EncryptionError::InvalidMasterKey(entry.split(':').next().unwrap_or_default().to_string());
+            let (id, encoded) = entry.split_once(':').filter(|(id, _)| !id.is_empty()).ok_or_else(invalid)?;
+            let key: [u8; KEY_BYTES] = STANDARD
+                .decode(encoded)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(invalid)?;
+            ring = Some(match ring {
+                Some(ring) => ring.with_key(id, key),
+                None => Self::new(id, key),
+            });
+        }
+        ring.ok_or(EncryptionError::NoMasterKey)
+    }
+
+    /// Id of the master key new data keys are wrapped with
+    pub fn current_key_id(&self) -> &str {
+        &self.current
+    }
+
+    /// A new data key, and the same key wrapped by the current master key
+    pub fn generate_data_key(&self) -> (DataKey, WrappedKey) {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let wrapped = self.wrap(&self.current, key.as_slice());
+        (DataKey(Aes256Gcm::new(&key)), wrapped)
+    }
+
+    /// Decrypt a wrapped data key
+    pub fn unwrap_key(&self, wrapped: &WrappedKey) -> Result<DataKey, EncryptionError> {
+        let key = self.unwrap_bytes(wrapped)?;
+        Ok(DataKey(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))))
+    }
+
+    /// The same data key wrapped by the current master key
+    pub fn rewrap(&self, wrapped: &WrappedKey) -> Result<WrappedKey, EncryptionError> {
+        let key = self.unwrap_bytes(wrapped)?;
+        Ok(self.wrap(&self.current, &key))
+    }
+
+    fn wrap(&self, key_id: &str, key: &[u8]) -> WrappedKey {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let sealed = self.keys[key_id].encrypt(&nonce, key).expect("sealing a data key cannot fail");
+        WrappedKey {
+            key_id: key_id.to_string(),
+            wrapped: [nonce.as_slice(), &sealed].concat(),
+        }
+    }
+
+    fn unwrap_bytes(&self, wrapped: &WrappedKey) -> Result<Vec<u8>, EncryptionError> {
+        let master = self
+            .keys
+            .get(&wrapped.key_id)
+            .ok_or_else(|| EncryptionError::UnknownKey(wrapped.key_id.clone()))?;
+        let unwrap = || EncryptionError::Unwrap(wrapped.key_id.clone());
+        if wrapped.wrapped.len() < NONCE_BYTES {
+            return Err(unwrap());
+        }
+        let (nonce, sealed) = wrapped.wrapped.split_at(NONCE_BYTES);
+        let key = master.decrypt(Nonce::from_slice(nonce), sealed).map_err(|_| unwrap())?;
+        if key.len() != KEY_BYTES {
+            return Err(unwrap());
+        }
+        Ok(key)
+    }
+}
+
+/// Outcome of rewrapping recording keys with the current master key
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct KeyRotation {
+    /// The master key the keys are now wrapped with
+    pub key_id: String,
+    pub rewrapped: usize,
+    /// Recordings whose key is wrapped by a master key that is not configured
+    pub unreadable: Vec<String>,
+}
+
+/// An unwrapped data key
+pub struct DataKey(Aes256Gcm);
+
+fn segment_nonce(segment: u64, last: bool) -> Nonce<<Aes256Gcm as AeadCore>::NonceSize> {
+    let mut nonce = [0u8; NONCE_BYTES];
+    nonce[0] = last as u8;
+    nonce[NONCE_BYTES - 8..].copy_from_slice(&segment.to_be_bytes());
+    nonce.into()
+}
+
+/// Encrypts everything written to a recording into sealed segments
+pub struct EncryptingWriter {
+    inner: Box<dyn RecordingWriter>,
+    key: DataKey,
+    buffer: Vec<u8>,
+    segment: u64,
+    last_seal: Instant,
+}
+
+impl EncryptingWriter {
+    pub fn new(inner: Box<dyn RecordingWriter>, key: DataKey) -> Self {
+        Self {
+            inner,
+            key,
+            buffer: Vec::with_capacity(SEGMENT_BYTES),
+            segment: 0,
+            last_seal: Instant::now(),
+        }
+    }
+
+    /// Seal the buffered plaintext; the last segment is sealed even if empty
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        self.last_seal = Instant::now();
+        if self.buffer.is_empty() && !last {
+            return Ok(());
+        }
+        let sealed = self
+            .key
+            .0
+            .encrypt(&segment_nonce(self.segment, last), self.buffer.as_slice())
+            .map_err(|_| io::Error::other("failed to encrypt recording segment"))?;
+        self.segment += 1;
+        self.buffer.clear();
+        self.inner.write_all(&(sealed.len() as u32).to_be_bytes())?;
+        self.inner.write_all(&sealed)
+    }
+}
+
+impl Write for EncryptingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(SEGMENT_BYTES - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if self.buffer.len() >= SEGMENT_BYTES || self.last_seal.elapsed() >= SEGMENT_INTERVAL {
+            self.seal(false)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.seal(false)?;
+        self.inner.flush()
+    }
+}
+
+impl RecordingWriter for EncryptingWriter {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.seal(true)?;
+        self.inner.finish()
+    }
+}
+
+/// Decrypts a recording written by [`EncryptingWriter`], skipping the first
+/// `offset` bytes of plaintext
+///
+/// Data ending before the last segment is [`io::ErrorKind::InvalidData`],
+/// unless the reader is [`tailing`](Self::tailing).
+pub struct DecryptingReader {
+    inner: RecordingReader,
+    key: DataKey,
+    skip: u64,
+    segment: u64,
+    tailing: bool,
+    /// The last segment has been read
+    finished: bool,
+    /// Bytes of the segment being read, length prefix included
+    pending: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl DecryptingReader {
+    pub fn new(inner: RecordingReader, key: DataKey, offset: u64) -> Self {
+        Self {
+            inner,
+            key,
+            skip: offset,
+            segment: 0,
+            tailing: false,
+            finished: false,
+            pending: Vec::new(),
+            plaintext: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Read a recording that may not be finished (one being written, or one
+    /// cut off): the end of the data is the end of what has been written so
+    /// far, not an error, and a partly written segment is picked up where it
+    /// was on the next read, so the recording can be tailed like a plain file
+    pub fn tailing(mut self) -> Self {
+        self.tailing = true;
+        self
+    }
+
+    /// Bytes still missing from the segment being read
+    fn missing(&self) -> io::Result<usize> {
+        let Some(len) = self.pending.get(..4) else {
+            return Ok(4 - self.pending.len());
+        };
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        if !(TAG_BYTES..=SEGMENT_BYTES + TAG_BYTES).contains(&len) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid recording segment length"));
+        }
+        Ok(4 + len - self.pending.len())
+    }
+
+    fn open_segment(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "data after the last recording segment"));
+        }
+        let sealed = &self.pending[4..];
+        let plaintext = match self.key.0.decrypt(&segment_nonce(self.segment, false), sealed) {
+            Ok(plaintext) => plaintext,
+            Err(_) => {
+                let plaintext = self
+                    .key
+                    .0
+                    .decrypt(&segment_nonce(self.segment, true), sealed)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "recording segment failed to decrypt"))?;
+                self.finished = true;
+                plaintext
+            }
+        };
+        self.segment += 1;
+        self.pending.clear();
+        let skipped = plaintext.len().min(self.skip as usize);
+        self.skip -= skipped as u64;
+        self.plaintext = plaintext;
+        self.position = skipped;
+        Ok(())
+    }
+}
+
+impl AsyncRead for DecryptingReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        while this.position == this.plaintext.len() {
+            let missing = this.missing()?;
+            if missing == 0 {
+                this.open_segment()?;
+                continue;
+            }
+            let start = this.pending.len();
+            this.pending.resize(start + missing, 0);
+            let mut segment = ReadBuf::new(&mut this.pending[start..]);
+            let poll = Pin::new(&mut this.inner).poll_read(cx, &mut segment);
+            let read = segment.filled().len();
+            this.pending.truncate(start + read);
+            ready!(poll)?;
+            if read == 0 {
+                if this.tailing || (this.finished && this.pending.is_empty()) {
+                    // End of what has been written so far
+                    return Poll::Ready(Ok(()));
+                }
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "recording ends before its last segment",
+                )));
+            }
+        }
+        let n = buf.remaining().min(this.plaintext.len() - this.position);
+        buf.put_slice(&this.plaintext[this.position..this.position + n]);
+        this.position += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+
+    /// A RecordingWriter into a shared buffer
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl RecordingWriter for SharedBuffer {
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn read_all(ring: &KeyRing, wrapped: &WrappedKey, data: Vec<u8>, offset: u64) -> io::Result<Vec<u8>> {
+        let key = ring.unwrap_key(wrapped).unwrap();
+        let mut reader = DecryptingReader::new(Box::new(io::Cursor::new(data)), key, offset);
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext).await?;
+        Ok(plaintext)
+    }
+
+    #[tokio::test]
+    async fn test_segments_round_trip() {
+        let ring = KeyRing::new("k1", [7; 32]);
+        let (key, wrapped) = ring.generate_data_key();
+        let file = SharedBuffer::default();
+        let mut writer = EncryptingWriter::new(Box::new(file.clone()), key);
+        let plaintext: Vec<u8> = (0..SEGMENT_BYTES * 2 + 100).map(|i| i as u8).collect();
+        writer.write_all(&plaintext[..10]).unwrap();
+        writer.flush().unwrap();
+        writer.write_all(&plaintext[10..]).unwrap();
+        Box::new(writer).finish().unwrap();
+
+        let data = file.0.lock().unwrap().clone();
+        assert!(!data.windows(32).any(|window| window == &plaintext[..32]));
+        assert_eq!(read_all(&ring, &wrapped, data.clone(), 0).await.unwrap(), plaintext);
+        assert_eq!(read_all(&ring, &wrapped, data.clone(), 32).await.unwrap(), plaintext[32..]);
+
+        let mut tampered = data;
+        tampered[20] ^= 1;
+        let error = read_all(&ring, &wrapped, tampered, 0).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_recording_cut_off_at_a_segment_boundary_is_rejected() {
+        let ring = KeyRing::new("k1", [7; 32]);
+        let (key, wrapped) = ring.generate_data_key();
+        let file = SharedBuffer::default();
+        let mut writer = EncryptingWriter::new(Box::new(file.clone()), key);
+        writer.write_all(b"first").unwrap();
+        writer.flush().unwrap();
+        let first_segment = file.0.lock().unwrap().len();
+        writer.write_all(b"second").unwrap();
+        Box::new(writer).finish().unwrap();
+
+        let data = file.0.lock().unwrap().clone();
+        assert_eq!(read_all(&ring, &wrapped, data.clone(), 0).await.unwrap(), b"firstsecond");
+
+        let cut_off = data[..first_segment].to_vec();
+        let error = read_all(&ring, &wrapped, cut_off.clone(), 0).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Tailing, the same bytes are a recording still being written
+        let key = ring.unwrap_key(&wrapped).unwrap();
+        let mut reader = DecryptingReader::new(Box::new(io::Cursor::new(cut_off)), key, 0).tailing();
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext).await.unwrap();
+        assert_eq!(plaintext, b"first");
+
+        // Nothing may follow the last segment
+        let extended = [data.as_slice(), &data[..first_segment]].concat();
+        let error = read_all(&ring, &wrapped, extended, 0).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_rotation_rewraps_with_the_current_key() {
+        let old = KeyRing::new("2024", [1; 32]);
+        let (_, wrapped) = old.generate_data_key();
+        assert_eq!(wrapped.key_id, "2024");
+
+        let ring = KeyRing::new("2025", [2; 32]).with_key("2024", [1; 32]);
+        let rewrapped = ring.rewrap(&wrapped).unwrap();
+        assert_eq!(rewrapped.key_id, "2025");
+        assert_eq!(
+            ring.unwrap_bytes(&rewrapped).unwrap(),
+            old.unwrap_bytes(&wrapped).unwrap()
+        );
+
+        let without_old = KeyRing::new("2025", [2; 32]);
+        assert!(matches!(without_old.rewrap(&wrapped), Err(EncryptionError::UnknownKey(id)) if id == "2024"));
+    }
+
+    #[test]
+    fn test_parse_master_keys() {
+        let spec = format!("new:{},old:{}", STANDARD.encode([2; 32]), STANDARD.encode([1; 32]));
+        let ring = KeyRing::parse(&spec).unwrap();
+        assert_eq!(ring.current_key_id(), "new");
+        assert_eq!(format!("{:?}", ring), r#"KeyRing { current: "new", keys: ["new", "old"] }"#);
+
+        assert!(matches!(KeyRing::parse(""), Err(EncryptionError::NoMasterKey)));
+        assert!(matches!(KeyRing::parse("short:AAAA"), Err(EncryptionError::InvalidMasterKey(id)) if id == "short"));
+        assert!(matches!(KeyRing::parse("no-separator"), Err(EncryptionError::InvalidMasterKey(_))));
+    }
+}
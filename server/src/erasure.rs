@@ -0,0 +1,618 @@
+//! Subject erasure: removing everything recorded about one person
+//!
+//! `POST /admin/erasure` takes a subject identifier, finds the recordings
+//! whose session identity (user id, email hash or anonymous id) or tags
+//! match it, and either deletes them or redacts them in place. Deleting
+//! also prunes cached assets no remaining recording refers to. Recordings on
+//! legal hold or still being written are left alone and listed in the report,
+//! so the request can be repeated once they are released or finished.
+//!
+//! Redaction rewrites the recording with the text of the page masked, input
+//! values and keystrokes removed, and canvases, annotations, identities and
+//! error details dropped; the structure, layout and pointer movement of the
+//! session stay watchable. The subject itself is never written to the audit log.
+
+use crate::asset_refs::referenced_assets;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::encryption::WrappedKey;
+use crate::privacy::strip_query;
+use crate::provenance::Provenance;
+use crate::recording_id::RecordingId;
+use crate::storage::stored_header;
+use crate::{StorageError, StorageState};
+use domcorder_proto::{FileHeader, Frame, FrameWriter, TextOperationData, VNode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use tracing::{info, warn};
+
+/// Whether matching recordings are deleted or redacted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureMode {
+    #[default]
+    Delete,
+    Redact,
+}
+
+/// What an erasure request did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureReport {
+    pub subject: String,
+    pub mode: ErasureMode,
+    /// Recordings deleted or redacted
+    pub erased: Vec<String>,
+    /// Matching recordings skipped because they are on legal hold
+    pub held: Vec<String>,
+    /// Matching recordings skipped because they are still being written
+    pub active: Vec<String>,
+    pub failed: Vec<ErasureFailure>,
+    /// Cached assets removed because no remaining recording refers to them
+    pub assets_pruned: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureFailure {
+    pub recording_id: String,
+    pub error: String,
+}
+
+/// Attributes whose values are user content rather than markup
+const REDACTED_ATTRIBUTES: &[&str] = &["value", "placeholder", "title", "alt", "aria-label"];
+
+/// Elements whose text is code, not content, and is kept
+const CODE_ELEMENTS: &[&str] = &["style", "script"];
+
+/// Replace every non-whitespace character with `*`, keeping the length and
+/// line structure of the text
+pub fn mask(text: &str) -> String {
+    text.chars().map(|c| if c.is_whitespace() { c } else { '*' }).collect()
+}
+
+/// Rewrites a recording's frames with their content masked
+#[derive(Debug, Default)]
+pub struct Redactor {
+    /// Text nodes inside `<style>` and `<script>`, whose edits are kept
+    code_text_nodes: HashSet<u32>,
+    /// Only input values and keystrokes are masked
+    inputs_only: bool,
+}
+
+impl Redactor {
+    /// A redactor that masks input values and drops keystrokes, leaving the
+    /// rest of the page as recorded
+    pub fn inputs_only() -> Self {
+        Self {
+            inputs_only: true,
+            ..Default::default()
+        }
+    }
+
+    /// The redacted frame, or None if the frame is dropped
+    pub fn redact(&mut self, frame: Frame) -> Option<Frame> {
+        if self.inputs_only {
+            return mask_inputs(frame);
+        }
+        // Provenance names recordings, not anything that was on the page
+        if Provenance::from_frame(&frame).is_some() {
+            return Some(frame);
+        }
+        let frame = match frame {
+            Frame::KeyPressed(_)
+            | Frame::Annotation(_)
+            | Frame::CanvasChanged(_)
+            | Frame::CanvasChangedDelta(_)
+            | Frame::SessionIdentity(_) => {
+                return None;
+            }
+            Frame::Keyframe(mut keyframe) => {
+                self.code_text_nodes.clear();
+                for node in &mut keyframe.document.children {
+                    self.redact_node(node, false);
+                }
+                Frame::Keyframe(keyframe)
+            }
+            Frame::KeyframeBegin(begin) => {
+                self.code_text_nodes.clear();
+                Frame::KeyframeBegin(begin)
+            }
+            Frame::KeyframeChunk(mut chunk) => {
+                // Chunks split documents at element boundaries, so a chunk's
+                // text is never a direct child of a <style> or <script>
+                for node in &mut chunk.nodes {
+                    self.redact_node(node, false);
+                }
+                Frame::KeyframeChunk(chunk)
+            }
+            Frame::DomNodeAdded(mut added) => {
+                self.redact_node(&mut added.node, false);
+                Frame::DomNodeAdded(added)
+            }
+            Frame::DomTextChanged(mut changed) => {
+                if !self.code_text_nodes.contains(&changed.node_id) {
+                    mask_operations(&mut changed.operations);
+                }
+                Frame::DomTextChanged(changed)
+            }
+            Frame::DomNodePropertyTextChanged(mut changed) => {
+                mask_operations(&mut changed.operations);
+                Frame::DomNodePropertyTextChanged(changed)
+            }
+            Frame::DomNodePropertyChanged(mut changed) => {
+                if changed.property_name == "value" {
+                    changed.property_value = mask(&changed.property_value);
+                }
+                Frame::DomNodePropertyChanged(changed)
+            }
+            Frame::DomAttributeChanged(mut changed) => {
+                if REDACTED_ATTRIBUTES.contains(&changed.attribute_name.as_str()) {
+                    changed.attribute_value = mask(&changed.attribute_value);
+                }
+                Frame::DomAttributeChanged(changed)
+            }
+            Frame::PageError(mut error) => {
+                error.message = mask(&error.message);
+                error.stack = None;
+                Frame::PageError(error)
+            }
+            Frame::RecordingMetadata(mut metadata) => {
+                metadata.initial_url = strip_query(&metadata.initial_url);
+                Frame::RecordingMetadata(metadata)
+            }
+            frame => frame,
+        };
+        Some(frame)
+    }
+
+    fn redact_node(&mut self, node: &mut VNode, in_code: bool) {
+        match node {
+            VNode::Element(element) => {
+                for (name, value) in &mut element.attrs {
+                    if REDACTED_ATTRIBUTES.contains(&name.as_str()) {
+                        *value = mask(value);
+                    }
+                }
+                let in_code = CODE_ELEMENTS.contains(&element.tag.to_ascii_lowercase().as_str());
+                for child in &mut element.children {
+                    self.redact_node(child, in_code);
+                }
+            }
+            VNode::Text(text) if in_code => {
+                self.code_text_nodes.insert(text.id);
+            }
+            VNode::Text(text) => text.content = mask(&text.content),
+            VNode::CData(cdata) => cdata.content = mask(&cdata.content),
+            VNode::Comment(comment) => comment.content = mask(&comment.content),
+            VNode::DocType(_) | VNode::ProcessingInstruction(_) => {}
+        }
+    }
+}
+
+/// The frame with input values masked, or None for keystrokes
+fn mask_inputs(frame: Frame) -> Option<Frame> {
+    let frame = match frame {
+        Frame::KeyPressed(_) => return None,
+        Frame::Keyframe(mut keyframe) => {
+            keyframe.document.children.iter_mut().for_each(mask_input_values);
+            Frame::Keyframe(keyframe)
+        }
+        Frame::KeyframeChunk(mut chunk) => {
+            chunk.nodes.iter_mut().for_each(mask_input_values);
+            Frame::KeyframeChunk(chunk)
+        }
+        Frame::DomNodeAdded(mut added) => {
+            mask_input_values(&mut added.node);
+            Frame::DomNodeAdded(added)
+        }
+        Frame::DomNodePropertyTextChanged(mut changed) => {
+            mask_operations(&mut changed.operations);
+            Frame::DomNodePropertyTextChanged(changed)
+        }
+        Frame::DomNodePropertyChanged(mut changed) if changed.property_name == "value" => {
+            changed.property_value = mask(&changed.property_value);
+            Frame::DomNodePropertyChanged(changed)
+        }
+        Frame::DomAttributeChanged(mut changed) if changed.attribute_name == "value" => {
+            changed.attribute_value = mask(&changed.attribute_value);
+            Frame::DomAttributeChanged(changed)
+        }
+        frame => frame,
+    };
+    Some(frame)
+}
+
+/// Mask the `value` attributes in a subtree
+fn mask_input_values(node: &mut VNode) {
+    if let VNode::Element(element) = node {
+        for (name, value) in &mut element.attrs {
+            if name == "value" {
+                *value = mask(value);
+            }
+        }
+        element.children.iter_mut().for_each(mask_input_values);
+    }
+}
+
+fn mask_operations(operations: &mut [TextOperationData]) {
+    for operation in operations {
+        if let TextOperationData::Insert(insert) = operation {
+            insert.text = mask(&insert.text);
+        }
+    }
+}
+
+impl StorageState {
+    /// Delete or redact every recording of `subject`, logging each with who
+    /// asked and why
+    ///
+    /// Recordings are matched by identity or tag. Failures on one recording
+    /// are reported and don't stop the others.
+    pub async fn erase_subject(
+        &self,
+        subject: &str,
+        mode: ErasureMode,
+        actor: Option<String>,
+        reason: Option<String>,
+    ) -> Result<ErasureReport, StorageError> {
+        let mut report = ErasureReport {
+            subject: subject.to_string(),
+            mode,
+            erased: Vec::new(),
+            held: Vec::new(),
+            active: Vec::new(),
+            failed: Vec::new(),
+            assets_pruned: 0,
+        };
+        let mut released_assets = HashSet::new();
+
+        for recording_id in self.metadata_store.find_recordings_by_subject(subject).await? {
+            let id = match RecordingId::new(recording_id.as_str()) {
+                Ok(id) => id,
+                Err(e) => {
+                    report.failed.push(ErasureFailure {
+                        recording_id,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if self.is_recording_active(&id) {
+                report.active.push(recording_id);
+                continue;
+            }
+            if self.is_on_legal_hold(&id).await? {
+                report.held.push(recording_id);
+                continue;
+            }
+
+            let result = match mode {
+                ErasureMode::Delete => self.erase_recording(&id, &mut released_assets).await,
+                ErasureMode::Redact => self.redact_recording(&id).await,
+            };
+            if let Err(e) = result {
+                warn!("⚠️ Failed to erase recording {}: {}", id, e);
+                report.failed.push(ErasureFailure {
+                    recording_id,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+            let action = match mode {
+                ErasureMode::Delete => AuditAction::Deleted,
+                ErasureMode::Redact => AuditAction::Redacted,
+            };
+            self.metadata_store
+                .append_audit_event(&AuditEvent::now(id.as_str(), action, actor.clone(), reason.clone()))
+                .await?;
+            report.erased.push(recording_id);
+        }
+
+        if !released_assets.is_empty() {
+            report.assets_pruned = self.prune_assets(released_assets).await;
+        }
+        info!(
+            "🧹 Erased {} recordings of a subject ({} held, {} active, {} failed, {} assets pruned)",
+            report.erased.len(),
+            report.held.len(),
+            report.active.len(),
+            report.failed.len(),
+            report.assets_pruned
+        );
+        Ok(report)
+    }
+
+    /// Delete a recording and its metadata, collecting the assets it used
+    async fn erase_recording(&self, id: &RecordingId, assets: &mut HashSet<String>) -> Result<(), StorageError> {
+        let mut reader = self.open_recording_reader(id).await?;
+        while let Some(frame) = reader.read_frame().await.map_err(StorageError::Frame)? {
+            referenced_assets(&frame, assets);
+        }
+        drop(reader);
+
+        self.recording_store
+            .delete(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        self.metadata_store.delete_recording_metadata(id.as_str()).await?;
+        Ok(())
+    }
+
+    /// Rewrite a recording with its content masked
+    ///
+    /// The recording is read whole before it is replaced (see
+    /// [`replace_recording`](Self::replace_recording)). Its analytics are
+    /// dropped, and its summary is kept without title, tags and identity.
+    pub(crate) async fn redact_recording(&self, id: &RecordingId) -> Result<(), StorageError> {
+        let mut reader = self.open_recording_reader(id).await?;
+        let header = reader.read_header().await.map_err(StorageError::Header)?;
+        let mut redactor = Redactor::default();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.map_err(StorageError::Frame)? {
+            frames.extend(redactor.redact(frame));
+        }
+        drop(reader);
+
+        let summary = self
+            .metadata_store
+            .get_recording_summaries(&[id.to_string()])
+            .await?
+            .into_iter()
+            .next();
+        let provenance = self.recording_provenance(id).await?;
+        self.replace_recording(id, &header, &frames, false).await?;
+
+        if let Some(mut summary) = summary {
+            summary.initial_url = summary.initial_url.as_deref().map(strip_query);
+            summary.title = None;
+            summary.tags.clear();
+            summary.anonymous_id = None;
+            summary.user_id = None;
+            summary.email_hash = None;
+            self.metadata_store.store_recording_summary(&summary).await?;
+        }
+        for provenance in &provenance {
+            self.store_provenance(id, provenance).await;
+        }
+        self.record_provenance(id, "redact", &[id]).await;
+        // The references went with the rest of its metadata
+        self.index_recording_assets(id).await
+    }
+
+    /// Replace a stored recording with one made of `header` and `frames`
+    ///
+    /// The new recording is written under a temporary id (with a new data key
+    /// if encryption is on) and moved over the old one once it is complete,
+    /// so a failed write leaves the old recording and its key as they were.
+    /// The new key is stored for the recording before the move and put back
+    /// if the move fails, so the recording can be read whichever step fails.
+    /// With `keep_metadata` only the old key is replaced; otherwise everything
+    /// else stored about the old recording is dropped once it is replaced,
+    /// and if that fails the recording is left rewritten with stale metadata.
+    pub(crate) async fn replace_recording(
+        &self,
+        id: &RecordingId,
+        header: &FileHeader,
+        frames: &[Frame],
+        keep_metadata: bool,
+    ) -> Result<(), StorageError> {
+        // Claimed throughout, so no session starts writing it meanwhile
+        self.mark_recording_active(id)?;
+        if let Err(e) = self.claim_active_lease(id).await {
+            self.mark_recording_completed(id);
+            return Err(e);
+        }
+        let replaced = self.swap_in_recording(id, header, frames, keep_metadata).await;
+        self.mark_recording_completed(id);
+        replaced
+    }
+
+    async fn swap_in_recording(
+        &self,
+        id: &RecordingId,
+        header: &FileHeader,
+        frames: &[Frame],
+        keep_metadata: bool,
+    ) -> Result<(), StorageError> {
+        let temp = RecordingId::new(format!("{}.rewrite", id))?;
+        let mut writer = self.create_recording(&temp).await?;
+        let written = write_recording(&mut writer, header, frames).and_then(|_| writer.finish());
+        self.mark_recording_completed(&temp);
+        let moved = match written {
+            Ok(()) => self.move_rewrite(&temp, id).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = moved {
+            if let Err(e) = self.recording_store.delete(&temp).await {
+                warn!("⚠️ Failed to delete the partial rewrite of {}: {}", id, e);
+            }
+            if let Err(e) = self.metadata_store.delete_recording_metadata(temp.as_str()).await {
+                warn!("⚠️ Failed to forget the partial rewrite of {}: {}", id, e);
+            }
+            return Err(e);
+        }
+
+        if keep_metadata {
+            if let Err(e) = self.metadata_store.delete_recording_metadata(temp.as_str()).await {
+                warn!("⚠️ Failed to forget {} after rewriting {}: {}", temp, id, e);
+            }
+        } else if let Err(e) = self.metadata_store.replace_recording_metadata(temp.as_str(), id.as_str()).await {
+            warn!("⚠️ Rewrote {} but failed to replace its metadata with that of {}: {}", id, temp, e);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Move rewritten recording `temp` over `id`, with its data key
+    ///
+    /// `temp` keeps its own copy of the key, moved with the rest of its metadata.
+    async fn move_rewrite(&self, temp: &RecordingId, id: &RecordingId) -> Result<(), StorageError> {
+        let old_key = self.metadata_store.get_recording_key(id.as_str()).await?;
+        let new_key = self.metadata_store.get_recording_key(temp.as_str()).await?;
+        self.set_recording_key(id, new_key.as_ref()).await?;
+        let moved = self.recording_store.rename(temp, id).await;
+        if let Err(e) = moved {
+            if let Err(e) = self.set_recording_key(id, old_key.as_ref()).await {
+                warn!("⚠️ Failed to restore the key of {} after failing to rewrite it: {}", id, e);
+            }
+            return Err(StorageError::from_store(id, e));
+        }
+        Ok(())
+    }
+
+    /// Store `key` as the data key of recording `id`, or remove its key if None
+    async fn set_recording_key(&self, id: &RecordingId, key: Option<&WrappedKey>) -> Result<(), StorageError> {
+        match key {
+            Some(key) => self.metadata_store.store_recording_key(id.as_str(), key).await?,
+            None => self.metadata_store.delete_recording_key(id.as_str()).await?,
+        }
+        Ok(())
+    }
+
+    /// Remove the cached assets in `candidates` that no remaining recording
+    /// refers to, returning how many were removed
+    ///
+    /// If the recordings' references aren't all indexed, nothing is removed:
+    /// an asset an unindexed recording refers to could otherwise be lost.
+    async fn prune_assets(&self, candidates: HashSet<String>) -> usize {
+        if let Err(e) = self.index_asset_references().await {
+            warn!("⚠️ Not pruning assets, failed to index asset references: {}", e);
+            return 0;
+        }
+        let candidates: Vec<String> = candidates.into_iter().collect();
+        match self.metadata_store.unreferenced_assets(Some(&candidates)).await {
+            Ok(unreferenced) => self.remove_assets(unreferenced).await,
+            Err(e) => {
+                warn!("⚠️ Not pruning assets, failed to count their references: {}", e);
+                0
+            }
+        }
+    }
+}
+
+/// Write a whole recording: `header` (as the current version), then
+/// `frames` and their index
+pub(crate) fn write_recording(writer: &mut impl Write, header: &FileHeader, frames: &[Frame]) -> std::io::Result<()> {
+    let mut frame_writer = FrameWriter::new(&mut *writer);
+    frame_writer.write_header(&stored_header(header))?;
+    for frame in frames {
+        frame_writer.write_frame(frame)?;
+    }
+    frame_writer.write_index()?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{
+        DomNodeAddedData, DomTextChangedData, KeyPressedData, PageErrorData, TextInsertOperationData, VElement,
+        VTextNode,
+    };
+
+    fn text(id: u32, content: &str) -> VNode {
+        VNode::Text(VTextNode {
+            id,
+            content: content.to_string(),
+        })
+    }
+
+    fn element(id: u32, tag: &str, attrs: &[(&str, &str)], children: Vec<VNode>) -> VNode {
+        VNode::Element(VElement {
+            id,
+            tag: tag.to_string(),
+            ns: None,
+            attrs: attrs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            children,
+        })
+    }
+
+    fn text_changed(node_id: u32, inserted: &str) -> Frame {
+        Frame::DomTextChanged(DomTextChangedData {
+            node_id,
+            operations: vec![TextOperationData::Insert(TextInsertOperationData {
+                index: 0,
+                text: inserted.to_string(),
+            })],
+        })
+    }
+
+    #[test]
+    fn test_mask_keeps_whitespace() {
+        assert_eq!(mask("Jane Doe\n42"), "**** ***\n**");
+    }
+
+    #[test]
+    fn test_redacts_content_and_keeps_code() {
+        let mut redactor = Redactor::default();
+        let added = Frame::DomNodeAdded(DomNodeAddedData {
+            parent_node_id: 1,
+            index: 0,
+            node: element(
+                2,
+                "div",
+                &[("class", "card"), ("title", "Jane")],
+                vec![text(3, "Jane Doe"), element(4, "style", &[], vec![text(5, ".a{}")])],
+            ),
+        });
+        let Some(Frame::DomNodeAdded(added)) = redactor.redact(added) else {
+            panic!("DomNodeAdded was dropped");
+        };
+        assert_eq!(
+            added.node,
+            element(
+                2,
+                "div",
+                &[("class", "card"), ("title", "****")],
+                vec![text(3, "**** ***"), element(4, "style", &[], vec![text(5, ".a{}")])],
+            )
+        );
+
+        assert_eq!(redactor.redact(text_changed(3, "John")), Some(text_changed(3, "****")));
+        assert_eq!(redactor.redact(text_changed(5, ".b{}")), Some(text_changed(5, ".b{}")));
+    }
+
+    #[test]
+    fn test_inputs_only_masks_values() {
+        let mut redactor = Redactor::inputs_only();
+        let added = Frame::DomNodeAdded(DomNodeAddedData {
+            parent_node_id: 1,
+            index: 0,
+            node: element(2, "label", &[], vec![text(3, "Card"), element(4, "input", &[("value", "4111")], vec![])]),
+        });
+        let Some(Frame::DomNodeAdded(added)) = redactor.redact(added) else {
+            panic!("DomNodeAdded was dropped");
+        };
+        assert_eq!(
+            added.node,
+            element(2, "label", &[], vec![text(3, "Card"), element(4, "input", &[("value", "****")], vec![])])
+        );
+        assert_eq!(redactor.redact(text_changed(3, "Name")), Some(text_changed(3, "Name")));
+    }
+
+    #[test]
+    fn test_drops_keystrokes_and_error_details() {
+        let mut redactor = Redactor::default();
+        let key = Frame::KeyPressed(KeyPressedData {
+            code: "KeyJ".to_string(),
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+            shift_key: true,
+        });
+        assert_eq!(redactor.redact(key), None);
+
+        let error = Frame::PageError(PageErrorData {
+            message: "No account for jane@example.com".to_string(),
+            source_url: Some("https://example.com/app.js".to_string()),
+            line: Some(1),
+            column: None,
+            stack: Some("at login (app.js:1)".to_string()),
+        });
+        let Some(Frame::PageError(error)) = redactor.redact(error) else {
+            panic!("PageError was dropped");
+        };
+        assert_eq!(error.message, "** ******* *** ****************");
+        assert_eq!(error.stack, None);
+        assert_eq!(strip_query("https://example.com/a?email=x#top"), "https://example.com/a");
+    }
+}
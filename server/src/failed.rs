@@ -0,0 +1,180 @@
+//! Failed recordings
+//!
+//! A recording whose ingestion fails part way (a corrupt frame, a write
+//! error, a dropped upload) is set aside by the recording store instead of
+//! being deleted, and is no longer listed with the other recordings.
+//! `GET /recordings/failed` lists these recordings. `GET /recordings/failed/{id}`
+//! reports where parsing stops, and `.../raw` downloads the bytes as written.
+//! `POST .../repair` salvages the frames before the failure point into a
+//! playable recording under the original id.
+//!
+//! An encrypted recording's data key moves with it when it is set aside, to
+//! the metadata of [`failed_key_id`], since a new recording may be written
+//! under the same id (and key) before the failed one is repaired.
+
+use crate::analytics::IngestAnalytics;
+use crate::encryption::{DecryptingReader, EncryptionError};
+use crate::recording_store::RecordingReader;
+use crate::{RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use crate::storage::stored_header;
+use domcorder_proto::{FrameReader, FrameWriter};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tracing::{info, warn};
+
+/// A recording set aside after its ingestion failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedRecording {
+    /// The id it was written under
+    pub id: String,
+    /// Bytes as stored
+    pub size: u64,
+    pub created: DateTime<Utc>,
+}
+
+/// How far a failed recording parses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureReport {
+    pub id: String,
+    /// Frames that parse, before the failure point; also the index of the
+    /// frame that doesn't
+    pub frames: u64,
+    /// Byte offset (decrypted, header included) of the first frame that
+    /// doesn't parse, or of the end if every frame does
+    pub offset: u64,
+    /// Why parsing stopped, if it did
+    pub error: Option<String>,
+}
+
+/// The id the data key of failed recording `id` is stored under
+pub(crate) fn failed_key_id(id: &RecordingId) -> String {
+    format!("{}.failed", id)
+}
+
+impl StorageState {
+    /// Every failed recording, newest first
+    pub async fn list_failed_recordings(&self) -> Result<Vec<FailedRecording>, StorageError> {
+        let mut failed: Vec<FailedRecording> = self
+            .recording_store
+            .list_failed()
+            .await?
+            .into_iter()
+            .map(|recording| FailedRecording {
+                id: recording.filename,
+                size: recording.size,
+                created: recording.header_created.unwrap_or(recording.created),
+            })
+            .collect();
+        failed.sort_by_key(|recording| std::cmp::Reverse(recording.created));
+        Ok(failed)
+    }
+
+    /// Open failed recording `id` from the start, decrypting it if it was
+    /// stored encrypted
+    pub async fn open_failed_recording(&self, id: &RecordingId) -> Result<RecordingReader, StorageError> {
+        let reader = self
+            .recording_store
+            .open_failed(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        let Some(wrapped) = self.metadata_store.get_recording_key(&failed_key_id(id)).await? else {
+            return Ok(reader);
+        };
+        let key = self
+            .key_ring
+            .as_ref()
+            .ok_or(EncryptionError::NoMasterKey)?
+            .unwrap_key(&wrapped)?;
+        Ok(Box::new(DecryptingReader::new(reader, key, 0)))
+    }
+
+    /// Parse failed recording `id` up to the point where it fails
+    pub async fn inspect_failed_recording(&self, id: &RecordingId) -> Result<FailureReport, StorageError> {
+        let mut reader = FrameReader::new(self.open_failed_recording(id).await?, true);
+        let mut frames = 0;
+        let error = loop {
+            match reader.read_frame().await {
+                Ok(Some(_)) => frames += 1,
+                Ok(None) => break None,
+                Err(e) => break Some(e.to_string()),
+            }
+        };
+        Ok(FailureReport {
+            id: id.to_string(),
+            frames,
+            offset: reader.position(),
+            error,
+        })
+    }
+
+    /// Write the frames of failed recording `id` before its failure point to
+    /// a recording under the same id and delete the failed one, returning
+    /// what was salvaged
+    ///
+    /// Fails if a recording with that id exists, or if the failed one has no
+    /// readable header.
+    pub async fn repair_failed_recording(&self, id: &RecordingId) -> Result<FailureReport, StorageError> {
+        if self.recording_exists(id).await {
+            let exists = io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", id));
+            return Err(StorageError::Io(exists));
+        }
+        let mut reader = FrameReader::new(self.open_failed_recording(id).await?, true);
+        let header = reader.read_header().await.map_err(StorageError::Frame)?;
+
+        let mut writer = FrameWriter::new(self.create_recording(id).await?);
+        let mut analytics = IngestAnalytics::new(id.as_str(), None);
+        let mut frames = 0;
+        let salvaged = async {
+            writer.write_header(&stored_header(&header))?;
+            let error = loop {
+                match reader.read_frame().await {
+                    Ok(Some(frame)) => {
+                        writer.write_frame(&frame)?;
+                        analytics.push_frame(&frame);
+                        frames += 1;
+                    }
+                    Ok(None) => break None,
+                    Err(e) => break Some(e.to_string()),
+                }
+            };
+            writer.write_index()?;
+            writer.flush()?;
+            writer.into_inner().finish()?;
+            Ok::<_, io::Error>(error)
+        }
+        .await;
+        self.mark_recording_completed(id);
+        let error = match salvaged {
+            Ok(error) => error,
+            Err(e) => {
+                if let Err(e) = self.recording_store.delete(id).await {
+                    warn!("⚠️ Failed to remove partial repair of {}: {}", id, e);
+                }
+                return Err(e.into());
+            }
+        };
+
+        if let Err(e) = analytics.persist(self.metadata_store.as_ref()).await {
+            warn!("⚠️ Failed to store analytics of repaired recording {}: {}", id, e);
+        }
+        if let Err(e) = self.index_recording_assets(id).await {
+            warn!("⚠️ Failed to index the asset references of repaired recording {}: {}", id, e);
+        }
+        self.recording_store
+            .delete_failed(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        if let Err(e) = self.metadata_store.delete_recording_metadata(&failed_key_id(id)).await {
+            warn!("⚠️ Failed to forget the key of repaired recording {}: {}", id, e);
+        }
+        self.record_provenance(id, "repair", &[id]).await;
+        info!("🩹 Repaired recording {} from {} frames", id, frames);
+        Ok(FailureReport {
+            id: id.to_string(),
+            frames,
+            offset: reader.position(),
+            error,
+        })
+    }
+}
@@ -0,0 +1,230 @@
+//! Templates for generated recording filenames
+//!
+//! When a recorder does not name its recording, the server renders the
+//! configured template, e.g. `{site}/{yyyy}/{mm}/{dd}/{uuid}.dcrr` to file
+//! recordings by site and day. Slashes in the result become subdirectories.
+//!
+//! Variables:
+//! - `{site}`: host (and port) of the recording's initial URL, or `unknown`
+//! - `{yyyy}`, `{mm}`, `{dd}`: UTC date the recording started
+//! - `{HH}`, `{MM}`, `{SS}`, `{nanos}`: UTC time of day
+//! - `{timestamp}`: Unix time in milliseconds
+//! - `{uuid}`: random id, required so generated names never collide
+
+use crate::RecordingId;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The historical naming scheme: `2024-01-31_12-00-00.123456789_<uuid>.dcrr`
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{yyyy}-{mm}-{dd}_{HH}-{MM}-{SS}.{nanos}_{uuid}.dcrr";
+
+/// `{site}` value when the initial URL has no host
+const UNKNOWN_SITE: &str = "unknown";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    Site,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Nanos,
+    Timestamp,
+    Uuid,
+}
+
+impl Variable {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "site" => Variable::Site,
+            "yyyy" => Variable::Year,
+            "mm" => Variable::Month,
+            "dd" => Variable::Day,
+            "HH" => Variable::Hour,
+            "MM" => Variable::Minute,
+            "SS" => Variable::Second,
+            "nanos" => Variable::Nanos,
+            "timestamp" => Variable::Timestamp,
+            "uuid" => Variable::Uuid,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(Variable),
+}
+
+/// Error returned when a filename template cannot be used
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid filename template {template:?}: {reason}")]
+pub struct InvalidFilenameTemplate {
+    pub template: String,
+    pub reason: String,
+}
+
+/// A parsed filename template; see the module docs for the variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+impl FilenameTemplate {
+    pub fn new(template: impl Into<String>) -> Result<Self, InvalidFilenameTemplate> {
+        let template = template.into();
+        let invalid = |reason: String| InvalidFilenameTemplate {
+            template: template.clone(),
+            reason,
+        };
+
+        let mut parts = Vec::new();
+        let mut rest = template.as_str();
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| invalid("unclosed '{'".to_string()))?;
+            let name = &rest[open + 1..open + close];
+            if name.contains('{') {
+                return Err(invalid("unclosed '{'".to_string()));
+            }
+            let variable = Variable::parse(name).ok_or_else(|| invalid(format!("unknown variable {{{}}}", name)))?;
+            parts.push(Part::Variable(variable));
+            rest = &rest[open + close + 1..];
+        }
+        if rest.contains('}') {
+            return Err(invalid("unmatched '}'".to_string()));
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        if !parts.contains(&Part::Variable(Variable::Uuid)) {
+            return Err(invalid("must contain {uuid} so generated names are unique".to_string()));
+        }
+        let parsed = Self { template: template.clone(), parts };
+
+        // Listings only pick up .dcrr files, and the result must be a usable id
+        let sample = parsed.render(Some("https://example.com"), Utc::now());
+        if !sample.ends_with(".dcrr") {
+            return Err(invalid("must end in .dcrr".to_string()));
+        }
+        RecordingId::new(sample).map_err(|e| invalid(e.reason.to_string()))?;
+        Ok(parsed)
+    }
+
+    /// Render a filename for a recording of `site_origin` started at `now`
+    pub fn render(&self, site_origin: Option<&str>, now: DateTime<Utc>) -> String {
+        let mut filename = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => filename.push_str(text),
+                Part::Variable(variable) => filename.push_str(&match variable {
+                    Variable::Site => site_segment(site_origin),
+                    Variable::Year => format!("{:04}", now.year()),
+                    Variable::Month => format!("{:02}", now.month()),
+                    Variable::Day => format!("{:02}", now.day()),
+                    Variable::Hour => format!("{:02}", now.hour()),
+                    Variable::Minute => format!("{:02}", now.minute()),
+                    Variable::Second => format!("{:02}", now.second()),
+                    Variable::Nanos => format!("{:09}", now.nanosecond()),
+                    Variable::Timestamp => now.timestamp_millis().to_string(),
+                    Variable::Uuid => Uuid::new_v4().simple().to_string(),
+                }),
+            }
+        }
+        filename
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self::new(DEFAULT_FILENAME_TEMPLATE).expect("default template is valid")
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = InvalidFilenameTemplate;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        Self::new(template)
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+/// `{site}` for an origin: its host and port, reduced to filename-safe characters
+fn site_segment(site_origin: Option<&str>) -> String {
+    let host = site_origin
+        .map(|origin| origin.split_once("://").map_or(origin, |(_, host)| host))
+        .unwrap_or_default();
+    let segment: String = host
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect();
+    // Dots alone would make a '.' or '..' path segment
+    if segment.chars().all(|c| c == '.') {
+        UNKNOWN_SITE.to_string()
+    } else {
+        segment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render_site_and_date_directories() {
+        let template = FilenameTemplate::new("{site}/{yyyy}/{mm}/{dd}/{HH}{MM}{SS}_{uuid}.dcrr").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 7, 9, 5, 1).unwrap();
+
+        let filename = template.render(Some("https://Shop.example.com:8443"), now);
+        let (dirs, name) = filename.rsplit_once('/').unwrap();
+        assert_eq!(dirs, "shop.example.com_8443/2024/03/07");
+        assert!(name.starts_with("090501_") && name.ends_with(".dcrr"));
+        assert_eq!(name.len(), "090501_".len() + 32 + ".dcrr".len());
+
+        assert!(template.render(None, now).starts_with("unknown/2024/03/07/"));
+    }
+
+    #[test]
+    fn test_default_template_matches_historical_names() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        let filename = FilenameTemplate::default().render(None, now);
+        assert!(filename.starts_with("2024-01-31_12-00-00.000000000_"));
+    }
+
+    #[test]
+    fn test_rejects_unusable_templates() {
+        let reason = |template: &str| FilenameTemplate::new(template).unwrap_err().reason;
+        assert_eq!(reason("{site}/{uuid}.dcrr}"), "unmatched '}'");
+        assert_eq!(reason("{site/{uuid}.dcrr"), "unclosed '{'");
+        assert_eq!(reason("{yyyy}/{hour}_{uuid}.dcrr"), "unknown variable {hour}");
+        assert_eq!(reason("{site}/{timestamp}.dcrr"), "must contain {uuid} so generated names are unique");
+        assert_eq!(reason("{uuid}.txt"), "must end in .dcrr");
+        assert!(FilenameTemplate::new("/{uuid}.dcrr").is_err());
+        assert!(FilenameTemplate::new("{site}/../{uuid}.dcrr").is_err());
+    }
+}
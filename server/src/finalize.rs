@@ -0,0 +1,143 @@
+//! Forced finalization of active recordings
+//!
+//! A recording stays active while its session is writing it. A session can
+//! get stuck (a half-open connection that never sends or closes, a wedged
+//! task), leaving the recording active: it can't be deleted or moved, and
+//! live viewers keep waiting for frames. `POST /recording/{id}/finalize`
+//! asks the session to stop reading and finish the recording as if its
+//! input had ended. A session that doesn't within a grace period is taken
+//! over: the recording is marked completed, which ends live playback, and
+//! its summary is rebuilt from the frames on disk.
+//!
+//! A server that crashes leaves its active recordings unfinished, and the
+//! next one starts with none active. At startup [`StorageState::reconcile_recordings`]
+//! finds them by the write claims they left behind. One whose frames all
+//! parse is finalized as above; one that ends in a partial frame is set
+//! aside as failed and repaired (see [`crate::failed`]).
+
+use crate::analytics::IngestAnalytics;
+use crate::{RecordingId, StorageError, StorageState};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// How long `POST /recording/{id}/finalize` waits for the session to finish
+pub const FINALIZE_GRACE: Duration = Duration::from_secs(5);
+
+/// How often the session is checked on during the grace period
+const FINALIZE_POLL: Duration = Duration::from_millis(50);
+
+/// Recordings left unfinished by a previous server, by what became of them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// Every frame parsed, so the recording was kept as it is
+    pub finalized: Vec<String>,
+    /// The frames before a partial or corrupt tail were salvaged
+    pub repaired: Vec<String>,
+    /// Nothing could be salvaged, so the recording was set aside as failed
+    pub failed: Vec<String>,
+}
+
+/// The outcome of finalizing an active recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finalization {
+    pub id: String,
+    /// The session didn't finish within the grace period, so the recording
+    /// was taken over
+    pub forced: bool,
+}
+
+impl StorageState {
+    /// Stop the session writing recording `id` and complete it, taking it
+    /// over if the session hasn't finished after `grace`
+    ///
+    /// Fails with [`StorageError::NotFound`] if there's no such recording,
+    /// [`StorageError::NotActive`] if it isn't being written, and
+    /// [`StorageError::InUse`] if another server is writing it.
+    pub async fn finalize_recording(&self, id: &RecordingId, grace: Duration) -> Result<Finalization, StorageError> {
+        let Some(signal) = self.finalize_signal(id) else {
+            if self.active_registry.remote_lease(id).is_some() {
+                return Err(StorageError::InUse(id.to_string()));
+            }
+            if self.recording_exists(id).await {
+                return Err(StorageError::NotActive(id.to_string()));
+            }
+            return Err(StorageError::NotFound(id.to_string()));
+        };
+        info!("🏁 Finalizing active recording {}", id);
+        signal.notify_one();
+
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if !self.is_recording_active(id) {
+                return Ok(Finalization {
+                    id: id.to_string(),
+                    forced: false,
+                });
+            }
+            tokio::time::sleep(FINALIZE_POLL).await;
+        }
+
+        warn!("⚠️ Session writing {} didn't finish; taking the recording over", id);
+        self.mark_recording_completed(id);
+        self.rebuild_summary(id).await?;
+        Ok(Finalization {
+            id: id.to_string(),
+            forced: true,
+        })
+    }
+
+    /// Finish the recordings a previous server was writing when it stopped,
+    /// returning what became of them; failures for one recording are logged
+    pub async fn reconcile_recordings(&self) -> Result<Reconciliation, StorageError> {
+        let mut reconciliation = Reconciliation::default();
+        for id in self.recording_store.release_abandoned().await? {
+            // Its write claim was taken before anything was written
+            if !self.recording_exists(&id).await {
+                continue;
+            }
+            match self.reconcile_recording(&id).await {
+                Ok(true) => reconciliation.finalized.push(id.to_string()),
+                Ok(false) => reconciliation.repaired.push(id.to_string()),
+                Err(e) => {
+                    warn!("⚠️ Failed to recover unfinished recording {}: {}", id, e);
+                    reconciliation.failed.push(id.to_string());
+                }
+            }
+        }
+        Ok(reconciliation)
+    }
+
+    /// Finalize unfinished recording `id` if its frames all parse (true), or
+    /// salvage the ones that do (false)
+    async fn reconcile_recording(&self, id: &RecordingId) -> Result<bool, StorageError> {
+        let mut reader = self.open_recording_reader(id).await?;
+        let intact = async {
+            reader.read_header().await?;
+            while reader.read_frame().await?.is_some() {}
+            Ok::<_, std::io::Error>(())
+        }
+        .await
+        .is_ok();
+        if intact {
+            self.rebuild_summary(id).await?;
+            return Ok(true);
+        }
+        self.fail_recording(id).await;
+        self.repair_failed_recording(id).await?;
+        Ok(false)
+    }
+
+    /// Recompute the analytics of recording `id` from its readable frames
+    async fn rebuild_summary(&self, id: &RecordingId) -> Result<(), StorageError> {
+        let mut reader = self.open_recording_reader(id).await?;
+        let mut analytics = IngestAnalytics::new(id.as_str(), None);
+        // The stuck session may have left a partial frame at the end
+        while let Ok(Some(frame)) = reader.read_frame().await {
+            analytics.push_frame(&frame);
+        }
+        analytics.persist(self.metadata_store.as_ref()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+//! Byte budgets per frame type
+//!
+//! A page redrawing a canvas every frame can write gigabytes of
+//! CanvasChanged in one session. A deployment can cap the bytes stored of a
+//! frame type per recording; once a type's budget is spent, its frames are
+//! summarized instead of stored. Where that happens is marked with an
+//! Annotation frame named [`FRAME_BUDGET_ANNOTATION`], and from then on only
+//! the frames' count and size and the last state they set are kept: the
+//! latest frame per node (or per window, for scrolling, resizing and pointer
+//! movement) is written when the recording ends, after a summary Annotation
+//! of what was left out. Frames that change rather than set state (DOM
+//! mutations, key presses, canvas deltas) are only counted, so budgeting them
+//! can leave the replayed page out of step with the recording.
+//!
+//! What was summarized is reported per type as `summarized_frames` in the
+//! recording's info. The budgets are applied by a [`FrameBudgetEnforcer`]
+//! per recording, one of the [`FrameProcessor`] stages of the ingestion
+//! pipeline.
+
+use crate::frame_filter::FrameProcessor;
+use domcorder_proto::{AnnotationData, Frame};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Name of the Annotation frames marking where a frame type went over its
+/// budget and what was summarized
+pub const FRAME_BUDGET_ANNOTATION: &str = "domcorder:frame-budget";
+
+/// Frames of one type left out of a recording once its budget was spent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SummarizedFrames {
+    pub frames: u64,
+    /// Encoded bytes, including each frame's length prefix
+    pub bytes: u64,
+}
+
+/// The payload of a [`FRAME_BUDGET_ANNOTATION`] Annotation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetAnnotation {
+    pub frame_type: String,
+    pub budget_bytes: u64,
+    /// What was left out, in the summary written when the recording ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summarized: Option<SummarizedFrames>,
+}
+
+/// Bytes stored per recording, by frame type name (see [`Frame::type_name`]);
+/// the default sets no budgets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameBudgets {
+    pub bytes: BTreeMap<String, u64>,
+}
+
+impl FrameBudgets {
+    /// Parse `Type=bytes` pairs separated by commas, e.g.
+    /// `CanvasChanged=5000000,StyleSheetReplaced=1000000`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut bytes = BTreeMap::new();
+        for pair in spec.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (type_name, budget) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected Type=bytes, got {:?}", pair))?;
+            let budget = budget
+                .trim()
+                .parse()
+                .map_err(|_| format!("expected a number of bytes for {}, got {:?}", type_name.trim(), budget))?;
+            bytes.insert(type_name.trim().to_string(), budget);
+        }
+        Ok(Self { bytes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// An enforcer applying these budgets to one recording
+    pub fn enforcer(&self) -> FrameBudgetEnforcer {
+        FrameBudgetEnforcer {
+            budgets: self.clone(),
+            spent: BTreeMap::new(),
+            summarized: BTreeMap::new(),
+            last_state: BTreeMap::new(),
+        }
+    }
+}
+
+/// What a frame sets the state of, if it sets rather than changes it: the
+/// node, or None for the window
+fn state_key(frame: &Frame) -> Option<Option<u32>> {
+    match frame {
+        Frame::ViewportResized(_)
+        | Frame::ScrollOffsetChanged(_)
+        | Frame::ScrollOffsetChangedV2(_)
+        | Frame::MouseMoved(_)
+        | Frame::MouseMovedV2(_) => Some(None),
+        Frame::CanvasChanged(data) => Some(Some(data.node_id)),
+        Frame::DomNodeResized(data) => Some(Some(data.node_id)),
+        Frame::ElementScrolled(data) => Some(Some(data.node_id)),
+        Frame::ElementScrolledV2(data) => Some(Some(data.node_id)),
+        _ => None,
+    }
+}
+
+fn annotation(annotation: &BudgetAnnotation) -> Frame {
+    Frame::Annotation(AnnotationData {
+        name: FRAME_BUDGET_ANNOTATION.to_string(),
+        data: Some(serde_json::to_string(annotation).expect("budget annotations serialize")),
+    })
+}
+
+/// Applies [`FrameBudgets`] to the frames of one recording
+#[derive(Debug)]
+pub struct FrameBudgetEnforcer {
+    budgets: FrameBudgets,
+    /// Bytes stored so far, by type
+    spent: BTreeMap<&'static str, u64>,
+    summarized: BTreeMap<&'static str, SummarizedFrames>,
+    /// The latest summarized frame of each type and node
+    last_state: BTreeMap<(&'static str, Option<u32>), Frame>,
+}
+
+impl FrameBudgetEnforcer {
+    /// End the recording, returning the summary of each type over budget
+    /// followed by the last state its frames set
+    pub fn finish(&mut self) -> Vec<Frame> {
+        let mut frames: Vec<Frame> = self
+            .summarized
+            .iter()
+            .map(|(type_name, summarized)| {
+                annotation(&BudgetAnnotation {
+                    frame_type: type_name.to_string(),
+                    budget_bytes: self.budgets.bytes[*type_name],
+                    summarized: Some(*summarized),
+                })
+            })
+            .collect();
+        frames.extend(std::mem::take(&mut self.last_state).into_values());
+        frames
+    }
+
+    /// Frames left out by type
+    pub fn into_summarized(self) -> BTreeMap<String, SummarizedFrames> {
+        self.summarized
+            .into_iter()
+            .map(|(type_name, summarized)| (type_name.to_string(), summarized))
+            .collect()
+    }
+}
+
+impl FrameProcessor for FrameBudgetEnforcer {
+    fn process(&mut self, frame: Frame) -> Option<Frame> {
+        if matches!(frame, Frame::Keyframe(_) | Frame::KeyframeBegin(_)) {
+            // Node ids start over with the new document
+            self.last_state.retain(|(_, node), _| node.is_none());
+        }
+        let type_name = frame.type_name();
+        let Some(&budget_bytes) = self.budgets.bytes.get(type_name) else {
+            return Some(frame);
+        };
+        let size = frame.encoded_size();
+        let spent = self.spent.entry(type_name).or_default();
+        if !self.summarized.contains_key(type_name) && *spent + size <= budget_bytes {
+            *spent += size;
+            return Some(frame);
+        }
+
+        let first = !self.summarized.contains_key(type_name);
+        let summarized = self.summarized.entry(type_name).or_default();
+        summarized.frames += 1;
+        summarized.bytes += size;
+        if let Some(node) = state_key(&frame) {
+            self.last_state.insert((type_name, node), frame);
+        }
+        first.then(|| {
+            annotation(&BudgetAnnotation {
+                frame_type: type_name.to_string(),
+                budget_bytes,
+                summarized: None,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{CanvasChangedData, KeyPressedData, ScrollOffsetChangedV2Data};
+
+    fn canvas(node_id: u32, len: usize) -> Frame {
+        Frame::CanvasChanged(CanvasChangedData {
+            node_id,
+            mime_type: "image/png".to_string(),
+            data: vec![0; len],
+        })
+    }
+
+    fn budget_annotation(frame: &Frame) -> BudgetAnnotation {
+        let Frame::Annotation(annotation) = frame else {
+            panic!("expected an Annotation, got {:?}", frame);
+        };
+        assert_eq!(annotation.name, FRAME_BUDGET_ANNOTATION);
+        serde_json::from_str(annotation.data.as_deref().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_budgets() {
+        let budgets = FrameBudgets::parse("CanvasChanged=5000000, KeyPressed=100,").unwrap();
+        assert_eq!(
+            budgets.bytes,
+            BTreeMap::from([("CanvasChanged".to_string(), 5_000_000), ("KeyPressed".to_string(), 100)])
+        );
+        assert!(FrameBudgets::parse("CanvasChanged").is_err());
+        assert!(FrameBudgets::parse("CanvasChanged=5MB").is_err());
+    }
+
+    #[test]
+    fn test_frames_over_budget_are_summarized() {
+        let size = canvas(1, 1_000).encoded_size();
+        let budgets = FrameBudgets {
+            bytes: BTreeMap::from([
+                ("CanvasChanged".to_string(), size * 2),
+                ("KeyPressed".to_string(), 0),
+            ]),
+        };
+        let mut enforcer = budgets.enforcer();
+        let scroll = Frame::ScrollOffsetChangedV2(ScrollOffsetChangedV2Data {
+            scroll_x_offset: 0,
+            scroll_y_offset: 100,
+        });
+
+        assert_eq!(enforcer.process(canvas(1, 1_000)), Some(canvas(1, 1_000)));
+        assert_eq!(enforcer.process(canvas(2, 1_000)), Some(canvas(2, 1_000)));
+        // Unbudgeted types pass through
+        assert_eq!(enforcer.process(scroll.clone()), Some(scroll));
+
+        let marker = enforcer.process(canvas(1, 1_000)).unwrap();
+        assert_eq!(
+            budget_annotation(&marker),
+            BudgetAnnotation {
+                frame_type: "CanvasChanged".to_string(),
+                budget_bytes: size * 2,
+                summarized: None,
+            }
+        );
+        // Even a frame that would fit is summarized once the budget is spent
+        assert_eq!(enforcer.process(canvas(2, 1)), None);
+        assert_eq!(enforcer.process(canvas(1, 1_000)), None);
+        let key = Frame::KeyPressed(KeyPressedData {
+            code: "KeyA".to_string(),
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+            shift_key: false,
+        });
+        assert!(enforcer.process(key).is_some());
+
+        let frames = enforcer.finish();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(
+            budget_annotation(&frames[0]).summarized,
+            Some(SummarizedFrames {
+                frames: 3,
+                bytes: size * 2 + canvas(2, 1).encoded_size(),
+            })
+        );
+        assert_eq!(budget_annotation(&frames[1]).frame_type, "KeyPressed");
+        // Key presses have no state to keep; each canvas's last image is
+        assert_eq!(frames[2..], [canvas(1, 1_000), canvas(2, 1)]);
+        assert_eq!(
+            enforcer.into_summarized().keys().collect::<Vec<_>>(),
+            ["CanvasChanged", "KeyPressed"]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Which frame types are stored
+//!
+//! Each deployment decides which Frame variants it persists: an optional
+//! allow list keeps only the named types, and named types can be dropped
+//! outright (e.g. `CanvasChanged`) or dropped and counted (e.g.
+//! `KeyPressed`, when how much was typed matters but not what). Counted
+//! frames are reported per type as `dropped_frames` in the recording's info.
+//! `RecordingMetadata` is always kept, since ingestion needs it.
+//!
+//! The rules are applied by a [`FrameTypeFilter`] per recording, one of the
+//! [`FrameProcessor`] stages of the ingestion pipeline.
+
+use domcorder_proto::Frame;
+use std::collections::{BTreeMap, HashSet};
+
+/// One stage of the ingestion pipeline, fed every frame of one recording
+pub trait FrameProcessor: Send {
+    /// The frame as it should continue down the pipeline, or None to drop it
+    fn process(&mut self, frame: Frame) -> Option<Frame>;
+}
+
+/// What happens to frames of one type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRule {
+    Keep,
+    Drop,
+    /// Dropped, but counted in the recording's info
+    Count,
+}
+
+/// Frame types stored, dropped and counted, by name (see [`Frame::type_name`]);
+/// the default stores everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameTypeRules {
+    /// Only these types are kept, if set
+    pub allowed: Option<HashSet<String>>,
+    pub dropped: HashSet<String>,
+    pub counted: HashSet<String>,
+}
+
+impl FrameTypeRules {
+    pub fn rule(&self, type_name: &str) -> FrameRule {
+        let allowed = self.allowed.as_ref().is_none_or(|allowed| allowed.contains(type_name));
+        if type_name == "RecordingMetadata" {
+            FrameRule::Keep
+        } else if self.counted.contains(type_name) {
+            FrameRule::Count
+        } else if self.dropped.contains(type_name) || !allowed {
+            FrameRule::Drop
+        } else {
+            FrameRule::Keep
+        }
+    }
+
+    /// Whether every frame is kept
+    pub fn keeps_all(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// A filter applying these rules to one recording
+    pub fn filter(&self) -> FrameTypeFilter {
+        FrameTypeFilter {
+            rules: self.clone(),
+            counted: BTreeMap::new(),
+        }
+    }
+}
+
+/// Applies [`FrameTypeRules`] to the frames of one recording
+#[derive(Debug)]
+pub struct FrameTypeFilter {
+    rules: FrameTypeRules,
+    counted: BTreeMap<String, u64>,
+}
+
+impl FrameTypeFilter {
+    /// Frames dropped under [`FrameRule::Count`], by type
+    pub fn into_counts(self) -> BTreeMap<String, u64> {
+        self.counted
+    }
+}
+
+impl FrameProcessor for FrameTypeFilter {
+    fn process(&mut self, frame: Frame) -> Option<Frame> {
+        match self.rules.rule(frame.type_name()) {
+            FrameRule::Keep => Some(frame),
+            FrameRule::Drop => None,
+            FrameRule::Count => {
+                *self.counted.entry(frame.type_name().to_string()).or_default() += 1;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{KeyPressedData, RecordingMetadataData, TimestampData};
+
+    #[test]
+    fn test_filter_frame_types() {
+        let key = Frame::KeyPressed(KeyPressedData {
+            code: "KeyA".to_string(),
+            alt_key: false,
+            ctrl_key: false,
+            meta_key: false,
+            shift_key: false,
+        });
+        let timestamp = Frame::Timestamp(TimestampData { timestamp: 1 });
+        let metadata = Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: "https://example.com/".to_string(),
+            heartbeat_interval_seconds: 10,
+        });
+
+        let rules = FrameTypeRules {
+            allowed: Some(HashSet::from(["Timestamp".to_string()])),
+            counted: HashSet::from(["KeyPressed".to_string()]),
+            ..Default::default()
+        };
+        let mut filter = rules.filter();
+        assert_eq!(filter.process(metadata.clone()), Some(metadata));
+        assert_eq!(filter.process(timestamp.clone()), Some(timestamp.clone()));
+        assert_eq!(filter.process(key.clone()), None);
+        assert_eq!(filter.process(key.clone()), None);
+        assert_eq!(filter.into_counts(), BTreeMap::from([("KeyPressed".to_string(), 2)]));
+
+        let rules = FrameTypeRules {
+            dropped: HashSet::from(["Timestamp".to_string()]),
+            ..Default::default()
+        };
+        let mut filter = rules.filter();
+        assert_eq!(filter.process(timestamp), None);
+        assert_eq!(filter.process(key.clone()), Some(key));
+        assert!(filter.into_counts().is_empty());
+    }
+}
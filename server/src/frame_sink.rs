@@ -0,0 +1,254 @@
+//! Secondary sinks that publish frames while a recording is being ingested
+//!
+//! Recordings are still written to the [`RecordingStore`](crate::RecordingStore);
+//! sinks additionally receive selected frames as they arrive, so real-time
+//! pipelines (Kafka, NATS, ...) can react to a session before it ends.
+//!
+//! With the `nats` feature, [`NatsSink`](crate::nats::NatsSink) publishes to
+//! NATS. For other brokers, implement [`FrameSink`] over the client you
+//! already use, or install a [`ChannelSink`] and forward its messages from a
+//! task you own.
+
+use serde::Serialize;
+use domcorder_proto::Frame;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Error returned by a sink that could not accept a message
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("Sink is full, message dropped")]
+    Full,
+
+    #[error("Sink is closed")]
+    Closed,
+
+    #[error("Sink error: {0}")]
+    Other(String),
+}
+
+/// Groups of frames a sink can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameCategory {
+    /// Page loads: RecordingMetadata, Keyframe and KeyframeBegin frames
+    Navigation,
+    /// PageError frames
+    Error,
+    /// Annotation frames
+    Annotation,
+    /// Everything else (DOM mutations, input, assets, ...)
+    Other,
+}
+
+impl FrameCategory {
+    pub fn of(frame: &Frame) -> Self {
+        match frame {
+            Frame::RecordingMetadata(_) | Frame::Keyframe(_) | Frame::KeyframeBegin(_) => FrameCategory::Navigation,
+            Frame::PageError(_) => FrameCategory::Error,
+            Frame::Annotation(_) => FrameCategory::Annotation,
+            _ => FrameCategory::Other,
+        }
+    }
+
+    /// Topic suffix for this category
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FrameCategory::Navigation => "navigation",
+            FrameCategory::Error => "error",
+            FrameCategory::Annotation => "annotation",
+            FrameCategory::Other => "other",
+        }
+    }
+
+    /// The category whose topic suffix is `name`
+    pub fn parse(name: &str) -> Option<Self> {
+        [
+            FrameCategory::Navigation,
+            FrameCategory::Error,
+            FrameCategory::Annotation,
+            FrameCategory::Other,
+        ]
+        .into_iter()
+        .find(|category| category.as_str() == name)
+    }
+}
+
+/// Which frames a sink receives, and the topics they are published to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSinkConfig {
+    /// Frames are published to `{topic_prefix}.{category}`
+    pub topic_prefix: String,
+    /// Categories to publish; `None` publishes every frame
+    pub categories: Option<Vec<FrameCategory>>,
+}
+
+impl FrameSinkConfig {
+    /// Publish every frame
+    pub fn all(topic_prefix: impl Into<String>) -> Self {
+        Self {
+            topic_prefix: topic_prefix.into(),
+            categories: None,
+        }
+    }
+
+    /// Publish only frames in `categories`
+    pub fn only(topic_prefix: impl Into<String>, categories: &[FrameCategory]) -> Self {
+        Self {
+            topic_prefix: topic_prefix.into(),
+            categories: Some(categories.to_vec()),
+        }
+    }
+
+    /// The topic for `frame`, or None if it is not selected
+    pub fn topic_for(&self, frame: &Frame) -> Option<String> {
+        let category = FrameCategory::of(frame);
+        let selected = match &self.categories {
+            Some(categories) => categories.contains(&category),
+            None => true,
+        };
+        selected.then(|| format!("{}.{}", self.topic_prefix, category.as_str()))
+    }
+}
+
+/// A frame published from an ingesting recording
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkMessage {
+    #[serde(skip)]
+    pub topic: String,
+    pub recording_id: String,
+    pub site_origin: Option<String>,
+    pub category: FrameCategory,
+    pub frame: Frame,
+}
+
+impl SinkMessage {
+    /// The message body as JSON; frames use the same encoding as the NDJSON endpoint
+    pub fn payload(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(self)
+    }
+}
+
+/// Destination for published frames
+///
+/// `publish` is awaited on the ingest path, so implementations should buffer
+/// rather than wait on the network.
+#[async_trait::async_trait]
+pub trait FrameSink: Send + Sync {
+    async fn publish(&self, message: SinkMessage) -> Result<(), SinkError>;
+}
+
+/// A sink that hands messages to a bounded channel
+///
+/// Messages are dropped (and logged) when the channel is full, so a slow
+/// consumer never holds up ingestion.
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: mpsc::Sender<SinkMessage>,
+}
+
+impl ChannelSink {
+    /// Create a sink and the receiver to forward its messages from
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<SinkMessage>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        (Self { sender }, receiver)
+    }
+}
+
+#[async_trait::async_trait]
+impl FrameSink for ChannelSink {
+    async fn publish(&self, message: SinkMessage) -> Result<(), SinkError> {
+        self.sender.try_send(message).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => SinkError::Full,
+            mpsc::error::TrySendError::Closed(_) => SinkError::Closed,
+        })
+    }
+}
+
+/// The sinks installed on a StorageState
+#[derive(Default)]
+pub struct FrameSinks {
+    sinks: Vec<(FrameSinkConfig, Box<dyn FrameSink>)>,
+}
+
+impl std::fmt::Debug for FrameSinks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.sinks.iter().map(|(config, _)| config))
+            .finish()
+    }
+}
+
+impl FrameSinks {
+    pub fn push(&mut self, config: FrameSinkConfig, sink: Box<dyn FrameSink>) {
+        self.sinks.push((config, sink));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Publish a frame to every sink that selects it; failures are logged, not fatal
+    pub async fn publish(&self, recording_id: &str, site_origin: Option<&str>, frame: &Frame) {
+        for (config, sink) in &self.sinks {
+            let Some(topic) = config.topic_for(frame) else {
+                continue;
+            };
+            let message = SinkMessage {
+                topic,
+                recording_id: recording_id.to_string(),
+                site_origin: site_origin.map(str::to_string),
+                category: FrameCategory::of(frame),
+                frame: frame.clone(),
+            };
+            if let Err(e) = sink.publish(message).await {
+                warn!("⚠️ Failed to publish frame for {} to {}: {}", recording_id, config.topic_prefix, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{AnnotationData, TimestampData};
+
+    fn annotation() -> Frame {
+        Frame::Annotation(AnnotationData {
+            name: "checkout".to_string(),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn test_topics_follow_the_selected_categories() {
+        let errors = FrameSinkConfig::only("sessions", &[FrameCategory::Error, FrameCategory::Annotation]);
+        let timestamp = Frame::Timestamp(TimestampData { timestamp: 1 });
+
+        assert_eq!(errors.topic_for(&annotation()).as_deref(), Some("sessions.annotation"));
+        assert_eq!(errors.topic_for(&timestamp), None);
+        assert_eq!(FrameCategory::parse("error"), Some(FrameCategory::Error));
+        assert_eq!(FrameCategory::parse("errors"), None);
+        assert_eq!(
+            FrameSinkConfig::all("sessions").topic_for(&timestamp).as_deref(),
+            Some("sessions.other")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_sink_drops_when_full() {
+        let (sink, mut receiver) = ChannelSink::new(1);
+        let mut sinks = FrameSinks::default();
+        sinks.push(FrameSinkConfig::all("sessions"), Box::new(sink.clone()));
+
+        sinks.publish("a.dcrr", Some("https://example.com"), &annotation()).await;
+        let message = receiver.recv().await.unwrap();
+        assert_eq!(message.topic, "sessions.annotation");
+        assert_eq!(message.recording_id, "a.dcrr");
+        let payload: serde_json::Value = serde_json::from_slice(&message.payload().unwrap()).unwrap();
+        assert_eq!(payload["category"], "annotation");
+
+        sink.publish(message.clone()).await.unwrap();
+        assert!(matches!(sink.publish(message).await, Err(SinkError::Full)));
+    }
+}
@@ -0,0 +1,139 @@
+//! Storage backend health checks
+//!
+//! Each store answers a cheap probe of its backend (a query against the
+//! SQLite file, a look at the storage directory, a request to the bucket).
+//! `GET /readyz` runs them together and answers 503 if any fails, so a
+//! wedged database or unreachable bucket takes the server out of rotation
+//! before recordings start failing. Each probe's latency is also reported
+//! to the observability hooks as [`names::STORAGE_HEALTH_SECONDS`].
+
+use crate::StorageState;
+use crate::observability::names;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+/// Whether a backend answered its probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Failing,
+    /// The store has no probe
+    Unknown,
+}
+
+impl HealthStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HealthStatus::Ok => "ok",
+            HealthStatus::Failing => "failing",
+            HealthStatus::Unknown => "unknown",
+        }
+    }
+}
+
+/// How one backend answered its probe
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackendHealth {
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    /// Why the probe failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BackendHealth {
+    /// The health of a store without a probe
+    pub fn unknown() -> Self {
+        Self {
+            status: HealthStatus::Unknown,
+            latency_ms: 0.0,
+            error: None,
+        }
+    }
+
+    /// Run `probe`, timing it
+    pub async fn measure<E: Display>(probe: impl Future<Output = Result<(), E>>) -> Self {
+        let started = Instant::now();
+        let result = probe.await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        match result {
+            Ok(()) => Self {
+                status: HealthStatus::Ok,
+                latency_ms,
+                error: None,
+            },
+            Err(e) => Self {
+                status: HealthStatus::Failing,
+                latency_ms,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Probe for a store kept in a local directory: it must be a directory the
+/// server can write to
+pub(crate) async fn probe_directory(path: &Path) -> io::Result<()> {
+    let metadata = tokio::fs::metadata(path).await?;
+    if !metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotADirectory,
+            format!("{} is not a directory", path.display()),
+        ));
+    }
+    if metadata.permissions().readonly() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is read-only", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// The health of every storage backend, reported by `GET /readyz`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageHealth {
+    pub metadata: BackendHealth,
+    pub assets: BackendHealth,
+    pub recordings: BackendHealth,
+}
+
+impl StorageHealth {
+    /// Whether no backend is failing
+    pub fn is_ready(&self) -> bool {
+        [&self.metadata, &self.assets, &self.recordings]
+            .iter()
+            .all(|backend| backend.status != HealthStatus::Failing)
+    }
+}
+
+impl StorageState {
+    /// Probe every storage backend at once
+    pub async fn health(&self) -> StorageHealth {
+        let (metadata, assets, recordings) = tokio::join!(
+            self.metadata_store.health(),
+            self.asset_file_store.health(),
+            self.recording_store.health(),
+        );
+        for (store, backend) in [
+            ("metadata", &metadata),
+            ("assets", &assets),
+            ("recordings", &recordings),
+        ] {
+            self.observability.histogram(
+                names::STORAGE_HEALTH_SECONDS,
+                backend.latency_ms / 1000.0,
+                &[("store", store), ("status", backend.status.as_str())],
+            );
+        }
+        StorageHealth {
+            metadata,
+            assets,
+            recordings,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Idle-period compression at ingestion
+//!
+//! A tab left open overnight keeps sending timestamps, pointer movement and
+//! heartbeats for hours. When a deployment sets a minimum idle time, spans of
+//! at least that long with nothing else in them are collapsed into an
+//! IdlePeriod frame with the span's duration, followed by its last pointer
+//! position and timestamp, so playback resumes in the same place and time.
+//!
+//! Frames of a span are held back until it either turns out to be idle or
+//! ends, so live viewers see them up to the minimum idle time late.
+
+use domcorder_proto::{Frame, IdlePeriodData};
+use std::mem;
+
+/// Whether `frame` carries nothing but the passage of time or the pointer's
+/// position
+fn is_noise(frame: &Frame) -> bool {
+    matches!(
+        frame,
+        Frame::Timestamp(_)
+            | Frame::MouseMoved(_)
+            | Frame::MouseMovedV2(_)
+            | Frame::Heartbeat
+            | Frame::HeartbeatPing(_)
+            | Frame::HeartbeatPong(_)
+    )
+}
+
+/// Collapses idle spans in the frames of one recording
+#[derive(Debug)]
+pub struct IdleCompressor {
+    /// None passes every frame straight through
+    min_idle_ms: Option<u64>,
+    /// The latest timestamp
+    now: Option<u64>,
+    /// The timestamp of the last activity, where the current span began
+    active_at: Option<u64>,
+    /// The span's frames, while it is too short to collapse
+    pending: Vec<Frame>,
+    /// The span is long enough to collapse; only its last pointer position
+    /// and timestamp are kept
+    idle: bool,
+    last_pointer: Option<Frame>,
+    last_timestamp: Option<Frame>,
+}
+
+impl IdleCompressor {
+    pub fn new(min_idle_ms: Option<u64>) -> Self {
+        Self {
+            min_idle_ms,
+            now: None,
+            active_at: None,
+            pending: Vec::new(),
+            idle: false,
+            last_pointer: None,
+            last_timestamp: None,
+        }
+    }
+
+    /// Feed the next frame, returning the frames to store so far, in order
+    pub fn push(&mut self, frame: Frame) -> Vec<Frame> {
+        let Some(min_idle_ms) = self.min_idle_ms else {
+            return vec![frame];
+        };
+        if let Frame::Timestamp(timestamp) = &frame {
+            self.now = Some(timestamp.timestamp);
+            self.active_at.get_or_insert(timestamp.timestamp);
+        }
+        if !is_noise(&frame) {
+            let mut frames = self.finish();
+            frames.push(frame);
+            self.active_at = self.now;
+            return frames;
+        }
+
+        if self.idle {
+            self.keep_last(frame);
+        } else {
+            self.pending.push(frame);
+            if self.idle_ms() >= min_idle_ms {
+                self.idle = true;
+                for frame in mem::take(&mut self.pending) {
+                    self.keep_last(frame);
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    /// End the current span, returning the frames held back for it
+    pub fn finish(&mut self) -> Vec<Frame> {
+        if !self.idle {
+            return mem::take(&mut self.pending);
+        }
+        self.idle = false;
+        let idle = Frame::IdlePeriod(IdlePeriodData {
+            duration_ms: self.idle_ms(),
+        });
+        [Some(idle), self.last_pointer.take(), self.last_timestamp.take()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Time since the last activity
+    fn idle_ms(&self) -> u64 {
+        match (self.active_at, self.now) {
+            (Some(active_at), Some(now)) => now.saturating_sub(active_at),
+            _ => 0,
+        }
+    }
+
+    fn keep_last(&mut self, frame: Frame) {
+        match frame {
+            Frame::Timestamp(_) => self.last_timestamp = Some(frame),
+            Frame::MouseMoved(_) | Frame::MouseMovedV2(_) => self.last_pointer = Some(frame),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{MouseClickedData, MouseMovedData, TimestampData};
+
+    fn timestamp(timestamp: u64) -> Frame {
+        Frame::Timestamp(TimestampData { timestamp })
+    }
+
+    fn mouse(x: u32) -> Frame {
+        Frame::MouseMoved(MouseMovedData { x, y: 0 })
+    }
+
+    fn click() -> Frame {
+        Frame::MouseClicked(MouseClickedData { x: 0, y: 0 })
+    }
+
+    fn compress(compressor: &mut IdleCompressor, frames: Vec<Frame>) -> Vec<Frame> {
+        let mut stored: Vec<Frame> = frames.into_iter().flat_map(|frame| compressor.push(frame)).collect();
+        stored.extend(compressor.finish());
+        stored
+    }
+
+    #[test]
+    fn test_short_spans_are_kept() {
+        let frames = vec![timestamp(0), click(), timestamp(500), mouse(1), timestamp(900), click()];
+        assert_eq!(compress(&mut IdleCompressor::new(Some(1000)), frames.clone()), frames);
+    }
+
+    #[test]
+    fn test_idle_spans_are_collapsed() {
+        let mut frames = vec![timestamp(0), click()];
+        for i in 1..=100 {
+            frames.extend([timestamp(i * 1000), mouse(i as u32), Frame::Heartbeat]);
+        }
+        frames.extend([timestamp(100_500), click()]);
+
+        let stored = compress(&mut IdleCompressor::new(Some(10_000)), frames);
+        assert_eq!(
+            stored,
+            vec![
+                timestamp(0),
+                click(),
+                Frame::IdlePeriod(IdlePeriodData { duration_ms: 100_500 }),
+                mouse(100),
+                timestamp(100_500),
+                click(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_idle_span_at_the_end_is_collapsed() {
+        let frames = vec![timestamp(0), click(), timestamp(5000), timestamp(60_000)];
+        let stored = compress(&mut IdleCompressor::new(Some(10_000)), frames);
+        assert_eq!(
+            stored,
+            vec![
+                timestamp(0),
+                click(),
+                Frame::IdlePeriod(IdlePeriodData { duration_ms: 60_000 }),
+                timestamp(60_000),
+            ]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Background jobs
+//!
+//! Work over many recordings runs as a job: `POST /jobs` submits one and
+//! `GET /jobs/{id}` reports its progress. A job's request is turned into a
+//! list of items (recording ids) when it is submitted, and each item then
+//! succeeds or fails on its own without stopping the job. Bulk operations
+//! (see [`crate::bulk`]) are jobs of kind `bulk`; `/recordings/bulk` submits
+//! and reports them in its own format. Jobs of kind `extract_assets` rewrite
+//! legacy recordings (see [`crate::reprocess`]).
+//!
+//! Jobs are stored in the metadata database, so they survive a restart: jobs
+//! that were queued or running start again, skipping the items whose results
+//! had been saved. Progress is saved every [`PROGRESS_SAVE_ITEMS`] items, so
+//! a few items may run twice. At most [`DEFAULT_MAX_CONCURRENT_JOBS`] jobs run
+//! at once (see [`StorageState::with_max_concurrent_jobs`]); the others wait
+//! their turn. The most recent [`FINISHED_JOBS_KEPT`] finished jobs are kept.
+
+use crate::bulk::BulkRequest;
+use crate::reprocess::ExtractAssetsRequest;
+use crate::{AppState, RecordingId, StorageError, StorageState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+/// Finished jobs kept for polling
+pub const FINISHED_JOBS_KEPT: usize = 100;
+
+/// Jobs run at once, by default
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 2;
+
+/// Items run between saves of a job's progress
+pub const PROGRESS_SAVE_ITEMS: usize = 50;
+
+/// What a job does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobRequest {
+    /// Delete, tag, move, export or redact recordings
+    Bulk(BulkRequest),
+    /// Move the inline assets of legacy recordings to the asset cache
+    ExtractAssets(ExtractAssetsRequest),
+}
+
+impl JobRequest {
+    /// Why the request can't be run, if it can't
+    pub fn check(&self) -> Result<(), &'static str> {
+        match self {
+            JobRequest::Bulk(request) => request.check(),
+            JobRequest::ExtractAssets(_) => Ok(()),
+        }
+    }
+
+    /// The items the job works through
+    async fn items(&self, state: &StorageState) -> Result<Vec<String>, StorageError> {
+        match self {
+            JobRequest::Bulk(request) => request.recording_ids(state).await,
+            JobRequest::ExtractAssets(request) => request.recording_ids(state).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a running job to finish
+    Queued,
+    Running,
+    Finished,
+}
+
+/// How one item of a job went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobItemResult {
+    pub item: String,
+    /// What the item produced, e.g. a moved recording's new id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A job and its progress
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(flatten)]
+    pub request: JobRequest,
+    pub status: JobStatus,
+    /// Every item the job works through
+    pub items: Vec<String>,
+    /// The items done so far, in the order they finished
+    pub results: Vec<JobItemResult>,
+    pub created: DateTime<Utc>,
+    pub started: Option<DateTime<Utc>>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+/// The jobs this server is running, and the limit on how many run at once
+#[derive(Debug)]
+pub struct Jobs {
+    /// Queued and running jobs, with their latest progress
+    active: Mutex<HashMap<String, Job>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_JOBS)
+    }
+}
+
+impl Jobs {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            active: Mutex::default(),
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    fn update(&self, job: &Job) {
+        self.active.lock().unwrap().insert(job.id.clone(), job.clone());
+    }
+}
+
+impl StorageState {
+    /// Run at most `max` jobs at once
+    pub fn with_max_concurrent_jobs(mut self, max: usize) -> Self {
+        self.jobs = Jobs::new(max);
+        self
+    }
+
+    /// A job and its latest progress, if it is known
+    pub async fn job(&self, id: &str) -> Result<Option<Job>, StorageError> {
+        if let Some(job) = self.jobs.active.lock().unwrap().get(id) {
+            return Ok(Some(job.clone()));
+        }
+        Ok(self.metadata_store.get_job(id).await?)
+    }
+
+    /// Run one item of `job`, returning what it produced
+    async fn run_job_item(&self, job: &Job, item: &str) -> Result<Option<String>, StorageError> {
+        match &job.request {
+            JobRequest::Bulk(request) => self.apply_bulk_operation(&job.id, request, item).await,
+            JobRequest::ExtractAssets(_) => {
+                let extracted = self.extract_inline_assets(&RecordingId::new(item)?).await?;
+                Ok((extracted > 0).then(|| format!("{} assets extracted", extracted)))
+            }
+        }
+    }
+}
+
+/// Select the items of `request` and queue it, returning the job as queued
+pub async fn submit_job(state: &AppState, request: JobRequest) -> Result<Job, StorageError> {
+    let job = Job {
+        id: uuid::Uuid::new_v4().to_string(),
+        items: request.items(state).await?,
+        request,
+        status: JobStatus::Queued,
+        results: Vec::new(),
+        created: Utc::now(),
+        started: None,
+        finished: None,
+    };
+    state.metadata_store.save_job(&job).await?;
+    info!("🧰 Queued job {} over {} items", job.id, job.items.len());
+    state.jobs.update(&job);
+    tokio::spawn(run_job(state.clone(), job.clone()));
+    Ok(job)
+}
+
+/// Queue again the jobs a previous run left queued or running, returning
+/// how many there were
+pub async fn resume_jobs(state: &AppState) -> Result<usize, StorageError> {
+    let jobs = state.metadata_store.list_unfinished_jobs().await?;
+    let resumed = jobs.len();
+    for mut job in jobs {
+        job.status = JobStatus::Queued;
+        info!(
+            "🧰 Resuming job {} ({} of {} items done)",
+            job.id,
+            job.results.len(),
+            job.items.len()
+        );
+        state.jobs.update(&job);
+        tokio::spawn(run_job(state.clone(), job));
+    }
+    Ok(resumed)
+}
+
+/// Work through the items of `job` once a slot is free
+async fn run_job(state: AppState, mut job: Job) {
+    let _permit = state
+        .jobs
+        .permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("the job semaphore is never closed");
+    job.status = JobStatus::Running;
+    job.started = Some(Utc::now());
+    save_progress(&state, &job).await;
+
+    let done: HashSet<String> = job.results.iter().map(|result| result.item.clone()).collect();
+    let remaining: Vec<String> = job.items.iter().filter(|item| !done.contains(*item)).cloned().collect();
+    for (n, item) in remaining.into_iter().enumerate() {
+        let result = match state.run_job_item(&job, &item).await {
+            Ok(output) => JobItemResult {
+                item,
+                output,
+                error: None,
+            },
+            Err(e) => {
+                warn!("⚠️ Job {} failed on {}: {}", job.id, item, e);
+                JobItemResult {
+                    item,
+                    output: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+        job.results.push(result);
+        state.jobs.update(&job);
+        if (n + 1) % PROGRESS_SAVE_ITEMS == 0 {
+            save_progress(&state, &job).await;
+        }
+    }
+
+    job.status = JobStatus::Finished;
+    job.finished = Some(Utc::now());
+    save_progress(&state, &job).await;
+    state.jobs.active.lock().unwrap().remove(&job.id);
+    if let Err(e) = state.metadata_store.prune_finished_jobs(FINISHED_JOBS_KEPT).await {
+        warn!("⚠️ Failed to prune finished jobs: {}", e);
+    }
+    info!("🧰 Finished job {}", job.id);
+}
+
+/// Save a job's progress, in memory and in the database; failures are logged
+async fn save_progress(state: &StorageState, job: &Job) {
+    state.jobs.update(job);
+    if let Err(e) = state.metadata_store.save_job(job).await {
+        warn!("⚠️ Failed to save the progress of job {}: {}", job.id, e);
+    }
+}
@@ -1,11 +1,70 @@
+pub mod alerts;
+pub mod analytics;
 pub mod asset_cache;
+pub mod asset_refs;
+pub mod audit;
+pub mod auth;
+pub mod bulk;
+pub mod canvas;
+pub mod clock;
+pub mod cluster;
+pub mod collections;
+pub mod comments;
+pub mod coview;
+pub mod diff;
+pub mod disk_usage;
+pub mod drift;
+pub mod encryption;
+pub mod erasure;
+pub mod failed;
+pub mod filename_template;
+pub mod finalize;
+pub mod frame_budget;
+pub mod frame_filter;
+pub mod frame_sink;
+pub mod health;
+pub mod idle;
+pub mod jobs;
+pub mod listen;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod observability;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod pipe;
+#[cfg(feature = "player-ui")]
+pub mod player_ui;
+pub mod policy;
+pub mod privacy;
+pub mod provenance;
 pub mod recording_handler;
+pub mod recording_id;
+pub mod recording_meta;
+pub mod recording_store;
+pub mod relay;
+pub mod replica;
+pub mod reprocess;
 pub mod server;
+pub mod sites;
 pub mod storage;
+pub mod timeline;
+pub mod trace;
+pub mod watch_folder;
+#[cfg(feature = "webtransport")]
+pub mod webtransport;
 
 // Re-export commonly used types
 pub use asset_cache::{AssetFileStore, MetadataStore};
-pub use recording_handler::{handle_websocket_recording, RecordingConfig, RecordingHooks};
+pub use filename_template::FilenameTemplate;
+pub use frame_sink::{FrameSink, FrameSinkConfig};
+pub use observability::ObservabilityHooks;
+pub use recording_handler::{
+    handle_recording_stream, handle_websocket_recording, RecordingConfig, RecordingHooks, RecordingTransport,
+};
+pub use recording_id::{InvalidRecordingId, RecordingId};
+pub use recording_store::RecordingStore;
+pub use storage::StorageError;
+pub use server::{AssetDisposition, DomcorderRouter, RouteGroup};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -14,17 +73,62 @@ use std::sync::Mutex;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RecordingInfo {
+    /// Path relative to the recordings directory (`subdir/filename`); used in
+    /// `/recording/{id}` with `/` percent-encoded
     pub id: String,
+    /// File name without the subdirectory
     pub filename: String,
     pub size: u64,
+    /// When recording started: the DCRR header's `created_at`, or `file_created`
+    /// for files without one
     pub created: DateTime<Utc>,
+    /// When the file was created according to the store; changes when files
+    /// are copied or restored
+    pub file_created: DateTime<Utc>,
     pub is_active: bool, // Whether the recording is still being written to
+    // From the metadata store; unset until the recording has been fully ingested
+    pub site_origin: Option<String>,
+    pub initial_url: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub frame_count: Option<u64>,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub anonymous_id: Option<String>,
+    pub user_id: Option<String>,
+    pub email_hash: Option<String>,
+    /// The recorder's address, as far as the privacy policy keeps it
+    pub client_ip: Option<String>,
+    /// Frames dropped by type and counted, rather than stored
+    #[serde(default)]
+    pub dropped_frames: std::collections::BTreeMap<String, u64>,
+    /// Frames left out by type once the type's byte budget was spent
+    #[serde(default)]
+    pub summarized_frames: std::collections::BTreeMap<String, frame_budget::SummarizedFrames>,
+}
+
+impl RecordingInfo {
+    /// Whether `user` is the session's user id, email hash or anonymous id
+    pub fn belongs_to(&self, user: &str) -> bool {
+        [&self.user_id, &self.email_hash, &self.anonymous_id]
+            .iter()
+            .any(|id| id.as_deref() == Some(user))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ActiveRecordingInfo {
     /// Most recent Timestamp frame value (None until first Timestamp frame)
     pub latest_timestamp: Option<u64>,
+    /// Frames written so far
+    pub frames_written: u64,
+    /// Each frame as it is written, for live viewers
+    pub live_frames: tokio::sync::broadcast::Sender<std::sync::Arc<domcorder_proto::Frame>>,
+    /// A viewer has subscribed and is reading the frames written before it
+    /// from the file, so the writer's buffer should be flushed
+    pub flush_requested: bool,
+    /// Notified to have the session finish the recording early
+    pub finalize: std::sync::Arc<tokio::sync::Notify>,
 }
 
 pub type AppState = std::sync::Arc<StorageState>;
@@ -32,10 +136,64 @@ pub type AppState = std::sync::Arc<StorageState>;
 pub struct StorageState {
     pub storage_dir: std::path::PathBuf,
     // Track which recordings are currently being written to
-    pub active_recordings: Mutex<HashMap<String, ActiveRecordingInfo>>,
+    pub active_recordings: Mutex<HashMap<RecordingId, ActiveRecordingInfo>>,
+    // Leases on active recordings shared with the other servers
+    pub active_registry: cluster::ActiveRegistry,
     // Asset caching stores
     pub metadata_store: Box<dyn MetadataStore>,
     pub asset_file_store: Box<dyn AssetFileStore>,
+    // Where recording files are kept
+    pub recording_store: Box<dyn RecordingStore>,
+    // Where counters, histograms and span events are reported
+    pub observability: Box<dyn ObservabilityHooks>,
+    // Carries the frames of recordings written on other servers to live playback here
+    pub frame_relay: Option<std::sync::Arc<dyn relay::FrameRelay>>,
+    // Secondary destinations for frames as they are ingested
+    pub frame_sinks: frame_sink::FrameSinks,
+    // Caps on asset bytes cached per asset and per recording
+    pub asset_limits: asset_cache::limits::AssetLimits,
+    // How asset bytes and fetch URLs are normalized before hashing
+    pub asset_normalization: asset_cache::normalize::AssetNormalization,
+    // Play assets missing from the cache from their original URL
+    pub original_url_fallback: bool,
+    // Decides whether new assets may be admitted to the CAS
+    pub asset_scanner: Box<dyn asset_cache::scanner::AssetScanner>,
+    // Names recordings the recorder did not name
+    pub filename_template: FilenameTemplate,
+    // New recordings are refused while free space is below this
+    pub min_free_bytes: Option<u64>,
+    // Stylesheet text at least this large is stored in the CAS; None keeps it inline
+    pub stylesheet_dedup_bytes: Option<u64>,
+    // Rewrite Timestamp frames onto a monotonic timeline on the server's clock
+    pub normalize_timestamps: bool,
+    // Spans without activity at least this long are collapsed into IdlePeriod frames
+    pub idle_compression_ms: Option<u64>,
+    // Stored frames end with a CRC32, checked when they are played
+    pub frame_checksums: bool,
+    // Accept uploads whose frames are zstd-compressed
+    pub compressed_uploads: bool,
+    // Master keys wrapping per-recording data keys; None stores recordings in plaintext
+    pub key_ring: Option<encryption::KeyRing>,
+    // What is kept of frames, page URLs and recorder addresses
+    pub privacy: privacy::PrivacyPolicy,
+    // Frame types stored, dropped and counted at ingestion
+    pub frame_rules: frame_filter::FrameTypeRules,
+    // Bytes stored per recording of each budgeted frame type
+    pub frame_budgets: frame_budget::FrameBudgets,
+    // Conditions that raise alerts when an ingested recording meets them
+    pub alert_rules: Vec<alerts::AlertRule>,
+    // Viewers watching each recording over `/ws/recording/{id}`
+    pub viewer_rooms: coview::ViewerRooms,
+    // Live recorders that can be asked for a fresh keyframe
+    pub keyframe_requests: drift::KeyframeRequests,
+    // Background jobs queued or running on this server
+    pub jobs: jobs::Jobs,
+    // How long a recording's handshake waits for its cache manifest
+    pub manifest_budget: std::time::Duration,
+    // The manifest last generated for each site, sent when one takes too long
+    pub manifest_cache: asset_cache::manifest::ManifestCache,
+    // Mirrors assets and recordings to a replica backend, if configured
+    pub replication: Option<std::sync::Arc<replica::Replication>>,
 }
 
 impl std::fmt::Debug for StorageState {
@@ -43,11 +201,38 @@ impl std::fmt::Debug for StorageState {
         f.debug_struct("StorageState")
             .field("storage_dir", &self.storage_dir)
             .field("active_recordings", &self.active_recordings)
+            .field("active_registry", &self.active_registry)
             .field("metadata_store", &"<dyn MetadataStore>")
             .field("asset_file_store", &"<dyn AssetFileStore>")
+            .field("recording_store", &"<dyn RecordingStore>")
+            .field("observability", &"<dyn ObservabilityHooks>")
+            .field("frame_relay", &self.frame_relay.as_ref().map(|_| "<dyn FrameRelay>"))
+            .field("frame_sinks", &self.frame_sinks)
+            .field("asset_limits", &self.asset_limits)
+            .field("asset_normalization", &self.asset_normalization)
+            .field("original_url_fallback", &self.original_url_fallback)
+            .field("asset_scanner", &"<dyn AssetScanner>")
+            .field("filename_template", &self.filename_template)
+            .field("min_free_bytes", &self.min_free_bytes)
+            .field("stylesheet_dedup_bytes", &self.stylesheet_dedup_bytes)
+            .field("normalize_timestamps", &self.normalize_timestamps)
+            .field("idle_compression_ms", &self.idle_compression_ms)
+            .field("frame_checksums", &self.frame_checksums)
+            .field("compressed_uploads", &self.compressed_uploads)
+            .field("key_ring", &self.key_ring)
+            .field("privacy", &self.privacy)
+            .field("frame_rules", &self.frame_rules)
+            .field("frame_budgets", &self.frame_budgets)
+            .field("alert_rules", &self.alert_rules)
+            .field("viewer_rooms", &self.viewer_rooms)
+            .field("keyframe_requests", &self.keyframe_requests)
+            .field("jobs", &self.jobs)
+            .field("manifest_budget", &self.manifest_budget)
+            .field("manifest_cache", &self.manifest_cache)
+            .field("replication", &self.replication)
             .finish()
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "sqlite"))]
 mod server_test;
@@ -0,0 +1,186 @@
+//! Listener addresses for the server binary
+//!
+//! `DOMCORDER_LISTEN` holds a comma-separated list of addresses, each either a
+//! TCP socket address (`127.0.0.1:8723`, `[::1]:8723`) or a Unix socket path
+//! prefixed with `unix:` (`unix:/run/domcorder.sock`).
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Address used when nothing is configured
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8723";
+
+/// Where the server accepts connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// Parse a comma-separated list of addresses
+    pub fn parse_list(value: &str) -> Result<Vec<Self>, String> {
+        let addrs = value
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Self>, String>>()?;
+        if addrs.is_empty() {
+            return Err("no listen addresses given".to_string());
+        }
+        Ok(addrs)
+    }
+
+    /// Start listening on this address
+    ///
+    /// A leftover Unix socket file from a previous run is removed first.
+    pub async fn bind(&self) -> io::Result<Listener> {
+        match self {
+            ListenAddr::Tcp(addr) => Ok(Listener::Tcp(tokio::net::TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+                if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Listener::Unix(tokio::net::UnixListener::bind(path)?))
+            }
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )),
+        }
+    }
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("empty Unix socket path".to_string()),
+            Some(path) => Ok(ListenAddr::Unix(PathBuf::from(path))),
+            None => s
+                .parse()
+                .map(ListenAddr::Tcp)
+                .map_err(|_| format!("invalid listen address {:?} (expected host:port or unix:/path)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A connection accepted from any listener
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Connection for T {}
+
+/// The other end of an accepted connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    Tcp(SocketAddr),
+    Unix,
+}
+
+impl Peer {
+    /// The peer's socket address, if it has one
+    pub fn socket_addr(self) -> Option<SocketAddr> {
+        match self {
+            Peer::Tcp(addr) => Some(addr),
+            Peer::Unix => None,
+        }
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Tcp(addr) => write!(f, "{}", addr),
+            Peer::Unix => write!(f, "unix socket"),
+        }
+    }
+}
+
+/// A bound listener
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    /// Accept the next connection and the peer it came from
+    pub async fn accept(&self) -> io::Result<(Box<dyn Connection>, Peer)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Box::new(stream), Peer::Tcp(addr)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Box::new(stream), Peer::Unix))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_list() {
+        let addrs = ListenAddr::parse_list("127.0.0.1:8723, [::1]:9000,unix:/tmp/domcorder.sock").unwrap();
+        assert_eq!(
+            addrs,
+            vec![
+                ListenAddr::Tcp("127.0.0.1:8723".parse().unwrap()),
+                ListenAddr::Tcp("[::1]:9000".parse().unwrap()),
+                ListenAddr::Unix(PathBuf::from("/tmp/domcorder.sock")),
+            ]
+        );
+        assert_eq!(addrs[2].to_string(), "unix:/tmp/domcorder.sock");
+
+        for invalid in ["", " , ", "localhost", "unix:", "127.0.0.1"] {
+            assert!(ListenAddr::parse_list(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_listener_replaces_stale_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let addr = ListenAddr::Unix(dir.path().join("domcorder.sock"));
+        drop(addr.bind().await.unwrap());
+
+        // The socket file left behind by the first listener does not block a restart
+        let listener = addr.bind().await.unwrap();
+        let ListenAddr::Unix(path) = &addr else { unreachable!() };
+        let mut client = tokio::net::UnixStream::connect(path).await.unwrap();
+        let (mut server, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer, Peer::Unix);
+        assert_eq!(peer.to_string(), "unix socket");
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
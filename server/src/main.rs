@@ -1,24 +1,188 @@
-use domcorder_server::{StorageState, server};
+use axum::Router;
+use axum::extract::ConnectInfo;
+use domcorder_server::alerts::parse_alert_rules;
+use domcorder_server::analytics::page_of_url;
+use domcorder_server::{AppState, FilenameTemplate, StorageState};
+use domcorder_server::server::{AssetDisposition, DomcorderRouter, RouteGroup};
 use domcorder_server::asset_cache::{AssetFileStore, MetadataStore};
+use domcorder_server::asset_cache::limits::AssetLimits;
 use domcorder_server::asset_cache::local::LocalBinaryStore;
-use domcorder_server::asset_cache::sqlite::SqliteMetadataStore;
+use domcorder_server::asset_cache::normalize::AssetNormalization;
+use domcorder_server::asset_cache::scanner::MimePolicyScanner;
+use domcorder_server::recording_store::local::{FsyncPolicy, LocalRecordingStore, WritePolicy};
+use domcorder_server::replica::Replica;
+use domcorder_server::auth::ApiKey;
+use domcorder_server::cluster::{ClusterConfig, run_active_registry};
+use domcorder_server::encryption::KeyRing;
+use domcorder_server::finalize::Reconciliation;
+use domcorder_server::frame_budget::FrameBudgets;
+use domcorder_server::frame_filter::FrameTypeRules;
+use domcorder_server::jobs::resume_jobs;
+use domcorder_server::pipe::{PipeIngest, ingest_pipe};
+use domcorder_server::relay::run_frame_relay;
+use domcorder_server::watch_folder::{WatchFolderConfig, run_watch_folder};
+use domcorder_server::privacy::{ClientIpMode, PrivacyPolicy};
+use domcorder_server::listen::{DEFAULT_LISTEN, ListenAddr, Listener};
+use domcorder_server::asset_cache::sqlite::{SqliteConfig, SqliteMetadataStore};
 use hyper_util::rt::TokioIo;
 use hyper_util::server::conn::auto::Builder as ConnBuilder;
+use std::collections::HashSet;
+use std::error::Error;
 use std::io;
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::Duration;
 use tower::Service;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+const USAGE: &str = "\
+Usage: domcorder-server [ingest - [options]]
+
+With no command, run the server. Configuration is read from DOMCORDER_*
+environment variables.
+
+Commands:
+  ingest -   Store one recording read from stdin (a frame stream or a whole
+             .dcrr file), print its id and exit
+
+Ingest options:
+  --site ORIGIN      Origin the recording's assets are registered under
+  --subdir DIR       Subdirectory to store the recording in
+  --filename NAME    File name (default: from DOMCORDER_FILENAME_TEMPLATE)";
 
 #[tokio::main]
-async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "debug,hyper=debug,h2=debug".into()),
-        )
-        .init();
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    // Initialize tracing; a one-shot command prints its result on stdout, so logs go to stderr
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "debug,hyper=debug,h2=debug".into());
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if args.is_empty() {
+        subscriber.init();
+    } else {
+        subscriber.with_writer(io::stderr).init();
+    }
+
+    let result = match args.split_first() {
+        None => run().await,
+        Some((command, rest)) if command == "ingest" => ingest(rest).await,
+        Some((command, _)) => Err(format!("Unknown command {:?}\n\n{}", command, USAGE).into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("❌ {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `domcorder-server ingest -`: store the recording piped to stdin
+async fn ingest(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut options = PipeIngest::default();
+    let mut input = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--site" => {
+                let site = value()?;
+                let (origin, _) = page_of_url(&site).ok_or_else(|| format!("Invalid origin {:?}", site))?;
+                options.site_origin = Some(origin);
+            }
+            "--subdir" => options.subdir = Some(PathBuf::from(value()?)),
+            "--filename" => options.filename = Some(value()?),
+            "-" if input.is_none() => input = Some(arg),
+            other => return Err(format!("Unexpected argument {:?}\n\n{}", other, USAGE).into()),
+        }
+    }
+    if input.is_none() {
+        return Err(format!("ingest reads from stdin; pass - as its input\n\n{}", USAGE).into());
+    }
+
+    let (state, _) = storage_state().await?;
+    let result = ingest_pipe(&state, tokio::io::stdin(), options).await;
+    // No heartbeat runs in a one-shot command, so the recording's lease is released now
+    if let Err(e) = state.sync_active_registry().await {
+        warn!("⚠️ Failed to release the recording's lease: {}", e);
+    }
+    let id = result.map_err(|e| format!("Failed to ingest recording: {}", e))?;
+    println!("{}", id);
+    Ok(())
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let (state, storage_dir) = storage_state().await?;
+    let state = start(state).await?;
+
+    // Create and run the server
+    let api_key = std::env::var("DOMCORDER_API_KEY").ok().map(ApiKey::new);
+    let mut router = DomcorderRouter::new(state.clone());
+    if let Some(api_key) = api_key.clone() {
+        info!("🔒 API key authentication enabled");
+        router = router.with_auth(api_key);
+    }
+    match std::env::var("DOMCORDER_ASSET_DISPOSITION").as_deref() {
+        Err(_) | Ok("inline") => {}
+        Ok("attachment") => router = router.with_asset_disposition(AssetDisposition::Attachment),
+        Ok(other) => return Err(format!("Invalid DOMCORDER_ASSET_DISPOSITION {:?}: expected inline or attachment", other).into()),
+    }
+    let app = router.routes(RouteGroup::ALL);
+
+    // Listen on every configured address (TCP and/or Unix sockets)
+    let listen = std::env::var("DOMCORDER_LISTEN").unwrap_or_else(|_| DEFAULT_LISTEN.to_string());
+    let addrs = ListenAddr::parse_list(&listen).map_err(|e| format!("Invalid DOMCORDER_LISTEN: {}", e))?;
+
+    let mut listeners = Vec::new();
+    for addr in &addrs {
+        let listener = addr
+            .bind()
+            .await
+            .map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+        info!("DomCorder server listening on {} (HTTP/1.1 + HTTP/2)", addr);
+        listeners.push(listener);
+    }
+    info!("Storage directory: {}", storage_dir.display());
+
+    // Accept recordings over WebTransport (HTTP/3) on the UDP address DOMCORDER_WEBTRANSPORT_LISTEN, presenting
+    // the PEM certificate chain and private key in DOMCORDER_WEBTRANSPORT_CERT and DOMCORDER_WEBTRANSPORT_KEY
+    #[cfg(feature = "webtransport")]
+    if let Ok(addr) = std::env::var("DOMCORDER_WEBTRANSPORT_LISTEN") {
+        use domcorder_server::webtransport::{RECORD_PATH, bind_webtransport, serve_webtransport};
+
+        let addr: std::net::SocketAddr =
+            addr.parse().map_err(|_| format!("Invalid DOMCORDER_WEBTRANSPORT_LISTEN {:?}: expected host:port", addr))?;
+        let cert = std::env::var("DOMCORDER_WEBTRANSPORT_CERT")
+            .map_err(|_| "DOMCORDER_WEBTRANSPORT_LISTEN requires DOMCORDER_WEBTRANSPORT_CERT")?;
+        let key = std::env::var("DOMCORDER_WEBTRANSPORT_KEY")
+            .map_err(|_| "DOMCORDER_WEBTRANSPORT_LISTEN requires DOMCORDER_WEBTRANSPORT_KEY")?;
+        let identity = wtransport::Identity::load_pemfiles(&cert, &key)
+            .await
+            .map_err(|e| format!("Failed to load the WebTransport certificate: {}", e))?;
+        let endpoint = bind_webtransport(addr, identity)
+            .map_err(|e| format!("Failed to listen on {} for WebTransport: {}", addr, e))?;
+        info!("DomCorder server accepting WebTransport recordings on https://{}{}", addr, RECORD_PATH);
+        tokio::spawn(serve_webtransport(endpoint, state.clone(), api_key.clone()));
+    }
+
+    let accept_loops: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(accept_loop(listener, app.clone())))
+        .collect();
+    for accept_loop in accept_loops {
+        accept_loop.await?;
+    }
+    Ok(())
+}
+
+/// The storage, configured from the environment, and its directory
+async fn storage_state() -> Result<(StorageState, PathBuf), Box<dyn Error>> {
     // Initialize storage
     // STORAGE_DIR structure:
     //   - recordings/ (subdirectory for .dcrr files)
@@ -30,13 +194,19 @@ async fn main() {
 
     // Ensure storage directory exists before creating database
     std::fs::create_dir_all(&storage_dir)
-        .expect("Failed to create storage directory");
+        .map_err(|e| format!("Failed to create storage directory {}: {}", storage_dir.display(), e))?;
 
     // Initialize asset cache stores
     let db_path = storage_dir.join("asset_cache.db");
+    let mut sqlite_config = SqliteConfig::default();
+    if let Ok(connections) = std::env::var("DOMCORDER_SQLITE_CONNECTIONS") {
+        sqlite_config.max_connections = connections
+            .parse()
+            .map_err(|_| format!("Invalid DOMCORDER_SQLITE_CONNECTIONS {:?}: expected a number", connections))?;
+    }
     let metadata_store: Box<dyn MetadataStore> = Box::new(
-        SqliteMetadataStore::new(&db_path)
-            .expect("Failed to initialize asset metadata store"),
+        SqliteMetadataStore::with_config(&db_path, sqlite_config)
+            .map_err(|e| format!("Failed to initialize asset metadata store: {}", e))?,
     );
 
     let assets_dir = storage_dir.join("assets");
@@ -44,25 +214,365 @@ async fn main() {
         .unwrap_or_else(|_| "http://127.0.0.1:8723".to_string());
     let asset_file_store: Box<dyn AssetFileStore> = Box::new(
         LocalBinaryStore::new(&assets_dir, base_url.clone())
-            .expect("Failed to initialize asset file store"),
+            .map_err(|e| format!("Failed to initialize asset file store: {}", e))?,
     );
 
-    let state = Arc::new(StorageState::new(storage_dir.clone(), metadata_store, asset_file_store));
+    let asset_limits = AssetLimits {
+        max_asset_size: env_bytes("DOMCORDER_MAX_ASSET_SIZE")?,
+        max_recording_bytes: env_bytes("DOMCORDER_MAX_RECORDING_ASSET_BYTES")?,
+    };
 
-    // Create and run the server
-    let app = server::create_app(state);
+    let mut state = StorageState::new(storage_dir.clone(), metadata_store, asset_file_store)
+        .map_err(|e| format!("Failed to initialize recording storage: {}", e))?
+        .with_asset_limits(asset_limits);
+
+    // Tracking query parameters are dropped from fetched URLs unless DOMCORDER_KEEP_TRACKING_PARAMS=1,
+    // and SVG whitespace is only canonicalized with DOMCORDER_CANONICALIZE_SVG=1
+    state = state.with_asset_normalization(AssetNormalization {
+        strip_tracking_params: !matches!(std::env::var("DOMCORDER_KEEP_TRACKING_PARAMS").as_deref(), Ok("1")),
+        canonicalize_svg: matches!(std::env::var("DOMCORDER_CANONICALIZE_SVG").as_deref(), Ok("1")),
+        ..AssetNormalization::default()
+    });
+
+    // Play assets missing from the cache from the URL they were recorded from
+    if matches!(std::env::var("DOMCORDER_ASSET_URL_FALLBACK").as_deref(), Ok("1")) {
+        state = state.with_original_url_fallback(true);
+    }
+
+    // Refuse new recordings while free space is below this many bytes
+    if let Some(min_free) = env_bytes("DOMCORDER_MIN_FREE_BYTES")? {
+        state = state.with_min_free_space(min_free);
+    }
+
+    // Stylesheet text at least this large is stored once in the asset cache (0 keeps it inline)
+    if let Some(bytes) = env_bytes("DOMCORDER_STYLESHEET_DEDUP_BYTES")? {
+        state = state.with_stylesheet_dedup(Some(bytes).filter(|&bytes| bytes > 0));
+    }
+
+    // Spans with only timestamps, pointer movement and heartbeats at least this
+    // long are stored as IdlePeriod frames
+    if let Ok(secs) = std::env::var("DOMCORDER_IDLE_COMPRESSION_SECS") {
+        let secs: u64 = secs
+            .parse()
+            .map_err(|_| format!("Invalid DOMCORDER_IDLE_COMPRESSION_SECS {:?}: expected a number of seconds", secs))?;
+        state = state.with_idle_compression(Some(secs * 1000).filter(|&ms| ms > 0));
+    }
+
+    // End each stored frame with a CRC32 that playback checks
+    if matches!(std::env::var("DOMCORDER_FRAME_CHECKSUMS").as_deref(), Ok("1")) {
+        state = state.with_frame_checksums(true);
+    }
+
+    // Accept uploads with zstd-compressed frames, which the server decompresses
+    if matches!(std::env::var("DOMCORDER_COMPRESSED_UPLOADS").as_deref(), Ok("1")) {
+        state = state.with_compressed_uploads(true);
+    }
+
+    // This server's name among those sharing the metadata store (random by default),
+    // and how long its leases on active recordings last without a heartbeat
+    let mut cluster = ClusterConfig::default();
+    if let Ok(node_id) = std::env::var("DOMCORDER_NODE_ID") {
+        cluster.node_id = node_id;
+    }
+    if let Ok(secs) = std::env::var("DOMCORDER_LEASE_SECS") {
+        let secs: u64 = secs
+            .parse()
+            .ok()
+            .filter(|&secs| secs > 0)
+            .ok_or_else(|| format!("Invalid DOMCORDER_LEASE_SECS {:?}: expected a positive number of seconds", secs))?;
+        cluster.lease = Duration::from_secs(secs);
+    }
+    info!("🪪 Node id: {}", cluster.node_id);
+    state = state.with_cluster(cluster);
+
+    // Background jobs (bulk operations) run at once; others wait their turn
+    if let Ok(jobs) = std::env::var("DOMCORDER_MAX_JOBS") {
+        let jobs: usize = jobs
+            .parse()
+            .ok()
+            .filter(|&jobs| jobs > 0)
+            .ok_or_else(|| format!("Invalid DOMCORDER_MAX_JOBS {:?}: expected a positive number", jobs))?;
+        state = state.with_max_concurrent_jobs(jobs);
+    }
+
+    // How long a recording's handshake waits for its cache manifest
+    if let Ok(ms) = std::env::var("DOMCORDER_MANIFEST_BUDGET_MS") {
+        let ms: u64 = ms
+            .parse()
+            .map_err(|_| format!("Invalid DOMCORDER_MANIFEST_BUDGET_MS {:?}: expected a number of milliseconds", ms))?;
+        state = state.with_manifest_budget(Duration::from_millis(ms));
+    }
+
+    // Where recorded timestamps are stored: server (monotonic, default) or recorder (as sent)
+    match std::env::var("DOMCORDER_TIMELINE").as_deref() {
+        Err(_) | Ok("server") => {}
+        Ok("recorder") => state = state.with_timestamp_normalization(false),
+        Ok(other) => return Err(format!("Invalid DOMCORDER_TIMELINE {:?}: expected server or recorder", other).into()),
+    }
+
+    // Comma-separated id:base64 master keys (32 bytes each); the first wraps new recording keys
+    if let Ok(keys) = std::env::var("DOMCORDER_MASTER_KEYS") {
+        let key_ring = KeyRing::parse(&keys).map_err(|e| format!("Invalid DOMCORDER_MASTER_KEYS: {}", e))?;
+        info!("🔒 Recordings are encrypted with master key {}", key_ring.current_key_id());
+        state = state.with_encryption(key_ring);
+    }
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8723")
+    // What is kept of recordings and their recorders (all kept but the IP address by default)
+    let mut privacy = PrivacyPolicy::default();
+    // How much of the recorder's IP address is stored: drop (default), truncate, hash or full
+    if let Ok(mode) = std::env::var("DOMCORDER_CLIENT_IP") {
+        privacy.client_ip = ClientIpMode::parse(&mode)
+            .ok_or_else(|| format!("Invalid DOMCORDER_CLIENT_IP {:?}: expected drop, truncate, hash or full", mode))?;
+    }
+    match std::env::var("DOMCORDER_CLIENT_IP_SALT") {
+        Ok(salt) => privacy.ip_salt = salt.into_bytes(),
+        // Without a salt, hashes of the small IPv4 space are easily reversed
+        Err(_) if privacy.client_ip == ClientIpMode::Hash => {
+            return Err("DOMCORDER_CLIENT_IP=hash requires DOMCORDER_CLIENT_IP_SALT".into());
+        }
+        Err(_) => {}
+    }
+    privacy.trust_forwarded_for = matches!(std::env::var("DOMCORDER_TRUST_FORWARDED_FOR").as_deref(), Ok("1"));
+    privacy.strip_url_queries = matches!(std::env::var("DOMCORDER_STRIP_URL_QUERIES").as_deref(), Ok("1"));
+    if privacy != PrivacyPolicy::default() {
+        info!("🕶️ Client IPs: {}", privacy.client_ip.as_str());
+    }
+    state = state.with_privacy(privacy);
+
+    // Comma-separated frame type names: only KEEP_FRAMES are stored, if set;
+    // DROP_FRAMES are never stored; COUNT_FRAMES are counted instead of stored
+    let frame_types = |var: &str| {
+        std::env::var(var).ok().map(|names| {
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect::<HashSet<_>>()
+        })
+    };
+    let frame_rules = FrameTypeRules {
+        allowed: frame_types("DOMCORDER_KEEP_FRAMES"),
+        dropped: frame_types("DOMCORDER_DROP_FRAMES").unwrap_or_default(),
+        counted: frame_types("DOMCORDER_COUNT_FRAMES").unwrap_or_default(),
+    };
+    if !frame_rules.keeps_all() {
+        info!(
+            "🧹 Frames kept: {:?}, dropped: {:?}, counted: {:?}",
+            frame_rules.allowed, frame_rules.dropped, frame_rules.counted
+        );
+    }
+    state = state.with_frame_rules(frame_rules);
+
+    // Comma-separated Type=bytes pairs: the bytes stored per recording of each
+    // type, after which its frames are summarized
+    if let Ok(spec) = std::env::var("DOMCORDER_FRAME_BUDGETS") {
+        let budgets = FrameBudgets::parse(&spec).map_err(|e| format!("Invalid DOMCORDER_FRAME_BUDGETS: {}", e))?;
+        if !budgets.is_empty() {
+            info!("💰 Frame budgets: {:?}", budgets.bytes);
+        }
+        state = state.with_frame_budgets(budgets);
+    }
+
+    // JSON file of alerting rules, matched against recordings as they are ingested
+    if let Some(path) = std::env::var_os("DOMCORDER_ALERT_RULES").map(PathBuf::from) {
+        let json = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read alert rules {}: {}", path.display(), e))?;
+        let rules = parse_alert_rules(&json).map_err(|e| format!("Invalid alert rules in {}: {}", path.display(), e))?;
+        info!("🚨 Loaded {} alert rules from {}", rules.len(), path.display());
+        state = state.with_alert_rules(rules);
+    }
+
+    // Layout for generated recording names, e.g. {site}/{yyyy}/{mm}/{dd}/{uuid}.dcrr
+    if let Ok(template) = std::env::var("DOMCORDER_FILENAME_TEMPLATE") {
+        let template = FilenameTemplate::new(template).map_err(|e| e.to_string())?;
+        state = state.with_filename_template(template);
+    }
+
+    // When recording data is forced to disk: never, finish (default) or always
+    if let Ok(fsync) = std::env::var("DOMCORDER_FSYNC") {
+        let fsync = match fsync.as_str() {
+            "never" => FsyncPolicy::Never,
+            "finish" => FsyncPolicy::OnFinish,
+            "always" => FsyncPolicy::EveryFlush,
+            other => return Err(format!("Invalid DOMCORDER_FSYNC {:?}: expected never, finish or always", other).into()),
+        };
+        let recording_store = LocalRecordingStore::new(storage_dir.join("recordings"))
+            .map_err(|e| format!("Failed to initialize recording storage: {}", e))?
+            .with_write_policy(WritePolicy { fsync, ..WritePolicy::default() });
+        state = state.with_recording_store(Box::new(recording_store));
+    }
+
+    // Comma-separated MIME type prefixes that are quarantined instead of cached
+    if let Ok(denied) = std::env::var("DOMCORDER_DENIED_ASSET_TYPES") {
+        let denied = denied.split(',').map(str::trim).filter(|mime| !mime.is_empty());
+        state = state.with_asset_scanner(Box::new(MimePolicyScanner::new(denied)));
+    }
+
+    // The NATS server ingested frames are published to and live recordings relayed through
+    #[cfg(feature = "nats")]
+    let nats = match std::env::var("DOMCORDER_NATS_URL") {
+        Ok(url) => Some(
+            async_nats::connect(&url)
+                .await
+                .map_err(|e| format!("Failed to connect to NATS at {}: {}", url, e))?,
+        ),
+        Err(_) => None,
+    };
+
+    // Publish ingested frames to NATS subjects {DOMCORDER_NATS_SUBJECT}.{category}; only
+    // the comma-separated DOMCORDER_NATS_CATEGORIES (navigation, error, annotation, other) if set
+    #[cfg(feature = "nats")]
+    if let Ok(subject) = std::env::var("DOMCORDER_NATS_SUBJECT") {
+        use domcorder_server::frame_sink::{FrameCategory, FrameSinkConfig};
+        use domcorder_server::nats::NatsSink;
+
+        let client = nats.clone().ok_or("DOMCORDER_NATS_SUBJECT requires DOMCORDER_NATS_URL")?;
+        let config = match std::env::var("DOMCORDER_NATS_CATEGORIES") {
+            Ok(names) => {
+                let categories = names
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| FrameCategory::parse(name).ok_or_else(|| format!("Unknown frame category {:?}", name)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("Invalid DOMCORDER_NATS_CATEGORIES: {}", e))?;
+                FrameSinkConfig::only(subject, &categories)
+            }
+            Err(_) => FrameSinkConfig::all(subject),
+        };
+        info!("📣 Publishing frames to NATS subjects {}.*", config.topic_prefix);
+        state = state.with_frame_sink(config, Box::new(NatsSink::new(client)));
+    }
+
+    // Play recordings being written on other servers, relayed through NATS
+    #[cfg(feature = "nats")]
+    if matches!(std::env::var("DOMCORDER_NATS_RELAY").as_deref(), Ok("1")) {
+        let client = nats.clone().ok_or("DOMCORDER_NATS_RELAY requires DOMCORDER_NATS_URL")?;
+        info!("📡 Relaying live recordings between servers through NATS");
+        state = state.with_frame_relay(Arc::new(domcorder_server::nats::NatsRelay::new(client)));
+    }
+
+    // Export trace spans over OTLP/HTTP to the collector named by the standard OTEL_EXPORTER_OTLP_* variables
+    #[cfg(feature = "otlp")]
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+        || std::env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some()
+    {
+        let hooks = domcorder_server::otlp::OtlpHooks::from_env()
+            .map_err(|e| format!("Failed to set up OTLP trace export: {}", e))?;
+        info!("🔭 Exporting trace spans over OTLP");
+        state = state.with_observability(Box::new(hooks));
+    }
+
+    // Mirror assets and recordings to a second directory (e.g. a mounted volume in another region)
+    if let Some(replica_dir) = std::env::var_os("DOMCORDER_REPLICA_DIR").map(PathBuf::from) {
+        let replica = Replica {
+            assets: Box::new(
+                LocalBinaryStore::new(replica_dir.join("assets"), base_url.clone())
+                    .map_err(|e| format!("Failed to initialize replica asset store: {}", e))?,
+            ),
+            recordings: Box::new(
+                LocalRecordingStore::new(replica_dir.join("recordings"))
+                    .map_err(|e| format!("Failed to initialize replica recording store: {}", e))?,
+            ),
+        };
+        info!("🪞 Mirroring assets and recordings to {}", replica_dir.display());
+        state = state.with_replica(replica);
+    }
+
+    Ok((state, storage_dir))
+}
+
+/// Recover from the previous run and start the background tasks of a running server
+async fn start(state: StorageState) -> Result<AppState, Box<dyn Error>> {
+    // Finish the recordings a previous run was writing when it stopped
+    let reconciliation = state
+        .reconcile_recordings()
         .await
-        .unwrap();
-    info!("DomCorder server listening on http://127.0.0.1:8723 (HTTP/1.1 + HTTP/2)");
-    info!("Storage directory: {}", storage_dir.display());
+        .map_err(|e| format!("Failed to recover unfinished recordings: {}", e))?;
+    if reconciliation != Reconciliation::default() {
+        info!(
+            "🔁 Recovered unfinished recordings: finalized {:?}, repaired {:?}, failed {:?}",
+            reconciliation.finalized, reconciliation.repaired, reconciliation.failed
+        );
+    }
+
+    // Index which assets the recordings stored before reference counting refer to
+    match state.index_asset_references().await {
+        Ok(0) => {}
+        Ok(indexed) => info!("🔗 Indexed the asset references of {} recordings", indexed),
+        Err(e) => warn!("⚠️ Asset references are not fully indexed, so unreferenced assets are kept: {}", e),
+    }
+
+    let state = Arc::new(state);
+
+    // Pick up the jobs a previous run didn't finish
+    match resume_jobs(&state).await {
+        Ok(0) => {}
+        Ok(resumed) => info!("🧰 Resumed {} unfinished jobs", resumed),
+        Err(e) => warn!("⚠️ Failed to resume unfinished jobs: {}", e),
+    }
+
+    // Keep this server's leases on its active recordings alive
+    tokio::spawn(run_active_registry(state.clone()));
 
+    // Answer the other servers' requests for the live recordings written here, if relaying
+    tokio::spawn(run_frame_relay(state.clone()));
+
+    // Catch the replica up on whatever it missed while the server was down
+    if state.replication.is_some() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = state.reconcile_replica().await {
+                warn!("⚠️ Failed to reconcile the replica: {}", e);
+            }
+        });
+    }
+
+    // Import the recordings dropped into a directory, e.g. by offline recorders
+    if let Some(dir) = std::env::var_os("DOMCORDER_WATCH_DIR").map(PathBuf::from) {
+        let mut config = WatchFolderConfig::new(dir);
+        if let Some(rejects_dir) = std::env::var_os("DOMCORDER_WATCH_REJECTS_DIR") {
+            config.rejects_dir = PathBuf::from(rejects_dir);
+        }
+        tokio::spawn(run_watch_folder(state.clone(), config));
+    }
+
+    // Comma-separated site origins whose manifest assets are fetched ahead of the first recordings
+    if let Ok(origins) = std::env::var("DOMCORDER_WARM_ORIGINS") {
+        let origins: Vec<String> = origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(str::to_string)
+            .collect();
+        let state = state.clone();
+        tokio::spawn(async move {
+            for origin in origins {
+                if let Err(e) = state.warm_site_assets(&origin).await {
+                    warn!("⚠️ Failed to warm the asset cache for {}: {}", origin, e);
+                }
+            }
+        });
+    }
+
+    Ok(state)
+}
+
+/// Accept connections from one listener until the process exits
+async fn accept_loop(listener: Listener, app: Router) {
     // Use hyper's auto-negotiating server to support both HTTP/1.1 and HTTP/2
     let conn_builder = ConnBuilder::new(hyper_util::rt::TokioExecutor::new());
 
     loop {
-        let (stream, addr) = listener.accept().await.unwrap();
+        let (stream, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Usually transient (e.g. too many open files); keep serving
+                warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
         info!("New connection from: {}", addr);
         let io = TokioIo::new(stream);
         let app_clone = app.clone();
@@ -73,8 +583,11 @@ async fn main() {
             if let Err(err) = conn_builder
                 .serve_connection_with_upgrades(
                     io,
-                    hyper::service::service_fn(move |req| {
-
+                    hyper::service::service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+                        // Lets handlers see the recorder's address (see ClientIp)
+                        if let Some(socket_addr) = addr.socket_addr() {
+                            req.extensions_mut().insert(ConnectInfo(socket_addr));
+                        }
                         app_clone.clone().call(req)
                     }),
                 )
@@ -105,3 +618,14 @@ async fn main() {
         });
     }
 }
+
+/// Read a byte count from `name`, if set
+fn env_bytes(name: &str) -> Result<Option<u64>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid {}: expected a number of bytes, got {:?}", name, value)),
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,291 @@
+//! NATS transport for the frames of ingested recordings (`nats` feature)
+//!
+//! [`NatsSink`] is a [`FrameSink`] publishing each selected frame to its
+//! topic (see [`FrameSinkConfig`](crate::frame_sink::FrameSinkConfig)) as
+//! the NATS subject, with the message's JSON as the payload. [`NatsRelay`]
+//! is a [`FrameRelay`] carrying live recordings between servers (see
+//! [`crate::relay`]). The server binary connects to `DOMCORDER_NATS_URL`,
+//! and installs the sink when `DOMCORDER_NATS_SUBJECT` is set and the relay
+//! when `DOMCORDER_NATS_RELAY=1`.
+
+use crate::frame_sink::{FrameSink, SinkError, SinkMessage};
+use crate::relay::FrameRelay;
+use async_nats::RequestErrorKind;
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// How long a relayed message waits for its subscribers to take it
+const RELAY_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Messages buffered for one relay subscriber
+const RELAY_BUFFER: usize = 64;
+
+/// Publishes ingested frames to NATS
+///
+/// Publishing queues the message on the client's connection, so ingestion
+/// only waits when the client's buffer is full.
+#[derive(Debug, Clone)]
+pub struct NatsSink {
+    client: async_nats::Client,
+}
+
+impl NatsSink {
+    pub fn new(client: async_nats::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl FrameSink for NatsSink {
+    async fn publish(&self, message: SinkMessage) -> Result<(), SinkError> {
+        let payload = message.payload().map_err(|e| SinkError::Other(e.to_string()))?;
+        self.client
+            .publish(message.topic, payload.into())
+            .await
+            .map_err(|e| SinkError::Other(e.to_string()))
+    }
+}
+
+/// Relays recordings between servers over NATS
+///
+/// Each message is sent as a request its subscribers answer once they have
+/// taken it. A topic nobody subscribes to gets no answer, which NATS
+/// reports at once, so `publish` fails with [`SinkError::Closed`] as
+/// [`FrameRelay`] asks; and a stream waits for a viewer that falls behind
+/// rather than dropping messages.
+#[derive(Debug, Clone)]
+pub struct NatsRelay {
+    client: async_nats::Client,
+}
+
+impl NatsRelay {
+    pub fn new(client: async_nats::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl FrameRelay for NatsRelay {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), SinkError> {
+        let request = async_nats::Request::new()
+            .payload(payload.into())
+            .timeout(Some(RELAY_ACK_TIMEOUT));
+        match self.client.send_request(topic.to_string(), request).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == RequestErrorKind::NoResponders => Err(SinkError::Closed),
+            Err(e) => Err(SinkError::Other(e.to_string())),
+        }
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<mpsc::Receiver<Vec<u8>>, SinkError> {
+        let mut subscriber = self
+            .client
+            .subscribe(topic.to_string())
+            .await
+            .map_err(|e| SinkError::Other(e.to_string()))?;
+        // Round trip, so the server has the subscription before anything is published
+        self.client.flush().await.map_err(|e| SinkError::Other(e.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(RELAY_BUFFER);
+        let client = self.client.clone();
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = subscriber.next() => message,
+                    _ = sender.closed() => None,
+                };
+                let Some(message) = message else {
+                    break;
+                };
+                if sender.send(message.payload.to_vec()).await.is_err() {
+                    break;
+                }
+                if let Some(reply) = message.reply
+                    && let Err(e) = client.publish(reply, Default::default()).await
+                {
+                    warn!("⚠️ Failed to acknowledge a relayed message: {}", e);
+                }
+            }
+            // Publishers see no responders from now on
+            let _ = subscriber.unsubscribe().await;
+        });
+        Ok(receiver)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::frame_sink::{FrameCategory, FrameSinkConfig, FrameSinks};
+    use domcorder_proto::{Frame, PageErrorData, TimestampData};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Subscriptions of one fake server: (connection, sid) to subject and
+    /// the connection's outgoing messages
+    type Subscriptions = Arc<Mutex<HashMap<(usize, String), (String, mpsc::UnboundedSender<Vec<u8>>)>>>;
+
+    /// A NATS server speaking just enough of the protocol for the client:
+    /// PING, SUB, UNSUB and PUB, with `*` and `>` wildcards, answering a
+    /// request nobody subscribes to with "no responders"
+    pub(crate) async fn fake_nats_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        let subscriptions = Subscriptions::default();
+        tokio::spawn(async move {
+            for connection in 0.. {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connection(connection, stream, subscriptions.clone()));
+            }
+        });
+        url
+    }
+
+    async fn serve_connection(connection: usize, stream: tokio::net::TcpStream, subscriptions: Subscriptions) {
+        let (reader, mut writer) = stream.into_split();
+        let (outgoing, mut queued) = mpsc::unbounded_channel::<Vec<u8>>();
+        tokio::spawn(async move {
+            while let Some(bytes) = queued.recv().await {
+                if writer.write_all(&bytes).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let info = r#"{"server_id":"fake","version":"2.10.0","proto":1,"headers":true,"max_payload":1048576}"#;
+        outgoing.send(format!("INFO {}\r\n", info).into_bytes()).unwrap();
+
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+            let args: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            line.clear();
+            match args.first().map(String::as_str) {
+                Some("PING") => outgoing.send(b"PONG\r\n".to_vec()).unwrap(),
+                Some("SUB") => {
+                    let (subject, sid) = (args[1].clone(), args.last().unwrap().clone());
+                    subscriptions.lock().unwrap().insert((connection, sid), (subject, outgoing.clone()));
+                }
+                Some("UNSUB") => {
+                    subscriptions.lock().unwrap().remove(&(connection, args[1].clone()));
+                }
+                Some("PUB") => {
+                    let size: usize = args.last().unwrap().parse().unwrap();
+                    let reply = (args.len() == 4).then(|| args[2].clone());
+                    let mut payload = vec![0; size + 2];
+                    if reader.read_exact(&mut payload).await.is_err() {
+                        break;
+                    }
+                    payload.truncate(size);
+                    deliver(&subscriptions, &args[1], reply.as_deref(), &payload);
+                }
+                _ => {}
+            }
+        }
+        subscriptions.lock().unwrap().retain(|(owner, _), _| *owner != connection);
+    }
+
+    fn deliver(subscriptions: &Subscriptions, subject: &str, reply: Option<&str>, payload: &[u8]) {
+        let mut delivered = false;
+        for ((_, sid), (pattern, outgoing)) in subscriptions.lock().unwrap().iter() {
+            if !subject_matches(pattern, subject) {
+                continue;
+            }
+            let reply = reply.map(|reply| format!(" {}", reply)).unwrap_or_default();
+            let mut message = format!("MSG {} {}{} {}\r\n", subject, sid, reply, payload.len()).into_bytes();
+            message.extend_from_slice(payload);
+            message.extend_from_slice(b"\r\n");
+            delivered |= outgoing.send(message).is_ok();
+        }
+        let Some(reply) = reply.filter(|_| !delivered) else {
+            return;
+        };
+        const NO_RESPONDERS: &str = "NATS/1.0 503\r\n\r\n";
+        for ((_, sid), (pattern, outgoing)) in subscriptions.lock().unwrap().iter() {
+            if subject_matches(pattern, reply) {
+                let len = NO_RESPONDERS.len();
+                let _ = outgoing.send(format!("HMSG {} {} {} {}\r\n{}\r\n", reply, sid, len, len, NO_RESPONDERS).into_bytes());
+            }
+        }
+    }
+
+    fn subject_matches(pattern: &str, subject: &str) -> bool {
+        let mut tokens = subject.split('.');
+        for wanted in pattern.split('.') {
+            match (wanted, tokens.next()) {
+                (">", Some(_)) => return true,
+                ("*", Some(_)) => {}
+                (wanted, Some(token)) if wanted == token => {}
+                _ => return false,
+            }
+        }
+        tokens.next().is_none()
+    }
+
+    #[tokio::test]
+    async fn test_sink_publishes_selected_frames_by_subject() {
+        let url = fake_nats_server().await;
+        let consumer = async_nats::connect(&url).await.unwrap();
+        let mut received = consumer.subscribe("sessions.>").await.unwrap();
+        consumer.flush().await.unwrap();
+
+        let mut sinks = FrameSinks::default();
+        let sink = NatsSink::new(async_nats::connect(&url).await.unwrap());
+        sinks.push(FrameSinkConfig::only("sessions", &[FrameCategory::Error]), Box::new(sink));
+        let error = Frame::PageError(PageErrorData {
+            message: "boom".to_string(),
+            source_url: None,
+            line: None,
+            column: None,
+            stack: None,
+        });
+        sinks.publish("a.dcrr", None, &Frame::Timestamp(TimestampData { timestamp: 1 })).await;
+        sinks.publish("a.dcrr", None, &error).await;
+
+        let message = tokio::time::timeout(Duration::from_secs(5), received.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.subject.as_str(), "sessions.error");
+        let payload: serde_json::Value = serde_json::from_slice(&message.payload).unwrap();
+        assert_eq!(payload["recording_id"], "a.dcrr");
+        assert_eq!(payload["category"], "error");
+    }
+
+    #[tokio::test]
+    async fn test_relay_delivers_in_order_until_the_subscriber_goes() {
+        let url = fake_nats_server().await;
+        let relay = NatsRelay::new(async_nats::connect(&url).await.unwrap());
+        let other = NatsRelay::new(async_nats::connect(&url).await.unwrap());
+        assert!(matches!(relay.publish("stream.1", b"early".to_vec()).await, Err(SinkError::Closed)));
+
+        let mut receiver = other.subscribe("stream.1").await.unwrap();
+        for n in 0..10u8 {
+            relay.publish("stream.1", vec![n]).await.unwrap();
+        }
+        for n in 0..10u8 {
+            assert_eq!(receiver.recv().await, Some(vec![n]));
+        }
+
+        // A message sent before the unsubscription lands would wait out its acknowledgement
+        drop(receiver);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let closed = async {
+            while relay.publish("stream.1", b"late".to_vec()).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), closed).await.unwrap();
+    }
+
+    #[test]
+    fn test_subject_wildcards() {
+        assert!(subject_matches("a.*.c", "a.b.c"));
+        assert!(subject_matches("a.>", "a.b.c"));
+        assert!(!subject_matches("a.>", "a"));
+        assert!(!subject_matches("a.*", "a.b.c"));
+    }
+}
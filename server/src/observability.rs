@@ -0,0 +1,127 @@
+//! Hooks for exporting server telemetry
+//!
+//! The server reports what it does through an [`ObservabilityHooks`]
+//! implementation on [`StorageState`](crate::StorageState). Nothing is exported
+//! by default; embedders implement the trait to forward counters, histograms
+//! and span events to whatever they already run (StatsD, OTLP, ...). The
+//! `otlp` feature provides an implementation exporting finished trace spans.
+//!
+//! Metric and span names are listed in [`names`] so they can be matched on
+//! without copying strings around.
+
+use crate::trace::FinishedSpan;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Metric labels as `(key, value)` pairs
+pub type Labels<'a> = &'a [(&'static str, &'a str)];
+
+/// Receives telemetry from the recording handler, storage and asset cache
+///
+/// Every method has an empty default, so implementations only override what
+/// they export. Hooks are called inline on the ingest path and should not block.
+pub trait ObservabilityHooks: Send + Sync {
+    /// Add `value` to a counter
+    fn counter(&self, name: &'static str, value: u64, labels: Labels<'_>) {
+        let _ = (name, value, labels);
+    }
+
+    /// Record one observation of a histogram
+    fn histogram(&self, name: &'static str, value: f64, labels: Labels<'_>) {
+        let _ = (name, value, labels);
+    }
+
+    /// Report a point in the life of a span, such as a recording's ingest
+    ///
+    /// `id` identifies the span instance (e.g. the recording id) so start and
+    /// end events can be correlated.
+    fn span_event(&self, span: &'static str, event: SpanEvent, id: &str) {
+        let _ = (span, event, id);
+    }
+
+    /// Export a completed trace span (see [`crate::trace`])
+    fn span_finished(&self, span: &FinishedSpan) {
+        let _ = span;
+    }
+}
+
+/// A point in a span's life
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanEvent {
+    Started,
+    Completed { elapsed: Duration },
+    Failed { elapsed: Duration },
+}
+
+/// Hooks that discard everything; the default for new storage
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopHooks;
+
+impl ObservabilityHooks for NoopHooks {}
+
+/// Shared hooks, so embedders can keep a handle on the hooks they install
+impl<T: ObservabilityHooks + ?Sized> ObservabilityHooks for Arc<T> {
+    fn counter(&self, name: &'static str, value: u64, labels: Labels<'_>) {
+        (**self).counter(name, value, labels)
+    }
+
+    fn histogram(&self, name: &'static str, value: f64, labels: Labels<'_>) {
+        (**self).histogram(name, value, labels)
+    }
+
+    fn span_event(&self, span: &'static str, event: SpanEvent, id: &str) {
+        (**self).span_event(span, event, id)
+    }
+
+    fn span_finished(&self, span: &FinishedSpan) {
+        (**self).span_finished(span)
+    }
+}
+
+/// Metric and span names reported by the server
+pub mod names {
+    /// Counter: recording WebSocket connections accepted
+    pub const WEBSOCKET_CONNECTIONS: &str = "domcorder.websocket.connections";
+    /// Counter: binary messages received over recording WebSockets (fewer
+    /// than frames when recorders batch)
+    pub const WEBSOCKET_MESSAGES_RECEIVED: &str = "domcorder.websocket.messages_received";
+    /// Counter: bytes received over recording WebSockets
+    pub const WEBSOCKET_BYTES_RECEIVED: &str = "domcorder.websocket.bytes_received";
+
+    /// Span: serving one recording for playback
+    pub const RECORDING_PLAYBACK: &str = "domcorder.recording.playback";
+    /// Span: ingesting one recording into storage
+    pub const RECORDING_INGEST: &str = "domcorder.recording.ingest";
+    /// Counter: alerts raised by alerting rules; label `rule` is the rule's name
+    pub const ALERTS: &str = "domcorder.alerts";
+    /// Counter: recordings whose ingest completed; label `outcome` is `completed` or `failed`
+    pub const RECORDINGS: &str = "domcorder.recordings";
+    /// Counter: frames written to recordings; label `frame` is the frame type
+    pub const FRAMES_WRITTEN: &str = "domcorder.frames.written";
+    /// Counter: keyframes requested from live recorders whose frames drifted
+    /// from their page
+    pub const KEYFRAMES_REQUESTED: &str = "domcorder.keyframes.requested";
+    /// Histogram: seconds spent ingesting a recording
+    pub const RECORDING_INGEST_SECONDS: &str = "domcorder.recording.ingest_seconds";
+
+    /// Counter: assets added to the cache; label `result` is `stored` or `deduplicated`
+    pub const ASSETS_CACHED: &str = "domcorder.assets.cached";
+    /// Counter: bytes of new asset data written to the asset store
+    pub const ASSET_BYTES_STORED: &str = "domcorder.assets.bytes_stored";
+    /// Counter: assets not cached because they exceeded the asset limits
+    pub const ASSETS_SKIPPED: &str = "domcorder.assets.skipped";
+    /// Counter: assets refused by the asset scanner
+    pub const ASSETS_QUARANTINED: &str = "domcorder.assets.quarantined";
+    /// Counter: server-side asset fetches; label `outcome` is `ok` or `error`
+    pub const ASSET_FETCHES: &str = "domcorder.assets.fetches";
+    /// Histogram: seconds spent on a server-side asset fetch
+    pub const ASSET_FETCH_SECONDS: &str = "domcorder.assets.fetch_seconds";
+    /// Counter: handshakes sent a cached manifest because generating one
+    /// took longer than the manifest budget
+    pub const MANIFEST_FALLBACKS: &str = "domcorder.assets.manifest_fallbacks";
+
+    /// Histogram: seconds a storage backend took to answer its health probe;
+    /// label `store` is `metadata`, `assets` or `recordings`, and `status` is
+    /// `ok`, `failing` or `unknown`
+    pub const STORAGE_HEALTH_SECONDS: &str = "domcorder.storage.health_seconds";
+}
@@ -0,0 +1,156 @@
+//! OTLP export of the server's trace spans (`otlp` feature)
+//!
+//! [`OtlpHooks`] is an [`ObservabilityHooks`] handing each
+//! [`FinishedSpan`] to an OpenTelemetry batch processor, which exports them
+//! from a background thread so the ingest path never waits on the collector.
+//! The server binary installs it when `OTEL_EXPORTER_OTLP_ENDPOINT` or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set; the other standard
+//! `OTEL_*` variables (headers, timeout, `OTEL_SERVICE_NAME`) apply as usual.
+
+use crate::observability::ObservabilityHooks;
+use crate::trace::FinishedSpan;
+use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_otlp::ExporterBuildError;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{BatchSpanProcessor, SpanData, SpanExporter, SpanEvents, SpanLinks, SpanProcessor};
+use std::borrow::Cow;
+use tracing::warn;
+
+/// Service name reported when `OTEL_SERVICE_NAME` is not set
+const SERVICE_NAME: &str = "domcorder-server";
+
+/// Exports finished spans to an OTLP collector
+///
+/// Spans still queued are flushed when the hooks are dropped.
+#[derive(Debug)]
+pub struct OtlpHooks {
+    processor: BatchSpanProcessor,
+    scope: InstrumentationScope,
+}
+
+impl OtlpHooks {
+    /// Export over OTLP/HTTP to the collector named by the `OTEL_EXPORTER_OTLP_*` variables
+    pub fn from_env() -> Result<Self, ExporterBuildError> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder().with_http().build()?;
+        Ok(Self::new(exporter))
+    }
+
+    /// Export through `exporter`
+    pub fn new(exporter: impl SpanExporter + 'static) -> Self {
+        let mut processor = BatchSpanProcessor::builder(exporter).build();
+        let resource = match std::env::var_os("OTEL_SERVICE_NAME") {
+            Some(_) => Resource::builder().build(),
+            None => Resource::builder().with_service_name(SERVICE_NAME).build(),
+        };
+        processor.set_resource(&resource);
+        let scope = InstrumentationScope::builder(SERVICE_NAME).with_version(env!("CARGO_PKG_VERSION")).build();
+        Self { processor, scope }
+    }
+
+    /// Export every span finished so far, waiting for the collector
+    pub fn flush(&self) {
+        if let Err(e) = self.processor.force_flush() {
+            warn!("⚠️ Failed to export trace spans: {}", e);
+        }
+    }
+
+    fn span_data(&self, span: &FinishedSpan) -> Option<SpanData> {
+        let trace_id = TraceId::from_hex(&span.trace_id).ok()?;
+        let span_id = SpanId::from_hex(&span.span_id).ok()?;
+        let parent_span_id = match &span.parent_span_id {
+            Some(parent) => SpanId::from_hex(parent).ok()?,
+            None => SpanId::INVALID,
+        };
+        Some(SpanData {
+            span_context: SpanContext::new(trace_id, span_id, TraceFlags::SAMPLED, false, TraceState::default()),
+            parent_span_id,
+            // The only parents are propagated from recorders and HTTP clients
+            parent_span_is_remote: span.parent_span_id.is_some(),
+            span_kind: SpanKind::Server,
+            name: Cow::Borrowed(span.name),
+            start_time: span.start,
+            end_time: span.end,
+            attributes: span.attributes.iter().map(|(key, value)| KeyValue::new(*key, value.clone())).collect(),
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: if span.ok { Status::Ok } else { Status::error("") },
+            instrumentation_scope: self.scope.clone(),
+        })
+    }
+}
+
+impl ObservabilityHooks for OtlpHooks {
+    fn span_finished(&self, span: &FinishedSpan) {
+        match self.span_data(span) {
+            Some(data) => self.processor.on_end(data),
+            None => warn!("⚠️ Not exporting span {} with malformed ids", span.name),
+        }
+    }
+}
+
+impl Drop for OtlpHooks {
+    fn drop(&mut self) {
+        if let Err(e) = self.processor.shutdown() {
+            warn!("⚠️ Failed to export trace spans: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{RECORDING_ID_ATTRIBUTE, SpanBuilder, TraceParent};
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::routing::post;
+    use opentelemetry_otlp::WithExportConfig;
+    use tokio::sync::mpsc;
+
+    /// Find `needle` in `haystack`
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_spans_are_exported_to_the_collector() {
+        // A collector keeping the bodies of trace export requests
+        let (sender, mut requests) = mpsc::unbounded_channel::<Bytes>();
+        let collector = Router::new().route(
+            "/v1/traces",
+            post(move |body: Bytes| async move {
+                let _ = sender.send(body);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, collector).await });
+
+        let parent = TraceParent::parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+        let span = SpanBuilder::start("domcorder.ingest", Some(&parent))
+            .attribute(RECORDING_ID_ATTRIBUTE, "a.dcrr".to_string())
+            .finish(true);
+        let hooks = tokio::task::spawn_blocking(move || {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+                .unwrap();
+            let hooks = OtlpHooks::new(exporter);
+            hooks.span_finished(&span);
+            hooks.flush();
+            hooks
+        })
+        .await
+        .unwrap();
+
+        let body = requests.recv().await.unwrap();
+        assert!(contains(&body, &TraceId::from_hex(&parent.trace_id).unwrap().to_bytes()));
+        assert!(contains(&body, &SpanId::from_hex(&parent.parent_id).unwrap().to_bytes()));
+        assert!(contains(&body, b"domcorder.ingest"));
+        assert!(contains(&body, RECORDING_ID_ATTRIBUTE.as_bytes()));
+        assert!(contains(&body, SERVICE_NAME.as_bytes()));
+        tokio::task::spawn_blocking(move || drop(hooks)).await.unwrap();
+    }
+}
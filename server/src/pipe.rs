@@ -0,0 +1,55 @@
+//! One-shot ingestion from a pipe
+//!
+//! `domcorder-server ingest -` reads one recording from stdin and stores it
+//! through the same validation and asset pipeline as an upload, then exits,
+//! so recordings can be produced by other tools in a shell pipeline (or
+//! generated in bulk for load tests). The input is either a frame stream as
+//! sent to `POST /record`, or a complete `.dcrr` file, told apart by the
+//! file's magic bytes.
+
+use crate::{RecordingId, StorageError, StorageState};
+use domcorder_proto::writer::DCRR_MAGIC;
+use std::io::Cursor;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Where a piped recording is stored, and its site
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipeIngest {
+    pub site_origin: Option<String>,
+    pub subdir: Option<PathBuf>,
+    pub filename: Option<String>,
+}
+
+/// Store the recording read from `source`, with or without a file header
+pub async fn ingest_pipe<R: AsyncRead + Unpin>(
+    state: &StorageState,
+    mut source: R,
+    options: PipeIngest,
+) -> Result<RecordingId, StorageError> {
+    let mut prefix = Vec::with_capacity(DCRR_MAGIC.len());
+    (&mut source)
+        .take(DCRR_MAGIC.len() as u64)
+        .read_to_end(&mut prefix)
+        .await?;
+    // A frame stream starts with a frame's length, which never looks like the magic
+    let has_header = prefix == DCRR_MAGIC;
+    let source = Cursor::new(prefix).chain(source);
+
+    let site_origin = options.site_origin.as_deref();
+    if has_header {
+        state
+            .save_recording_stream_with_site_and_path(source, site_origin, None, options.subdir, options.filename)
+            .await
+    } else {
+        state
+            .save_recording_stream_frames_only_with_site_and_path(
+                source,
+                site_origin,
+                None,
+                options.subdir,
+                options.filename,
+            )
+            .await
+    }
+}
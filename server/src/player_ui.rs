@@ -0,0 +1,137 @@
+//! The web player, embedded in the server binary
+//!
+//! `player/dist` (the output of `bun run build` in `player/`) is bundled at
+//! compile time and served under `/play/`, so a bare server can show its own
+//! recordings. `GET /play/{id}` returns the player's `index.html` with a few
+//! tags injected: a `<base>` pointing at `/play/` (so the player's relative
+//! asset URLs resolve however deep the id is), the API base URL, and the
+//! recording to open.
+//!
+//! If the player was not built before the server, the page answers 404 and
+//! the rest of the API is unaffected.
+
+use crate::RecordingId;
+use axum::{
+    extract::{OriginalUri, Path},
+    http::{StatusCode, Uri, header},
+    response::{Html, IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "../player/dist/"]
+#[allow_missing = true]
+struct PlayerAssets;
+
+const NOT_BUILT: &str = "Player UI not built: run `bun run build` in player/ and rebuild the server";
+
+/// `GET /play`: the player with no recording preselected
+pub(crate) async fn handle_play_index(OriginalUri(original): OriginalUri, uri: Uri) -> Response {
+    index_page(&mount_prefix(&original, &uri), None)
+}
+
+/// `GET /play/{*path}`: a bundled asset, or the player opened on recording `path`
+pub(crate) async fn handle_play(
+    Path(path): Path<String>,
+    OriginalUri(original): OriginalUri,
+    uri: Uri,
+) -> Response {
+    if path != "index.html"
+        && let Some(file) = PlayerAssets::get(&path)
+    {
+        // Vite puts content-hashed bundles under assets/; anything else may change
+        let cache_control = if path.starts_with("assets/") {
+            "public, max-age=31536000, immutable"
+        } else {
+            "no-cache"
+        };
+        return (
+            [
+                (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+                (header::CACHE_CONTROL, cache_control.to_string()),
+            ],
+            file.data,
+        )
+            .into_response();
+    }
+
+    match RecordingId::new(path) {
+        Ok(id) => index_page(&mount_prefix(&original, &uri), Some(&id)),
+        Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
+
+fn index_page(prefix: &str, recording: Option<&RecordingId>) -> Response {
+    let Some(index) = PlayerAssets::get("index.html") else {
+        return (StatusCode::NOT_FOUND, NOT_BUILT).into_response();
+    };
+    let html = String::from_utf8_lossy(&index.data);
+    (
+        [(header::CACHE_CONTROL, "no-cache")],
+        Html(inject_head(&html, prefix, recording)),
+    )
+        .into_response()
+}
+
+/// The part of the request path in front of the DomCorder routes, e.g.
+/// `/domcorder` when the router is nested under that prefix
+fn mount_prefix(original: &Uri, uri: &Uri) -> String {
+    original
+        .path()
+        .strip_suffix(uri.path())
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Insert the `<base>` and `domcorder-*` meta tags at the start of `<head>`
+fn inject_head(html: &str, prefix: &str, recording: Option<&RecordingId>) -> String {
+    let mut tags = format!(
+        "<base href=\"{}/play/\"><meta name=\"domcorder-api-base\" content=\"{}\">",
+        escape_attr(prefix),
+        escape_attr(prefix),
+    );
+    if let Some(id) = recording {
+        tags.push_str(&format!("<meta name=\"domcorder-recording\" content=\"{}\">", escape_attr(id.as_str())));
+    }
+
+    match html.find("<head>") {
+        Some(pos) => {
+            let at = pos + "<head>".len();
+            format!("{}{}{}", &html[..at], tags, &html[at..])
+        }
+        None => format!("{}{}", tags, html),
+    }
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_head_adds_base_and_recording() {
+        let id = RecordingId::new("sub/a\"b.dcrr").unwrap();
+        let html = inject_head("<html><head><title>x</title></head></html>", "/dc", Some(&id));
+        assert_eq!(
+            html,
+            "<html><head><base href=\"/dc/play/\"><meta name=\"domcorder-api-base\" content=\"/dc\">\
+             <meta name=\"domcorder-recording\" content=\"sub/a&quot;b.dcrr\"><title>x</title></head></html>"
+        );
+    }
+
+    #[test]
+    fn test_mount_prefix() {
+        let original: Uri = "/domcorder/play/sub/x.dcrr".parse().unwrap();
+        let nested: Uri = "/play/sub/x.dcrr".parse().unwrap();
+        assert_eq!(mount_prefix(&original, &nested), "/domcorder");
+        assert_eq!(mount_prefix(&nested, &nested), "");
+    }
+}
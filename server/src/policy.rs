@@ -0,0 +1,246 @@
+//! Per-origin recording policy
+//!
+//! Operators keep a table of policies keyed by site origin, with `*` as the
+//! fallback for origins that have none of their own, and edit it through
+//! `/admin/policies`. A recording's policy is resolved when its
+//! `RecordingMetadata` arrives: recordings of denied origins, and those left
+//! out by the sampling rate, are refused before anything is stored; unsampled
+//! live sessions are either told to stop or silently discarded. Admitted
+//! recordings are masked to the policy's level and cut off at its maximum
+//! duration during ingestion, so recording can be restricted centrally
+//! without touching recorder deployments.
+
+use crate::erasure::Redactor;
+use crate::frame_filter::FrameProcessor;
+use crate::recording_id::RecordingId;
+use crate::{StorageError, StorageState};
+use domcorder_proto::Frame;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Origin of the policy used for origins without their own
+pub const DEFAULT_ORIGIN: &str = "*";
+
+/// How much of the page is masked before it is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingLevel {
+    /// Stored as recorded
+    #[default]
+    None,
+    /// Input values masked and keystrokes dropped
+    Inputs,
+    /// All text content masked, as for redaction (see [`Redactor`])
+    All,
+}
+
+impl MaskingLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MaskingLevel::None => "none",
+            MaskingLevel::Inputs => "inputs",
+            MaskingLevel::All => "all",
+        }
+    }
+
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "none" => Some(MaskingLevel::None),
+            "inputs" => Some(MaskingLevel::Inputs),
+            "all" => Some(MaskingLevel::All),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to a live session left out by the sampling rate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsampledAction {
+    /// The recorder is sent a StopRecording frame and the connection closed
+    #[default]
+    Stop,
+    /// The stream is accepted and thrown away, for recorders that don't
+    /// understand StopRecording
+    Discard,
+}
+
+impl UnsampledAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnsampledAction::Stop => "stop",
+            UnsampledAction::Discard => "discard",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "stop" => Some(UnsampledAction::Stop),
+            "discard" => Some(UnsampledAction::Discard),
+            _ => None,
+        }
+    }
+}
+
+/// How recordings of one site origin are treated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OriginPolicy {
+    /// Site origin (e.g. `https://shop.example`), or [`DEFAULT_ORIGIN`]
+    pub origin: String,
+    /// Whether the origin is recorded at all
+    pub allowed: bool,
+    pub masking: MaskingLevel,
+    /// Fraction of sessions recorded, from 0 to 1
+    pub sample_rate: f64,
+    pub unsampled: UnsampledAction,
+    /// Recordings end once this much recorded time has passed
+    pub max_duration_ms: Option<u64>,
+}
+
+impl OriginPolicy {
+    /// A policy that records every session of `origin` as it is
+    pub fn new(origin: impl Into<String>) -> Self {
+        Self {
+            origin: origin.into(),
+            allowed: true,
+            masking: MaskingLevel::None,
+            sample_rate: 1.0,
+            unsampled: UnsampledAction::Stop,
+            max_duration_ms: None,
+        }
+    }
+
+    /// Check that recording `id` is stored under this policy, failing with
+    /// [`StorageError::Refused`] if the origin is denied and
+    /// [`StorageError::NotSampled`] if the session was not sampled
+    pub fn admit(&self, id: &RecordingId) -> Result<(), StorageError> {
+        if !self.allowed {
+            return Err(StorageError::Refused {
+                origin: self.origin.clone(),
+                reason: "recording is disabled for this origin".to_string(),
+            });
+        }
+        if !self.samples(id) {
+            return Err(StorageError::NotSampled {
+                origin: self.origin.clone(),
+                action: self.unsampled,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether recording `id` falls within the sampling rate
+    ///
+    /// The decision is derived from the id, so every check of the same
+    /// recording agrees.
+    pub fn samples(&self, id: &RecordingId) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        let digest = Sha256::digest(id.as_str().as_bytes());
+        let bucket = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        (bucket as f64 / u64::MAX as f64) < self.sample_rate
+    }
+
+    /// Applies this policy to the frames of one admitted recording
+    pub fn enforcer(&self) -> PolicyEnforcer {
+        PolicyEnforcer {
+            masker: match self.masking {
+                MaskingLevel::None => None,
+                MaskingLevel::Inputs => Some(Redactor::inputs_only()),
+                MaskingLevel::All => Some(Redactor::default()),
+            },
+            max_duration_ms: self.max_duration_ms,
+            started_at: None,
+        }
+    }
+}
+
+/// A policy's masking and duration limit, applied frame by frame
+#[derive(Debug, Default)]
+pub struct PolicyEnforcer {
+    masker: Option<Redactor>,
+    max_duration_ms: Option<u64>,
+    /// The recording's first timestamp
+    started_at: Option<u64>,
+}
+
+impl FrameProcessor for PolicyEnforcer {
+    /// The frame masked as the policy requires
+    fn process(&mut self, frame: Frame) -> Option<Frame> {
+        match &mut self.masker {
+            Some(masker) => masker.redact(frame),
+            None => Some(frame),
+        }
+    }
+}
+
+impl PolicyEnforcer {
+    /// Whether `frame` is past the maximum duration, which ends the recording
+    pub fn expired(&mut self, frame: &Frame) -> bool {
+        let (Some(max_duration_ms), Frame::Timestamp(timestamp)) = (self.max_duration_ms, frame) else {
+            return false;
+        };
+        let started_at = *self.started_at.get_or_insert(timestamp.timestamp);
+        timestamp.timestamp.saturating_sub(started_at) > max_duration_ms
+    }
+}
+
+impl StorageState {
+    /// The policy for recordings of `origin`: its own, else the default
+    /// policy, else one that records everything
+    pub async fn recording_policy(&self, origin: Option<&str>) -> Result<OriginPolicy, StorageError> {
+        if let Some(origin) = origin
+            && let Some(policy) = self.metadata_store.get_origin_policy(origin).await?
+        {
+            return Ok(policy);
+        }
+        let policy = self.metadata_store.get_origin_policy(DEFAULT_ORIGIN).await?;
+        Ok(policy.unwrap_or_else(|| OriginPolicy::new(DEFAULT_ORIGIN)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::TimestampData;
+
+    #[test]
+    fn test_admit() {
+        let mut policy = OriginPolicy::new("https://example.com");
+        let ids: Vec<_> = (0..200)
+            .map(|i| RecordingId::new(format!("{}.dcrr", i)).unwrap())
+            .collect();
+        assert!(ids.iter().all(|id| policy.admit(id).is_ok()));
+
+        policy.sample_rate = 0.25;
+        let sampled = ids.iter().filter(|id| policy.admit(id).is_ok()).count();
+        assert!((20..80).contains(&sampled), "{} of 200 sampled", sampled);
+        let unsampled = ids.iter().find(|id| !policy.samples(id)).unwrap();
+        assert!(matches!(
+            policy.admit(unsampled),
+            Err(StorageError::NotSampled {
+                action: UnsampledAction::Stop,
+                ..
+            })
+        ));
+
+        policy.sample_rate = 1.0;
+        policy.allowed = false;
+        let Err(StorageError::Refused { origin, .. }) = policy.admit(&ids[0]) else {
+            panic!("expected a refusal");
+        };
+        assert_eq!(origin, "https://example.com");
+    }
+
+    #[test]
+    fn test_enforcer_ends_at_max_duration() {
+        let mut policy = OriginPolicy::new(DEFAULT_ORIGIN);
+        policy.max_duration_ms = Some(1000);
+        let mut enforcer = policy.enforcer();
+        let timestamp = |timestamp| Frame::Timestamp(TimestampData { timestamp });
+        assert!(!enforcer.expired(&timestamp(5000)));
+        assert!(!enforcer.expired(&timestamp(6000)));
+        assert!(enforcer.expired(&timestamp(6001)));
+    }
+}
@@ -0,0 +1,173 @@
+//! Data minimization applied to everything the server stores
+//!
+//! The policy is enforced in the ingestion pipeline, so it covers live and
+//! uploaded recordings alike: page URLs can be stored without their query
+//! string and fragment. It also decides how much of the recorder's IP address
+//! is kept in the recordings index, if any. Which frame types are stored at
+//! all is configured separately (see [`crate::frame_filter`]).
+
+use domcorder_proto::Frame;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+/// How much of the recorder's IP address is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClientIpMode {
+    /// Not stored at all
+    #[default]
+    Drop,
+    /// The network only: the last octet of IPv4 and all but the first 48
+    /// bits of IPv6 are zeroed
+    Truncate,
+    /// A salted SHA-256 of the address, which groups sessions without
+    /// revealing where they came from
+    Hash,
+    /// The full address
+    Full,
+}
+
+impl ClientIpMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClientIpMode::Drop => "drop",
+            ClientIpMode::Truncate => "truncate",
+            ClientIpMode::Hash => "hash",
+            ClientIpMode::Full => "full",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "drop" => Some(ClientIpMode::Drop),
+            "truncate" => Some(ClientIpMode::Truncate),
+            "hash" => Some(ClientIpMode::Hash),
+            "full" => Some(ClientIpMode::Full),
+            _ => None,
+        }
+    }
+}
+
+/// What the server keeps of each recording; the default keeps every frame
+/// and URL as recorded and no IP address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrivacyPolicy {
+    pub client_ip: ClientIpMode,
+    /// Mixed into hashed addresses; keep it secret and stable, or hashes of
+    /// the same address stop matching
+    pub ip_salt: Vec<u8>,
+    /// Take the recorder's address from the first `X-Forwarded-For` entry,
+    /// for servers behind a reverse proxy
+    pub trust_forwarded_for: bool,
+    /// Store page URLs without their query string and fragment
+    pub strip_url_queries: bool,
+}
+
+impl PrivacyPolicy {
+    /// The form of `ip` to store, or None if addresses are not stored
+    pub fn client_ip(&self, ip: IpAddr) -> Option<String> {
+        match self.client_ip {
+            ClientIpMode::Drop => None,
+            ClientIpMode::Truncate => Some(truncate_ip(ip).to_string()),
+            ClientIpMode::Hash => {
+                let mut hasher = Sha256::new();
+                hasher.update(&self.ip_salt);
+                hasher.update(ip.to_string().as_bytes());
+                Some(format!("{:x}", hasher.finalize()))
+            }
+            ClientIpMode::Full => Some(ip.to_string()),
+        }
+    }
+
+    /// A page URL as it should be stored
+    pub fn page_url(&self, url: &str) -> String {
+        if self.strip_url_queries {
+            strip_query(url)
+        } else {
+            url.to_string()
+        }
+    }
+
+    /// The frame as it should be stored
+    pub fn minimize(&self, frame: Frame) -> Frame {
+        if !self.strip_url_queries {
+            return frame;
+        }
+        match frame {
+            Frame::RecordingMetadata(mut metadata) => {
+                metadata.initial_url = strip_query(&metadata.initial_url);
+                Frame::RecordingMetadata(metadata)
+            }
+            Frame::PageError(mut error) => {
+                error.source_url = error.source_url.as_deref().map(strip_query);
+                Frame::PageError(error)
+            }
+            frame => frame,
+        }
+    }
+}
+
+/// `url` without its query string and fragment, which often carry identifiers
+pub fn strip_query(url: &str) -> String {
+    url.split(['?', '#']).next().unwrap_or_default().to_string()
+}
+
+/// The network part of an address
+fn truncate_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            IpAddr::from([a, b, c, 0])
+        }
+        // IPv4-mapped addresses are truncated as IPv4
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => truncate_ip(IpAddr::V4(v4)),
+            None => {
+                let segments = v6.segments();
+                IpAddr::from([segments[0], segments[1], segments[2], 0, 0, 0, 0, 0])
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::RecordingMetadataData;
+
+    #[test]
+    fn test_client_ip_modes() {
+        let v4: IpAddr = "203.0.113.77".parse().unwrap();
+        let v6: IpAddr = "2001:db8:85a3:8d3:1319:8a2e:370:7348".parse().unwrap();
+        let mut policy = PrivacyPolicy::default();
+        assert_eq!(policy.client_ip(v4), None);
+
+        policy.client_ip = ClientIpMode::Truncate;
+        assert_eq!(policy.client_ip(v4).as_deref(), Some("203.0.113.0"));
+        assert_eq!(policy.client_ip(v6).as_deref(), Some("2001:db8:85a3::"));
+        let mapped: IpAddr = "::ffff:203.0.113.77".parse().unwrap();
+        assert_eq!(policy.client_ip(mapped).as_deref(), Some("203.0.113.0"));
+
+        policy.client_ip = ClientIpMode::Hash;
+        let hashed = policy.client_ip(v4).unwrap();
+        assert_eq!(hashed.len(), 64);
+        assert_eq!(policy.client_ip(v4).unwrap(), hashed);
+        policy.ip_salt = b"secret".to_vec();
+        assert_ne!(policy.client_ip(v4).unwrap(), hashed);
+    }
+
+    #[test]
+    fn test_minimize_frames() {
+        let policy = PrivacyPolicy {
+            strip_url_queries: true,
+            ..Default::default()
+        };
+        let metadata = Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: "https://shop.example/reset?token=abc#top".to_string(),
+            heartbeat_interval_seconds: 10,
+        });
+        let Frame::RecordingMetadata(metadata) = policy.minimize(metadata) else {
+            panic!("expected the metadata");
+        };
+        assert_eq!(metadata.initial_url, "https://shop.example/reset");
+    }
+}
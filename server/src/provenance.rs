@@ -0,0 +1,112 @@
+//! Provenance of derived recordings
+//!
+//! A recording made from others (trimmed, split or merged by `dcrr`, or
+//! rewritten by the server by a redaction, asset extraction or repair)
+//! records where it came from: the source recordings, the operation, and the
+//! tool and version that did it. `GET /recording/{id}/info` lists a
+//! recording's provenance, oldest first.
+//!
+//! Provenance is kept in the metadata store, next to the rest of a
+//! recording's metadata. Files derived outside the server can't reach it, so
+//! they carry their provenance in an Annotation frame named
+//! [`PROVENANCE_ANNOTATION`] instead, which is recorded when the file is
+//! ingested. The annotations of a derived file's sources pass through with
+//! their frames, so the whole lineage is recorded.
+
+use crate::{RecordingId, StorageError, StorageState};
+use chrono::{DateTime, SubsecRound, Utc};
+use domcorder_proto::{AnnotationData, Frame};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Name of the Annotation frames carrying a recording's provenance
+pub const PROVENANCE_ANNOTATION: &str = "domcorder:provenance";
+
+/// The server, as named in the provenance it records
+pub const SERVER_TOOL: &str = concat!("domcorder-server/", env!("CARGO_PKG_VERSION"));
+
+/// How a recording was derived
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// What was done, e.g. `trim`, `merge` or `redact`
+    pub operation: String,
+    /// The recordings it was derived from, by id (or file name, for files
+    /// derived outside the server)
+    pub sources: Vec<String>,
+    /// The tool and version that did it, e.g. `dcrr/0.1.0`
+    pub tool: String,
+    pub created: DateTime<Utc>,
+}
+
+impl Provenance {
+    pub fn new(tool: &str, operation: &str, sources: Vec<String>) -> Self {
+        Self {
+            operation: operation.to_string(),
+            sources,
+            tool: tool.to_string(),
+            // As precise as the metadata store keeps it
+            created: Utc::now().trunc_subsecs(3),
+        }
+    }
+
+    /// The Annotation frame carrying this provenance in a recording
+    pub fn to_frame(&self) -> Frame {
+        Frame::Annotation(AnnotationData {
+            name: PROVENANCE_ANNOTATION.to_string(),
+            data: Some(serde_json::to_string(self).expect("provenance serializes")),
+        })
+    }
+
+    /// The provenance `frame` carries, if it is a provenance annotation
+    pub fn from_frame(frame: &Frame) -> Option<Self> {
+        match frame {
+            Frame::Annotation(annotation) if annotation.name == PROVENANCE_ANNOTATION => {
+                serde_json::from_str(annotation.data.as_deref()?).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl StorageState {
+    /// The provenance of recording `id`, oldest first
+    pub async fn recording_provenance(&self, id: &RecordingId) -> Result<Vec<Provenance>, StorageError> {
+        Ok(self.metadata_store.list_recording_provenance(id.as_str()).await?)
+    }
+
+    /// Record that the server derived recording `id` by `operation` from
+    /// `sources`; failures are logged
+    pub(crate) async fn record_provenance(&self, id: &RecordingId, operation: &str, sources: &[&RecordingId]) {
+        let sources = sources.iter().map(|source| source.to_string()).collect();
+        self.store_provenance(id, &Provenance::new(SERVER_TOOL, operation, sources))
+            .await;
+    }
+
+    /// Store one provenance entry of recording `id`; failures are logged
+    pub(crate) async fn store_provenance(&self, id: &RecordingId, provenance: &Provenance) {
+        if let Err(e) = self
+            .metadata_store
+            .add_recording_provenance(id.as_str(), provenance)
+            .await
+        {
+            warn!("⚠️ Failed to record the provenance of {}: {}", id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance_round_trips_through_its_frame() {
+        let provenance = Provenance::new("dcrr/0.1.0", "merge", vec!["a.dcrr".to_string(), "b.dcrr".to_string()]);
+        assert_eq!(Provenance::from_frame(&provenance.to_frame()), Some(provenance));
+
+        let other = Frame::Annotation(AnnotationData {
+            name: "checkout:started".to_string(),
+            data: Some("{}".to_string()),
+        });
+        assert_eq!(Provenance::from_frame(&other), None);
+    }
+}
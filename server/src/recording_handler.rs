@@ -2,65 +2,340 @@
 //!
 //! This module extracts the WebSocket recording logic so it can be reused
 //! by both the domcorder server and simplikeys, with hooks for custom behavior.
-
-use crate::asset_cache::manifest::generate_manifest;
-use crate::AppState;
+//!
+//! The frame stream handling is written against [`RecordingTransport`], so
+//! other bidirectional transports can reuse it through
+//! [`handle_recording_stream`]; the `webtransport` feature adds a WebTransport
+//! listener this way.
+//!
+//! Message boundaries carry no meaning: the server parses the concatenated
+//! bytes as one frame stream, so a recorder may split a frame across messages
+//! or pack many frames into one. Recorders should batch small frames: during
+//! the handshake they may ask for a batch size with the `batch_bytes` query
+//! parameter, and the server answers with the size it accepts (see
+//! [`negotiate_batch_bytes`]) in the `x-domcorder-batch-bytes` response
+//! header. The agreed size is also how much the server buffers between the
+//! connection and the recording file, so disk writes are coalesced to match.
+//!
+//! If the metadata asks for heartbeats, the server also sends a HeartbeatPing
+//! on that interval. The recorder's HeartbeatPong answers go into the frame
+//! stream, where ingestion turns them into a clock skew estimate (see
+//! [`crate::clock`]).
+//!
+//! When ingestion finds the frames have drifted from the page (see
+//! [`crate::drift`]), the server sends a RequestKeyframe frame and the
+//! recorder's fresh Keyframe carries on in the same stream.
+//!
+//! Sessions the origin's recording policy (see [`crate::policy`]) doesn't
+//! sample are turned away at the handshake: with a StopRecording frame, or by
+//! accepting the stream and throwing it away.
+
+use crate::analytics::page_of_url;
+use crate::clock::now_ms;
+use crate::drift::KeyframeRequestListener;
+use crate::observability::names;
+use crate::{AppState, RecordingId, StorageError};
 use axum::extract::ws::{Message, WebSocket};
-use domcorder_proto::{Frame, FrameReader, FrameWriter, CacheManifestData, ManifestEntryData};
+use crate::policy::UnsampledAction;
+use domcorder_proto::{
+    CacheManifestData, Frame, FrameReader, FrameWriter, HeartbeatPingData, ManifestEntryData, RecordingMetadataData,
+    RequestKeyframeData, StopRecordingData,
+};
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
+use std::collections::HashSet;
 use std::error::Error;
 use std::io;
 use std::io::Cursor;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
+use tokio::time::{Interval, MissedTickBehavior};
 use tracing::{debug, error, info, warn};
 
+/// Batch size used when the recorder asks for none
+pub const DEFAULT_BATCH_BYTES: usize = 64 * 1024;
+
+/// Largest batch the server agrees to; larger requests are clamped
+pub const MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Handshake query parameter carrying the recorder's preferred batch size
+pub const BATCH_BYTES_PARAM: &str = "batch_bytes";
+
+/// Handshake response header carrying the agreed batch size
+pub const BATCH_BYTES_HEADER: &str = "x-domcorder-batch-bytes";
+
+/// Least buffering between the connection and the recording file
+const MIN_PIPE_BYTES: usize = 8192;
+
+/// The batch size to agree to, given the recorder's preference (0 asks for
+/// one message per frame)
+pub fn negotiate_batch_bytes(requested: Option<usize>) -> usize {
+    requested.unwrap_or(DEFAULT_BATCH_BYTES).min(MAX_BATCH_BYTES)
+}
+
 /// Configuration for the recording handler
 pub struct RecordingConfig {
     pub max_size: usize,
     pub subdir: Option<PathBuf>,
     pub custom_filename: Option<String>,
+    /// Negotiated batch size; see [`negotiate_batch_bytes`]
+    pub batch_bytes: usize,
+    /// The recorder's address, stored as far as the privacy policy allows
+    pub client_ip: Option<IpAddr>,
 }
 
+/// Boxed future returned by recording hooks
+pub type HookFuture<T> = std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send>>;
+
+/// Hook invoked before the recording starts
+pub type OnStartHook = Box<dyn Fn() -> HookFuture<Result<String, String>> + Send + Sync>;
+
+/// Hook invoked when RecordingMetadata is received
+pub type OnMetadataHook = Box<dyn Fn(&str) -> HookFuture<Result<Option<String>, String>> + Send + Sync>;
+
+/// Hook invoked after the recording completes
+pub type OnCompleteHook = Box<dyn Fn(&str, usize) -> HookFuture<()> + Send + Sync>;
+
+/// Hook invoked when the recording fails
+pub type OnErrorHook = Box<dyn Fn(&str) -> HookFuture<()> + Send + Sync>;
+
 /// Hooks for customizing behavior (for simplikeys integration)
+#[derive(Default)]
 pub struct RecordingHooks {
     /// Called before starting the recording to validate the connection
     /// Returns the filename to use, or an error message
-    pub on_start: Option<
-        Box<
-            dyn Fn() -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<String, String>> + Send>>
-                + Send
-                + Sync,
-        >,
-    >,
+    pub on_start: Option<OnStartHook>,
 
     /// Called when RecordingMetadata is received
     /// Can return custom site_origin or None to use default
-    pub on_metadata: Option<
-        Box<
-            dyn Fn(&str) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<String>, String>> + Send>>
-                + Send
-                + Sync,
-        >,
-    >,
+    pub on_metadata: Option<OnMetadataHook>,
 
     /// Called after recording completes successfully
-    pub on_complete: Option<
-        Box<
-            dyn Fn(&str, usize) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
-                + Send
-                + Sync,
-        >,
-    >,
+    pub on_complete: Option<OnCompleteHook>,
 
     /// Called if recording fails
-    pub on_error: Option<
-        Box<dyn Fn(&str) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> + Send + Sync>,
-    >,
+    pub on_error: Option<OnErrorHook>,
+}
+
+/// A bidirectional connection to a recorder
+///
+/// The recorder sends frame bytes (in arbitrary chunks); the server answers
+/// with the binary CacheManifest frame, or a text message explaining why the
+/// recording was refused.
+#[async_trait::async_trait]
+pub trait RecordingTransport: Send {
+    /// The next chunk of frame bytes, or None once the recorder has finished
+    ///
+    /// A connection dropped by the client (reset, broken pipe) also ends the
+    /// stream with None rather than an error.
+    async fn recv(&mut self) -> Option<io::Result<Vec<u8>>>;
+
+    async fn send_binary(&mut self, data: Vec<u8>) -> io::Result<()>;
+
+    async fn send_text(&mut self, text: String) -> io::Result<()>;
+
+    /// Close the connection; errors are ignored since the recording is already settled
+    async fn close(&mut self);
+}
+
+/// [`RecordingTransport`] over an axum WebSocket
+pub struct WebSocketTransport {
+    sender: SplitSink<WebSocket, Message>,
+    receiver: SplitStream<WebSocket>,
+    state: AppState,
+}
+
+impl WebSocketTransport {
+    pub fn new(socket: WebSocket, state: AppState) -> Self {
+        let (sender, receiver) = socket.split();
+        Self { sender, receiver, state }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordingTransport for WebSocketTransport {
+    async fn recv(&mut self) -> Option<io::Result<Vec<u8>>> {
+        while let Some(msg) = self.receiver.next().await {
+            match msg {
+                Ok(Message::Binary(data)) => {
+                    let observability = &self.state.observability;
+                    observability.counter(names::WEBSOCKET_MESSAGES_RECEIVED, 1, &[]);
+                    observability.counter(names::WEBSOCKET_BYTES_RECEIVED, data.len() as u64, &[]);
+                    return Some(Ok(data.to_vec()));
+                }
+                Ok(Message::Text(_)) => {
+                    warn!("Received unexpected text message, ignoring");
+                }
+                Ok(Message::Close(_)) => {
+                    info!("🔌 WebSocket closed by the recorder");
+                    return None;
+                }
+                Err(e) => {
+                    // Check if this is a normal close vs a real error
+                    let is_normal_close = e
+                        .source()
+                        .and_then(|err| err.downcast_ref::<io::Error>())
+                        .map(|io_err| {
+                            matches!(
+                                io_err.kind(),
+                                io::ErrorKind::ConnectionReset
+                                    | io::ErrorKind::BrokenPipe
+                                    | io::ErrorKind::UnexpectedEof
+                            )
+                        })
+                        .unwrap_or_else(|| {
+                            // Fallback to string check if source chain doesn't have io::Error
+                            let err_str = e.to_string();
+                            err_str.contains("connection closed")
+                                || err_str.contains("broken pipe")
+                                || err_str.contains("Connection reset")
+                        });
+
+                    if is_normal_close {
+                        debug!("🔌 WebSocket connection closed normally");
+                        return None;
+                    }
+                    return Some(Err(io::Error::other(e)));
+                }
+                _ => {
+                    debug!("Received other message type");
+                }
+            }
+        }
+        None
+    }
+
+    async fn send_binary(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.sender.send(Message::Binary(data.into())).await.map_err(io::Error::other)
+    }
+
+    async fn send_text(&mut self, text: String) -> io::Result<()> {
+        self.sender.send(Message::Text(text.into())).await.map_err(io::Error::other)
+    }
+
+    async fn close(&mut self) {
+        let _ = self.sender.close().await;
+    }
+}
+
+/// Tell the recorder why its recording was refused, then close the connection
+async fn refuse<T: RecordingTransport>(transport: &mut T, hooks: &RecordingHooks, error: StorageError) {
+    let error_msg = error.to_string();
+    error!("❌ {}", error_msg);
+    let _ = transport.send_text(error_msg.clone()).await;
+    if let Some(ref on_error) = hooks.on_error {
+        on_error(&error_msg).await;
+    }
+    transport.close().await;
+}
+
+/// Turn away a session its origin's policy doesn't record: unsampled
+/// sessions are told to stop or silently discarded, others are refused
+async fn decline<T: RecordingTransport>(transport: &mut T, hooks: &RecordingHooks, error: StorageError) {
+    match error {
+        StorageError::NotSampled {
+            origin,
+            action: UnsampledAction::Stop,
+        } => {
+            info!("🎲 Session on {} not sampled, stopping the recorder", origin);
+            let stop = Frame::StopRecording(StopRecordingData {
+                reason: "session not sampled".to_string(),
+            });
+            if let Err(e) = send_frame(transport, &stop).await {
+                debug!("Failed to send StopRecording: {}", e);
+            }
+            transport.close().await;
+        }
+        StorageError::NotSampled {
+            origin,
+            action: UnsampledAction::Discard,
+        } => {
+            info!("🎲 Session on {} not sampled, discarding it", origin);
+            // An empty manifest completes the handshake as usual
+            let manifest = Frame::CacheManifest(CacheManifestData {
+                site_origin: origin,
+                assets: Vec::new(),
+            });
+            if send_frame(transport, &manifest).await.is_ok() {
+                while let Some(Ok(_)) = transport.recv().await {}
+            }
+            transport.close().await;
+        }
+        error => refuse(transport, hooks, error).await,
+    }
+}
+
+/// The RecordingMetadata frame opening a recorder's stream, with the hashes
+/// of any CacheInventory frames sent before it; None until it has arrived
+async fn read_handshake(data: Vec<u8>) -> Option<(RecordingMetadataData, HashSet<String>)> {
+    let mut reader = FrameReader::new(Cursor::new(data), false);
+    let mut cached = HashSet::new();
+    loop {
+        match reader.next().await? {
+            Ok(Frame::CacheInventory(inventory)) => cached.extend(inventory.sha256_hashes),
+            Ok(Frame::RecordingMetadata(metadata)) => return Some((metadata, cached)),
+            _ => return None,
+        }
+    }
+}
+
+/// Send the recorder one frame as a binary message
+async fn send_frame<T: RecordingTransport>(transport: &mut T, frame: &Frame) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    FrameWriter::new(&mut buffer).write_frame(frame)?;
+    transport.send_binary(buffer).await
+}
+
+/// Send the recorder a HeartbeatPing stamped with the server's time
+async fn send_ping<T: RecordingTransport>(transport: &mut T) {
+    let ping = Frame::HeartbeatPing(HeartbeatPingData {
+        server_timestamp: now_ms(),
+    });
+    // A dead connection also ends `recv`, which handles it
+    if let Err(e) = send_frame(transport, &ping).await {
+        debug!("Failed to send heartbeat ping: {}", e);
+    }
+}
+
+/// The next ping time, or never if the recorder asked for no heartbeats
+async fn next_ping(pings: &mut Option<Interval>) {
+    match pings {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// The reason for the next keyframe request, or never if there is no listener
+async fn next_keyframe_request(listener: &mut Option<KeyframeRequestListener<'_>>) -> Option<String> {
+    match listener {
+        Some(listener) => listener.next().await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Main reusable WebSocket recording handler
 ///
+/// See [`handle_recording_stream`] for what it does with the frames.
+pub async fn handle_websocket_recording(
+    socket: WebSocket,
+    state: AppState,
+    user_agent: Option<String>,
+    config: RecordingConfig,
+    hooks: RecordingHooks,
+) {
+    info!("🔌 WebSocket connection established for recording");
+    state.observability.counter(names::WEBSOCKET_CONNECTIONS, 1, &[]);
+
+    let transport = WebSocketTransport::new(socket, state.clone());
+    handle_recording_stream(transport, state, user_agent, config, hooks).await;
+}
+
+/// Record a frame stream from any [`RecordingTransport`]
+///
 /// This handles:
 /// - Waiting for RecordingMetadata frame
 /// - Registering recording and generating cache manifest
@@ -68,193 +343,184 @@ pub struct RecordingHooks {
 /// - Frame processing and validation
 ///
 /// Simplikeys can call this from its own axum handlers
-pub async fn handle_websocket_recording(
-    socket: WebSocket,
+pub async fn handle_recording_stream<T: RecordingTransport>(
+    mut transport: T,
     state: AppState,
     user_agent: Option<String>,
     config: RecordingConfig,
     hooks: RecordingHooks,
 ) {
-    info!("🔌 WebSocket connection established for recording");
-
-    let (mut sender, mut receiver) = socket.split();
+    if let Err(e) = state.check_free_space() {
+        refuse(&mut transport, &hooks, e).await;
+        return;
+    }
 
     // Wait for RecordingMetadata frame to get initial_url
     let mut site_origin: Option<String> = None;
-    let mut filename: Option<String> = None;
+    let final_filename: String;
+    let heartbeat_interval_seconds: u32;
 
     // Buffer for initial frames until we get metadata
     let mut frame_buffer = Vec::new();
 
     // Read initial frames to find RecordingMetadata
-    while let Some(msg) = receiver.next().await {
-        match msg {
-            Ok(Message::Binary(data)) => {
+    loop {
+        match transport.recv().await {
+            Some(Ok(data)) => {
                 frame_buffer.push(data);
 
                 // Try to parse frames from the buffer to find RecordingMetadata
                 if !frame_buffer.is_empty() && site_origin.is_none() {
-                    let combined = frame_buffer.concat();
-                    let cursor = std::io::Cursor::new(combined);
-                    let mut reader = FrameReader::new(cursor, false);
-
-                    if let Some(Ok(frame)) = reader.next().await {
-                        if let Frame::RecordingMetadata(metadata) = frame {
-                            info!("📋 Received RecordingMetadata: initial_url={}", metadata.initial_url);
-
-                            // Call on_start hook if provided (for simplikeys entity creation)
-                            let final_filename = if let Some(ref on_start) = hooks.on_start {
-                                match on_start().await {
-                                    Ok(fname) => {
-                                        filename = Some(fname.clone());
-                                        fname
-                                    }
-                                    Err(e) => {
-                                        error!("❌ on_start hook failed: {}", e);
-                                        let _ = sender.send(Message::Text(e.into())).await;
-                                        let _ = sender.close().await;
-                                        return;
-                                    }
+                    let handshake = read_handshake(frame_buffer.concat()).await;
+                    if let Some((metadata, cached)) = handshake {
+                        info!("📋 Received RecordingMetadata: initial_url={}", metadata.initial_url);
+                        heartbeat_interval_seconds = metadata.heartbeat_interval_seconds;
+
+                        // Call on_start hook if provided (for simplikeys entity creation)
+                        let filename = if let Some(ref on_start) = hooks.on_start {
+                            match on_start().await {
+                                Ok(fname) => fname,
+                                Err(e) => {
+                                    error!("❌ on_start hook failed: {}", e);
+                                    let _ = transport.send_text(e).await;
+                                    transport.close().await;
+                                    return;
                                 }
-                            } else {
-                                // Use config filename or generate default
-                                config
-                                    .custom_filename
-                                    .clone()
-                                    .unwrap_or_else(|| state.generate_filename())
-                            };
-
-                            // Register recording and extract site origin
-                            match state
-                                .metadata_store
-                                .register_recording(&final_filename, &metadata.initial_url)
-                                .await
-                            {
-                                Ok(site_info) => {
-                                    // Call on_metadata hook if provided
-                                    let origin = if let Some(ref on_metadata) = hooks.on_metadata {
-                                        match on_metadata(&metadata.initial_url).await {
-                                            Ok(Some(custom_origin)) => custom_origin,
-                                            Ok(None) => site_info.origin.clone(),
-                                            Err(e) => {
-                                                error!("❌ on_metadata hook failed: {}", e);
-                                                let _ = sender.close().await;
-                                                return;
-                                            }
-                                        }
-                                    } else {
-                                        site_info.origin.clone()
-                                    };
-
-                                    site_origin = Some(origin.clone());
-
-                                    // Generate and send cache manifest as a binary frame
-                                    match generate_manifest(state.metadata_store.as_ref(), &origin, None).await {
-                                        Ok(manifest) => {
-                                            info!("📦 Sending cache manifest with {} entries", manifest.assets.len());
-
-                                            // Convert manifest to frame data
-                                            let manifest_entries: Vec<ManifestEntryData> = manifest
-                                                .assets
-                                                .iter()
-                                                .map(|e| ManifestEntryData {
-                                                    url: e.url.clone(),
-                                                    sha256_hash: e.sha256_hash.clone(),
-                                                })
-                                                .collect();
-
-                                            let manifest_frame = Frame::CacheManifest(CacheManifestData {
-                                                site_origin: manifest.site_origin.clone(),
-                                                assets: manifest_entries,
-                                            });
-
-                                            // Encode frame to bytes
-                                            let mut buffer = Vec::new();
-                                            let mut cursor = Cursor::new(&mut buffer);
-                                            let mut frame_writer = FrameWriter::new(&mut cursor);
-
-                                            if let Err(e) = frame_writer.write_frame(&manifest_frame) {
-                                                error!("Failed to encode manifest frame: {}", e);
-                                                let _ = sender.close().await;
-                                                return;
-                                            }
-
-                                            // Send as binary message
-                                            let buffer_len = buffer.len();
-                                            let bytes = buffer.into();
-                                            if let Err(e) = sender.send(Message::Binary(bytes)).await {
-                                                error!("Failed to send manifest frame: {}", e);
-                                                let _ = sender.close().await;
-                                                return;
-                                            }
-                                            info!("✅ Sent cache manifest frame ({} bytes)", buffer_len);
-                                        }
+                            }
+                        } else {
+                            // Use config filename or generate one for this site
+                            let site = page_of_url(&metadata.initial_url).map(|(origin, _)| origin);
+                            config
+                                .custom_filename
+                                .clone()
+                                .unwrap_or_else(|| state.generate_filename_for_site(site.as_deref()))
+                        };
+                        final_filename = filename;
+
+                        // Register recording and extract site origin
+                        match state
+                            .metadata_store
+                            .register_recording(&final_filename, &state.privacy.page_url(&metadata.initial_url))
+                            .await
+                        {
+                            Ok(site_info) => {
+                                // Call on_metadata hook if provided
+                                let origin = if let Some(ref on_metadata) = hooks.on_metadata {
+                                    match on_metadata(&metadata.initial_url).await {
+                                        Ok(Some(custom_origin)) => custom_origin,
+                                        Ok(None) => site_info.origin.clone(),
                                         Err(e) => {
-                                            error!("Failed to generate manifest: {}", e);
-                                            let _ = sender.close().await;
+                                            error!("❌ on_metadata hook failed: {}", e);
+                                            transport.close().await;
                                             return;
                                         }
                                     }
+                                } else {
+                                    site_info.origin.clone()
+                                };
+
+                                // Turn away denied and unsampled sessions before storing anything
+                                let admitted = async {
+                                    let id = RecordingId::in_subdir(config.subdir.as_deref(), &final_filename)?;
+                                    state.recording_policy(Some(&origin)).await?.admit(&id)
                                 }
-                                Err(e) => {
-                                    error!("Failed to register recording: {}", e);
-                                    let _ = sender.close().await;
+                                .await;
+                                if let Err(e) = admitted {
+                                    if let Err(e) = state.metadata_store.delete_recording_metadata(&final_filename).await {
+                                        warn!("Failed to forget refused recording {}: {}", final_filename, e);
+                                    }
+                                    decline(&mut transport, &hooks, e).await;
                                     return;
                                 }
-                            }
 
-                            // Continue processing - the metadata frame will be written to the recording
-                            break;
+                                site_origin = Some(origin.clone());
+
+                                // Generate and send cache manifest as a binary frame
+                                match state.handshake_manifest(&origin, &cached).await {
+                                    Ok(manifest) => {
+                                        info!("📦 Sending cache manifest with {} entries", manifest.assets.len());
+
+                                        // Convert manifest to frame data
+                                        let manifest_entries: Vec<ManifestEntryData> = manifest
+                                            .assets
+                                            .iter()
+                                            .map(|e| ManifestEntryData {
+                                                url: e.url.clone(),
+                                                sha256_hash: e.sha256_hash.clone(),
+                                            })
+                                            .collect();
+
+                                        let manifest_frame = Frame::CacheManifest(CacheManifestData {
+                                            site_origin: manifest.site_origin.clone(),
+                                            assets: manifest_entries,
+                                        });
+
+                                        // Encode frame to bytes
+                                        let mut buffer = Vec::new();
+                                        let mut cursor = Cursor::new(&mut buffer);
+                                        let mut frame_writer = FrameWriter::new(&mut cursor);
+
+                                        if let Err(e) = frame_writer.write_frame(&manifest_frame) {
+                                            error!("Failed to encode manifest frame: {}", e);
+                                            transport.close().await;
+                                            return;
+                                        }
+
+                                        // Send as binary message
+                                        let buffer_len = buffer.len();
+                                        if let Err(e) = transport.send_binary(buffer).await {
+                                            error!("Failed to send manifest frame: {}", e);
+                                            transport.close().await;
+                                            return;
+                                        }
+                                        info!("✅ Sent cache manifest frame ({} bytes)", buffer_len);
+                                    }
+                                    Err(e) => {
+                                        error!("Failed to generate manifest: {}", e);
+                                        transport.close().await;
+                                        return;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                error!("Failed to register recording: {}", e);
+                                transport.close().await;
+                                return;
+                            }
                         }
+
+                        // Continue processing - the metadata frame will be written to the recording
+                        break;
                     }
                 }
             }
-            Ok(Message::Close(_)) => {
-                info!("🔌 WebSocket closed before metadata received");
+            None => {
+                info!("🔌 Connection closed before metadata received");
                 return;
             }
-            Err(e) => {
-                // Check if this is a normal close vs a real error
-                let is_normal_close = e
-                    .source()
-                    .and_then(|err| err.downcast_ref::<io::Error>())
-                    .map(|io_err| {
-                        matches!(
-                            io_err.kind(),
-                            io::ErrorKind::ConnectionReset
-                                | io::ErrorKind::BrokenPipe
-                                | io::ErrorKind::UnexpectedEof
-                        )
-                    })
-                    .unwrap_or_else(|| {
-                        // Fallback to string check if source chain doesn't have io::Error
-                        let err_str = e.to_string();
-                        err_str.contains("connection closed")
-                            || err_str.contains("broken pipe")
-                            || err_str.contains("Connection reset")
-                    });
-
-                if is_normal_close {
-                    debug!("🔌 WebSocket connection closed normally while waiting for metadata");
-                } else {
-                    error!("WebSocket error while waiting for metadata: {}", e);
-                }
+            Some(Err(e)) => {
+                error!("Transport error while waiting for metadata: {}", e);
                 return;
             }
-            _ => {}
         }
     }
 
-    // Get final filename
-    let final_filename = filename.unwrap_or_else(|| {
-        config
-            .custom_filename
-            .clone()
-            .unwrap_or_else(|| state.generate_filename())
-    });
+    // Refuse a second session writing the same recording before piping any
+    // frames; the save itself claims the recording, so a race is still caught
+    let id = RecordingId::in_subdir(config.subdir.as_deref(), &final_filename).ok();
+    if let Some(id) = &id {
+        if state.is_recording_active(id) {
+            refuse(&mut transport, &hooks, StorageError::InUse(id.to_string())).await;
+            return;
+        }
+        state.store_client_ip(id, config.client_ip).await;
+    }
+    // Ingestion asks for a keyframe when the frames drift from the page
+    let mut keyframe_requests = id.as_ref().map(|id| state.keyframe_requests.listen(id));
 
-    // Create a pipe to stream WebSocket data to the save method
-    let (mut pipe_writer, pipe_reader) = tokio::io::duplex(8192);
+    // Create a pipe to stream recorder data to the save method, sized so a
+    // whole batch is handed over (and written out) at once
+    let (mut pipe_writer, pipe_reader) = tokio::io::duplex(config.batch_bytes.max(MIN_PIPE_BYTES));
 
     // Calculate total bytes from buffer before moving it
     let mut total_bytes = frame_buffer.iter().map(|b| b.len()).sum::<usize>();
@@ -263,7 +529,7 @@ pub async fn handle_websocket_recording(
     for data in frame_buffer {
         if let Err(e) = pipe_writer.write_all(&data).await {
             error!("Failed to write buffered frame: {}", e);
-            let _ = sender.close().await;
+            transport.close().await;
             return;
         }
     }
@@ -276,7 +542,7 @@ pub async fn handle_websocket_recording(
     let filename_for_save = final_filename.clone();
     let subdir_clone = config.subdir.clone();
 
-    let save_task = tokio::spawn(async move {
+    let mut save_task = tokio::spawn(async move {
         state_clone
             .save_recording_stream_frames_only_with_site_and_path(
                 pipe_reader,
@@ -288,10 +554,38 @@ pub async fn handle_websocket_recording(
             .await
     });
 
-    // Process remaining WebSocket messages and stream to pipe
-    while let Some(msg) = receiver.next().await {
-        match msg {
-            Ok(Message::Binary(data)) => {
+    let mut pings = (heartbeat_interval_seconds > 0).then(|| {
+        let period = Duration::from_secs(heartbeat_interval_seconds as u64);
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    // Process remaining messages and stream to pipe, until the recorder
+    // finishes or the save does (e.g. an operator finalized the recording)
+    let mut saved = None;
+    loop {
+        let received = tokio::select! {
+            received = transport.recv() => received,
+            result = &mut save_task => {
+                info!("🏁 Recording finished before the recorder did");
+                saved = Some(result);
+                break;
+            }
+            _ = next_ping(&mut pings) => {
+                send_ping(&mut transport).await;
+                continue;
+            }
+            Some(reason) = next_keyframe_request(&mut keyframe_requests) => {
+                let request = Frame::RequestKeyframe(RequestKeyframeData { reason });
+                if let Err(e) = send_frame(&mut transport, &request).await {
+                    debug!("Failed to request a keyframe: {}", e);
+                }
+                continue;
+            }
+        };
+        match received {
+            Some(Ok(data)) => {
                 total_bytes += data.len();
 
                 // Safety check: prevent runaway recordings
@@ -302,60 +596,36 @@ pub async fn handle_websocket_recording(
                     if let Some(ref on_error) = hooks.on_error {
                         on_error(&error_msg).await;
                     }
-                    let _ = sender.close().await;
+                    transport.close().await;
                     return;
                 }
 
                 // Write data to the pipe (streams to disk with frame processing)
                 if let Err(e) = pipe_writer.write_all(&data).await {
-                    let error_msg = format!("Failed to write to pipe: {}", e);
+                    // The save stopped reading; its error says why (e.g. InUse)
+                    drop(pipe_writer);
+                    let error_msg = match save_task.await {
+                        Ok(Err(save_error)) => format!("Failed to save recording: {}", save_error),
+                        _ => format!("Failed to write to pipe: {}", e),
+                    };
                     error!("❌ {}", error_msg);
 
+                    let _ = transport.send_text(error_msg.clone()).await;
                     if let Some(ref on_error) = hooks.on_error {
                         on_error(&error_msg).await;
                     }
-                    let _ = sender.close().await;
+                    transport.close().await;
                     return;
                 }
             }
-            Ok(Message::Text(_)) => {
-                warn!("Received unexpected text message, ignoring");
-            }
-            Ok(Message::Close(_)) => {
-                info!("🔌 WebSocket connection closed, finalizing recording");
+            None => {
+                info!("🔌 Connection closed, finalizing recording");
                 break;
             }
-            Err(e) => {
-                // Check if this is a normal close vs a real error
-                let is_normal_close = e
-                    .source()
-                    .and_then(|err| err.downcast_ref::<io::Error>())
-                    .map(|io_err| {
-                        matches!(
-                            io_err.kind(),
-                            io::ErrorKind::ConnectionReset
-                                | io::ErrorKind::BrokenPipe
-                                | io::ErrorKind::UnexpectedEof
-                        )
-                    })
-                    .unwrap_or_else(|| {
-                        // Fallback to string check if source chain doesn't have io::Error
-                        let err_str = e.to_string();
-                        err_str.contains("connection closed")
-                            || err_str.contains("broken pipe")
-                            || err_str.contains("Connection reset")
-                    });
-
-                if is_normal_close {
-                    debug!("🔌 WebSocket connection closed normally, finalizing recording");
-                } else {
-                    error!("WebSocket error: {}", e);
-                }
+            Some(Err(e)) => {
+                error!("Transport error: {}", e);
                 break;
             }
-            _ => {
-                debug!("Received other message type");
-            }
         }
     }
 
@@ -364,15 +634,19 @@ pub async fn handle_websocket_recording(
     drop(pipe_writer);
 
     // Wait for the save task to complete
-    match save_task.await {
+    let saved = match saved {
+        Some(saved) => saved,
+        None => save_task.await,
+    };
+    match saved {
         Ok(Ok(saved_filename)) => {
             info!("✅ Recording saved as {} ({} bytes)", saved_filename, total_bytes);
 
             if let Some(ref on_complete) = hooks.on_complete {
-                on_complete(&saved_filename, total_bytes).await;
+                on_complete(saved_filename.as_str(), total_bytes).await;
             }
 
-            let _ = sender.close().await;
+            transport.close().await;
         }
         Ok(Err(e)) => {
             let error_msg = format!("Failed to save recording: {}", e);
@@ -381,7 +655,7 @@ pub async fn handle_websocket_recording(
             if let Some(ref on_error) = hooks.on_error {
                 on_error(&error_msg).await;
             }
-            let _ = sender.close().await;
+            transport.close().await;
         }
         Err(e) => {
             let error_msg = format!("Save task panicked: {}", e);
@@ -390,10 +664,10 @@ pub async fn handle_websocket_recording(
             if let Some(ref on_error) = hooks.on_error {
                 on_error(&error_msg).await;
             }
-            let _ = sender.close().await;
+            transport.close().await;
         }
     }
 
-    info!("🔌 WebSocket connection ended");
+    info!("🔌 Recording connection ended");
 }
 
@@ -0,0 +1,127 @@
+//! Validated recording identifiers
+//!
+//! Recording ids come from URLs and hook callbacks and end up as storage keys
+//! (file paths for the local store), so they are checked once, when the
+//! [`RecordingId`] is constructed, rather than at each use.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest accepted id, in bytes
+const MAX_LEN: usize = 512;
+
+/// Identifies a stored recording: a file name like `2024-01-01_..._abc.dcrr`,
+/// or `subdir/name.dcrr` for recordings saved into a subdirectory
+///
+/// Ids are relative paths made of plain segments: no empty, `.` or `..`
+/// segments, no leading `/`, no backslashes and no control characters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RecordingId(String);
+
+/// Error returned when a string is not a valid [`RecordingId`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid recording id {id:?}: {reason}")]
+pub struct InvalidRecordingId {
+    pub id: String,
+    pub reason: &'static str,
+}
+
+impl RecordingId {
+    pub fn new(id: impl Into<String>) -> Result<Self, InvalidRecordingId> {
+        let id = id.into();
+        match Self::problem(&id) {
+            Some(reason) => Err(InvalidRecordingId { id, reason }),
+            None => Ok(Self(id)),
+        }
+    }
+
+    fn problem(id: &str) -> Option<&'static str> {
+        if id.is_empty() {
+            return Some("empty");
+        }
+        if id.len() > MAX_LEN {
+            return Some("too long");
+        }
+        if id.chars().any(|c| c.is_control() || c == '\\') {
+            return Some("contains a control character or backslash");
+        }
+        if id.split('/').any(|segment| matches!(segment, "" | "." | "..")) {
+            return Some("must be a relative path without empty, '.' or '..' segments");
+        }
+        None
+    }
+
+    /// Build the id for `filename` inside `subdir` (if any)
+    pub fn in_subdir(subdir: Option<&std::path::Path>, filename: &str) -> Result<Self, InvalidRecordingId> {
+        match subdir {
+            Some(subdir) => Self::new(format!("{}/{}", subdir.to_string_lossy(), filename)),
+            None => Self::new(filename),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RecordingId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for RecordingId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for RecordingId {
+    type Err = InvalidRecordingId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for RecordingId {
+    type Error = InvalidRecordingId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<RecordingId> for String {
+    fn from(id: RecordingId) -> Self {
+        id.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_filenames_and_subdirectories() {
+        for id in ["a.dcrr", "2024-01-01_12-00-00.000_abc.dcrr", "team/a.dcrr", "a/b/c.dcrr", "..dcrr"] {
+            assert!(RecordingId::new(id).is_ok(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_rejects_traversal_and_absolute_paths() {
+        for id in ["", "..", "../secret", "a/../../b.dcrr", "/etc/passwd", "a//b", "./a", "a\\..\\b", "a\0b"] {
+            assert!(RecordingId::new(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let id: RecordingId = serde_json::from_str("\"team/a.dcrr\"").unwrap();
+        assert_eq!(id.as_str(), "team/a.dcrr");
+        assert!(serde_json::from_str::<RecordingId>("\"../a.dcrr\"").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Recording metadata for players
+//!
+//! Duration, frame counts by type, the initial viewport and URL, and the
+//! number of assets, so a player can lay out its timeline before streaming
+//! the whole recording. Extracting it reads every frame, so the metadata of
+//! finished recordings is cached in the metadata store along with the size
+//! of the file it was read from; a recording rewritten since (e.g. by
+//! erasure) no longer matches that size and is read again.
+
+use crate::timeline::ActiveClock;
+use domcorder_proto::{Frame, FrameReader};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use tokio::io::AsyncRead;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingMeta {
+    /// Stored size of the recording the metadata was read from
+    pub size: u64,
+    /// Recorded time, leaving out paused and idle spans, as in the timeline
+    pub duration_ms: u64,
+    /// First Timestamp frame value (Unix ms)
+    pub start_timestamp: Option<u64>,
+    /// Last Timestamp frame value (Unix ms)
+    pub end_timestamp: Option<u64>,
+    pub frame_count: u64,
+    /// Frames by type name
+    pub frame_counts: BTreeMap<String, u64>,
+    /// The first keyframe's viewport
+    pub viewport: Option<Viewport>,
+    pub initial_url: Option<String>,
+    /// Asset and AssetReference frames
+    pub asset_count: u64,
+}
+
+/// Incrementally builds a recording's metadata from its frames
+#[derive(Debug, Default)]
+pub struct RecordingMetaBuilder {
+    meta: RecordingMeta,
+    clock: ActiveClock,
+}
+
+impl RecordingMetaBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next frame of the recording
+    pub fn push_frame(&mut self, frame: &Frame) {
+        self.clock.push_frame(frame);
+        self.meta.frame_count += 1;
+        *self.meta.frame_counts.entry(frame.type_name().to_string()).or_default() += 1;
+        match frame {
+            Frame::RecordingMetadata(metadata) => {
+                self.meta.initial_url.get_or_insert_with(|| metadata.initial_url.clone());
+            }
+            Frame::Keyframe(keyframe) => {
+                self.meta.viewport.get_or_insert(Viewport {
+                    width: keyframe.viewport_width,
+                    height: keyframe.viewport_height,
+                });
+            }
+            Frame::KeyframeBegin(keyframe) => {
+                self.meta.viewport.get_or_insert(Viewport {
+                    width: keyframe.viewport_width,
+                    height: keyframe.viewport_height,
+                });
+            }
+            Frame::Asset(_) | Frame::AssetReference(_) => self.meta.asset_count += 1,
+            _ => {}
+        }
+    }
+
+    /// The metadata of a recording of `size` bytes
+    pub fn finish(mut self, size: u64) -> RecordingMeta {
+        self.meta.size = size;
+        self.meta.duration_ms = self.clock.offset();
+        self.meta.start_timestamp = self.clock.start();
+        self.meta.end_timestamp = self.clock.now();
+        self.meta
+    }
+}
+
+/// Read a whole frame stream of `size` bytes and extract its metadata
+pub async fn extract_recording_meta<R: AsyncRead + Unpin>(
+    reader: &mut FrameReader<R>,
+    size: u64,
+) -> io::Result<RecordingMeta> {
+    let mut builder = RecordingMetaBuilder::new();
+    while let Some(frame) = reader.read_frame().await? {
+        builder.push_frame(&frame);
+    }
+    Ok(builder.finish(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{FrameBuilder, RecordingPausedData};
+
+    #[test]
+    fn test_meta_counts_frames_and_leaves_out_paused_time() {
+        let frames = FrameBuilder::new()
+            .metadata("https://example.com/")
+            .at(1_000)
+            .viewport(1280, 720)
+            .new_page()
+            .asset(1, "https://example.com/a.png", "image/png", b"png")
+            .wait(2_000)
+            .frame(Frame::RecordingPaused(RecordingPausedData { reason: None }))
+            .wait(60_000)
+            .frame(Frame::RecordingResumed)
+            .click(1, 2)
+            .wait(500)
+            .resize(800, 600)
+            .build();
+
+        let mut builder = RecordingMetaBuilder::new();
+        for frame in &frames {
+            builder.push_frame(frame);
+        }
+        let meta = builder.finish(4096);
+
+        assert_eq!(meta.size, 4096);
+        assert_eq!(meta.duration_ms, 2_500);
+        assert_eq!(meta.start_timestamp, Some(1_000));
+        assert_eq!(meta.end_timestamp, Some(63_500));
+        assert_eq!(meta.frame_count, frames.len() as u64);
+        assert_eq!(meta.frame_counts["Timestamp"], 4);
+        assert_eq!(meta.frame_counts["MouseClicked"], 1);
+        assert_eq!(meta.viewport, Some(Viewport { width: 1280, height: 720 }));
+        assert_eq!(meta.initial_url.as_deref(), Some("https://example.com/"));
+        assert_eq!(meta.asset_count, 1);
+    }
+}
@@ -0,0 +1,626 @@
+//! Local filesystem implementation of the RecordingStore trait
+
+use crate::RecordingId;
+use crate::health::{BackendHealth, probe_directory};
+use crate::recording_store::{
+    RecordingReader, RecordingStore, RecordingWriter, SeekableRecordingReader, StoredRecording,
+};
+use chrono::{DateTime, Utc};
+use domcorder_proto::SyncFrameReader;
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncSeekExt;
+use tracing::info;
+
+/// When written data is forced to stable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    /// Leave it to the OS
+    Never,
+    /// Once, when the recording is finished
+    #[default]
+    OnFinish,
+    /// After every periodic flush (safest, slowest)
+    EveryFlush,
+}
+
+/// How recording writes are batched before they reach the file
+///
+/// Frames are small, so writing each one straight to the file costs several
+/// syscalls per frame. Writes are buffered instead and handed to the OS once
+/// `flush_bytes` have accumulated or `flush_interval` has passed since the
+/// last flush (checked on each write), so live viewers tailing the file lag
+/// by at most about `flush_interval` while frames keep arriving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WritePolicy {
+    pub flush_bytes: usize,
+    pub flush_interval: Duration,
+    pub fsync: FsyncPolicy,
+}
+
+impl Default for WritePolicy {
+    fn default() -> Self {
+        Self {
+            flush_bytes: 64 * 1024,
+            flush_interval: Duration::from_millis(250),
+            fsync: FsyncPolicy::default(),
+        }
+    }
+}
+
+/// Stores each recording as a `.dcrr` file under a base directory
+pub struct LocalRecordingStore {
+    base_path: PathBuf,
+    write_policy: WritePolicy,
+}
+
+impl LocalRecordingStore {
+    /// Create a new local recording store
+    ///
+    /// The base_path will be created if it doesn't exist.
+    pub fn new<P: AsRef<Path>>(base_path: P) -> io::Result<Self> {
+        let base_path = base_path.as_ref().to_path_buf();
+        fs::create_dir_all(&base_path)?;
+        info!("Initialized LocalRecordingStore at {:?}", base_path);
+        Ok(Self {
+            base_path,
+            write_policy: WritePolicy::default(),
+        })
+    }
+
+    /// Batch and sync recording writes according to `policy`
+    pub fn with_write_policy(mut self, policy: WritePolicy) -> Self {
+        self.write_policy = policy;
+        self
+    }
+
+    /// Get the filesystem path for a recording
+    fn id_to_path(&self, id: &RecordingId) -> PathBuf {
+        self.base_path.join(id.as_str())
+    }
+
+    /// Get the filesystem path a failed recording is set aside at
+    fn failed_path(&self, id: &RecordingId) -> PathBuf {
+        self.set_aside_path(id, "failed")
+    }
+
+    /// Get the filesystem path a recording is set aside at, with `suffix`
+    /// appended to its file name
+    fn set_aside_path(&self, id: &RecordingId, suffix: &str) -> PathBuf {
+        let path = self.id_to_path(id);
+        path.with_file_name(format!(
+            "{}.{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            suffix
+        ))
+    }
+
+    /// List the files whose ids (`subdir/filename`) start with `prefix` and
+    /// are accepted by `select`, under the id it returns
+    fn walk(&self, prefix: &str, select: impl Fn(&str) -> Option<&str>) -> io::Result<Vec<StoredRecording>> {
+        let mut recordings = Vec::new();
+        // (directory, its id prefix: "" or "subdir/")
+        let mut pending = vec![(self.base_path.clone(), String::new())];
+        while let Some((dir, dir_id)) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let id = format!("{}{}", dir_id, name);
+                // Symlinks are not followed, so the walk stays inside the store
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    let sub_id = format!("{}/", id);
+                    if sub_id.starts_with(prefix) || prefix.starts_with(&sub_id) {
+                        pending.push((entry.path(), sub_id));
+                    }
+                } else if file_type.is_file()
+                    && let Some(id) = select(&id)
+                    && id.starts_with(prefix)
+                {
+                    recordings.push(stored_recording(&entry.path(), id.to_string())?);
+                }
+            }
+        }
+        Ok(recordings)
+    }
+
+    /// Get the filesystem path for a subdirectory, refusing paths that escape the base path
+    fn subdir_to_path(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+        let is_plain = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if key.is_empty() || !is_plain {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid recording subdirectory: {}", key),
+            ));
+        }
+        Ok(self.base_path.join(relative))
+    }
+}
+
+fn is_recording(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("dcrr")
+}
+
+fn stored_recording(path: &Path, filename: String) -> io::Result<StoredRecording> {
+    let metadata = fs::metadata(path)?;
+    // Many Linux filesystems don't report a birth time
+    let created = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .map(DateTime::from)
+        .unwrap_or_else(|_| Utc::now());
+    let modified = metadata.modified().map(DateTime::from).unwrap_or(created);
+    Ok(StoredRecording {
+        filename,
+        size: metadata.len(),
+        created,
+        modified,
+        header_created: read_header_created(path),
+    })
+}
+
+/// The header's `created_at`, if the file has a readable header with one set
+fn read_header_created(path: &Path) -> Option<DateTime<Utc>> {
+    let file = fs::File::open(path).ok()?;
+    let header = SyncFrameReader::new(file, true).read_header().ok()?;
+    match header.created_at {
+        0 => None,
+        millis => DateTime::from_timestamp_millis(i64::try_from(millis).ok()?),
+    }
+}
+
+impl RecordingWriter for fs::File {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Exclusive lock on `<recording>.lock`, held while the recording is written
+///
+/// The lock is taken with the OS (`flock`/`LockFileEx`), so one left behind by
+/// a crashed server is released with its process rather than blocking the
+/// recording forever. The lock file is removed when the writer is dropped,
+/// which a writer that opened it just before must notice: it would lock the
+/// removed file while a later writer locks a new one at the same path.
+struct WriteLock {
+    path: PathBuf,
+    _file: fs::File,
+}
+
+impl WriteLock {
+    /// Lock `recording`, failing with `ErrorKind::ResourceBusy` if another
+    /// writer holds it
+    fn acquire(recording: &Path) -> io::Result<Self> {
+        let path = recording.with_file_name(format!(
+            "{}.lock",
+            recording.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        loop {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(&path)?;
+            if let Some(lock) = Self::lock(recording, &path, file)? {
+                return Ok(lock);
+            }
+        }
+    }
+
+    /// Lock `file`, opened at `path`; None if the file has been removed from
+    /// `path` meanwhile, so the path must be opened again
+    fn lock(recording: &Path, path: &Path, file: fs::File) -> io::Result<Option<Self>> {
+        match file.try_lock() {
+            Ok(()) if is_file_at(&file, path)? => Ok(Some(Self {
+                path: path.to_path_buf(),
+                _file: file,
+            })),
+            Ok(()) => Ok(None),
+            Err(fs::TryLockError::WouldBlock) => Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                format!("{} is locked by another writer", recording.display()),
+            )),
+            Err(fs::TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// Whether `path` still names open file `file`
+#[cfg(unix)]
+fn is_file_at(file: &fs::File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(current.dev() == opened.dev() && current.ino() == opened.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether `path` still names open file `file`
+///
+/// Windows doesn't let a file be removed while it's open, so it always does.
+#[cfg(not(unix))]
+fn is_file_at(_file: &fs::File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        // Removed before `_file` releases the lock, so a writer arriving later
+        // creates and locks a fresh file
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A recording file written through a buffer flushed per [`WritePolicy`]
+struct BufferedRecordingFile {
+    file: BufWriter<fs::File>,
+    policy: WritePolicy,
+    last_flush: Instant,
+    _lock: WriteLock,
+}
+
+impl BufferedRecordingFile {
+    fn new(file: fs::File, policy: WritePolicy, lock: WriteLock) -> Self {
+        Self {
+            // Sized so a full buffer is flushed by us, not by BufWriter itself
+            file: BufWriter::with_capacity(policy.flush_bytes.max(1) * 2, file),
+            policy,
+            last_flush: Instant::now(),
+            _lock: lock,
+        }
+    }
+
+    fn flush_to_os(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.policy.fsync == FsyncPolicy::EveryFlush {
+            self.file.get_ref().sync_data()?;
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for BufferedRecordingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(buf)?;
+        if self.file.buffer().len() >= self.policy.flush_bytes
+            || self.last_flush.elapsed() >= self.policy.flush_interval
+        {
+            self.flush_to_os()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_to_os()
+    }
+}
+
+impl RecordingWriter for BufferedRecordingFile {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.file.flush()?;
+        if self.policy.fsync != FsyncPolicy::Never {
+            self.file.get_ref().sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordingStore for LocalRecordingStore {
+    async fn create(&self, id: &RecordingId) -> io::Result<Box<dyn RecordingWriter>> {
+        let path = self.id_to_path(id);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Locked before the file is truncated, so a second writer leaves it alone
+        let lock = WriteLock::acquire(&path)?;
+        let file = fs::File::create(path)?;
+        Ok(Box::new(BufferedRecordingFile::new(file, self.write_policy, lock)))
+    }
+
+    async fn open(&self, id: &RecordingId, offset: u64) -> io::Result<RecordingReader> {
+        let path = self.id_to_path(id);
+        let mut file = tokio::fs::File::open(path).await?;
+        if offset > 0 {
+            file.seek(io::SeekFrom::Start(offset)).await?;
+        }
+        Ok(Box::new(file))
+    }
+
+    async fn open_seekable(&self, id: &RecordingId) -> io::Result<Option<SeekableRecordingReader>> {
+        let file = tokio::fs::File::open(self.id_to_path(id)).await?;
+        Ok(Some(Box::new(file)))
+    }
+
+    async fn exists(&self, id: &RecordingId) -> io::Result<bool> {
+        Ok(self.id_to_path(id).is_file())
+    }
+
+    async fn list(&self, subdir: Option<&str>) -> io::Result<Vec<StoredRecording>> {
+        let dir = match subdir {
+            Some(subdir) => self.subdir_to_path(subdir)?,
+            None => self.base_path.clone(),
+        };
+
+        let mut recordings = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !is_recording(&path) {
+                continue;
+            }
+            let filename = path.file_name().unwrap().to_string_lossy().to_string();
+            recordings.push(stored_recording(&path, filename)?);
+        }
+        Ok(recordings)
+    }
+
+    async fn list_all(&self, prefix: Option<&str>) -> io::Result<Vec<StoredRecording>> {
+        self.walk(prefix.unwrap_or_default(), |id| is_recording(Path::new(id)).then_some(id))
+    }
+
+    async fn delete(&self, id: &RecordingId) -> io::Result<()> {
+        fs::remove_file(self.id_to_path(id))
+    }
+
+    async fn rename(&self, from: &RecordingId, to: &RecordingId) -> io::Result<()> {
+        let path = self.id_to_path(to);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = WriteLock::acquire(&path)?;
+        // Atomic within one filesystem, which both ids are in
+        fs::rename(self.id_to_path(from), path)
+    }
+
+    async fn mark_failed(&self, id: &RecordingId) -> io::Result<()> {
+        let path = self.id_to_path(id);
+        fs::rename(&path, self.failed_path(id)).or_else(|_| fs::remove_file(&path))
+    }
+
+    async fn list_failed(&self) -> io::Result<Vec<StoredRecording>> {
+        self.walk("", |id| id.strip_suffix(".failed").filter(|id| is_recording(Path::new(id))))
+    }
+
+    async fn open_failed(&self, id: &RecordingId) -> io::Result<RecordingReader> {
+        Ok(Box::new(tokio::fs::File::open(self.failed_path(id)).await?))
+    }
+
+    async fn delete_failed(&self, id: &RecordingId) -> io::Result<()> {
+        fs::remove_file(self.failed_path(id))
+    }
+
+    async fn mark_corrupt(&self, id: &RecordingId) -> io::Result<()> {
+        fs::rename(self.id_to_path(id), self.set_aside_path(id, "corrupt"))
+    }
+
+    async fn release_abandoned(&self) -> io::Result<Vec<RecordingId>> {
+        let mut abandoned = Vec::new();
+        for lock in self.walk("", |id| id.strip_suffix(".lock").filter(|id| is_recording(Path::new(id))))? {
+            let Ok(id) = RecordingId::new(lock.filename) else {
+                continue;
+            };
+            // A lock that can be taken has no writer; dropping it removes the file
+            match WriteLock::acquire(&self.id_to_path(&id)) {
+                Ok(_) => abandoned.push(id),
+                Err(e) if e.kind() == io::ErrorKind::ResourceBusy => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(abandoned)
+    }
+
+    async fn health(&self) -> BackendHealth {
+        BackendHealth::measure(probe_directory(&self.base_path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_second_writer_is_refused_while_locked() {
+        let temp_dir = TempDir::new().unwrap();
+        // Two stores over one directory, as two server processes would be
+        let first = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let second = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let id = RecordingId::new("shared.dcrr").unwrap();
+
+        let mut writer = first.create(&id).await.unwrap();
+        writer.write_all(b"first").unwrap();
+        let err = second.create(&id).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::ResourceBusy);
+        writer.finish().unwrap();
+
+        // The lock goes with the writer, and the first writer's data was left alone
+        assert!(!temp_dir.path().join("shared.dcrr.lock").exists());
+        assert_eq!(fs::read(temp_dir.path().join("shared.dcrr")).unwrap(), b"first");
+        second.create(&id).await.unwrap().finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_file_removed_before_locking_is_not_locked() {
+        let temp_dir = TempDir::new().unwrap();
+        let recording = temp_dir.path().join("shared.dcrr");
+        let path = temp_dir.path().join("shared.dcrr.lock");
+
+        // A writer opens the lock file just as the one holding it finishes
+        let first = WriteLock::acquire(&recording).unwrap();
+        let opened = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        drop(first);
+        // and a third writer locks a new lock file
+        let third = WriteLock::acquire(&recording).unwrap();
+
+        // The file it opened is gone, so its lock would not exclude the third
+        assert!(WriteLock::lock(&recording, &path, opened).unwrap().is_none());
+        let busy = WriteLock::acquire(&recording).err().unwrap();
+        assert_eq!(busy.kind(), io::ErrorKind::ResourceBusy);
+        drop(third);
+    }
+
+    #[tokio::test]
+    async fn test_rename_replaces_the_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let (from, to) = (RecordingId::new("a.dcrr.rewrite").unwrap(), RecordingId::new("a.dcrr").unwrap());
+        fs::write(temp_dir.path().join("a.dcrr"), b"old").unwrap();
+        fs::write(temp_dir.path().join("a.dcrr.rewrite"), b"new").unwrap();
+
+        let writer = store.create(&to).await.unwrap();
+        let busy = store.rename(&from, &to).await.unwrap_err();
+        assert_eq!(busy.kind(), io::ErrorKind::ResourceBusy);
+        drop(writer);
+
+        store.rename(&from, &to).await.unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("a.dcrr")).unwrap(), b"new");
+        assert!(!temp_dir.path().join("a.dcrr.rewrite").exists());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_locks_are_released() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let live = RecordingId::new("live.dcrr").unwrap();
+        let _writer = store.create(&live).await.unwrap();
+        // Left behind by a crashed server, whose lock went with its process
+        fs::create_dir(temp_dir.path().join("site")).unwrap();
+        fs::write(temp_dir.path().join("site/crashed.dcrr"), b"partial").unwrap();
+        fs::write(temp_dir.path().join("site/crashed.dcrr.lock"), b"").unwrap();
+
+        let abandoned = store.release_abandoned().await.unwrap();
+        assert_eq!(abandoned, vec![RecordingId::new("site/crashed.dcrr").unwrap()]);
+        assert!(!temp_dir.path().join("site/crashed.dcrr.lock").exists());
+        assert!(temp_dir.path().join("live.dcrr.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_list_open_and_fail() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let id = RecordingId::new("team/a.dcrr").unwrap();
+
+        let mut writer = store.create(&id).await.unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.finish().unwrap();
+
+        assert!(store.exists(&id).await.unwrap());
+        assert!(store.list(None).await.unwrap().is_empty());
+        let listed = store.list(Some("team")).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].filename, "a.dcrr");
+        assert_eq!(listed[0].size, 10);
+
+        let mut tail = String::new();
+        store.open(&id, 6).await.unwrap().read_to_string(&mut tail).await.unwrap();
+        assert_eq!(tail, "6789");
+
+        store.mark_failed(&id).await.unwrap();
+        assert!(!store.exists(&id).await.unwrap());
+        assert!(temp_dir.path().join("team/a.dcrr.failed").exists());
+
+        let failed = store.list_failed().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].filename, "team/a.dcrr");
+        let mut contents = String::new();
+        store.open_failed(&id).await.unwrap().read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "0123456789");
+        store.delete_failed(&id).await.unwrap();
+        assert!(store.list_failed().await.unwrap().is_empty());
+
+        store.create(&id).await.unwrap().finish().unwrap();
+        store.mark_corrupt(&id).await.unwrap();
+        assert!(!store.exists(&id).await.unwrap());
+        assert!(temp_dir.path().join("team/a.dcrr.corrupt").exists());
+        assert!(store.list_failed().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_all_walks_subdirectories() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        for id in ["a.dcrr", "team/b.dcrr", "team/nested/c.dcrr", "other/d.dcrr"] {
+            let writer = store.create(&RecordingId::new(id).unwrap()).await.unwrap();
+            writer.finish().unwrap();
+        }
+        fs::write(temp_dir.path().join("team/notes.txt"), b"").unwrap();
+
+        let ids = |listed: Vec<StoredRecording>| {
+            let mut ids: Vec<_> = listed.into_iter().map(|r| r.filename).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            ids(store.list_all(None).await.unwrap()),
+            ["a.dcrr", "other/d.dcrr", "team/b.dcrr", "team/nested/c.dcrr"]
+        );
+        assert_eq!(ids(store.list_all(Some("team/")).await.unwrap()), ["team/b.dcrr", "team/nested/c.dcrr"]);
+        assert_eq!(ids(store.list_all(Some("team/nested/c")).await.unwrap()), ["team/nested/c.dcrr"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_reads_created_time_from_header() {
+        use domcorder_proto::{FileHeader, FrameWriter};
+
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path()).unwrap();
+        let mut writer = FrameWriter::new(fs::File::create(temp_dir.path().join("a.dcrr")).unwrap());
+        writer.write_header(&FileHeader::with_timestamp(1_700_000_000_123)).unwrap();
+        fs::write(temp_dir.path().join("headerless.dcrr"), b"").unwrap();
+
+        let mut listed = store.list(None).await.unwrap();
+        listed.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(
+            listed[0].header_created.map(|t| t.timestamp_millis()),
+            Some(1_700_000_000_123)
+        );
+        assert_eq!(listed[1].header_created, None);
+    }
+
+    #[tokio::test]
+    async fn test_writes_are_batched_until_flush_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path())
+            .unwrap()
+            .with_write_policy(WritePolicy {
+                flush_bytes: 8,
+                flush_interval: Duration::from_secs(3600),
+                fsync: FsyncPolicy::Never,
+            });
+        let id = RecordingId::new("a.dcrr").unwrap();
+        let path = temp_dir.path().join("a.dcrr");
+
+        let mut writer = store.create(&id).await.unwrap();
+        writer.write_all(b"0123").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        writer.write_all(b"4567").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 8);
+        writer.write_all(b"89").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_subdirectories_outside_the_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalRecordingStore::new(temp_dir.path().join("recordings")).unwrap();
+
+        for subdir in ["..", "/etc", "a/../..", ""] {
+            let err = store.list(Some(subdir)).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", subdir);
+        }
+    }
+}
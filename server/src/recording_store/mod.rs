@@ -0,0 +1,115 @@
+//! Recording persistence
+//!
+//! Recordings are addressed by [`RecordingId`]: a filename, or
+//! `subdir/filename` for recordings saved into a subdirectory. The
+//! [`RecordingStore`] trait lets other backends (object storage, database
+//! blobs) replace the local filesystem, like the asset cache stores.
+
+pub mod local;
+
+use crate::RecordingId;
+use crate::health::BackendHealth;
+use chrono::{DateTime, Utc};
+use std::io::{self, Write};
+use tokio::io::{AsyncRead, AsyncSeek};
+
+/// A recording as listed by a store
+#[derive(Debug, Clone)]
+pub struct StoredRecording {
+    /// File name within the listed directory, or the full id (`subdir/filename`)
+    /// when listed with [`RecordingStore::list_all`]
+    pub filename: String,
+    pub size: u64,
+    /// When the store created the file (filesystem metadata, or upload time)
+    pub created: DateTime<Utc>,
+    /// When the file was last written
+    pub modified: DateTime<Utc>,
+    /// `created_at` from the DCRR file header, if the store could read it
+    pub header_created: Option<DateTime<Utc>>,
+}
+
+/// Reader over a stored recording
+pub type RecordingReader = Box<dyn AsyncRead + Unpin + Send>;
+
+/// A reader that can also seek, for reading a recording's seek index
+pub trait SeekableRead: AsyncRead + AsyncSeek + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncSeek + Unpin + Send> SeekableRead for T {}
+
+/// Reader over a stored recording with random access
+pub type SeekableRecordingReader = Box<dyn SeekableRead>;
+
+/// Destination for a recording being written
+///
+/// Frames are written synchronously by `FrameWriter`; stores that upload
+/// elsewhere can buffer and do the work in `finish`.
+pub trait RecordingWriter: Write + Send {
+    /// Called once after the last frame has been written
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// Storage backend for recording files
+#[async_trait::async_trait]
+pub trait RecordingStore: Send + Sync {
+    /// Start writing a recording, replacing any existing one with the same id
+    ///
+    /// Fails with `ErrorKind::ResourceBusy` if another writer (possibly in
+    /// another process) is still writing that id.
+    async fn create(&self, id: &RecordingId) -> io::Result<Box<dyn RecordingWriter>>;
+
+    /// Open a recording for reading, starting `offset` bytes in
+    ///
+    /// For live playback, a reader opened while the recording is still being
+    /// written must see data appended afterwards, as a file reader does.
+    async fn open(&self, id: &RecordingId, offset: u64) -> io::Result<RecordingReader>;
+
+    /// Open a recording for random access, or None if the store can only
+    /// stream it (the default)
+    async fn open_seekable(&self, _id: &RecordingId) -> io::Result<Option<SeekableRecordingReader>> {
+        Ok(None)
+    }
+
+    /// Check if a recording exists
+    async fn exists(&self, id: &RecordingId) -> io::Result<bool>;
+
+    /// List the recordings directly inside `subdir` (or the top level)
+    async fn list(&self, subdir: Option<&str>) -> io::Result<Vec<StoredRecording>>;
+
+    /// List every recording, subdirectories included, whose id starts with `prefix`
+    async fn list_all(&self, prefix: Option<&str>) -> io::Result<Vec<StoredRecording>>;
+
+    /// Delete a recording
+    async fn delete(&self, id: &RecordingId) -> io::Result<()>;
+
+    /// Move recording `from` to `to`, replacing any recording there
+    ///
+    /// Readers see either the old recording at `to` or the new one, never a
+    /// partial one. Fails with `ErrorKind::ResourceBusy` if `to` is being written.
+    async fn rename(&self, from: &RecordingId, to: &RecordingId) -> io::Result<()>;
+
+    /// Set aside a recording whose write failed so it is no longer listed
+    async fn mark_failed(&self, id: &RecordingId) -> io::Result<()>;
+
+    /// List every recording set aside by [`mark_failed`](Self::mark_failed),
+    /// by its full id (`subdir/filename`)
+    async fn list_failed(&self) -> io::Result<Vec<StoredRecording>>;
+
+    /// Open a failed recording for reading
+    async fn open_failed(&self, id: &RecordingId) -> io::Result<RecordingReader>;
+
+    /// Delete a failed recording
+    async fn delete_failed(&self, id: &RecordingId) -> io::Result<()>;
+
+    /// Set aside a stored recording whose frames fail their checksums, so it
+    /// is no longer listed but can still be inspected
+    async fn mark_corrupt(&self, id: &RecordingId) -> io::Result<()>;
+
+    /// Release the claims of writers that went away without finishing (e.g.
+    /// a crashed server), returning the recordings they were writing
+    async fn release_abandoned(&self) -> io::Result<Vec<RecordingId>>;
+
+    /// Probe the backend the recordings are kept in (reported by `/readyz`)
+    async fn health(&self) -> BackendHealth {
+        BackendHealth::unknown()
+    }
+}
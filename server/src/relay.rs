@@ -0,0 +1,324 @@
+//! Live playback of recordings being written on another server
+//!
+//! With several servers behind a load balancer, a live playback request can
+//! reach a server other than the one ingesting the recording, which doesn't
+//! have (or has a stale copy of) the recording file. The shared registry
+//! (see [`crate::cluster`]) tells it which recordings other servers are
+//! writing, and a [`FrameRelay`] carries the frames over: the server asks on
+//! the [`REQUEST_TOPIC`] for a stream of the recording, and the server
+//! writing it plays the recording (the frames written so far, then the new
+//! ones as they arrive) into a topic of that stream's own.
+//!
+//! With the `nats` feature, [`NatsRelay`](crate::nats::NatsRelay) relays
+//! through NATS. For other brokers, implement [`FrameRelay`] over the
+//! pub/sub client you use (Redis, ...). Install it on every server with
+//! [`StorageState::with_frame_relay`], and run [`run_frame_relay`] so each
+//! server answers requests for its recordings. [`LocalRelay`] relays between
+//! servers in one process.
+
+use crate::frame_sink::SinkError;
+use crate::{AppState, RecordingId, StorageError, StorageState};
+use axum::body::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+use tokio_util::io::StreamReader;
+use tracing::{info, warn};
+
+/// Topic servers ask each other for recording streams on
+pub const REQUEST_TOPIC: &str = "domcorder.relay.requests";
+
+/// Bytes of the recording sent in one message
+const RELAY_CHUNK: usize = 64 * 1024;
+
+/// How often an idle stream says it is still there
+const KEEPALIVE: Duration = Duration::from_secs(5);
+
+/// How long a stream may go without a message before it is given up
+const RELAY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Messages buffered for one [`LocalRelay`] subscriber
+const LOCAL_RELAY_BUFFER: usize = 64;
+
+/// Publish/subscribe transport between servers
+///
+/// Messages on a topic must reach its subscribers in order. `publish` should
+/// fail with [`SinkError::Closed`] when a topic has no subscribers, so a
+/// stream stops once its viewer has gone.
+#[async_trait::async_trait]
+pub trait FrameRelay: Send + Sync {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), SinkError>;
+
+    /// Receive the messages published to `topic` from now on
+    async fn subscribe(&self, topic: &str) -> Result<mpsc::Receiver<Vec<u8>>, SinkError>;
+}
+
+/// A request for a stream of a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayRequest {
+    pub recording_id: String,
+    /// The stream's topic is [`stream_topic`] of this
+    pub stream: String,
+}
+
+/// The topic the frames of stream `stream` are relayed on
+pub fn stream_topic(stream: &str) -> String {
+    format!("domcorder.relay.stream.{}", stream)
+}
+
+/// One message of a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RelayChunk {
+    /// Recording bytes, numbered so a lost message is noticed
+    Data {
+        seq: u64,
+        data: Vec<u8>,
+    },
+    Keepalive,
+    /// The recording is complete (or could no longer be read)
+    End,
+}
+
+impl RelayChunk {
+    fn encode(self) -> Vec<u8> {
+        match self {
+            RelayChunk::Data { seq, data } => {
+                let mut payload = Vec::with_capacity(9 + data.len());
+                payload.push(b'd');
+                payload.extend_from_slice(&seq.to_be_bytes());
+                payload.extend_from_slice(&data);
+                payload
+            }
+            RelayChunk::Keepalive => vec![b'k'],
+            RelayChunk::End => vec![b'e'],
+        }
+    }
+
+    fn decode(payload: &[u8]) -> Option<Self> {
+        match payload.split_first()? {
+            (b'd', rest) if rest.len() >= 8 => {
+                let (seq, data) = rest.split_at(8);
+                Some(RelayChunk::Data {
+                    seq: u64::from_be_bytes(seq.try_into().ok()?),
+                    data: data.to_vec(),
+                })
+            }
+            (b'k', []) => Some(RelayChunk::Keepalive),
+            (b'e', []) => Some(RelayChunk::End),
+            _ => None,
+        }
+    }
+}
+
+/// Relays messages between the servers (StorageStates) of one process
+#[derive(Debug, Default)]
+pub struct LocalRelay {
+    topics: Mutex<HashMap<String, Vec<mpsc::Sender<Vec<u8>>>>>,
+}
+
+#[async_trait::async_trait]
+impl FrameRelay for LocalRelay {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), SinkError> {
+        let subscribers: Vec<_> = {
+            let mut topics = self.topics.lock().unwrap();
+            let Some(subscribers) = topics.get_mut(topic) else {
+                return Err(SinkError::Closed);
+            };
+            subscribers.retain(|subscriber| !subscriber.is_closed());
+            subscribers.clone()
+        };
+        let mut delivered = false;
+        for subscriber in subscribers {
+            delivered |= subscriber.send(payload.clone()).await.is_ok();
+        }
+        if !delivered {
+            return Err(SinkError::Closed);
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<mpsc::Receiver<Vec<u8>>, SinkError> {
+        let (sender, receiver) = mpsc::channel(LOCAL_RELAY_BUFFER);
+        let mut topics = self.topics.lock().unwrap();
+        topics.retain(|_, subscribers| subscribers.iter().any(|subscriber| !subscriber.is_closed()));
+        topics.entry(topic.to_string()).or_default().push(sender);
+        Ok(receiver)
+    }
+}
+
+impl StorageState {
+    /// Play recordings being written on other servers through `relay`
+    pub fn with_frame_relay(mut self, relay: Arc<dyn FrameRelay>) -> Self {
+        self.frame_relay = Some(relay);
+        self
+    }
+
+    /// A reader over recording `id` relayed from the server writing it, if
+    /// another server is writing it and a relay is installed
+    pub(crate) async fn open_relayed(
+        &self,
+        id: &RecordingId,
+    ) -> Result<Option<Box<dyn AsyncRead + Unpin + Send>>, StorageError> {
+        let Some(relay) = &self.frame_relay else {
+            return Ok(None);
+        };
+        if self.active_recordings.lock().unwrap().contains_key(id) || self.active_registry.remote_lease(id).is_none() {
+            return Ok(None);
+        }
+        let request = RelayRequest {
+            recording_id: id.to_string(),
+            stream: uuid::Uuid::new_v4().to_string(),
+        };
+        let relay_error = |e: SinkError| StorageError::Io(io::Error::other(format!("frame relay: {}", e)));
+        // Subscribed first, so no message is missed
+        let receiver = relay
+            .subscribe(&stream_topic(&request.stream))
+            .await
+            .map_err(relay_error)?;
+        let payload = serde_json::to_vec(&request).expect("relay requests serialize");
+        relay.publish(REQUEST_TOPIC, payload).await.map_err(relay_error)?;
+        info!("📡 Relaying active recording {} from another server", id);
+        Ok(Some(Box::new(relayed_reader(receiver))))
+    }
+}
+
+/// The recording bytes of a stream, failing if a message is lost or the
+/// stream goes quiet
+fn relayed_reader(receiver: mpsc::Receiver<Vec<u8>>) -> impl AsyncRead + Unpin + Send {
+    let chunks = futures::stream::unfold(Some((receiver, 0u64)), |stream| async move {
+        let (mut receiver, next) = stream?;
+        loop {
+            let payload = match tokio::time::timeout(RELAY_TIMEOUT, receiver.recv()).await {
+                Ok(Some(payload)) => payload,
+                Ok(None) => return Some((Err(io::Error::from(io::ErrorKind::UnexpectedEof)), None)),
+                Err(_) => {
+                    let e = io::Error::new(io::ErrorKind::TimedOut, "the relayed recording stopped");
+                    return Some((Err(e), None));
+                }
+            };
+            match RelayChunk::decode(&payload) {
+                Some(RelayChunk::Data { seq, data }) if seq == next => {
+                    return Some((Ok(Bytes::from(data)), Some((receiver, next + 1))));
+                }
+                Some(RelayChunk::Data { .. }) | None => {
+                    let e = io::Error::new(io::ErrorKind::InvalidData, "lost part of the relayed recording");
+                    return Some((Err(e), None));
+                }
+                Some(RelayChunk::Keepalive) => continue,
+                Some(RelayChunk::End) => return None,
+            }
+        }
+    });
+    StreamReader::new(Box::pin(chunks))
+}
+
+/// Answer the relay requests for the recordings this server is writing
+/// until the process exits
+pub async fn run_frame_relay(state: AppState) {
+    let Some(relay) = state.frame_relay.clone() else {
+        return;
+    };
+    let mut requests = match relay.subscribe(REQUEST_TOPIC).await {
+        Ok(requests) => requests,
+        Err(e) => {
+            warn!("⚠️ Failed to subscribe to frame relay requests: {}", e);
+            return;
+        }
+    };
+    while let Some(payload) = requests.recv().await {
+        let Ok(request) = serde_json::from_slice::<RelayRequest>(&payload) else {
+            warn!("⚠️ Ignoring a malformed frame relay request");
+            continue;
+        };
+        let Ok(id) = RecordingId::new(request.recording_id.as_str()) else {
+            continue;
+        };
+        // Another server answers for its own recordings
+        if !state.active_recordings.lock().unwrap().contains_key(&id) {
+            continue;
+        }
+        tokio::spawn(relay_recording(
+            state.clone(),
+            relay.clone(),
+            id,
+            stream_topic(&request.stream),
+        ));
+    }
+}
+
+/// Play recording `id` into `topic` until it is complete or nobody listens
+async fn relay_recording(state: AppState, relay: Arc<dyn FrameRelay>, id: RecordingId, topic: String) {
+    let mut reader = match state.clone().get_recording_stream(&id).await {
+        Ok(reader) => reader,
+        Err(e) => {
+            warn!("⚠️ Failed to open {} for relaying: {}", id, e);
+            let _ = relay.publish(&topic, RelayChunk::End.encode()).await;
+            return;
+        }
+    };
+    let mut buffer = vec![0; RELAY_CHUNK];
+    let mut seq = 0;
+    loop {
+        let chunk = match tokio::time::timeout(KEEPALIVE, reader.read(&mut buffer)).await {
+            Ok(Ok(0)) => RelayChunk::End,
+            Ok(Ok(read)) => {
+                seq += 1;
+                RelayChunk::Data {
+                    seq: seq - 1,
+                    data: buffer[..read].to_vec(),
+                }
+            }
+            Ok(Err(e)) => {
+                warn!("⚠️ Failed to read {} for relaying: {}", id, e);
+                RelayChunk::End
+            }
+            Err(_) => RelayChunk::Keepalive,
+        };
+        let end = chunk == RelayChunk::End;
+        if relay.publish(&topic, chunk.encode()).await.is_err() || end {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_round_trip() {
+        for chunk in [
+            RelayChunk::Data {
+                seq: 7,
+                data: b"frames".to_vec(),
+            },
+            RelayChunk::Keepalive,
+            RelayChunk::End,
+        ] {
+            assert_eq!(RelayChunk::decode(&chunk.clone().encode()), Some(chunk));
+        }
+        assert_eq!(RelayChunk::decode(b"d123"), None);
+        assert_eq!(RelayChunk::decode(b""), None);
+    }
+
+    #[tokio::test]
+    async fn test_lost_chunks_fail_the_stream() {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut reader = relayed_reader(receiver);
+        for seq in [0, 2] {
+            let chunk = RelayChunk::Data {
+                seq,
+                data: vec![seq as u8],
+            };
+            sender.send(chunk.encode()).await.unwrap();
+        }
+        let mut data = Vec::new();
+        let e = reader.read_to_end(&mut data).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(data, [0]);
+    }
+}
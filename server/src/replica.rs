@@ -0,0 +1,443 @@
+//! Double-writing to a replica storage backend
+//!
+//! For cross-region durability before the server runs highly available, assets
+//! and recordings can be mirrored to a second [`AssetFileStore`] and
+//! [`RecordingStore`]. Writes go to the primary stores as before. Each asset
+//! stored or deleted, and each recording finished or deleted, is then queued
+//! and copied to the replica (or deleted there) by a background task, so a
+//! slow or unreachable replica never holds up ingestion.
+//!
+//! Copies that fail are retried with backoff. Tasks compare against the
+//! primary when they run, so a retried copy of something since deleted is
+//! dropped, and the replica converges on the primary however the tasks
+//! interleave. Recordings set aside as failed are removed from the
+//! replica. `POST /admin/replica/reconcile` (also run at startup) queues
+//! everything the replica is missing: the recordings and cached assets it
+//! doesn't have, recordings whose copy is of another size or was written
+//! before the primary (rewritten since, with the queued copy lost in a
+//! restart), and the recordings the primary no longer has. Assets only the
+//! replica has are left there, since the replica can't list them.
+//! `GET /admin/replica` reports the queue.
+
+use crate::asset_cache::{AssetError, AssetFileStore, AssetReader};
+use crate::health::BackendHealth;
+use crate::recording_store::{
+    RecordingReader, RecordingStore, RecordingWriter, SeekableRecordingReader, StoredRecording,
+};
+use crate::{RecordingId, StorageError, StorageState};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Delay before the first retry of a failed copy; doubled on each failure
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The stores writes are mirrored to
+pub struct Replica {
+    pub assets: Box<dyn AssetFileStore>,
+    pub recordings: Box<dyn RecordingStore>,
+}
+
+/// A change to bring over to the replica
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReplicaTask {
+    PutAsset { hash: String, mime: String },
+    DeleteAsset { hash: String },
+    CopyRecording { id: RecordingId },
+    DeleteRecording { id: RecordingId },
+}
+
+/// How far the replica is behind the primary stores
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaStatus {
+    /// Changes queued or waiting to be retried
+    pub pending: u64,
+    /// Changes applied to the replica since the server started
+    pub replicated: u64,
+    /// Failed attempts since the server started
+    pub failures: u64,
+    pub last_error: Option<String>,
+}
+
+/// What a reconciliation queued
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    pub assets_queued: usize,
+    pub recordings_queued: usize,
+    /// Recordings only the replica has, queued for deletion
+    pub deletions_queued: usize,
+}
+
+/// The primary and replica stores, and the queue of changes between them
+pub struct Replication {
+    primary_assets: Arc<dyn AssetFileStore>,
+    primary_recordings: Arc<dyn RecordingStore>,
+    replica: Replica,
+    sender: mpsc::UnboundedSender<(ReplicaTask, u32)>,
+    status: Mutex<ReplicaStatus>,
+}
+
+impl std::fmt::Debug for Replication {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Replication")
+            .field("replica_assets", &self.replica.assets.storage_type())
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl Replication {
+    /// Start mirroring `primary_assets` and `primary_recordings` to `replica`,
+    /// returning the stores to write through in their place
+    ///
+    /// Must be called within a Tokio runtime, which runs the copying task.
+    fn start(
+        primary_assets: Box<dyn AssetFileStore>,
+        primary_recordings: Box<dyn RecordingStore>,
+        replica: Replica,
+    ) -> (Arc<Self>, ReplicatedAssetStore, ReplicatedRecordingStore) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let replication = Arc::new(Self {
+            primary_assets: Arc::from(primary_assets),
+            primary_recordings: Arc::from(primary_recordings),
+            replica,
+            sender,
+            status: Mutex::new(ReplicaStatus::default()),
+        });
+        tokio::spawn(Arc::clone(&replication).run(receiver));
+        let assets = ReplicatedAssetStore {
+            primary: Arc::clone(&replication.primary_assets),
+            replication: Arc::clone(&replication),
+        };
+        let recordings = ReplicatedRecordingStore {
+            primary: Arc::clone(&replication.primary_recordings),
+            replication: Arc::clone(&replication),
+        };
+        (replication, assets, recordings)
+    }
+
+    /// How far the replica is behind
+    pub fn status(&self) -> ReplicaStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn push(&self, task: ReplicaTask) {
+        self.status.lock().unwrap().pending += 1;
+        // The receiver lives as long as the runtime
+        let _ = self.sender.send((task, 0));
+    }
+
+    async fn run(self: Arc<Self>, mut receiver: mpsc::UnboundedReceiver<(ReplicaTask, u32)>) {
+        while let Some((task, attempt)) = receiver.recv().await {
+            match self.apply(&task).await {
+                Ok(()) => {
+                    let mut status = self.status.lock().unwrap();
+                    status.pending -= 1;
+                    status.replicated += 1;
+                }
+                Err(e) => {
+                    warn!("⚠️ Failed to replicate {:?} (attempt {}): {}", task, attempt + 1, e);
+                    {
+                        let mut status = self.status.lock().unwrap();
+                        status.failures += 1;
+                        status.last_error = Some(e.to_string());
+                    }
+                    let delay = RETRY_DELAY.saturating_mul(1 << attempt.min(16)).min(MAX_RETRY_DELAY);
+                    let sender = self.sender.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        let _ = sender.send((task, attempt + 1));
+                    });
+                }
+            }
+        }
+    }
+
+    /// Bring one change over, checking the primary so a stale task doesn't
+    /// undo a later one
+    async fn apply(&self, task: &ReplicaTask) -> Result<(), StorageError> {
+        match task {
+            ReplicaTask::PutAsset { hash, mime } => {
+                if !self.primary_assets.exists(hash).await? || self.replica.assets.exists(hash).await? {
+                    return Ok(());
+                }
+                let data = self.primary_assets.get(hash).await?;
+                self.replica.assets.put(hash, &data, mime).await?;
+            }
+            ReplicaTask::DeleteAsset { hash } => {
+                if !self.primary_assets.exists(hash).await? {
+                    self.replica.assets.delete(hash).await?;
+                }
+            }
+            ReplicaTask::CopyRecording { id } => {
+                if !self.primary_recordings.exists(id).await? {
+                    return Ok(());
+                }
+                let mut reader = self.primary_recordings.open(id, 0).await?;
+                let mut writer = self.replica.recordings.create(id).await?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    let read = reader.read(&mut buffer).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    writer.write_all(&buffer[..read])?;
+                }
+                writer.finish()?;
+            }
+            ReplicaTask::DeleteRecording { id } => {
+                if !self.primary_recordings.exists(id).await? {
+                    match self.replica.recordings.delete(id).await {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue every change the replica is missing
+    async fn reconcile(&self, cached_assets: Vec<(String, String)>) -> Result<ReconcileReport, StorageError> {
+        let mut report = ReconcileReport::default();
+        for (hash, mime) in cached_assets {
+            if self.primary_assets.exists(&hash).await? && !self.replica.assets.exists(&hash).await? {
+                self.push(ReplicaTask::PutAsset { hash, mime });
+                report.assets_queued += 1;
+            }
+        }
+
+        let primary = self.primary_recordings.list_all(None).await?;
+        let replica: HashMap<String, StoredRecording> = self
+            .replica
+            .recordings
+            .list_all(None)
+            .await?
+            .into_iter()
+            .map(|recording| (recording.filename.clone(), recording))
+            .collect();
+        for recording in &primary {
+            // A copy of another size, or written before the primary, predates a
+            // rewrite (a redaction or erasure) whose queued copy was lost
+            let current = replica.get(&recording.filename).is_some_and(|copy| {
+                copy.size == recording.size && copy.modified >= recording.modified
+            });
+            if current {
+                continue;
+            }
+            if let Ok(id) = RecordingId::new(recording.filename.as_str()) {
+                self.push(ReplicaTask::CopyRecording { id });
+                report.recordings_queued += 1;
+            }
+        }
+        let primary: HashSet<&str> = primary.iter().map(|recording| recording.filename.as_str()).collect();
+        for filename in replica.keys().filter(|filename| !primary.contains(filename.as_str())) {
+            if let Ok(id) = RecordingId::new(filename.as_str()) {
+                self.push(ReplicaTask::DeleteRecording { id });
+                report.deletions_queued += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Asset store writing to the primary and queueing the change for the replica
+pub struct ReplicatedAssetStore {
+    primary: Arc<dyn AssetFileStore>,
+    replication: Arc<Replication>,
+}
+
+#[async_trait::async_trait]
+impl AssetFileStore for ReplicatedAssetStore {
+    async fn put(&self, hash: &str, data: &[u8], mime: &str) -> Result<(), AssetError> {
+        self.primary.put(hash, data, mime).await?;
+        self.replication.push(ReplicaTask::PutAsset {
+            hash: hash.to_string(),
+            mime: mime.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn exists(&self, hash: &str) -> Result<bool, AssetError> {
+        self.primary.exists(hash).await
+    }
+
+    async fn resolve_url(&self, hash: &str) -> Result<String, AssetError> {
+        self.primary.resolve_url(hash).await
+    }
+
+    async fn get(&self, hash: &str) -> Result<Vec<u8>, AssetError> {
+        self.primary.get(hash).await
+    }
+
+    async fn delete(&self, hash: &str) -> Result<(), AssetError> {
+        self.primary.delete(hash).await?;
+        self.replication
+            .push(ReplicaTask::DeleteAsset { hash: hash.to_string() });
+        Ok(())
+    }
+
+    async fn open(&self, hash: &str) -> Result<AssetReader, AssetError> {
+        self.primary.open(hash).await
+    }
+
+    async fn disk_usage(&self) -> Result<Option<u64>, AssetError> {
+        self.primary.disk_usage().await
+    }
+
+    async fn health(&self) -> BackendHealth {
+        self.primary.health().await
+    }
+
+    fn storage_type(&self) -> &str {
+        self.primary.storage_type()
+    }
+
+    fn config_json(&self) -> Result<String, AssetError> {
+        self.primary.config_json()
+    }
+}
+
+/// Recording store writing to the primary and queueing finished and deleted
+/// recordings for the replica
+pub struct ReplicatedRecordingStore {
+    primary: Arc<dyn RecordingStore>,
+    replication: Arc<Replication>,
+}
+
+/// Queues the recording for the replica once it is finished
+struct ReplicatedWriter {
+    inner: Box<dyn RecordingWriter>,
+    id: RecordingId,
+    replication: Arc<Replication>,
+}
+
+impl Write for ReplicatedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl RecordingWriter for ReplicatedWriter {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.inner.finish()?;
+        self.replication.push(ReplicaTask::CopyRecording { id: self.id });
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordingStore for ReplicatedRecordingStore {
+    async fn create(&self, id: &RecordingId) -> io::Result<Box<dyn RecordingWriter>> {
+        Ok(Box::new(ReplicatedWriter {
+            inner: self.primary.create(id).await?,
+            id: id.clone(),
+            replication: Arc::clone(&self.replication),
+        }))
+    }
+
+    async fn open(&self, id: &RecordingId, offset: u64) -> io::Result<RecordingReader> {
+        self.primary.open(id, offset).await
+    }
+
+    async fn open_seekable(&self, id: &RecordingId) -> io::Result<Option<SeekableRecordingReader>> {
+        self.primary.open_seekable(id).await
+    }
+
+    async fn exists(&self, id: &RecordingId) -> io::Result<bool> {
+        self.primary.exists(id).await
+    }
+
+    async fn list(&self, subdir: Option<&str>) -> io::Result<Vec<StoredRecording>> {
+        self.primary.list(subdir).await
+    }
+
+    async fn list_all(&self, prefix: Option<&str>) -> io::Result<Vec<StoredRecording>> {
+        self.primary.list_all(prefix).await
+    }
+
+    async fn delete(&self, id: &RecordingId) -> io::Result<()> {
+        self.primary.delete(id).await?;
+        self.replication.push(ReplicaTask::DeleteRecording { id: id.clone() });
+        Ok(())
+    }
+
+    async fn rename(&self, from: &RecordingId, to: &RecordingId) -> io::Result<()> {
+        self.primary.rename(from, to).await?;
+        self.replication.push(ReplicaTask::DeleteRecording { id: from.clone() });
+        self.replication.push(ReplicaTask::CopyRecording { id: to.clone() });
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: &RecordingId) -> io::Result<()> {
+        self.primary.mark_failed(id).await?;
+        self.replication.push(ReplicaTask::DeleteRecording { id: id.clone() });
+        Ok(())
+    }
+
+    async fn list_failed(&self) -> io::Result<Vec<StoredRecording>> {
+        self.primary.list_failed().await
+    }
+
+    async fn open_failed(&self, id: &RecordingId) -> io::Result<RecordingReader> {
+        self.primary.open_failed(id).await
+    }
+
+    async fn delete_failed(&self, id: &RecordingId) -> io::Result<()> {
+        self.primary.delete_failed(id).await
+    }
+
+    async fn mark_corrupt(&self, id: &RecordingId) -> io::Result<()> {
+        self.primary.mark_corrupt(id).await?;
+        self.replication.push(ReplicaTask::DeleteRecording { id: id.clone() });
+        Ok(())
+    }
+
+    async fn release_abandoned(&self) -> io::Result<Vec<RecordingId>> {
+        self.primary.release_abandoned().await
+    }
+
+    async fn health(&self) -> BackendHealth {
+        self.primary.health().await
+    }
+}
+
+impl StorageState {
+    /// Mirror the asset and recording stores configured so far to `replica`
+    ///
+    /// Must be called within a Tokio runtime, after any other store is set.
+    pub fn with_replica(mut self, replica: Replica) -> Self {
+        let (replication, assets, recordings) =
+            Replication::start(self.asset_file_store, self.recording_store, replica);
+        self.asset_file_store = Box::new(assets);
+        self.recording_store = Box::new(recordings);
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Queue everything the replica is missing; None without a replica
+    pub async fn reconcile_replica(&self) -> Result<Option<ReconcileReport>, StorageError> {
+        let Some(replication) = &self.replication else {
+            return Ok(None);
+        };
+        let cached_assets = self.metadata_store.list_asset_hashes().await?;
+        let report = replication.reconcile(cached_assets).await?;
+        info!(
+            "🪞 Replica reconciliation queued {} assets, {} recordings and {} deletions",
+            report.assets_queued, report.recordings_queued, report.deletions_queued
+        );
+        Ok(Some(report))
+    }
+}
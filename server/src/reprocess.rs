@@ -0,0 +1,110 @@
+//! Re-processing legacy recordings
+//!
+//! Recordings written before the asset cache carry each asset's bytes inline,
+//! in Asset frames. Playback can only point the player at a cached copy when
+//! the same bytes happen to be cached (see
+//! [`crate::asset_cache::playback::PlaybackFrameTransformer`]), and the file
+//! stays as large as it was. An `extract_assets` job (see [`crate::jobs`])
+//! rewrites them the way ingestion writes recordings now: the bytes of every
+//! Asset frame go to the asset cache, and the frame is replaced with an
+//! AssetReference the player loads over HTTP.
+//!
+//! Assets over the asset limits stay inline, so nothing is lost. Recordings
+//! without inline assets are left as they are, so running the job again is
+//! cheap. Recordings on legal hold or still being written are refused.
+
+use crate::asset_cache::AssetError;
+use crate::asset_cache::limits::AssetBudget;
+use crate::storage::RecordingFilter;
+use crate::{RecordingId, StorageError, StorageState};
+use domcorder_proto::Frame;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// An `extract_assets` job: the recordings to re-process
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtractAssetsRequest {
+    /// The recordings to re-process; otherwise those selected by `filter`,
+    /// which selects every recording when empty
+    pub ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub filter: RecordingFilter,
+}
+
+impl ExtractAssetsRequest {
+    /// The recordings the request names or selects
+    pub(crate) async fn recording_ids(&self, state: &StorageState) -> Result<Vec<String>, StorageError> {
+        match &self.ids {
+            Some(ids) => Ok(ids.clone()),
+            None => Ok(state
+                .filter_recordings(&self.filter)
+                .await?
+                .into_iter()
+                .map(|recording| recording.id)
+                .collect()),
+        }
+    }
+}
+
+impl StorageState {
+    /// Move the inline assets of recording `id` to the asset cache, rewriting
+    /// it with references, and return how many were moved
+    ///
+    /// The recording is read whole and only rewritten if it had inline
+    /// assets, into a copy that replaces it once complete (see
+    /// [`replace_recording`](Self::replace_recording)). Its summary and
+    /// analytics are kept.
+    pub async fn extract_inline_assets(&self, id: &RecordingId) -> Result<usize, StorageError> {
+        if !self.recording_exists(id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        if self.is_recording_active(id) {
+            return Err(StorageError::InUse(id.to_string()));
+        }
+        if self.is_on_legal_hold(id).await? {
+            return Err(StorageError::OnHold(id.to_string()));
+        }
+
+        let site_origin = self
+            .metadata_store
+            .get_recording_summaries(&[id.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .and_then(|summary| summary.site_origin);
+        let mut reader = self.open_recording_reader(id).await?;
+        let header = reader.read_header().await.map_err(StorageError::Header)?;
+        let mut budget = AssetBudget::new(self.asset_limits);
+        let mut frames = Vec::new();
+        let mut extracted = 0;
+        while let Some(frame) = reader.read_frame().await.map_err(StorageError::Frame)? {
+            let frame = match frame {
+                Frame::Asset(asset) if !asset.buf.is_empty() => {
+                    let processed = self
+                        .process_asset_frame(&asset, site_origin.as_deref(), &mut budget)
+                        .await;
+                    match processed {
+                        Ok(Some(reference)) => {
+                            extracted += 1;
+                            Frame::AssetReference(reference)
+                        }
+                        Ok(None) | Err(StorageError::Asset(AssetError::TooLarge { .. })) => Frame::Asset(asset),
+                        Err(e) => return Err(e),
+                    }
+                }
+                frame => frame,
+            };
+            frames.push(frame);
+        }
+        drop(reader);
+        if extracted == 0 {
+            return Ok(0);
+        }
+
+        self.replace_recording(id, &header, &frames, true).await?;
+        self.index_recording_assets(id).await?;
+        self.record_provenance(id, "extract_assets", &[id]).await;
+        info!("📦 Extracted {} inline assets from {}", extracted, id);
+        Ok(extracted)
+    }
+}
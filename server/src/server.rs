@@ -1,43 +1,296 @@
-use crate::recording_handler::{handle_websocket_recording, RecordingConfig, RecordingHooks};
-use crate::AppState;
+use crate::analytics::funnel::{FunnelStep, evaluate_funnel};
+use crate::analytics::heatmap::ClickHeatmap;
+use crate::analytics::scroll::ScrollReport;
+use crate::analytics::text_entry::TextEntered;
+use crate::auth::{ApiKey, require_api_key};
+use crate::bulk::{BulkJob, BulkRequest, start_bulk_job};
+use crate::jobs::{JobRequest, submit_job};
+use crate::canvas::{CanvasError, materialize_canvas};
+use crate::clock::ClockSkew;
+use crate::collections::{Collection, MAX_COLLECTION_NAME_BYTES, valid_name};
+use crate::comments::{MAX_COMMENT_BYTES, RecordingComment};
+use crate::diff::{AlignmentMode, DiffOptions, MAX_INTERVAL_MS, MAX_POINTS, MIN_INTERVAL_MS, diff_recordings};
+use crate::encryption::EncryptionError;
+use crate::analytics::page_of_url;
+use crate::erasure::ErasureMode;
+use crate::finalize::FINALIZE_GRACE;
+use crate::policy::{DEFAULT_ORIGIN, MaskingLevel, OriginPolicy, UnsampledAction};
+use crate::provenance::Provenance;
+use crate::sites::SiteProfile;
+use crate::recording_handler::{
+    BATCH_BYTES_HEADER, RecordingConfig, RecordingHooks, handle_websocket_recording, negotiate_batch_bytes,
+};
+use crate::observability::names;
+use crate::recording_meta::extract_recording_meta;
+use crate::timeline::{SessionTimeline, extract_timeline, extract_timeline_with_timestamps};
+use crate::trace::{RECORDING_ID_ATTRIBUTE, SpanBuilder, TraceParent};
+use crate::storage::RecordingFilter;
+use crate::{AppState, RecordingId, RecordingInfo, StorageError};
 use axum::{
     Router,
-    body::Body,
-    extract::{Path, State, WebSocketUpgrade},
-    http::{StatusCode, header},
+    body::{Body, Bytes},
+    Extension, Json,
+    extract::{ConnectInfo, FromRequestParts, Path, Query, State, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode, header},
+    middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
-use domcorder_proto::{Frame, FrameWriter, PlaybackConfigData};
+use domcorder_proto::{Frame, FrameReader, FrameWriter, PlaybackConfigData};
 use futures::TryStreamExt;
 use futures::stream;
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json;
 use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
 
 use tokio_util::io::{ReaderStream, StreamReader};
 use tower_http::cors::CorsLayer;
 use tracing::{debug, error, info, warn};
 
+/// A group of related routes that a host application can choose to expose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// `POST /record` and `GET /ws/record`
+    Ingest,
+    /// `GET /recordings`, `GET /recordings/diff` and `/collections`
+    Listing,
+    /// `GET /recording/{filename}`, its info, metadata, comments, timeline, text entries and canvases,
+    /// `GET /ws/recording/{filename}` for live co-viewing, `GET /session/{session_id}/timeline`
+    /// and `GET /assets/{hash}`
+    Playback,
+    /// `GET /analytics/*`
+    Analytics,
+    /// `/admin/*`: storage usage, key rotation, deletion, legal holds, erasure and recording
+    /// policies; `/recordings/import`, `/recordings/bulk` and `/jobs`
+    Admin,
+    /// `GET /play/{id}`: the embedded web player
+    #[cfg(feature = "player-ui")]
+    Player,
+    /// `GET /readyz`: storage backend health, for load balancers and orchestrators
+    Health,
+}
+
+impl RouteGroup {
+    pub const ALL: &'static [RouteGroup] = &[
+        RouteGroup::Ingest,
+        RouteGroup::Listing,
+        RouteGroup::Playback,
+        RouteGroup::Analytics,
+        RouteGroup::Admin,
+        #[cfg(feature = "player-ui")]
+        RouteGroup::Player,
+        RouteGroup::Health,
+    ];
+
+    fn add_to(self, router: Router<AppState>) -> Router<AppState> {
+        match self {
+            RouteGroup::Ingest => router
+                .route("/record", post(handle_record).options(handle_options))
+                .route("/ws/record", get(handle_websocket_record)),
+            RouteGroup::Listing => router
+                .route("/recordings", get(handle_list_recordings))
+                .route("/recordings/diff", get(handle_diff_recordings))
+                .route("/collections", get(handle_list_collections))
+                .route(
+                    "/collections/{name}",
+                    get(handle_get_collection).put(handle_save_collection).delete(handle_delete_collection),
+                )
+                .route(
+                    "/collections/{name}/recordings/{filename}",
+                    put(handle_add_to_collection).delete(handle_remove_from_collection),
+                ),
+            RouteGroup::Playback => router
+                .route("/recording/{filename}", get(handle_get_recording))
+                .route("/recording/{filename}/info", get(handle_get_info))
+                .route("/recording/{filename}/meta", get(handle_get_meta))
+                .route("/recording/{filename}/comments", post(handle_add_comment))
+                .route("/recording/{filename}/timeline", get(handle_get_timeline))
+                .route("/recording/{filename}/text", get(handle_get_text_entries))
+                .route("/recording/{filename}/canvas/{node_id}", get(handle_get_canvas))
+                .route("/ws/recording/{filename}", get(handle_websocket_view))
+                .route("/session/{session_id}/timeline", get(handle_get_session_timeline))
+                .route("/assets/{hash}", get(handle_get_asset)),
+            RouteGroup::Analytics => router
+                .route("/analytics/heatmap", get(handle_get_heatmap))
+                .route("/analytics/scroll", get(handle_get_scroll_report))
+                .route("/analytics/sessions", get(handle_list_sessions))
+                .route("/analytics/funnel", get(handle_get_funnel))
+                .route("/analytics/text", get(handle_search_text_entries)),
+            RouteGroup::Admin => router
+                .route("/admin/storage", get(handle_get_storage_usage))
+                .route("/admin/keys/rotate", post(handle_rotate_keys))
+                .route("/admin/recordings/{filename}", delete(handle_delete_recording))
+                .route("/recording/{filename}/finalize", post(handle_finalize_recording))
+                .route(
+                    "/admin/recordings/{filename}/hold",
+                    get(handle_get_hold).put(handle_apply_hold).delete(handle_release_hold),
+                )
+                .route("/admin/holds", get(handle_list_holds))
+                .route("/admin/audit", get(handle_list_audit_events))
+                .route("/admin/erasure", post(handle_erase_subject))
+                .route("/admin/assets/gc", post(handle_collect_assets))
+                .route("/admin/assets/warm", post(handle_warm_assets))
+                .route("/admin/assets/url-history", get(handle_url_history))
+                .route("/admin/assets/changes", get(handle_asset_changes))
+                .route("/admin/assets/misses", get(handle_list_asset_misses))
+                .route("/admin/replica", get(handle_get_replica_status))
+                .route("/admin/replica/reconcile", post(handle_reconcile_replica))
+                .route(
+                    "/admin/policies",
+                    get(handle_list_policies).put(handle_set_policy).delete(handle_delete_policy),
+                )
+                .route(
+                    "/admin/sites",
+                    get(handle_list_site_profiles).put(handle_set_site_profile).delete(handle_delete_site_profile),
+                )
+                .route("/recordings/import", post(handle_import_recording))
+                .route("/recordings/bulk", post(handle_start_bulk_job))
+                .route("/recordings/bulk/{job}", get(handle_get_bulk_job))
+                .route("/jobs", post(handle_submit_job))
+                .route("/jobs/{job}", get(handle_get_job))
+                .route("/recordings/failed", get(handle_list_failed_recordings))
+                .route("/recordings/failed/{filename}", get(handle_inspect_failed_recording))
+                .route("/recordings/failed/{filename}/raw", get(handle_get_failed_recording))
+                .route("/recordings/failed/{filename}/repair", post(handle_repair_failed_recording)),
+            #[cfg(feature = "player-ui")]
+            RouteGroup::Player => router
+                .route("/play", get(crate::player_ui::handle_play_index))
+                .route("/play/", get(crate::player_ui::handle_play_index))
+                .route("/play/{*path}", get(crate::player_ui::handle_play)),
+            RouteGroup::Health => router.route("/readyz", get(handle_readyz)),
+        }
+    }
+}
+
+/// `Content-Disposition` sent with `GET /assets/{hash}`
+///
+/// Assets are arbitrary third-party content, so they are always served with a
+/// sandboxing CSP and `nosniff`; `Attachment` additionally stops a browser
+/// from rendering one if it is opened directly rather than loaded by the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssetDisposition {
+    #[default]
+    Inline,
+    Attachment,
+}
+
+impl AssetDisposition {
+    fn header_value(self) -> &'static str {
+        match self {
+            AssetDisposition::Inline => "inline",
+            AssetDisposition::Attachment => "attachment",
+        }
+    }
+}
+
+/// Builds the DomCorder HTTP API as an axum Router
+///
+/// Host applications can mount the API under a prefix, require an API key, and
+/// pick which route groups to expose, then merge or nest the result into their
+/// own Router:
+///
+/// ```ignore
+/// let domcorder = DomcorderRouter::new(state)
+///     .with_prefix("/domcorder")
+///     .with_auth(ApiKey::new(key))
+///     .routes(&[RouteGroup::Ingest, RouteGroup::Playback]);
+/// let app = host_routes.merge(domcorder);
+/// ```
+pub struct DomcorderRouter {
+    state: AppState,
+    prefix: Option<String>,
+    api_key: Option<ApiKey>,
+    asset_disposition: AssetDisposition,
+}
+
+impl DomcorderRouter {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            state,
+            prefix: None,
+            api_key: None,
+            asset_disposition: AssetDisposition::default(),
+        }
+    }
+
+    /// Mount every route under `prefix` (e.g. `/domcorder`)
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        let prefix = prefix.trim_matches('/');
+        self.prefix = (!prefix.is_empty()).then(|| format!("/{}", prefix));
+        self
+    }
+
+    /// Require an API key on every route (CORS preflight requests excepted)
+    pub fn with_auth(mut self, key: ApiKey) -> Self {
+        self.api_key = Some(key);
+        self
+    }
+
+    /// Set the `Content-Disposition` of served assets (default `inline`)
+    pub fn with_asset_disposition(mut self, disposition: AssetDisposition) -> Self {
+        self.asset_disposition = disposition;
+        self
+    }
+
+    /// Build a Router exposing the given route groups
+    pub fn routes(self, groups: &[RouteGroup]) -> Router {
+        let mut router = Router::new();
+        for group in groups {
+            router = group.add_to(router);
+        }
+        if let Some(key) = self.api_key {
+            router = router.layer(middleware::from_fn_with_state(key, require_api_key));
+        }
+        let router = router
+            .layer(Extension(self.asset_disposition))
+            .layer(CorsLayer::permissive()) // Allow CORS for all origins during development
+            .with_state(self.state);
+
+        match self.prefix {
+            Some(prefix) => Router::new().nest(&prefix, router),
+            None => router,
+        }
+    }
+}
+
+/// The full API with no prefix or authentication
 pub fn create_app(state: AppState) -> Router {
-    Router::new()
-        .route("/record", post(handle_record).options(handle_options))
-        .route("/ws/record", get(handle_websocket_record))
-        .route("/recordings", get(handle_list_recordings))
-        .route("/recording/{filename}", get(handle_get_recording))
-        .route("/assets/{hash}", get(handle_get_asset))
-        .layer(CorsLayer::permissive()) // Allow CORS for all origins during development
-        .with_state(state)
+    DomcorderRouter::new(state).routes(RouteGroup::ALL)
+}
+
+/// The recorder's address: the first `X-Forwarded-For` entry if the privacy
+/// policy trusts it, otherwise the connection's peer (absent over Unix sockets
+/// and in tests)
+struct ClientIp(Option<IpAddr>);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if state.privacy.trust_forwarded_for
+            && let Some(forwarded) = parts.headers.get("x-forwarded-for").and_then(|h| h.to_str().ok())
+        {
+            return Ok(ClientIp(forwarded.split(',').next().and_then(|ip| ip.trim().parse().ok())));
+        }
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>();
+        Ok(ClientIp(peer.map(|ConnectInfo(addr)| addr.ip())))
+    }
 }
 
-async fn handle_record(State(state): State<AppState>, body: Body) -> impl IntoResponse {
+async fn handle_record(State(state): State<AppState>, ClientIp(client_ip): ClientIp, body: Body) -> impl IntoResponse {
     info!("📡 Received POST /record request");
     debug!("Request body type: {:?}", std::any::type_name::<Body>());
 
     // Convert the axum Body to a stream of bytes, then to an AsyncRead
     let stream = body.into_data_stream().map_err(|e| {
         warn!("Error converting body to data stream: {}", e);
-        std::io::Error::new(std::io::ErrorKind::Other, e)
+        std::io::Error::other(e)
     });
     let async_reader = StreamReader::new(stream);
     debug!("Created StreamReader from body");
@@ -47,22 +300,93 @@ async fn handle_record(State(state): State<AppState>, body: Body) -> impl IntoRe
     match state.save_recording_stream_frames_only(async_reader).await {
         Ok(filename) => {
             info!("✅ Successfully saved recording: {}", filename);
+            state.store_client_ip(&filename, client_ip).await;
             (StatusCode::OK, format!("Recording saved as {}", filename)).into_response()
         }
         Err(e) => {
             error!("❌ Failed to save recording: {}", e);
-            (
-                StatusCode::BAD_REQUEST,
-                format!("Failed to process recording: {}", e),
-            )
-                .into_response()
+            let status = match e {
+                StorageError::InUse(_) => StatusCode::CONFLICT,
+                StorageError::InsufficientSpace { .. } => StatusCode::INSUFFICIENT_STORAGE,
+                StorageError::Refused { .. } => StatusCode::FORBIDDEN,
+                // Nothing is stored, but the upload itself was fine
+                StorageError::NotSampled { .. } => StatusCode::ACCEPTED,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            (status, format!("Failed to process recording: {}", e)).into_response()
+        }
+    }
+}
+
+/// Where `POST /recordings/import` stores the recording, and its site
+#[derive(Debug, Default, Deserialize)]
+struct ImportQuery {
+    subdir: Option<String>,
+    filename: Option<String>,
+    /// Origin the recording's assets are registered under, for asset warming
+    /// and URL history
+    site: Option<String>,
+}
+
+/// Import a complete recording (header included) produced elsewhere, e.g. by
+/// another server or an offline recorder. It is validated and ingested like
+/// a live upload: embedded assets are moved to the asset cache, and the
+/// recording's summary is stored for listings.
+async fn handle_import_recording(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    body: Body,
+) -> Response {
+    let site_origin = match query.site.as_deref().map(page_of_url) {
+        Some(Some((origin, _))) => Some(origin),
+        Some(None) => return (StatusCode::BAD_REQUEST, "Invalid origin").into_response(),
+        None => None,
+    };
+    let stream = body.into_data_stream().map_err(std::io::Error::other);
+    let result = state
+        .save_recording_stream_with_site_and_path(
+            StreamReader::new(stream),
+            site_origin.as_deref(),
+            None,
+            query.subdir.map(std::path::PathBuf::from),
+            query.filename,
+        )
+        .await;
+    match result {
+        Ok(id) => {
+            info!("📥 Imported recording {}", id);
+            (StatusCode::CREATED, json_response(&serde_json::json!({ "id": id.as_str() }))).into_response()
+        }
+        Err(e) => {
+            let status = match e {
+                StorageError::Header(_) | StorageError::Frame(_) | StorageError::InvalidId(_) => {
+                    StatusCode::BAD_REQUEST
+                }
+                StorageError::InUse(_) => StatusCode::CONFLICT,
+                StorageError::InsufficientSpace { .. } => StatusCode::INSUFFICIENT_STORAGE,
+                StorageError::Refused { .. } | StorageError::NotSampled { .. } => StatusCode::FORBIDDEN,
+                _ => {
+                    error!("❌ Failed to import recording: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (status, format!("Failed to import recording: {}", e)).into_response()
         }
     }
 }
 
+/// Handshake query parameters for `/ws/record`
+#[derive(Debug, Default, Deserialize)]
+struct RecordHandshake {
+    /// Preferred batch size (`recording_handler::BATCH_BYTES_PARAM`)
+    batch_bytes: Option<usize>,
+}
+
 async fn handle_websocket_record(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    Query(handshake): Query<RecordHandshake>,
+    ClientIp(client_ip): ClientIp,
     headers: axum::http::HeaderMap,
 ) -> impl IntoResponse {
     info!("📡 WebSocket upgrade request for /ws/record");
@@ -77,24 +401,37 @@ async fn handle_websocket_record(
         debug!("User-Agent: {}", ua);
     }
     
-    ws.on_upgrade(move |socket| {
-        handle_websocket_recording(
-            socket,
-            state,
-            user_agent,
-            RecordingConfig {
-                max_size: 100 * 1024 * 1024, // 100MB
-                subdir: None,
-                custom_filename: None,
-            },
-            RecordingHooks {
-                on_start: None,
-                on_metadata: None,
-                on_complete: None,
-                on_error: None,
-            },
-        )
-    })
+    // Refuse before upgrading, so the recorder sees a distinct status
+    if let Err(e) = state.check_free_space() {
+        warn!("❌ Refusing recording: {}", e);
+        return (StatusCode::INSUFFICIENT_STORAGE, e.to_string()).into_response();
+    }
+
+    let batch_bytes = negotiate_batch_bytes(handshake.batch_bytes);
+    let mut response = ws
+        .on_upgrade(move |socket| {
+            handle_websocket_recording(
+                socket,
+                state,
+                user_agent,
+                RecordingConfig {
+                    max_size: 100 * 1024 * 1024, // 100MB
+                    subdir: None,
+                    custom_filename: None,
+                    batch_bytes,
+                    client_ip,
+                },
+                RecordingHooks {
+                    on_start: None,
+                    on_metadata: None,
+                    on_complete: None,
+                    on_error: None,
+                },
+            )
+        })
+        .into_response();
+    response.headers_mut().insert(BATCH_BYTES_HEADER, batch_bytes.into());
+    response
 }
 
 
@@ -110,8 +447,12 @@ async fn handle_options() -> impl IntoResponse {
         .unwrap()
 }
 
-async fn handle_list_recordings(State(state): State<AppState>) -> impl IntoResponse {
-    match state.list_recordings(None) {
+/// `GET /recordings`: every recording, subdirectories included
+async fn handle_list_recordings(
+    State(state): State<AppState>,
+    Query(filter): Query<RecordingFilter>,
+) -> impl IntoResponse {
+    match state.filter_recordings(&filter).await {
         Ok(recordings) => {
             let json = serde_json::to_string(&recordings).unwrap_or_else(|_| "[]".to_string());
 
@@ -123,6 +464,7 @@ async fn handle_list_recordings(State(state): State<AppState>) -> impl IntoRespo
                 .unwrap()
                 .into_response()
         }
+        Err(StorageError::CollectionNotFound(_)) => (StatusCode::NOT_FOUND, "Collection not found").into_response(),
         Err(_) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to list recordings",
@@ -131,103 +473,1353 @@ async fn handle_list_recordings(State(state): State<AppState>) -> impl IntoRespo
     }
 }
 
-async fn handle_get_recording(
+/// Build a JSON response with the same headers as the other API endpoints
+fn json_response<T: Serialize>(value: &T) -> Response {
+    match serde_json::to_string(value) {
+        Ok(json) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .body(axum::body::Body::from(json))
+            .unwrap(),
+        Err(e) => {
+            error!("Failed to serialize response: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to serialize response").into_response()
+        }
+    }
+}
+
+async fn handle_list_collections(State(state): State<AppState>) -> Response {
+    match state.metadata_store.list_collections().await {
+        Ok(collections) => json_response(&collections),
+        Err(e) => {
+            error!("Failed to list collections: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list collections").into_response()
+        }
+    }
+}
+
+/// The collection as a response, or why it couldn't be had
+fn collection_response(name: &str, collection: Result<Collection, StorageError>) -> Response {
+    match collection {
+        Ok(collection) => json_response(&collection),
+        Err(StorageError::CollectionNotFound(_)) => (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to update collection {}: {}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update collection").into_response()
+        }
+    }
+}
+
+async fn handle_get_collection(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    collection_response(&name, state.collection(&name).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionQuery {
+    description: Option<String>,
+}
+
+/// Create a collection, or replace its description
+async fn handle_save_collection(
     State(state): State<AppState>,
-    Path(filename): Path<String>,
+    Path(name): Path<String>,
+    Query(query): Query<CollectionQuery>,
+) -> Response {
+    if !valid_name(&name) {
+        let message = format!("Collection names must be 1 to {} bytes", MAX_COLLECTION_NAME_BYTES);
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    if let Err(e) = state.metadata_store.save_collection(&name, query.description.as_deref()).await {
+        return collection_response(&name, Err(e.into()));
+    }
+    collection_response(&name, state.collection(&name).await)
+}
+
+async fn handle_delete_collection(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.metadata_store.delete_collection(&name).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Err(e) => collection_response(&name, Err(e.into())),
+    }
+}
+
+async fn handle_add_to_collection(
+    State(state): State<AppState>,
+    Path((name, filename)): Path<(String, RecordingId)>,
+) -> Response {
+    collection_response(&name, state.set_collection_member(&name, &filename, true).await)
+}
+
+async fn handle_remove_from_collection(
+    State(state): State<AppState>,
+    Path((name, filename)): Path<(String, RecordingId)>,
+) -> Response {
+    collection_response(&name, state.set_collection_member(&name, &filename, false).await)
+}
+
+#[derive(Debug, Deserialize)]
+struct DiffQuery {
+    a: RecordingId,
+    b: RecordingId,
+    mode: Option<AlignmentMode>,
+    interval_ms: Option<u64>,
+    max_points: Option<usize>,
+    /// Comma-separated attribute names to ignore
+    ignore_attributes: Option<String>,
+}
+
+async fn handle_diff_recordings(
+    State(state): State<AppState>,
+    Query(query): Query<DiffQuery>,
 ) -> impl IntoResponse {
-    if !state.recording_exists(&filename) {
-        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    for filename in [&query.a, &query.b] {
+        if !state.recording_exists(filename).await {
+            return (StatusCode::NOT_FOUND, format!("Recording not found: {}", filename)).into_response();
+        }
     }
 
-    // Generate PlaybackConfig frame before moving state
-    let storage_type = state.asset_file_store.storage_type().to_string();
-    let config_json = match state.asset_file_store.config_json() {
-        Ok(json) => json,
+    let defaults = DiffOptions::default();
+    let options = DiffOptions {
+        mode: query.mode.unwrap_or(defaults.mode),
+        // Each point copies both documents, so keep requests to what the server can hold
+        interval_ms: query.interval_ms.unwrap_or(defaults.interval_ms).clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS),
+        max_points: query.max_points.unwrap_or(defaults.max_points).min(MAX_POINTS),
+        ignore_attributes: query
+            .ignore_attributes
+            .map(|attrs| attrs.split(',').map(|a| a.trim().to_string()).collect())
+            .unwrap_or_default(),
+        ..defaults
+    };
+
+    let readers = tokio::try_join!(
+        state.open_recording_reader(&query.a),
+        state.open_recording_reader(&query.b)
+    );
+    let (mut reader_a, mut reader_b) = match readers {
+        Ok(readers) => readers,
         Err(e) => {
-            warn!("Failed to generate config_json: {}", e);
-            serde_json::json!({}).to_string()
+            error!("Failed to open recordings for diff: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
         }
     };
-    
-    // Check if recording is live and get latest timestamp
-    let is_live = state.is_recording_active(&filename);
-    let latest_timestamp = if is_live {
-        state.get_latest_timestamp(&filename)
-    } else {
-        None
+
+    match diff_recordings(&mut reader_a, &mut reader_b, &options).await {
+        Ok(diff) => json_response(&diff),
+        Err(e) => {
+            warn!("Failed to diff {} and {}: {}", query.a, query.b, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to parse recording: {}", e)).into_response()
+        }
+    }
+}
+
+/// A recording as listed, with what is stored about it beyond the listing
+#[derive(Debug, Serialize)]
+struct RecordingDetails {
+    #[serde(flatten)]
+    info: RecordingInfo,
+    comments: Vec<RecordingComment>,
+    /// How the recording was derived from others, oldest step first
+    provenance: Vec<Provenance>,
+}
+
+async fn handle_get_info(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    let info = match state.recording_info(&filename).await {
+        Ok(info) => info,
+        Err(StorageError::NotFound(_)) => return (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to list recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list recording").into_response();
+        }
     };
-    
-    let playback_config = Frame::PlaybackConfig(PlaybackConfigData {
-        storage_type,
-        config_json,
-        is_live,
-        latest_timestamp,
-    });
-    
-    match state.get_recording_stream(&filename).await {
-        Ok(recording_stream) => {
-            // Encode PlaybackConfig frame to bytes
-            let mut config_buffer = Vec::new();
-            let mut config_writer = FrameWriter::new(Cursor::new(&mut config_buffer));
-            if let Err(e) = config_writer.write_frame(&playback_config) {
-                error!("Failed to encode PlaybackConfig frame: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate playback config").into_response();
-            }
-            drop(config_writer);
-            
-            // Create a stream that first yields the PlaybackConfig frame, then the recording
-            let config_stream = stream::once(async move { Ok::<_, std::io::Error>(config_buffer.into()) });
-            let recording_bytes = ReaderStream::new(recording_stream);
-            let combined_stream = config_stream.chain(recording_bytes.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
-            
-            let body = axum::body::Body::from_stream(combined_stream);
+    let comments = match state.metadata_store.list_comments(filename.as_str()).await {
+        Ok(comments) => comments,
+        Err(e) => {
+            error!("Failed to load comments of {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load comments").into_response();
+        }
+    };
+    match state.recording_provenance(&filename).await {
+        Ok(provenance) => json_response(&RecordingDetails {
+            info,
+            comments,
+            provenance,
+        }),
+        Err(e) => {
+            error!("Failed to load the provenance of {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load provenance").into_response()
+        }
+    }
+}
 
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, "application/octet-stream")
-                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                .header(header::CACHE_CONTROL, "no-cache") // Prevent caching for live streams
-                .body(body)
-                .unwrap()
-                .into_response()
+/// Duration, frame counts, viewport, initial URL and asset count, read from
+/// the recording or, once it has finished, from the metadata store
+async fn handle_get_meta(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    let info = match state.recording_info(&filename).await {
+        Ok(info) => info,
+        Err(StorageError::NotFound(_)) => return (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to list recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list recording").into_response();
         }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to read recording",
-        )
-            .into_response(),
+    };
+    if !info.is_active {
+        match state.metadata_store.get_recording_meta(filename.as_str()).await {
+            Ok(Some(meta)) if meta.size == info.size => return json_response(&meta),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to load the cached metadata of {}: {}", filename, e),
+        }
+    }
+
+    let mut reader = match state.open_recording_reader(&filename).await {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!("Failed to open recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
+        }
+    };
+    let meta = match extract_recording_meta(&mut reader, info.size).await {
+        Ok(meta) => meta,
+        Err(e) => {
+            warn!("Failed to extract metadata for {}: {}", filename, e);
+            return (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to parse recording: {}", e)).into_response();
+        }
+    };
+    // An active recording is still growing; only cache it once finished
+    if !info.is_active
+        && let Err(e) = state.metadata_store.store_recording_meta(filename.as_str(), &meta).await
+    {
+        warn!("Failed to cache the metadata of {}: {}", filename, e);
     }
+    json_response(&meta)
 }
 
-async fn handle_get_asset(
+#[derive(Debug, Deserialize)]
+struct CommentQuery {
+    /// Milliseconds of recorded time since the first timestamp, as in the timeline
+    offset_ms: u64,
+    author: Option<String>,
+    text: String,
+}
+
+async fn handle_add_comment(
     State(state): State<AppState>,
-    Path(random_id): Path<String>,
+    Path(filename): Path<RecordingId>,
+    Query(query): Query<CommentQuery>,
+) -> Response {
+    let text = query.text.trim();
+    if text.is_empty() || text.len() > MAX_COMMENT_BYTES {
+        let message = format!("Comment text must be 1 to {} bytes", MAX_COMMENT_BYTES);
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    match state.add_comment(&filename, query.offset_ms, query.author, text.to_string()).await {
+        Ok(comment) => (StatusCode::CREATED, json_response(&comment)).into_response(),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to store comment on {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store comment").into_response()
+        }
+    }
+}
+
+async fn handle_get_timeline(
+    State(state): State<AppState>,
+    Path(filename): Path<RecordingId>,
 ) -> impl IntoResponse {
-    // Resolve random_id to SHA-256 (storage key)
-    let sha256 = match state.metadata_store.resolve_random_id(&random_id).await {
-        Ok(Some(sha256)) => sha256,
-        Ok(None) => return (StatusCode::NOT_FOUND, "Asset not found").into_response(),
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+
+    let mut reader = match state.open_recording_reader(&filename).await {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!("Failed to open recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
+        }
     };
-    
-    // Get asset data using SHA-256 (CAS key)
-    let data = match state.asset_file_store.get(&sha256).await {
-        Ok(data) => data,
-        Err(_) => return (StatusCode::NOT_FOUND, "Asset not found").into_response(),
+
+    match extract_timeline(&mut reader).await {
+        Ok(timeline) => json_response(&timeline),
+        Err(e) => {
+            warn!("Failed to extract timeline for {}: {}", filename, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to parse recording: {}", e)).into_response()
+        }
+    }
+}
+
+/// The events of every recording linked to a session (its tabs and windows),
+/// interleaved by timestamp
+async fn handle_get_session_timeline(State(state): State<AppState>, Path(session_id): Path<String>) -> Response {
+    let recording_ids = match state.metadata_store.list_session_recordings(&session_id).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!("Failed to list the recordings of session {}: {}", session_id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list session recordings").into_response();
+        }
     };
 
-    // Get MIME type from metadata using random_id
-    let mime = match state.metadata_store.get_asset_metadata(&random_id).await {
-        Ok(Some((mime_type, _))) => mime_type,
-        Ok(None) | Err(_) => "application/octet-stream".to_string(),
+    let mut session = SessionTimeline::new(&session_id);
+    for id in recording_ids {
+        // Skip recordings deleted since they were linked
+        let Ok(filename) = RecordingId::new(id) else { continue };
+        if !state.recording_exists(&filename).await {
+            continue;
+        }
+        let mut reader = match state.open_recording_reader(&filename).await {
+            Ok(reader) => reader,
+            Err(e) => {
+                error!("Failed to open recording {}: {}", filename, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
+            }
+        };
+        let (timeline, timestamps) = match extract_timeline_with_timestamps(&mut reader).await {
+            Ok(extracted) => extracted,
+            Err(e) => {
+                warn!("Failed to extract timeline for {}: {}", filename, e);
+                return (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to parse recording {}: {}", filename, e))
+                    .into_response();
+            }
+        };
+        // Each tab's recorder has its own clock
+        let skew = playback_clock_skew(&state, &filename).await;
+        session.add_recording(filename.as_str(), timeline, timestamps, |timestamp| match &skew {
+            Some(skew) => skew.to_server_time(timestamp),
+            None => timestamp,
+        });
+    }
+
+    if session.recordings.is_empty() {
+        return (StatusCode::NOT_FOUND, "Session not found").into_response();
+    }
+    json_response(&session)
+}
+
+async fn handle_get_text_entries(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+    match state.metadata_store.list_text_entries(filename.as_str()).await {
+        Ok(entries) => json_response(&entries),
+        Err(e) => {
+            error!("Failed to load the text entries of {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load text entries").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CanvasQuery {
+    /// Timestamp (ms since the epoch) to materialize the canvas at; defaults
+    /// to the end of the recording
+    at: Option<u64>,
+}
+
+/// The full image of a canvas, with any delta frames applied
+async fn handle_get_canvas(
+    State(state): State<AppState>,
+    Path((filename, node_id)): Path<(RecordingId, u32)>,
+    Query(query): Query<CanvasQuery>,
+) -> impl IntoResponse {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+
+    let mut reader = match state.open_recording_reader(&filename).await {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!("Failed to open recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
+        }
     };
 
-    Response::builder()
+    match materialize_canvas(&mut reader, node_id, query.at).await {
+        Ok(Some(snapshot)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, snapshot.mime_type)
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .body(Body::from(snapshot.data))
+            .unwrap(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Canvas not found").into_response(),
+        Err(e @ CanvasError::Encode(_)) => {
+            error!("Failed to encode canvas {} of {}: {}", node_id, filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode canvas").into_response()
+        }
+        Err(e) => {
+            warn!("Failed to materialize canvas {} of {}: {}", node_id, filename, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to materialize canvas: {}", e)).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    /// Site origin, e.g. `https://example.com`
+    site: String,
+    /// Page path (defaults to `/`)
+    path: Option<String>,
+}
+
+async fn handle_get_heatmap(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    let path = query.path.as_deref().unwrap_or("/");
+
+    match state.metadata_store.get_click_heatmap(site, path).await {
+        Ok(buckets) => json_response(&ClickHeatmap::new(site, path, buckets)),
+        Err(e) => {
+            error!("Failed to load heatmap for {}{}: {}", site, path, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load heatmap").into_response()
+        }
+    }
+}
+
+async fn handle_get_scroll_report(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    let path = query.path.as_deref().unwrap_or("/");
+
+    match state.metadata_store.list_scroll_depths(site, path).await {
+        Ok(depths) => json_response(&ScrollReport::new(site, path, &depths)),
+        Err(e) => {
+            error!("Failed to load scroll depths for {}{}: {}", site, path, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load scroll depths").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionsQuery {
+    site: String,
+}
+
+async fn handle_list_sessions(
+    State(state): State<AppState>,
+    Query(query): Query<SessionsQuery>,
+) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    match state.metadata_store.list_session_metrics(site).await {
+        Ok(sessions) => json_response(&sessions),
+        Err(e) => {
+            error!("Failed to list sessions for {}: {}", site, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list sessions").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TextEntriesQuery {
+    site: String,
+    /// Only entries whose value contains this, ignoring ASCII case
+    contains: Option<String>,
+}
+
+/// Text entered during a session, with the recording it was entered in
+#[derive(Debug, Serialize)]
+struct TextEntryMatch {
+    recording_id: String,
+    #[serde(flatten)]
+    entry: TextEntered,
+}
+
+async fn handle_search_text_entries(
+    State(state): State<AppState>,
+    Query(query): Query<TextEntriesQuery>,
+) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    match state.metadata_store.search_text_entries(site, query.contains.as_deref()).await {
+        Ok(entries) => {
+            let matches: Vec<TextEntryMatch> = entries
+                .into_iter()
+                .map(|(recording_id, entry)| TextEntryMatch { recording_id, entry })
+                .collect();
+            json_response(&matches)
+        }
+        Err(e) => {
+            error!("Failed to search text entries for {}: {}", site, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to search text entries").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FunnelQuery {
+    site: String,
+    /// Comma-separated steps: `page:/path` or `annotation:name`
+    steps: String,
+}
+
+async fn handle_get_funnel(
+    State(state): State<AppState>,
+    Query(query): Query<FunnelQuery>,
+) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    let steps: Option<Vec<FunnelStep>> = query.steps.split(',').map(FunnelStep::parse).collect();
+    let steps = match steps {
+        Some(steps) if !steps.is_empty() => steps,
+        _ => return (StatusCode::BAD_REQUEST, "Invalid funnel steps").into_response(),
+    };
+
+    match state.metadata_store.list_session_events(site).await {
+        Ok(sessions) => {
+            let result = evaluate_funnel(&steps, sessions.iter().map(|(_, events)| events.as_slice()));
+            json_response(&result)
+        }
+        Err(e) => {
+            error!("Failed to load session events for {}: {}", site, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to evaluate funnel").into_response()
+        }
+    }
+}
+
+/// `GET /admin/storage`: space used by recordings, assets and the database
+/// Storage backend health; 503 if any backend is failing
+async fn handle_readyz(State(state): State<AppState>) -> Response {
+    let health = state.health().await;
+    let status = if health.is_ready() {
+        StatusCode::OK
+    } else {
+        warn!("🩺 Storage is not ready: {:?}", health);
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, json_response(&health)).into_response()
+}
+
+async fn handle_get_storage_usage(State(state): State<AppState>) -> impl IntoResponse {
+    match state.disk_usage().await {
+        Ok(usage) => json_response(&usage),
+        Err(e) => {
+            error!("Failed to measure storage usage: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to measure storage usage").into_response()
+        }
+    }
+}
+
+/// Rewrap every recording's data key with the current master key
+async fn handle_rotate_keys(State(state): State<AppState>) -> impl IntoResponse {
+    match state.rotate_recording_keys().await {
+        Ok(rotation) => json_response(&rotation),
+        Err(StorageError::Encryption(EncryptionError::NoMasterKey)) => {
+            (StatusCode::CONFLICT, "Recording encryption is not enabled").into_response()
+        }
+        Err(e) => {
+            error!("Failed to rotate recording keys: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to rotate recording keys").into_response()
+        }
+    }
+}
+
+/// Who is asking for a compliance action and why, for the audit log
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    actor: Option<String>,
+    reason: Option<String>,
+}
+
+async fn handle_delete_recording(
+    State(state): State<AppState>,
+    Path(filename): Path<RecordingId>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    match state.delete_recording(&filename, query.actor, query.reason).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e @ (StorageError::InUse(_) | StorageError::OnHold(_))) => {
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!("Failed to delete recording {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete recording").into_response()
+        }
+    }
+}
+
+/// Force-complete a recording left active by a stuck session
+async fn handle_finalize_recording(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    match state.finalize_recording(&filename, FINALIZE_GRACE).await {
+        Ok(finalization) => json_response(&finalization),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e @ (StorageError::NotActive(_) | StorageError::InUse(_))) => {
+            (StatusCode::CONFLICT, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!("Failed to finalize recording {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to finalize recording").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HoldStatus {
+    recording_id: String,
+    held: bool,
+    /// Whether this request changed the hold (absent when only reading it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed: Option<bool>,
+}
+
+async fn handle_get_hold(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> impl IntoResponse {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+    match state.is_on_legal_hold(&filename).await {
+        Ok(held) => json_response(&HoldStatus {
+            recording_id: filename.to_string(),
+            held,
+            changed: None,
+        }),
+        Err(e) => {
+            error!("Failed to read legal hold of {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read legal hold").into_response()
+        }
+    }
+}
+
+async fn handle_apply_hold(
+    State(state): State<AppState>,
+    Path(filename): Path<RecordingId>,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    set_hold(&state, filename, true, query).await
+}
+
+async fn handle_release_hold(
+    State(state): State<AppState>,
+    Path(filename): Path<RecordingId>,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    set_hold(&state, filename, false, query).await
+}
+
+async fn set_hold(state: &AppState, filename: RecordingId, held: bool, query: AuditQuery) -> Response {
+    match state.set_legal_hold(&filename, held, query.actor, query.reason).await {
+        Ok(changed) => json_response(&HoldStatus {
+            recording_id: filename.to_string(),
+            held,
+            changed: Some(changed),
+        }),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to set legal hold of {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to set legal hold").into_response()
+        }
+    }
+}
+
+async fn handle_list_holds(State(state): State<AppState>) -> impl IntoResponse {
+    match state.metadata_store.list_legal_holds().await {
+        Ok(holds) => json_response(&holds),
+        Err(e) => {
+            error!("Failed to list legal holds: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list legal holds").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditLogQuery {
+    /// Only entries of this recording
+    recording: Option<String>,
+}
+
+async fn handle_list_audit_events(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> impl IntoResponse {
+    match state.metadata_store.list_audit_events(query.recording.as_deref()).await {
+        Ok(events) => json_response(&events),
+        Err(e) => {
+            error!("Failed to list audit events: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list audit events").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErasureQuery {
+    /// The identifier recordings of the subject are tagged with
+    subject: String,
+    #[serde(default)]
+    mode: ErasureMode,
+    actor: Option<String>,
+    reason: Option<String>,
+}
+
+async fn handle_erase_subject(State(state): State<AppState>, Query(query): Query<ErasureQuery>) -> impl IntoResponse {
+    if query.subject.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "A subject is required").into_response();
+    }
+    match state.erase_subject(&query.subject, query.mode, query.actor, query.reason).await {
+        Ok(report) => json_response(&report),
+        Err(e) => {
+            error!("Failed to erase subject: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to erase subject").into_response()
+        }
+    }
+}
+
+async fn handle_collect_assets(State(state): State<AppState>) -> impl IntoResponse {
+    match state.collect_unreferenced_assets().await {
+        Ok(collection) => json_response(&collection),
+        Err(e) => {
+            error!("Failed to collect unreferenced assets: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to collect unreferenced assets").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WarmQuery {
+    /// Site origin, e.g. `https://example.com`
+    site: String,
+}
+
+async fn handle_warm_assets(State(state): State<AppState>, Query(query): Query<WarmQuery>) -> impl IntoResponse {
+    match state.warm_site_assets(&query.site).await {
+        Ok(report) => json_response(&report),
+        Err(e @ StorageError::Refused { .. }) => (StatusCode::FORBIDDEN, e.to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to warm the asset cache for {}: {}", query.site, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to warm the asset cache").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlHistoryQuery {
+    url: String,
+}
+
+async fn handle_url_history(State(state): State<AppState>, Query(query): Query<UrlHistoryQuery>) -> impl IntoResponse {
+    match state.metadata_store.get_url_versions(&query.url).await {
+        Ok(versions) => json_response(&versions),
+        Err(e) => {
+            error!("Failed to get the version history of {}: {}", query.url, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get URL history").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetChangesQuery {
+    site: String,
+    from: chrono::DateTime<chrono::Utc>,
+    /// Defaults to now
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+async fn handle_asset_changes(State(state): State<AppState>, Query(query): Query<AssetChangesQuery>) -> impl IntoResponse {
+    let site = query.site.trim_end_matches('/');
+    let to = query.to.unwrap_or_else(chrono::Utc::now);
+    if query.from > to {
+        return (StatusCode::BAD_REQUEST, "from is after to").into_response();
+    }
+    match state.asset_changes(site, query.from, to).await {
+        Ok(changes) => json_response(&changes),
+        Err(e) => {
+            error!("Failed to compare the assets of {}: {}", site, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to compare assets").into_response()
+        }
+    }
+}
+
+async fn handle_list_asset_misses(State(state): State<AppState>) -> impl IntoResponse {
+    match state.metadata_store.list_asset_misses().await {
+        Ok(misses) => json_response(&misses),
+        Err(e) => {
+            error!("Failed to list missing assets: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list missing assets").into_response()
+        }
+    }
+}
+
+/// 404 when no replica is configured
+async fn handle_get_replica_status(State(state): State<AppState>) -> impl IntoResponse {
+    match &state.replication {
+        Some(replication) => json_response(&replication.status()),
+        None => (StatusCode::NOT_FOUND, "No replica is configured").into_response(),
+    }
+}
+
+async fn handle_reconcile_replica(State(state): State<AppState>) -> impl IntoResponse {
+    match state.reconcile_replica().await {
+        Ok(Some(report)) => json_response(&report),
+        Ok(None) => (StatusCode::NOT_FOUND, "No replica is configured").into_response(),
+        Err(e) => {
+            error!("Failed to reconcile the replica: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to reconcile the replica").into_response()
+        }
+    }
+}
+
+async fn handle_list_policies(State(state): State<AppState>) -> impl IntoResponse {
+    match state.metadata_store.list_origin_policies().await {
+        Ok(policies) => json_response(&policies),
+        Err(e) => {
+            error!("Failed to list recording policies: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list recording policies").into_response()
+        }
+    }
+}
+
+/// A recording policy; fields left out take the values that record everything
+#[derive(Debug, Deserialize)]
+struct PolicyQuery {
+    /// Site origin, or `*` for the default policy
+    origin: String,
+    allowed: Option<bool>,
+    masking: Option<MaskingLevel>,
+    sample_rate: Option<f64>,
+    unsampled: Option<UnsampledAction>,
+    max_duration_seconds: Option<u64>,
+}
+
+/// `origin` as policies are keyed: `*`, or scheme, host and port only
+fn policy_origin(origin: &str) -> Option<String> {
+    if origin == DEFAULT_ORIGIN {
+        return Some(origin.to_string());
+    }
+    page_of_url(origin).map(|(origin, _)| origin)
+}
+
+async fn handle_set_policy(State(state): State<AppState>, Query(query): Query<PolicyQuery>) -> impl IntoResponse {
+    let Some(origin) = policy_origin(&query.origin) else {
+        return (StatusCode::BAD_REQUEST, "Invalid origin").into_response();
+    };
+    let mut policy = OriginPolicy::new(origin);
+    policy.allowed = query.allowed.unwrap_or(policy.allowed);
+    policy.masking = query.masking.unwrap_or(policy.masking);
+    policy.sample_rate = query.sample_rate.unwrap_or(policy.sample_rate);
+    policy.unsampled = query.unsampled.unwrap_or(policy.unsampled);
+    policy.max_duration_ms = query.max_duration_seconds.map(|seconds| seconds * 1000);
+    if !(0.0..=1.0).contains(&policy.sample_rate) {
+        return (StatusCode::BAD_REQUEST, "sample_rate must be between 0 and 1").into_response();
+    }
+    match state.metadata_store.set_origin_policy(&policy).await {
+        Ok(()) => {
+            info!("📜 Recording policy for {} set", policy.origin);
+            json_response(&policy)
+        }
+        Err(e) => {
+            error!("Failed to set recording policy: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to set recording policy").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PolicyOriginQuery {
+    origin: String,
+}
+
+async fn handle_delete_policy(
+    State(state): State<AppState>,
+    Query(query): Query<PolicyOriginQuery>,
+) -> impl IntoResponse {
+    let Some(origin) = policy_origin(&query.origin) else {
+        return (StatusCode::BAD_REQUEST, "Invalid origin").into_response();
+    };
+    match state.metadata_store.delete_origin_policy(&origin).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "No policy for this origin").into_response(),
+        Err(e) => {
+            error!("Failed to delete recording policy: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete recording policy").into_response()
+        }
+    }
+}
+
+async fn handle_list_site_profiles(State(state): State<AppState>) -> impl IntoResponse {
+    match state.metadata_store.list_site_profiles().await {
+        Ok(profiles) => json_response(&profiles),
+        Err(e) => {
+            error!("Failed to list site profiles: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list site profiles").into_response()
+        }
+    }
+}
+
+async fn handle_set_site_profile(State(state): State<AppState>, Json(profile): Json<SiteProfile>) -> Response {
+    let Some(profile) = profile.normalized() else {
+        return (StatusCode::BAD_REQUEST, "A site profile needs a valid site origin and at least one alias")
+            .into_response();
+    };
+    match state.set_site_profile(&profile).await {
+        Ok(()) => {
+            info!("🧭 Site profile for {} set: {:?}", profile.site, profile.aliases);
+            json_response(&profile)
+        }
+        Err(e @ StorageError::SiteConflict { .. }) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+        Err(e) => {
+            error!("Failed to set site profile: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to set site profile").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteQuery {
+    site: String,
+}
+
+async fn handle_delete_site_profile(State(state): State<AppState>, Query(query): Query<SiteQuery>) -> Response {
+    let Some((site, _)) = page_of_url(&query.site) else {
+        return (StatusCode::BAD_REQUEST, "Invalid origin").into_response();
+    };
+    match state.metadata_store.delete_site_profile(&site).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "No profile for this site").into_response(),
+        Err(e) => {
+            error!("Failed to delete site profile: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete site profile").into_response()
+        }
+    }
+}
+
+async fn handle_start_bulk_job(State(state): State<AppState>, Json(request): Json<BulkRequest>) -> Response {
+    if let Err(reason) = request.check() {
+        return (StatusCode::BAD_REQUEST, reason).into_response();
+    }
+    match start_bulk_job(&state, request).await {
+        Ok(job) => (StatusCode::ACCEPTED, json_response(&job)).into_response(),
+        Err(StorageError::CollectionNotFound(_)) => (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Err(e) => {
+            error!("Failed to start bulk job: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to start bulk job").into_response()
+        }
+    }
+}
+
+async fn handle_get_bulk_job(State(state): State<AppState>, Path(job): Path<String>) -> Response {
+    match state.job(&job).await {
+        Ok(job) => match job.and_then(|job| BulkJob::from_job(&state, &job)) {
+            Some(job) => json_response(&job),
+            None => (StatusCode::NOT_FOUND, "Bulk job not found").into_response(),
+        },
+        Err(e) => {
+            error!("Failed to get bulk job {}: {}", job, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get bulk job").into_response()
+        }
+    }
+}
+
+async fn handle_submit_job(State(state): State<AppState>, Json(request): Json<JobRequest>) -> Response {
+    if let Err(reason) = request.check() {
+        return (StatusCode::BAD_REQUEST, reason).into_response();
+    }
+    match submit_job(&state, request).await {
+        Ok(job) => (StatusCode::ACCEPTED, json_response(&job)).into_response(),
+        Err(StorageError::CollectionNotFound(_)) => (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Err(e) => {
+            error!("Failed to submit job: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to submit job").into_response()
+        }
+    }
+}
+
+async fn handle_get_job(State(state): State<AppState>, Path(job): Path<String>) -> Response {
+    match state.job(&job).await {
+        Ok(Some(job)) => json_response(&job),
+        Ok(None) => (StatusCode::NOT_FOUND, "Job not found").into_response(),
+        Err(e) => {
+            error!("Failed to get job {}: {}", job, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get job").into_response()
+        }
+    }
+}
+
+async fn handle_list_failed_recordings(State(state): State<AppState>) -> Response {
+    match state.list_failed_recordings().await {
+        Ok(failed) => json_response(&failed),
+        Err(e) => {
+            error!("Failed to list failed recordings: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list failed recordings").into_response()
+        }
+    }
+}
+
+async fn handle_inspect_failed_recording(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    match state.inspect_failed_recording(&filename).await {
+        Ok(report) => json_response(&report),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Failed recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to inspect failed recording {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to inspect failed recording").into_response()
+        }
+    }
+}
+
+/// Download a failed recording's bytes as written (decrypted)
+async fn handle_get_failed_recording(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    match state.open_failed_recording(&filename).await {
+        Ok(reader) => {
+            let name = filename.as_str().replace('/', "_");
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header(
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.failed\"", name),
+                )
+                .body(Body::from_stream(ReaderStream::with_capacity(reader, STREAM_CHUNK_SIZE)))
+                .unwrap()
+                .into_response()
+        }
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Failed recording not found").into_response(),
+        Err(e) => {
+            error!("Failed to read failed recording {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read failed recording").into_response()
+        }
+    }
+}
+
+async fn handle_repair_failed_recording(State(state): State<AppState>, Path(filename): Path<RecordingId>) -> Response {
+    match state.repair_failed_recording(&filename).await {
+        Ok(report) => (StatusCode::CREATED, json_response(&report)).into_response(),
+        Err(StorageError::NotFound(_)) => (StatusCode::NOT_FOUND, "Failed recording not found").into_response(),
+        Err(StorageError::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            (StatusCode::CONFLICT, "A recording with this id already exists").into_response()
+        }
+        Err(StorageError::Frame(e)) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, format!("Nothing to salvage: {}", e)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to repair failed recording {}: {}", filename, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to repair failed recording").into_response()
+        }
+    }
+}
+
+/// Query parameters for `/ws/recording/{filename}`
+#[derive(Debug, Default, Deserialize)]
+struct ViewerQuery {
+    /// Name shown to the other viewers
+    viewer: Option<String>,
+}
+
+async fn handle_websocket_view(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(filename): Path<RecordingId>,
+    Query(query): Query<ViewerQuery>,
+) -> Response {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+    ws.on_upgrade(move |socket| crate::coview::handle_viewer(socket, state, filename, query.viewer))
+}
+
+/// The recorder's clock skew, if it is known and not zero
+///
+/// Playback serves Timestamp frames on the server's clock when it is.
+pub(crate) async fn playback_clock_skew(state: &AppState, filename: &RecordingId) -> Option<ClockSkew> {
+    match state.metadata_store.get_clock_skew(filename.as_str()).await {
+        Ok(skew) => skew.filter(|skew| skew.skew_ms != 0),
+        Err(e) => {
+            warn!("Failed to load clock skew of {}: {}", filename, e);
+            None
+        }
+    }
+}
+
+/// Stream a recording as newline-delimited JSON, one frame per line
+///
+/// Frames go through the playback transformer, so cached assets are returned
+/// as URLs rather than inline byte arrays.
+async fn handle_get_recording_ndjson(state: AppState, filename: RecordingId, at: Option<u64>) -> Response {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+    let skew = playback_clock_skew(&state, &filename).await;
+
+    let recording_stream = match playback_stream(&state, &filename, at, skew).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to open recording {}: {}", filename, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recording").into_response();
+        }
+    };
+    let reader = FrameReader::new(recording_stream, false).with_index(true);
+    let pinned_versions = match state.pinned_asset_versions(&filename).await {
+        Ok(pinned_versions) => pinned_versions,
+        Err(e) => {
+            warn!("Failed to load the asset versions pinned to {}: {}", filename, e);
+            Default::default()
+        }
+    };
+
+    // The reader is dropped after the first decode error so the stream ends there
+    let lines = stream::unfold((Some(reader), state, pinned_versions), move |(reader, state, pinned_versions)| async move {
+        let mut reader = reader?;
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Failed to decode frame for NDJSON stream: {}", e);
+                return Some((Err(e), (None, state, pinned_versions)));
+            }
+        };
+
+        // Keep the original asset frame if it cannot be resolved
+        let frame = match frame {
+            Frame::Asset(_)
+            | Frame::AssetReference(_)
+            | Frame::NewAdoptedStyleSheetReference(_)
+            | Frame::StyleSheetReplacedReference(_) => {
+                let transformer = state.playback_transformer().with_pinned_versions(&pinned_versions);
+                match transformer.transform_frame(frame.clone()).await {
+                    Ok(transformed) => transformed,
+                    Err(e) => {
+                        warn!("Failed to transform asset frame: {}", e);
+                        frame
+                    }
+                }
+            }
+            frame => frame,
+        };
+        let frame = match skew {
+            Some(skew) => skew.correct_frame(frame),
+            None => frame,
+        };
+
+        let line = serde_json::to_vec(&frame)
+            .map(|mut line| {
+                line.push(b'\n');
+                Bytes::from(line)
+            })
+            .map_err(std::io::Error::other);
+        Some((line, (Some(reader), state, pinned_versions)))
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(lines))
+        .unwrap()
+}
+
+/// Read size for streaming recordings and assets to clients; each response
+/// holds at most one chunk in memory at a time
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct PlaybackQuery {
+    /// Start at the last keyframe at or before this timestamp, on the
+    /// playback clock (ms since the epoch)
+    at: Option<u64>,
+}
+
+async fn handle_get_recording(
+    State(state): State<AppState>,
+    Path(filename): Path<String>,
+    Query(query): Query<PlaybackQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // `/recording/{id}.ndjson` serves the same recording as JSON lines
+    let (filename, ndjson) = match filename.strip_suffix(".ndjson") {
+        Some(filename) => (filename, true),
+        None => (filename.as_str(), false),
+    };
+    // Ids are storage keys, so anything that could escape the store is rejected here
+    let filename = match RecordingId::new(filename) {
+        Ok(filename) => filename,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    // Join the caller's trace if it sent one. The span covers opening the
+    // recording; the body keeps streaming after it ends.
+    let parent = headers
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceParent::parse);
+    let span = SpanBuilder::start(names::RECORDING_PLAYBACK, parent.as_ref())
+        .attribute(RECORDING_ID_ATTRIBUTE, filename.as_str());
+
+    let response = if ndjson {
+        handle_get_recording_ndjson(state.clone(), filename, query.at).await
+    } else {
+        handle_get_recording_binary(state.clone(), filename, query.at).await
+    };
+    state.observability.span_finished(&span.finish(response.status().is_success()));
+    response
+}
+
+/// The PlaybackConfig frame that starts playback of a recording, with the
+/// live recording's latest timestamp on the server's clock when `skew` is
+/// known, and the recording's scope if it has one
+pub(crate) async fn playback_config(state: &AppState, filename: &RecordingId, skew: Option<ClockSkew>) -> Frame {
+    let storage_type = state.asset_file_store.storage_type().to_string();
+    let config_json = match state.asset_file_store.config_json() {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Failed to generate config_json: {}", e);
+            serde_json::json!({}).to_string()
+        }
+    };
+    
+    // Check if recording is live and get latest timestamp
+    let is_live = state.is_recording_active(filename);
+    let latest_timestamp = if is_live {
+        state.get_latest_timestamp(filename)
+    } else {
+        None
+    };
+    let latest_timestamp = match skew {
+        Some(skew) => latest_timestamp.map(|timestamp| skew.to_server_time(timestamp)),
+        None => latest_timestamp,
+    };
+    
+    let root_selector = match state.metadata_store.get_recording_scope(filename.as_str()).await {
+        Ok(root_selector) => root_selector,
+        Err(e) => {
+            warn!("Failed to load the scope of {}: {}", filename, e);
+            None
+        }
+    };
+
+    Frame::PlaybackConfig(PlaybackConfigData {
+        storage_type,
+        config_json,
+        is_live,
+        latest_timestamp,
+        root_selector,
+    })
+}
+
+/// Open a recording for playback, from the keyframe before `at` if it has a
+/// seek index
+async fn playback_stream(
+    state: &AppState,
+    filename: &RecordingId,
+    at: Option<u64>,
+    skew: Option<ClockSkew>,
+) -> Result<Box<dyn tokio::io::AsyncRead + Unpin + Send>, StorageError> {
+    if let Some(at) = at {
+        // Stored timestamps are on the recorder's clock
+        let at = skew.map_or(at, |skew| at.saturating_add_signed(skew.skew_ms));
+        if let Some(stream) = state.clone().get_recording_stream_at(filename, at).await? {
+            return Ok(stream);
+        }
+    }
+    state.clone().get_recording_stream(filename).await
+}
+
+/// Stream a recording in the binary frame format, prefixed with a PlaybackConfig frame
+async fn handle_get_recording_binary(state: AppState, filename: RecordingId, at: Option<u64>) -> Response {
+    if !state.recording_exists(&filename).await {
+        return (StatusCode::NOT_FOUND, "Recording not found").into_response();
+    }
+
+    let skew = playback_clock_skew(&state, &filename).await;
+    let playback_config = playback_config(&state, &filename, skew).await;
+
+    match playback_stream(&state, &filename, at, skew).await {
+        Ok(recording_stream) => {
+            // Encode PlaybackConfig frame to bytes
+            let mut config_buffer = Vec::new();
+            let mut config_writer = FrameWriter::new(Cursor::new(&mut config_buffer));
+            if let Err(e) = config_writer.write_frame(&playback_config) {
+                error!("Failed to encode PlaybackConfig frame: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate playback config").into_response();
+            }
+            
+            // Create a stream that first yields the PlaybackConfig frame, then the recording
+            let config_stream = stream::once(async move { Ok::<_, std::io::Error>(config_buffer.into()) });
+            let body = match skew {
+                // Frames are re-encoded only when their timestamps change
+                Some(skew) => Body::from_stream(config_stream.chain(corrected_frames(recording_stream, skew))),
+                None => {
+                    let recording_bytes = ReaderStream::with_capacity(recording_stream, STREAM_CHUNK_SIZE);
+                    Body::from_stream(config_stream.chain(recording_bytes.map_err(std::io::Error::other)))
+                }
+            };
+
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .header(header::CACHE_CONTROL, "no-cache") // Prevent caching for live streams
+                .body(body)
+                .unwrap()
+                .into_response()
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to read recording",
+        )
+            .into_response(),
+    }
+}
+
+/// Re-encode a recording's frames with their timestamps on the server's clock
+fn corrected_frames(
+    recording_stream: Box<dyn tokio::io::AsyncRead + Unpin + Send>,
+    skew: ClockSkew,
+) -> impl futures::Stream<Item = std::io::Result<Bytes>> {
+    let reader = FrameReader::new(recording_stream, false).with_index(true);
+    // The reader is dropped after the first decode error so the stream ends there
+    stream::unfold(Some(reader), move |reader| async move {
+        let mut reader = reader?;
+        let frame = match reader.read_frame().await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Failed to decode frame for playback: {}", e);
+                return Some((Err(e), None));
+            }
+        };
+        let mut buffer = Vec::new();
+        let encoded = FrameWriter::new(&mut buffer)
+            .write_frame(&skew.correct_frame(frame))
+            .map(|()| Bytes::from(buffer));
+        Some((encoded, Some(reader)))
+    })
+}
+
+async fn handle_get_asset(
+    State(state): State<AppState>,
+    Extension(disposition): Extension<AssetDisposition>,
+    Path(random_id): Path<String>,
+) -> impl IntoResponse {
+    // Resolve random_id to SHA-256 (storage key)
+    let sha256 = match state.metadata_store.resolve_random_id(&random_id).await {
+        Ok(Some(sha256)) => sha256,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Asset not found").into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response(),
+    };
+    
+    // Stream asset data using SHA-256 (CAS key)
+    let reader = match state.asset_file_store.open(&sha256).await {
+        Ok(reader) => reader,
+        Err(_) => return (StatusCode::NOT_FOUND, "Asset not found").into_response(),
+    };
+
+    // Get MIME type and size from metadata using random_id
+    let (mime, size) = match state.metadata_store.get_asset_metadata(&random_id).await {
+        Ok(Some((mime_type, size))) => (mime_type, Some(size)),
+        Ok(None) | Err(_) => ("application/octet-stream".to_string(), None),
+    };
+
+    let mut response = Response::builder();
+    if let Some(size) = size {
+        response = response.header(header::CONTENT_LENGTH, size);
+    }
+    response
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, mime)
         .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
         .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
-        .body(axum::body::Body::from(data))
+        // Re-hosted pages, SVGs and scripts must not run in our origin
+        .header(header::CONTENT_SECURITY_POLICY, "sandbox")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_DISPOSITION, disposition.header_value())
+        .body(axum::body::Body::from_stream(ReaderStream::with_capacity(reader, STREAM_CHUNK_SIZE)))
         .unwrap()
         .into_response()
 }
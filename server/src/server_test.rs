@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use crate::{StorageState, AssetFileStore, MetadataStore};
+    use crate::{StorageState, AssetFileStore, MetadataStore, RecordingId};
     use crate::asset_cache::local::LocalBinaryStore;
     use crate::asset_cache::sqlite::SqliteMetadataStore;
+    use crate::recording_handler::{RecordingConfig, RecordingTransport};
     use domcorder_proto::{FileHeader, Frame, FrameReader, FrameWriter};
     use std::io::Cursor;
     use tempfile::TempDir;
@@ -24,46 +25,230 @@ mod tests {
             LocalBinaryStore::new(&assets_dir, "http://test.example".to_string()).unwrap(),
         );
         
-        let storage = StorageState::new(temp_dir.path().to_path_buf(), metadata_store, asset_file_store);
+        let storage = StorageState::new(temp_dir.path().to_path_buf(), metadata_store, asset_file_store).unwrap();
         (storage, temp_dir)
     }
 
-    #[test]
-    fn test_storage_save_and_list_recordings() {
+    /// Cache a one-byte asset `sha-<name>` for each name, used by https://example.com
+    async fn seed_site_assets(storage: &StorageState, names: &[&str]) {
+        use crate::asset_cache::{AssetMetadata, AssetUsageParams};
+
+        for name in names {
+            let sha256_hash = format!("sha-{}", name);
+            storage
+                .metadata_store
+                .store_asset_metadata(AssetMetadata {
+                    sha256_hash: sha256_hash.clone(),
+                    random_id: name.to_string(),
+                    size: 1,
+                    mime_type: "image/png".to_string(),
+                })
+                .await
+                .unwrap();
+            storage
+                .metadata_store
+                .register_asset_usage(AssetUsageParams {
+                    site_origin: "https://example.com".to_string(),
+                    url: format!("https://example.com/{}", name),
+                    sha256_hash,
+                    size: 1,
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    /// A frame as a recorder sends it
+    fn encode(frame: &Frame) -> Vec<u8> {
+        let mut data = Vec::new();
+        FrameWriter::new(&mut data).write_frame(frame).unwrap();
+        data
+    }
+
+    /// Recording handler configuration storing the recording as `filename`
+    fn recording_config(filename: &str) -> RecordingConfig {
+        RecordingConfig {
+            max_size: usize::MAX,
+            subdir: None,
+            custom_filename: Some(filename.to_string()),
+            batch_bytes: crate::recording_handler::DEFAULT_BATCH_BYTES,
+            client_ip: None,
+        }
+    }
+
+    /// Longest a [`ScriptedTransport`] waits for something to answer
+    const ANSWER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+    /// Frames a recorder sends back in answer to a frame from the server, if any
+    type Answer = Box<dyn FnMut(&Frame) -> Option<Vec<Frame>> + Send>;
+
+    /// Replays prepared chunks and keeps what the server sends back
+    struct ScriptedTransport {
+        incoming: std::collections::VecDeque<Vec<u8>>,
+        sent: std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+        answer: Option<Answer>,
+    }
+
+    impl ScriptedTransport {
+        /// A transport sending `incoming`, and what it will have been sent
+        fn new(incoming: impl IntoIterator<Item = Vec<u8>>) -> (Self, std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>) {
+            let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let transport = Self {
+                incoming: incoming.into_iter().collect(),
+                sent: sent.clone(),
+                answer: None,
+            };
+            (transport, sent)
+        }
+
+        /// Once out of chunks, keep the stream open until `answer` has
+        /// answered a frame from the server, and send its answer
+        fn answering(mut self, answer: impl FnMut(&Frame) -> Option<Vec<Frame>> + Send + 'static) -> Self {
+            self.answer = Some(Box::new(answer));
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RecordingTransport for ScriptedTransport {
+        async fn recv(&mut self) -> Option<std::io::Result<Vec<u8>>> {
+            if let Some(data) = self.incoming.pop_front() {
+                return Some(Ok(data));
+            }
+            if self.answer.is_some() {
+                // The handler stops waiting whenever it has something to send
+                let _ = tokio::time::timeout(ANSWER_TIMEOUT, std::future::pending::<()>()).await;
+                panic!("the server sent nothing to answer");
+            }
+            None
+        }
+
+        async fn send_binary(&mut self, data: Vec<u8>) -> std::io::Result<()> {
+            if let Some(answer) = &mut self.answer {
+                let mut reader = FrameReader::new(Cursor::new(data.clone()), false);
+                while let Some(frame) = reader.read_frame().await.unwrap() {
+                    if let Some(frames) = answer(&frame) {
+                        self.incoming.extend(frames.iter().map(encode));
+                        self.answer = None;
+                        break;
+                    }
+                }
+            }
+            self.sent.lock().unwrap().push(data);
+            Ok(())
+        }
+
+        async fn send_text(&mut self, text: String) -> std::io::Result<()> {
+            panic!("unexpected text message: {}", text);
+        }
+
+        async fn close(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_storage_save_and_list_recordings() {
         let (storage, _temp_dir) = create_test_storage();
 
         // Create test data
         let test_data = b"test recording content";
 
         // Save recording
-        let filename = storage.save_recording(test_data).unwrap();
-        assert!(filename.ends_with(".dcrr"));
+        let filename = storage.save_recording(test_data).await.unwrap();
+        assert!(filename.as_str().ends_with(".dcrr"));
 
         // List recordings
-        let recordings = storage.list_recordings(None).unwrap();
+        let recordings = storage.list_recordings(None).await.unwrap();
         assert_eq!(recordings.len(), 1);
-        assert_eq!(recordings[0].filename, filename);
+        assert_eq!(recordings[0].filename, filename.as_str());
         assert_eq!(recordings[0].size, test_data.len() as u64);
     }
 
-    #[test]
-    fn test_storage_get_recording() {
+    #[tokio::test]
+    async fn test_list_recordings_includes_ingest_summary() {
+        let (storage, _temp_dir) = create_test_storage();
+        let filename = storage.save_recording_stream(Cursor::new(SAMPLE_FILE_DATA)).await.unwrap();
+
+        let mut reader = FrameReader::new(Cursor::new(SAMPLE_FILE_DATA), true);
+        let mut frames = 0;
+        let mut initial_url = None;
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames += 1;
+            if let Frame::RecordingMetadata(metadata) = frame {
+                initial_url.get_or_insert(metadata.initial_url);
+            }
+        }
+
+        let recordings = storage.list_recordings(None).await.unwrap();
+        assert_eq!(recordings[0].id, filename.as_str());
+        assert_eq!(recordings[0].frame_count, Some(frames));
+        assert_eq!(recordings[0].initial_url, initial_url);
+        assert!(recordings[0].duration_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_list_recordings_created_from_header() {
+        let (storage, temp_dir) = create_test_storage();
+        let recordings_dir = temp_dir.path().join("recordings");
+        // Written first, so the file times disagree with the headers
+        let mut newer = SAMPLE_FILE_DATA.to_vec();
+        newer[8..16].copy_from_slice(&2_000_000_000_000u64.to_be_bytes());
+        std::fs::write(recordings_dir.join("newer.dcrr"), &newer).unwrap();
+        let mut older = SAMPLE_FILE_DATA.to_vec();
+        older[8..16].copy_from_slice(&1_000_000_000_000u64.to_be_bytes());
+        std::fs::write(recordings_dir.join("older.dcrr"), &older).unwrap();
+
+        let recordings = storage.list_recordings(None).await.unwrap();
+        let ids: Vec<_> = recordings.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["newer.dcrr", "older.dcrr"]);
+        assert_eq!(recordings[0].created.timestamp_millis(), 2_000_000_000_000);
+        assert_ne!(recordings[0].file_created, recordings[0].created);
+    }
+
+    #[tokio::test]
+    async fn test_storage_get_recording() {
         let (storage, _temp_dir) = create_test_storage();
 
         let test_data = b"test recording content";
-        let filename = storage.save_recording(test_data).unwrap();
+        let filename = storage.save_recording(test_data).await.unwrap();
 
         // Get recording
-        let retrieved_data = storage.get_recording(&filename).unwrap();
+        let retrieved_data = storage.get_recording(&filename).await.unwrap();
         assert_eq!(retrieved_data, test_data);
     }
 
-    #[test]
-    fn test_storage_nonexistent_recording() {
+    #[tokio::test]
+    async fn test_storage_nonexistent_recording() {
+        let (storage, _temp_dir) = create_test_storage();
+
+        let result = storage.get_recording(&RecordingId::new("nonexistent.dcrr").unwrap()).await;
+        assert!(matches!(result, Err(crate::StorageError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_storage_errors_distinguish_header_and_frame_failures() {
         let (storage, _temp_dir) = create_test_storage();
 
-        let result = storage.get_recording("nonexistent.dcrr");
-        assert!(result.is_err());
+        let result = storage.save_recording_stream(Cursor::new(b"not a recording".to_vec())).await;
+        assert!(matches!(result, Err(crate::StorageError::Header(_))));
+
+        // A valid header followed by a frame that doesn't decode
+        let mut corrupt = SAMPLE_FILE_DATA.to_vec();
+        corrupt.extend([0, 0, 0, 5, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let result = storage.save_recording_stream(Cursor::new(corrupt)).await;
+        assert!(matches!(result, Err(crate::StorageError::Frame(_))));
+
+        // Failed recordings are set aside rather than listed
+        assert!(storage.list_recordings(None).await.unwrap().is_empty());
+
+        // A stream cut off part way through a frame is kept up to that frame
+        let truncated = SAMPLE_FILE_DATA[..SAMPLE_FILE_DATA.len() - 3].to_vec();
+        let id = storage.save_recording_stream(Cursor::new(truncated)).await.unwrap();
+        let mut reader = storage.open_recording_reader(&id).await.unwrap();
+        let mut last = None;
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            last = Some(frame);
+        }
+        assert!(matches!(last, Some(Frame::RecordingTruncated(_))));
     }
 
     #[tokio::test]
@@ -83,7 +268,7 @@ mod tests {
         // Verify we can read it back
         let mut reader = FrameReader::new(Cursor::new(&frame_data), true);
         let read_header = reader.read_header().await.unwrap();
-        assert_eq!(read_header.version, 1);
+        assert_eq!(read_header.version, domcorder_proto::writer::DCRR_VERSION);
 
         let frame = reader.read_frame().await.unwrap();
         assert!(frame.is_some());
@@ -104,6 +289,30 @@ mod tests {
         assert!(filename1.contains("_"));
     }
 
+    #[tokio::test]
+    async fn test_filename_template_files_recordings_by_site() {
+        let (storage, _temp_dir) = create_test_storage();
+        let template = crate::FilenameTemplate::new("{site}/{yyyy}/{uuid}.dcrr").unwrap();
+        let storage = storage.with_filename_template(template);
+
+        let filename = storage.generate_filename_for_site(Some("https://shop.example.com"));
+        let year = chrono::Utc::now().format("%Y").to_string();
+        assert!(filename.starts_with(&format!("shop.example.com/{}/", year)));
+
+        let mut frames = Vec::new();
+        FrameWriter::new(&mut frames)
+            .write_frame(&Frame::Timestamp(domcorder_proto::TimestampData { timestamp: 1 }))
+            .unwrap();
+        let id = storage
+            .save_recording_stream_frames_only_with_site_and_path(Cursor::new(frames), None, None, None, Some(filename))
+            .await
+            .unwrap();
+
+        let listed = storage.list_all_recordings(Some("shop.example.com/")).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, id.as_str());
+    }
+
     #[tokio::test]
     async fn test_sample_file_storage_roundtrip() {
         // Test that the sample file can be saved and retrieved correctly
@@ -113,18 +322,18 @@ mod tests {
         let sample_data = SAMPLE_FILE_DATA;
 
         // Save it using our storage
-        let filename = storage.save_recording(sample_data).unwrap();
-        assert!(filename.ends_with(".dcrr"));
+        let filename = storage.save_recording(sample_data).await.unwrap();
+        assert!(filename.as_str().ends_with(".dcrr"));
 
         // Retrieve it
-        let saved_data = storage.get_recording(&filename).unwrap();
+        let saved_data = storage.get_recording(&filename).await.unwrap();
 
         // Verify the data matches exactly
         assert_eq!(
             saved_data, sample_data,
             "Saved data should match uploaded data"
         );
-        assert_eq!(saved_data.len(), 1985, "Saved file should be 1985 bytes");
+        assert_eq!(saved_data.len(), 1989, "Saved file should be 1989 bytes");
 
         // Verify we can still read it as a valid DCRR file
         let mut reader = FrameReader::new(Cursor::new(&saved_data), true);
@@ -146,6 +355,419 @@ mod tests {
         assert!(frame.is_some(), "Should have at least one frame");
     }
 
+    #[tokio::test]
+    async fn test_asset_limits_replace_oversized_assets_with_annotation() {
+        use crate::asset_cache::limits::{ASSET_SKIPPED_ANNOTATION, AssetLimits};
+        use domcorder_proto::{AssetData, AssetFetchError};
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_asset_limits(AssetLimits {
+            max_asset_size: Some(8),
+            max_recording_bytes: Some(12),
+        });
+        let asset = |asset_id: u32, len: usize| {
+            Frame::Asset(AssetData {
+                asset_id,
+                url: format!("https://example.com/{}.png", asset_id),
+                mime: Some("image/png".to_string()),
+                buf: vec![asset_id as u8; len],
+                fetch_error: AssetFetchError::None,
+            })
+        };
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        // Fits; too large on its own; fits; over the per-recording budget
+        for frame in [asset(1, 4), asset(2, 9), asset(3, 4), asset(4, 5)] {
+            writer.write_frame(&frame).unwrap();
+        }
+
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+
+        assert_eq!(frames.len(), 4);
+        assert!(matches!(&frames[0], Frame::AssetReference(r) if r.asset_id == 1));
+        assert!(matches!(&frames[2], Frame::AssetReference(r) if r.asset_id == 3));
+        for (frame, asset_id, limit) in [(&frames[1], 2, 8), (&frames[3], 4, 4)] {
+            let Frame::Annotation(annotation) = frame else {
+                panic!("expected an annotation, got {:?}", frame);
+            };
+            assert_eq!(annotation.name, ASSET_SKIPPED_ANNOTATION);
+            let data: serde_json::Value = serde_json::from_str(annotation.data.as_deref().unwrap()).unwrap();
+            assert_eq!(data["asset_id"], asset_id);
+            assert_eq!(data["limit"], limit);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantined_assets_are_not_cached() {
+        use crate::asset_cache::scanner::{MimePolicyScanner, QUARANTINED_ASSET_HASH};
+        use domcorder_proto::{AssetData, AssetFetchError};
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_asset_scanner(Box::new(MimePolicyScanner::new(["application/x-msdownload"])));
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for (asset_id, mime) in [(1, "application/x-msdownload"), (2, "image/png")] {
+            writer
+                .write_frame(&Frame::Asset(AssetData {
+                    asset_id,
+                    url: format!("https://example.com/{}", asset_id),
+                    mime: Some(mime.to_string()),
+                    buf: vec![asset_id as u8; 16],
+                    fetch_error: AssetFetchError::None,
+                }))
+                .unwrap();
+        }
+
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+
+        let Some(Frame::AssetReference(quarantined)) = reader.read_frame().await.unwrap() else {
+            panic!("expected an AssetReference");
+        };
+        assert_eq!(quarantined.hash, QUARANTINED_ASSET_HASH);
+        let sha256 = crate::asset_cache::hash::sha256(&[1u8; 16]);
+        assert!(!storage.asset_file_store.exists(&sha256).await.unwrap());
+
+        let Some(Frame::AssetReference(clean)) = reader.read_frame().await.unwrap() else {
+            panic!("expected an AssetReference");
+        };
+        assert_ne!(clean.hash, QUARANTINED_ASSET_HASH);
+    }
+
+    #[tokio::test]
+    async fn test_large_stylesheets_are_stored_once_and_restored_for_playback() {
+        use domcorder_proto::{NewAdoptedStyleSheetData, StyleSheetReplacedData, VStyleSheet};
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_stylesheet_dedup(Some(64));
+        let large = ".button { color: red; }\n".repeat(10);
+        let frames = vec![
+            Frame::NewAdoptedStyleSheet(NewAdoptedStyleSheetData {
+                style_sheet: VStyleSheet {
+                    id: 1,
+                    text: large.clone(),
+                    media: Some("screen".to_string()),
+                },
+            }),
+            Frame::StyleSheetReplaced(StyleSheetReplacedData {
+                style_sheet_id: 1,
+                content: large.clone(),
+            }),
+            Frame::StyleSheetReplaced(StyleSheetReplacedData {
+                style_sheet_id: 1,
+                content: "a { color: blue; }".to_string(),
+            }),
+        ];
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut stored = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            stored.push(frame);
+        }
+
+        let (Frame::NewAdoptedStyleSheetReference(added), Frame::StyleSheetReplacedReference(replaced)) =
+            (&stored[0], &stored[1])
+        else {
+            panic!("expected stylesheet references, got {:?}", &stored[..2]);
+        };
+        assert_eq!(added.hash, replaced.hash);
+        assert_eq!(stored[2], frames[2]);
+
+        let transformer = storage.playback_transformer();
+        for (stored, original) in stored.into_iter().zip(&frames) {
+            assert_eq!(&transformer.transform_frame(stored).await.unwrap(), original);
+        }
+    }
+
+    #[cfg(feature = "fetcher")]
+    #[tokio::test]
+    async fn test_slow_asset_fetch_does_not_hold_up_later_frames() {
+        use crate::asset_cache::PENDING_ASSET_HASH;
+        use domcorder_proto::{AssetData, AssetFetchError, TimestampData};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A CDN that takes a while to answer
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let response = "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 4\r\n\r\nabcd";
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let (storage, _temp_dir) = create_test_storage();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer
+            .write_frame(&Frame::Asset(AssetData {
+                asset_id: 7,
+                url: format!("http://{}/logo.png", addr),
+                mime: None,
+                buf: Vec::new(),
+                fetch_error: AssetFetchError::CORS,
+            }))
+            .unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 42 })).unwrap();
+
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+
+        assert_eq!(frames.len(), 3);
+        assert!(matches!(&frames[0], Frame::AssetReference(r) if r.asset_id == 7 && r.hash == PENDING_ASSET_HASH));
+        assert!(matches!(&frames[1], Frame::Timestamp(t) if t.timestamp == 42));
+        let Frame::AssetReference(resolved) = &frames[2] else {
+            panic!("expected the resolved AssetReference, got {:?}", frames[2]);
+        };
+        assert_eq!(resolved.asset_id, 7);
+        assert_eq!(resolved.mime.as_deref(), Some("image/png"));
+        assert_eq!(storage.metadata_store.resolve_random_id(&resolved.hash).await.unwrap(), Some(crate::asset_cache::hash::sha256(b"abcd")));
+    }
+
+    #[cfg(feature = "fetcher")]
+    #[tokio::test]
+    async fn test_failed_fetch_plays_the_version_current_at_recording_time() {
+        use crate::asset_cache::{AssetMetadata, AssetUsageParams, PENDING_ASSET_HASH};
+        use crate::clock::now_ms;
+        use domcorder_proto::{AssetData, AssetFetchError, AssetReferenceData, TimestampData};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // The site has since stopped serving the stylesheet
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                socket.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+            }
+        });
+
+        let (storage, _temp_dir) = create_test_storage();
+        let url = format!("http://{}/app.css", addr);
+        let cache = |sha256_hash: &'static str, random_id: &'static str| {
+            let storage = &storage;
+            let url = url.clone();
+            async move {
+                storage
+                    .metadata_store
+                    .store_asset_metadata(AssetMetadata {
+                        sha256_hash: sha256_hash.to_string(),
+                        random_id: random_id.to_string(),
+                        size: 1,
+                        mime_type: "text/css".to_string(),
+                    })
+                    .await
+                    .unwrap();
+                storage
+                    .metadata_store
+                    .register_asset_usage(AssetUsageParams {
+                        site_origin: "https://example.com".to_string(),
+                        url,
+                        sha256_hash: sha256_hash.to_string(),
+                        size: 1,
+                    })
+                    .await
+                    .unwrap();
+            }
+        };
+        cache("sha-old", "old").await;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let recorded_at = now_ms();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        // Re-deployed after the recording was made
+        cache("sha-new", "new").await;
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: recorded_at })).unwrap();
+        writer
+            .write_frame(&Frame::Asset(AssetData {
+                asset_id: 3,
+                url: url.clone(),
+                mime: None,
+                buf: Vec::new(),
+                fetch_error: AssetFetchError::CORS,
+            }))
+            .unwrap();
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut references = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            if let Frame::AssetReference(reference) = frame {
+                references.push((reference.hash, reference.mime));
+            }
+        }
+        assert_eq!(references, [
+            (PENDING_ASSET_HASH.to_string(), None),
+            ("old".to_string(), Some("text/css".to_string())),
+        ]);
+
+        let pinned = storage.pinned_asset_versions(&filename).await.unwrap();
+        assert_eq!(pinned, std::collections::HashMap::from([(url.clone(), "old".to_string())]));
+        // The provisional reference plays the pinned version too
+        let transformer = storage.playback_transformer().with_pinned_versions(&pinned);
+        let pending = Frame::AssetReference(AssetReferenceData {
+            asset_id: 3,
+            url: url.clone(),
+            hash: PENDING_ASSET_HASH.to_string(),
+            mime: None,
+        });
+        let Frame::Asset(played) = transformer.transform_frame(pending).await.unwrap() else {
+            panic!("expected an Asset frame");
+        };
+        assert_eq!(played.url, "/assets/old");
+
+        storage.delete_recording(&filename, None, None).await.unwrap();
+        assert!(storage.pinned_asset_versions(&filename).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "fetcher")]
+    #[tokio::test]
+    async fn test_warming_caches_changed_site_assets() {
+        use crate::asset_cache::hash::sha256;
+        use crate::asset_cache::manifest::generate_manifest;
+        use crate::asset_cache::{AssetMetadata, AssetUsageParams};
+        use std::collections::HashSet;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // The site's stylesheet changed in the last deploy
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nContent-Length: 3\r\n\r\nnew";
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let (storage, _temp_dir) = create_test_storage();
+        let url = format!("http://{}/app.css", addr);
+        storage
+            .metadata_store
+            .store_asset_metadata(AssetMetadata {
+                sha256_hash: sha256(b"old"),
+                random_id: "old-css".to_string(),
+                size: 3,
+                mime_type: "text/css".to_string(),
+            })
+            .await
+            .unwrap();
+        storage
+            .metadata_store
+            .register_asset_usage(AssetUsageParams {
+                site_origin: "https://example.com".to_string(),
+                url: url.clone(),
+                sha256_hash: sha256(b"old"),
+                size: 3,
+            })
+            .await
+            .unwrap();
+
+        let report = storage.warm_site_assets("https://example.com").await.unwrap();
+        assert_eq!((report.assets, report.changed, report.skipped, report.failed), (1, 1, 0, 0));
+        let manifest = generate_manifest(storage.metadata_store.as_ref(), "https://example.com", None, &HashSet::new())
+            .await
+            .unwrap();
+        let hashes: HashSet<_> = manifest.assets.iter().map(|entry| entry.sha256_hash.clone()).collect();
+        assert_eq!(hashes, HashSet::from([sha256(b"old"), sha256(b"new")]));
+        assert!(storage.metadata_store.resolve_hashes(&sha256(b"new")).await.unwrap().is_some());
+
+        // Nothing changed since
+        let report = storage.warm_site_assets("https://example.com").await.unwrap();
+        assert_eq!((report.assets, report.changed), (1, 0));
+
+        let mut denied = crate::policy::OriginPolicy::new("https://denied.example");
+        denied.allowed = false;
+        storage.metadata_store.set_origin_policy(&denied).await.unwrap();
+        assert!(matches!(
+            storage.warm_site_assets("https://denied.example").await,
+            Err(crate::StorageError::Refused { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_gzipped_and_plain_copies_of_an_asset_dedupe() {
+        use domcorder_proto::{AssetData, AssetFetchError};
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let css = b"body { color: red }";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(css).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let (storage, _temp_dir) = create_test_storage();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for (asset_id, buf) in [(1, gzipped), (2, css.to_vec())] {
+            writer
+                .write_frame(&Frame::Asset(AssetData {
+                    asset_id,
+                    url: format!("https://example.com/app.css?v={}", asset_id),
+                    mime: Some("text/css".to_string()),
+                    buf,
+                    fetch_error: AssetFetchError::None,
+                }))
+                .unwrap();
+        }
+
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+        let saved = storage.get_recording(&filename).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut hashes = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            if let Frame::AssetReference(reference) = frame {
+                hashes.push(reference.hash);
+            }
+        }
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], hashes[1]);
+        let sha256 = crate::asset_cache::hash::sha256(css);
+        assert_eq!(storage.metadata_store.resolve_hashes(&sha256).await.unwrap(), Some(hashes[0].clone()));
+    }
+
     #[tokio::test]
     async fn test_streaming_sample_file() {
         // Test that the sample file can be processed via streaming
@@ -157,14 +779,33 @@ mod tests {
 
         // Use the new streaming save method
         let filename = storage.save_recording_stream(cursor).await.unwrap();
-        assert!(filename.ends_with(".dcrr"));
+        assert!(filename.as_str().ends_with(".dcrr"));
 
-        // Retrieve and verify the saved file
-        let saved_data = storage.get_recording(&filename).unwrap();
+        // Retrieve and verify the saved file. Inline Asset frames are moved into
+        // the CAS during ingestion, so they come back as AssetReference frames.
+        let saved_data = storage.get_recording(&filename).await.unwrap();
+        let mut original_reader = FrameReader::new(Cursor::new(sample_data), true);
+        let mut saved_reader = FrameReader::new(Cursor::new(&saved_data), true);
         assert_eq!(
-            saved_data, sample_data,
-            "Streamed data should match original"
+            saved_reader.read_header().await.unwrap().created_at,
+            original_reader.read_header().await.unwrap().created_at,
+            "Streamed header should preserve the original timestamp"
         );
+        while let Some(original) = original_reader.read_frame().await.unwrap() {
+            let saved = saved_reader
+                .read_frame()
+                .await
+                .unwrap()
+                .expect("Saved recording should have the same number of frames");
+            match (original, saved) {
+                (Frame::Asset(asset), Frame::AssetReference(asset_ref)) => {
+                    assert_eq!(asset.asset_id, asset_ref.asset_id);
+                    assert_eq!(asset.url, asset_ref.url);
+                }
+                (original, saved) => assert_eq!(saved, original, "Streamed frame should match original"),
+            }
+        }
+        assert!(saved_reader.read_frame().await.unwrap().is_none());
 
         // Verify the file is still valid
         let mut reader = FrameReader::new(Cursor::new(&saved_data), true);
@@ -172,7 +813,7 @@ mod tests {
             .read_header()
             .await
             .expect("Should be able to read header");
-        assert_eq!(header.version, 1, "Header should have version 1");
+        assert_eq!(header.version, domcorder_proto::writer::DCRR_VERSION, "Header should have the current version");
 
         let frame = reader
             .read_frame()
@@ -180,4 +821,3397 @@ mod tests {
             .expect("Should be able to read frames");
         assert!(frame.is_some(), "Should have at least one frame");
     }
+
+    #[tokio::test]
+    async fn test_diff_identical_recordings() {
+        let (storage, _temp_dir) = create_test_storage();
+        let a = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let b = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+
+        let mut reader_a = storage.open_recording_reader(&a).await.unwrap();
+        let mut reader_b = storage.open_recording_reader(&b).await.unwrap();
+        let diff = crate::diff::diff_recordings(&mut reader_a, &mut reader_b, &Default::default())
+            .await
+            .unwrap();
+
+        assert!(!diff.points.is_empty());
+        assert_eq!(diff.total_differences, 0);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_records_analytics() {
+        use domcorder_proto::{MouseClickedData, RecordingMetadataData, ViewportResizedData};
+
+        let (storage, _temp_dir) = create_test_storage();
+        let frames = vec![
+            Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/pricing".to_string(),
+                heartbeat_interval_seconds: 0,
+            }),
+            Frame::ViewportResized(ViewportResizedData { width: 100, height: 100 }),
+            Frame::MouseClicked(MouseClickedData { x: 0, y: 0 }),
+        ];
+        let mut writer = FrameWriter::new(Vec::new());
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let data = writer.into_inner();
+
+        for _ in 0..2 {
+            storage
+                .save_recording_stream_frames_only(Cursor::new(data.clone()))
+                .await
+                .unwrap();
+        }
+
+        let heatmap = storage
+            .metadata_store
+            .get_click_heatmap("https://example.com", "/pricing")
+            .await
+            .unwrap();
+        assert_eq!(heatmap.len(), 1);
+        assert_eq!((heatmap[0].x, heatmap[0].y, heatmap[0].count), (0, 0, 2));
+
+        let sessions = storage
+            .metadata_store
+            .list_session_metrics("https://example.com")
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(|s| s.click_count == 1));
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_recordings_and_key_rotation() {
+        use crate::encryption::KeyRing;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tokio::io::AsyncReadExt;
+        use tower::ServiceExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = |key_ring: Option<KeyRing>| {
+            let metadata_store = SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap();
+            let asset_file_store =
+                LocalBinaryStore::new(temp_dir.path().join("assets"), "http://test.example".to_string()).unwrap();
+            let storage =
+                StorageState::new(temp_dir.path().to_path_buf(), Box::new(metadata_store), Box::new(asset_file_store))
+                    .unwrap();
+            match key_ring {
+                Some(key_ring) => storage.with_encryption(key_ring),
+                None => storage,
+            }
+        };
+
+        // A recording from before encryption was enabled, and one after
+        let plain = storage(None).save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let old = storage(Some(KeyRing::new("old", [1; 32])));
+        let encrypted = old.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let mut raw = Vec::new();
+        old.recording_store.open(&encrypted, 0).await.unwrap().read_to_end(&mut raw).await.unwrap();
+        assert!(!raw.windows(4).any(|window| window == b"DCRR"));
+        assert_eq!(old.get_recording(&encrypted).await.unwrap(), SAMPLE_FILE_DATA);
+        assert_eq!(old.get_recording(&plain).await.unwrap(), SAMPLE_FILE_DATA);
+
+        // Rotate to a new master key, keeping the old one until the keys are rewrapped
+        let rotating = std::sync::Arc::new(storage(Some(KeyRing::new("new", [2; 32]).with_key("old", [1; 32]))));
+        let app = DomcorderRouter::new(rotating).routes(&[RouteGroup::Admin]);
+        let request = Request::post("/admin/keys/rotate").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rotation: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(rotation, serde_json::json!({"key_id": "new", "rewrapped": 1, "unreadable": []}));
+
+        let new = storage(Some(KeyRing::new("new", [2; 32])));
+        let key = new.metadata_store.get_recording_key(encrypted.as_str()).await.unwrap().unwrap();
+        assert_eq!(key.key_id, "new");
+        assert_eq!(new.get_recording(&encrypted).await.unwrap(), SAMPLE_FILE_DATA);
+
+        // Without master keys an encrypted recording cannot be read, and there is nothing to rotate
+        assert!(matches!(
+            storage(None).get_recording(&encrypted).await,
+            Err(crate::StorageError::Encryption(_))
+        ));
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage(None))).routes(&[RouteGroup::Admin]);
+        let request = Request::post("/admin/keys/rotate").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_failed_redaction_keeps_the_recording_and_its_key() {
+        use crate::analytics::summary::RecordingSummary;
+        use crate::encryption::KeyRing;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = |min_free_space: u64| {
+            let metadata_store = SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap();
+            let asset_file_store =
+                LocalBinaryStore::new(temp_dir.path().join("assets"), "http://test.example".to_string()).unwrap();
+            StorageState::new(temp_dir.path().to_path_buf(), Box::new(metadata_store), Box::new(asset_file_store))
+                .unwrap()
+                .with_encryption(KeyRing::new("k1", [1; 32]))
+                .with_min_free_space(min_free_space)
+        };
+        let id = storage(0).save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let key = storage(0).metadata_store.get_recording_key(id.as_str()).await.unwrap();
+        let summary = RecordingSummary {
+            recording_id: id.to_string(),
+            frame_count: 1,
+            user_id: Some("jane".to_string()),
+            ..Default::default()
+        };
+        storage(0).metadata_store.store_recording_summary(&summary).await.unwrap();
+
+        // The rewrite is refused before anything is written
+        let full = storage(u64::MAX);
+        assert!(matches!(full.redact_recording(&id).await, Err(crate::StorageError::InsufficientSpace { .. })));
+        assert_eq!(full.metadata_store.get_recording_key(id.as_str()).await.unwrap(), key);
+        assert_eq!(full.get_recording(&id).await.unwrap(), SAMPLE_FILE_DATA);
+        let summaries = full.metadata_store.get_recording_summaries(&[id.to_string()]).await.unwrap();
+        assert_eq!(summaries[0].user_id.as_deref(), Some("jane"));
+        assert!(!full.is_recording_active(&id));
+        full.sync_active_registry().await.unwrap();
+
+        let storage = storage(0);
+        storage.redact_recording(&id).await.unwrap();
+        assert_ne!(storage.metadata_store.get_recording_key(id.as_str()).await.unwrap(), key);
+        assert_ne!(storage.get_recording(&id).await.unwrap(), SAMPLE_FILE_DATA);
+        let summaries = storage.metadata_store.get_recording_summaries(&[id.to_string()]).await.unwrap();
+        assert_eq!(summaries[0].user_id, None);
+        // Nothing is left under the temporary id
+        assert_eq!(storage.list_all_recordings(None).await.unwrap().len(), 1);
+        assert!(!temp_dir.path().join(format!("{}.rewrite", id)).exists());
+        let temp = format!("{}.rewrite", id);
+        assert_eq!(storage.metadata_store.get_recording_key(&temp).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_redaction_failing_after_the_swap_leaves_the_recording_readable() {
+        use crate::analytics::summary::RecordingSummary;
+        use crate::encryption::KeyRing;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = temp_dir.path().join("asset_cache.db");
+        let metadata_store = SqliteMetadataStore::new(&database).unwrap();
+        let asset_file_store =
+            LocalBinaryStore::new(temp_dir.path().join("assets"), "http://test.example".to_string()).unwrap();
+        let storage = StorageState::new(temp_dir.path().to_path_buf(), Box::new(metadata_store), Box::new(asset_file_store))
+            .unwrap()
+            .with_encryption(KeyRing::new("k1", [1; 32]));
+        let id = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let summary = RecordingSummary {
+            recording_id: id.to_string(),
+            frame_count: 1,
+            user_id: Some("jane".to_string()),
+            ..Default::default()
+        };
+        storage.metadata_store.store_recording_summary(&summary).await.unwrap();
+
+        // The summary can't be replaced once the rewrite is in place
+        let connection = rusqlite::Connection::open(&database).unwrap();
+        connection
+            .execute_batch("CREATE TRIGGER keep_summary BEFORE DELETE ON recordings BEGIN SELECT RAISE(ABORT, 'injected'); END")
+            .unwrap();
+        assert!(storage.redact_recording(&id).await.is_err());
+        assert!(!storage.is_recording_active(&id));
+        let redacted = storage.get_recording(&id).await.unwrap();
+        assert_ne!(redacted, SAMPLE_FILE_DATA);
+
+        // Redacting again once the metadata store recovers finishes the job
+        connection.execute_batch("DROP TRIGGER keep_summary").unwrap();
+        storage.redact_recording(&id).await.unwrap();
+        let summaries = storage.metadata_store.get_recording_summaries(&[id.to_string()]).await.unwrap();
+        assert_eq!(summaries[0].user_id, None);
+        assert!(storage.get_recording(&id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_legal_hold_blocks_deletion() {
+        use crate::audit::AuditAction;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let id = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let send = |method: &str, uri: String| {
+            let app = app.clone();
+            let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+
+        let hold = format!("/admin/recordings/{}/hold", id);
+        let (status, body) = send("PUT", format!("{}?actor=legal&reason=case%2042", hold)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({"recording_id": id.as_str(), "held": true, "changed": true})
+        );
+        assert_eq!(send("GET", "/admin/holds".to_string()).await.1, format!(r#"["{}"]"#, id));
+
+        // Held recordings cannot be deleted
+        let recording = format!("/admin/recordings/{}", id);
+        assert_eq!(send("DELETE", recording.clone()).await.0, StatusCode::CONFLICT);
+        assert!(state.recording_exists(&id).await);
+
+        assert_eq!(send("DELETE", format!("{}?actor=legal", hold)).await.0, StatusCode::OK);
+        assert_eq!(send("DELETE", format!("{}?actor=ops&reason=expired", recording)).await.0, StatusCode::NO_CONTENT);
+        assert!(!state.recording_exists(&id).await);
+        assert_eq!(send("GET", hold).await.0, StatusCode::NOT_FOUND);
+
+        let events = state.metadata_store.list_audit_events(Some(id.as_str())).await.unwrap();
+        let log: Vec<_> = events
+            .iter()
+            .map(|event| (event.action, event.actor.as_deref(), event.reason.as_deref()))
+            .collect();
+        assert_eq!(
+            log,
+            [
+                (AuditAction::HoldApplied, Some("legal"), Some("case 42")),
+                (AuditAction::HoldReleased, Some("legal"), None),
+                (AuditAction::Deleted, Some("ops"), Some("expired")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subject_erasure() {
+        use crate::analytics::summary::RecordingSummary;
+        use crate::asset_cache::AssetMetadata;
+        use crate::audit::AuditAction;
+        use crate::erasure::ErasureMode;
+        use domcorder_proto::{AssetReferenceData, DomTextChangedData, TextInsertOperationData, TextOperationData};
+
+        let (storage, _temp_dir) = create_test_storage();
+        for name in ["only-a", "shared"] {
+            let sha256 = format!("sha-{}", name);
+            storage.asset_file_store.put(&sha256, name.as_bytes(), "image/png").await.unwrap();
+            storage
+                .metadata_store
+                .store_asset_metadata(AssetMetadata {
+                    sha256_hash: sha256,
+                    random_id: name.to_string(),
+                    size: name.len() as u64,
+                    mime_type: "image/png".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        let save = |assets: &'static [&'static str], tags: &'static [&'static str]| {
+            let storage = &storage;
+            async move {
+                let mut data = Vec::new();
+                let mut writer = FrameWriter::new(&mut data);
+                writer.write_header(&FileHeader::new()).unwrap();
+                for (asset_id, hash) in assets.iter().enumerate() {
+                    let reference = AssetReferenceData {
+                        asset_id: asset_id as u32,
+                        url: format!("https://example.com/{}.png", hash),
+                        hash: hash.to_string(),
+                        mime: None,
+                    };
+                    writer.write_frame(&Frame::AssetReference(reference)).unwrap();
+                }
+                let typed = TextOperationData::Insert(TextInsertOperationData { index: 0, text: "Jane".to_string() });
+                writer
+                    .write_frame(&Frame::DomTextChanged(DomTextChangedData { node_id: 7, operations: vec![typed] }))
+                    .unwrap();
+                let id = storage.save_recording(&data).await.unwrap();
+                let summary = RecordingSummary {
+                    recording_id: id.to_string(),
+                    site_origin: Some("https://example.com".to_string()),
+                    initial_url: Some("https://example.com/account?email=jane".to_string()),
+                    duration_ms: 1_000,
+                    frame_count: assets.len() as u64 + 1,
+                    title: Some("Jane's account".to_string()),
+                    tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                    ..Default::default()
+                };
+                storage.metadata_store.store_recording_summary(&summary).await.unwrap();
+                id
+            }
+        };
+        let erased = save(&["only-a", "shared"], &["user:jane"]).await;
+        let held = save(&[], &["user:jane", "vip"]).await;
+        let other = save(&["shared"], &["user:john"]).await;
+        let redacted = save(&[], &["user:jo"]).await;
+        storage.set_legal_hold(&held, true, None, None).await.unwrap();
+
+        let report = storage
+            .erase_subject("user:jane", ErasureMode::Delete, Some("dpo".into()), Some("request 7".into()))
+            .await
+            .unwrap();
+        assert_eq!(report.erased, [erased.to_string()]);
+        assert_eq!(report.held, [held.to_string()]);
+        assert!(report.active.is_empty() && report.failed.is_empty());
+        // The asset still used by another recording is kept
+        assert_eq!(report.assets_pruned, 1);
+        assert!(!storage.recording_exists(&erased).await);
+        assert!(storage.recording_exists(&other).await);
+        assert_eq!(storage.metadata_store.get_asset_metadata("only-a").await.unwrap(), None);
+        assert!(!storage.asset_file_store.exists("sha-only-a").await.unwrap());
+        assert!(storage.asset_file_store.exists("sha-shared").await.unwrap());
+        assert!(storage.metadata_store.get_asset_metadata("shared").await.unwrap().is_some());
+        assert!(storage.metadata_store.get_recording_summaries(&[erased.to_string()]).await.unwrap().is_empty());
+        let events = storage.metadata_store.list_audit_events(Some(erased.as_str())).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, AuditAction::Deleted);
+        assert_eq!(events[0].reason.as_deref(), Some("request 7"));
+
+        let report = storage.erase_subject("user:jo", ErasureMode::Redact, None, None).await.unwrap();
+        assert_eq!(report.erased, [redacted.to_string()]);
+        assert_eq!(report.assets_pruned, 0);
+        let mut reader = storage.open_recording_reader(&redacted).await.unwrap();
+        let Some(Frame::DomTextChanged(changed)) = reader.read_frame().await.unwrap() else {
+            panic!("expected the text change");
+        };
+        assert_eq!(
+            changed.operations,
+            [TextOperationData::Insert(TextInsertOperationData { index: 0, text: "****".to_string() })]
+        );
+        let summaries = storage.metadata_store.get_recording_summaries(&[redacted.to_string()]).await.unwrap();
+        assert_eq!(summaries[0].initial_url.as_deref(), Some("https://example.com/account"));
+        assert_eq!((summaries[0].title.as_ref(), summaries[0].tags.len()), (None, 0));
+        // Redacted recordings no longer match the subject
+        assert!(storage.erase_subject("user:jo", ErasureMode::Delete, None, None).await.unwrap().erased.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recordings_by_user() {
+        use crate::erasure::ErasureMode;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::Request;
+        use domcorder_proto::SessionIdentityData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let mut ids = Vec::new();
+        for user_id in [Some("u42"), None, Some("u7")] {
+            let mut data = Vec::new();
+            let mut writer = FrameWriter::new(&mut data);
+            // Signed in part way through the session
+            for user_id in [None, user_id] {
+                let identity = SessionIdentityData {
+                    anonymous_id: "browser-1".to_string(),
+                    user_id: user_id.map(str::to_string),
+                    email_hash: None,
+                };
+                writer.write_frame(&Frame::SessionIdentity(identity)).unwrap();
+            }
+            ids.push(storage.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap());
+        }
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Listing]);
+        let list = |query: &str| {
+            let request = Request::builder().uri(format!("/recordings{}", query)).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+                let recordings: Vec<crate::RecordingInfo> = serde_json::from_slice(&body).unwrap();
+                recordings.into_iter().map(|r| r.id).collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(list("?user=u42").await, [ids[0].to_string()]);
+        assert_eq!(list("?user=browser-1").await.len(), 3);
+        assert!(list("?user=nobody").await.is_empty());
+
+        let report = state.erase_subject("u42", ErasureMode::Delete, None, None).await.unwrap();
+        assert_eq!(report.erased, [ids[0].to_string()]);
+        assert!(list("?user=u42").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_privacy_policy_minimizes_ingest() {
+        use crate::frame_filter::FrameTypeRules;
+        use crate::privacy::{ClientIpMode, PrivacyPolicy};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::{KeyPressedData, RecordingMetadataData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage
+            .with_privacy(PrivacyPolicy {
+                client_ip: ClientIpMode::Truncate,
+                trust_forwarded_for: true,
+                strip_url_queries: true,
+                ..Default::default()
+            })
+            .with_frame_rules(FrameTypeRules {
+                counted: std::collections::HashSet::from(["KeyPressed".to_string()]),
+                ..Default::default()
+            });
+        let state = std::sync::Arc::new(storage);
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer
+            .write_frame(&Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/reset?token=secret".to_string(),
+                heartbeat_interval_seconds: 10,
+            }))
+            .unwrap();
+        writer
+            .write_frame(&Frame::KeyPressed(KeyPressedData {
+                code: "KeyS".to_string(),
+                alt_key: false,
+                ctrl_key: false,
+                meta_key: false,
+                shift_key: false,
+            }))
+            .unwrap();
+        let request = Request::builder()
+            .method("POST")
+            .uri("/record")
+            .header("x-forwarded-for", "203.0.113.77, 10.0.0.1")
+            .body(Body::from(data))
+            .unwrap();
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Ingest]);
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let recordings = state.list_recordings(None).await.unwrap();
+        assert_eq!(recordings.len(), 1);
+        assert_eq!(recordings[0].client_ip.as_deref(), Some("203.0.113.0"));
+        assert_eq!(recordings[0].initial_url.as_deref(), Some("https://example.com/reset"));
+        assert_eq!(
+            recordings[0].dropped_frames,
+            std::collections::BTreeMap::from([("KeyPressed".to_string(), 1)])
+        );
+
+        let id = RecordingId::new(&recordings[0].id).unwrap();
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 1);
+        let Frame::RecordingMetadata(metadata) = &frames[0] else {
+            panic!("expected the metadata");
+        };
+        assert_eq!(metadata.initial_url, "https://example.com/reset");
+    }
+
+    #[tokio::test]
+    async fn test_idle_periods_are_collapsed() {
+        use domcorder_proto::{IdlePeriodData, MouseClickedData, MouseMovedData, RecordingMetadataData, TimestampData};
+
+        let (storage, _temp_dir) = create_test_storage();
+        // Frames-only uploads count as live, so keep the recorded timestamps
+        let state = storage
+            .with_timestamp_normalization(false)
+            .with_idle_compression(Some(60_000));
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        let click = Frame::MouseClicked(MouseClickedData { x: 5, y: 5 });
+        writer
+            .write_frame(&Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/".to_string(),
+                heartbeat_interval_seconds: 10,
+            }))
+            .unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1000 })).unwrap();
+        writer.write_frame(&click).unwrap();
+        // Two hours of a tab left open, with the pointer drifting
+        for second in 1..=7200 {
+            writer
+                .write_frame(&Frame::Timestamp(TimestampData {
+                    timestamp: 1000 + second * 1000,
+                }))
+                .unwrap();
+            writer
+                .write_frame(&Frame::MouseMoved(MouseMovedData { x: second as u32, y: 0 }))
+                .unwrap();
+        }
+        writer.write_frame(&click).unwrap();
+
+        let id = state.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap();
+
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 7);
+        assert_eq!(frames[3], Frame::IdlePeriod(IdlePeriodData { duration_ms: 7_200_000 }));
+        assert_eq!(frames[4], Frame::MouseMoved(MouseMovedData { x: 7200, y: 0 }));
+        assert_eq!(frames[6], click);
+    }
+
+    #[tokio::test]
+    async fn test_origin_policies() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::{DomNodePropertyChangedData, RecordingMetadataData, TimestampData};
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        for query in [
+            "origin=https://blocked.example/&allowed=false",
+            "origin=*&masking=inputs&max_duration_seconds=1",
+        ] {
+            let request = Request::builder()
+                .method("PUT")
+                .uri(format!("/admin/policies?{}", query))
+                .body(Body::empty())
+                .unwrap();
+            assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+        }
+        let bad = Request::builder().method("PUT").uri("/admin/policies?origin=*&sample_rate=2").body(Body::empty());
+        assert_eq!(app.oneshot(bad.unwrap()).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let policies = state.metadata_store.list_origin_policies().await.unwrap();
+        assert_eq!(policies.iter().map(|p| p.origin.as_str()).collect::<Vec<_>>(), ["*", "https://blocked.example"]);
+
+        let recording = |url: &str| {
+            let mut data = Vec::new();
+            let mut writer = FrameWriter::new(&mut data);
+            let metadata = RecordingMetadataData {
+                initial_url: url.to_string(),
+                heartbeat_interval_seconds: 10,
+            };
+            writer.write_frame(&Frame::RecordingMetadata(metadata)).unwrap();
+            for timestamp in [1_000, 1_500, 2_500] {
+                writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+                let changed = DomNodePropertyChangedData {
+                    node_id: 7,
+                    property_name: "value".to_string(),
+                    property_value: "hunter2".to_string(),
+                };
+                writer.write_frame(&Frame::DomNodePropertyChanged(changed)).unwrap();
+            }
+            Cursor::new(data)
+        };
+
+        let refused = state.save_recording_stream_frames_only(recording("https://blocked.example/a")).await;
+        assert!(matches!(refused, Err(crate::StorageError::Refused { .. })), "{:?}", refused);
+        assert!(state.list_recordings(None).await.unwrap().is_empty());
+        // Deleted rather than set aside as failed
+        let stored = std::fs::read_dir(temp_dir.path().join("recordings")).unwrap().count();
+        assert_eq!(stored, 0);
+
+        // The default policy masks inputs and ends the recording after a second
+        let id = state.save_recording_stream_frames_only(recording("https://shop.example/")).await.unwrap();
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut values = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            if let Frame::DomNodePropertyChanged(changed) = frame {
+                values.push(changed.property_value);
+            }
+        }
+        assert_eq!(values, ["*******", "*******"]);
+    }
+
+    #[tokio::test]
+    async fn test_recording_comments() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        for timestamp in [1_000, 4_000] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+        let id = state.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        let post = |uri: String| Request::builder().method("POST").uri(uri).body(Body::empty()).unwrap();
+        for (query, status) in [
+            ("offset_ms=2000&author=ana&text=Checkout%20fails%20here", StatusCode::CREATED),
+            ("offset_ms=500&text=Slow%20first%20paint", StatusCode::CREATED),
+            ("offset_ms=500&text=%20%20", StatusCode::BAD_REQUEST),
+        ] {
+            let response = app.clone().oneshot(post(format!("/recording/{}/comments?{}", id, query))).await;
+            assert_eq!(response.unwrap().status(), status, "{}", query);
+        }
+        let missing = app.clone().oneshot(post("/recording/missing.dcrr/comments?offset_ms=0&text=x".to_string()));
+        assert_eq!(missing.await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get(format!("/recording/{}/info", id)).body(Body::empty()).unwrap();
+        let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["id"], id.as_str());
+        assert_eq!(info["frame_count"], 2);
+        let comments = info["comments"].as_array().unwrap();
+        let texts: Vec<_> = comments.iter().map(|c| (c["offset_ms"].as_u64().unwrap(), c["text"].clone())).collect();
+        assert_eq!(texts, [(500, "Slow first paint".into()), (2000, "Checkout fails here".into())]);
+        assert_eq!(comments[1]["author"], "ana");
+
+        // Deleting the recording forgets its comments
+        state.delete_recording(&id, None, None).await.unwrap();
+        assert!(state.metadata_store.list_comments(id.as_str()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_collections() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let mut ids = Vec::new();
+        for timestamp in [1_000, 2_000, 3_000] {
+            let mut data = Vec::new();
+            FrameWriter::new(&mut data)
+                .write_frame(&Frame::Timestamp(TimestampData { timestamp }))
+                .unwrap();
+            ids.push(state.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap());
+        }
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Listing]);
+        let send = |method: &str, uri: String| {
+            let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+        let status = send("PUT", "/collections/checkout%20repros?description=Cart%20bugs".to_string()).await;
+        assert_eq!(status.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("PUT", "/collections/%20".to_string()).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        for id in [&ids[2], &ids[0], &ids[2]] {
+            let response = send("PUT", format!("/collections/checkout%20repros/recordings/{}", id)).await;
+            assert_eq!(response.unwrap().status(), StatusCode::OK);
+        }
+        let missing = send("PUT", "/collections/other/recordings/a.dcrr".to_string()).await;
+        assert_eq!(missing.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let collection = state.collection("checkout repros").await.unwrap();
+        assert_eq!(collection.description.as_deref(), Some("Cart bugs"));
+        assert_eq!(collection.recording_ids, [ids[2].to_string(), ids[0].to_string()]);
+
+        let listed = send("GET", "/recordings?collection=checkout%20repros".to_string()).await.unwrap();
+        let body = to_bytes(listed.into_body(), usize::MAX).await.unwrap();
+        let listed: Vec<crate::RecordingInfo> = serde_json::from_slice(&body).unwrap();
+        let mut listed: Vec<_> = listed.into_iter().map(|recording| recording.id).collect();
+        listed.sort();
+        let mut expected = vec![ids[0].to_string(), ids[2].to_string()];
+        expected.sort();
+        assert_eq!(listed, expected);
+        let unknown = send("GET", "/recordings?collection=other".to_string()).await;
+        assert_eq!(unknown.unwrap().status(), StatusCode::NOT_FOUND);
+
+        // Members leave when taken out or deleted, and the collection outlives them
+        let removed = send("DELETE", format!("/collections/checkout%20repros/recordings/{}", ids[2])).await;
+        assert_eq!(removed.unwrap().status(), StatusCode::OK);
+        state.delete_recording(&ids[0], None, None).await.unwrap();
+        assert!(state.collection("checkout repros").await.unwrap().recording_ids.is_empty());
+        let deleted = send("DELETE", "/collections/checkout%20repros".to_string()).await;
+        assert_eq!(deleted.unwrap().status(), StatusCode::NO_CONTENT);
+        assert!(state.metadata_store.list_collections().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_operations() {
+        use crate::bulk::BulkJob;
+        use crate::jobs::JobStatus;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode, header};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let mut ids = Vec::new();
+        for timestamp in [1_000, 2_000, 3_000] {
+            let mut data = Vec::new();
+            FrameWriter::new(&mut data)
+                .write_frame(&Frame::Timestamp(TimestampData { timestamp }))
+                .unwrap();
+            ids.push(state.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap());
+        }
+        state.set_legal_hold(&ids[1], true, None, None).await.unwrap();
+        state.add_comment(&ids[0], 0, None, "moves along".to_string()).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        // Starts a job and waits for it to finish
+        let run = |body: serde_json::Value| {
+            let app = app.clone();
+            let state = state.clone();
+            async move {
+                let request = Request::post("/recordings/bulk")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                if status != StatusCode::ACCEPTED {
+                    return Err(status);
+                }
+                let job: BulkJob = serde_json::from_slice(&body).unwrap();
+                loop {
+                    let request = Request::get(format!("/recordings/bulk/{}", job.id)).body(Body::empty()).unwrap();
+                    let body = to_bytes(app.clone().oneshot(request).await.unwrap().into_body(), usize::MAX);
+                    let job: BulkJob = serde_json::from_slice(&body.await.unwrap()).unwrap();
+                    if job.status == JobStatus::Finished {
+                        let stored = state.job(&job.id).await.unwrap().unwrap();
+                        assert_eq!(BulkJob::from_job(&state, &stored), Some(job.clone()));
+                        return Ok(job);
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            }
+        };
+        let all: Vec<_> = ids.iter().map(|id| id.to_string()).collect();
+
+        assert_eq!(run(serde_json::json!({"operation": "delete"})).await, Err(StatusCode::BAD_REQUEST));
+
+        let tagged = run(serde_json::json!({"operation": "tag", "tags": ["repro"], "ids": all})).await.unwrap();
+        assert_eq!((tagged.total, tagged.succeeded.len()), (3, 3));
+        let listed = state.list_all_recordings(None).await.unwrap();
+        assert!(listed.iter().all(|recording| recording.tags == ["repro"]));
+
+        // An empty prefix selects everything explicitly
+        let exported = run(serde_json::json!({"operation": "export", "filter": {"prefix": ""}}));
+        let exported = exported.await.unwrap();
+        assert_eq!(exported.succeeded.len(), 3);
+        let export_dir = exported.export_dir.unwrap();
+        let file = std::fs::read(export_dir.join(ids[2].as_str())).unwrap();
+        let mut reader = FrameReader::new(Cursor::new(file), true);
+        reader.read_header().await.unwrap();
+        assert!(matches!(reader.read_frame().await.unwrap(), Some(Frame::Timestamp(_))));
+
+        let moved = run(serde_json::json!({"operation": "move", "subdir": "archive", "ids": [all[0]]})).await.unwrap();
+        let new_id = RecordingId::new(moved.succeeded[0].moved_to.clone().unwrap()).unwrap();
+        assert_eq!(new_id.as_str(), format!("archive/{}", ids[0]));
+        assert!(!state.recording_exists(&ids[0]).await);
+        assert_eq!(state.recording_info(&new_id).await.unwrap().tags, ["repro"]);
+        assert_eq!(state.metadata_store.list_comments(new_id.as_str()).await.unwrap().len(), 1);
+
+        // Held recordings are left alone
+        let body = serde_json::json!({"operation": "delete", "ids": [new_id.as_str(), all[1], all[2]], "actor": "ops"});
+        let deleted = run(body).await.unwrap();
+        assert_eq!(deleted.succeeded.len(), 2);
+        assert_eq!(deleted.failed.len(), 1);
+        assert_eq!(deleted.failed[0].recording_id, all[1]);
+        let remaining = state.list_all_recordings(None).await.unwrap();
+        assert_eq!(remaining.iter().map(|recording| recording.id.as_str()).collect::<Vec<_>>(), [all[1].as_str()]);
+    }
+
+    #[tokio::test]
+    async fn test_live_timestamps_are_normalized() {
+        use crate::clock::now_ms;
+        use domcorder_proto::TimestampData;
+
+        const T: u64 = 1_700_000_000_000;
+        const HOUR: u64 = 3_600_000;
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        // A recorder whose clock is set back an hour and then forward a day
+        for timestamp in [T, T + 500 - HOUR, T + 1_000 - HOUR, T + 24 * HOUR] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+
+        let (storage, _temp_dir) = create_test_storage();
+        let before = now_ms();
+        let id = storage.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap();
+        let after = now_ms();
+
+        let mut reader = storage.open_recording_reader(&id).await.unwrap();
+        let mut timestamps = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            if let Frame::Timestamp(timestamp) = frame {
+                timestamps.push(timestamp.timestamp);
+            }
+        }
+        let start = timestamps[0];
+        assert!((before..=after).contains(&start), "{} not in {}..={}", start, before, after);
+        assert_eq!(timestamps[1..3], [start, start + 500]);
+        // The day-long jump is moved back to its arrival, which is no earlier
+        // than the previous timestamp
+        assert!((start + 500..=after.max(start + 500)).contains(&timestamps[3]), "{:?}", timestamps);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_recording_stream() {
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let filename = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let app = crate::server::create_app(std::sync::Arc::new(storage));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/recording/{}.ndjson", filename))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<Frame> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let mut reader = FrameReader::new(Cursor::new(SAMPLE_FILE_DATA), true);
+        reader.read_header().await.unwrap();
+        let mut expected = 0;
+        while reader.read_frame().await.unwrap().is_some() {
+            expected += 1;
+        }
+        assert_eq!(lines.len(), expected);
+        assert!(matches!(lines[0], Frame::Timestamp(_)));
+    }
+
+    #[tokio::test]
+    async fn test_api_key_required_when_configured() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let app = crate::server::DomcorderRouter::new(std::sync::Arc::new(storage))
+            .with_auth(crate::auth::ApiKey::new("secret"))
+            .routes(crate::server::RouteGroup::ALL);
+        let status = |request: Request<Body>| {
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        let anonymous = Request::get("/recordings").body(Body::empty()).unwrap();
+        assert_eq!(status(anonymous).await, StatusCode::UNAUTHORIZED);
+
+        let wrong = Request::get("/recordings")
+            .header("authorization", "Bearer wrong")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(wrong).await, StatusCode::UNAUTHORIZED);
+
+        let authorized = Request::get("/recordings")
+            .header("authorization", "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(authorized).await, StatusCode::OK);
+
+        let preflight = Request::options("/record").body(Body::empty()).unwrap();
+        assert_ne!(status(preflight).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_router_prefix_and_route_groups() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage))
+            .with_prefix("/domcorder/")
+            .routes(&[RouteGroup::Listing]);
+        let status = |uri: &str| {
+            let app = app.clone();
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(status("/domcorder/recordings").await, StatusCode::OK);
+        assert_eq!(status("/recordings").await, StatusCode::NOT_FOUND);
+        // Groups that were not selected are not routed
+        assert_eq!(status("/domcorder/analytics/sessions?site=x").await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_storage_reports_usage() {
+        use crate::disk_usage::DiskUsage;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage)).routes(&[RouteGroup::Admin]);
+
+        let request = Request::get("/admin/storage").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let usage: DiskUsage = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(usage.recordings.count, 1);
+        assert_eq!(usage.recordings.bytes, SAMPLE_FILE_DATA.len() as u64);
+        assert_eq!(usage.assets_bytes, Some(0));
+        assert!(usage.database_bytes.is_some_and(|bytes| bytes > 0));
+        assert!(usage.filesystem.available_bytes > 0);
+        assert!(usage.filesystem.accepting_recordings);
+    }
+
+    #[tokio::test]
+    async fn test_recordings_refused_when_space_is_low() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_min_free_space(u64::MAX);
+
+        let result = storage.save_recording_stream_frames_only(Cursor::new(Vec::new())).await;
+        assert!(matches!(result, Err(crate::StorageError::InsufficientSpace { required: u64::MAX, .. })));
+        // Nothing was claimed or created
+        assert!(storage.active_recordings.lock().unwrap().is_empty());
+        assert!(storage.list_all_recordings(None).await.unwrap().is_empty());
+
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage)).routes(&[RouteGroup::Ingest, RouteGroup::Admin]);
+        let request = Request::post("/record").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+
+        let request = Request::get("/admin/storage").body(Body::empty()).unwrap();
+        let body = axum::body::to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let usage: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(usage["filesystem"]["accepting_recordings"], false);
+    }
+
+    #[tokio::test]
+    async fn test_assets_served_sandboxed() {
+        use crate::asset_cache::AssetMetadata;
+        use crate::server::{AssetDisposition, DomcorderRouter, RouteGroup};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let sha256 = "a".repeat(64);
+        storage.asset_file_store.put(&sha256, b"<svg onload=alert(1)/>", "image/svg+xml").await.unwrap();
+        storage
+            .metadata_store
+            .store_asset_metadata(AssetMetadata {
+                sha256_hash: sha256.clone(),
+                random_id: "r".repeat(64),
+                size: 22,
+                mime_type: "image/svg+xml".to_string(),
+            })
+            .await
+            .unwrap();
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage))
+            .with_asset_disposition(AssetDisposition::Attachment)
+            .routes(&[RouteGroup::Playback]);
+
+        let request = Request::get(format!("/assets/{}", "r".repeat(64))).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+        assert_eq!(response.headers()["content-security-policy"], "sandbox");
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+        assert_eq!(response.headers()["content-disposition"], "attachment");
+        assert_eq!(response.headers()["content-length"], "22");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"<svg onload=alert(1)/>");
+    }
+
+    #[tokio::test]
+    async fn test_list_recordings_includes_subdirectories() {
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        let recordings_dir = temp_dir.path().join("recordings");
+        std::fs::create_dir_all(recordings_dir.join("team")).unwrap();
+        std::fs::write(recordings_dir.join("top.dcrr"), SAMPLE_FILE_DATA).unwrap();
+        std::fs::write(recordings_dir.join("team/nested.dcrr"), SAMPLE_FILE_DATA).unwrap();
+        let app = crate::server::create_app(std::sync::Arc::new(storage));
+
+        let list = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let recordings: Vec<crate::RecordingInfo> = serde_json::from_slice(&body).unwrap();
+                let mut ids: Vec<_> = recordings.into_iter().map(|r| (r.id, r.filename)).collect();
+                ids.sort();
+                ids
+            }
+        };
+        let pair = |id: &str, filename: &str| (id.to_string(), filename.to_string());
+        assert_eq!(
+            list("/recordings").await,
+            [pair("team/nested.dcrr", "nested.dcrr"), pair("top.dcrr", "top.dcrr")]
+        );
+        assert_eq!(list("/recordings?prefix=team/").await, [pair("team/nested.dcrr", "nested.dcrr")]);
+
+        let request = Request::get("/recording/team%2Fnested.dcrr").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_recording_routes_reject_path_traversal() {
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        // A file in the storage dir, one level above the recordings
+        std::fs::write(temp_dir.path().join("secret.dcrr"), SAMPLE_FILE_DATA).unwrap();
+        let app = crate::server::create_app(std::sync::Arc::new(storage));
+
+        for uri in [
+            "/recording/..%2Fsecret.dcrr",
+            "/recording/..%2Fsecret.dcrr.ndjson",
+            "/recording/..%2Fsecret.dcrr/timeline",
+            "/recording/..%2F..%2Fetc%2Fpasswd",
+            "/recordings/diff?a=../secret.dcrr&b=../secret.dcrr",
+        ] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert!(!body.starts_with(b"DCRR"), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observability_hooks_report_ingest() {
+        use crate::observability::{Labels, ObservabilityHooks, SpanEvent, names};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Default)]
+        struct Recorded {
+            counters: Mutex<Vec<(&'static str, u64, Vec<String>)>>,
+            spans: Mutex<Vec<SpanEvent>>,
+        }
+
+        impl ObservabilityHooks for Recorded {
+            fn counter(&self, name: &'static str, value: u64, labels: Labels<'_>) {
+                let labels = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                self.counters.lock().unwrap().push((name, value, labels));
+            }
+
+            fn span_event(&self, _span: &'static str, event: SpanEvent, _id: &str) {
+                self.spans.lock().unwrap().push(event);
+            }
+        }
+
+        let hooks = Arc::new(Recorded::default());
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_observability(Box::new(hooks.clone()));
+
+        storage.save_recording_stream(Cursor::new(SAMPLE_FILE_DATA)).await.unwrap();
+        assert!(storage.save_recording_stream(Cursor::new(b"not a recording".to_vec())).await.is_err());
+
+        let counters = hooks.counters.lock().unwrap();
+        let total = |name: &str, label: &str| -> u64 {
+            counters
+                .iter()
+                .filter(|(n, _, labels)| *n == name && labels.iter().any(|l| l == label))
+                .map(|(_, value, _)| value)
+                .sum()
+        };
+        assert_eq!(total(names::RECORDINGS, "outcome=completed"), 1);
+        assert_eq!(total(names::RECORDINGS, "outcome=failed"), 1);
+        assert!(total(names::FRAMES_WRITTEN, "frame=Timestamp") > 0);
+
+        let spans = hooks.spans.lock().unwrap();
+        assert_eq!(spans.iter().filter(|e| **e == SpanEvent::Started).count(), 2);
+        assert!(spans.iter().any(|e| matches!(e, SpanEvent::Completed { .. })));
+        assert!(spans.iter().any(|e| matches!(e, SpanEvent::Failed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_frame_sink_receives_selected_frames_during_ingest() {
+        use crate::frame_sink::{ChannelSink, FrameCategory, FrameSinkConfig};
+
+        let (sink, mut receiver) = ChannelSink::new(64);
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_frame_sink(
+            FrameSinkConfig::only("domcorder", &[FrameCategory::Navigation]),
+            Box::new(sink),
+        );
+
+        let filename = storage.save_recording_stream(Cursor::new(SAMPLE_FILE_DATA)).await.unwrap();
+        drop(storage);
+
+        let mut messages = Vec::new();
+        while let Some(message) = receiver.recv().await {
+            messages.push(message);
+        }
+        assert!(!messages.is_empty());
+        for message in &messages {
+            assert_eq!(message.topic, "domcorder.navigation");
+            assert_eq!(message.recording_id, filename.as_str());
+            assert!(matches!(message.frame, Frame::Keyframe(_) | Frame::RecordingMetadata(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_context_parents_ingest_and_playback_spans() {
+        use crate::observability::{ObservabilityHooks, names};
+        use crate::trace::{FinishedSpan, RECORDING_ID_ATTRIBUTE};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TraceContextData;
+        use std::sync::{Arc, Mutex};
+        use tower::ServiceExt;
+
+        #[derive(Default)]
+        struct Spans(Mutex<Vec<FinishedSpan>>);
+
+        impl ObservabilityHooks for Spans {
+            fn span_finished(&self, span: &FinishedSpan) {
+                self.0.lock().unwrap().push(span.clone());
+            }
+        }
+
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let traceparent = format!("00-{}-00f067aa0ba902b7-01", trace_id);
+
+        // The sample recording with a TraceContext frame after its first frame
+        let mut reader = FrameReader::new(Cursor::new(SAMPLE_FILE_DATA), true);
+        let header = reader.read_header().await.unwrap();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&header).unwrap();
+        writer.write_frame(&reader.read_frame().await.unwrap().unwrap()).unwrap();
+        writer
+            .write_frame(&Frame::TraceContext(TraceContextData {
+                traceparent: traceparent.clone(),
+                tracestate: None,
+            }))
+            .unwrap();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            writer.write_frame(&frame).unwrap();
+        }
+
+        let spans = Arc::new(Spans::default());
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_observability(Box::new(spans.clone()));
+        let filename = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        let app = crate::server::create_app(std::sync::Arc::new(storage));
+        let request = Request::get(format!("/recording/{}", filename))
+            .header("traceparent", &traceparent)
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let spans = spans.0.lock().unwrap();
+        let names: Vec<_> = spans.iter().map(|span| span.name).collect();
+        assert_eq!(names, [names::RECORDING_INGEST, names::RECORDING_PLAYBACK]);
+        for span in spans.iter() {
+            assert!(span.ok);
+            assert_eq!(span.trace_id, trace_id);
+            assert_eq!(span.parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+            assert!(span.attributes.contains(&(RECORDING_ID_ATTRIBUTE, filename.to_string())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_second_writer_for_a_recording_is_rejected() {
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let id = RecordingId::new("custom.dcrr").unwrap();
+
+        let (first_input, first_reader) = tokio::io::duplex(1024);
+        let first = tokio::spawn({
+            let state = state.clone();
+            async move {
+                state
+                    .save_recording_stream_frames_only_with_site_and_path(
+                        first_reader,
+                        None,
+                        None,
+                        None,
+                        Some("custom.dcrr".to_string()),
+                    )
+                    .await
+            }
+        });
+        while !state.is_recording_active(&id) {
+            tokio::task::yield_now().await;
+        }
+
+        let second = state
+            .save_recording_stream_frames_only_with_site_and_path(
+                Cursor::new(Vec::new()),
+                None,
+                None,
+                None,
+                Some("custom.dcrr".to_string()),
+            )
+            .await;
+        assert!(matches!(second, Err(crate::StorageError::InUse(ref name)) if name == "custom.dcrr"));
+        // The rejected writer leaves the first one's claim in place
+        assert!(state.is_recording_active(&id));
+
+        drop(first_input);
+        assert_eq!(first.await.unwrap().unwrap(), id);
+        assert!(!state.is_recording_active(&id));
+    }
+
+    #[tokio::test]
+    async fn test_recording_stream_over_custom_transport() {
+        use crate::recording_handler::{RecordingHooks, handle_recording_stream};
+        use domcorder_proto::RecordingMetadataData;
+
+        let mut incoming = vec![encode(&Frame::RecordingMetadata(RecordingMetadataData {
+            initial_url: "https://example.com/".to_string(),
+            heartbeat_interval_seconds: 0,
+        }))];
+        let mut reader = FrameReader::new(Cursor::new(SAMPLE_FILE_DATA), true);
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            incoming.push(encode(&frame));
+        }
+        let chunks = incoming.len();
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let (transport, sent) = ScriptedTransport::new(incoming);
+        let config = recording_config("transport.dcrr");
+        handle_recording_stream(transport, state.clone(), None, config, RecordingHooks::default()).await;
+
+        // The cache manifest is the only thing sent back
+        let sent = sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        let mut manifest = FrameReader::new(Cursor::new(sent[0].clone()), false);
+        assert!(matches!(manifest.read_frame().await.unwrap(), Some(Frame::CacheManifest(_))));
+
+        let id = RecordingId::new("transport.dcrr").unwrap();
+        let mut stored = state.open_recording_reader(&id).await.unwrap();
+        let mut frames = 0;
+        while stored.read_frame().await.unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, chunks);
+    }
+
+    #[cfg(feature = "webtransport")]
+    #[tokio::test]
+    async fn test_recording_over_webtransport() {
+        use crate::auth::ApiKey;
+        use crate::webtransport::{RECORD_PATH, bind_webtransport, serve_webtransport};
+        use domcorder_proto::RecordingMetadataData;
+        use wtransport::error::ConnectingError;
+        use wtransport::{ClientConfig, Endpoint, Identity};
+
+        let identity = Identity::self_signed(["localhost"]).unwrap();
+        let certificate_hash = identity.certificate_chain().as_slice()[0].hash();
+        let endpoint = bind_webtransport("127.0.0.1:0".parse().unwrap(), identity).unwrap();
+        let url = format!("https://127.0.0.1:{}{}", endpoint.local_addr().unwrap().port(), RECORD_PATH);
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        tokio::spawn(serve_webtransport(endpoint, state.clone(), Some(ApiKey::new("secret"))));
+
+        let config = ClientConfig::builder().with_bind_default().with_server_certificate_hashes([certificate_hash]).build();
+        let client = Endpoint::client(config).unwrap();
+        assert!(matches!(client.connect(&url).await, Err(ConnectingError::SessionRejected)));
+
+        let connection = client.connect(format!("{}?api_key=secret", url)).await.unwrap();
+        let (mut send, mut recv) = connection.open_bi().await.unwrap().await.unwrap();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer
+            .write_frame(&Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/".to_string(),
+                heartbeat_interval_seconds: 0,
+            }))
+            .unwrap();
+        let mut frames = 1;
+        let mut reader = FrameReader::new(Cursor::new(SAMPLE_FILE_DATA), true);
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            writer.write_frame(&frame).unwrap();
+            frames += 1;
+        }
+        send.write_all(&data).await.unwrap();
+        send.finish().await.unwrap();
+
+        // The server answers with the cache manifest and finishes the stream once the recording is stored
+        let mut answer = Vec::new();
+        let mut buffer = [0; 4096];
+        while let Some(read) = recv.read(&mut buffer).await.unwrap() {
+            answer.extend_from_slice(&buffer[..read]);
+        }
+        connection.close(0u32.into(), b"");
+        let mut answer = FrameReader::new(Cursor::new(answer), false);
+        assert!(matches!(answer.read_frame().await.unwrap(), Some(Frame::CacheManifest(_))));
+        assert!(answer.read_frame().await.unwrap().is_none());
+
+        let recordings = state.list_recordings(None).await.unwrap();
+        assert_eq!(recordings.len(), 1);
+        let id = RecordingId::new(recordings[0].id.clone()).unwrap();
+        let mut stored = state.open_recording_reader(&id).await.unwrap();
+        let mut stored_frames = 0;
+        while stored.read_frame().await.unwrap().is_some() {
+            stored_frames += 1;
+        }
+        assert_eq!(stored_frames, frames);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_leaves_out_assets_the_recorder_has() {
+        use crate::recording_handler::{RecordingHooks, handle_recording_stream};
+        use domcorder_proto::{CacheInventoryData, RecordingMetadataData, TimestampData};
+
+        let (storage, _temp_dir) = create_test_storage();
+        seed_site_assets(&storage, &["logo", "font", "hero"]).await;
+        let state = std::sync::Arc::new(storage);
+
+        let (transport, sent) = ScriptedTransport::new([
+            encode(&Frame::CacheInventory(CacheInventoryData {
+                sha256_hashes: vec!["sha-logo".to_string(), "sha-elsewhere".to_string()],
+            })),
+            encode(&Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/".to_string(),
+                heartbeat_interval_seconds: 0,
+            })),
+            encode(&Frame::Timestamp(TimestampData { timestamp: 1000 })),
+        ]);
+        let config = recording_config("inventory.dcrr");
+        handle_recording_stream(transport, state.clone(), None, config, RecordingHooks::default()).await;
+
+        let sent = sent.lock().unwrap().clone();
+        let mut reader = FrameReader::new(Cursor::new(sent[0].clone()), false);
+        let Some(Frame::CacheManifest(manifest)) = reader.read_frame().await.unwrap() else {
+            panic!("expected the cache manifest");
+        };
+        let mut hashes: Vec<_> = manifest.assets.into_iter().map(|entry| entry.sha256_hash).collect();
+        hashes.sort();
+        assert_eq!(hashes, ["sha-font", "sha-hero"]);
+
+        // The inventory is part of the handshake, not the recording
+        let id = RecordingId::new("inventory.dcrr").unwrap();
+        let mut stored = state.open_recording_reader(&id).await.unwrap();
+        let mut types = Vec::new();
+        while let Some(frame) = stored.read_frame().await.unwrap() {
+            types.push(frame.type_name());
+        }
+        assert_eq!(types, ["RecordingMetadata", "Timestamp"]);
+    }
+
+    #[tokio::test]
+    async fn test_slow_manifest_falls_back_to_the_previous_one() {
+        use std::collections::HashSet;
+        use std::time::Duration;
+
+        let (storage, _temp_dir) = create_test_storage();
+        seed_site_assets(&storage, &["logo", "font"]).await;
+        // No query can answer within the budget
+        let state = std::sync::Arc::new(storage.with_manifest_budget(Duration::ZERO));
+        let cached = HashSet::from(["sha-logo".to_string()]);
+
+        // Nothing to fall back on yet
+        let manifest = state.handshake_manifest("https://example.com", &cached).await.unwrap();
+        assert!(manifest.assets.is_empty());
+
+        // The overrunning query still refreshes the site's manifest
+        let mut assets = Vec::new();
+        for _ in 0..100 {
+            assets = state.handshake_manifest("https://example.com", &cached).await.unwrap().assets;
+            if !assets.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let hashes: Vec<_> = assets.into_iter().map(|entry| entry.sha256_hash).collect();
+        assert_eq!(hashes, ["sha-font"]);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_pongs_correct_playback_timestamps() {
+        use crate::clock::now_ms;
+        use crate::recording_handler::{RecordingHooks, handle_recording_stream};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::Request;
+        use domcorder_proto::{HeartbeatPongData, RecordingMetadataData, TimestampData};
+        use std::sync::Arc;
+        use tower::ServiceExt;
+
+        const SKEW_MS: u64 = 5_000;
+
+        let recorded_at = 1_700_000_000_000;
+        let incoming = [
+            Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/".to_string(),
+                heartbeat_interval_seconds: 1,
+            }),
+            Frame::Timestamp(TimestampData { timestamp: recorded_at }),
+        ];
+
+        // The raw skew, not what is left of it on a normalized timeline
+        let (storage, _temp_dir) = create_test_storage();
+        let state = Arc::new(storage.with_timestamp_normalization(false));
+        // A recorder whose clock is SKEW_MS ahead, answering the first ping
+        let (transport, _) = ScriptedTransport::new(incoming.iter().map(encode));
+        let transport = transport.answering(|frame| match frame {
+            Frame::HeartbeatPing(ping) => Some(vec![Frame::HeartbeatPong(HeartbeatPongData {
+                server_timestamp: ping.server_timestamp,
+                client_timestamp: now_ms() + SKEW_MS,
+            })]),
+            _ => None,
+        });
+        let config = recording_config("skewed.dcrr");
+        handle_recording_stream(transport, state.clone(), None, config, RecordingHooks::default()).await;
+
+        let skew = state.metadata_store.get_clock_skew("skewed.dcrr").await.unwrap().unwrap();
+        // Within the round trip, which is rounded to whole milliseconds
+        let tolerance = skew.round_trip_ms as i64 / 2 + 1;
+        assert!(
+            skew.skew_ms <= SKEW_MS as i64 + tolerance && skew.skew_ms > SKEW_MS as i64 - 500,
+            "{:?}",
+            skew
+        );
+
+        // The pong is not stored, and playback moves timestamps onto the server's clock
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        let request = Request::get("/recording/skewed.dcrr").body(Body::empty()).unwrap();
+        let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(body.to_vec()), false);
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        assert!(matches!(frames[0], Frame::PlaybackConfig(_)));
+        assert!(matches!(frames[1], Frame::RecordingMetadata(_)));
+        assert_eq!(
+            frames[2..],
+            [Frame::Timestamp(TimestampData {
+                timestamp: recorded_at - skew.skew_ms as u64,
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drifted_live_recordings_request_a_keyframe() {
+        use crate::recording_handler::{RecordingHooks, handle_recording_stream};
+        use domcorder_proto::{DomNodeRemovedData, FrameBuilder};
+        use std::sync::Arc;
+
+        let page = FrameBuilder::new().metadata("https://example.com/").at(1_000).new_page().build();
+        // The recorder missed a node it later removes, then another
+        let removed = |node_id| Frame::DomNodeRemoved(DomNodeRemovedData { node_id });
+        let mut incoming: Vec<_> = page.iter().map(encode).collect();
+        incoming.push(encode(&removed(900)));
+        incoming.push(encode(&removed(901)));
+        let fresh = FrameBuilder::new().at(2_000).new_page().build();
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = Arc::new(storage);
+        // A recorder that answers the first keyframe request with `fresh`
+        let (transport, sent) = ScriptedTransport::new(incoming);
+        let transport = transport.answering(move |frame| match frame {
+            Frame::RequestKeyframe(_) => Some(fresh.clone()),
+            _ => None,
+        });
+        let config = recording_config("drifting.dcrr");
+        handle_recording_stream(transport, state.clone(), None, config, RecordingHooks::default()).await;
+
+        // Asked once, however many frames drifted
+        let sent = sent.lock().unwrap().clone();
+        let mut requests = Vec::new();
+        for data in sent {
+            let mut reader = FrameReader::new(Cursor::new(data), false);
+            while let Some(frame) = reader.read_frame().await.unwrap() {
+                if let Frame::RequestKeyframe(request) = frame {
+                    requests.push(request.reason);
+                }
+            }
+        }
+        assert_eq!(requests, ["unknown node id 900 in DomNodeRemoved"]);
+        // and no longer once the recorder has gone
+        let id = RecordingId::new("drifting.dcrr").unwrap();
+        assert!(!state.keyframe_requests.request(&id, String::new()));
+
+        // The fresh keyframe follows the drifted frames in the recording
+        let mut reader = FrameReader::new(state.clone().get_recording_stream(&id).await.unwrap(), false).with_index(true);
+        let mut stored = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            stored.push(frame);
+        }
+        let keyframes = stored.iter().filter(|frame| matches!(frame, Frame::Keyframe(_))).count();
+        assert_eq!(keyframes, 2);
+        assert!(matches!(stored.last(), Some(Frame::Keyframe(_))));
+    }
+
+    #[tokio::test]
+    async fn test_scoped_recordings_play_in_scoped_mode() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::Request;
+        use domcorder_proto::{FrameBuilder, RecordingScopeData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let upload = |frames: Vec<Frame>| {
+            let mut data = Vec::new();
+            let mut writer = FrameWriter::new(&mut data);
+            writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+            for frame in &frames {
+                writer.write_frame(frame).unwrap();
+            }
+            data
+        };
+        let scoped = FrameBuilder::new()
+            .metadata("https://example.com/")
+            .frame(Frame::RecordingScope(RecordingScopeData {
+                root_selector: "#support-widget".to_string(),
+            }))
+            .at(1_000)
+            .new_page()
+            .build();
+        let scoped = state.save_recording_stream(Cursor::new(upload(scoped))).await.unwrap();
+        let page = FrameBuilder::new().metadata("https://example.com/").at(1_000).new_page().build();
+        let page = state.save_recording_stream(Cursor::new(upload(page))).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        for (id, expected) in [(scoped, Some("#support-widget")), (page, None)] {
+            let request = Request::get(format!("/recording/{}", id)).body(Body::empty()).unwrap();
+            let body = to_bytes(app.clone().oneshot(request).await.unwrap().into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let mut reader = FrameReader::new(Cursor::new(body.to_vec()), false);
+            let Some(Frame::PlaybackConfig(config)) = reader.read_frame().await.unwrap() else {
+                panic!("playback starts with a PlaybackConfig frame");
+            };
+            assert_eq!(config.root_selector.as_deref(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_timeline_interleaves_linked_recordings() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::{FrameBuilder, SessionLinkData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let upload = |frames: Vec<Frame>| {
+            let mut data = Vec::new();
+            let mut writer = FrameWriter::new(&mut data);
+            writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+            for frame in &frames {
+                writer.write_frame(frame).unwrap();
+            }
+            data
+        };
+        let link = || {
+            Frame::SessionLink(SessionLinkData {
+                session_id: "session-1".to_string(),
+            })
+        };
+        let first_tab = FrameBuilder::new()
+            .metadata("https://example.com/")
+            .frame(link())
+            .at(1_000)
+            .new_page()
+            .click(1, 1)
+            .at(3_000)
+            .click(3, 3)
+            .build();
+        let first_tab = state.save_recording_stream(Cursor::new(upload(first_tab))).await.unwrap();
+        let second_tab = FrameBuilder::new()
+            .metadata("https://example.com/cart")
+            .frame(link())
+            .at(2_000)
+            .new_page()
+            .click(2, 2)
+            .build();
+        let second_tab = state.save_recording_stream(Cursor::new(upload(second_tab))).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        let request = Request::get("/session/session-1/timeline").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let session: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(session["recordings"].as_array().unwrap().len(), 2);
+        let clicks: Vec<_> = session["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["kind"] == "click")
+            .map(|event| (event["recording_id"].as_str().unwrap().to_string(), event["x"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(
+            clicks,
+            [
+                (first_tab.to_string(), 1),
+                (second_tab.to_string(), 2),
+                (first_tab.to_string(), 3),
+            ]
+        );
+
+        let request = Request::get("/session/unknown/timeline").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_recording_meta_is_read_once_and_cached() {
+        use crate::recording_meta::{RecordingMeta, Viewport};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::FrameBuilder;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let frames = FrameBuilder::new()
+            .metadata("https://example.com/")
+            .at(1_000)
+            .viewport(1280, 720)
+            .new_page()
+            .wait(1_500)
+            .click(1, 2)
+            .build();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = state.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        let get_meta = || async {
+            let request = Request::get(format!("/recording/{}/meta", id)).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<RecordingMeta>(&body).unwrap()
+        };
+
+        let meta = get_meta().await;
+        assert_eq!(meta.duration_ms, 1_500);
+        assert_eq!(meta.frame_count, frames.len() as u64);
+        assert_eq!(meta.frame_counts["MouseClicked"], 1);
+        assert_eq!(meta.viewport, Some(Viewport { width: 1280, height: 720 }));
+        assert_eq!(meta.initial_url.as_deref(), Some("https://example.com/"));
+        assert_eq!(state.metadata_store.get_recording_meta(id.as_str()).await.unwrap(), Some(meta.clone()));
+
+        // Served from the cache while the size matches, read again once it doesn't
+        let cached = RecordingMeta { duration_ms: 7, ..meta.clone() };
+        state.metadata_store.store_recording_meta(id.as_str(), &cached).await.unwrap();
+        assert_eq!(get_meta().await.duration_ms, 7);
+        let stale = RecordingMeta { size: meta.size + 1, ..cached };
+        state.metadata_store.store_recording_meta(id.as_str(), &stale).await.unwrap();
+        assert_eq!(get_meta().await, meta);
+
+        let request = Request::get("/recording/missing.dcrr/meta").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_uploads_are_stored_uncompressed() {
+        use domcorder_proto::CanvasChangedData;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let canvas = Frame::CanvasChanged(CanvasChangedData {
+            node_id: 1,
+            mime_type: "image/png".to_string(),
+            data: vec![0; 4096],
+        });
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::with_timestamp(1_000).with_frame_compression(true)).unwrap();
+        writer.write_frame(&canvas).unwrap();
+        // Refused unless the server opts in
+        let refused = storage.save_recording_stream(Cursor::new(data.clone())).await;
+        assert!(matches!(refused, Err(crate::StorageError::Header(_))));
+        let storage = storage.with_compressed_uploads(true);
+        let id = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        // Playback streams the stored frames to players that can't decompress them
+        let saved = storage.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        assert!(!reader.read_header().await.unwrap().frame_compression());
+        assert_eq!(reader.read_frame().await.unwrap(), Some(canvas));
+    }
+
+    #[tokio::test]
+    async fn test_checksummed_recordings_are_set_aside_when_corrupt() {
+        use domcorder_proto::{ChecksumMismatch, FrameBuilder};
+
+        let (storage, temp_dir) = create_test_storage();
+        let storage = std::sync::Arc::new(storage.with_frame_checksums(true));
+        let frames = FrameBuilder::new().at(1_000).new_page().at(2_000).build();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::with_timestamp(1_000)).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = storage.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        let saved = storage.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved.clone()), true);
+        assert!(reader.read_header().await.unwrap().frame_checksums());
+        // Playback streams the frames without their checksums
+        let stream = storage.clone().get_recording_stream(&id).await.unwrap();
+        let mut reader = FrameReader::new(stream, false);
+        let mut played = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            played.push(frame);
+        }
+        assert_eq!(played, frames);
+
+        // A byte of the second frame flipped on disk
+        let path = temp_dir.path().join("recordings").join(id.as_str());
+        let mut corrupt = saved;
+        let second = 32 + 4 + u32::from_be_bytes(corrupt[32..36].try_into().unwrap()) as usize;
+        corrupt[second + 6] ^= 0xff;
+        std::fs::write(&path, &corrupt).unwrap();
+
+        let stream = storage.clone().get_recording_stream(&id).await.unwrap();
+        let mut reader = FrameReader::new(stream, false);
+        let error = loop {
+            match reader.read_frame().await {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("the corrupt frame was played"),
+                Err(e) => break e,
+            }
+        };
+        assert!(ChecksumMismatch::find(&error).is_some(), "{}", error);
+        assert!(!storage.recording_exists(&id).await);
+        assert!(path.with_file_name(format!("{}.corrupt", path.file_name().unwrap().to_string_lossy())).exists());
+    }
+
+    #[tokio::test]
+    async fn test_playback_starts_at_the_keyframe_before_a_timestamp() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::Request;
+        use domcorder_proto::{FrameBuilder, TimestampData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let recorded = FrameBuilder::new()
+            .at(1_000)
+            .new_page()
+            .at(2_000)
+            .new_page()
+            .at(5_000)
+            .build();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for frame in &recorded {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = state.save_recording_stream(Cursor::new(data)).await.unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback]);
+        let play = |uri: String| {
+            let app = app.clone();
+            async move {
+                let request = Request::get(uri).body(Body::empty()).unwrap();
+                let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+                let mut reader = FrameReader::new(Cursor::new(body.to_vec()), false).with_index(true);
+                let mut frames = Vec::new();
+                while let Some(frame) = reader.read_frame().await.unwrap() {
+                    frames.push(frame);
+                }
+                assert!(matches!(frames[0], Frame::PlaybackConfig(_)));
+                frames.split_off(1)
+            }
+        };
+
+        assert_eq!(play(format!("/recording/{}", id)).await, recorded);
+        assert_eq!(play(format!("/recording/{}?at=3000", id)).await, recorded[2..]);
+        // Before the first keyframe, playback starts there
+        assert_eq!(play(format!("/recording/{}?at=0", id)).await, recorded);
+        let ndjson = format!("/recording/{}.ndjson?at=9000", id);
+        let request = Request::get(ndjson).body(Body::empty()).unwrap();
+        let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3, "{:?}", lines);
+        assert_eq!(
+            lines[0],
+            serde_json::to_value(Frame::Timestamp(TimestampData { timestamp: 2_000 })).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_recordings_are_inspected_and_repaired() {
+        use crate::failed::{FailedRecording, FailureReport};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+
+        // Two frames, then bytes that don't parse
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for timestamp in [1000, 2000] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+        let valid = data.len() as u64;
+        data.extend([0, 0, 0, 9, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        std::fs::write(temp_dir.path().join("recordings/broken.dcrr.failed"), &data).unwrap();
+
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let get = |uri: &str| {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                (response.status(), to_bytes(response.into_body(), usize::MAX).await.unwrap())
+            }
+        };
+        let repair = || {
+            let request = Request::post("/recordings/failed/broken.dcrr/repair").body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let (status, body) = get("/recordings/failed").await;
+        assert_eq!(status, StatusCode::OK);
+        let failed: Vec<FailedRecording> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!((failed[0].id.as_str(), failed[0].size), ("broken.dcrr", data.len() as u64));
+
+        let (status, body) = get("/recordings/failed/broken.dcrr").await;
+        assert_eq!(status, StatusCode::OK);
+        let report: FailureReport = serde_json::from_slice(&body).unwrap();
+        assert_eq!((report.frames, report.offset), (2, valid));
+        assert!(report.error.is_some());
+
+        let (status, body) = get("/recordings/failed/broken.dcrr/raw").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), data.as_slice());
+        assert_eq!(get("/recordings/failed/missing.dcrr").await.0, StatusCode::NOT_FOUND);
+
+        let response = repair().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = RecordingId::new("broken.dcrr").unwrap();
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 2);
+        assert!(state.list_failed_recordings().await.unwrap().is_empty());
+        // The repaired recording now holds the id
+        assert_eq!(repair().await.unwrap().status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_failed_recording_keeps_its_key_when_the_id_is_reused() {
+        use crate::encryption::KeyRing;
+        use crate::failed::failed_key_id;
+        use domcorder_proto::TimestampData;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let metadata_store = SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap();
+        let asset_file_store =
+            LocalBinaryStore::new(temp_dir.path().join("assets"), "http://test.example".to_string()).unwrap();
+        let storage = StorageState::new(temp_dir.path().to_path_buf(), Box::new(metadata_store), Box::new(asset_file_store))
+            .unwrap()
+            .with_encryption(KeyRing::new("k1", [1; 32]));
+
+        // An encrypted recording whose session failed after two frames
+        let id = RecordingId::new("reused.dcrr").unwrap();
+        let mut writer = FrameWriter::new(storage.create_recording(&id).await.unwrap());
+        writer.write_header(&FileHeader::new()).unwrap();
+        for timestamp in [1000, 2000] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        storage.fail_recording(&id).await;
+        storage.mark_recording_completed(&id);
+
+        // A new session records under the same id, with a key of its own
+        let saved = storage
+            .save_recording_stream_raw(SAMPLE_FILE_DATA, None, Some(id.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(saved, id);
+        assert!(storage.get_recording(&id).await.unwrap().ends_with(SAMPLE_FILE_DATA));
+
+        // The failed one still decrypts, up to where it was cut off
+        let report = storage.inspect_failed_recording(&id).await.unwrap();
+        assert_eq!(report.frames, 2);
+        assert!(report.error.is_some());
+
+        storage.delete_recording(&id, None, None).await.unwrap();
+        assert_eq!(storage.repair_failed_recording(&id).await.unwrap().frames, 2);
+        let mut reader = storage.open_recording_reader(&id).await.unwrap();
+        let mut frames = 0;
+        while reader.read_frame().await.unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+        assert_eq!(storage.metadata_store.get_recording_key(&failed_key_id(&id)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_recordings_are_finalized() {
+        use crate::finalize::Finalization;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TimestampData;
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let finalize = |id: &str| {
+            let request = Request::post(format!("/recording/{}/finalize", id)).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                (response.status(), to_bytes(response.into_body(), usize::MAX).await.unwrap())
+            }
+        };
+        let mut timestamps = Vec::new();
+        let mut writer = FrameWriter::new(&mut timestamps);
+        for timestamp in [1000, 2000] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+
+        // A session whose recorder went quiet without closing the connection
+        let (mut input, pipe) = tokio::io::duplex(1024);
+        input.write_all(&timestamps).await.unwrap();
+        let save = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let filename = Some("quiet.dcrr".to_string());
+                state.save_recording_stream_frames_only_with_site_and_path(pipe, None, None, None, filename).await
+            }
+        });
+        let id = RecordingId::new("quiet.dcrr").unwrap();
+        while state.get_latest_timestamp(&id).is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let (_, mut viewer) = state.subscribe_live_frames(&id).unwrap();
+
+        let (status, body) = finalize("quiet.dcrr").await;
+        assert_eq!(status, StatusCode::OK);
+        let finalization: Finalization = serde_json::from_slice(&body).unwrap();
+        assert!(!finalization.forced);
+        assert_eq!(save.await.unwrap().unwrap(), id);
+        assert!(viewer.recv().await.is_err());
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut frames = 0;
+        while reader.read_frame().await.unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+        assert_eq!(finalize("quiet.dcrr").await.0, StatusCode::CONFLICT);
+        assert_eq!(finalize("missing.dcrr").await.0, StatusCode::NOT_FOUND);
+        drop(input);
+
+        // A session that doesn't respond at all is taken over
+        let id = RecordingId::new("wedged.dcrr").unwrap();
+        let mut writer = FrameWriter::new(state.create_recording(&id).await.unwrap());
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1000 })).unwrap();
+        writer.flush().unwrap();
+        let (_, mut viewer) = state.subscribe_live_frames(&id).unwrap();
+
+        let finalization = state.finalize_recording(&id, Duration::from_millis(100)).await.unwrap();
+        assert!(finalization.forced);
+        assert!(!state.is_recording_active(&id));
+        assert!(viewer.recv().await.is_err());
+        let summaries = state.metadata_store.get_recording_summaries(&[id.to_string()]).await.unwrap();
+        assert_eq!(summaries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_recordings_are_reconciled_at_startup() {
+        use domcorder_proto::TimestampData;
+
+        let (storage, temp_dir) = create_test_storage();
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for timestamp in [1000, 2000] {
+            writer.write_frame(&Frame::Timestamp(TimestampData { timestamp })).unwrap();
+        }
+
+        // What a crashed server leaves behind: each recording with its lock file
+        let recordings = temp_dir.path().join("recordings");
+        for (name, bytes) in [("intact.dcrr", &data[..]), ("partial.dcrr", &data[..data.len() - 3])] {
+            std::fs::write(recordings.join(name), bytes).unwrap();
+            std::fs::write(recordings.join(format!("{}.lock", name)), b"").unwrap();
+        }
+
+        let reconciliation = storage.reconcile_recordings().await.unwrap();
+        assert_eq!(reconciliation.finalized, vec!["intact.dcrr"]);
+        assert_eq!(reconciliation.repaired, vec!["partial.dcrr"]);
+        assert!(reconciliation.failed.is_empty());
+        assert!(storage.list_failed_recordings().await.unwrap().is_empty());
+
+        let partial = RecordingId::new("partial.dcrr").unwrap();
+        let mut reader = storage.open_recording_reader(&partial).await.unwrap();
+        let mut frames = 0;
+        while reader.read_frame().await.unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 1);
+        let ids = ["intact.dcrr".to_string(), "partial.dcrr".to_string()];
+        assert_eq!(storage.metadata_store.get_recording_summaries(&ids).await.unwrap().len(), 2);
+
+        // Nothing is left to reconcile
+        assert_eq!(storage.reconcile_recordings().await.unwrap(), Default::default());
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_storage_health() {
+        use crate::health::{HealthStatus, StorageHealth};
+        use crate::observability::{Labels, ObservabilityHooks, names};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use std::sync::{Arc, Mutex};
+        use tower::ServiceExt;
+
+        #[derive(Default)]
+        struct Probes(Mutex<Vec<String>>);
+
+        impl ObservabilityHooks for Probes {
+            fn histogram(&self, name: &'static str, _value: f64, labels: Labels<'_>) {
+                if name == names::STORAGE_HEALTH_SECONDS {
+                    let labels: Vec<_> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    self.0.lock().unwrap().push(labels.join(","));
+                }
+            }
+        }
+
+        let probes = Arc::new(Probes::default());
+        let (storage, temp_dir) = create_test_storage();
+        let storage = storage.with_observability(Box::new(probes.clone()));
+        let app = DomcorderRouter::new(Arc::new(storage)).routes(&[RouteGroup::Health]);
+        let readyz = || async {
+            let response = app.clone().oneshot(Request::get("/readyz").body(Body::empty()).unwrap()).await.unwrap();
+            let status = response.status();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<StorageHealth>(&body).unwrap())
+        };
+
+        let (status, health) = readyz().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(health.metadata.status, HealthStatus::Ok);
+        assert_eq!(health.assets.status, HealthStatus::Ok);
+        assert_eq!(health.recordings.status, HealthStatus::Ok);
+        assert!(probes.0.lock().unwrap().contains(&"store=metadata,status=ok".to_string()));
+
+        // The recordings directory went away under the server
+        std::fs::remove_dir_all(temp_dir.path().join("recordings")).unwrap();
+        let (status, health) = readyz().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.recordings.status, HealthStatus::Failing);
+        assert!(health.recordings.error.is_some());
+        assert_eq!(health.metadata.status, HealthStatus::Ok);
+        assert!(probes.0.lock().unwrap().contains(&"store=recordings,status=failing".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_unreferenced_assets_are_collected() {
+        use crate::asset_cache::AssetMetadata;
+        use crate::asset_refs::AssetCollection;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::AssetReferenceData;
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        for name in ["ingested", "legacy", "orphan"] {
+            let sha256 = format!("sha-{}", name);
+            storage.asset_file_store.put(&sha256, name.as_bytes(), "image/png").await.unwrap();
+            storage
+                .metadata_store
+                .store_asset_metadata(AssetMetadata {
+                    sha256_hash: sha256,
+                    random_id: name.to_string(),
+                    size: name.len() as u64,
+                    mime_type: "image/png".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        let recording = |hash: &str| {
+            let mut data = Vec::new();
+            let mut writer = FrameWriter::new(&mut data);
+            writer.write_header(&FileHeader::new()).unwrap();
+            let reference = AssetReferenceData {
+                asset_id: 1,
+                url: format!("https://example.com/{}.png", hash),
+                hash: hash.to_string(),
+                mime: None,
+            };
+            writer.write_frame(&Frame::AssetReference(reference)).unwrap();
+            data
+        };
+        let ingested = storage.save_recording(&recording("ingested")).await.unwrap();
+        // Stored before references were tracked
+        std::fs::write(temp_dir.path().join("recordings/legacy.dcrr"), recording("legacy")).unwrap();
+
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let collect = || async {
+            let request = Request::post("/admin/assets/gc").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<AssetCollection>(&body).unwrap()
+        };
+
+        let collection = collect().await;
+        assert_eq!(collection, AssetCollection { indexed: 2, unreferenced: 1, removed: 1 });
+        assert_eq!(state.metadata_store.get_asset_metadata("orphan").await.unwrap(), None);
+        assert!(!state.asset_file_store.exists("sha-orphan").await.unwrap());
+        for name in ["ingested", "legacy"] {
+            assert!(state.metadata_store.get_asset_metadata(name).await.unwrap().is_some());
+        }
+
+        // Deleting its last recording releases an asset; the index isn't rebuilt
+        state.delete_recording(&ingested, None, None).await.unwrap();
+        let collection = collect().await;
+        assert_eq!(collection, AssetCollection { indexed: 0, unreferenced: 1, removed: 1 });
+        assert!(!state.asset_file_store.exists("sha-ingested").await.unwrap());
+        assert!(state.asset_file_store.exists("sha-legacy").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_url_history_lists_each_version() {
+        use crate::asset_cache::{AssetMetadata, AssetUsageParams, UrlVersion};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let url = "https://example.com/app.css?v=1";
+        for (site, sha256_hash) in [("https://example.com", "sha-old"), ("https://shop.example", "sha-new")] {
+            storage
+                .metadata_store
+                .register_asset_usage(AssetUsageParams {
+                    site_origin: site.to_string(),
+                    url: url.to_string(),
+                    sha256_hash: sha256_hash.to_string(),
+                    size: 1,
+                })
+                .await
+                .unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        // The old version has since been collected
+        storage
+            .metadata_store
+            .store_asset_metadata(AssetMetadata {
+                sha256_hash: "sha-new".to_string(),
+                random_id: "new".to_string(),
+                size: 1,
+                mime_type: "text/css".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage)).routes(&[RouteGroup::Admin]);
+        let history = |url: &str| {
+            let request = Request::get(format!("/admin/assets/url-history?url={}", url.replace('?', "%3F").replace('=', "%3D")))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<Vec<UrlVersion>>(&body).unwrap()
+            }
+        };
+
+        let versions = history(url).await;
+        let summary: Vec<_> = versions.iter().map(|v| (v.sha256_hash.as_str(), v.cached)).collect();
+        assert_eq!(summary, [("sha-new", true), ("sha-old", false)]);
+        assert!(versions[0].first_seen_at > versions[1].last_seen_at);
+        assert!(history("https://example.com/other.css").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_asset_changes_between_dates() {
+        use crate::asset_cache::AssetUsageParams;
+        use crate::asset_cache::versions::AssetChange;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let use_asset = |site: &str, url: &str, sha256_hash: &str| AssetUsageParams {
+            site_origin: site.to_string(),
+            url: url.to_string(),
+            sha256_hash: sha256_hash.to_string(),
+            size: 1,
+        };
+        let store = &storage.metadata_store;
+        let site = "https://example.com";
+        store.register_asset_usage(use_asset(site, "https://example.com/app.css", "css-1")).await.unwrap();
+        store.register_asset_usage(use_asset(site, "https://example.com/app.js", "js-1")).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let before_deploy = chrono::Utc::now();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        store.register_asset_usage(use_asset(site, "https://example.com/app.css", "css-2")).await.unwrap();
+        store.register_asset_usage(use_asset(site, "https://example.com/app.js", "js-1")).await.unwrap();
+        // Another site's assets are not compared
+        store.register_asset_usage(use_asset("https://shop.example", "https://shop.example/a.css", "a-1")).await.unwrap();
+
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage)).routes(&[RouteGroup::Admin]);
+        let changes = |query: String| {
+            let request = Request::get(format!("/admin/assets/changes?{}", query)).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<Vec<AssetChange>>(&body).ok())
+            }
+        };
+        let at = |time: chrono::DateTime<chrono::Utc>| time.to_rfc3339().replace('+', "%2B");
+
+        let (status, found) = changes(format!("site=https://example.com/&from={}", at(before_deploy))).await;
+        assert_eq!(status, StatusCode::OK);
+        let found = found.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].url, "https://example.com/app.css");
+        assert_eq!(found[0].before.as_ref().unwrap().sha256_hash, "css-1");
+        assert_eq!(found[0].after.sha256_hash, "css-2");
+        assert!(found[0].after.first_seen_at > before_deploy);
+
+        // Nothing changed before the deploy
+        let (_, found) = changes(format!("site={}&from={}&to={}", site, at(before_deploy), at(before_deploy))).await;
+        assert_eq!(found.unwrap(), []);
+        let later = before_deploy + chrono::Duration::hours(1);
+        let (status, _) = changes(format!("site={}&from={}&to={}", site, at(later), at(before_deploy))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_site_profiles_group_origins() {
+        use crate::asset_cache::{AssetMetadata, AssetUsageParams};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use crate::sites::SiteProfile;
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        // The logo is mostly loaded on www, the font on the apex domain
+        for (origin, name, uses) in [
+            ("https://www.shop.example", "logo", 3),
+            ("https://shop.example", "logo", 1),
+            ("https://shop.example", "font", 2),
+        ] {
+            storage
+                .metadata_store
+                .store_asset_metadata(AssetMetadata {
+                    sha256_hash: format!("sha-{}", name),
+                    random_id: name.to_string(),
+                    size: 1,
+                    mime_type: "image/png".to_string(),
+                })
+                .await
+                .unwrap();
+            for _ in 0..uses {
+                storage
+                    .metadata_store
+                    .register_asset_usage(AssetUsageParams {
+                        site_origin: origin.to_string(),
+                        url: format!("https://cdn.example/{}", name),
+                        sha256_hash: format!("sha-{}", name),
+                        size: 1,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+        let manifest = |origin: &'static str| {
+            let store = &storage.metadata_store;
+            async move {
+                let entries = store.get_site_manifest(origin, 10).await.unwrap();
+                entries.into_iter().map(|entry| entry.sha256_hash).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(manifest("https://www.shop.example").await, ["sha-logo"]);
+
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let put = |profile: serde_json::Value| {
+            let request = Request::put("/admin/sites")
+                .header("content-type", "application/json")
+                .body(Body::from(profile.to_string()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = put(serde_json::json!({
+            "site": "https://shop.example/",
+            "aliases": ["https://www.shop.example/home", "https://shop.example"],
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let expected = SiteProfile {
+            site: "https://shop.example".to_string(),
+            aliases: vec!["https://www.shop.example".to_string()],
+        };
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<SiteProfile>(&body).unwrap(), expected);
+        assert_eq!(state.metadata_store.list_site_profiles().await.unwrap(), [expected]);
+
+        // Usage on either origin counts for both
+        let manifest = |origin: &'static str| {
+            let state = state.clone();
+            async move {
+                let entries = state.metadata_store.get_site_manifest(origin, 10).await.unwrap();
+                entries.into_iter().map(|entry| entry.sha256_hash).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(manifest("https://www.shop.example").await, ["sha-logo", "sha-font"]);
+        assert_eq!(manifest("https://shop.example").await, ["sha-logo", "sha-font"]);
+
+        // www already belongs to shop.example
+        let response = put(serde_json::json!({
+            "site": "https://staging.shop.example",
+            "aliases": ["https://www.shop.example"],
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = put(serde_json::json!({ "site": "https://shop.example", "aliases": [] })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let delete = || {
+            let request = Request::delete("/admin/sites?site=https%3A%2F%2Fshop.example")
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        assert_eq!(delete().await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(delete().await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(manifest("https://www.shop.example").await, ["sha-logo"]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_assets_fall_back_to_their_original_url() {
+        use crate::asset_cache::AssetMetadata;
+        use crate::asset_cache::playback::ORIGINAL_URL_FALLBACK;
+        use domcorder_proto::{AssetFetchError, AssetReferenceData};
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_original_url_fallback(true);
+        for name in ["kept", "lost"] {
+            let sha256 = format!("sha-{}", name);
+            storage.asset_file_store.put(&sha256, name.as_bytes(), "image/png").await.unwrap();
+            storage
+                .metadata_store
+                .store_asset_metadata(AssetMetadata {
+                    sha256_hash: sha256,
+                    random_id: name.to_string(),
+                    size: name.len() as u64,
+                    mime_type: "image/png".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        // Its file went missing after it was recorded
+        storage.asset_file_store.delete("sha-lost").await.unwrap();
+
+        let transformer = storage.playback_transformer();
+        let play = |name: &str| {
+            transformer.transform_frame(Frame::AssetReference(AssetReferenceData {
+                asset_id: 1,
+                url: format!("https://example.com/{}.png", name),
+                hash: name.to_string(),
+                mime: None,
+            }))
+        };
+        let Frame::Asset(kept) = play("kept").await.unwrap() else {
+            panic!("expected an Asset frame");
+        };
+        assert_eq!((kept.url.as_str(), &kept.fetch_error), ("/assets/kept", &AssetFetchError::None));
+        for name in ["lost", "never-cached", "lost"] {
+            let Frame::Asset(missing) = play(name).await.unwrap() else {
+                panic!("expected an Asset frame");
+            };
+            assert_eq!(missing.url, format!("https://example.com/{}.png", name));
+            assert_eq!(missing.fetch_error, AssetFetchError::Unknown(ORIGINAL_URL_FALLBACK.to_string()));
+        }
+
+        let misses = storage.metadata_store.list_asset_misses().await.unwrap();
+        let mut misses: Vec<_> = misses.iter().map(|miss| (miss.random_id.as_str(), miss.count)).collect();
+        misses.sort();
+        assert_eq!(misses, [("lost", 2), ("never-cached", 1)]);
+
+        // Off by default: the player is sent the cache's URL
+        let (storage, _temp_dir) = create_test_storage();
+        let frame = Frame::AssetReference(AssetReferenceData {
+            asset_id: 1,
+            url: "https://example.com/lost.png".to_string(),
+            hash: "lost".to_string(),
+            mime: None,
+        });
+        let Frame::Asset(missing) = storage.playback_transformer().transform_frame(frame).await.unwrap() else {
+            panic!("expected an Asset frame");
+        };
+        assert_eq!(missing.url, "/assets/lost");
+    }
+
+    #[tokio::test]
+    async fn test_replica_mirrors_assets_and_recordings() {
+        use crate::recording_store::RecordingStore;
+        use std::io::Write;
+        use crate::recording_store::local::LocalRecordingStore;
+        use crate::replica::Replica;
+        use tokio::io::AsyncReadExt;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let (storage, temp_dir) = create_test_storage();
+        // Saved before the replica was configured
+        let old = storage.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+
+        let replica_dir = temp_dir.path().join("replica");
+        let replica_assets = LocalBinaryStore::new(replica_dir.join("assets"), "http://test.example".to_string()).unwrap();
+        let replica_recordings = LocalRecordingStore::new(replica_dir.join("recordings")).unwrap();
+        let storage = storage.with_replica(Replica {
+            assets: Box::new(LocalBinaryStore::new(replica_dir.join("assets"), "http://test.example".to_string()).unwrap()),
+            recordings: Box::new(LocalRecordingStore::new(replica_dir.join("recordings")).unwrap()),
+        });
+        let state = std::sync::Arc::new(storage);
+        let caught_up = || async {
+            for _ in 0..200 {
+                if state.replication.as_ref().unwrap().status().pending == 0 {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            panic!("the replica never caught up");
+        };
+
+        let new = state.save_recording(SAMPLE_FILE_DATA).await.unwrap();
+        state.asset_file_store.put("sha-logo", b"logo", "image/png").await.unwrap();
+        caught_up().await;
+        assert_eq!(replica_assets.get("sha-logo").await.unwrap(), b"logo");
+        let mut copied = Vec::new();
+        replica_recordings.open(&new, 0).await.unwrap().read_to_end(&mut copied).await.unwrap();
+        assert_eq!(copied, state.get_recording(&new).await.unwrap());
+        assert!(!replica_recordings.exists(&old).await.unwrap());
+
+        // Reconciling copies what was written before
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let request = Request::post("/admin/replica/reconcile").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report, serde_json::json!({"assets_queued": 0, "recordings_queued": 1, "deletions_queued": 0}));
+        caught_up().await;
+        assert!(replica_recordings.exists(&old).await.unwrap());
+
+        // A recording rewritten in place whose queued copy was lost, as in a
+        // restart, is copied again
+        let primary = LocalRecordingStore::new(temp_dir.path().join("recordings")).unwrap();
+        let rewrite = RecordingId::new("rewrite.dcrr").unwrap();
+        let mut writer = primary.create(&rewrite).await.unwrap();
+        writer.write_all(&SAMPLE_FILE_DATA[..SAMPLE_FILE_DATA.len() / 2]).unwrap();
+        writer.finish().unwrap();
+        primary.rename(&rewrite, &old).await.unwrap();
+        let request = Request::post("/admin/replica/reconcile").body(Body::empty()).unwrap();
+        let body = to_bytes(app.clone().oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report, serde_json::json!({"assets_queued": 0, "recordings_queued": 1, "deletions_queued": 0}));
+        caught_up().await;
+        let mut copied = Vec::new();
+        replica_recordings.open(&old, 0).await.unwrap().read_to_end(&mut copied).await.unwrap();
+        assert_eq!(copied, &SAMPLE_FILE_DATA[..SAMPLE_FILE_DATA.len() / 2]);
+
+        // Nothing is queued once the replica is current
+        let request = Request::post("/admin/replica/reconcile").body(Body::empty()).unwrap();
+        let body = to_bytes(app.clone().oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report, serde_json::json!({"assets_queued": 0, "recordings_queued": 0, "deletions_queued": 0}));
+
+        // Deletions follow
+        state.recording_store.delete(&new).await.unwrap();
+        caught_up().await;
+        assert!(!replica_recordings.exists(&new).await.unwrap());
+        let request = Request::get("/admin/replica").body(Body::empty()).unwrap();
+        let body = to_bytes(app.oneshot(request).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!((&status["pending"], &status["failures"]), (&serde_json::json!(0), &serde_json::json!(0)));
+
+        let (storage, _temp_dir) = create_test_storage();
+        let app = DomcorderRouter::new(std::sync::Arc::new(storage)).routes(&[RouteGroup::Admin]);
+        let request = Request::get("/admin/replica").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_nodes_share_active_recordings() {
+        use crate::cluster::ClusterConfig;
+        use std::time::Duration;
+
+        let (storage, temp_dir) = create_test_storage();
+        let node = |storage: StorageState, node_id: &str| {
+            storage.with_cluster(ClusterConfig {
+                node_id: node_id.to_string(),
+                lease: Duration::from_secs(30),
+            })
+        };
+        let a = node(storage, "a");
+        // A second server sharing the storage and the metadata database
+        let b = node(
+            StorageState::new(
+                temp_dir.path().to_path_buf(),
+                Box::new(SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap()),
+                Box::new(LocalBinaryStore::new(temp_dir.path().join("assets"), "http://test.example".to_string()).unwrap()),
+            )
+            .unwrap(),
+            "b",
+        );
+
+        let id = RecordingId::new("live.dcrr").unwrap();
+        let writer = a.create_recording(&id).await.unwrap();
+        a.update_recording_timestamp(&id, 1234);
+        a.sync_active_registry().await.unwrap();
+        assert!(matches!(b.create_recording(&id).await, Err(crate::StorageError::InUse(_))));
+        assert!(!b.is_recording_active(&id));
+        b.sync_active_registry().await.unwrap();
+        assert!(b.is_recording_active(&id));
+        assert_eq!(b.get_latest_timestamp(&id), Some(1234));
+
+        writer.finish().unwrap();
+        a.mark_recording_completed(&id);
+        a.sync_active_registry().await.unwrap();
+        b.sync_active_registry().await.unwrap();
+        assert!(!b.is_recording_active(&id));
+
+        // The lease of a server that stopped without releasing it expires
+        let crashed = a.with_cluster(ClusterConfig {
+            node_id: "crashed".to_string(),
+            lease: Duration::from_millis(50),
+        });
+        let id = RecordingId::new("orphan.dcrr").unwrap();
+        let writer = crashed.create_recording(&id).await.unwrap();
+        b.sync_active_registry().await.unwrap();
+        assert!(b.is_recording_active(&id));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!b.is_recording_active(&id));
+        // The OS releases its file lock along with the process
+        drop(writer);
+        b.create_recording(&id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_live_recordings_are_relayed_between_nodes() {
+        relay_live_recording(std::sync::Arc::new(crate::relay::LocalRelay::default())).await;
+    }
+
+    #[cfg(feature = "nats")]
+    #[tokio::test]
+    async fn test_live_recordings_are_relayed_over_nats() {
+        use crate::nats::NatsRelay;
+        use crate::nats::tests::fake_nats_server;
+
+        let client = async_nats::connect(fake_nats_server().await).await.unwrap();
+        relay_live_recording(std::sync::Arc::new(NatsRelay::new(client))).await;
+    }
+
+    /// Play a recording live on one server while another writes it
+    async fn relay_live_recording(relay: std::sync::Arc<dyn crate::relay::FrameRelay>) {
+        use crate::cluster::ClusterConfig;
+        use crate::relay::run_frame_relay;
+        use domcorder_proto::{AnnotationData, TimestampData};
+        use std::io::Write;
+        use std::sync::Arc;
+
+        // Two servers sharing the metadata database but not the recordings
+        let node = |storage: StorageState, node_id: &str| {
+            storage
+                .with_cluster(ClusterConfig {
+                    node_id: node_id.to_string(),
+                    ..ClusterConfig::default()
+                })
+                .with_frame_relay(relay.clone())
+        };
+        let (a, temp_dir) = create_test_storage();
+        let a = Arc::new(node(a, "a"));
+        let b_dir = tempfile::tempdir().unwrap();
+        let b = Arc::new(node(
+            StorageState::new(
+                b_dir.path().to_path_buf(),
+                Box::new(SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap()),
+                Box::new(LocalBinaryStore::new(b_dir.path().join("assets"), "http://test.example".to_string()).unwrap()),
+            )
+            .unwrap(),
+            "b",
+        ));
+        tokio::spawn(run_frame_relay(a.clone()));
+        tokio::spawn(run_frame_relay(b.clone()));
+        tokio::task::yield_now().await;
+
+        let id = RecordingId::new("live.dcrr").unwrap();
+        let mut writer = a.create_recording(&id).await.unwrap();
+        let mut frames = FrameWriter::new(Vec::new());
+        frames.write_header(&FileHeader::new()).unwrap();
+        frames.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1 })).unwrap();
+        writer.write_all(&frames.into_inner()).unwrap();
+        writer.flush().unwrap();
+        b.sync_active_registry().await.unwrap();
+
+        let mut reader = FrameReader::new(b.clone().get_recording_stream(&id).await.unwrap(), false);
+        assert!(matches!(reader.read_frame().await.unwrap(), Some(Frame::Timestamp(_))));
+
+        // Frames written afterwards arrive as they are written
+        let mut frames = FrameWriter::new(Vec::new());
+        let annotation = AnnotationData {
+            name: "checkout".to_string(),
+            data: None,
+        };
+        frames.write_frame(&Frame::Annotation(annotation.clone())).unwrap();
+        writer.write_all(&frames.into_inner()).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(reader.read_frame().await.unwrap(), Some(Frame::Annotation(annotation)));
+
+        writer.finish().unwrap();
+        a.mark_recording_completed(&id);
+        assert_eq!(reader.read_frame().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_persist_and_resume() {
+        use crate::jobs::{Job, JobRequest, JobStatus, resume_jobs};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode, header};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage.with_max_concurrent_jobs(1));
+        let mut ids = Vec::new();
+        for timestamp in [1_000, 2_000] {
+            let mut data = Vec::new();
+            FrameWriter::new(&mut data)
+                .write_frame(&Frame::Timestamp(TimestampData { timestamp }))
+                .unwrap();
+            ids.push(state.save_recording_stream_frames_only(Cursor::new(data)).await.unwrap());
+        }
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+        let finished = |id: String| {
+            let app = app.clone();
+            async move {
+                loop {
+                    let request = Request::get(format!("/jobs/{}", id)).body(Body::empty()).unwrap();
+                    let response = app.clone().oneshot(request).await.unwrap();
+                    assert_eq!(response.status(), StatusCode::OK);
+                    let job: Job = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                    if job.status == JobStatus::Finished {
+                        return job;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            }
+        };
+
+        let body = serde_json::json!({"kind": "bulk", "operation": "tag", "tags": ["triage"], "ids": [ids[0].as_str()]});
+        let request = Request::post("/jobs")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let job: Job = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        let job = finished(job.id).await;
+        assert_eq!(job.results.len(), 1);
+        assert_eq!(job.results[0].error, None);
+        assert_eq!(state.recording_info(&ids[0]).await.unwrap().tags, ["triage"]);
+
+        // A job a previous run stopped partway through resumes where it was
+        let JobRequest::Bulk(mut request) = job.request.clone() else {
+            panic!("expected a bulk job");
+        };
+        request.ids = Some(ids.iter().map(|id| id.to_string()).collect());
+        let interrupted = Job {
+            id: "interrupted".to_string(),
+            request: JobRequest::Bulk(request),
+            status: JobStatus::Running,
+            items: ids.iter().map(|id| id.to_string()).collect(),
+            results: job.results.clone(),
+            created: chrono::Utc::now(),
+            started: Some(chrono::Utc::now()),
+            finished: None,
+        };
+        state.metadata_store.save_job(&interrupted).await.unwrap();
+        assert_eq!(resume_jobs(&state).await.unwrap(), 1);
+        let resumed = finished("interrupted".to_string()).await;
+        let items: Vec<_> = resumed.results.iter().map(|result| result.item.as_str()).collect();
+        assert_eq!(items, [ids[0].as_str(), ids[1].as_str()]);
+        assert_eq!(state.recording_info(&ids[1]).await.unwrap().tags, ["triage"]);
+        assert!(state.metadata_store.list_unfinished_jobs().await.unwrap().is_empty());
+
+        let request = Request::get("/jobs/unknown").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_import_recording() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::{AssetData, AssetFetchError, TimestampData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1_000 })).unwrap();
+        writer
+            .write_frame(&Frame::Asset(AssetData {
+                asset_id: 1,
+                url: "https://example.com/logo.png".to_string(),
+                mime: Some("image/png".to_string()),
+                buf: vec![7; 32],
+                fetch_error: AssetFetchError::None,
+            }))
+            .unwrap();
+
+        let request = Request::post("/recordings/import?subdir=imported&filename=offline.dcrr&site=https://example.com")
+            .body(Body::from(data))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["id"], "imported/offline.dcrr");
+
+        // The embedded asset went to the asset cache
+        let id = RecordingId::new("imported/offline.dcrr").unwrap();
+        let saved = state.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        assert!(matches!(reader.read_frame().await.unwrap(), Some(Frame::Timestamp(_))));
+        let Some(Frame::AssetReference(reference)) = reader.read_frame().await.unwrap() else {
+            panic!("expected an AssetReference");
+        };
+        assert_eq!(reference.url, "https://example.com/logo.png");
+        let sha256 = crate::asset_cache::hash::sha256(&[7u8; 32]);
+        assert!(state.asset_file_store.exists(&sha256).await.unwrap());
+
+        // And the recording is listed with its summary
+        assert_eq!(state.recording_info(&id).await.unwrap().frame_count, Some(2));
+
+        // Header-less frames aren't a complete recording
+        let mut frames = Vec::new();
+        FrameWriter::new(&mut frames)
+            .write_frame(&Frame::Timestamp(TimestampData { timestamp: 1_000 }))
+            .unwrap();
+        let request = Request::post("/recordings/import?filename=broken.dcrr").body(Body::from(frames)).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert!(!state.recording_exists(&RecordingId::new("broken.dcrr").unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn test_extract_assets_job_rewrites_legacy_recordings() {
+        use crate::asset_cache::limits::AssetLimits;
+        use crate::jobs::{Job, JobStatus};
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode, header};
+        use domcorder_proto::{AssetData, AssetFetchError, TimestampData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_asset_limits(AssetLimits {
+            max_asset_size: Some(64),
+            max_recording_bytes: None,
+        });
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin]);
+
+        // Written as an old server did, with the asset bytes inline
+        let asset = |asset_id: u32, size: usize| {
+            Frame::Asset(AssetData {
+                asset_id,
+                url: format!("https://example.com/{}.png", asset_id),
+                mime: Some("image/png".to_string()),
+                buf: vec![asset_id as u8; size],
+                fetch_error: AssetFetchError::None,
+            })
+        };
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1_000 })).unwrap();
+        writer.write_frame(&asset(1, 48)).unwrap();
+        writer.write_frame(&asset(2, 128)).unwrap();
+        let id = state.save_recording(&data).await.unwrap();
+
+        let run = |app: axum::Router| {
+            let body = serde_json::json!({"kind": "extract_assets", "ids": [id.as_str()]});
+            async move {
+                let request = Request::post("/jobs")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::ACCEPTED);
+                let job: Job = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                loop {
+                    let request = Request::get(format!("/jobs/{}", job.id)).body(Body::empty()).unwrap();
+                    let response = app.clone().oneshot(request).await.unwrap();
+                    let job: Job = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                    if job.status == JobStatus::Finished {
+                        return job;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+            }
+        };
+
+        let job = run(app.clone()).await;
+        assert_eq!(job.results[0].error, None);
+        assert_eq!(job.results[0].output.as_deref(), Some("1 assets extracted"));
+
+        let saved = state.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved.clone()), true);
+        reader.read_header().await.unwrap();
+        assert!(matches!(reader.read_frame().await.unwrap(), Some(Frame::Timestamp(_))));
+        let Some(Frame::AssetReference(reference)) = reader.read_frame().await.unwrap() else {
+            panic!("expected an AssetReference");
+        };
+        assert_eq!(reference.url, "https://example.com/1.png");
+        assert!(state.asset_file_store.exists(&crate::asset_cache::hash::sha256(&[1u8; 48])).await.unwrap());
+        // Over the asset limit, so kept inline
+        assert_eq!(reader.read_frame().await.unwrap(), Some(asset(2, 128)));
+        // Only the asset over the limit is left inline. The file itself need not
+        // shrink: a reference and the v2 seek index can outweigh a small asset.
+        let (mut inline, mut references) = (Vec::new(), 0);
+        let mut reader = FrameReader::new(Cursor::new(saved.clone()), true);
+        reader.read_header().await.unwrap();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            match frame {
+                Frame::Asset(asset) if !asset.buf.is_empty() => inline.push(asset.asset_id),
+                Frame::AssetReference(_) => references += 1,
+                _ => {}
+            }
+        }
+        assert_eq!((inline, references), (vec![2], 1));
+        assert!(state.metadata_store.unreferenced_assets(None).await.unwrap().is_empty());
+        // The rewrite replaced the recording rather than sitting beside it
+        assert!(!state.recording_exists(&RecordingId::new(format!("{}.rewrite", id)).unwrap()).await);
+        assert_eq!(state.list_all_recordings(None).await.unwrap().len(), 1);
+
+        // Nothing left to extract
+        let job = run(app).await;
+        assert_eq!(job.results[0].error, None);
+        assert_eq!(job.results[0].output, None);
+        assert_eq!(state.get_recording(&id).await.unwrap(), saved);
+    }
+
+    #[tokio::test]
+    async fn test_provenance_of_derived_recordings() {
+        use crate::bulk::BulkRequest;
+        use crate::jobs::{JobRequest, JobStatus, submit_job};
+        use crate::provenance::Provenance;
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::TimestampData;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Admin, RouteGroup::Playback]);
+
+        // A file trimmed offline, as `dcrr trim` writes it
+        let trimmed = Provenance::new("dcrr/0.1.0", "trim", vec!["original.dcrr".to_string()]);
+        let mut data = Vec::new();
+        let mut writer = FrameWriter::new(&mut data);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&trimmed.to_frame()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1_000 })).unwrap();
+        let request = Request::post("/recordings/import?filename=trimmed.dcrr").body(Body::from(data)).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::CREATED);
+        let id = RecordingId::new("trimmed.dcrr").unwrap();
+        assert_eq!(state.recording_provenance(&id).await.unwrap(), std::slice::from_ref(&trimmed));
+
+        // Redacting it adds a step, and keeps the earlier ones
+        let request: BulkRequest = serde_json::from_value(serde_json::json!({"operation": "redact", "ids": ["trimmed.dcrr"]})).unwrap();
+        let job = submit_job(&state, JobRequest::Bulk(request)).await.unwrap();
+        while state.job(&job.id).await.unwrap().unwrap().status != JobStatus::Finished {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let provenance = state.recording_provenance(&id).await.unwrap();
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0], trimmed);
+        assert_eq!(provenance[1].operation, "redact");
+        assert_eq!(provenance[1].sources, ["trimmed.dcrr"]);
+        assert_eq!(provenance[1].tool, crate::provenance::SERVER_TOOL);
+
+        // The redacted file still says where it came from
+        let saved = state.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let first = reader.read_frame().await.unwrap().unwrap();
+        assert_eq!(Provenance::from_frame(&first), Some(trimmed));
+
+        let request = Request::get("/recording/trimmed.dcrr/info").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let info: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(info["provenance"][0]["operation"], "trim");
+        assert_eq!(info["provenance"][1]["operation"], "redact");
+    }
+
+    #[tokio::test]
+    async fn test_watch_folder_imports_and_rejects() {
+        use crate::watch_folder::{WatchFolderConfig, scan_watch_folder};
+        use domcorder_proto::TimestampData;
+        use std::time::Duration;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let watch_dir = tempfile::tempdir().unwrap();
+        let mut config = WatchFolderConfig::new(watch_dir.path().to_path_buf());
+        config.settle = Duration::ZERO;
+
+        let mut good = Vec::new();
+        let mut writer = FrameWriter::new(&mut good);
+        writer.write_header(&FileHeader::new()).unwrap();
+        writer.write_frame(&Frame::Timestamp(TimestampData { timestamp: 1_000 })).unwrap();
+        writer.write_index().unwrap();
+        std::fs::write(watch_dir.path().join("offline.dcrr"), &good).unwrap();
+        std::fs::write(watch_dir.path().join("corrupt.dcrr"), b"not a recording").unwrap();
+        // Still being copied in, or not a recording at all
+        std::fs::write(watch_dir.path().join(".copying.dcrr"), &good).unwrap();
+        std::fs::write(watch_dir.path().join("notes.txt"), b"hello").unwrap();
+
+        let scan = scan_watch_folder(&state, &config).await.unwrap();
+        assert_eq!(scan.imported, ["offline.dcrr"]);
+        assert_eq!(scan.rejected, ["corrupt.dcrr"]);
+        let id = RecordingId::new("offline.dcrr").unwrap();
+        assert_eq!(state.get_recording(&id).await.unwrap(), good);
+        assert!(!watch_dir.path().join("offline.dcrr").exists());
+        assert!(!watch_dir.path().join("corrupt.dcrr").exists());
+        assert_eq!(std::fs::read(config.rejects_dir.join("corrupt.dcrr")).unwrap(), b"not a recording");
+        let reason = std::fs::read_to_string(config.rejects_dir.join("corrupt.dcrr.error.txt")).unwrap();
+        assert!(reason.contains("header"), "{}", reason);
+        assert!(watch_dir.path().join(".copying.dcrr").exists());
+        assert!(watch_dir.path().join("notes.txt").exists());
+
+        // A name that's already taken is rejected rather than overwritten
+        std::fs::write(watch_dir.path().join("offline.dcrr"), &good).unwrap();
+        let scan = scan_watch_folder(&state, &config).await.unwrap();
+        assert_eq!(scan.rejected, ["offline.dcrr"]);
+
+        // Files that haven't settled wait for a later scan
+        std::fs::write(watch_dir.path().join("later.dcrr"), &good).unwrap();
+        config.settle = Duration::from_secs(3600);
+        assert_eq!(scan_watch_folder(&state, &config).await.unwrap(), Default::default());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_pipe_accepts_files_and_frame_streams() {
+        use crate::pipe::{PipeIngest, ingest_pipe};
+        use domcorder_proto::TimestampData;
+
+        let (state, _temp_dir) = create_test_storage();
+        let frames = [1_000, 2_000].map(|timestamp| Frame::Timestamp(TimestampData { timestamp }));
+
+        // A whole .dcrr file, as `dcrr` writes it
+        let mut file = Vec::new();
+        let mut writer = FrameWriter::new(&mut file);
+        writer.write_header(&FileHeader::new()).unwrap();
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let options = PipeIngest {
+            subdir: Some("piped".into()),
+            filename: Some("file.dcrr".to_string()),
+            ..Default::default()
+        };
+        let id = ingest_pipe(&state, Cursor::new(file), options).await.unwrap();
+        assert_eq!(id.as_str(), "piped/file.dcrr");
+        assert_eq!(state.recording_info(&id).await.unwrap().frame_count, Some(2));
+
+        // Frames alone, as a recorder streams them
+        let mut stream = Vec::new();
+        let mut writer = FrameWriter::new(&mut stream);
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let options = PipeIngest {
+            site_origin: Some("https://example.com".to_string()),
+            ..Default::default()
+        };
+        let id = ingest_pipe(&state, Cursor::new(stream), options).await.unwrap();
+        let info = state.recording_info(&id).await.unwrap();
+        assert_eq!(info.frame_count, Some(2));
+        assert!(!state.is_recording_active(&id));
+
+        // The stream is stored as a complete recording, header included
+        let saved = state.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        assert!(matches!(reader.read_frame().await.unwrap(), Some(Frame::Timestamp(_))));
+    }
+
+    #[tokio::test]
+    async fn test_text_entries_are_recorded_at_ingestion() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::{DomNodePropertyTextChangedData, FrameBuilder, TextInsertOperationData, TextOperationData};
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        let state = std::sync::Arc::new(storage);
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Playback, RouteGroup::Analytics]);
+
+        let page = FrameBuilder::new()
+            .metadata("https://shop.example.com/checkout")
+            .new_page()
+            .with_element("input#email", |input| input.attr("name", "email"))
+            .with_element("input#password", |input| input.attr("type", "password"));
+        let (email, password) = (page.node_id("email"), page.node_id("password"));
+        let typed = |node_id: u32, text: &str| {
+            Frame::DomNodePropertyTextChanged(DomNodePropertyTextChangedData {
+                node_id,
+                property_name: "value".to_string(),
+                operations: vec![TextOperationData::Insert(TextInsertOperationData {
+                    index: 0,
+                    text: text.to_string(),
+                })],
+            })
+        };
+        let frames = page
+            .at(1_000)
+            .focus(email)
+            .wait(500)
+            .type_text("Ada")
+            .frame(typed(email, "Ada"))
+            .wait(1_500)
+            .focus(password)
+            .type_text("secret")
+            .frame(typed(password, "secret"))
+            .blur(password)
+            .build();
+        let mut stream = Vec::new();
+        let mut writer = FrameWriter::new(&mut stream);
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = state
+            .save_recording_stream_frames_only(Cursor::new(stream))
+            .await
+            .unwrap();
+
+        let request = Request::get(format!("/recording/{}/text", id)).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let entries: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(
+            entries,
+            serde_json::json!([
+                {"node_id": email, "tag": "input", "field": "email", "value": "Ada", "keystrokes": 3, "offset_ms": 500, "typing_ms": 0},
+                {"node_id": password, "tag": "input", "field": "password", "value": null, "keystrokes": 6, "offset_ms": 2_000, "typing_ms": 0},
+            ])
+        );
+
+        // Searched across the site's sessions by value
+        let request = Request::get("/analytics/text?site=https://shop.example.com&contains=ada")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let matches: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(matches.as_array().unwrap().len(), 1);
+        assert_eq!(matches[0]["recording_id"], id.as_str());
+        assert_eq!(matches[0]["value"], "Ada");
+
+        let request = Request::get("/analytics/text?site=https://shop.example.com&contains=secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let matches: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(matches, serde_json::json!([]));
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[tokio::test]
+    async fn test_alert_rules_annotate_and_post_webhooks() {
+        use crate::alerts::{ALERT_ANNOTATION, Alert, parse_alert_rules};
+        use axum::Json;
+        use axum::extract::State;
+        use domcorder_proto::{FrameBuilder, PageErrorData};
+
+        // A webhook receiver
+        let (alerts_tx, mut alerts_rx) = tokio::sync::mpsc::unbounded_channel::<Alert>();
+        let receiver = axum::Router::new()
+            .route(
+                "/hook",
+                axum::routing::post(|State(tx): State<tokio::sync::mpsc::UnboundedSender<Alert>>, Json(alert): Json<Alert>| async move {
+                    tx.send(alert).unwrap();
+                }),
+            )
+            .with_state(alerts_tx);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let rules = parse_alert_rules(&format!(
+            r#"[{{"name": "checkout error", "origin": "https://shop.example.com", "when": "frame", "frame": "PageError",
+                 "webhook": "http://{}/hook", "annotate": true}}]"#,
+            addr
+        ))
+        .unwrap();
+        let (storage, _temp_dir) = create_test_storage();
+        let storage = storage.with_alert_rules(rules);
+
+        let error = Frame::PageError(PageErrorData {
+            message: "TypeError: cart is undefined".to_string(),
+            source_url: None,
+            line: None,
+            column: None,
+            stack: None,
+        });
+        let frames = FrameBuilder::new()
+            .metadata("https://shop.example.com/checkout")
+            .at(1_000)
+            .wait(250)
+            .frame(error.clone())
+            .frame(error)
+            .build();
+        let mut stream = Vec::new();
+        let mut writer = FrameWriter::new(&mut stream);
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = storage
+            .save_recording_stream_frames_only(Cursor::new(stream))
+            .await
+            .unwrap();
+
+        let expected = Alert {
+            rule: "checkout error".to_string(),
+            recording_id: id.to_string(),
+            site_origin: Some("https://shop.example.com".to_string()),
+            offset_ms: 250,
+        };
+        let posted = tokio::time::timeout(std::time::Duration::from_secs(5), alerts_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(posted, expected);
+
+        // Marked once, right after the first error
+        let saved = storage.get_recording(&id).await.unwrap();
+        let mut reader = FrameReader::new(Cursor::new(saved), true);
+        reader.read_header().await.unwrap();
+        let mut stored = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            stored.push(frame);
+        }
+        let types: Vec<&str> = stored.iter().map(|frame| frame.type_name()).collect();
+        assert_eq!(
+            types,
+            ["RecordingMetadata", "Timestamp", "Timestamp", "PageError", "Annotation", "PageError"]
+        );
+        let Frame::Annotation(annotation) = &stored[4] else {
+            unreachable!();
+        };
+        assert_eq!(annotation.name, ALERT_ANNOTATION);
+        let annotated: Alert = serde_json::from_str(annotation.data.as_deref().unwrap()).unwrap();
+        assert_eq!(annotated, expected);
+    }
+
+    #[tokio::test]
+    async fn test_scroll_depth_is_aggregated_per_page() {
+        use crate::server::{DomcorderRouter, RouteGroup};
+        use axum::body::{Body, to_bytes};
+        use axum::http::{Request, StatusCode};
+        use domcorder_proto::FrameBuilder;
+        use tower::ServiceExt;
+
+        let (storage, _temp_dir) = create_test_storage();
+        // Frames-only uploads count as live, so keep the recorded timestamps
+        let state = std::sync::Arc::new(storage.with_timestamp_normalization(false));
+        let app = DomcorderRouter::new(state.clone()).routes(&[RouteGroup::Analytics]);
+
+        // Numbered from 0: the document, its doctype, then <html>
+        let html = 2;
+        let sessions = [
+            // Reads to the bottom of the page, leaving the fold after 2s
+            FrameBuilder::new()
+                .metadata("https://example.com/pricing")
+                .viewport(800, 600)
+                .new_page()
+                .at(1_000)
+                .resize_node(html, 800, 2_400)
+                .wait(2_000)
+                .scroll(0, 1_800)
+                .wait(2_000)
+                .build(),
+            // Never scrolls
+            FrameBuilder::new()
+                .metadata("https://example.com/pricing")
+                .viewport(800, 600)
+                .new_page()
+                .at(1_000)
+                .resize_node(html, 800, 2_400)
+                .wait(4_000)
+                .build(),
+        ];
+        for frames in &sessions {
+            let mut stream = Vec::new();
+            let mut writer = FrameWriter::new(&mut stream);
+            for frame in frames {
+                writer.write_frame(frame).unwrap();
+            }
+            state
+                .save_recording_stream_frames_only(Cursor::new(stream))
+                .await
+                .unwrap();
+        }
+
+        let request = Request::get("/analytics/scroll?site=https://example.com&path=/pricing")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let report: serde_json::Value =
+            serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(report["sessions"], 2);
+        assert_eq!(report["average_depth_percent"], 62.5);
+        assert_eq!(
+            report["reached"],
+            serde_json::json!([
+                {"percent": 25, "sessions": 2},
+                {"percent": 50, "sessions": 1},
+                {"percent": 75, "sessions": 1},
+                {"percent": 100, "sessions": 1},
+            ])
+        );
+        assert_eq!(report["above_fold_share"], 0.75);
+    }
+
+    #[tokio::test]
+    async fn test_frames_over_budget_are_summarized() {
+        use crate::frame_budget::{FRAME_BUDGET_ANNOTATION, FrameBudgets, SummarizedFrames};
+        use domcorder_proto::{CanvasChangedData, FrameBuilder};
+
+        let canvas = |node_id: u32, fill: u8| {
+            Frame::CanvasChanged(CanvasChangedData {
+                node_id,
+                mime_type: "image/png".to_string(),
+                data: vec![fill; 1_000],
+            })
+        };
+        let size = canvas(0, 0).encoded_size();
+        let (storage, _temp_dir) = create_test_storage();
+        let state = storage.with_frame_budgets(FrameBudgets {
+            bytes: std::collections::BTreeMap::from([("CanvasChanged".to_string(), size)]),
+        });
+
+        let frames = FrameBuilder::new()
+            .metadata("https://example.com/draw")
+            .at(1_000)
+            .frame(canvas(7, 1))
+            .wait(100)
+            .frame(canvas(7, 2))
+            .wait(100)
+            .frame(canvas(7, 3))
+            .build();
+        let mut stream = Vec::new();
+        let mut writer = FrameWriter::new(&mut stream);
+        for frame in &frames {
+            writer.write_frame(frame).unwrap();
+        }
+        let id = state
+            .save_recording_stream_frames_only(Cursor::new(stream))
+            .await
+            .unwrap();
+
+        let info = state.recording_info(&id).await.unwrap();
+        assert_eq!(
+            info.summarized_frames,
+            std::collections::BTreeMap::from([(
+                "CanvasChanged".to_string(),
+                SummarizedFrames {
+                    frames: 2,
+                    bytes: size * 2,
+                }
+            )])
+        );
+
+        let mut reader = state.open_recording_reader(&id).await.unwrap();
+        let mut stored = Vec::new();
+        while let Some(frame) = reader.read_frame().await.unwrap() {
+            stored.push(frame);
+        }
+        let annotations: Vec<_> = stored
+            .iter()
+            .filter(|frame| matches!(frame, Frame::Annotation(a) if a.name == FRAME_BUDGET_ANNOTATION))
+            .collect();
+        assert_eq!(annotations.len(), 2);
+        // The first image is stored where it was drawn, and the last one once the recording ends
+        let images: Vec<_> = stored.iter().filter(|frame| matches!(frame, Frame::CanvasChanged(_))).collect();
+        assert_eq!(images, [&canvas(7, 1), &canvas(7, 3)]);
+        assert_eq!(stored.last(), Some(&canvas(7, 3)));
+    }
 }
@@ -0,0 +1,74 @@
+//! Site profiles: related origins treated as one site
+//!
+//! A site is often served from several origins: `www.` and the apex domain,
+//! or staging next to production. Each records its own asset usage and
+//! analytics, so the usage counts that rank the cache manifest are split
+//! between them. A site profile names a site's primary origin and its
+//! aliases. Cache manifests, heatmaps and session analytics asked for any
+//! origin of a profile combine the data of all of them.
+//!
+//! Profiles are applied when data is read, and recordings keep the origin
+//! they were made on. Editing or deleting a profile regroups the data already
+//! stored, and recording policies still apply per origin. Profiles are
+//! managed through `/admin/sites`.
+
+use crate::analytics::page_of_url;
+use crate::{StorageError, StorageState};
+use serde::{Deserialize, Serialize};
+
+/// A site's primary origin and the origins that are aliases of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteProfile {
+    /// The primary origin, e.g. `https://shop.example`
+    pub site: String,
+    /// Other origins of the same site, e.g. `https://www.shop.example`
+    pub aliases: Vec<String>,
+}
+
+impl SiteProfile {
+    /// The profile with every origin reduced to scheme, host and port, and
+    /// aliases deduplicated; None if an origin isn't a URL or there are no
+    /// aliases besides the site itself
+    pub fn normalized(&self) -> Option<Self> {
+        let origin = |url: &str| page_of_url(url).map(|(origin, _)| origin);
+        let site = origin(&self.site)?;
+        let mut aliases = self
+            .aliases
+            .iter()
+            .map(|alias| origin(alias))
+            .collect::<Option<Vec<_>>>()?;
+        aliases.retain(|alias| *alias != site);
+        aliases.sort();
+        aliases.dedup();
+        if aliases.is_empty() {
+            return None;
+        }
+        Some(Self { site, aliases })
+    }
+}
+
+impl StorageState {
+    /// Store (or replace) a site profile, which must already be normalized
+    ///
+    /// Fails with [`StorageError::SiteConflict`] if one of its origins belongs
+    /// to another profile: an origin is an alias of at most one site, and the
+    /// primary origin of a site can't be an alias.
+    pub async fn set_site_profile(&self, profile: &SiteProfile) -> Result<(), StorageError> {
+        for other in self.metadata_store.list_site_profiles().await? {
+            if other.site == profile.site {
+                continue;
+            }
+            let taken = std::iter::once(&profile.site)
+                .chain(&profile.aliases)
+                .find(|origin| **origin == other.site || other.aliases.contains(origin));
+            if let Some(origin) = taken {
+                return Err(StorageError::SiteConflict {
+                    origin: origin.clone(),
+                    site: other.site,
+                });
+            }
+        }
+        self.metadata_store.set_site_profile(profile).await?;
+        Ok(())
+    }
+}
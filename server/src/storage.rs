@@ -1,147 +1,724 @@
+use crate::alerts::{AlertMatcher, AlertRule};
+use crate::analytics::{IngestAnalytics, page_of_url};
+use crate::analytics::summary::RecordingSummary;
+use crate::asset_cache::limits::{AssetBudget, AssetLimits, skipped_annotation};
+use crate::asset_cache::manifest::{DEFAULT_MANIFEST_BUDGET, ManifestCache};
+use crate::asset_cache::normalize::AssetNormalization;
+use crate::asset_cache::playback::PlaybackFrameTransformer;
+use crate::provenance::Provenance;
+use crate::asset_cache::scanner::{AssetScanner, NoopScanner, QUARANTINED_ASSET_HASH, ScannedAsset, scan_new_asset};
+use crate::asset_cache::stylesheets::{DEFAULT_STYLESHEET_DEDUP_BYTES, dedup_stylesheet_frame};
 use crate::asset_cache::{
-    AssetUsageParams, AssetFileStore, MetadataStore,
+    AssetError, AssetUsageParams, AssetFileStore, FetchedAsset, MetadataStore, PENDING_ASSET_HASH,
     store_or_get_asset_metadata,
 };
-use crate::{RecordingInfo, StorageState};
+use crate::asset_refs::AssetReferences;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::jobs::Jobs;
+use crate::coview::{LIVE_FRAME_BUFFER, ViewerRooms};
+use crate::drift::{DriftDetector, KeyframeRequests};
+use crate::clock::{ClockSkew, ClockSkewEstimator, TimelineNormalizer, now_ms};
+use crate::encryption::{DecryptingReader, EncryptingWriter, EncryptionError, KeyRing, KeyRotation};
+use crate::failed::failed_key_id;
+use crate::filename_template::FilenameTemplate;
+use crate::frame_sink::{FrameSink, FrameSinkConfig, FrameSinks};
+use crate::observability::{NoopHooks, ObservabilityHooks, SpanEvent, names};
+use crate::policy::{PolicyEnforcer, UnsampledAction};
+use crate::frame_budget::FrameBudgets;
+use crate::frame_filter::{FrameProcessor, FrameTypeRules};
+use crate::idle::IdleCompressor;
+use crate::privacy::PrivacyPolicy;
+use crate::recording_store::local::LocalRecordingStore;
+use crate::trace::{RECORDING_ID_ATTRIBUTE, SpanBuilder, TraceParent};
+use crate::recording_store::{RecordingReader, RecordingStore, RecordingWriter, StoredRecording};
+use crate::{InvalidRecordingId, RecordingId, RecordingInfo, StorageState};
 use chrono::Utc;
-use domcorder_proto::{FileHeader, FrameReader, FrameWriter};
+use domcorder_proto::writer::{DCRR_VERSION, HEADER_SIZE};
+use domcorder_proto::{ChecksumMismatch, FileHeader, FrameReader, FrameWriter, RecordingTruncatedData, TimestampData};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
 use std::fs;
-use std::io::{self, Read, Write};
-use std::path::PathBuf;
-use tokio::io::AsyncRead;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use futures::stream::FuturesUnordered;
 use tokio_stream::StreamExt;
 use tracing::{debug, info, warn};
-use uuid::Uuid;
+
+/// Error type for recording storage operations
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("Invalid recording header: {0}")]
+    Header(io::Error),
+
+    #[error("Invalid frame: {0}")]
+    Frame(io::Error),
+
+    #[error("Asset error: {0}")]
+    Asset(#[from] AssetError),
+
+    #[error("Recording not found: {0}")]
+    NotFound(String),
+
+    #[error("Collection not found: {0}")]
+    CollectionNotFound(String),
+
+    #[error("Recording {0} is already being written by another session")]
+    InUse(String),
+
+    #[error("Recording {0} is not being written")]
+    NotActive(String),
+
+    #[error("Recording {0} is on legal hold")]
+    OnHold(String),
+
+    #[error("Recording of {origin} refused: {reason}")]
+    Refused { origin: String, reason: String },
+
+    #[error("Session on {origin} not sampled")]
+    NotSampled { origin: String, action: UnsampledAction },
+
+    #[error("{origin} already belongs to site {site}")]
+    SiteConflict { origin: String, site: String },
+
+    #[error("Not enough free disk space for a new recording: {available} bytes free, {required} required")]
+    InsufficientSpace { available: u64, required: u64 },
+
+    #[error(transparent)]
+    InvalidId(#[from] InvalidRecordingId),
+
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    #[error("Filesystem error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl StorageError {
+    /// Map a store error for `id`, reporting a missing recording as NotFound
+    /// and one locked by another writer as InUse
+    pub(crate) fn from_store(id: &RecordingId, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => StorageError::NotFound(id.to_string()),
+            io::ErrorKind::ResourceBusy => StorageError::InUse(id.to_string()),
+            _ => StorageError::Io(e),
+        }
+    }
+}
+
+/// Which recordings to list; every recording when empty
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecordingFilter {
+    /// Only recordings whose id starts with this, e.g. `team/`
+    pub prefix: Option<String>,
+    /// Only sessions of this user id, email hash or anonymous id
+    pub user: Option<String>,
+    /// Only recordings in this collection
+    pub collection: Option<String>,
+}
+
+impl RecordingFilter {
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_none() && self.user.is_none() && self.collection.is_none()
+    }
+}
 
 impl StorageState {
     pub fn new(
         storage_dir: PathBuf,
         metadata_store: Box<dyn MetadataStore>,
         asset_file_store: Box<dyn AssetFileStore>,
-    ) -> Self {
+    ) -> Result<Self, StorageError> {
         // Ensure storage directory exists
-        fs::create_dir_all(&storage_dir).expect("Failed to create storage directory");
+        fs::create_dir_all(&storage_dir)?;
         
-        // Ensure recordings subdirectory exists
-        let recordings_dir = storage_dir.join("recordings");
-        fs::create_dir_all(&recordings_dir).expect("Failed to create recordings directory");
+        // Recordings live in the recordings subdirectory unless another store is configured
+        let recording_store = LocalRecordingStore::new(storage_dir.join("recordings"))?;
 
-        Self {
+        Ok(Self {
             storage_dir,
             active_recordings: std::sync::Mutex::new(std::collections::HashMap::new()),
+            active_registry: crate::cluster::ActiveRegistry::default(),
             metadata_store,
             asset_file_store,
-        }
+            recording_store: Box::new(recording_store),
+            observability: Box::new(NoopHooks),
+            frame_relay: None,
+            frame_sinks: FrameSinks::default(),
+            asset_limits: AssetLimits::default(),
+            asset_normalization: AssetNormalization::default(),
+            original_url_fallback: false,
+            asset_scanner: Box::new(NoopScanner),
+            filename_template: FilenameTemplate::default(),
+            min_free_bytes: None,
+            stylesheet_dedup_bytes: Some(DEFAULT_STYLESHEET_DEDUP_BYTES),
+            normalize_timestamps: true,
+            idle_compression_ms: None,
+            frame_checksums: false,
+            compressed_uploads: false,
+            key_ring: None,
+            privacy: PrivacyPolicy::default(),
+            frame_rules: FrameTypeRules::default(),
+            frame_budgets: FrameBudgets::default(),
+            alert_rules: Vec::new(),
+            viewer_rooms: ViewerRooms::default(),
+            keyframe_requests: KeyframeRequests::default(),
+            jobs: Jobs::default(),
+            manifest_budget: DEFAULT_MANIFEST_BUDGET,
+            manifest_cache: ManifestCache::default(),
+            replication: None,
+        })
+    }
+
+    /// Replace the default local recording store
+    pub fn with_recording_store(mut self, recording_store: Box<dyn RecordingStore>) -> Self {
+        self.recording_store = recording_store;
+        self
     }
-    
-    /// Get the recordings directory path
-    fn recordings_dir(&self) -> PathBuf {
-        self.storage_dir.join("recordings")
+
+    /// Also publish the frames selected by `config` to `sink` during ingestion
+    pub fn with_frame_sink(mut self, config: FrameSinkConfig, sink: Box<dyn FrameSink>) -> Self {
+        self.frame_sinks.push(config, sink);
+        self
+    }
+
+    /// Report telemetry to `hooks` instead of discarding it
+    pub fn with_observability(mut self, hooks: Box<dyn ObservabilityHooks>) -> Self {
+        self.observability = hooks;
+        self
+    }
+
+    /// Cap the size of cached assets, per asset and per recording
+    pub fn with_asset_limits(mut self, limits: AssetLimits) -> Self {
+        self.asset_limits = limits;
+        self
+    }
+
+    /// Normalize assets as `normalization` says before they are hashed
+    pub fn with_asset_normalization(mut self, normalization: AssetNormalization) -> Self {
+        self.asset_normalization = normalization;
+        self
+    }
+
+    /// During playback, send assets missing from the cache with the URL they
+    /// were recorded from (see [`PlaybackFrameTransformer::with_original_url_fallback`])
+    pub fn with_original_url_fallback(mut self, fallback: bool) -> Self {
+        self.original_url_fallback = fallback;
+        self
+    }
+
+    /// End each stored frame with a CRC32 (see [`domcorder_proto::checksum`]),
+    /// so playback detects recordings corrupted on disk
+    pub fn with_frame_checksums(mut self, checksums: bool) -> Self {
+        self.frame_checksums = checksums;
+        self
+    }
+
+    /// Accept uploads whose header turns on zstd frame compression (see
+    /// [`domcorder_proto::compression`])
+    ///
+    /// Off by default: decompressing costs the server CPU and memory the
+    /// client didn't spend in bandwidth, up to
+    /// [`MAX_FRAME_BYTES`](domcorder_proto::compression::MAX_FRAME_BYTES) per frame.
+    pub fn with_compressed_uploads(mut self, accepted: bool) -> Self {
+        self.compressed_uploads = accepted;
+        self
+    }
+
+    /// Scan new assets with `scanner` before they are cached
+    pub fn with_asset_scanner(mut self, scanner: Box<dyn AssetScanner>) -> Self {
+        self.asset_scanner = scanner;
+        self
     }
 
+    /// Name generated recordings with `template` instead of the default
+    pub fn with_filename_template(mut self, template: FilenameTemplate) -> Self {
+        self.filename_template = template;
+        self
+    }
+
+    /// Refuse new recordings while the storage filesystem has less than
+    /// `bytes` free
+    pub fn with_min_free_space(mut self, bytes: u64) -> Self {
+        self.min_free_bytes = Some(bytes);
+        self
+    }
+
+    /// Store stylesheet text of at least `bytes` in the CAS instead of in the
+    /// recording (None to keep all stylesheet text inline)
+    pub fn with_stylesheet_dedup(mut self, bytes: Option<u64>) -> Self {
+        self.stylesheet_dedup_bytes = bytes;
+        self
+    }
+
+    /// Store Timestamp frames as the recorder sent them instead of on a
+    /// monotonic timeline anchored to the server's clock (see
+    /// [`TimelineNormalizer`])
+    pub fn with_timestamp_normalization(mut self, normalize: bool) -> Self {
+        self.normalize_timestamps = normalize;
+        self
+    }
+
+    /// Collapse spans of at least `min_idle_ms` without activity into
+    /// IdlePeriod frames (None to store them as recorded); see [`IdleCompressor`]
+    pub fn with_idle_compression(mut self, min_idle_ms: Option<u64>) -> Self {
+        self.idle_compression_ms = min_idle_ms;
+        self
+    }
+
+    /// Wait at most `budget` for a recording's cache manifest before sending
+    /// the site's previous one; see [`StorageState::handshake_manifest`]
+    pub fn with_manifest_budget(mut self, budget: Duration) -> Self {
+        self.manifest_budget = budget;
+        self
+    }
+
+    /// Encrypt new recordings with per-recording data keys wrapped by the
+    /// current key of `key_ring`
+    ///
+    /// Recordings stored before encryption was enabled stay readable.
+    pub fn with_encryption(mut self, key_ring: KeyRing) -> Self {
+        self.key_ring = Some(key_ring);
+        self
+    }
+
+    /// Minimize what is stored of each recording; see [`PrivacyPolicy`]
+    pub fn with_privacy(mut self, policy: PrivacyPolicy) -> Self {
+        self.privacy = policy;
+        self
+    }
+
+    /// Choose which frame types are stored; see [`FrameTypeRules`]
+    pub fn with_frame_rules(mut self, rules: FrameTypeRules) -> Self {
+        self.frame_rules = rules;
+        self
+    }
+
+    /// Cap the bytes stored per recording of some frame types; see [`FrameBudgets`]
+    pub fn with_frame_budgets(mut self, budgets: FrameBudgets) -> Self {
+        self.frame_budgets = budgets;
+        self
+    }
+
+    /// Raise alerts for the recordings that meet `rules`; see [`crate::alerts`]
+    pub fn with_alert_rules(mut self, rules: Vec<AlertRule>) -> Self {
+        self.alert_rules = rules;
+        self
+    }
+
+    /// Store the recorder's address with a recording, as far as the privacy
+    /// policy allows; failures are logged, not fatal
+    pub async fn store_client_ip(&self, id: &RecordingId, ip: Option<IpAddr>) {
+        let Some(client_ip) = ip.and_then(|ip| self.privacy.client_ip(ip)) else {
+            return;
+        };
+        if let Err(e) = self.metadata_store.store_client_ip(id.as_str(), &client_ip).await {
+            warn!("⚠️ Failed to store the client address of {}: {}", id, e);
+        }
+    }
+
+    /// A new recording filename from the configured template, with no site
     pub fn generate_filename(&self) -> String {
-        let timestamp = Utc::now().format("%Y-%m-%d_%H-%M-%S.%f");
-        let uuid = Uuid::new_v4().simple();
-        format!("{}_{}.dcrr", timestamp, uuid)
+        self.generate_filename_for_site(None)
+    }
+
+    /// A new recording filename from the configured template for a recording
+    /// of `site_origin`
+    pub fn generate_filename_for_site(&self, site_origin: Option<&str>) -> String {
+        self.filename_template.render(site_origin, Utc::now())
+    }
+
+    /// Pick the id for a new recording, generating a filename if none was given
+    fn new_recording_id(
+        &self,
+        subdir: Option<&Path>,
+        filename: Option<String>,
+    ) -> Result<RecordingId, InvalidRecordingId> {
+        let filename = filename.unwrap_or_else(|| self.generate_filename());
+        RecordingId::in_subdir(subdir, &filename)
     }
 
-    pub fn save_recording(&self, data: &[u8]) -> io::Result<String> {
-        let filename = self.generate_filename();
-        let filepath = self.recordings_dir().join(&filename);
+    pub async fn save_recording(&self, data: &[u8]) -> Result<RecordingId, StorageError> {
+        self.check_free_space()?;
+        let filename = self.new_recording_id(None, None)?;
 
-        let mut file = fs::File::create(&filepath)?;
-        file.write_all(data)?;
-        file.flush()?;
+        let mut writer = self.open_writer(&filename).await?;
+        writer.write_all(data)?;
+        writer.flush()?;
+        writer.finish()?;
 
         Ok(filename)
     }
 
-    pub fn list_recordings(&self, subdir: Option<PathBuf>) -> io::Result<Vec<RecordingInfo>> {
-        let mut recordings = Vec::new();
-        let active_recordings = self.active_recordings.lock().unwrap();
+    pub async fn list_recordings(&self, subdir: Option<PathBuf>) -> Result<Vec<RecordingInfo>, StorageError> {
+        let subdir = subdir.map(|subdir| subdir.to_string_lossy().to_string());
+        let stored = self.recording_store.list(subdir.as_deref()).await?;
+        Ok(self.recording_infos(subdir.as_deref(), stored).await)
+    }
 
-        let read_dir = if let Some(subdir) = subdir {
-            fs::read_dir(&self.recordings_dir().join(&subdir))?
-        } else {
-            fs::read_dir(&self.recordings_dir())?
+    /// List recordings in every subdirectory, with `subdir/filename` ids,
+    /// keeping those whose id starts with `prefix`
+    pub async fn list_all_recordings(&self, prefix: Option<&str>) -> Result<Vec<RecordingInfo>, StorageError> {
+        let stored = self.recording_store.list_all(prefix).await?;
+        Ok(self.recording_infos(None, stored).await)
+    }
+
+    /// List the recordings selected by `filter`, subdirectories included
+    pub async fn filter_recordings(&self, filter: &RecordingFilter) -> Result<Vec<RecordingInfo>, StorageError> {
+        let members = match &filter.collection {
+            Some(name) => Some(self.collection(name).await?.recording_ids.into_iter().collect::<HashSet<_>>()),
+            None => None,
         };
+        let mut recordings = self.list_all_recordings(filter.prefix.as_deref()).await?;
+        if let Some(user) = &filter.user {
+            recordings.retain(|recording| recording.belongs_to(user));
+        }
+        if let Some(members) = &members {
+            recordings.retain(|recording| members.contains(&recording.id));
+        }
+        Ok(recordings)
+    }
 
-        for entry in read_dir {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().and_then(|s| s.to_str()) == Some("dcrr") {
-                let metadata = fs::metadata(&path)?;
-                let created = metadata
-                    .created()
-                    .map(|t| chrono::DateTime::from(t))
-                    .unwrap_or_else(|_| Utc::now());
-
-                let filename = path.file_name().unwrap().to_string_lossy().to_string();
-                let is_active = active_recordings.contains_key(&filename);
-
-                recordings.push(RecordingInfo {
-                    id: filename.clone(),
-                    filename,
-                    size: metadata.len(),
-                    created,
-                    is_active,
-                });
+    /// List one recording, by id
+    pub async fn recording_info(&self, id: &RecordingId) -> Result<RecordingInfo, StorageError> {
+        let stored = self.recording_store.list_all(Some(id.as_str())).await?;
+        self.recording_infos(None, stored)
+            .await
+            .into_iter()
+            .find(|recording| recording.id == id.as_str())
+            .ok_or_else(|| StorageError::NotFound(id.to_string()))
+    }
+
+    /// `stored` as listed from `subdir`, with ids made relative to the store
+    /// root and details filled in from the metadata store
+    async fn recording_infos(&self, subdir: Option<&str>, stored: Vec<StoredRecording>) -> Vec<RecordingInfo> {
+        let mut recordings = self.bare_recording_infos(subdir, stored);
+
+        let ids: Vec<String> = recordings.iter().map(|r| r.id.clone()).collect();
+        let summaries = match self.metadata_store.get_recording_summaries(&ids).await {
+            Ok(summaries) => summaries,
+            Err(e) => {
+                warn!("⚠️ Failed to load recording summaries: {}", e);
+                Vec::new()
+            }
+        };
+        let mut summaries: HashMap<String, RecordingSummary> =
+            summaries.into_iter().map(|s| (s.recording_id.clone(), s)).collect();
+        for recording in &mut recordings {
+            if let Some(summary) = summaries.remove(&recording.id) {
+                recording.site_origin = summary.site_origin;
+                recording.initial_url = summary.initial_url;
+                recording.duration_ms = Some(summary.duration_ms);
+                recording.frame_count = Some(summary.frame_count);
+                recording.title = summary.title;
+                recording.tags = summary.tags;
+                recording.anonymous_id = summary.anonymous_id;
+                recording.user_id = summary.user_id;
+                recording.email_hash = summary.email_hash;
+                recording.client_ip = summary.client_ip;
+                recording.dropped_frames = summary.dropped_frames;
+                recording.summarized_frames = summary.summarized_frames;
             }
         }
+        recordings
+    }
+
+    fn bare_recording_infos(&self, subdir: Option<&str>, stored: Vec<StoredRecording>) -> Vec<RecordingInfo> {
+        let mut recordings: Vec<RecordingInfo> = stored
+            .into_iter()
+            .map(|recording| {
+                let id = match subdir {
+                    Some(subdir) => format!("{}/{}", subdir.trim_end_matches('/'), recording.filename),
+                    None => recording.filename,
+                };
+                RecordingInfo {
+                    is_active: RecordingId::new(id.as_str()).is_ok_and(|id| self.is_recording_active(&id)),
+                    filename: id.rsplit('/').next().unwrap_or(&id).to_string(),
+                    id,
+                    size: recording.size,
+                    created: recording.header_created.unwrap_or(recording.created),
+                    file_created: recording.created,
+                    site_origin: None,
+                    initial_url: None,
+                    duration_ms: None,
+                    frame_count: None,
+                    title: None,
+                    tags: Vec::new(),
+                    anonymous_id: None,
+                    user_id: None,
+                    email_hash: None,
+                    client_ip: None,
+                    dropped_frames: Default::default(),
+                    summarized_frames: Default::default(),
+                }
+            })
+            .collect();
 
         // Sort by creation time, newest first
-        recordings.sort_by(|a, b| b.created.cmp(&a.created));
+        recordings.sort_by_key(|r| std::cmp::Reverse(r.created));
 
-        Ok(recordings)
+        recordings
     }
 
-    pub fn get_recording(&self, filename: &str) -> io::Result<Vec<u8>> {
-        let filepath = self.recordings_dir().join(filename);
+    /// Read a whole recording, header included, into memory
+    ///
+    /// Meant for tests and small recordings; serving paths use
+    /// [`get_recording_stream`](Self::get_recording_stream).
+    pub async fn get_recording(&self, filename: &RecordingId) -> Result<Vec<u8>, StorageError> {
+        let mut reader = self.open_reader(filename, 0).await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
 
-        if !filepath.exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "Recording not found",
-            ));
+        Ok(data)
+    }
+
+    pub async fn recording_exists(&self, filename: &RecordingId) -> bool {
+        self.recording_store.exists(filename).await.unwrap_or(false)
+    }
+
+    /// Open a saved recording for frame-level reading (header is consumed on first read)
+    pub async fn open_recording_reader(
+        &self,
+        filename: &RecordingId,
+    ) -> Result<FrameReader<tokio::io::BufReader<RecordingReader>>, StorageError> {
+        let reader = self.open_reader(filename, 0).await?;
+        Ok(FrameReader::new(tokio::io::BufReader::new(reader), true))
+    }
+
+    /// Open a recording from the store `offset` bytes in, decrypting it if it
+    /// was stored encrypted
+    ///
+    /// An encrypted recording that isn't active must have been finished: if
+    /// it ends before its last segment, reading it fails at the end.
+    pub(crate) async fn open_reader(&self, id: &RecordingId, offset: u64) -> Result<RecordingReader, StorageError> {
+        let Some(wrapped) = self.metadata_store.get_recording_key(id.as_str()).await? else {
+            return self
+                .recording_store
+                .open(id, offset)
+                .await
+                .map_err(|e| StorageError::from_store(id, e));
+        };
+        let key = self.key_ring.as_ref().ok_or(EncryptionError::NoMasterKey)?.unwrap_key(&wrapped)?;
+        let reader = self
+            .recording_store
+            .open(id, 0)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        let reader = DecryptingReader::new(reader, key, offset);
+        Ok(match self.is_recording_active(id) {
+            true => Box::new(reader.tailing()),
+            false => Box::new(reader),
+        })
+    }
+
+    /// Start writing a recording in the store, encrypted with a new data key
+    /// if encryption is enabled
+    ///
+    /// The key is stored once the store has let us write the recording (so a
+    /// busy recording keeps its key) and before anything is written to it.
+    async fn open_writer(&self, id: &RecordingId) -> Result<Box<dyn RecordingWriter>, StorageError> {
+        let writer = self
+            .recording_store
+            .create(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        let Some(key_ring) = &self.key_ring else {
+            return Ok(writer);
+        };
+        let (key, wrapped) = key_ring.generate_data_key();
+        if let Err(e) = self.metadata_store.store_recording_key(id.as_str(), &wrapped).await {
+            drop(writer);
+            self.fail_recording(id).await;
+            return Err(e.into());
         }
+        Ok(Box::new(EncryptingWriter::new(writer, key)))
+    }
 
-        let mut file = fs::File::open(&filepath)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+    /// Rewrap every recording's data key with the current master key
+    ///
+    /// Keys wrapped by a master key that is no longer configured are left as
+    /// they are and reported.
+    pub async fn rotate_recording_keys(&self) -> Result<KeyRotation, StorageError> {
+        let key_ring = self.key_ring.as_ref().ok_or(EncryptionError::NoMasterKey)?;
+        let mut rotation = KeyRotation {
+            key_id: key_ring.current_key_id().to_string(),
+            rewrapped: 0,
+            unreadable: Vec::new(),
+        };
+        for (recording_id, wrapped) in self.metadata_store.list_recording_keys_not_wrapped_by(&rotation.key_id).await? {
+            match key_ring.rewrap(&wrapped) {
+                Ok(rewrapped) => {
+                    self.metadata_store.store_recording_key(&recording_id, &rewrapped).await?;
+                    rotation.rewrapped += 1;
+                }
+                Err(e) => {
+                    warn!("⚠️ Failed to rewrap the key of {}: {}", recording_id, e);
+                    rotation.unreadable.push(recording_id);
+                }
+            }
+        }
+        info!("🔑 Rewrapped {} recording keys with master key {}", rotation.rewrapped, rotation.key_id);
+        Ok(rotation)
+    }
 
-        Ok(data)
+    /// Check whether a recording is on legal hold; held recordings are never
+    /// deleted
+    pub async fn is_on_legal_hold(&self, id: &RecordingId) -> Result<bool, StorageError> {
+        Ok(self.metadata_store.is_on_legal_hold(id.as_str()).await?)
+    }
+
+    /// Place a recording on legal hold or release it, logging the change with
+    /// who asked and why; returns false if it already was in that state
+    pub async fn set_legal_hold(
+        &self,
+        id: &RecordingId,
+        held: bool,
+        actor: Option<String>,
+        reason: Option<String>,
+    ) -> Result<bool, StorageError> {
+        if !self.recording_exists(id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        if !self.metadata_store.set_legal_hold(id.as_str(), held).await? {
+            return Ok(false);
+        }
+        let action = if held { AuditAction::HoldApplied } else { AuditAction::HoldReleased };
+        self.metadata_store
+            .append_audit_event(&AuditEvent::now(id.as_str(), action, actor, reason))
+            .await?;
+        info!("⚖️ Legal hold {} for {}", if held { "applied" } else { "released" }, id);
+        Ok(true)
+    }
+
+    /// Delete a recording that is neither on legal hold nor being written,
+    /// logging who asked and why
+    pub async fn delete_recording(
+        &self,
+        id: &RecordingId,
+        actor: Option<String>,
+        reason: Option<String>,
+    ) -> Result<(), StorageError> {
+        if !self.recording_exists(id).await {
+            return Err(StorageError::NotFound(id.to_string()));
+        }
+        if self.is_recording_active(id) {
+            return Err(StorageError::InUse(id.to_string()));
+        }
+        if self.is_on_legal_hold(id).await? {
+            return Err(StorageError::OnHold(id.to_string()));
+        }
+        self.recording_store
+            .delete(id)
+            .await
+            .map_err(|e| StorageError::from_store(id, e))?;
+        self.metadata_store.delete_recording_metadata(id.as_str()).await?;
+        self.metadata_store
+            .append_audit_event(&AuditEvent::now(id.as_str(), AuditAction::Deleted, actor, reason))
+            .await?;
+        info!("🗑️ Deleted recording {}", id);
+        Ok(())
+    }
+
+    /// Set aside a stored recording whose frames fail their checksums, so it
+    /// is no longer listed or played; failures are logged, not fatal
+    ///
+    /// A recording still being written is left alone.
+    pub(crate) async fn mark_corrupt(&self, id: &RecordingId) {
+        if self.is_recording_active(id) {
+            return;
+        }
+        warn!("💥 Recording {} failed a frame checksum; setting it aside as corrupt", id);
+        if let Err(e) = self.recording_store.mark_corrupt(id).await {
+            warn!("⚠️ Failed to set aside corrupt recording {}: {}", id, e);
+        }
     }
 
-    pub fn recording_exists(&self, filename: &str) -> bool {
-        self.recordings_dir().join(filename).exists()
+    /// Set aside a recording whose write failed; failures are logged, not fatal
+    pub(crate) async fn fail_recording(&self, id: &RecordingId) {
+        if let Err(e) = self.recording_store.mark_failed(id).await {
+            warn!("⚠️ Failed to set aside failed recording {}: {}", id, e);
+            return;
+        }
+        // Its key goes with it, so a new recording under the same id can't replace it
+        if self.key_ring.is_some()
+            && let Err(e) = self.metadata_store.replace_recording_key(id.as_str(), &failed_key_id(id)).await
+        {
+            warn!("⚠️ Failed to set aside the key of failed recording {}: {}", id, e);
+        }
     }
 
-    /// Mark a recording as active (being written to)
-    pub fn mark_recording_active(&self, filename: &str) {
+    /// Clean up after ingestion stopped with `error`: a recording refused or
+    /// not sampled by its origin's policy is deleted, since it should never
+    /// have been stored, and any other is set aside as failed
+    async fn abandon_recording(&self, id: &RecordingId, error: &StorageError) {
+        if !matches!(error, StorageError::Refused { .. } | StorageError::NotSampled { .. }) {
+            return self.fail_recording(id).await;
+        }
+        if let Err(e) = self.recording_store.delete(id).await {
+            warn!("⚠️ Failed to delete refused recording {}: {}", id, e);
+        }
+        if let Err(e) = self.metadata_store.delete_recording_metadata(id.as_str()).await {
+            warn!("⚠️ Failed to forget refused recording {}: {}", id, e);
+        }
+    }
+
+    /// Mark a recording as active (being written to), failing with
+    /// [`StorageError::InUse`] if another session is already writing it
+    pub fn mark_recording_active(&self, filename: &RecordingId) -> Result<(), StorageError> {
         let mut active_recordings = self.active_recordings.lock().unwrap();
-        active_recordings.insert(
-            filename.to_string(),
-            crate::ActiveRecordingInfo {
-                latest_timestamp: None,
-            },
-        );
+        match active_recordings.entry(filename.clone()) {
+            Entry::Occupied(_) => Err(StorageError::InUse(filename.to_string())),
+            Entry::Vacant(entry) => {
+                entry.insert(crate::ActiveRecordingInfo {
+                    latest_timestamp: None,
+                    frames_written: 0,
+                    live_frames: tokio::sync::broadcast::channel(LIVE_FRAME_BUFFER).0,
+                    flush_requested: false,
+                    finalize: Default::default(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Claim `id` for this session and start writing it, if there is space
+    ///
+    /// The in-process claim rejects a second session on this server, the
+    /// lease in the shared registry (see [`crate::cluster`]) a session on
+    /// another server, and the store's own lock (a lock file for local
+    /// storage) any other writer sharing the storage.
+    pub(crate) async fn create_recording(&self, id: &RecordingId) -> Result<Box<dyn RecordingWriter>, StorageError> {
+        self.check_free_space()?;
+        self.mark_recording_active(id)?;
+        if let Err(e) = self.claim_active_lease(id).await {
+            self.mark_recording_completed(id);
+            return Err(e);
+        }
+        let writer = self.open_writer(id).await;
+        if writer.is_err() {
+            self.mark_recording_completed(id);
+        }
+        writer
     }
 
     /// Mark a recording as completed (no longer being written to)
-    pub fn mark_recording_completed(&self, filename: &str) {
+    pub fn mark_recording_completed(&self, filename: &RecordingId) {
         let mut active_recordings = self.active_recordings.lock().unwrap();
-        active_recordings.remove(&filename.to_string());
+        if active_recordings.remove(filename).is_some() {
+            self.active_registry.release(filename);
+        }
     }
 
-    /// Check if a recording is currently active
-    pub fn is_recording_active(&self, filename: &str) -> bool {
+    /// Check if a recording is currently active, here or on another server
+    pub fn is_recording_active(&self, filename: &RecordingId) -> bool {
         let active_recordings = self.active_recordings.lock().unwrap();
-        active_recordings.contains_key(&filename.to_string())
+        active_recordings.contains_key(filename) || self.active_registry.remote_lease(filename).is_some()
     }
 
     /// Update the latest timestamp for an active recording
-    pub fn update_recording_timestamp(&self, filename: &str, timestamp: u64) {
+    pub fn update_recording_timestamp(&self, filename: &RecordingId, timestamp: u64) {
         let mut active_recordings = self.active_recordings.lock().unwrap();
         if let Some(info) = active_recordings.get_mut(filename) {
             info.latest_timestamp = Some(timestamp);
@@ -149,11 +726,45 @@ impl StorageState {
     }
 
     /// Get the latest timestamp for an active recording
-    pub fn get_latest_timestamp(&self, filename: &str) -> Option<u64> {
+    pub fn get_latest_timestamp(&self, filename: &RecordingId) -> Option<u64> {
         let active_recordings = self.active_recordings.lock().unwrap();
-        active_recordings
-            .get(filename)
-            .and_then(|info| info.latest_timestamp)
+        match active_recordings.get(filename) {
+            Some(info) => info.latest_timestamp,
+            None => self
+                .active_registry
+                .remote_lease(filename)
+                .and_then(|lease| lease.latest_timestamp),
+        }
+    }
+
+    /// Count a frame written to an active recording and hand it to its live
+    /// viewers, if there are any; true if the recording should be flushed
+    /// for a viewer that has just subscribed
+    fn publish_live_frame(&self, filename: &RecordingId, frame: &domcorder_proto::Frame) -> bool {
+        let mut active_recordings = self.active_recordings.lock().unwrap();
+        let Some(info) = active_recordings.get_mut(filename) else {
+            return false;
+        };
+        info.frames_written += 1;
+        if info.live_frames.receiver_count() > 0 {
+            let _ = info.live_frames.send(std::sync::Arc::new(frame.clone()));
+        }
+        std::mem::take(&mut info.flush_requested)
+    }
+
+    /// Subscribe to the frames of an active recording as they are written,
+    /// with the number written before the subscription; None if the
+    /// recording is not active
+    ///
+    /// The earlier frames are flushed to the file along with the next one.
+    pub fn subscribe_live_frames(
+        &self,
+        filename: &RecordingId,
+    ) -> Option<(u64, tokio::sync::broadcast::Receiver<std::sync::Arc<domcorder_proto::Frame>>)> {
+        let mut active_recordings = self.active_recordings.lock().unwrap();
+        let info = active_recordings.get_mut(filename)?;
+        info.flush_requested = true;
+        Some((info.frames_written, info.live_frames.subscribe()))
     }
 
     /// TEMPORARILY BYPASS FRAME PROCESSING: Stream raw data directly to file with header
@@ -162,62 +773,55 @@ impl StorageState {
         mut source: R,
         subdir: Option<PathBuf>,
         filename: Option<String>,
-    ) -> io::Result<String> {
-        let recording_dir = match subdir.clone() {    
-            Some(subdir) => self.recordings_dir().join(subdir.clone()),
-            None => self.recordings_dir(),
-        };
-
-        fs::create_dir_all(&recording_dir)?;
-
-
-        let file_name = match filename {
-            Some(filename) => filename,
-            None => self.generate_filename(),
-        };
-
-        let recording_file = recording_dir.join(file_name.clone());
-
-        let relative_path = match subdir {
-            Some(subdir) => subdir.join(file_name.clone()).to_string_lossy().to_string(),
-            None => file_name,
-        };
+    ) -> Result<RecordingId, StorageError> {
+        let relative_path = self.new_recording_id(subdir.as_deref(), filename)?;
 
         info!("Saving recording to: {}", relative_path);
 
-        // Mark this recording as active
-        self.mark_recording_active(&relative_path);
+        let span = IngestSpan::start(self.observability.as_ref(), &relative_path);
 
-        // First, write the file header using the sync FrameWriter
-        let header = FileHeader::new();
-        {
-            // Create a temporary sync file handle for header writing
-            let sync_file = std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(&recording_file)?;
-            let mut frame_writer = FrameWriter::new(sync_file);
-            frame_writer.write_header(&header)?;
-            frame_writer.flush()?;
+        // Mark this recording as active
+        let mut writer = self.create_recording(&relative_path).await?;
+
+        // Write the header, then copy raw frame bytes after it - no frame processing
+        let result = async {
+            FrameWriter::new(&mut writer).write_header(&FileHeader::new())?;
+            let mut bytes_copied = 0u64;
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                let n = source.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+                writer.write_all(&buffer[..n])?;
+                bytes_copied += n as u64;
+            }
+            writer.flush()?;
+            Ok::<_, io::Error>(bytes_copied)
         }
+        .await;
 
-        // Reopen the file in append mode for async operations
-        let mut output_file = tokio::fs::OpenOptions::new()
-            .append(true)
-            .open(&recording_file)
-            .await?;
+        // Mark this recording as completed
+        self.mark_recording_completed(&relative_path);
 
-        // Copy raw frame bytes directly after the header - no frame processing
-        let bytes_copied = tokio::io::copy(&mut source, &mut output_file).await?;
+        let bytes_copied = match result.and_then(|bytes| writer.finish().map(|_| bytes)) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.fail_recording(&relative_path).await;
+                return Err(e.into());
+            }
+        };
 
         info!(
             "📁 Raw copy completed: {} bytes written to {} (plus header)",
-            bytes_copied, recording_file.to_string_lossy().to_string()
+            bytes_copied, relative_path
         );
 
-        // Mark this recording as completed
-        self.mark_recording_completed(&relative_path);
+        if let Err(e) = self.index_recording_assets(&relative_path).await {
+            warn!("⚠️ Failed to index the asset references of {}: {}", relative_path, e);
+        }
+
+        span.complete();
 
         Ok(relative_path)
     }
@@ -226,7 +830,7 @@ impl StorageState {
     pub async fn save_recording_stream_frames_only<R: AsyncRead + Unpin>(
         &self,
         source: R,
-    ) -> io::Result<String> {
+    ) -> Result<RecordingId, StorageError> {
         self.save_recording_stream_frames_only_with_site(source, None, None).await
     }
 
@@ -236,7 +840,7 @@ impl StorageState {
         source: R,
         site_origin: Option<&str>,
         user_agent: Option<&str>,
-    ) -> io::Result<String> {
+    ) -> Result<RecordingId, StorageError> {
         self.save_recording_stream_frames_only_with_site_and_path(source, site_origin, user_agent, None, None).await
     }
 
@@ -248,93 +852,95 @@ impl StorageState {
         user_agent: Option<&str>,
         subdir: Option<PathBuf>,
         custom_filename: Option<String>,
-    ) -> io::Result<String> {
-        let recording_dir = match subdir {
-            Some(ref subdir) => self.recordings_dir().join(subdir),
-            None => self.recordings_dir(),
-        };
-        
-        fs::create_dir_all(&recording_dir)?;
-        
-        let filename = custom_filename.unwrap_or_else(|| self.generate_filename());
-        let filepath = recording_dir.join(&filename);
-        
-        // For active recording tracking, use relative path if subdir is provided
-        let tracking_path = match subdir {
-            Some(ref subdir) => subdir.join(&filename).to_string_lossy().to_string(),
-            None => filename.clone(),
-        };
+    ) -> Result<RecordingId, StorageError> {
+        // Relative path if subdir is provided; used for active recording tracking
+        let tracking_path = self.new_recording_id(subdir.as_deref(), custom_filename)?;
 
-        // Mark this recording as active
-        self.mark_recording_active(&tracking_path);
+        // Create the recording for writing
+        let mut span = IngestSpan::start(self.observability.as_ref(), &tracking_path);
 
-        // Create the file for writing
-        let output_file = fs::File::create(&filepath)?;
-        let mut frame_writer = FrameWriter::new(output_file);
+        // Mark this recording as active
+        let output = self.create_recording(&tracking_path).await?;
+        let mut frame_writer = FrameWriter::new(output);
 
         // Create frame reader from the async source (no header expected)
         let mut frame_reader = FrameReader::new(source, false);
 
         // Create and write a new header with current timestamp
-        let header = FileHeader::new();
+        let header = FileHeader::new().with_frame_checksums(self.frame_checksums);
 
         if let Err(e) = frame_writer.write_header(&header) {
-            let failed_filename = format!("{}.failed", filename);
-            let failed_filepath = recording_dir.join(&failed_filename);
-            let _ = fs::rename(&filepath, &failed_filepath);
-            return Err(e);
+            self.mark_recording_completed(&tracking_path);
+            self.fail_recording(&tracking_path).await;
+            return Err(e.into());
         }
 
+        let mut analytics = IngestAnalytics::new(tracking_path.as_str(), site_origin);
+
         // Stream frames from input to output, validating each one
-        while let Some(frame_result) = frame_reader.next().await {
-            match frame_result {
-                Ok(frame) => {
-                    // Update latest timestamp if this is a Timestamp frame
-                    if let domcorder_proto::Frame::Timestamp(timestamp_data) = &frame {
-                        self.update_recording_timestamp(&tracking_path, timestamp_data.timestamp);
-                    }
+        let ingest = IngestContext {
+            id: &tracking_path,
+            site_origin,
+            user_agent,
+            live: true,
+        };
+        if let Err(e) = self
+            .ingest_frames(&mut frame_reader, &mut frame_writer, ingest, &mut span, &mut analytics)
+            .await
+        {
+            // Frame parsing or writing failed, or the policy refused the recording
+            drop(frame_writer);
+            self.abandon_recording(&tracking_path, &e).await;
+            self.mark_recording_completed(&tracking_path);
+            return Err(e);
+        }
 
-                    // Process Asset and AssetReference frames
-                    let processed_frame = self.filter_frame_async(frame, site_origin, user_agent).await;
-
-                    if let Some(frame) = processed_frame {
-                        // Write the validated frame to output
-                        if let Err(e) = frame_writer.write_frame(&frame) {
-                            let failed_filename = format!("{}.failed", filename);
-                            let failed_filepath = recording_dir.join(&failed_filename);
-                            let _ = fs::rename(&filepath, &failed_filepath);
-                            self.mark_recording_completed(&tracking_path);
-                            return Err(e);
-                        }
-                    }
-                    // If filter returned None, skip this frame
-                }
-                Err(e) => {
-                    // Frame parsing failed - mark as failed and return error
-                    let failed_filename = format!("{}.failed", filename);
-                    let failed_filepath = recording_dir.join(&failed_filename);
-                    let _ = fs::rename(&filepath, &failed_filepath);
-                    self.mark_recording_completed(&tracking_path);
-                    return Err(e);
-                }
-            }
+        // Index the keyframes and flush the writer to ensure all data is written
+        if let Err(e) = frame_writer
+            .write_index()
+            .and_then(|_| frame_writer.flush())
+            .and_then(|_| frame_writer.into_inner().finish())
+        {
+            self.fail_recording(&tracking_path).await;
+            self.mark_recording_completed(&tracking_path);
+            return Err(e.into());
         }
 
-        // Flush the writer to ensure all data is written
-        frame_writer.flush()?;
+        self.persist_analytics(analytics).await;
 
         // Mark this recording as completed
         self.mark_recording_completed(&tracking_path);
 
         // Return the tracking path (relative path if subdir was used)
+        span.complete();
+
         Ok(tracking_path)
     }
 
+    /// Persist analytics for a completed recording; failures are logged, not fatal
+    async fn persist_analytics(&self, analytics: IngestAnalytics) {
+        if let Err(e) = analytics.persist(self.metadata_store.as_ref()).await {
+            warn!("⚠️ Failed to store recording analytics: {}", e);
+        }
+    }
+
+    /// Refuse an upload's header if it turns on frame compression and
+    /// compressed uploads aren't accepted
+    fn accept_header(&self, header: FileHeader) -> io::Result<FileHeader> {
+        if header.frame_compression() && !self.compressed_uploads {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Compressed frames are not accepted by this server",
+            ));
+        }
+        Ok(header)
+    }
+
     /// Stream and validate frames from an AsyncRead source, writing them to a file
     pub async fn save_recording_stream<R: AsyncRead + Unpin>(
         &self,
         source: R,
-    ) -> io::Result<String> {
+    ) -> Result<RecordingId, StorageError> {
         self.save_recording_stream_with_site(source, None, None).await
     }
 
@@ -344,121 +950,213 @@ impl StorageState {
         source: R,
         site_origin: Option<&str>,
         user_agent: Option<&str>,
-    ) -> io::Result<String> {
-        let filename = self.generate_filename();
-        let filepath = self.recordings_dir().join(&filename);
+    ) -> Result<RecordingId, StorageError> {
+        self.save_recording_stream_with_site_and_path(source, site_origin, user_agent, None, None).await
+    }
 
-        // Mark this recording as active
-        self.mark_recording_active(&filename);
+    /// Stream and validate frames with site context, with custom path/filename
+    pub async fn save_recording_stream_with_site_and_path<R: AsyncRead + Unpin>(
+        &self,
+        source: R,
+        site_origin: Option<&str>,
+        user_agent: Option<&str>,
+        subdir: Option<PathBuf>,
+        custom_filename: Option<String>,
+    ) -> Result<RecordingId, StorageError> {
+        let filename = self.new_recording_id(subdir.as_deref(), custom_filename)?;
+
+        // Create the recording for writing
+        let mut span = IngestSpan::start(self.observability.as_ref(), &filename);
 
-        // Create the file for writing
-        let output_file = fs::File::create(&filepath)?;
-        let mut frame_writer = FrameWriter::new(output_file);
+        // Mark this recording as active
+        let output = self.create_recording(&filename).await?;
+        let mut frame_writer = FrameWriter::new(output);
 
         // Create frame reader from the async source (expect header)
         let mut frame_reader = FrameReader::new(source, true);
 
         // Read and validate the header first
-        let header = match frame_reader.read_header().await {
+        let header = match frame_reader.read_header().await.and_then(|header| self.accept_header(header)) {
             Ok(header) => header,
             Err(e) => {
                 // Header validation failed - mark as failed and return error
-                let failed_filename = format!("{}.failed", filename);
-                let failed_filepath = self.recordings_dir().join(&failed_filename);
-                if let Err(_) = fs::rename(&filepath, &failed_filepath) {
-                    // If rename fails, try to delete the original file
-                    let _ = fs::remove_file(&filepath);
-                }
-                return Err(e);
+                drop(frame_writer);
+                self.fail_recording(&filename).await;
+                self.mark_recording_completed(&filename);
+                return Err(StorageError::Header(e));
             }
         };
 
         // Write the original header to the output file (preserving timestamp)
+        let header = stored_header(&header).with_frame_checksums(self.frame_checksums);
         if let Err(e) = frame_writer.write_header(&header) {
-            let failed_filename = format!("{}.failed", filename);
-            let failed_filepath = self.recordings_dir().join(&failed_filename);
-            let _ = fs::rename(&filepath, &failed_filepath);
-            return Err(e);
+            drop(frame_writer);
+            self.fail_recording(&filename).await;
+            self.mark_recording_completed(&filename);
+            return Err(e.into());
         }
 
+        let mut analytics = IngestAnalytics::new(filename.as_str(), site_origin);
+
         // Stream frames from input to output, validating each one
-        while let Some(frame_result) = frame_reader.next().await {
-            match frame_result {
-                Ok(frame) => {
-                    // Process Asset and AssetReference frames
-                    let processed_frame = self.filter_frame_async(frame, site_origin, user_agent).await;
-
-                    if let Some(frame) = processed_frame {
-                        // Write the validated frame to output
-                        if let Err(e) = frame_writer.write_frame(&frame) {
-                            let failed_filename = format!("{}.failed", filename);
-                            let failed_filepath = self.recordings_dir().join(&failed_filename);
-                            let _ = fs::rename(&filepath, &failed_filepath);
-                            self.mark_recording_completed(&filename);
-                            return Err(e);
-                        }
-                    }
-                    // If filter returned None, skip this frame
-                }
-                Err(e) => {
-                    // Frame parsing failed - mark as failed and return error
-                    let failed_filename = format!("{}.failed", filename);
-                    let failed_filepath = self.recordings_dir().join(&failed_filename);
-                    let _ = fs::rename(&filepath, &failed_filepath);
-                    self.mark_recording_completed(&filename);
-                    return Err(e);
-                }
-            }
+        let ingest = IngestContext {
+            id: &filename,
+            site_origin,
+            user_agent,
+            live: false,
+        };
+        if let Err(e) = self
+            .ingest_frames(&mut frame_reader, &mut frame_writer, ingest, &mut span, &mut analytics)
+            .await
+        {
+            // Frame parsing or writing failed, or the policy refused the recording
+            drop(frame_writer);
+            self.abandon_recording(&filename, &e).await;
+            self.mark_recording_completed(&filename);
+            return Err(e);
         }
 
-        // Flush the writer to ensure all data is written
-        frame_writer.flush()?;
+        // Index the keyframes and flush the writer to ensure all data is written
+        if let Err(e) = frame_writer
+            .write_index()
+            .and_then(|_| frame_writer.flush())
+            .and_then(|_| frame_writer.into_inner().finish())
+        {
+            self.fail_recording(&filename).await;
+            self.mark_recording_completed(&filename);
+            return Err(e.into());
+        }
+
+        self.persist_analytics(analytics).await;
 
         // Mark this recording as completed
         self.mark_recording_completed(&filename);
 
+        span.complete();
+
         Ok(filename)
     }
 
     /// Get a streaming reader for a recording (supports live tailing for active recordings)
     pub async fn get_recording_stream(
         self: std::sync::Arc<Self>,
-        filename: &str,
-    ) -> io::Result<Box<dyn tokio::io::AsyncRead + Unpin + Send>> {
-        use tokio::fs::File;
-        use tokio::io::AsyncSeekExt;
-
-        let filepath = self.recordings_dir().join(filename);
-
-        if !filepath.exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "Recording not found",
-            ));
+        filename: &RecordingId,
+    ) -> Result<Box<dyn tokio::io::AsyncRead + Unpin + Send>, StorageError> {
+        // Another server is writing it; its copy here may be stale or missing
+        if let Some(reader) = self.open_relayed(filename).await? {
+            return Ok(reader);
         }
 
-        let mut file = File::open(&filepath).await?;
-
-        // Skip the 32-byte DCRR header
-        file.seek(std::io::SeekFrom::Start(32)).await?;
-
-        if self.is_recording_active(filename) {
+        let (reader, checksums) = if self.is_recording_active(filename) {
             info!("Creating tailing reader for active recording: {}", filename);
-            // For active recordings, create a tailing reader
-            Ok(Box::new(TailingReader::new(
-                file,
-                filepath,
-                filename.to_string(),
+            // For active recordings, create a tailing reader past the 32-byte
+            // DCRR header, which may not be written yet; it is this server's
+            let reader = self.open_reader(filename, HEADER_SIZE as u64).await?;
+            let reader: Box<dyn AsyncRead + Unpin + Send> = Box::new(TailingReader::new(
+                reader,
+                filename.clone(),
                 self.clone(),
-            )))
+            ));
+            (reader, self.frame_checksums)
         } else {
             info!("Creating reader for completed recording: {}", filename);
-            // For completed recordings, just return the file
-            Ok(Box::new(file))
+            // For completed recordings, read past the header
+            let mut reader = FrameReader::new(self.open_reader(filename, 0).await?, true);
+            let header = reader.read_header().await.map_err(StorageError::Header)?;
+            (reader.into_inner(), header.frame_checksums())
+        };
+        Ok(match checksums {
+            true => self.verified_frames(filename, reader),
+            false => reader,
+        })
+    }
+
+    /// The frames of recording `id` read from `reader` (past the header),
+    /// with their checksums checked and removed, so playback streams plain
+    /// frames; a frame that fails its checksum ends the stream with an error
+    /// and sets the recording aside as corrupt
+    fn verified_frames(
+        self: std::sync::Arc<Self>,
+        id: &RecordingId,
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+    ) -> Box<dyn AsyncRead + Unpin + Send> {
+        let reader = FrameReader::new(reader, false).with_frame_checksums(true).with_index(true);
+        let frames = futures::stream::unfold(Some((reader, self, id.clone())), |state| async move {
+            let (mut reader, storage, id) = state?;
+            match reader.read_frame().await {
+                Ok(Some(frame)) => {
+                    let mut buffer = Vec::new();
+                    let encoded = FrameWriter::new(&mut buffer)
+                        .write_frame(&frame)
+                        .map(|()| axum::body::Bytes::from(buffer));
+                    Some((encoded, Some((reader, storage, id))))
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    if ChecksumMismatch::find(&e).is_some() {
+                        storage.mark_corrupt(&id).await;
+                    }
+                    Some((Err(e), None))
+                }
+            }
+        });
+        Box::new(tokio_util::io::StreamReader::new(Box::pin(frames)))
+    }
+
+    /// Open a completed recording for playback from the last keyframe at or
+    /// before `timestamp`, found with the recording's seek index
+    ///
+    /// The stream starts with a Timestamp frame at the keyframe's time, then
+    /// the keyframe. None if the recording can't be entered there (it is
+    /// active, encrypted, kept in a store without random access, or has no
+    /// index), so playback starts from the beginning instead.
+    pub async fn get_recording_stream_at(
+        self: std::sync::Arc<Self>,
+        filename: &RecordingId,
+        timestamp: u64,
+    ) -> Result<Option<Box<dyn tokio::io::AsyncRead + Unpin + Send>>, StorageError> {
+        if self.is_recording_active(filename) || self.metadata_store.get_recording_key(filename.as_str()).await?.is_some() {
+            return Ok(None);
         }
+        let file = self
+            .recording_store
+            .open_seekable(filename)
+            .await
+            .map_err(|e| StorageError::from_store(filename, e))?;
+        let Some(file) = file else {
+            return Ok(None);
+        };
+        let mut reader = FrameReader::new(file, true);
+        let Some(entry) = reader.seek_to_timestamp(timestamp).await.map_err(StorageError::Frame)? else {
+            return Ok(None);
+        };
+        debug!("Playing {} from frame {} at {}", filename, entry.frame_index, entry.timestamp);
+
+        let checksums = reader.header().is_some_and(FileHeader::frame_checksums);
+        let frames: Box<dyn AsyncRead + Unpin + Send> = Box::new(reader.into_inner());
+        let frames = match checksums {
+            true => self.verified_frames(filename, frames),
+            false => frames,
+        };
+        let mut start = Vec::new();
+        FrameWriter::new(&mut start).write_frame(&domcorder_proto::Frame::Timestamp(TimestampData {
+            timestamp: entry.timestamp,
+        }))?;
+        Ok(Some(Box::new(io::Cursor::new(start).chain(frames))))
+    }
+
+    /// Create a transformer that rewrites cached assets to HTTP URLs for playback
+    ///
+    /// Asset stores resolve absolute URLs, so no base URL is applied.
+    pub fn playback_transformer(&self) -> PlaybackFrameTransformer<'_> {
+        PlaybackFrameTransformer::new(
+            self.metadata_store.as_ref(),
+            self.asset_file_store.as_ref(),
+            String::new(),
+        )
+        .with_original_url_fallback(self.original_url_fallback)
     }
 
-    /// Process an Asset frame: extract binary data, hash it, store it in CAS
     /// Determine if server-side fetch should be attempted based on fetch_error
     fn should_fetch_server_side(fetch_error: &domcorder_proto::AssetFetchError) -> bool {
         match fetch_error {
@@ -477,64 +1175,52 @@ impl StorageState {
         }
     }
 
+    /// Fetch an asset the recorder could not, and add it to the cache
+    #[cfg(feature = "fetcher")]
+    pub(crate) async fn fetch_server_side(
+        &self,
+        url: &str,
+        user_agent: Option<&str>,
+        max_size: Option<u64>,
+    ) -> Result<FetchedAsset, AssetError> {
+        let options = crate::asset_cache::fetcher::FetchOptions {
+            user_agent,
+            max_size,
+            normalization: self.asset_normalization,
+        };
+        crate::asset_cache::fetcher::fetch_and_cache_asset(
+            url,
+            &options,
+            self.metadata_store.as_ref(),
+            self.asset_file_store.as_ref(),
+            self.asset_scanner.as_ref(),
+            self.observability.as_ref(),
+        ).await
+    }
+
+    /// Without the `fetcher` feature, assets the recorder could not fetch are dropped
+    #[cfg(not(feature = "fetcher"))]
+    pub(crate) async fn fetch_server_side(
+        &self,
+        url: &str,
+        _user_agent: Option<&str>,
+        _max_size: Option<u64>,
+    ) -> Result<FetchedAsset, AssetError> {
+        Err(AssetError::NotFound(format!("{} (server-side fetching is disabled)", url)))
+    }
+
+    /// Process an Asset frame carrying inline data: normalize and hash it,
+    /// store it in CAS
     /// Returns an AssetReference frame with random_id for writing to recording
-    /// Returns None if the asset is empty and server-side fetch also fails
-    async fn process_asset_frame(
+    /// Returns None if the asset is empty, and AssetError::TooLarge if it does
+    /// not fit in `budget`
+    pub(crate) async fn process_asset_frame(
         &self,
         asset: &domcorder_proto::AssetData,
         site_origin: Option<&str>,
-        user_agent: Option<&str>,
-    ) -> Result<Option<domcorder_proto::AssetReferenceData>, Box<dyn std::error::Error + Send + Sync>> {
-        let data = &asset.buf;
-        
-        // Check fetch_error to determine if we should attempt server-side fetch
-        let should_fetch = Self::should_fetch_server_side(&asset.fetch_error);
-        
-        if data.is_empty() && should_fetch {
-            // Log unknown errors
-            if let domcorder_proto::AssetFetchError::Unknown(msg) = &asset.fetch_error {
-                warn!("⚠️  Asset fetch unknown error: asset_id={}, url={}, error={}, attempting server-side fetch", 
-                      asset.asset_id, asset.url, msg);
-            }
-            
-            
-            match crate::asset_cache::fetcher::fetch_and_cache_asset(
-                &asset.url,
-                user_agent,
-                self.metadata_store.as_ref(),
-                self.asset_file_store.as_ref(),
-            ).await {
-                Ok((sha256_hash, random_id)) => {
-                    info!("✅ Successfully fetched asset server-side: random_id={}", &random_id[..16]);
-                    
-                    // Register asset usage on the site (if we have site context)
-                    if let Some(origin) = site_origin {
-                        let usage_params = AssetUsageParams {
-                            site_origin: origin.to_string(),
-                            url: asset.url.clone(),
-                            sha256_hash: sha256_hash.clone(),
-                            size: 0, // We don't know the actual size from the fetch result
-                        };
-                        if let Err(e) = self.metadata_store.register_asset_usage(usage_params).await {
-                            warn!("Failed to register asset usage: {}", e);
-                        }
-                    }
-                    
-                    // Return AssetReference with random_id (for recording)
-                    return Ok(Some(domcorder_proto::AssetReferenceData {
-                        asset_id: asset.asset_id,
-                        url: asset.url.clone(),
-                        hash: random_id,
-                        mime: asset.mime.clone(),
-                    }));
-                }
-                Err(e) => {
-                    warn!("❌ Failed to fetch asset server-side: {}", e);
-                    // Skip this asset - both client and server fetch failed
-                    return Ok(None);
-                }
-            }
-        } else if data.is_empty() && !should_fetch {
+        budget: &mut AssetBudget,
+    ) -> Result<Option<domcorder_proto::AssetReferenceData>, StorageError> {
+        if asset.buf.is_empty() {
             // Legitimately empty asset or HTTP error - skip it
             if matches!(asset.fetch_error, domcorder_proto::AssetFetchError::Http) {
                 warn!("⚠️  Asset HTTP error: asset_id={}, url={}, skipping", 
@@ -543,31 +1229,49 @@ impl StorageState {
             return Ok(None);
         }
 
+        budget.check(asset.buf.len() as u64)?;
+
+        let mime = asset.mime.as_deref().unwrap_or("application/octet-stream");
+        let normalized = self.asset_normalization.normalize(asset.buf.clone(), mime, budget.max_next())?;
+        let data = &normalized;
+        budget.check(data.len() as u64)?;
+
         // Compute SHA-256 hash (for storage and manifest)
         let sha256_hash = crate::asset_cache::hash::sha256(data);
         
         // Store asset and get/ensure random_id exists
-        let mime = asset.mime.as_deref().unwrap_or("application/octet-stream");
+        let scanned = ScannedAsset {
+            url: &asset.url,
+            mime,
+            sha256_hash: &sha256_hash,
+            data,
+        };
+        match scan_new_asset(
+            self.asset_scanner.as_ref(),
+            self.asset_file_store.as_ref(),
+            self.observability.as_ref(),
+            &scanned,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(AssetError::Quarantined(_)) => {
+                return Ok(Some(placeholder_reference(asset.asset_id, &asset.url, &asset.mime, QUARANTINED_ASSET_HASH)));
+            }
+            Err(e) => return Err(e.into()),
+        }
         let random_id = store_or_get_asset_metadata(
             &sha256_hash,
             data,
             mime,
             self.metadata_store.as_ref(),
             self.asset_file_store.as_ref(),
+            self.observability.as_ref(),
         ).await?;
+        budget.charge(data.len() as u64);
 
         // Register asset usage on the site (if we have site context)
-        if let Some(origin) = site_origin {
-            let usage_params = AssetUsageParams {
-                site_origin: origin.to_string(),
-                url: asset.url.clone(),
-                sha256_hash: sha256_hash.clone(),
-                size: data.len() as u64,
-            };
-            if let Err(e) = self.metadata_store.register_asset_usage(usage_params).await {
-                warn!("Failed to register asset usage: {}", e);
-            }
-        }
+        self.register_asset_usage(site_origin, &asset.url, &sha256_hash, data.len() as u64).await;
 
         // Return AssetReference with random_id (for recording)
         Ok(Some(domcorder_proto::AssetReferenceData {
@@ -579,13 +1283,13 @@ impl StorageState {
     }
 
     /// Process an AssetReference frame: verify server has the asset and resolve SHA-256 → random_id
-    /// Returns AssetReference with random_id for writing to recording
+    /// Returns AssetReference with random_id for writing to recording, or None
+    /// if the asset is not cached and has to be fetched
     async fn process_asset_reference_frame(
         &self,
         asset_ref: &domcorder_proto::AssetReferenceData,
         site_origin: Option<&str>,
-        user_agent: Option<&str>,
-    ) -> Result<domcorder_proto::AssetReferenceData, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Option<domcorder_proto::AssetReferenceData>, StorageError> {
         // The hash field contains SHA-256 from the client
         // Resolve it to random_id for storage in the recording
         match self.metadata_store.resolve_hashes(&asset_ref.hash).await {
@@ -593,17 +1297,8 @@ impl StorageState {
                 // Asset exists! Just register usage
                 debug!("✅ AssetReference verified: sha256={}, random_id={}", &asset_ref.hash[..16], &random_id[..16]);
                 
-                if let Some(origin) = site_origin {
-                    let usage_params = AssetUsageParams {
-                        site_origin: origin.to_string(),
-                        url: asset_ref.url.clone(),
-                        sha256_hash: asset_ref.hash.clone(), // Original SHA-256 from client
-                        size: 0, // We don't know size from reference, but that's OK
-                    };
-                    if let Err(e) = self.metadata_store.register_asset_usage(usage_params).await {
-                        warn!("Failed to register asset usage: {}", e);
-                    }
-                }
+                // We don't know size from reference, but that's OK
+                self.register_asset_usage(site_origin, &asset_ref.url, &asset_ref.hash, 0).await;
                 
                 // Get MIME type from metadata store
                 let mime = self.metadata_store.get_asset_mime_type(&random_id).await
@@ -611,143 +1306,541 @@ impl StorageState {
                     .flatten();
                 
                 // Return AssetReference with random_id (for recording)
-                Ok(domcorder_proto::AssetReferenceData {
+                Ok(Some(domcorder_proto::AssetReferenceData {
                     asset_id: asset_ref.asset_id,
                     url: asset_ref.url.clone(),
                     hash: random_id,
                     mime,
-                })
+                }))
             }
             Ok(None) => {
-                // Asset not found - try to fetch it server-side
+                // Asset not found - it will be fetched server-side
                 warn!("⚠️  AssetReference not found in cache: sha256={}, attempting server fetch", 
                       &asset_ref.hash[..16]);
-                
-                match crate::asset_cache::fetcher::fetch_and_cache_asset(
-                    &asset_ref.url,
-                    user_agent,
-                    self.metadata_store.as_ref(),
-                    self.asset_file_store.as_ref(),
-                ).await {
-                    Ok((fetched_sha256, fetched_random_id)) => {
-                        // Verify the fetched hash matches what recorder expected
-                        if fetched_sha256 != asset_ref.hash {
-                            return Err(Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Hash mismatch: expected {}, got {}", 
-                                       &asset_ref.hash[..16], &fetched_sha256[..16]),
-                            )));
-                        }
-                        
-                        // Register usage
-                        if let Some(origin) = site_origin {
-                            let usage_params = AssetUsageParams {
-                                site_origin: origin.to_string(),
-                                url: asset_ref.url.clone(),
-                                sha256_hash: asset_ref.hash.clone(),
-                                size: 0,
-                            };
-                            if let Err(e) = self.metadata_store.register_asset_usage(usage_params).await {
-                                warn!("Failed to register asset usage: {}", e);
-                            }
-                        }
-                        
-                        // Get MIME type from metadata store
-                        let mime = self.metadata_store.get_asset_mime_type(&fetched_random_id).await
-                            .ok()
-                            .flatten();
-                        
-                        // Return AssetReference with random_id (for recording)
-                        Ok(domcorder_proto::AssetReferenceData {
-                            asset_id: asset_ref.asset_id,
-                            url: asset_ref.url.clone(),
-                            hash: fetched_random_id,
-                            mime,
-                        })
-                    }
-                    Err(e) => {
-                        warn!("Failed to fetch asset server-side: {}", e);
-                        Err(Box::new(e))
-                    }
-                }
+                Ok(None)
             }
             Err(e) => {
                 warn!("Error checking asset cache: {}", e);
-                Err(Box::new(e))
+                Err(e.into())
             }
         }
     }
 
     /// Filter function for frames - processes Asset and AssetReference frames
     /// Converts AssetData → AssetReference and resolves AssetReference hash (SHA-256 → random_id)
+    /// Moves large stylesheet text to the CAS (see [`crate::asset_cache::stylesheets`])
+    ///
+    /// Assets that exceed `budget` are replaced by an asset-skipped Annotation.
+    /// Assets that need a server-side fetch are returned as
+    /// [`FilteredFrame::Fetch`] for the caller to queue.
     async fn filter_frame_async(
         &self,
         frame: domcorder_proto::Frame,
         site_origin: Option<&str>,
-        user_agent: Option<&str>,
-    ) -> Option<domcorder_proto::Frame> {
+        budget: &mut AssetBudget,
+    ) -> FilteredFrame {
         match &frame {
+            // Assets the recorder could not load are fetched server-side
+            domcorder_proto::Frame::Asset(asset)
+                if asset.buf.is_empty() && Self::should_fetch_server_side(&asset.fetch_error) =>
+            {
+                // Log unknown errors
+                if let domcorder_proto::AssetFetchError::Unknown(msg) = &asset.fetch_error {
+                    warn!("⚠️  Asset fetch unknown error: asset_id={}, url={}, error={}, attempting server-side fetch", 
+                          asset.asset_id, asset.url, msg);
+                }
+                FilteredFrame::Fetch(PendingFetch {
+                    asset_id: asset.asset_id,
+                    url: asset.url.clone(),
+                    mime: asset.mime.clone(),
+                    expected_sha256: None,
+                })
+            }
             // Process Asset frames: extract and cache the binary data, convert to AssetReference
             domcorder_proto::Frame::Asset(asset) => {
-                match self.process_asset_frame(asset, site_origin, user_agent).await {
+                match self.process_asset_frame(asset, site_origin, budget).await {
                     Ok(Some(asset_ref)) => {
                         // Convert to AssetReference frame with random_id
-                        Some(domcorder_proto::Frame::AssetReference(asset_ref))
+                        FilteredFrame::Write(domcorder_proto::Frame::AssetReference(asset_ref))
                     }
                     Ok(None) => {
                         // Empty asset - skip it
-                        None
+                        FilteredFrame::Skip
+                    }
+                    Err(StorageError::Asset(AssetError::TooLarge { size, limit })) => {
+                        FilteredFrame::Write(self.skip_asset(asset.asset_id, &asset.url, size, limit))
                     }
                     Err(e) => {
                         warn!("Failed to process asset frame: {}", e);
-                        None // Skip this frame on error
+                        FilteredFrame::Skip // Skip this frame on error
                     }
                 }
             }
             // Process AssetReference frames: resolve SHA-256 → random_id
             domcorder_proto::Frame::AssetReference(asset_ref) => {
-                match self.process_asset_reference_frame(asset_ref, site_origin, user_agent).await {
-                    Ok(asset_ref_with_random_id) => {
+                match self.process_asset_reference_frame(asset_ref, site_origin).await {
+                    Ok(Some(asset_ref_with_random_id)) => {
                         // Return AssetReference with random_id
-                        Some(domcorder_proto::Frame::AssetReference(asset_ref_with_random_id))
+                        FilteredFrame::Write(domcorder_proto::Frame::AssetReference(asset_ref_with_random_id))
                     }
+                    Ok(None) => FilteredFrame::Fetch(PendingFetch {
+                        asset_id: asset_ref.asset_id,
+                        url: asset_ref.url.clone(),
+                        mime: asset_ref.mime.clone(),
+                        expected_sha256: Some(asset_ref.hash.clone()),
+                    }),
                     Err(e) => {
                         warn!("Failed to process asset reference frame: {}", e);
-                        None // Skip this frame on error
+                        FilteredFrame::Skip // Skip this frame on error
+                    }
+                }
+            }
+            // Large stylesheet text is stored once in the CAS and referenced
+            domcorder_proto::Frame::NewAdoptedStyleSheet(_) | domcorder_proto::Frame::StyleSheetReplaced(_) => {
+                let Some(min_bytes) = self.stylesheet_dedup_bytes else {
+                    return FilteredFrame::Write(frame);
+                };
+                match dedup_stylesheet_frame(
+                    &frame,
+                    min_bytes,
+                    self.metadata_store.as_ref(),
+                    self.asset_file_store.as_ref(),
+                    self.observability.as_ref(),
+                )
+                .await
+                {
+                    Ok(Some(reference)) => FilteredFrame::Write(reference),
+                    Ok(None) => FilteredFrame::Write(frame),
+                    Err(e) => {
+                        warn!("Failed to cache stylesheet text, keeping it inline: {}", e);
+                        FilteredFrame::Write(frame)
                     }
                 }
             }
             // Heartbeat frames - keep connection alive but don't write to recording
-            domcorder_proto::Frame::Heartbeat => {
-                None // Skip heartbeat frames in recording
+            domcorder_proto::Frame::Heartbeat
+            | domcorder_proto::Frame::HeartbeatPing(_)
+            | domcorder_proto::Frame::HeartbeatPong(_) => {
+                FilteredFrame::Skip // Skip heartbeat frames in recording
+            }
+            _ => FilteredFrame::Write(frame),
+        }
+    }
+
+    /// Run a queued server-side fetch, returning the frame that resolves the
+    /// asset (if any) and the bytes it added
+    ///
+    /// If the fetch fails, the asset resolves to the version its URL served
+    /// at `recorded_at` (ms since the epoch), if that is cached.
+    async fn complete_fetch(
+        &self,
+        pending: PendingFetch,
+        site_origin: Option<&str>,
+        user_agent: Option<&str>,
+        max_size: Option<u64>,
+        recorded_at: u64,
+    ) -> (Option<domcorder_proto::Frame>, u64) {
+        let FetchedAsset { sha256_hash, source_sha256_hash, random_id, size } = match self.fetch_server_side(&pending.url, user_agent, max_size).await {
+            Ok(fetched) => fetched,
+            Err(AssetError::TooLarge { size, limit }) => {
+                return (Some(self.skip_asset(pending.asset_id, &pending.url, size, limit)), 0);
+            }
+            Err(AssetError::Quarantined(_)) => {
+                let placeholder = placeholder_reference(pending.asset_id, &pending.url, &pending.mime, QUARANTINED_ASSET_HASH);
+                return (Some(domcorder_proto::Frame::AssetReference(placeholder)), 0);
+            }
+            Err(e) => {
+                // Both client and server fetch failed
+                warn!("❌ Failed to fetch asset server-side: {}", e);
+                return (self.version_at_recording_time(pending, recorded_at).await, 0);
+            }
+        };
+        info!("✅ Successfully fetched asset server-side: random_id={}", &random_id[..16]);
+
+        // Verify the fetched hash matches what recorder expected; the
+        // recorder hashed the bytes before normalization
+        if let Some(expected) = &pending.expected_sha256
+            && *expected != sha256_hash
+            && *expected != source_sha256_hash
+        {
+            warn!("Failed to fetch asset server-side: {}", AssetError::HashMismatch {
+                expected: expected.clone(),
+                actual: source_sha256_hash,
+            });
+            return (self.version_at_recording_time(pending, recorded_at).await, size);
+        }
+
+        self.register_asset_usage(site_origin, &pending.url, &sha256_hash, size).await;
+
+        let mime = match pending.mime {
+            Some(mime) => Some(mime),
+            None => self.metadata_store.get_asset_mime_type(&random_id).await.ok().flatten(),
+        };
+        let asset_ref = domcorder_proto::AssetReferenceData {
+            asset_id: pending.asset_id,
+            url: pending.url,
+            hash: random_id,
+            mime,
+        };
+        (Some(domcorder_proto::Frame::AssetReference(asset_ref)), size)
+    }
+
+    /// A reference to the cached version `pending`'s URL served at
+    /// `recorded_at`, for an asset that couldn't be loaded; None (the asset
+    /// is skipped) if there is none
+    async fn version_at_recording_time(&self, pending: PendingFetch, recorded_at: u64) -> Option<domcorder_proto::Frame> {
+        let random_id = match self.asset_version_at(&pending.url, recorded_at).await {
+            Ok(Some(random_id)) => random_id,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Failed to look up the version of {} at recording time: {}", pending.url, e);
+                return None;
             }
-            _ => Some(frame),
+        };
+        info!("📌 Using the version of {} cached at recording time", pending.url);
+        let mime = match pending.mime {
+            Some(mime) => Some(mime),
+            None => self.metadata_store.get_asset_mime_type(&random_id).await.ok().flatten(),
+        };
+        Some(domcorder_proto::Frame::AssetReference(domcorder_proto::AssetReferenceData {
+            asset_id: pending.asset_id,
+            url: pending.url,
+            hash: random_id,
+            mime,
+        }))
+    }
+
+    /// Register asset usage on the site (if we have site context); failures are logged
+    pub(crate) async fn register_asset_usage(&self, site_origin: Option<&str>, url: &str, sha256_hash: &str, size: u64) {
+        let Some(origin) = site_origin else {
+            return;
+        };
+        let usage_params = AssetUsageParams {
+            site_origin: origin.to_string(),
+            url: url.to_string(),
+            sha256_hash: sha256_hash.to_string(),
+            size,
+        };
+        if let Err(e) = self.metadata_store.register_asset_usage(usage_params).await {
+            warn!("Failed to register asset usage: {}", e);
+        }
+    }
+
+    /// Record an asset that exceeded the asset limits, returning its marker frame
+    fn skip_asset(&self, asset_id: u32, url: &str, size: u64, limit: u64) -> domcorder_proto::Frame {
+        warn!("⚠️  Asset over limit, not caching: asset_id={}, url={}, {} bytes > {} bytes", asset_id, url, size, limit);
+        self.observability.counter(names::ASSETS_SKIPPED, 1, &[]);
+        domcorder_proto::Frame::Annotation(skipped_annotation(asset_id, url, size, limit))
+    }
+
+    /// Copy frames from `frame_reader` to `frame_writer`, caching assets on the way
+    ///
+    /// Server-side asset fetches can take up to 30s, so they do not hold up
+    /// the frames behind them: the asset is written as a provisional
+    /// AssetReference with [`PENDING_ASSET_HASH`] (which the player skips),
+    /// the fetch runs on a queue of at most [`ASSET_FETCH_QUEUE`] concurrent
+    /// fetches, and the resolved AssetReference is appended when it finishes.
+    /// Reading stops while the queue is full, and fetches still running when
+    /// the input ends are awaited before returning.
+    async fn ingest_frames<R: AsyncRead + Unpin>(
+        &self,
+        frame_reader: &mut FrameReader<R>,
+        frame_writer: &mut FrameWriter<Box<dyn RecordingWriter>>,
+        ingest: IngestContext<'_>,
+        span: &mut IngestSpan<'_>,
+        analytics: &mut IngestAnalytics,
+    ) -> Result<(), StorageError> {
+        let mut budget = AssetBudget::new(self.asset_limits);
+        let mut clock = ClockSkewEstimator::new();
+        let mut timeline = TimelineNormalizer::new();
+        let mut fetches = FuturesUnordered::new();
+        let mut input_done = false;
+        let mut enforcer = PolicyEnforcer::default();
+        let mut frame_filter = self.frame_rules.filter();
+        let mut frame_budget = self.frame_budgets.enforcer();
+        let mut idle = IdleCompressor::new(self.idle_compression_ms);
+        let finalize = self.finalize_signal(ingest.id).unwrap_or_default();
+        let mut references = AssetReferences::default();
+        let mut alerts = AlertMatcher::new(&self.alert_rules, ingest.site_origin);
+        // Only a live recorder can be asked for a keyframe
+        let mut drift = ingest.live.then(DriftDetector::new);
+        // The latest Timestamp written, for assets resolved by recording time
+        let mut recorded_at = None;
+
+        loop {
+            tokio::select! {
+                Some((frame, size)) = fetches.next(), if !fetches.is_empty() => {
+                    budget.charge(size);
+                    if let Some(frame) = frame {
+                        self.write_ingested_frame(frame_writer, &ingest, &mut references, frame).await?;
+                    }
+                }
+                next = next_frame(frame_reader, &finalize), if !input_done && fetches.len() < ASSET_FETCH_QUEUE => {
+                    let frames = match next {
+                        // The recorder's cache is only of use to the handshake
+                        Some(Ok(domcorder_proto::Frame::CacheInventory(_))) => Vec::new(),
+                        Some(Ok(frame)) => 'frame: {
+                            let Some(frame) = frame_filter.process(frame) else {
+                                break 'frame Vec::new();
+                            };
+                            let frame = self.privacy.minimize(frame);
+                            if let domcorder_proto::Frame::RecordingMetadata(metadata) = &frame {
+                                let origin = match ingest.site_origin {
+                                    Some(origin) => Some(origin.to_string()),
+                                    None => page_of_url(&metadata.initial_url).map(|(origin, _)| origin),
+                                };
+                                let policy = self.recording_policy(origin.as_deref()).await?;
+                                policy.admit(ingest.id)?;
+                                enforcer = policy.enforcer();
+                            }
+                            let arrived_at = now_ms();
+                            let frame = if self.normalize_timestamps {
+                                timeline.normalize_frame(frame, ingest.live.then_some(arrived_at))
+                            } else {
+                                frame
+                            };
+                            if enforcer.expired(&frame) {
+                                info!("⏱️ Recording {} reached its maximum duration", ingest.id);
+                                input_done = true;
+                                break 'frame [idle.finish(), frame_budget.finish()].concat();
+                            }
+                            let Some(frame) = enforcer.process(frame) else {
+                                break 'frame Vec::new();
+                            };
+                            let Some(frame) = frame_budget.process(frame) else {
+                                break 'frame Vec::new();
+                            };
+
+                            // Update latest timestamp if this is a Timestamp frame
+                            if ingest.live
+                                && let domcorder_proto::Frame::Timestamp(timestamp_data) = &frame
+                            {
+                                self.update_recording_timestamp(ingest.id, timestamp_data.timestamp);
+                            }
+
+                            // Skew is measured against the timeline the recording is stored on
+                            if let domcorder_proto::Frame::HeartbeatPong(pong) = &frame {
+                                let mut pong = pong.clone();
+                                if self.normalize_timestamps {
+                                    pong.client_timestamp = timeline.to_timeline(pong.client_timestamp);
+                                }
+                                if clock.push(ClockSkew::measure(&pong, arrived_at)) {
+                                    self.store_clock_skew(ingest.id, &clock).await;
+                                }
+                            }
+
+                            // Idle spans are held back until they end
+                            idle.push(frame)
+                        }
+                        // The stream ended part way through a frame (e.g. the
+                        // recorder's connection dropped): that frame is replaced
+                        // by a marker and the recording is finished as usual
+                        Some(Err(e)) if e.kind() == io::ErrorKind::UnexpectedEof && frame_reader.buffered_len() > 0 => {
+                            let discarded_bytes = frame_reader.buffered_len() as u64;
+                            warn!("✂️ Recording {} ended part way through a frame, discarding {} bytes", ingest.id, discarded_bytes);
+                            input_done = true;
+                            let mut frames = idle.finish();
+                            frames.extend(frame_budget.finish());
+                            frames.push(domcorder_proto::Frame::RecordingTruncated(RecordingTruncatedData { discarded_bytes }));
+                            frames
+                        }
+                        Some(Err(e)) => return Err(StorageError::Frame(e)),
+                        None => {
+                            input_done = true;
+                            [idle.finish(), frame_budget.finish()].concat()
+                        }
+                    };
+
+                    for frame in frames {
+                        analytics.push_frame(&frame);
+                        span.push_frame(&frame);
+                        let fired = alerts.push_frame(&frame);
+                        if let Some(reason) = drift.as_mut().and_then(|drift| drift.push_frame(&frame)) {
+                            self.request_keyframe(ingest.id, reason);
+                        }
+                        if let domcorder_proto::Frame::Timestamp(data) = &frame {
+                            recorded_at = Some(data.timestamp);
+                        }
+
+                        // Process Asset and AssetReference frames
+                        match self.filter_frame_async(frame, ingest.site_origin, &mut budget).await {
+                            FilteredFrame::Write(frame) => self.write_ingested_frame(frame_writer, &ingest, &mut references, frame).await?,
+                            FilteredFrame::Fetch(pending) => {
+                                let provisional = placeholder_reference(pending.asset_id, &pending.url, &pending.mime, PENDING_ASSET_HASH);
+                                self.write_ingested_frame(frame_writer, &ingest, &mut references, domcorder_proto::Frame::AssetReference(provisional)).await?;
+                                let recorded_at = recorded_at.unwrap_or_else(now_ms);
+                                fetches.push(self.complete_fetch(pending, ingest.site_origin, ingest.user_agent, budget.max_next(), recorded_at));
+                            }
+                            // If filter returned Skip, skip this frame
+                            FilteredFrame::Skip => {}
+                        }
+
+                        for rule in fired {
+                            let annotation = self.raise_alert(rule, ingest.id, alerts.site_origin(), alerts.offset());
+                            if let Some(annotation) = annotation {
+                                analytics.push_frame(&annotation);
+                                span.push_frame(&annotation);
+                                self.write_ingested_frame(frame_writer, &ingest, &mut references, annotation).await?;
+                            }
+                        }
+                    }
+                }
+                else => break,
+            }
+        }
+        analytics.set_dropped_frames(frame_filter.into_counts());
+        analytics.set_summarized_frames(frame_budget.into_summarized());
+        Ok(())
+    }
+
+    /// The signal that asks the session writing `id` to finish it (see
+    /// [`StorageState::finalize_recording`]); None if `id` is not active
+    pub(crate) fn finalize_signal(&self, id: &RecordingId) -> Option<std::sync::Arc<tokio::sync::Notify>> {
+        let active_recordings = self.active_recordings.lock().unwrap();
+        active_recordings.get(id).map(|info| info.finalize.clone())
+    }
+
+    /// Ask the recorder of live recording `id` for a fresh keyframe, since its
+    /// frames no longer match its page
+    fn request_keyframe(&self, id: &RecordingId, reason: String) {
+        info!("🔄 Recording {} drifted from its page ({}), requesting a keyframe", id, reason);
+        if self.keyframe_requests.request(id, reason) {
+            self.observability.counter(names::KEYFRAMES_REQUESTED, 1, &[]);
+        }
+    }
+
+    /// Store a recording's improved clock skew estimate; failures are logged
+    async fn store_clock_skew(&self, id: &RecordingId, clock: &ClockSkewEstimator) {
+        let Some(skew) = clock.estimate() else {
+            return;
+        };
+        debug!("Clock skew of {}: {} ms (round trip {} ms)", id, skew.skew_ms, skew.round_trip_ms);
+        if let Err(e) = self.metadata_store.store_clock_skew(id.as_str(), skew).await {
+            warn!("⚠️ Failed to store clock skew of {}: {}", id, e);
+        }
+    }
+
+    /// Write one frame to the recording, record the assets it newly refers
+    /// to, the asset versions it pins and the provenance or scope it
+    /// carries, and publish it to the frame sinks
+    async fn write_ingested_frame(
+        &self,
+        frame_writer: &mut FrameWriter<Box<dyn RecordingWriter>>,
+        ingest: &IngestContext<'_>,
+        references: &mut AssetReferences,
+        frame: domcorder_proto::Frame,
+    ) -> Result<(), StorageError> {
+        frame_writer.write_frame(&frame)?;
+        if let Some(provenance) = Provenance::from_frame(&frame) {
+            self.store_provenance(ingest.id, &provenance).await;
+        }
+        if let domcorder_proto::Frame::RecordingScope(scope) = &frame
+            && let Err(e) = self.metadata_store.store_recording_scope(ingest.id.as_str(), &scope.root_selector).await
+        {
+            warn!("⚠️ Failed to store the scope of {}: {}", ingest.id, e);
+        }
+        if let domcorder_proto::Frame::SessionLink(link) = &frame
+            && let Err(e) = self.metadata_store.store_session_link(ingest.id.as_str(), &link.session_id).await
+        {
+            warn!("⚠️ Failed to store the session of {}: {}", ingest.id, e);
+        }
+        let referenced = references.new_references(&frame);
+        if !referenced.is_empty()
+            && let Err(e) = self.metadata_store.add_asset_references(ingest.id.as_str(), &referenced).await
+        {
+            warn!("⚠️ Failed to record the asset references of {}: {}", ingest.id, e);
+        }
+        if let Some((url, random_id)) = references.new_pin(&frame)
+            && let Err(e) = self.metadata_store.pin_asset_version(ingest.id.as_str(), &url, &random_id).await
+        {
+            warn!("⚠️ Failed to pin the version of {} played by {}: {}", url, ingest.id, e);
+        }
+        self.observability.counter(names::FRAMES_WRITTEN, 1, &[("frame", frame.type_name())]);
+        if self.publish_live_frame(ingest.id, &frame) {
+            frame_writer.flush()?;
         }
+        self.frame_sinks.publish(ingest.id.as_str(), ingest.site_origin, &frame).await;
+        Ok(())
     }
+}
 
+/// Most server-side asset fetches in flight for one recording
+const ASSET_FETCH_QUEUE: usize = 8;
+
+/// What ingestion does with a frame after asset processing
+enum FilteredFrame {
+    Write(domcorder_proto::Frame),
+    /// The asset has to be fetched server-side first
+    Fetch(PendingFetch),
+    Skip,
+}
+
+/// An asset waiting for a server-side fetch
+struct PendingFetch {
+    asset_id: u32,
+    url: String,
+    mime: Option<String>,
+    /// SHA-256 the recorder reported, for AssetReference frames
+    expected_sha256: Option<String>,
+}
+
+/// The recording being ingested and where it came from
+struct IngestContext<'a> {
+    id: &'a RecordingId,
+    site_origin: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    /// Frames arrive as they are recorded: the active recording's latest
+    /// timestamp is tracked, and arrival times anchor the timeline
+    live: bool,
+}
+
+/// The next frame of the input, or None once it ends or `finalize` is notified
+async fn next_frame<R: AsyncRead + Unpin>(
+    frame_reader: &mut FrameReader<R>,
+    finalize: &tokio::sync::Notify,
+) -> Option<io::Result<domcorder_proto::Frame>> {
+    tokio::select! {
+        next = frame_reader.next() => next,
+        _ = finalize.notified() => None,
+    }
+}
+
+/// `header` as recordings are stored: the current version, with frames left
+/// uncompressed, since playback streams stored frames as they are
+pub(crate) fn stored_header(header: &FileHeader) -> FileHeader {
+    FileHeader {
+        version: DCRR_VERSION,
+        ..header.clone()
+    }
+    .with_frame_compression(false)
 }
 
-/// A reader that can tail a file that's still being written to
+/// An AssetReference to `hash` in place of a cached asset
+fn placeholder_reference(asset_id: u32, url: &str, mime: &Option<String>, hash: &str) -> domcorder_proto::AssetReferenceData {
+    domcorder_proto::AssetReferenceData {
+        asset_id,
+        url: url.to_string(),
+        hash: hash.to_string(),
+        mime: mime.clone(),
+    }
+}
+
+/// A reader that can tail a recording that's still being written to
 pub struct TailingReader {
-    file: tokio::fs::File,
-    filepath: std::path::PathBuf,
-    filename: String,
-    position: u64,
+    reader: RecordingReader,
+    filename: RecordingId,
     storage_state: std::sync::Arc<StorageState>,
 }
 
 impl TailingReader {
     pub fn new(
-        file: tokio::fs::File,
-        filepath: std::path::PathBuf,
-        filename: String,
+        reader: RecordingReader,
+        filename: RecordingId,
         storage_state: std::sync::Arc<StorageState>,
     ) -> Self {
         Self {
-            file,
-            filepath,
+            reader,
             filename,
-            position: 32, // Start after the header
             storage_state,
         }
     }
@@ -761,52 +1854,90 @@ impl tokio::io::AsyncRead for TailingReader {
     ) -> std::task::Poll<io::Result<()>> {
         use std::pin::Pin;
 
-        // Try to read from the current position
-        let poll_result = Pin::new(&mut self.file).poll_read(cx, buf);
+        // Check before reading: if the recording was already complete, an empty
+        // read below means every byte has been seen
+        let was_active = self.storage_state.is_recording_active(&self.filename);
+        let filled_before = buf.filled().len();
+
+        match Pin::new(&mut self.reader).poll_read(cx, buf) {
+            std::task::Poll::Ready(Ok(())) if buf.filled().len() == filled_before && was_active => {
+                // Recording is still active, keep waiting
+                // TODO: Register wakers in active_recordings so writes and
+                // mark_recording_completed() wake readers instead of polling
+
+                // Schedule a wake-up after a short delay (current polling approach)
+                let waker = cx.waker().clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    waker.wake();
+                });
+                std::task::Poll::Pending
+            }
+            other => other,
+        }
+    }
+}
 
-        match poll_result {
-            std::task::Poll::Ready(Ok(())) => {
-                if buf.filled().is_empty() {
-                    // No data available, check if file has grown
-                    let metadata = match std::fs::metadata(&self.filepath) {
-                        Ok(metadata) => metadata,
-                        Err(e) => return std::task::Poll::Ready(Err(e)),
-                    };
+/// Reports one recording's ingest to the observability hooks
+///
+/// Dropping the span without calling [`IngestSpan::complete`] reports a failure,
+/// so every early return on the ingest path is counted.
+struct IngestSpan<'a> {
+    hooks: &'a dyn ObservabilityHooks,
+    id: String,
+    started: Instant,
+    completed: bool,
+    trace: Option<SpanBuilder>,
+}
 
-                    if metadata.len() > self.position {
-                        // File has grown, seek to current position and try reading again
-                        // Note: We need to wake the task to retry reading
-                        cx.waker().wake_by_ref();
-                        return std::task::Poll::Pending;
-                    } else {
-                        // File hasn't grown yet, check if recording is still active
-                        if !self.storage_state.is_recording_active(&self.filename) {
-                            // Recording is no longer active, return EOF
-                            return std::task::Poll::Ready(Ok(()));
-                        }
+impl<'a> IngestSpan<'a> {
+    fn start(hooks: &'a dyn ObservabilityHooks, id: &RecordingId) -> Self {
+        hooks.span_event(names::RECORDING_INGEST, SpanEvent::Started, id.as_str());
+        Self {
+            hooks,
+            id: id.to_string(),
+            started: Instant::now(),
+            completed: false,
+            trace: Some(
+                SpanBuilder::start(names::RECORDING_INGEST, None)
+                    .attribute(RECORDING_ID_ATTRIBUTE, id.as_str()),
+            ),
+        }
+    }
 
-                        // Recording is still active, keep waiting
-                        // TODO: Optimize this polling approach:
-                        // 1. Use filesystem notifications (inotify/kqueue) to detect file changes
-                        // 2. Register waker in active_recordings HashMap so mark_recording_completed()
-                        //    can immediately wake all TailingReaders for that file
-                        // 3. This would eliminate the 100ms polling delay and be more efficient
-
-                        // Schedule a wake-up after a short delay (current polling approach)
-                        let waker = cx.waker().clone();
-                        tokio::spawn(async move {
-                            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                            waker.wake();
-                        });
-                        return std::task::Poll::Pending;
-                    }
-                } else {
-                    // Successfully read some data
-                    self.position += buf.filled().len() as u64;
-                    std::task::Poll::Ready(Ok(()))
-                }
-            }
-            other => other,
+    /// Join the recorder's trace when the recording carries a TraceContext frame
+    fn push_frame(&mut self, frame: &domcorder_proto::Frame) {
+        let domcorder_proto::Frame::TraceContext(context) = frame else {
+            return;
+        };
+        let Some(trace) = self.trace.as_mut().filter(|trace| !trace.has_parent()) else {
+            return;
+        };
+        match TraceParent::parse(&context.traceparent) {
+            Some(parent) => trace.set_parent(&parent),
+            None => warn!("⚠️ Ignoring invalid traceparent in {}: {}", self.id, context.traceparent),
+        }
+    }
+
+    fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for IngestSpan<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let (event, outcome) = if self.completed {
+            (SpanEvent::Completed { elapsed }, "completed")
+        } else {
+            (SpanEvent::Failed { elapsed }, "failed")
+        };
+        self.hooks.span_event(names::RECORDING_INGEST, event, &self.id);
+        self.hooks.counter(names::RECORDINGS, 1, &[("outcome", outcome)]);
+        self.hooks
+            .histogram(names::RECORDING_INGEST_SECONDS, elapsed.as_secs_f64(), &[("outcome", outcome)]);
+        if let Some(trace) = self.trace.take() {
+            self.hooks.span_finished(&trace.finish(self.completed));
         }
     }
 }
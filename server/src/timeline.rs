@@ -0,0 +1,526 @@
+//! Interaction timeline extraction
+//!
+//! Produces a compact list of significant events (clicks, key presses,
+//! navigations, errors, annotations, trace links) from a recording so UIs can render an
+//! event strip without downloading and decoding the binary stream.
+//!
+//! Offsets count recorded time only: the span between a RecordingPaused and
+//! the next RecordingResumed frame is left out and marked with a gap event,
+//! matching the player, which skips paused spans. Idle periods collapsed at
+//! ingestion are skipped the same way, with gap events whose reason is `idle`.
+//!
+//! A session timeline interleaves the events of the recordings linked to one
+//! user session (its tabs and windows) by the wall-clock time of the
+//! Timestamp frame before each event.
+
+use crate::trace::TraceParent;
+use domcorder_proto::{Frame, FrameReader};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::io::AsyncRead;
+
+/// A significant event in a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimelineEventKind {
+    /// A new page was loaded (initial URL or subsequent keyframe)
+    Navigation {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Click { x: u32, y: u32 },
+    KeyPress {
+        code: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        modifiers: Vec<String>,
+    },
+    Error {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source_url: Option<String>,
+    },
+    Annotation {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<String>,
+    },
+    /// The recording was linked to a distributed trace
+    Trace { trace_id: String },
+    /// Capture was paused, or the session idle, for `duration_ms` at this point
+    Gap {
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    /// Milliseconds of recorded (unpaused) time since the first timestamp
+    pub offset_ms: u64,
+    #[serde(flatten)]
+    pub kind: TimelineEventKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeline {
+    /// First Timestamp frame value (Unix ms)
+    pub start_timestamp: Option<u64>,
+    /// Last Timestamp frame value (Unix ms)
+    pub end_timestamp: Option<u64>,
+    /// Total time spent paused or idle, included in the timestamps but not the offsets
+    #[serde(default)]
+    pub paused_ms: u64,
+    pub events: Vec<TimelineEvent>,
+}
+
+/// An event of one of a session's recordings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub recording_id: String,
+    /// Wall-clock time of the event (Unix ms), on the server's clock when the
+    /// recorder's skew is known
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+}
+
+/// A recording linked to a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionRecording {
+    pub recording_id: String,
+    pub start_timestamp: Option<u64>,
+    pub end_timestamp: Option<u64>,
+}
+
+/// The events of all of a session's recordings, in timestamp order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTimeline {
+    pub session_id: String,
+    pub recordings: Vec<SessionRecording>,
+    pub events: Vec<SessionEvent>,
+}
+
+impl SessionTimeline {
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Add the timeline of recording `recording_id`, whose events happened at
+    /// `timestamps` (see [`TimelineBuilder::finish_with_timestamps`]), moved
+    /// onto the server's clock by `to_server_time`
+    pub fn add_recording(
+        &mut self,
+        recording_id: &str,
+        timeline: Timeline,
+        timestamps: Vec<Option<u64>>,
+        to_server_time: impl Fn(u64) -> u64,
+    ) {
+        self.recordings.push(SessionRecording {
+            recording_id: recording_id.to_string(),
+            start_timestamp: timeline.start_timestamp.map(&to_server_time),
+            end_timestamp: timeline.end_timestamp.map(&to_server_time),
+        });
+        for (event, timestamp) in timeline.events.into_iter().zip(timestamps) {
+            // Events before the first timestamp happened when it was taken
+            let Some(timestamp) = timestamp.or(timeline.start_timestamp) else {
+                continue;
+            };
+            self.events.push(SessionEvent {
+                recording_id: recording_id.to_string(),
+                timestamp: to_server_time(timestamp),
+                event,
+            });
+        }
+        // Stable, so each recording's events keep their order
+        self.events.sort_by_key(|event| event.timestamp);
+    }
+}
+
+/// Tracks recording time from Timestamp frames, leaving out paused spans
+/// and idle periods
+#[derive(Debug, Default)]
+pub struct ActiveClock {
+    start: Option<u64>,
+    now: Option<u64>,
+    /// When the current pause began, if paused
+    paused_at: Option<u64>,
+    paused_ms: u64,
+    /// An idle period whose end the next timestamp brings
+    idle_ms: u64,
+}
+
+impl ActiveClock {
+    /// Feed a Timestamp, RecordingPaused, RecordingResumed or IdlePeriod
+    /// frame, returning the length of the pause a RecordingResumed frame ends
+    /// or of the idle period
+    pub fn push_frame(&mut self, frame: &Frame) -> Option<u64> {
+        match frame {
+            Frame::Timestamp(ts) => {
+                self.start.get_or_insert(ts.timestamp);
+                self.paused_ms += std::mem::take(&mut self.idle_ms);
+                // A pause before the first timestamp starts with the recording
+                if self.now.is_none() && self.paused_at.is_some() {
+                    self.paused_at = Some(ts.timestamp);
+                }
+                self.now = Some(ts.timestamp);
+                None
+            }
+            Frame::RecordingPaused(_) => {
+                self.paused_at.get_or_insert(self.now.unwrap_or(0));
+                None
+            }
+            Frame::RecordingResumed => {
+                let gap = self.current_pause()?;
+                self.paused_at = None;
+                self.paused_ms += gap;
+                Some(gap)
+            }
+            Frame::IdlePeriod(idle) => {
+                self.idle_ms += idle.duration_ms;
+                Some(idle.duration_ms)
+            }
+            _ => None,
+        }
+    }
+
+    /// First timestamp seen
+    pub fn start(&self) -> Option<u64> {
+        self.start
+    }
+
+    /// Latest timestamp seen
+    pub fn now(&self) -> Option<u64> {
+        self.now
+    }
+
+    /// Length of the pause in progress, if paused
+    pub fn current_pause(&self) -> Option<u64> {
+        let paused_at = self.paused_at?;
+        Some(self.now.unwrap_or(paused_at).saturating_sub(paused_at))
+    }
+
+    /// Paused and idle time so far, including a pause still in progress
+    pub fn paused_ms(&self) -> u64 {
+        self.paused_ms + self.current_pause().unwrap_or(0)
+    }
+
+    /// Recorded time since the first timestamp
+    pub fn offset(&self) -> u64 {
+        match (self.start, self.now) {
+            (Some(start), Some(now)) => now.saturating_sub(start).saturating_sub(self.paused_ms()),
+            _ => 0,
+        }
+    }
+}
+
+/// Incrementally builds a timeline from frames
+#[derive(Debug, Default)]
+pub struct TimelineBuilder {
+    timeline: Timeline,
+    clock: ActiveClock,
+    pending_url: Option<String>,
+    pause_reason: Option<String>,
+    /// The latest timestamp when each event happened
+    timestamps: Vec<Option<u64>>,
+}
+
+impl TimelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, kind: TimelineEventKind) {
+        let offset_ms = self.clock.offset();
+        self.timeline.events.push(TimelineEvent { offset_ms, kind });
+        self.timestamps.push(self.clock.now());
+    }
+
+    /// Feed the next frame of the recording
+    pub fn push_frame(&mut self, frame: &Frame) {
+        let gap = self.clock.push_frame(frame);
+        match frame {
+            Frame::Timestamp(_) => {
+                self.timeline.start_timestamp = self.clock.start();
+                self.timeline.end_timestamp = self.clock.now();
+            }
+            Frame::RecordingPaused(paused) => {
+                self.pause_reason = paused.reason.clone();
+            }
+            Frame::RecordingResumed => {
+                if let Some(duration_ms) = gap {
+                    let reason = self.pause_reason.take();
+                    self.push(TimelineEventKind::Gap { duration_ms, reason });
+                }
+            }
+            Frame::IdlePeriod(idle) => {
+                self.push(TimelineEventKind::Gap {
+                    duration_ms: idle.duration_ms,
+                    reason: Some("idle".to_string()),
+                });
+            }
+            Frame::RecordingMetadata(metadata) => {
+                // Attach the URL to the keyframe that follows
+                self.pending_url = Some(metadata.initial_url.clone());
+            }
+            Frame::Keyframe(_) | Frame::KeyframeBegin(_) => {
+                let url = self.pending_url.take();
+                self.push(TimelineEventKind::Navigation { url });
+            }
+            Frame::MouseClicked(click) => {
+                self.push(TimelineEventKind::Click { x: click.x, y: click.y });
+            }
+            Frame::MouseClickedV2(click) => {
+                self.push(TimelineEventKind::Click {
+                    x: click.x as u32,
+                    y: click.y as u32,
+                });
+            }
+            Frame::KeyPressed(key) => {
+                let modifiers = [
+                    (key.ctrl_key, "ctrl"),
+                    (key.alt_key, "alt"),
+                    (key.meta_key, "meta"),
+                    (key.shift_key, "shift"),
+                ]
+                .into_iter()
+                .filter(|(pressed, _)| *pressed)
+                .map(|(_, name)| name.to_string())
+                .collect();
+                self.push(TimelineEventKind::KeyPress {
+                    code: key.code.clone(),
+                    modifiers,
+                });
+            }
+            Frame::PageError(error) => {
+                self.push(TimelineEventKind::Error {
+                    message: error.message.clone(),
+                    source_url: error.source_url.clone(),
+                });
+            }
+            Frame::Annotation(annotation) => {
+                self.push(TimelineEventKind::Annotation {
+                    name: annotation.name.clone(),
+                    data: annotation.data.clone(),
+                });
+            }
+            Frame::TraceContext(context) => {
+                if let Some(parent) = TraceParent::parse(&context.traceparent) {
+                    self.push(TimelineEventKind::Trace { trace_id: parent.trace_id });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn finish(self) -> Timeline {
+        self.finish_with_timestamps().0
+    }
+
+    /// Finish the timeline, along with the latest Timestamp frame value
+    /// before each of its events (None before the first)
+    pub fn finish_with_timestamps(mut self) -> (Timeline, Vec<Option<u64>>) {
+        // A recording that ends paused ends with its gap
+        if let Some(duration_ms) = self.clock.current_pause() {
+            let reason = self.pause_reason.take();
+            self.push(TimelineEventKind::Gap { duration_ms, reason });
+        }
+        self.timeline.paused_ms = self.clock.paused_ms();
+        (self.timeline, self.timestamps)
+    }
+}
+
+/// Read a whole frame stream and extract its timeline
+pub async fn extract_timeline<R: AsyncRead + Unpin>(reader: &mut FrameReader<R>) -> io::Result<Timeline> {
+    let mut builder = TimelineBuilder::new();
+    while let Some(frame) = reader.read_frame().await? {
+        builder.push_frame(&frame);
+    }
+    Ok(builder.finish())
+}
+
+/// Read a whole frame stream and extract its timeline along with the
+/// timestamp of each event
+pub async fn extract_timeline_with_timestamps<R: AsyncRead + Unpin>(
+    reader: &mut FrameReader<R>,
+) -> io::Result<(Timeline, Vec<Option<u64>>)> {
+    let mut builder = TimelineBuilder::new();
+    while let Some(frame) = reader.read_frame().await? {
+        builder.push_frame(&frame);
+    }
+    Ok(builder.finish_with_timestamps())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::{
+        IdlePeriodData, KeyPressedData, MouseClickedData, PageErrorData, RecordingMetadataData, RecordingPausedData,
+        TimestampData,
+    };
+
+    #[test]
+    fn test_timeline_events_and_offsets() {
+        let mut builder = TimelineBuilder::new();
+        let frames = vec![
+            Frame::RecordingMetadata(RecordingMetadataData {
+                initial_url: "https://example.com/".to_string(),
+                heartbeat_interval_seconds: 0,
+            }),
+            Frame::Timestamp(TimestampData { timestamp: 1000 }),
+            Frame::MouseClicked(MouseClickedData { x: 10, y: 20 }),
+            Frame::Timestamp(TimestampData { timestamp: 1500 }),
+            Frame::KeyPressed(KeyPressedData {
+                code: "KeyS".to_string(),
+                alt_key: false,
+                ctrl_key: true,
+                meta_key: false,
+                shift_key: false,
+            }),
+            Frame::PageError(PageErrorData {
+                message: "boom".to_string(),
+                source_url: None,
+                line: None,
+                column: None,
+                stack: None,
+            }),
+        ];
+        for frame in &frames {
+            builder.push_frame(frame);
+        }
+        let timeline = builder.finish();
+
+        assert_eq!(timeline.start_timestamp, Some(1000));
+        assert_eq!(timeline.end_timestamp, Some(1500));
+        assert_eq!(timeline.events.len(), 3);
+        assert_eq!(timeline.events[0].offset_ms, 0);
+        assert_eq!(timeline.events[1].offset_ms, 500);
+
+        let json = serde_json::to_value(&timeline.events[1]).unwrap();
+        assert_eq!(json["kind"], "key_press");
+        assert_eq!(json["modifiers"][0], "ctrl");
+    }
+
+    #[test]
+    fn test_timeline_leaves_out_paused_time() {
+        let mut builder = TimelineBuilder::new();
+        let frames = vec![
+            Frame::Timestamp(TimestampData { timestamp: 1000 }),
+            Frame::Timestamp(TimestampData { timestamp: 2000 }),
+            Frame::RecordingPaused(RecordingPausedData {
+                reason: Some("payment-form".to_string()),
+            }),
+            Frame::Timestamp(TimestampData { timestamp: 60_000 }),
+            Frame::RecordingResumed,
+            Frame::MouseClicked(MouseClickedData { x: 1, y: 2 }),
+            Frame::Timestamp(TimestampData { timestamp: 61_000 }),
+            Frame::RecordingPaused(RecordingPausedData { reason: None }),
+            Frame::Timestamp(TimestampData { timestamp: 65_000 }),
+        ];
+        for frame in &frames {
+            builder.push_frame(frame);
+        }
+        let timeline = builder.finish();
+
+        assert_eq!(timeline.paused_ms, 62_000);
+        assert_eq!(
+            timeline.events,
+            vec![
+                TimelineEvent {
+                    offset_ms: 1000,
+                    kind: TimelineEventKind::Gap {
+                        duration_ms: 58_000,
+                        reason: Some("payment-form".to_string()),
+                    },
+                },
+                TimelineEvent {
+                    offset_ms: 1000,
+                    kind: TimelineEventKind::Click { x: 1, y: 2 },
+                },
+                // Still paused when the recording ended
+                TimelineEvent {
+                    offset_ms: 2000,
+                    kind: TimelineEventKind::Gap {
+                        duration_ms: 4000,
+                        reason: None,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timeline_leaves_out_idle_periods() {
+        let mut builder = TimelineBuilder::new();
+        let frames = vec![
+            Frame::Timestamp(TimestampData { timestamp: 1000 }),
+            Frame::Timestamp(TimestampData { timestamp: 2000 }),
+            Frame::IdlePeriod(IdlePeriodData { duration_ms: 3_600_000 }),
+            Frame::Timestamp(TimestampData { timestamp: 3_602_000 }),
+            Frame::MouseClicked(MouseClickedData { x: 1, y: 2 }),
+        ];
+        for frame in &frames {
+            builder.push_frame(frame);
+        }
+        let timeline = builder.finish();
+
+        assert_eq!(timeline.paused_ms, 3_600_000);
+        assert_eq!(
+            timeline.events,
+            vec![
+                TimelineEvent {
+                    offset_ms: 1000,
+                    kind: TimelineEventKind::Gap {
+                        duration_ms: 3_600_000,
+                        reason: Some("idle".to_string()),
+                    },
+                },
+                TimelineEvent {
+                    offset_ms: 1000,
+                    kind: TimelineEventKind::Click { x: 1, y: 2 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_session_timeline_interleaves_recordings() {
+        let timeline_of = |frames: &[Frame]| {
+            let mut builder = TimelineBuilder::new();
+            for frame in frames {
+                builder.push_frame(frame);
+            }
+            builder.finish_with_timestamps()
+        };
+        let (first, first_timestamps) = timeline_of(&[
+            Frame::Timestamp(TimestampData { timestamp: 1000 }),
+            Frame::MouseClicked(MouseClickedData { x: 1, y: 1 }),
+            Frame::Timestamp(TimestampData { timestamp: 3000 }),
+            Frame::MouseClicked(MouseClickedData { x: 3, y: 3 }),
+        ]);
+        let (second, second_timestamps) = timeline_of(&[
+            Frame::Timestamp(TimestampData { timestamp: 2500 }),
+            Frame::MouseClicked(MouseClickedData { x: 2, y: 2 }),
+        ]);
+
+        let mut session = SessionTimeline::new("session-1");
+        session.add_recording("tab-1.dcrr", first, first_timestamps, |t| t);
+        // This tab's recorder runs 500ms ahead of the server
+        session.add_recording("tab-2.dcrr", second, second_timestamps, |t| t - 500);
+
+        let order: Vec<_> = session
+            .events
+            .iter()
+            .map(|event| (event.recording_id.as_str(), event.timestamp))
+            .collect();
+        assert_eq!(order, [("tab-1.dcrr", 1000), ("tab-2.dcrr", 2000), ("tab-1.dcrr", 3000)]);
+        assert_eq!(session.recordings[1].start_timestamp, Some(2000));
+
+        let json = serde_json::to_value(&session.events[1]).unwrap();
+        assert_eq!(json["kind"], "click");
+        assert_eq!(json["offset_ms"], 0);
+    }
+}
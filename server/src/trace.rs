@@ -0,0 +1,174 @@
+//! Distributed trace correlation
+//!
+//! Recorders can send a `TraceContext` frame carrying a W3C `traceparent`, and
+//! HTTP clients can send the `traceparent` header. The server parents its own
+//! ingestion and playback spans on that context and reports them through
+//! [`ObservabilityHooks::span_finished`](crate::ObservabilityHooks::span_finished),
+//! with the recording id as an attribute. The span fields map one-to-one onto
+//! an OTLP span; with the `otlp` feature, `otlp::OtlpHooks` exports
+//! them to a collector.
+
+use std::fmt;
+use std::time::SystemTime;
+
+/// Span attribute holding the recording id
+pub const RECORDING_ID_ATTRIBUTE: &str = "domcorder.recording.id";
+
+/// A parsed W3C `traceparent` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 32 lowercase hex characters
+    pub trace_id: String,
+    /// 16 lowercase hex characters: the span this context was issued from
+    pub parent_id: String,
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Parse a version 00 `traceparent`; returns None for anything malformed
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || version != "00" {
+            return None;
+        }
+        if !is_hex_id(trace_id, 32) || !is_hex_id(parent_id, 16) || flags.len() != 2 {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{}-{}-{:02x}", self.trace_id, self.parent_id, self.flags)
+    }
+}
+
+/// Lowercase hex of the given length, not all zeros (the W3C invalid value)
+fn is_hex_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && id.bytes().any(|b| b != b'0')
+}
+
+fn random_hex_id(bytes: usize) -> String {
+    (0..bytes).map(|_| format!("{:02x}", rand::random::<u8>())).collect()
+}
+
+/// A completed server span, ready to be exported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedSpan {
+    pub name: &'static str,
+    pub trace_id: String,
+    pub span_id: String,
+    /// The span from the propagated context, if there was one
+    pub parent_span_id: Option<String>,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub ok: bool,
+    pub attributes: Vec<(&'static str, String)>,
+}
+
+/// A server span in progress
+#[derive(Debug, Clone)]
+pub struct SpanBuilder {
+    name: &'static str,
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+}
+
+impl SpanBuilder {
+    /// Start a span, in `parent`'s trace if given or a new trace otherwise
+    pub fn start(name: &'static str, parent: Option<&TraceParent>) -> Self {
+        let mut span = Self {
+            name,
+            trace_id: random_hex_id(16),
+            span_id: random_hex_id(8),
+            parent_span_id: None,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+        };
+        if let Some(parent) = parent {
+            span.set_parent(parent);
+        }
+        span
+    }
+
+    /// Move the span into `parent`'s trace, e.g. once a TraceContext frame arrives
+    pub fn set_parent(&mut self, parent: &TraceParent) {
+        self.trace_id = parent.trace_id.clone();
+        self.parent_span_id = Some(parent.parent_id.clone());
+    }
+
+    pub fn has_parent(&self) -> bool {
+        self.parent_span_id.is_some()
+    }
+
+    pub fn attribute(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.attributes.push((key, value.into()));
+        self
+    }
+
+    pub fn finish(self, ok: bool) -> FinishedSpan {
+        FinishedSpan {
+            name: self.name,
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_span_id: self.parent_span_id,
+            start: self.start,
+            end: SystemTime::now(),
+            ok,
+            attributes: self.attributes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_traceparent_round_trips() {
+        let parent = TraceParent::parse(TRACEPARENT).unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.flags, 1);
+        assert_eq!(parent.to_string(), TRACEPARENT);
+
+        for invalid in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_spans_join_the_parent_trace() {
+        let parent = TraceParent::parse(TRACEPARENT).unwrap();
+        let span = SpanBuilder::start("test", Some(&parent))
+            .attribute(RECORDING_ID_ATTRIBUTE, "a.dcrr")
+            .finish(true);
+        assert_eq!(span.trace_id, parent.trace_id);
+        assert_eq!(span.parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_eq!(span.span_id.len(), 16);
+        assert_eq!(span.attributes, vec![(RECORDING_ID_ATTRIBUTE, "a.dcrr".to_string())]);
+
+        let root = SpanBuilder::start("test", None).finish(true);
+        assert_eq!(root.trace_id.len(), 32);
+        assert_eq!(root.parent_span_id, None);
+    }
+}
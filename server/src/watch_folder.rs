@@ -0,0 +1,151 @@
+//! Watch-folder ingestion
+//!
+//! Offline recorders and batch pipelines can drop complete `.dcrr` files
+//! (header included) into a directory instead of uploading them. The server
+//! scans it every few seconds and imports each file as `POST
+//! /recordings/import` does, under the file's own name. Imported files are
+//! removed from the folder. Files that can't be imported (a corrupt header
+//! or frame, a name that's taken, a refused origin) are moved to the rejects
+//! folder, next to a `.error.txt` file giving the reason. Failures that may
+//! pass, such as a full disk, leave the file where it is for the next scan.
+//!
+//! A file is only picked up once it hasn't changed for a while (by default
+//! [`DEFAULT_SETTLE`]), so one still being copied in isn't imported
+//! half-written. Writers that can should copy under a hidden name (starting
+//! with `.`) and rename when done; hidden files and files without the
+//! `.dcrr` extension are ignored. Only one server should watch a folder.
+
+use crate::{AppState, RecordingId, StorageError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// How often the folder is scanned, by default
+pub const DEFAULT_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a file must go unmodified before it is imported, by default
+pub const DEFAULT_SETTLE: Duration = Duration::from_secs(2);
+
+/// The folder to watch and where rejected files go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchFolderConfig {
+    pub dir: PathBuf,
+    /// Rejected files go here, by default `rejects` inside `dir`
+    pub rejects_dir: PathBuf,
+    pub scan_interval: Duration,
+    /// How long a file must go unmodified before it is imported
+    pub settle: Duration,
+}
+
+impl WatchFolderConfig {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            rejects_dir: dir.join("rejects"),
+            dir,
+            scan_interval: DEFAULT_SCAN_INTERVAL,
+            settle: DEFAULT_SETTLE,
+        }
+    }
+}
+
+/// What one scan did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchScan {
+    /// Ids of the recordings imported
+    pub imported: Vec<String>,
+    /// Names of the files moved to the rejects folder
+    pub rejected: Vec<String>,
+}
+
+/// Scan the folder until the process exits; failures are logged and the
+/// scan is retried after the interval
+pub async fn run_watch_folder(state: AppState, config: WatchFolderConfig) {
+    info!("📂 Watching {} for recordings", config.dir.display());
+    loop {
+        if let Err(e) = scan_watch_folder(&state, &config).await {
+            warn!("⚠️ Failed to scan watch folder {}: {}", config.dir.display(), e);
+        }
+        tokio::time::sleep(config.scan_interval).await;
+    }
+}
+
+/// Import the settled recordings in the folder once
+pub async fn scan_watch_folder(state: &AppState, config: &WatchFolderConfig) -> io::Result<WatchScan> {
+    let mut scan = WatchScan::default();
+    for path in settled_recordings(&config.dir, config.settle).await? {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match import_file(state, &path, &name).await {
+            Ok(id) => {
+                info!("📥 Imported {} from the watch folder", id);
+                tokio::fs::remove_file(&path).await?;
+                scan.imported.push(id.to_string());
+            }
+            Err(e) if is_rejection(&e) => {
+                warn!("⚠️ Rejected {} from the watch folder: {}", name, e);
+                reject(config, &path, &name, &e).await?;
+                scan.rejected.push(name);
+            }
+            Err(e) => warn!("⚠️ Failed to import {} from the watch folder, will retry: {}", name, e),
+        }
+    }
+    Ok(scan)
+}
+
+/// The `.dcrr` files in `dir` that haven't changed for `settle`, by name
+async fn settled_recordings(dir: &Path, settle: Duration) -> io::Result<Vec<PathBuf>> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || path.extension().is_none_or(|extension| extension != "dcrr") {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        if metadata.is_file() && age >= settle {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+async fn import_file(state: &AppState, path: &Path, name: &str) -> Result<RecordingId, StorageError> {
+    let id = RecordingId::new(name)?;
+    if state.recording_exists(&id).await {
+        let exists = io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", id));
+        return Err(StorageError::Io(exists));
+    }
+    let file = tokio::fs::File::open(path).await?;
+    state
+        .save_recording_stream_with_site_and_path(file, None, None, None, Some(name.to_string()))
+        .await
+}
+
+/// Whether the file itself is at fault, so importing it again would fail too
+fn is_rejection(e: &StorageError) -> bool {
+    match e {
+        StorageError::Header(_)
+        | StorageError::Frame(_)
+        | StorageError::InvalidId(_)
+        | StorageError::InUse(_)
+        | StorageError::Refused { .. }
+        | StorageError::NotSampled { .. } => true,
+        StorageError::Io(e) => e.kind() == io::ErrorKind::AlreadyExists,
+        _ => false,
+    }
+}
+
+/// Move a file to the rejects folder, with the reason next to it
+async fn reject(config: &WatchFolderConfig, path: &Path, name: &str, e: &StorageError) -> io::Result<()> {
+    tokio::fs::create_dir_all(&config.rejects_dir).await?;
+    let reason = format!("{}\n", e);
+    tokio::fs::write(config.rejects_dir.join(format!("{}.error.txt", name)), reason).await?;
+    tokio::fs::rename(path, config.rejects_dir.join(name)).await
+}
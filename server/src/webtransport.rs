@@ -0,0 +1,184 @@
+//! WebTransport listener for recorders (`webtransport` feature)
+//!
+//! A recorder opens a WebTransport session at [`RECORD_PATH`] and one
+//! bidirectional stream on it, then speaks the protocol of `/ws/record`:
+//! frame bytes up, and the CacheManifest, heartbeat pings and keyframe
+//! requests down as frames. The stream has no message boundaries, which the
+//! protocol never relied on. The batch size is negotiated as on the WebSocket,
+//! with the `batch_bytes` query parameter and the `x-domcorder-batch-bytes`
+//! response header, and an API key is presented the same ways. A refusal,
+//! sent as a text message over a WebSocket, closes the session with
+//! [`REFUSED_CODE`] and the reason.
+//!
+//! The server binary listens when `DOMCORDER_WEBTRANSPORT_LISTEN` is set.
+
+use crate::auth::{ApiKey, presented_key};
+use crate::recording_handler::{
+    BATCH_BYTES_HEADER, BATCH_BYTES_PARAM, RecordingConfig, RecordingHooks, RecordingTransport,
+    handle_recording_stream, negotiate_batch_bytes,
+};
+use crate::AppState;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use url::form_urlencoded;
+use wtransport::endpoint::IncomingSession;
+use wtransport::endpoint::endpoint_side::Server;
+use wtransport::error::StreamReadError;
+use wtransport::{Connection, Endpoint, Identity, RecvStream, SendStream, ServerConfig, VarInt};
+
+/// Path of the recording session
+pub const RECORD_PATH: &str = "/wt/record";
+
+/// Application error code a session is closed with when its recording is refused
+pub const REFUSED_CODE: u32 = 1;
+
+/// Most bytes handed over per read of the stream
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// How long a finished session waits for the recorder to read the last frames and close it
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keeps idle sessions (e.g. a paused recording) from timing out
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Listen for WebTransport sessions on `addr` (UDP), presenting `identity`
+pub fn bind_webtransport(addr: SocketAddr, identity: Identity) -> io::Result<Endpoint<Server>> {
+    let config = ServerConfig::builder()
+        .with_bind_address(addr)
+        .with_identity(identity)
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        .build();
+    Endpoint::server(config)
+}
+
+/// Record every session opened on `endpoint`, requiring `api_key` if given
+pub async fn serve_webtransport(endpoint: Endpoint<Server>, state: AppState, api_key: Option<ApiKey>) {
+    loop {
+        let incoming = endpoint.accept().await;
+        tokio::spawn(handle_session(incoming, state.clone(), api_key.clone()));
+    }
+}
+
+async fn handle_session(incoming: IncomingSession, state: AppState, api_key: Option<ApiKey>) {
+    let request = match incoming.await {
+        Ok(request) => request,
+        Err(e) => {
+            debug!("WebTransport handshake failed: {}", e);
+            return;
+        }
+    };
+    let (path, query) = match request.path().split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.path(), None),
+    };
+    if path != RECORD_PATH {
+        request.not_found().await;
+        return;
+    }
+    if let Some(key) = &api_key {
+        // Header names arrive lowercased, as HeaderMap expects
+        let headers: HeaderMap = request
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((HeaderName::try_from(name).ok()?, HeaderValue::try_from(value).ok()?)))
+            .collect();
+        if !presented_key(&headers, query).is_some_and(|candidate| key.matches(&candidate)) {
+            warn!("🔒 Rejected WebTransport session without a valid API key");
+            request.forbidden().await;
+            return;
+        }
+    }
+
+    let requested = query.and_then(|query| {
+        form_urlencoded::parse(query.as_bytes()).find_map(|(name, value)| (name == BATCH_BYTES_PARAM).then(|| value.parse().ok()))?
+    });
+    let batch_bytes = negotiate_batch_bytes(requested);
+    let user_agent = request.user_agent().map(str::to_string);
+    let client_ip = Some(request.remote_address().ip());
+    let connection = match request.accept_with_headers([(BATCH_BYTES_HEADER, batch_bytes.to_string())]).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            debug!("WebTransport session failed: {}", e);
+            return;
+        }
+    };
+    let (send, recv) = match connection.accept_bi().await {
+        Ok(stream) => stream,
+        Err(e) => {
+            debug!("WebTransport session closed before opening a stream: {}", e);
+            return;
+        }
+    };
+    info!("🔌 WebTransport session established for recording");
+
+    let transport = WebTransportStream { connection, send, recv };
+    let config = RecordingConfig {
+        max_size: 100 * 1024 * 1024, // 100MB
+        subdir: None,
+        custom_filename: None,
+        batch_bytes,
+        client_ip,
+    };
+    let hooks = RecordingHooks {
+        on_start: None,
+        on_metadata: None,
+        on_complete: None,
+        on_error: None,
+    };
+    handle_recording_stream(transport, state, user_agent, config, hooks).await;
+}
+
+/// [`RecordingTransport`] over the bidirectional stream of a WebTransport session
+pub struct WebTransportStream {
+    connection: Connection,
+    send: SendStream,
+    recv: RecvStream,
+}
+
+impl WebTransportStream {
+    pub fn new(connection: Connection, send: SendStream, recv: RecvStream) -> Self {
+        Self { connection, send, recv }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecordingTransport for WebTransportStream {
+    async fn recv(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let mut buffer = vec![0; READ_CHUNK_BYTES];
+        match self.recv.read(&mut buffer).await {
+            Ok(Some(read)) => {
+                buffer.truncate(read);
+                Some(Ok(buffer))
+            }
+            Ok(None) => {
+                info!("🔌 WebTransport stream finished by the recorder");
+                None
+            }
+            Err(StreamReadError::NotConnected | StreamReadError::Reset(_)) => {
+                debug!("🔌 WebTransport session closed by the recorder");
+                None
+            }
+            Err(e) => Some(Err(io::Error::other(e))),
+        }
+    }
+
+    async fn send_binary(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.send.write_all(&data).await.map_err(io::Error::other)
+    }
+
+    async fn send_text(&mut self, text: String) -> io::Result<()> {
+        self.connection.close(VarInt::from_u32(REFUSED_CODE), text.as_bytes());
+        Ok(())
+    }
+
+    async fn close(&mut self) {
+        // Closing the session at once could drop frames the recorder hasn't read yet
+        if self.send.finish().await.is_ok() {
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, self.connection.closed()).await;
+        }
+        self.connection.close(VarInt::from_u32(0), b"");
+    }
+}
@@ -0,0 +1,277 @@
+//! Load and end-to-end testing for DomCorder ingestion
+//!
+//! The simulator opens many `/ws/record` sessions at once, each streaming
+//! either a replay of an existing recording or a synthesized browsing session
+//! (see [`synthetic`]), and reports how they fared. Sessions are paced by the
+//! recording's Timestamp frames, scaled by the configured speed, so a replay
+//! at 1x arrives at the server the way the original browser sent it.
+
+pub mod synthetic;
+
+use domcorder_client::{ReconnectPolicy, Recorder, RecorderConfig, RecorderError, RecorderStats};
+use domcorder_proto::writer::DCRR_MAGIC;
+use domcorder_proto::{Frame, FrameReader};
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Most session errors kept in a [`SimulationReport`]
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// What each simulated session sends
+#[derive(Debug, Clone)]
+pub enum Workload {
+    /// The same recording in every session
+    Replay { initial_url: String, frames: Arc<Vec<Frame>> },
+    /// A different synthesized session each time, with this many interactions
+    Synthetic { interactions: usize },
+}
+
+impl Workload {
+    /// Replay a .dcrr file (with or without a header)
+    ///
+    /// Its RecordingMetadata frame supplies the initial URL; the recorder
+    /// sends that frame itself during the handshake.
+    pub async fn from_file(path: &Path) -> std::io::Result<Self> {
+        let data = tokio::fs::read(path).await?;
+        let has_header = data.starts_with(&DCRR_MAGIC);
+        let mut reader = FrameReader::new(Cursor::new(data), has_header);
+        let mut initial_url = None;
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.read_frame().await? {
+            match frame {
+                Frame::RecordingMetadata(metadata) => {
+                    initial_url.get_or_insert(metadata.initial_url);
+                }
+                frame => frames.push(frame),
+            }
+        }
+        Ok(Workload::Replay {
+            initial_url: initial_url.unwrap_or_else(|| synthetic::INITIAL_URL.to_string()),
+            frames: Arc::new(frames),
+        })
+    }
+
+    /// Initial URL and frames for session number `index`
+    fn session(&self, index: usize) -> (String, Vec<Frame>) {
+        match self {
+            Workload::Replay { initial_url, frames } => (initial_url.clone(), frames.as_ref().clone()),
+            Workload::Synthetic { interactions } => {
+                let start = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                let frames = synthetic::session(index as u64, *interactions, start);
+                (synthetic::INITIAL_URL.to_string(), frames)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// WebSocket endpoint, e.g. `ws://127.0.0.1:8723/ws/record`
+    pub url: String,
+    pub api_key: Option<String>,
+    pub workload: Workload,
+    /// Total sessions to run
+    pub sessions: usize,
+    /// Sessions streaming at the same time
+    pub concurrency: usize,
+    /// Playback speed multiplier; None sends frames as fast as possible
+    pub speed: Option<f64>,
+}
+
+impl SimulatorConfig {
+    pub fn new(url: impl Into<String>, workload: Workload) -> Self {
+        Self {
+            url: url.into(),
+            api_key: None,
+            workload,
+            sessions: 1,
+            concurrency: 1,
+            speed: Some(1.0),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_sessions(mut self, sessions: usize) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: Option<f64>) -> Self {
+        self.speed = speed;
+        self
+    }
+}
+
+/// Outcome of a [`run`]
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub sessions_succeeded: usize,
+    pub sessions_failed: usize,
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub assets_deduplicated: u64,
+    /// Wall-clock time of the whole run
+    pub elapsed: Duration,
+    /// Wall-clock time of each successful session, sorted
+    pub session_durations: Vec<Duration>,
+    /// The first few session errors
+    pub errors: Vec<String>,
+}
+
+impl SimulationReport {
+    /// Session duration at percentile `p` (0-100)
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let last = self.session_durations.len().checked_sub(1)?;
+        let index = ((p / 100.0) * last as f64).round() as usize;
+        self.session_durations.get(index.min(last)).copied()
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        writeln!(
+            f,
+            "Sessions:   {} succeeded, {} failed",
+            self.sessions_succeeded, self.sessions_failed
+        )?;
+        writeln!(f, "Elapsed:    {:.2}s", self.elapsed.as_secs_f64())?;
+        writeln!(
+            f,
+            "Sent:       {} frames, {} bytes ({:.0} frames/s, {:.0} bytes/s)",
+            self.frames_sent,
+            self.bytes_sent,
+            self.frames_sent as f64 / seconds,
+            self.bytes_sent as f64 / seconds
+        )?;
+        writeln!(f, "Assets deduplicated: {}", self.assets_deduplicated)?;
+        if let (Some(p50), Some(p95), Some(max)) = (self.percentile(50.0), self.percentile(95.0), self.percentile(100.0))
+        {
+            writeln!(
+                f,
+                "Session time: p50 {:.2}s, p95 {:.2}s, max {:.2}s",
+                p50.as_secs_f64(),
+                p95.as_secs_f64(),
+                max.as_secs_f64()
+            )?;
+        }
+        for error in &self.errors {
+            writeln!(f, "Error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every session and report the totals
+///
+/// A failed session is counted and reported; it does not stop the others.
+pub async fn run(config: SimulatorConfig) -> SimulationReport {
+    let started = Instant::now();
+    let config = Arc::new(config);
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let mut sessions = JoinSet::new();
+    for index in 0..config.sessions {
+        let config = config.clone();
+        let permits = permits.clone();
+        sessions.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("semaphore is never closed");
+            let started = Instant::now();
+            run_session(&config, index).await.map(|stats| (stats, started.elapsed()))
+        });
+    }
+
+    let mut report = SimulationReport::default();
+    while let Some(result) = sessions.join_next().await {
+        match result.map_err(|e| e.to_string()).and_then(|r| r.map_err(|e| e.to_string())) {
+            Ok((stats, duration)) => {
+                report.sessions_succeeded += 1;
+                report.frames_sent += stats.frames_sent;
+                report.bytes_sent += stats.bytes_sent;
+                report.assets_deduplicated += stats.assets_deduplicated;
+                report.session_durations.push(duration);
+            }
+            Err(e) => {
+                report.sessions_failed += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    report.errors.push(e);
+                }
+            }
+        }
+    }
+    report.session_durations.sort();
+    report.elapsed = started.elapsed();
+    report
+}
+
+async fn run_session(config: &SimulatorConfig, index: usize) -> Result<RecorderStats, RecorderError> {
+    let (initial_url, frames) = config.workload.session(index);
+    // A load test should see dropped connections, not retry around them
+    let mut recorder_config = RecorderConfig::new(config.url.clone(), initial_url)
+        .with_heartbeat_interval(0)
+        .with_reconnect(ReconnectPolicy::never());
+    if let Some(api_key) = &config.api_key {
+        recorder_config = recorder_config.with_api_key(api_key.clone());
+    }
+
+    let recorder = Recorder::connect(recorder_config).await?;
+    let mut last_timestamp = None;
+    for frame in frames {
+        if let Frame::Timestamp(ts) = &frame {
+            if let (Some(last), Some(speed)) = (last_timestamp, config.speed) {
+                tokio::time::sleep(pace(last, ts.timestamp, speed)).await;
+            }
+            last_timestamp = Some(ts.timestamp);
+        }
+        recorder.send(frame).await?;
+    }
+    recorder.finish().await
+}
+
+/// How long to wait between Timestamp frames `from` and `to` at `speed`
+fn pace(from: u64, to: u64, speed: f64) -> Duration {
+    if speed <= 0.0 || !speed.is_finite() {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(to.saturating_sub(from) as f64 / 1000.0 / speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pace_scales_timestamp_gaps() {
+        assert_eq!(pace(1_000, 3_000, 1.0), Duration::from_secs(2));
+        assert_eq!(pace(1_000, 3_000, 4.0), Duration::from_millis(500));
+        // Clock steps backwards do not wait
+        assert_eq!(pace(3_000, 1_000, 1.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_report_percentiles() {
+        let report = SimulationReport {
+            session_durations: (1..=10).map(Duration::from_secs).collect(),
+            ..Default::default()
+        };
+        assert_eq!(report.percentile(50.0), Some(Duration::from_secs(6)));
+        assert_eq!(report.percentile(100.0), Some(Duration::from_secs(10)));
+        assert_eq!(SimulationReport::default().percentile(50.0), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! `domcorder-simulator`: stream simulated recordings at a server
+
+use domcorder_simulator::{SimulatorConfig, Workload, run};
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: domcorder-simulator [recording.dcrr] [options]
+
+Open sessions against a server's /ws/record endpoint and report throughput
+and failures. Each session replays the given recording, or a synthesized
+browsing session if none is given.
+
+Options:
+  --url URL             WebSocket endpoint (default ws://127.0.0.1:8723/ws/record)
+  --api-key KEY         API key (or DOMCORDER_API_KEY)
+  --sessions N          Total sessions to run (default 1)
+  --concurrency N       Sessions streaming at once (default 1)
+  --speed X             Playback speed multiplier, or `max` for no pacing (default 1)
+  --interactions N      User actions per synthesized session (default 50)";
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:8723/ws/record";
+const VALUE_OPTIONS: [&str; 6] = ["url", "api-key", "sessions", "concurrency", "speed", "interactions"];
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match simulate(&args).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the simulation; false if any session failed
+async fn simulate(args: &[String]) -> Result<bool, String> {
+    let mut recording = None;
+    let mut options = std::collections::HashMap::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(option) = arg.strip_prefix("--") else {
+            if recording.replace(arg.as_str()).is_some() {
+                return Err("only one recording can be replayed".to_string());
+            }
+            continue;
+        };
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, value.to_string()),
+            None => (option, iter.next().ok_or_else(|| format!("--{} requires a value", option))?.clone()),
+        };
+        if !VALUE_OPTIONS.contains(&name) {
+            return Err(format!("unknown option --{}", name));
+        }
+        options.insert(name, value);
+    }
+    let number = |name: &str, default: usize| -> Result<usize, String> {
+        options.get(name).map_or(Ok(default), |value| {
+            value.parse().map_err(|_| format!("invalid value for --{}: {}", name, value))
+        })
+    };
+
+    let workload = match recording {
+        Some(path) => Workload::from_file(Path::new(path))
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?,
+        None => Workload::Synthetic {
+            interactions: number("interactions", 50)?,
+        },
+    };
+    let speed = match options.get("speed").map(String::as_str) {
+        None => Some(1.0),
+        Some("max") => None,
+        Some(value) => Some(
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|speed| *speed > 0.0)
+                .ok_or_else(|| format!("invalid value for --speed: {}", value))?,
+        ),
+    };
+    let url = options.get("url").map_or(DEFAULT_URL, String::as_str);
+    let mut config = SimulatorConfig::new(url, workload)
+        .with_sessions(number("sessions", 1)?)
+        .with_concurrency(number("concurrency", 1)?)
+        .with_speed(speed);
+    if let Some(api_key) = options.get("api-key").cloned().or_else(|| env::var("DOMCORDER_API_KEY").ok()) {
+        config = config.with_api_key(api_key);
+    }
+
+    let report = run(config).await;
+    print!("{}", report);
+    Ok(report.sessions_failed == 0)
+}
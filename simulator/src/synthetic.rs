@@ -0,0 +1,119 @@
+//! Synthesized browsing sessions
+//!
+//! Each session is a small shop page followed by a run of interactions: mouse
+//! movement and clicks, typing into a form, scrolling, and DOM mutations. The
+//! session number seeds the choices, so session `n` is the same every run while
+//! concurrent sessions still differ from each other.
+
+use domcorder_proto::{Frame, FrameBuilder};
+
+/// Page URL reported by synthesized sessions; they all share one site so the
+/// server's asset cache sees repeat visitors
+pub const INITIAL_URL: &str = "https://simulator.domcorder.test/shop";
+
+const VIEWPORT: (u32, u32) = (1280, 800);
+
+/// A tiny PNG-like asset, sent once per session
+const LOGO: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D];
+
+/// Deterministic xorshift generator; quality is irrelevant here
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift never leaves zero
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `low..high`
+    fn range(&mut self, low: u32, high: u32) -> u32 {
+        low + (self.next() % (high - low) as u64) as u32
+    }
+}
+
+/// Frames for synthesized session `seed`, starting at `start` (ms since the
+/// epoch), with `interactions` user actions after the first Keyframe
+pub fn session(seed: u64, interactions: usize, start: u64) -> Vec<Frame> {
+    let mut rng = Rng::new(seed);
+    let mut builder = FrameBuilder::new()
+        .viewport(VIEWPORT.0, VIEWPORT.1)
+        .at(start)
+        .new_page()
+        .title("Simulated shop")
+        .with_element("header#top", |header| {
+            header
+                .with_element("img.logo", |img| img.attr("src", "/logo.png"))
+                .with_element("nav", |nav| {
+                    nav.with_element("a", |a| a.attr("href", "/").text("Home"))
+                        .with_element("a", |a| a.attr("href", "/cart").text("Cart"))
+                })
+        })
+        .with_element("main#content", |main| {
+            main.with_element("ul#items", |ul| {
+                (1..=5).fold(ul, |ul, n| {
+                    ul.with_element("li.item", |li| li.text(&format!("Item {}", n)))
+                })
+            })
+            .with_element("form#checkout", |form| {
+                form.with_element("input#email", |input| input.attr("type", "email"))
+                    .with_element("button#buy", |button| button.text("Buy"))
+            })
+        });
+    let items = builder.node_id("items");
+    let email = builder.node_id("email");
+    let buy = builder.node_id("buy");
+
+    builder = builder.asset(1, "https://simulator.domcorder.test/logo.png", "image/png", LOGO);
+    let mut added = 5;
+    let mut scroll = 0;
+    for _ in 0..interactions {
+        builder = builder.wait(rng.range(50, 1500) as u64);
+        builder = match rng.range(0, 5) {
+            0 => {
+                let (x, y) = (rng.range(0, VIEWPORT.0), rng.range(0, VIEWPORT.1));
+                builder.mouse_path(&[(x, y)], 16).click(x, y)
+            }
+            1 => {
+                let text = format!("user{}@example.com", rng.range(0, 10_000));
+                builder.focus(email).type_text(&text).blur(email)
+            }
+            2 => {
+                scroll = (scroll + rng.range(0, 600)) % 2000;
+                builder.scroll(0, scroll)
+            }
+            3 => {
+                added += 1;
+                builder.add_node(items, added - 1, "li.item.new", |li| {
+                    li.text(&format!("Item {}", added))
+                })
+            }
+            _ => builder.set_attribute(buy, "class", if rng.range(0, 2) == 0 { "busy" } else { "ready" }),
+        };
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domcorder_proto::VDomEngine;
+
+    #[test]
+    fn test_session_is_deterministic_and_replays_cleanly() {
+        let frames = session(7, 100, 1_700_000_000_000);
+        assert_eq!(frames, session(7, 100, 1_700_000_000_000));
+        assert_ne!(frames, session(8, 100, 1_700_000_000_000));
+
+        let mut engine = VDomEngine::new();
+        for frame in &frames {
+            engine.apply(frame).unwrap();
+        }
+    }
+}
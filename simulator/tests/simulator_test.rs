@@ -0,0 +1,78 @@
+use domcorder_server::asset_cache::local::LocalBinaryStore;
+use domcorder_server::asset_cache::sqlite::SqliteMetadataStore;
+use domcorder_server::{AppState, DomcorderRouter, RouteGroup, StorageState};
+use domcorder_simulator::{SimulatorConfig, Workload, run};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const SAMPLE: &str = "../.sample_data/proto/file-basic.dcrr";
+
+fn create_state() -> (AppState, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let metadata_store = SqliteMetadataStore::new(temp_dir.path().join("asset_cache.db")).unwrap();
+    let asset_store =
+        LocalBinaryStore::new(temp_dir.path().join("assets"), "http://localhost".to_string()).unwrap();
+    let state = StorageState::new(
+        temp_dir.path().to_path_buf(),
+        Box::new(metadata_store),
+        Box::new(asset_store),
+    )
+    .unwrap();
+    (Arc::new(state), temp_dir)
+}
+
+async fn serve(state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let app = DomcorderRouter::new(state).routes(RouteGroup::ALL);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("ws://{}/ws/record", addr)
+}
+
+#[tokio::test]
+async fn test_synthetic_sessions_are_recorded() {
+    let (state, _temp_dir) = create_state();
+    let url = serve(state.clone()).await;
+
+    let config = SimulatorConfig::new(url, Workload::Synthetic { interactions: 20 })
+        .with_sessions(4)
+        .with_concurrency(2)
+        .with_speed(None);
+    let report = run(config).await;
+
+    assert_eq!(report.sessions_succeeded, 4, "{}", report);
+    assert_eq!(report.sessions_failed, 0);
+    assert_eq!(report.session_durations.len(), 4);
+    // Later sessions of the same site find the logo in the cache manifest
+    assert!(report.assets_deduplicated >= 1);
+    assert_eq!(state.list_recordings(None).await.unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_replay_sends_every_frame() {
+    let (state, _temp_dir) = create_state();
+    let url = serve(state.clone()).await;
+
+    let workload = Workload::from_file(Path::new(SAMPLE)).await.unwrap();
+    let Workload::Replay { frames, .. } = &workload else {
+        panic!("expected a replay workload");
+    };
+    let frame_count = frames.len() as u64;
+
+    let report = run(SimulatorConfig::new(url, workload).with_sessions(2).with_speed(None)).await;
+    assert_eq!(report.sessions_succeeded, 2, "{}", report);
+    assert_eq!(report.frames_sent, frame_count * 2);
+    assert_eq!(state.list_recordings(None).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_unreachable_server_is_reported() {
+    let config = SimulatorConfig::new("ws://127.0.0.1:1/ws/record", Workload::Synthetic { interactions: 1 })
+        .with_sessions(3)
+        .with_concurrency(3);
+    let report = run(config).await;
+    assert_eq!(report.sessions_failed, 3);
+    assert_eq!(report.errors.len(), 3);
+}